@@ -0,0 +1,323 @@
+use crate::*;
+
+/** The typed ids of the records, which the API types use instead of plain numbers so that ids of different tables cannot be mixed up.
+
+    The ids convert from and to `u64` and with the `serde` feature they are serialized as plain numbers.
+ */
+pub use crate::records::{ProjectId, CommitId, HashId, PathId, UserId};
+
+/** Datastore versions whose records can be converted to the API types.
+ */
+pub const SUPPORTED_VERSIONS : [u16; 3] = [0, 1, 2];
+
+/** A project with its latest heads.
+ */
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Project {
+    pub id : ProjectId,
+    /** Clone url of the project.
+     */
+    pub url : String,
+    /** Name of the substore the project's commits are stored in. Empty if the project has not yet been assigned to a substore.
+     */
+    pub substore : String,
+    /** Datastore version of the project's latest update.
+     */
+    pub version : u16,
+    /** Time of the latest successful update, if any.
+     */
+    pub updated : Option<i64>,
+    /** Heads of the project as name, commit id and commit hash.
+     */
+    pub heads : Vec<(String, CommitId, String)>,
+}
+
+/** A commit with its changes.
+ */
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Commit {
+    pub id : CommitId,
+    pub hash : String,
+    pub substore : String,
+    /** Emails of the author and committer.
+     */
+    pub author : String,
+    pub author_time : i64,
+    pub committer : String,
+    pub committer_time : i64,
+    pub parents : Vec<CommitId>,
+    pub message : String,
+    pub changes : Vec<Change>,
+    /** Submodules changed by the commit, which are not included in its changes. 
+     */
+    pub submodules : Vec<Submodule>,
+}
+
+/** Change of a single file in a commit.
+ */
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Change {
+    pub path : String,
+    /** Id of the new contents of the file, None if the file was deleted.
+     */
+    pub contents : Option<HashId>,
+    /** Numbers of added and deleted lines, if line stats were stored for the commit. 
+     */
+    pub lines : Option<(u32, u32)>,
+    /** The path the file was renamed from, if the change is a rename. 
+     */
+    pub renamed_from : Option<String>,
+}
+
+/** Change of a submodule in a commit.
+ */
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Submodule {
+    pub path : String,
+    /** Hash of the commit the submodule points to, None if the submodule was removed.
+     */
+    pub commit : Option<String>,
+    /** Url of the submodule's repository from the `.gitmodules` file, empty if not known.
+     */
+    pub url : String,
+}
+
+/** Contents of a file.
+ */
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileContents {
+    pub id : HashId,
+    /** Kind of the contents (usually the language), as determined from the file's extension.
+     */
+    pub kind : String,
+    pub bytes : Vec<u8>,
+}
+
+/** A user, i.e. an author, or committer of commits.
+ */
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct User {
+    pub id : UserId,
+    pub substore : String,
+    /** Email of the user, which is a keyed hash of the email if the datastore's emails are anonymized.
+     */
+    pub email : String,
+}
+
+/** Read-only access to a datastore via the stable API types.
+ */
+pub struct Dataset {
+    view : DatastoreView,
+}
+
+impl Dataset {
+
+    /** Opens the datastore at given root.
+
+        Fails if the datastore contains projects updated by a datastore version that is not supported.
+     */
+    pub fn open(root : & str) -> Result<Dataset, std::io::Error> {
+        return Dataset::check(DatastoreView::from(root));
+    }
+
+    /** Returns the same datastore as of the savepoint of given name.
+     */
+    pub fn at_savepoint(& self, name : & str) -> Result<Dataset, std::io::Error> {
+        match self.view.at_savepoint(name) {
+            Some(view) => return Dataset::check(view),
+            None => return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("Savepoint {} not found", name))),
+        }
+    }
+
+    fn check(view : DatastoreView) -> Result<Dataset, std::io::Error> {
+        for (id, log) in view.project_updates() {
+            if ! SUPPORTED_VERSIONS.contains(& log.version()) {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Project {} was updated by unsupported datastore version {}", id, log.version())));
+            }
+        }
+        return Ok(Dataset{ view });
+    }
+
+    /** Returns the underlying view for access to the internal tables.
+
+        Unlike the rest of this module, the view is not stable across datastore versions.
+     */
+    pub fn view(& self) -> & DatastoreView {
+        return & self.view;
+    }
+
+    /** Returns all projects in the datastore ordered by their ids.
+     */
+    pub fn projects(& self) -> Vec<Project> {
+        let mut result : Vec<Project> = crate::Project::assemble(& self.view).into_iter()
+            .map(|(id, p)| Project::from_records(id, & p))
+            .collect();
+        result.sort_by_key(|p| u64::from(p.id));
+        return result;
+    }
+
+    /** Returns the project with given id, if it exists.
+
+        Only the records of the project are read, so this is much faster than finding the project in all projects.
+     */
+    pub fn project(& self, id : ProjectId) -> Option<Project> {
+        if ! self.view.includes_project(id) {
+            return None;
+        }
+        let mut p = crate::Project::new(self.view.project_urls().get(id)?, self.view.project_substore_of(id).unwrap_or(StoreKind::Unspecified));
+        if let Some(status) = self.view.project_latest_update(id) {
+            p.latest_status = status;
+        }
+        if let Some(heads) = self.view.project_heads_of(id) {
+            p.heads = heads;
+        }
+        return Some(Project::from_records(id, & p));
+    }
+
+    /** Iterates over all commits reachable from the heads of given project.
+     */
+    pub fn project_commits<'a>(& 'a self, project : & Project) -> impl Iterator<Item = Commit> + 'a {
+        let kind = StoreKind::from_string(& project.substore);
+        let heads : ProjectHeads = project.heads.iter()
+            .filter_map(|(name, id, hash)| SHA::from_str(hash).ok().map(|hash| (name.to_owned(), (*id, hash))))
+            .collect();
+        let reader = CommitReader::new(& self.view, kind.unwrap_or(StoreKind::Unspecified));
+        let commits = match kind {
+            Some(kind) => Some(ProjectCommitsIterator::new(& heads, self.view.commits_info(kind))),
+            None => None,
+        };
+        return commits.into_iter().flatten().map(move |(id, info)| reader.commit(id, info));
+    }
+
+    /** Iterates over all commits of given substore.
+     */
+    pub fn commits<'a>(& 'a self, substore : & str) -> impl Iterator<Item = Commit> + 'a {
+        let kind = StoreKind::from_string(substore);
+        let reader = CommitReader::new(& self.view, kind.unwrap_or(StoreKind::Unspecified));
+        let commits = kind.map(|kind| self.view.commits_info(kind));
+        return commits.into_iter().flatten().map(move |(id, info)| reader.commit(id, info));
+    }
+
+    /** Returns the commit with given id from given substore, if it exists.
+     */
+    pub fn commit(& self, substore : & str, id : CommitId) -> Option<Commit> {
+        let kind = StoreKind::from_string(substore)?;
+        let info = self.view.commits_info(kind).get(id)?;
+        return Some(CommitReader::new(& self.view, kind).commit(id, info));
+    }
+
+    /** Returns the user with given id from given substore, if it exists.
+     */
+    pub fn user(& self, substore : & str, id : UserId) -> Option<User> {
+        let kind = StoreKind::from_string(substore)?;
+        let email = self.view.users(kind).get(id)?;
+        return Some(User{
+            id,
+            substore : format!("{:?}", kind),
+            email,
+        });
+    }
+
+    /** Returns the file contents with given id from given substore, if stored.
+     */
+    pub fn contents(& self, substore : & str, id : HashId) -> Option<FileContents> {
+        let kind = StoreKind::from_string(substore)?;
+        let (contents_kind, bytes) = self.view.contents(kind).get(id)?;
+        return Some(FileContents{
+            id,
+            kind : format!("{:?}", contents_kind),
+            bytes,
+        });
+    }
+}
+
+impl Project {
+    /** Converts the assembled project records.
+
+        Version 0 records map directly to the API types. Records of future versions whose layout differs are converted by their own arms, dispatched on the version of the project's latest update.
+     */
+    fn from_records(id : ProjectId, p : & crate::Project) -> Project {
+        return Project{
+            id,
+            url : p.url.clone_url(),
+            substore : if p.substore == StoreKind::Unspecified { String::new() } else { format!("{:?}", p.substore) },
+            version : p.latest_status.version(),
+            updated : p.latest_valid_update_time(),
+            heads : p.heads.iter().map(|(name, (id, hash))| (name.to_owned(), *id, hash.to_string())).collect(),
+        };
+    }
+}
+
+/** Readers of the substore tables needed to convert commit records.
+ */
+struct CommitReader {
+    substore : String,
+    hashes : Option<table_readers::MappingReader<SHA, CommitId>>,
+    users : Option<table_readers::IndirectMappingReader<String, UserId>>,
+    paths : Option<table_readers::StoreReader<PathString, PathId>>,
+    line_stats : Option<table_readers::StoreReader<LineStats, CommitId>>,
+    renames : Option<table_readers::StoreReader<PathRenames, CommitId>>,
+    submodules : Option<table_readers::StoreReader<CommitSubmodules, CommitId>>,
+}
+
+impl CommitReader {
+    fn new(view : & DatastoreView, kind : StoreKind) -> CommitReader {
+        if kind == StoreKind::Unspecified {
+            return CommitReader{ substore : String::new(), hashes : None, users : None, paths : None, line_stats : None, renames : None, submodules : None };
+        }
+        return CommitReader{
+            substore : format!("{:?}", kind),
+            hashes : Some(view.commits(kind)),
+            users : Some(view.users(kind)),
+            paths : Some(view.paths_strings(kind)),
+            line_stats : Some(view.commits_line_stats(kind)),
+            renames : Some(view.path_renames(kind)),
+            submodules : view.commits_submodules_if_recorded(kind),
+        };
+    }
+
+    fn commit(& self, id : CommitId, info : CommitInfo) -> Commit {
+        let line_stats = self.line_stats.as_ref().and_then(|x| x.get(id)).unwrap_or_default();
+        let renamed_from : HashMap<PathId, PathId> = self.renames.as_ref().and_then(|x| x.get(id)).unwrap_or_default().into_iter().map(|(old, new)| (new, old)).collect();
+        let mut changes : Vec<Change> = info.changes.iter().map(|(path, contents)| Change{
+            path : self.path(*path).unwrap_or_default(),
+            contents : if *contents == HashId::DELETED { None } else { Some(*contents) },
+            lines : line_stats.get(path).cloned(),
+            renamed_from : renamed_from.get(path).and_then(|old| self.path(*old)),
+        }).collect();
+        changes.sort_by(|a, b| a.path.cmp(& b.path));
+        let mut submodules : Vec<Submodule> = self.submodules.as_ref().and_then(|x| x.get(id)).unwrap_or_default().into_iter().map(|x| Submodule{
+            path : self.path(x.path).unwrap_or_default(),
+            commit : if x.commit.is_zero() { None } else { Some(x.commit.to_string()) },
+            url : x.url,
+        }).collect();
+        submodules.sort_by(|a, b| a.path.cmp(& b.path));
+        return Commit{
+            id,
+            hash : self.hashes.as_ref().and_then(|x| x.get(id)).map(|x| x.to_string()).unwrap_or_default(),
+            substore : self.substore.clone(),
+            author : self.user(info.author),
+            author_time : info.author_time,
+            committer : self.user(info.committer),
+            committer_time : info.committer_time,
+            parents : info.parents,
+            message : info.message,
+            changes,
+            submodules,
+        };
+    }
+
+    fn path(& self, id : PathId) -> Option<String> {
+        return self.paths.as_ref().and_then(|x| x.get(id));
+    }
+
+    fn user(& self, id : UserId) -> String {
+        return self.users.as_ref().and_then(|x| x.get(id)).unwrap_or_default();
+    }
+}
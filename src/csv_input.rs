@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::io::Read;
+
+/** Options for reading CSV inputs, such as project lists.
+
+    By default the inputs are parsed according to RFC 4180, i.e. the fields are separated by commas and may be enclosed in double quotes, in which case they may contain commas, newlines and doubled quotes. Rows may have different number of fields.
+ */
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    pub delimiter : u8,
+    pub quote : u8,
+    /** If set, quotes in quoted fields are escaped by the escape character instead of being doubled.
+     */
+    pub escape : Option<u8>,
+    pub has_headers : bool,
+}
+
+impl CsvOptions {
+
+    pub fn new() -> CsvOptions {
+        return CsvOptions{
+            delimiter : b',',
+            quote : b'"',
+            escape : None,
+            has_headers : true,
+        };
+    }
+
+    /** Parses the character given to the delimiter, quote and escape options.
+
+        The character must be a single byte, `tab` or `\t` can be used for the tab character.
+     */
+    pub fn parse_char(value : & str) -> Option<u8> {
+        match value {
+            "tab" | "\\t" => return Some(b'\t'),
+            _ if value.len() == 1 => return Some(value.as_bytes()[0]),
+            _ => return None,
+        }
+    }
+
+    /** Creates reader of given input with the options.
+     */
+    pub fn reader<R : Read>(& self, input : R) -> csv::Reader<R> {
+        return csv::ReaderBuilder::new()
+            .has_headers(self.has_headers)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .double_quote(self.escape.is_none())
+            .escape(self.escape)
+            .flexible(true)
+            .from_reader(input);
+    }
+
+    /** Opens the given file with the options.
+     */
+    pub fn from_path(& self, path : & str) -> Result<csv::Reader<File>, std::io::Error> {
+        return Ok(self.reader(File::open(path)?));
+    }
+}
+
+/** Iterates over the records of CSV reader skipping the malformed ones.
+
+    Each malformed record is reported to the callback together with the line it occurs on and the iteration continues with the next record. Only I/O errors terminate the iteration.
+ */
+pub struct TolerantRecords<'a, R : Read, F : FnMut(String)> {
+    records : csv::StringRecordsIter<'a, R>,
+    on_error : F,
+}
+
+impl<'a, R : Read, F : FnMut(String)> TolerantRecords<'a, R, F> {
+    pub fn new(reader : &'a mut csv::Reader<R>, on_error : F) -> TolerantRecords<'a, R, F> {
+        return TolerantRecords{
+            records : reader.records(),
+            on_error,
+        };
+    }
+}
+
+impl<'a, R : Read, F : FnMut(String)> Iterator for TolerantRecords<'a, R, F> {
+    type Item = csv::StringRecord;
+
+    fn next(& mut self) -> Option<csv::StringRecord> {
+        loop {
+            match self.records.next() {
+                Some(Ok(record)) => return Some(record),
+                Some(Err(e)) => {
+                    (self.on_error)(describe_error(& e));
+                    if e.is_io_error() {
+                        return None;
+                    }
+                },
+                None => return None,
+            }
+        }
+    }
+}
+
+/** Returns the description of given CSV error including the line where it occured, if known.
+ */
+pub fn describe_error(e : & csv::Error) -> String {
+    match e.position() {
+        Some(pos) => return format!("line {}: {}", pos.line(), e),
+        None => return format!("{}", e),
+    }
+}
@@ -9,6 +9,7 @@ use crate::records::*;
 use crate::helpers;
 use crate::updater;
 use crate::settings::SETTINGS;
+use crate::substore_classifier::*;
 
 use crate::LOG;
 
@@ -52,14 +53,27 @@ pub struct Datastore {
         - which substore it belongs to (this can change over time)
         - the linked history of its updates with precise timestamps and update results
         - heads of all branches in the project
+        - tags in the project
+        - labels assigned to the project by the users
         - project metadata
+        - summaries of the project commits
 
      */
     pub (crate) projects : Mutex<Store<ProjectUrl, ProjectId>>,
     pub (crate) project_substores : Mutex<Store<StoreKind, ProjectId>>,
     pub (crate) project_updates : Mutex<LinkedStore<ProjectLog, ProjectId>>,
     pub (crate) project_heads : Mutex<Store<ProjectHeads, ProjectId>>,
+    pub (crate) project_tags : Mutex<Store<ProjectTags, ProjectId>>,
+    pub (crate) project_labels : Mutex<Store<ProjectLabels, ProjectId>>,
     pub (crate) project_metadata : Mutex<LinkedStore<Metadata, ProjectId>>,
+    pub (crate) project_summaries : Mutex<LinkedStore<ProjectSummary, ProjectId>>,
+    pub (crate) project_popularity : Mutex<LinkedStore<ProjectPopularity, ProjectId>>,
+    pub (crate) project_default_branches : Mutex<Store<DefaultBranch, ProjectId>>,
+    /** Heads of the projects as last seen by heads only updates. 
+     
+        Heads only updates do not analyze commits, so the commit ids of the heads are `CommitId::NONE` and only their hashes are known. The heads are recorded whenever they differ from the ones last seen, or analyzed. 
+     */
+    pub (crate) project_remote_heads : Mutex<Store<ProjectHeads, ProjectId>>,
 
     /** Current and past urls for known projects so that when new projects are added we can check for ambiguity.
      
@@ -74,6 +88,39 @@ pub struct Datastore {
     pub (crate) substores : Vec<Substore>,
 
     pub (crate) savepoints : Mutex<LinkedStore<Savepoint>>,
+
+    /** Global index of the substores that contain commits and contents of given hash. 
+     
+        The lower 32 bits of the bitmap of a hash are the substores that contain a commit of the hash, the upper 32 bits the substores that contain file contents of the hash. The index is shared with the substores which buffer the new commits and hashes they add and write them to the index in batches (see `Substore::index_sha`). A readonly datastore may not have the index. When a writable datastore is opened and the index is missing or incomplete, it is built from the substores. 
+     */
+    pub (crate) sha_substores : Arc<Mutex<Option<BitmapIndex<SHA>>>>,
+
+    /** Projects that must not be added, or updated, loaded from the denylist file in the datastore root. 
+     */
+    denylist : Mutex<Vec<DenylistEntry>>,
+    /** Modification time of the denylist file when the denylist was last read, or written, so that changes of the file made by other processes can be detected, see `reload_denylist_if_changed`. 
+     */
+    denylist_modified : Mutex<Option<std::time::SystemTime>>,
+
+    /** Update campaigns, loaded from the campaigns file in the datastore root. 
+     */
+    campaigns : Mutex<Vec<Campaign>>,
+
+    /** Unfinished passes of the substore updates, loaded from the update passes file in the datastore root. 
+     */
+    update_passes : Mutex<Vec<UpdatePass>>,
+
+    /** Assigns the updated projects to substores. 
+     
+        Unless replaced, this is the default classifier configured by the datastore's classifier configuration file, if any. 
+     */
+    substore_classifier : Box<dyn SubstoreClassifier>,
+
+    /** The lock of the datastore held by this process, if locking is supported by the datastore's filesystem. 
+     
+        Declared last so that it is released only after all tables are closed. 
+     */
+    lock : Option<DatastoreLock>,
 }
 
 impl Datastore {
@@ -82,15 +129,84 @@ impl Datastore {
     pub (crate) const PROJECT_SUBSTORES : &'static str = "project-substores";
     pub (crate) const PROJECT_UPDATES : &'static str = "project-updates";
     pub (crate) const PROJECT_HEADS : &'static str = "project-heads";
+    pub (crate) const PROJECT_TAGS : &'static str = "project-tags";
+    pub (crate) const PROJECT_LABELS : &'static str = "project-labels";
     pub (crate) const PROJECT_METADATA : &'static str = "project-metadata";
+    pub (crate) const PROJECT_SUMMARIES : &'static str = "project-summaries";
+    pub (crate) const PROJECT_POPULARITY : &'static str = "project-popularity";
+    pub (crate) const PROJECT_DEFAULT_BRANCHES : &'static str = "project-default-branches";
+    pub (crate) const PROJECT_REMOTE_HEADS : &'static str = "project-remote-heads";
     pub (crate) const SAVEPOINTS : &'static str = "savepoints";
+    pub (crate) const SHA_SUBSTORES : &'static str = "sha-substores";
 
 
     /** The version of the datastore. 
      
         Versions have backwards compatibility, but newer versions may add extra items, or metadata. When new version is executed, all projects & commits and other items are force updated to make sure that all data that should be obtained are obtained. 
      */
-    pub const VERSION : u16 = 0;
+    pub const VERSION : u16 = 2;
+
+    /** Name of the file in the datastore root that contains the version of the datastore. 
+     
+        Datastores created before the file was introduced have version 0, new datastores are created with the current version. 
+     */
+    pub (crate) const VERSION_FILE : &'static str = "version";
+
+    /** Name of the append-only file in the datastore root where the migrations of the datastore are logged. 
+     
+        Each line contains the time of the migration, the version migrated from and to and the description of the migration. 
+     */
+    pub (crate) const MIGRATION_LOG : &'static str = "migration-log.csv";
+
+    /** Name of the file in the datastore root whose presence enables per-record checksums. 
+     
+        The file is created when the datastore is opened with the `--checksums` setting. Once enabled, all stores that do not yet have their checksums file create it and records stored from then on get their checksums, while the older records remain unchecked. 
+     */
+    pub (crate) const CHECKSUMS_FILE : &'static str = "checksums";
+
+    /** Name of the file in the datastore root that contains the unique id of the datastore. 
+     
+        The id is generated randomly when the datastore is first opened for writing and never changes afterwards, so that manifests of exports can tell whether they refer to the same datastore even if it has been moved, or copied. 
+     */
+    pub (crate) const ID_FILE : &'static str = "id";
+
+    /** Name of the file in the datastore root that contains the key of the user email hashes. 
+     
+        The file is created with a random key when a new datastore is opened with the `--anonymize-emails` setting, unless the key is kept elsewhere (see `--email-key`). User emails are then stored as keyed hashes of the emails (see `Datastore::anonymize_email`), so that the same email always maps to the same user, but the emails cannot be recovered without the key. 
+     */
+    pub (crate) const EMAIL_KEY_FILE : &'static str = "email-key";
+
+    /** Name of the file in the datastore root whose presence enables the anonymization of the user emails. 
+     
+        Contains the fingerprint of the key (see `Datastore::email_key_fingerprint`), so that the datastore is never opened with a different key and datastores anonymized with different keys are not merged. Datastores anonymized before the file existed keep their key in the datastore root and have no fingerprint file. 
+     */
+    pub (crate) const ANONYMIZED_FILE : &'static str = "anonymized";
+
+    /** Name of the file in the datastore root that contains the denylist. 
+     
+        Each line is a single entry, either `url,PATTERN,REASON`, or `project,ID,REASON`, where the reason is optional. 
+     */
+    pub (crate) const DENYLIST_FILE : &'static str = "denylist";
+
+    /** Name of the file in the datastore root that contains the update campaigns. 
+     
+        Each line is a single campaign, `NAME,TIME,PROJECTS,SAVEPOINT,FILTER`, where time is the time of the savepoint the campaign started at and projects is the number of projects in the datastore when the campaign started. 
+     */
+    pub (crate) const CAMPAIGNS_FILE : &'static str = "campaigns";
+
+    /** Name of the file in the datastore root that contains the unfinished update passes. 
+     
+        Each line is a single pass, `START,SUBSTORE,PASS`, where start is the time the pass started, substore is the substore the pass is updating and pass describes the pass, i.e. its mode and filter. 
+     */
+    pub (crate) const UPDATE_PASSES_FILE : &'static str = "update-passes";
+
+    /** Name of the file in the datastore root that is locked by the processes using the datastore, see `DatastoreLock`. 
+     */
+    pub (crate) const LOCK_FILE : &'static str = "lock";
+
+    /** Name of the file in the datastore root that is locked shared by the views pinned to savepoints, see `DatastoreLock::pin`. 
+     */
+    pub (crate) const PINS_FILE : &'static str = "pins";
 
     pub const SMALL_PROJECT_THRESHOLD : usize = 10;
 
@@ -98,7 +214,7 @@ impl Datastore {
 
     /** Creates the datastore from given root folder. 
      
-        If the path does not exist, initializes an empty datastore. 
+        If the path does not exist, initializes an empty datastore. A datastore opened for writing is locked exclusively and panics if another process uses it. A readonly datastore is locked shared, but is opened even if another process writes to it, in which case the latest changes may be incomplete and a warning is printed. 
      */
     pub fn new(root : & str, readonly : bool) -> Datastore {
         // make sure the paths exist
@@ -106,7 +222,44 @@ impl Datastore {
         if ! root_path.exists() {
             std::fs::create_dir_all(& root_path).unwrap();
         }
+        let lock = match DatastoreLock::acquire(root, ! readonly) {
+            Ok(lock) => Some(lock),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if ! readonly {
+                    panic!("Datastore in {} is used by another process", root);
+                }
+                println!("WARNING: Datastore in {} is being written to by another process, its latest changes may be incomplete", root);
+                None
+            },
+            Err(e) => {
+                if ! readonly {
+                    panic!("Unable to lock datastore in {}: {}", root, e);
+                }
+                None
+            }
+        };
+        // datastores of other versions must be migrated first, new datastores get the current version
+        let version = if ! root_path.join(Datastore::VERSION_FILE).exists() && ! root_path.join(format!("{}.store", Datastore::PROJECTS)).exists() {
+            Datastore::VERSION
+        } else {
+            Datastore::stored_version(root)
+        };
+        if version != Datastore::VERSION {
+            panic!("Datastore in {} has version {}, but version {} is required, use the migrate command", root, version, Datastore::VERSION);
+        }
+        if ! readonly && ! root_path.join(Datastore::VERSION_FILE).exists() {
+            Datastore::write_version(root, version).unwrap();
+        }
+        if ! readonly && ! root_path.join(Datastore::ID_FILE).exists() {
+            std::fs::write(root_path.join(Datastore::ID_FILE), format!("{:016x}{:016x}\n", rand::random::<u64>(), rand::random::<u64>())).unwrap();
+        }
+        if ! readonly && SETTINGS.checksums && ! root_path.join(Datastore::CHECKSUMS_FILE).exists() {
+            std::fs::write(root_path.join(Datastore::CHECKSUMS_FILE), "").unwrap();
+        }
+        enable_checksums(root, root_path.join(Datastore::CHECKSUMS_FILE).exists());
         LOG!("* Loading datastore in {}", root);
+        // datastores created before the hash index existed, or whose index was left incomplete, have it built once the substores are loaded
+        let build_sha_index = ! readonly && ! BitmapIndex::<SHA>::is_complete(root, Datastore::SHA_SUBSTORES);
         // create the datastore
         let mut ds = Datastore{
             root : root.to_owned(),
@@ -114,27 +267,84 @@ impl Datastore {
             project_substores : Mutex::new(Store::new(root, Datastore::PROJECT_SUBSTORES, readonly)),
             project_updates : Mutex::new(LinkedStore::new(root, Datastore::PROJECT_UPDATES, readonly)),
             project_heads : Mutex::new(Store::new(root, Datastore::PROJECT_HEADS, readonly)),
+            project_tags : Mutex::new(Store::new(root, Datastore::PROJECT_TAGS, readonly)),
+            project_labels : Mutex::new(Store::new(root, Datastore::PROJECT_LABELS, readonly)),
             project_metadata : Mutex::new(LinkedStore::new(root, Datastore::PROJECT_METADATA, readonly)),
+            project_summaries : Mutex::new(LinkedStore::new(root, Datastore::PROJECT_SUMMARIES, readonly)),
+            project_popularity : Mutex::new(LinkedStore::new(root, Datastore::PROJECT_POPULARITY, readonly)),
+            project_default_branches : Mutex::new(Store::new(root, Datastore::PROJECT_DEFAULT_BRANCHES, readonly)),
+            project_remote_heads : Mutex::new(Store::new(root, Datastore::PROJECT_REMOTE_HEADS, readonly)),
             project_urls : Mutex::new(HashSet::new()),
 
             substores : Vec::new(),
 
             savepoints : Mutex::new(LinkedStore::new(root, Datastore::SAVEPOINTS, readonly)),
+
+            sha_substores : Arc::new(Mutex::new(if readonly { BitmapIndex::open(root, Datastore::SHA_SUBSTORES) } else { Some(BitmapIndex::new(root, Datastore::SHA_SUBSTORES).unwrap()) })),
+
+            denylist : match Datastore::read_denylist(root) {
+                Ok(denylist) => Mutex::new(denylist),
+                Err(e) => panic!("Unable to load denylist: {}", e),
+            },
+            denylist_modified : Mutex::new(Datastore::denylist_modified_time(root)),
+
+            campaigns : match Datastore::read_campaigns(root) {
+                Ok(campaigns) => Mutex::new(campaigns),
+                Err(e) => panic!("Unable to load campaigns: {}", e),
+            },
+
+            update_passes : match Datastore::read_update_passes(root) {
+                Ok(passes) => Mutex::new(passes),
+                Err(e) => panic!("Unable to load update passes: {}", e),
+            },
+
+            substore_classifier : match DefaultSubstoreClassifier::from_datastore(root, Datastore::SMALL_PROJECT_THRESHOLD) {
+                Ok(classifier) => Box::new(classifier),
+                Err(e) => panic!("Unable to load substore classifier: {}", e),
+            },
+
+            lock,
         };
+        if ! readonly && SETTINGS.anonymize_emails && Datastore::email_key_fingerprint(root).is_none() {
+            // emails stored before would stay in the clear, so only new datastores can be anonymized
+            if ds.projects.lock().unwrap().len() > 0 {
+                panic!("Email anonymization can only be enabled for new datastores, {} already contains projects", root);
+            }
+            let key_path = Datastore::email_key_path(root);
+            // an existing key may be shared by multiple datastores so that they can be merged
+            if ! std::path::Path::new(& key_path).exists() {
+                let key : String = (0..4).map(|_| format!("{:016x}", rand::random::<u64>())).collect();
+                std::fs::write(& key_path, format!("{}\n", key)).unwrap();
+            }
+            let key = Datastore::read_key_file(& key_path);
+            std::fs::write(root_path.join(Datastore::ANONYMIZED_FILE), format!("{}\n", Datastore::hash_of(& key))).unwrap();
+        }
+        // readonly datastores never store new users, so they do not need the key
+        let email_key = if readonly { None } else { Datastore::read_email_key(root) };
         // initialize the substores
         for store_kind in SplitKindIter::<StoreKind>::new() {
             ds.substores.push(Substore::new(
                 & root_path.join(format!("{:?}", store_kind)),
                 store_kind,
-                readonly
+                readonly,
+                & ds.sha_substores,
+                email_key.clone()
             ));
         }
+        if build_sha_index {
+            LOG!("  building global hash index...");
+            // if the rebuild fails, the index stays marked incomplete and is rebuilt next time
+            match ds.rebuild_sha_index() {
+                Ok(indexed) => LOG!("    {} hashes indexed", indexed),
+                Err(e) => LOG!("    failed to build global hash index: {}", e),
+            }
+        }
         return ds;
     }
 
     pub (crate) fn verify(& self, task : & updater::TaskStatus) -> Result<usize, std::io::Error> {
         let mut progress = 0;
-        let max_progress = 6;
+        let max_progress = 12;
         task.progress(progress, max_progress);
         let mut items = 0;
         self.projects.lock().unwrap().verify(& mut |_|{
@@ -173,6 +383,24 @@ impl Datastore {
         })?;
         progress += 1;
         task.progress(progress, max_progress);
+        self.project_tags.lock().unwrap().verify(& mut |_|{
+            items += 1;
+            if items % 1000 == 0 {
+                task.info(format!("{} items, checking project tags...", helpers::pretty_value(items)));
+            }
+            return Ok(());
+        })?;
+        progress += 1;
+        task.progress(progress, max_progress);
+        self.project_labels.lock().unwrap().verify(& mut |_|{
+            items += 1;
+            if items % 1000 == 0 {
+                task.info(format!("{} items, checking project labels...", helpers::pretty_value(items)));
+            }
+            return Ok(());
+        })?;
+        progress += 1;
+        task.progress(progress, max_progress);
         self.project_metadata.lock().unwrap().verify(& mut |_|{
             items += 1;
             if items % 1000 == 0 {
@@ -182,6 +410,42 @@ impl Datastore {
         })?;
         progress += 1;
         task.progress(progress, max_progress);
+        self.project_summaries.lock().unwrap().verify(& mut |_|{
+            items += 1;
+            if items % 1000 == 0 {
+                task.info(format!("{} items, checking project summaries...", helpers::pretty_value(items)));
+            }
+            return Ok(());
+        })?;
+        progress += 1;
+        task.progress(progress, max_progress);
+        self.project_popularity.lock().unwrap().verify(& mut |_|{
+            items += 1;
+            if items % 1000 == 0 {
+                task.info(format!("{} items, checking project popularity...", helpers::pretty_value(items)));
+            }
+            return Ok(());
+        })?;
+        progress += 1;
+        task.progress(progress, max_progress);
+        self.project_default_branches.lock().unwrap().verify(& mut |_|{
+            items += 1;
+            if items % 1000 == 0 {
+                task.info(format!("{} items, checking project default branches...", helpers::pretty_value(items)));
+            }
+            return Ok(());
+        })?;
+        progress += 1;
+        task.progress(progress, max_progress);
+        self.project_remote_heads.lock().unwrap().verify(& mut |_|{
+            items += 1;
+            if items % 1000 == 0 {
+                task.info(format!("{} items, checking project remote heads...", helpers::pretty_value(items)));
+            }
+            return Ok(());
+        })?;
+        progress += 1;
+        task.progress(progress, max_progress);
         self.savepoints.lock().unwrap().verify(& mut |_|{
             items += 1;
             if items % 1000 == 0 {
@@ -194,59 +458,308 @@ impl Datastore {
         return Ok(items);
     }
 
+    /** Repairs recoverable corruption of the main datastore tables. 
+     
+        Partially written trailing records are truncated and indices are rebuilt from the stores. Returns the descriptions of the repairs made. Corruption that cannot be repaired without silently losing data is returned as an error and the remaining tables are left untouched. 
+     */
+    pub (crate) fn repair(& self, task : & updater::TaskStatus) -> Result<Vec<String>, std::io::Error> {
+        let mut repairs = Vec::<String>::new();
+        {
+            let mut report = |x : String| {
+                task.info(x.clone());
+                repairs.push(x);
+            };
+            let max_progress = 12;
+            task.progress(0, max_progress);
+            self.projects.lock().unwrap().repair(& mut report)?;
+            task.progress(1, max_progress);
+            self.project_substores.lock().unwrap().repair(& mut report)?;
+            task.progress(2, max_progress);
+            self.project_updates.lock().unwrap().repair(& mut report)?;
+            task.progress(3, max_progress);
+            self.project_heads.lock().unwrap().repair(& mut report)?;
+            task.progress(4, max_progress);
+            self.project_tags.lock().unwrap().repair(& mut report)?;
+            task.progress(5, max_progress);
+            self.project_labels.lock().unwrap().repair(& mut report)?;
+            task.progress(6, max_progress);
+            self.project_metadata.lock().unwrap().repair(& mut report)?;
+            task.progress(7, max_progress);
+            self.project_summaries.lock().unwrap().repair(& mut report)?;
+            task.progress(8, max_progress);
+            self.project_popularity.lock().unwrap().repair(& mut report)?;
+            task.progress(9, max_progress);
+            self.project_default_branches.lock().unwrap().repair(& mut report)?;
+            task.progress(10, max_progress);
+            self.project_remote_heads.lock().unwrap().repair(& mut report)?;
+            task.progress(11, max_progress);
+            self.savepoints.lock().unwrap().repair(& mut report)?;
+            task.progress(12, max_progress);
+        }
+        return Ok(repairs);
+    }
+
+    /** Returns the version of the datastore at given root. 
+     */
+    pub (crate) fn stored_version(root : & str) -> u16 {
+        return std::fs::read_to_string(format!("{}/{}", root, Datastore::VERSION_FILE)).ok()
+            .and_then(|x| x.trim().parse::<u16>().ok())
+            .unwrap_or(0);
+    }
+
+    /** Returns the id of the datastore at given root, or None for datastores that have never been opened for writing since ids were introduced. 
+     */
+    pub (crate) fn stored_id(root : & str) -> Option<String> {
+        return std::fs::read_to_string(format!("{}/{}", root, Datastore::ID_FILE)).ok()
+            .map(|x| x.trim().to_owned())
+            .filter(|x| ! x.is_empty());
+    }
+
+    /** Returns the path of the file with the key of the user email hashes of the datastore at given root. 
+     */
+    pub (crate) fn email_key_path(root : & str) -> String {
+        return SETTINGS.email_key.clone().unwrap_or_else(|| format!("{}/{}", root, Datastore::EMAIL_KEY_FILE));
+    }
+
+    /** Returns the fingerprint of the key of the user email hashes of the datastore at given root, or None if its emails are not anonymized. 
+     
+        The fingerprint is the hash of the key. Datastores anonymized before the fingerprint was stored have their key in the datastore root, from which the fingerprint is calculated. 
+     */
+    pub fn email_key_fingerprint(root : & str) -> Option<String> {
+        if let Ok(fingerprint) = std::fs::read_to_string(format!("{}/{}", root, Datastore::ANONYMIZED_FILE)) {
+            return Some(fingerprint.trim().to_owned());
+        }
+        let path = format!("{}/{}", root, Datastore::EMAIL_KEY_FILE);
+        if std::path::Path::new(& path).exists() {
+            return Some(Datastore::hash_of(& Datastore::read_key_file(& path)).to_string());
+        }
+        return None;
+    }
+
+    /** Returns the key of the user email hashes of the datastore at given root, or None if its emails are not anonymized. 
+     
+        Panics if the emails are anonymized, but the key is not available, or is not the key the datastore was anonymized with. 
+     */
+    pub (crate) fn read_email_key(root : & str) -> Option<Vec<u8>> {
+        let fingerprint = Datastore::email_key_fingerprint(root)?;
+        let path = if std::path::Path::new(& format!("{}/{}", root, Datastore::ANONYMIZED_FILE)).exists() { Datastore::email_key_path(root) } else { format!("{}/{}", root, Datastore::EMAIL_KEY_FILE) };
+        if ! std::path::Path::new(& path).exists() {
+            panic!("Emails of datastore {} are anonymized, but the email key {} does not exist, use --email-key", root, path);
+        }
+        let key = Datastore::read_key_file(& path);
+        if Datastore::hash_of(& key).to_string() != fingerprint {
+            panic!("Email key {} is not the key the emails of datastore {} were anonymized with", path, root);
+        }
+        return Some(key);
+    }
+
+    /** Reads the hex encoded email key from given file. 
+     */
+    fn read_key_file(path : & str) -> Vec<u8> {
+        let key = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("Cannot read email key {}: {}", path, e));
+        let key = key.trim();
+        if key.is_empty() || key.len() % 2 != 0 {
+            panic!("Invalid email key in {}", path);
+        }
+        return (0..key.len()).step_by(2).map(|i| u8::from_str_radix(& key[i..i + 2], 16).expect("Invalid email key")).collect();
+    }
+
+    /** Returns the anonymized form of given email. 
+     
+        The parts of the email are replaced by their HMAC-SHA1 with given key as hex strings separately, so that the anonymized email keeps the structure used to determine user aliases (see `Substore::resolve_user_aliases`): the address before the `+` suffix, the suffix and the domain are hashed, the lowercase domain followed by `.anon`, except for the GitHub noreply domain, which identifies GitHub logins, and is kept. Strings without `@` are hashed whole. 
+     */
+    pub (crate) fn anonymize_email(key : & [u8], email : & str) -> String {
+        let at = match email.rfind('@') {
+            Some(at) => at,
+            None => return Datastore::keyed_hash(key, email),
+        };
+        let (local, domain) = (& email[..at], & email[at + 1..]);
+        let local : Vec<String> = local.splitn(2, '+').map(|x| Datastore::keyed_hash(key, x)).collect();
+        if domain.eq_ignore_ascii_case("users.noreply.github.com") {
+            return format!("{}@{}", local.join("+"), domain);
+        }
+        return format!("{}@{}.anon", local.join("+"), Datastore::keyed_hash(key, & domain.to_lowercase()));
+    }
+
+    /** Returns the HMAC-SHA1 of given string with given key as hex string. 
+     */
+    fn keyed_hash(key : & [u8], value : & str) -> String {
+        let mut block = [0u8; 64];
+        if key.len() > block.len() {
+            block[..20].copy_from_slice(Datastore::hash_of(key).as_bytes());
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+        let mut inner = Sha1::new();
+        inner.update(block.iter().map(|x| x ^ 0x36).collect::<Vec<u8>>());
+        inner.update(value.as_bytes());
+        let mut outer = Sha1::new();
+        outer.update(block.iter().map(|x| x ^ 0x5c).collect::<Vec<u8>>());
+        outer.update(inner.finalize());
+        return SHA::from_bytes(& outer.finalize()).unwrap().to_string();
+    }
+
+    /** Sets the version of the datastore at given root. 
+     */
+    pub (crate) fn write_version(root : & str, version : u16) -> Result<(), std::io::Error> {
+        return std::fs::write(format!("{}/{}", root, Datastore::VERSION_FILE), format!("{}\n", version));
+    }
+
     /** Returns the root folder of the datastore. 
      */
     pub fn root_folder(&self) -> & str {
         return & self.root;
     }
 
+    /** Returns the folder where the repositories of the projects are cloned. 
+     
+        This is the `repo_clones` folder in the datastore root, or in the temporary directory from the settings, where it is suffixed with the datastore id so that multiple datastores can share the directory. 
+     */
+    pub (crate) fn clones_folder(& self) -> String {
+        match & SETTINGS.tmp_dir {
+            Some(tmp_dir) => return format!("{}/repo_clones-{}", tmp_dir, Datastore::stored_id(& self.root).unwrap_or_default()),
+            None => return format!("{}/repo_clones", self.root),
+        }
+    }
+
+    /** Returns the classifier that assigns projects to substores. 
+     */
+    pub fn substore_classifier(& self) -> & dyn SubstoreClassifier {
+        return self.substore_classifier.as_ref();
+    }
+
+    /** Replaces the classifier that assigns projects to substores. 
+     
+        Must be called before the updater is started. 
+     */
+    pub fn set_substore_classifier(& mut self, classifier : Box<dyn SubstoreClassifier>) {
+        self.substore_classifier = classifier;
+    }
+
     // savepoints -------------------------------------------------------------------------------------------------------
 
     /** Creates new savepoint and stores it in the datastore. 
      
         The savepoint will contain itself as well, i.e. when restoring the datastore to the savepoint the savepoint definition will remain intact. 
         
-        This is done by first creating dummy savepoint, storing it, thus increasing the savepoints size, creating new savepoint, reverting to the dummy one, and writing the proper savepoint. 
+        This is done by first creating dummy savepoint, storing it, thus increasing the savepoints size, creating new savepoint, reverting to the dummy one, and writing the proper savepoint. The progress of both is reported to the given task. 
+
+        If a fsync policy is selected, the tables are synced before the savepoint is written so that it never refers to data that may be lost, and once more after so that the savepoint itself is durable. 
      */
-    pub (crate) fn create_and_save_savepoint(& self, name : String) -> Savepoint {
-        let dummy = self.create_savepoint(name.clone());
+    pub (crate) fn create_and_save_savepoint(& self, name : String, task : & updater::TaskStatus) -> Result<Savepoint, std::io::Error> {
+        let dummy = self.create_savepoint(name.clone(), task)?;
         self.savepoints.lock().unwrap().set(0, & dummy);
-        let result = self.create_savepoint(name);
+        let result = self.create_savepoint(name, task)?;
         self.savepoints.lock().unwrap().revert_to_savepoint(& dummy);
         *self.savepoints.lock().unwrap() = LinkedStore::new(& self.root, "savepoints", false);
+        if SETTINGS.fsync.is_some() {
+            task.info("syncing tables...");
+            sync_tables()?;
+        }
         self.savepoints.lock().unwrap().set(0, & result);
-        return result;
+        if SETTINGS.fsync.is_some() {
+            sync_tables()?;
+        }
+        return Ok(result);
     }
 
     /** Creates savepoint. 
+     
+        Every table is flushed and sized, which may take a while as the tables have to be locked in turn. The progress is reported to the given task for each datastore table and each substore. 
      */
-    pub (crate) fn create_savepoint(& self, name : String) -> Savepoint {
+    pub (crate) fn create_savepoint(& self, name : String, task : & updater::TaskStatus) -> Result<Savepoint, std::io::Error> {
         let mut savepoint = Savepoint::new(name);
+        let max_progress = 12 + self.substores.len();
+        task.info("sizing datastore tables...");
+        task.progress(0, max_progress);
         self.projects.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(1, max_progress);
         self.project_substores.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(2, max_progress);
         self.project_updates.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(3, max_progress);
         self.project_heads.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(4, max_progress);
+        self.project_tags.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(5, max_progress);
+        self.project_labels.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(6, max_progress);
         self.project_metadata.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(7, max_progress);
+        self.project_summaries.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(8, max_progress);
+        self.project_popularity.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(9, max_progress);
+        self.project_default_branches.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(10, max_progress);
+        self.project_remote_heads.lock().unwrap().savepoint(& mut savepoint);
+        task.progress(11, max_progress);
         self.savepoints.lock().unwrap().savepoint(& mut savepoint);
-        for substore in self.substores.iter() {
+        task.progress(12, max_progress);
+        for (i, substore) in self.substores.iter().enumerate() {
+            task.info(format!("sizing substore {:?}...", substore.prefix));
+            substore.flush_sha_index()?;
             substore.savepoint(& mut savepoint);
+            task.progress(13 + i, max_progress);
         }
-        return savepoint;
+        return Ok(savepoint);
     }
 
     /** Reverts the datastore to given savepoint. 
      
         Note that this is destructive operation and *will* delete all data *after the savepoint 
+
+        Fails without changing anything if contents of any substore have been collected after the savepoint, since the collection rewrites the contents tables and their sizes in the savepoint are meaningless. 
      */
-    pub (crate) fn revert_to_savepoint(& self, sp : & Savepoint) {
+    pub (crate) fn revert_to_savepoint(& self, sp : & Savepoint) -> Result<(), std::io::Error> {
+        let _pins = DatastoreLock::exclude_pins(& self.root).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("Cannot revert to savepoint {}: {}", sp.name(), e)))?;
+        for substore in self.substores.iter() {
+            if let Some(time) = substore.contents_collected() {
+                if sp.time() <= time {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Cannot revert to savepoint {}, contents of substore {:?} were collected after it", sp.name(), substore.prefix)));
+                }
+            }
+        }
         self.projects.lock().unwrap().revert_to_savepoint(sp);
         self.project_substores.lock().unwrap().revert_to_savepoint(sp);
         self.project_updates.lock().unwrap().revert_to_savepoint(sp);
         self.project_heads.lock().unwrap().revert_to_savepoint(sp);
+        self.project_tags.lock().unwrap().revert_to_savepoint(sp);
+        self.project_labels.lock().unwrap().revert_to_savepoint(sp);
         self.project_metadata.lock().unwrap().revert_to_savepoint(sp);
+        self.project_summaries.lock().unwrap().revert_to_savepoint(sp);
+        self.project_popularity.lock().unwrap().revert_to_savepoint(sp);
+        self.project_default_branches.lock().unwrap().revert_to_savepoint(sp);
+        self.project_remote_heads.lock().unwrap().revert_to_savepoint(sp);
         self.savepoints.lock().unwrap().revert_to_savepoint(sp);
         for substore in self.substores.iter() {
-            substore.revert_to_savepoint(sp);
+            substore.revert_to_savepoint(sp)?;
+        }
+        // the hash index is not part of savepoints and must be rebuilt
+        self.rebuild_sha_index()?;
+        return Ok(());
+    }
+
+    /** Executes the savepoints command with given arguments and returns its output, see `Savepoint::command`. 
+     */
+    pub (crate) fn savepoints_command(& self, args : & [& str]) -> Result<String, std::io::Error> {
+        return Savepoint::command(self.savepoints.lock().unwrap().iter_all().map(|(_, sp)| sp).collect(), args);
+    }
+
+    /** Reports the statistics of given substore, such as the number of projects assigned to it, the number of records in its tables and its memory footprint. 
+     
+        Counting the projects and the stored contents reads the respective indices, which may take a while for large datastores. 
+     */
+    pub (crate) fn stats_command(& self, args : & [& str]) -> Result<String, std::io::Error> {
+        if args.len() != 1 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Usage: stats SUBSTORE"));
+        }
+        match StoreKind::from_string(args[0]) {
+            Some(kind) => {
+                let projects = self.project_substores.lock().unwrap().iter().filter(|(_, store)| *store == kind).count();
+                return Ok(self.substore(kind).stats(projects));
+            },
+            None => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Unknown substore {}", args[0]))),
         }
     }
 
@@ -259,6 +772,64 @@ impl Datastore {
 
     // substores --------------------------------------------------------------------------------------------------------
 
+    /** Returns the substores that contain a commit of given hash, according to the global hash index. 
+     */
+    pub fn find_commit(& self, hash : & SHA) -> Result<Vec<StoreKind>, std::io::Error> {
+        return Ok(Datastore::substores_in_bitmap(self.sha_bitmap(hash)?));
+    }
+
+    /** Returns the substores that contain file contents of given hash, according to the global hash index. 
+     */
+    pub fn find_contents(& self, hash : & SHA) -> Result<Vec<StoreKind>, std::io::Error> {
+        return Ok(Datastore::substores_in_bitmap(self.sha_bitmap(hash)? >> 32));
+    }
+
+    /** Returns the bitmap of given hash in the global hash index, including the bits the substores have not yet written to the index. 
+     */
+    fn sha_bitmap(& self, hash : & SHA) -> Result<u64, std::io::Error> {
+        let bitmap = match self.sha_substores.lock().unwrap().as_mut() {
+            Some(index) => index.get(hash)?,
+            None => 0,
+        };
+        return Ok(self.substores.iter().fold(bitmap, |bitmap, substore| bitmap | substore.pending_sha(hash)));
+    }
+
+    /** Returns the substores whose bits are set in the lower 32 bits of given bitmap. 
+     */
+    pub (crate) fn substores_in_bitmap(bitmap : u64) -> Vec<StoreKind> {
+        return SplitKindIter::<StoreKind>::new().filter(|x| bitmap & (1 << x.to_number()) != 0).collect();
+    }
+
+    /** Rebuilds the global hash index from the commits and hashes mappings of all substores. 
+     
+        Returns the number of hashes indexed. 
+     */
+    pub (crate) fn rebuild_sha_index(& self) -> Result<usize, std::io::Error> {
+        for substore in self.substores.iter() {
+            substore.flush_sha_index()?;
+        }
+        let mut index = self.sha_substores.lock().unwrap();
+        let index = match index.as_mut() {
+            Some(index) => index,
+            None => return Ok(0),
+        };
+        index.begin_batch()?;
+        index.clear()?;
+        for substore in self.substores.iter() {
+            LOG!("    indexing hashes of substore {:?}", substore.prefix);
+            let bit = substore.commit_bit();
+            for (_, hash) in substore.commits.lock().unwrap().iter() {
+                index.set(& hash, bit)?;
+            }
+            let bit = substore.contents_bit();
+            for (_, hash) in substore.hashes.lock().unwrap().iter() {
+                index.set(& hash, bit)?;
+            }
+        }
+        index.end_batch()?;
+        return Ok(index.len());
+    }
+
     /** Returns the appropriate substore.
      */
     pub (crate) fn substore(& self, substore : StoreKind) -> & Substore {
@@ -309,17 +880,29 @@ impl Datastore {
         self.project_updates.lock().unwrap().set(id, & status);    
     }
 
+    /** Returns the path filters last recorded in the project's log, if any. 
+     */
+    pub fn get_project_path_filters(& self, id : ProjectId) -> Option<String> {
+        for status in self.project_updates.lock().unwrap().iter_id(id) {
+            if let ProjectLog::PathFilters{time : _, version : _, filters} = status {
+                return Some(filters);
+            }
+        }
+        return None;
+    }
+
     pub fn get_project_substore(& self, id : ProjectId) -> StoreKind {
         return self.project_substores.lock().unwrap().get(id).or(Some(StoreKind::Unspecified)).unwrap();
     }
 
     /** Updates project substore information. 
      
-        Adds the update status about store change and inserts a blank heads so that next time the project will be analyzed in its entirety in the new substore. 
+        Adds the update status about store change and inserts a blank heads and tags so that next time the project will be analyzed in its entirety in the new substore. 
      */
     pub (crate) fn update_project_substore(& self, id : ProjectId, store : StoreKind) {
         self.project_substores.lock().unwrap().set(id, & store);
         self.project_heads.lock().unwrap().set(id, & ProjectHeads::new());
+        self.project_tags.lock().unwrap().set(id, & ProjectTags::new());
         self.project_updates.lock().unwrap().set(id,  & ProjectLog::ChangeStore{
             time : helpers::now(),
             version : Datastore::VERSION,
@@ -339,47 +922,582 @@ impl Datastore {
         self.project_heads.lock().unwrap().set(id, heads);
     }
 
-    /** Returns metadata value for given key and project, if one exists. 
+    /** Returns the heads of given project last seen by a heads only update, if any. 
      */
-    pub fn get_project_metadata(& self, id : ProjectId, key : & str) -> Option<String> {
-        let mut metadata = self.project_metadata.lock().unwrap();
-        for kv in metadata.iter_id(id) {
-            if kv.key == key {
-                return Some(kv.value);
-            }
-        }
-        return None;
+    pub fn get_project_remote_heads(& self, id : ProjectId) -> Option<ProjectHeads> {
+        return self.project_remote_heads.lock().unwrap().get(id);
     }
 
-    /** Updates metadata value for given key if the last stored value differs. 
-     
-        Returns true if the value was updated, false otherwise.
+    /** Records the remote heads of given project. 
      */
-    pub (crate) fn update_project_metadata_if_differ(& self, id : ProjectId, key : String, value : String) -> bool {
-        let mut metadata = self.project_metadata.lock().unwrap();
-        for kv in metadata.iter_id(id) {
-            if kv.key == key {
-                if kv.value == value {
-                    return false;
-                } else {
-                    break;
-                }
-            }
-        }
-        metadata.set(id, & Metadata{key, value });
-        return true;
+    pub (crate) fn update_project_remote_heads(& self, id : ProjectId, heads : & ProjectHeads) {
+        self.project_remote_heads.lock().unwrap().set(id, heads);
     }
 
-    pub (crate) fn project_urls_loaded(& self) -> bool {
-        if self.project_urls.lock().unwrap().len() > 0 {
-            return true;
-        }
-        return self.projects.lock().unwrap().len() == 0;
+    /** Returns the latest project tags for given project. 
+     */
+    pub fn get_project_tags(& self, id : ProjectId) -> Option<ProjectTags> {
+        return self.project_tags.lock().unwrap().get(id);
     }
 
-    /** Memory report for the project urls. 
+    /** Updates the project tags to given value. 
+     */
+    pub (crate) fn update_project_tags(& self, id : ProjectId, tags : & ProjectTags) {
+        self.project_tags.lock().unwrap().set(id, tags);
+    }
+
+    /** Returns the labels of given project. 
      
-        Returns empty string if the project urls are not loaded, otherwise returns their shortname (`purl`) and and the number of projects loaded. 
+        Projects that were never labelled have no labels. 
+     */
+    pub fn get_project_labels(& self, id : ProjectId) -> ProjectLabels {
+        return self.project_labels.lock().unwrap().get(id).unwrap_or_else(|| ProjectLabels::new());
+    }
+
+    /** Adds the label to given project. 
+     
+        Returns true if the label was added, false if the project already had the label. 
+     */
+    pub (crate) fn add_project_label(& self, id : ProjectId, label : & str) -> bool {
+        let mut labels_store = self.project_labels.lock().unwrap();
+        let mut labels = labels_store.get(id).unwrap_or_else(|| ProjectLabels::new());
+        if labels.iter().any(|x| x == label) {
+            return false;
+        }
+        labels.push(label.to_owned());
+        labels_store.set(id, & labels);
+        return true;
+    }
+
+    /** Removes the label from given project. 
+     
+        Returns true if the label was removed, false if the project did not have the label. 
+     */
+    pub (crate) fn remove_project_label(& self, id : ProjectId, label : & str) -> bool {
+        let mut labels_store = self.project_labels.lock().unwrap();
+        let mut labels = labels_store.get(id).unwrap_or_else(|| ProjectLabels::new());
+        let len = labels.len();
+        labels.retain(|x| x != label);
+        if labels.len() == len {
+            return false;
+        }
+        labels_store.set(id, & labels);
+        return true;
+    }
+
+    /** Reads the denylist of the datastore at given root, which is empty if the datastore has no denylist file. 
+     */
+    fn read_denylist(root : & str) -> Result<Vec<DenylistEntry>, std::io::Error> {
+        let filename = format!("{}/{}", root, Datastore::DENYLIST_FILE);
+        let mut result = Vec::new();
+        if ! std::path::Path::new(& filename).exists() {
+            return Ok(result);
+        }
+        for (i, line) in std::fs::read_to_string(& filename)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+            let parts : Vec<& str> = line.splitn(3, ",").map(|x| x.trim()).collect();
+            let entry = DenylistEntry::new(parts[0], parts.get(1).cloned().unwrap_or(""), parts.get(2).cloned().unwrap_or(""));
+            result.push(entry.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}, line {}: invalid entry {}", filename, i + 1, line)))?);
+        }
+        return Ok(result);
+    }
+
+    fn write_denylist(& self, denylist : & Vec<DenylistEntry>) -> Result<(), std::io::Error> {
+        let contents : String = denylist.iter().map(|x| format!("{},{},{}\n", x.kind, x.value, x.reason)).collect();
+        std::fs::write(format!("{}/{}", self.root, Datastore::DENYLIST_FILE), contents)?;
+        *self.denylist_modified.lock().unwrap() = Datastore::denylist_modified_time(& self.root);
+        return Ok(());
+    }
+
+    fn denylist_modified_time(root : & str) -> Option<std::time::SystemTime> {
+        return std::fs::metadata(format!("{}/{}", root, Datastore::DENYLIST_FILE)).and_then(|x| x.modified()).ok();
+    }
+
+    /** Reads the denylist file again if it was changed since the denylist was last read, or written, e.g. edited by hand while the updater runs. 
+     
+        Returns true if the denylist was reloaded. If the changed file is invalid, the error is returned and the current denylist is kept. 
+     */
+    pub (crate) fn reload_denylist_if_changed(& self) -> Result<bool, std::io::Error> {
+        let mut denylist = self.denylist.lock().unwrap();
+        let mut modified = self.denylist_modified.lock().unwrap();
+        let current = Datastore::denylist_modified_time(& self.root);
+        if current == *modified {
+            return Ok(false);
+        }
+        // the time is remembered even if the file is invalid, so that the error is reported only once per change
+        *modified = current;
+        *denylist = Datastore::read_denylist(& self.root)?;
+        return Ok(true);
+    }
+
+    /** Returns the entries of the denylist. 
+     */
+    pub (crate) fn denylist(& self) -> Vec<DenylistEntry> {
+        return self.denylist.lock().unwrap().clone();
+    }
+
+    /** Adds the entry to the denylist and persists the denylist. 
+     
+        Returns false if the same url pattern, or project id is already denied. 
+     */
+    pub (crate) fn deny(& self, entry : DenylistEntry) -> Result<bool, std::io::Error> {
+        let mut denylist = self.denylist.lock().unwrap();
+        if denylist.iter().any(|x| x.kind == entry.kind && x.value == entry.value) {
+            return Ok(false);
+        }
+        denylist.push(entry);
+        self.write_denylist(& denylist)?;
+        return Ok(true);
+    }
+
+    /** Removes the url pattern, or project id from the denylist and persists the denylist. 
+     
+        Returns false if the entry was not denied. 
+     */
+    pub (crate) fn allow(& self, kind : & str, value : & str) -> Result<bool, std::io::Error> {
+        let mut denylist = self.denylist.lock().unwrap();
+        let len = denylist.len();
+        denylist.retain(|x| x.kind != kind || x.value != value);
+        if denylist.len() == len {
+            return Ok(false);
+        }
+        self.write_denylist(& denylist)?;
+        return Ok(true);
+    }
+
+    /** Returns the denylist entry that matches given project, if any. 
+     
+        The id is None for projects that are not yet in the datastore, in which case only the url patterns are checked. 
+     */
+    pub (crate) fn find_denied(& self, id : Option<ProjectId>, project : & ProjectUrl) -> Option<DenylistEntry> {
+        let url = project.clone_url();
+        return self.denylist.lock().unwrap().iter().find(|x| x.matches(id, & url)).cloned();
+    }
+
+    /** Executes the `blacklist` command with given arguments (without the command itself) and returns the message to be displayed. 
+     
+        The arguments are `add url|project VALUE [REASON]`, `remove url|project VALUE`, or `list`. Invalid arguments are reported as `InvalidInput` errors with the usage. 
+     */
+    pub (crate) fn denylist_command(& self, args : & [& str]) -> Result<String, std::io::Error> {
+        let usage = || std::io::Error::new(std::io::ErrorKind::InvalidInput, "Usage: blacklist add url|project VALUE [REASON], blacklist remove url|project VALUE, or blacklist list");
+        match args.get(0).cloned() {
+            Some("list") if args.len() == 1 => {
+                let denylist = self.denylist();
+                let mut result = format!("{} denylist entries", denylist.len());
+                for entry in denylist.iter() {
+                    result.push_str(& format!("\n    {}", entry));
+                }
+                return Ok(result);
+            },
+            Some("add") if args.len() >= 3 => {
+                let entry = DenylistEntry::new(args[1], args[2], & args[3..].join(" ")).ok_or_else(usage)?;
+                let description = entry.to_string();
+                if self.deny(entry)? {
+                    return Ok(format!("Denied {}", description));
+                } else {
+                    return Ok(format!("{} {} already denied", args[1], args[2]));
+                }
+            },
+            Some("remove") if args.len() == 3 => {
+                if self.allow(args[1], args[2])? {
+                    return Ok(format!("{} {} removed from the denylist", args[1], args[2]));
+                } else {
+                    return Ok(format!("{} {} not denied", args[1], args[2]));
+                }
+            },
+            _ => return Err(usage()),
+        }
+    }
+
+    /** Checks whether given project is denied and if so, records that its update was suppressed. 
+     
+        The denied record is only added if the latest record of the project is not a denied record already. Returns true if the project is denied. 
+     */
+    pub (crate) fn suppress_if_denied(& self, id : ProjectId) -> bool {
+        let project = match self.get_project(id) {
+            Some(project) => project,
+            None => return false,
+        };
+        match self.find_denied(Some(id), & project) {
+            Some(entry) => {
+                if ! self.get_project_last_update(id).map(|x| x.is_denied()).unwrap_or(false) {
+                    self.update_project_update_status(id, ProjectLog::Denied{
+                        time : helpers::now(),
+                        version : Datastore::VERSION,
+                        reason : entry.to_string(),
+                    });
+                }
+                return true;
+            },
+            None => return false,
+        }
+    }
+
+    /** Reads the update campaigns of the datastore at given root, which are empty if the datastore has no campaigns file. 
+     */
+    fn read_campaigns(root : & str) -> Result<Vec<Campaign>, std::io::Error> {
+        let filename = format!("{}/{}", root, Datastore::CAMPAIGNS_FILE);
+        let mut result = Vec::new();
+        if ! std::path::Path::new(& filename).exists() {
+            return Ok(result);
+        }
+        for (i, line) in std::fs::read_to_string(& filename)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+            let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}, line {}: invalid campaign {}", filename, i + 1, line));
+            let parts : Vec<& str> = line.splitn(5, ",").map(|x| x.trim()).collect();
+            if parts.len() != 5 {
+                return Err(invalid());
+            }
+            let time = parts[1].parse::<i64>().map_err(|_| invalid())?;
+            let projects = parts[2].parse::<usize>().map_err(|_| invalid())?;
+            result.push(Campaign::new(parts[0], parts[4], parts[3], time, projects).ok_or_else(invalid)?);
+        }
+        return Ok(result);
+    }
+
+    fn write_campaigns(& self, campaigns : & Vec<Campaign>) -> Result<(), std::io::Error> {
+        let contents : String = campaigns.iter().map(|x| format!("{},{},{},{},{}\n", x.name, x.time, x.projects, x.savepoint, x.filter)).collect();
+        return std::fs::write(format!("{}/{}", self.root, Datastore::CAMPAIGNS_FILE), contents);
+    }
+
+    /** Returns the campaign of given name, if any. 
+     */
+    pub (crate) fn get_campaign(& self, name : & str) -> Option<Campaign> {
+        return self.campaigns.lock().unwrap().iter().find(|x| x.name == name).cloned();
+    }
+
+    /** Starts new update campaign of the projects matching given filter at the given savepoint and persists the campaigns. 
+     
+        The campaign contains the projects matching the filter that are in the datastore when it starts. Fails if a campaign of the same name already exists, or if the savepoint does not exist. 
+     */
+    pub (crate) fn start_campaign(& self, name : & str, filter : & str, savepoint : & str) -> Result<Campaign, std::io::Error> {
+        let sp = self.get_savepoint(savepoint).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, format!("Savepoint {} not found", savepoint)))?;
+        let campaign = Campaign::new(name, filter, savepoint, sp.time(), self.num_projects()).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid campaign name {}, or filter {}", name, filter)))?;
+        let mut campaigns = self.campaigns.lock().unwrap();
+        if campaigns.iter().any(|x| x.name == campaign.name) {
+            return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, format!("Campaign {} already exists", name)));
+        }
+        campaigns.push(campaign.clone());
+        self.write_campaigns(& campaigns)?;
+        return Ok(campaign);
+    }
+
+    /** Removes the campaign of given name and persists the campaigns. 
+     
+        Returns false if there is no such campaign. The project updates of the campaign are kept. 
+     */
+    pub (crate) fn remove_campaign(& self, name : & str) -> Result<bool, std::io::Error> {
+        let mut campaigns = self.campaigns.lock().unwrap();
+        let len = campaigns.len();
+        campaigns.retain(|x| x.name != name);
+        if campaigns.len() == len {
+            return Ok(false);
+        }
+        self.write_campaigns(& campaigns)?;
+        return Ok(true);
+    }
+
+    /** Returns the ids of the projects of given campaign, i.e. the projects that existed when the campaign started and currently match its filter. 
+     */
+    pub (crate) fn campaign_projects(& self, campaign : & Campaign) -> Vec<ProjectId> {
+        let in_campaign = |id : ProjectId| (u64::from(id) as usize) < campaign.projects;
+        match campaign.filter_kind() {
+            "substore" => {
+                let kind = StoreKind::from_string(campaign.filter_value()).unwrap();
+                return self.project_substores.lock().unwrap().iter()
+                    .filter(|(id, store)| *store == kind && in_campaign(*id))
+                    .map(|(id, _)| id)
+                    .collect();
+            },
+            "label" => {
+                return self.projects_with_label(campaign.filter_value()).into_iter().filter(|id| in_campaign(*id)).collect();
+            },
+            "url" => {
+                let pattern = helpers::UrlPattern::new(campaign.filter_value());
+                return self.projects.lock().unwrap().iter()
+                    .filter(|(id, project)| in_campaign(*id) && pattern.matches(& project.clone_url()))
+                    .map(|(id, _)| id)
+                    .collect();
+            },
+            _ => return (0..campaign.projects as u64).map(ProjectId::from).collect(),
+        }
+    }
+
+    /** Determines the progress of each project of given campaign from the update log. 
+     
+        A project is completed once the outcome of its latest update is newer than the start of the campaign. Records that are not outcomes of updates, such as renames detected by metadata refreshes, are skipped. Since the update log is persistent, so is the completion and campaigns continue where they stopped when the updater is restarted. 
+     */
+    pub (crate) fn campaign_progress(& self, campaign : & Campaign) -> Vec<(ProjectId, CampaignProgress)> {
+        let projects = self.campaign_projects(campaign);
+        let mut updates = self.project_updates.lock().unwrap();
+        return projects.into_iter().map(|id| {
+            let progress = updates.iter_id(id)
+                .take_while(|log| log.time() >= campaign.time)
+                .find_map(|log| CampaignProgress::from(& log))
+                .unwrap_or(CampaignProgress::Pending);
+            return (id, progress);
+        }).collect();
+    }
+
+    /** Returns the projects of given campaign that have not yet been completed. 
+     */
+    pub (crate) fn campaign_pending(& self, name : & str) -> Result<Vec<ProjectId>, std::io::Error> {
+        let campaign = self.get_campaign(name).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, format!("Campaign {} not found", name)))?;
+        return Ok(self.campaign_progress(& campaign).into_iter()
+            .filter(|(_, progress)| *progress == CampaignProgress::Pending)
+            .map(|(id, _)| id)
+            .collect());
+    }
+
+    /** Returns the status of given campaign, i.e. the number of its projects in each state. 
+     */
+    fn campaign_status(& self, campaign : & Campaign) -> String {
+        let progress = self.campaign_progress(campaign);
+        let count = |state : CampaignProgress| progress.iter().filter(|(_, x)| *x == state).count();
+        let pending = count(CampaignProgress::Pending);
+        let completed = progress.len() - pending;
+        let mut result = format!("{}: {} of {} projects completed ({:.1}%), {} updated, {} unchanged, {} failed, {} pending",
+            campaign,
+            completed,
+            progress.len(),
+            if progress.is_empty() { 100.0 } else { completed as f64 * 100.0 / progress.len() as f64 },
+            count(CampaignProgress::Updated),
+            count(CampaignProgress::Unchanged),
+            count(CampaignProgress::Failed),
+            pending
+        );
+        if pending == 0 {
+            result.push_str(", done");
+        }
+        return result;
+    }
+
+    /** Executes the `campaign` command with given arguments (without the command itself) and returns the message to be displayed. 
+     
+        The arguments are `start NAME FILTER SAVEPOINT`, `status [NAME]`, `list`, or `remove NAME`. Scheduling the updates of the campaign's projects is left to the updater. Invalid arguments are reported as `InvalidInput` errors with the usage. 
+     */
+    pub (crate) fn campaign_command(& self, args : & [& str]) -> Result<String, std::io::Error> {
+        let usage = || std::io::Error::new(std::io::ErrorKind::InvalidInput, "Usage: campaign start NAME all|substore=KIND|label=LABEL|url=PATTERN SAVEPOINT, campaign status [NAME], campaign list, or campaign remove NAME");
+        match args.get(0).cloned() {
+            Some("list") if args.len() == 1 => {
+                let campaigns = self.campaigns.lock().unwrap().clone();
+                let mut result = format!("{} campaigns", campaigns.len());
+                for campaign in campaigns.iter() {
+                    result.push_str(& format!("\n    {}", campaign));
+                }
+                return Ok(result);
+            },
+            Some("start") if args.len() == 4 => {
+                let campaign = self.start_campaign(args[1], args[2], args[3])?;
+                return Ok(format!("Started campaign {}", campaign));
+            },
+            Some("status") if args.len() == 1 => {
+                let campaigns = self.campaigns.lock().unwrap().clone();
+                let mut result = format!("{} campaigns", campaigns.len());
+                for campaign in campaigns.iter() {
+                    result.push_str(& format!("\n    {}", self.campaign_status(campaign)));
+                }
+                return Ok(result);
+            },
+            Some("status") if args.len() == 2 => {
+                match self.get_campaign(args[1]) {
+                    Some(campaign) => return Ok(self.campaign_status(& campaign)),
+                    None => return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("Campaign {} not found", args[1]))),
+                }
+            },
+            Some("remove") if args.len() == 2 => {
+                if self.remove_campaign(args[1])? {
+                    return Ok(format!("Campaign {} removed", args[1]));
+                } else {
+                    return Ok(format!("Campaign {} not found", args[1]));
+                }
+            },
+            _ => return Err(usage()),
+        }
+    }
+
+    /** Reads the unfinished update passes. 
+     
+        Each line contains the start of the pass, the substore it reached, its mode, the arguments of its filter and its name, separated by tabs. Malformed lines are skipped with a warning, since the passes are only an optimization and losing one only means that its projects will be updated again. 
+     */
+    fn read_update_passes(root : & str) -> Result<Vec<UpdatePass>, std::io::Error> {
+        let filename = format!("{}/{}", root, Datastore::UPDATE_PASSES_FILE);
+        let mut result = Vec::new();
+        if ! std::path::Path::new(& filename).exists() {
+            return Ok(result);
+        }
+        for (i, line) in std::fs::read_to_string(& filename)?.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parts : Vec<& str> = line.splitn(5, '\t').collect();
+            let pass = match parts[..] {
+                [start, substore, mode, filter, name] => match (start.parse::<i64>(), StoreKind::from_string(substore), updater::UpdateMode::from_string(mode), updater::UpdateFilter::parse(& filter.split_whitespace().collect::<Vec<& str>>())) {
+                    (Ok(start), Some(substore), Some(mode), Ok(filter)) => Some(UpdatePass{ name : name.to_owned(), start, substore, mode, filter }),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match pass {
+                Some(pass) => result.push(pass),
+                None => LOG!("WARNING: {}, line {}: invalid update pass {}, skipped", filename, i + 1, line),
+            }
+        }
+        return Ok(result);
+    }
+
+    /** Writes the unfinished update passes. 
+     
+        The passes are written to a temporary file first, which then replaces the passes file, so that the passes are never lost by an interrupted write. 
+     */
+    fn write_update_passes(& self, passes : & Vec<UpdatePass>) -> Result<(), std::io::Error> {
+        let contents : String = passes.iter().map(|x| format!("{}\t{:?}\t{:?}\t{}\t{}\n", x.start, x.substore, x.mode, x.filter.args().join(" "), x.name)).collect();
+        let filename = format!("{}/{}", self.root, Datastore::UPDATE_PASSES_FILE);
+        let tmp = format!("{}.tmp", filename);
+        std::fs::write(& tmp, contents)?;
+        return std::fs::rename(tmp, filename);
+    }
+
+    /** Returns all unfinished update passes. 
+     */
+    pub (crate) fn update_passes(& self) -> Vec<UpdatePass> {
+        return self.update_passes.lock().unwrap().clone();
+    }
+
+    /** Returns the unfinished update pass of given name, if any. 
+     */
+    pub (crate) fn get_update_pass(& self, name : & str) -> Option<UpdatePass> {
+        return self.update_passes.lock().unwrap().iter().find(|x| x.name == name).cloned();
+    }
+
+    /** Stores the update pass, replacing the pass of the same name, if any, and persists the passes. 
+     */
+    pub (crate) fn set_update_pass(& self, pass : UpdatePass) -> Result<(), std::io::Error> {
+        let mut passes = self.update_passes.lock().unwrap();
+        passes.retain(|x| x.name != pass.name);
+        passes.push(pass);
+        return self.write_update_passes(& passes);
+    }
+
+    /** Removes the update pass of given name, i.e. marks it as finished, and persists the passes. 
+     */
+    pub (crate) fn remove_update_pass(& self, name : & str) -> Result<(), std::io::Error> {
+        let mut passes = self.update_passes.lock().unwrap();
+        let len = passes.len();
+        passes.retain(|x| x.name != name);
+        if passes.len() != len {
+            self.write_update_passes(& passes)?;
+        }
+        return Ok(());
+    }
+
+    /** Returns the ids of all projects that have the given label. 
+     */
+    pub (crate) fn projects_with_label(& self, label : & str) -> Vec<ProjectId> {
+        return self.project_labels.lock().unwrap().iter()
+            .filter(|(_, labels)| labels.iter().any(|x| x == label))
+            .map(|(id, _)| id)
+            .collect();
+    }
+
+    /** Returns the latest summary of given project, if the project has been summarized. 
+     */
+    pub fn get_project_summary(& self, id : ProjectId) -> Option<ProjectSummary> {
+        return self.project_summaries.lock().unwrap().get(id);
+    }
+
+    /** Adds new summary of given project. 
+     */
+    pub (crate) fn update_project_summary(& self, id : ProjectId, summary : & ProjectSummary) {
+        self.project_summaries.lock().unwrap().set(id, summary);
+    }
+
+    /** Returns the popularity of given project over time, from the oldest to the latest record. 
+     */
+    pub fn get_project_popularity(& self, id : ProjectId) -> Vec<ProjectPopularity> {
+        let mut result : Vec<ProjectPopularity> = self.project_popularity.lock().unwrap().iter_id(id).collect();
+        result.reverse();
+        return result;
+    }
+
+    /** Returns the latest popularity record of given project, if any. 
+     */
+    pub fn get_project_latest_popularity(& self, id : ProjectId) -> Option<ProjectPopularity> {
+        return self.project_popularity.lock().unwrap().get(id);
+    }
+
+    /** Adds new popularity record of given project. 
+     */
+    pub (crate) fn update_project_popularity(& self, id : ProjectId, popularity : & ProjectPopularity) {
+        self.project_popularity.lock().unwrap().set(id, popularity);
+    }
+
+    /** Returns the default branch of given project, if known. 
+     */
+    pub fn get_project_default_branch(& self, id : ProjectId) -> Option<DefaultBranch> {
+        return self.project_default_branches.lock().unwrap().get(id);
+    }
+
+    /** Updates the default branch of given project if it differs from the one stored. 
+     
+        Returns true if the default branch was updated. 
+     */
+    pub (crate) fn update_project_default_branch_if_differ(& self, id : ProjectId, branch : & DefaultBranch) -> bool {
+        let mut store = self.project_default_branches.lock().unwrap();
+        if store.get(id).as_ref() == Some(branch) {
+            return false;
+        }
+        store.set(id, branch);
+        return true;
+    }
+
+    /** Returns metadata value for given key and project, if one exists. 
+     */
+    pub fn get_project_metadata(& self, id : ProjectId, key : & str) -> Option<String> {
+        let mut metadata = self.project_metadata.lock().unwrap();
+        for kv in metadata.iter_id(id) {
+            if kv.key == key {
+                return Some(kv.value);
+            }
+        }
+        return None;
+    }
+
+    /** Updates metadata value for given key if the last stored value differs. 
+     
+        Returns true if the value was updated, false otherwise.
+     */
+    pub (crate) fn update_project_metadata_if_differ(& self, id : ProjectId, key : String, value : String) -> bool {
+        let mut metadata = self.project_metadata.lock().unwrap();
+        for kv in metadata.iter_id(id) {
+            if kv.key == key {
+                if kv.value == value {
+                    return false;
+                } else {
+                    break;
+                }
+            }
+        }
+        metadata.set(id, & Metadata{key, value });
+        return true;
+    }
+
+    pub (crate) fn project_urls_loaded(& self) -> bool {
+        if self.project_urls.lock().unwrap().len() > 0 {
+            return true;
+        }
+        return self.projects.lock().unwrap().len() == 0;
+    }
+
+    /** Memory report for the project urls. 
+     
+        Returns empty string if the project urls are not loaded, otherwise returns their shortname (`purl`) and and the number of projects loaded. 
      */
     pub (crate) fn project_urls_memory_report(& self) -> String {
         let loaded_projects = self.project_urls.lock().unwrap().len();
@@ -441,72 +1559,567 @@ impl Datastore {
     }
 }
 
-/** Contains information about a selected subset of projetcs from the datastore. 
+/** Advisory lock of a datastore that coordinates the processes using it. 
  
+    Processes writing to the datastore hold the lock exclusively and readers hold it shared, so that no process reads the tables while another process writes them. The lock is a `flock` of the lock file in the datastore root, which the operating system releases when the process terminates, even if it crashes. Locks are never waited for, conflicting locks fail immediately with `WouldBlock`. 
+ 
+    Since `flock` locks of the same file opened twice conflict even within a single process, the locks are counted per process and the lock file is opened only once. The process holds the file locked exclusively while it has any exclusive locks and shared while it only has shared ones. 
+ 
+    Views pinned to savepoints may read the datastore while another process writes to it, since writers only append past the sizes recorded in the savepoint. They lock the pins file instead (see `pin`), which is locked exclusively only by the operations that truncate, or rewrite the tables, i.e. reverting to a savepoint and collecting contents, so that these fail while any pinned view is in use. 
  */
-pub (crate) struct Substore {
-
-    /** Root folder where the dataset is located. 
-     */
-    root : String,
-    /** The prefix of the dataset. 
-     */
-    pub (crate) prefix : StoreKind,
+pub (crate) struct DatastoreLock {
+    path : String,
+    exclusive : bool,
+}
 
-    /** Determines whether the substore's mappings are loaded in memory and therefore new items can be added to it. 
-     
-        Atomic flag and mutex for actually loading and clearing the substore.
-     */
-    loaded : AtomicBool,
-    load_mutex : Mutex<()>,
+/** The lock file of a datastore opened by this process, with the number of exclusive and shared locks of it. 
+ */
+struct HeldLock {
+    file : std::fs::File,
+    exclusive : usize,
+    shared : usize,
+}
 
-    /** Commits stored in the dataset. 
-     */
-    pub (crate) commits : Mutex<Mapping<SHA, CommitId>>,
-    pub (crate) commits_info : Mutex<Store<CommitInfo, CommitId>>,
-    pub (crate) commits_metadata : Mutex<LinkedStore<Metadata, CommitId>>,
+lazy_static! {
+    static ref HELD_LOCKS : Mutex<HashMap<String, HeldLock>> = Mutex::new(HashMap::new());
+}
 
-    /** File hashes and their contents. 
+impl DatastoreLock {
+    /** Locks the datastore in given root folder, which must exist. 
      
-        Every time a commit a file is changed, the hash of its contents is added to the hashes mapping. Some of these files may then have their contents stored and some won't.  
-
-        We use a split store based on the ContentsKind of the file to be stored. The index is kept for *all* file hashes, i.e. there will be a lot of empty holes in it, but these holes will be relatively cheap (20 bytes per hole, 10 bytes for contents and 10 bytes for metadata) and on disk, where it bothers us less. 
+        Readers may lock datastores they cannot write to, as long as the lock file exists. 
      */
-    pub (crate) hashes : Mutex<Mapping<SHA, HashId>>,
-    pub (crate) contents : Mutex<SplitStore<FileContents, ContentsKind, HashId>>,
-    pub (crate) contents_metadata : Mutex<LinkedStore<Metadata, HashId>>,
+    pub (crate) fn acquire(root : & str, exclusive : bool) -> Result<DatastoreLock, std::io::Error> {
+        return DatastoreLock::lock_file(root, Datastore::LOCK_FILE, exclusive);
+    }
 
-    /** Paths. 
-     
-        Path hash to ids is stored in a mapping at runtime, while path strings are stored separately in an indexable store on disk. 
+    /** Locks the pins file of the datastore shared for a view pinned to a savepoint. 
      */
-    pub (crate) paths : Mutex<Mapping<SHA, PathId>>,
-    pub (crate) path_strings : Mutex<Store<PathString, PathId>>,
+    pub (crate) fn pin(root : & str) -> Result<DatastoreLock, std::io::Error> {
+        return DatastoreLock::lock_file(root, Datastore::PINS_FILE, false);
+    }
 
-    /** Users.
-     
-        Users are mapped by their email. 
+    /** Locks the pins file of the datastore exclusively, failing with `WouldBlock` if any view pinned to a savepoint is in use, including views of this process. 
      */
-    pub (crate) users : Mutex<IndirectMapping<String, UserId>>,
-    pub (crate) users_metadata : Mutex<LinkedStore<Metadata, UserId>>,
+    pub (crate) fn exclude_pins(root : & str) -> Result<DatastoreLock, std::io::Error> {
+        let path = std::fs::canonicalize(root)?.join(Datastore::PINS_FILE).to_string_lossy().to_string();
+        if HELD_LOCKS.lock().unwrap().get(& path).map(|x| x.shared > 0).unwrap_or(false) {
+            return Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "datastore is read by a view pinned to a savepoint"));
+        }
+        return DatastoreLock::lock_file(root, Datastore::PINS_FILE, true).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::WouldBlock {
+                return std::io::Error::new(std::io::ErrorKind::WouldBlock, "datastore is read by a view pinned to a savepoint");
+            }
+            return e;
+        });
+    }
 
+    fn lock_file(root : & str, name : & str, exclusive : bool) -> Result<DatastoreLock, std::io::Error> {
+        let path = std::fs::canonicalize(root)?.join(name).to_string_lossy().to_string();
+        let mut held = HELD_LOCKS.lock().unwrap();
+        if let Some(lock) = held.get_mut(& path) {
+            if exclusive && lock.exclusive == 0 {
+                if let Err(e) = DatastoreLock::flock(& lock.file, libc::LOCK_EX) {
+                    // converting the lock is not atomic, the shared lock may have been released already
+                    DatastoreLock::flock(& lock.file, libc::LOCK_SH)?;
+                    return Err(e);
+                }
+            }
+            if exclusive {
+                lock.exclusive += 1;
+            } else {
+                lock.shared += 1;
+            }
+        } else {
+            let file = match std::fs::OpenOptions::new().read(true).write(true).create(true).open(& path) {
+                Ok(file) => file,
+                Err(_) if ! exclusive => std::fs::File::open(& path)?,
+                Err(e) => return Err(e),
+            };
+            DatastoreLock::flock(& file, if exclusive { libc::LOCK_EX } else { libc::LOCK_SH })?;
+            held.insert(path.clone(), HeldLock{ file, exclusive : exclusive as usize, shared : (! exclusive) as usize });
+        }
+        return Ok(DatastoreLock{ path, exclusive });
+    }
+
+    fn flock(file : & std::fs::File, operation : libc::c_int) -> Result<(), std::io::Error> {
+        use std::os::unix::io::AsRawFd;
+        if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } != 0 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() == std::io::ErrorKind::WouldBlock {
+                return Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "datastore is locked by another process"));
+            }
+            return Err(e);
+        }
+        return Ok(());
+    }
+}
+
+impl Drop for DatastoreLock {
+    fn drop(& mut self) {
+        let mut held = HELD_LOCKS.lock().unwrap();
+        let lock = held.get_mut(& self.path).unwrap();
+        if self.exclusive {
+            lock.exclusive -= 1;
+        } else {
+            lock.shared -= 1;
+        }
+        if lock.exclusive == 0 && lock.shared == 0 {
+            // closing the file releases the lock
+            held.remove(& self.path);
+        } else if lock.exclusive == 0 && self.exclusive {
+            let _ = DatastoreLock::flock(& lock.file, libc::LOCK_SH);
+        }
+    }
+}
+
+/** An entry of the datastore's denylist. 
+ 
+    Denies either all projects whose clone urls match the url pattern (see `helpers::UrlPattern`), or a single project of given id. 
+ */
+#[derive(Clone, Debug)]
+pub (crate) struct DenylistEntry {
+    /** Either `url`, or `project`. 
+     */
+    pub (crate) kind : String,
+    pub (crate) value : String,
+    pub (crate) reason : String,
+    /** The url pattern of url entries, prepared once when the entry is created. 
+     */
+    pattern : Option<helpers::UrlPattern>,
+}
+
+impl DenylistEntry {
+    /** Creates new entry, returns None if the kind is unknown, or the value is invalid for the kind. 
+     */
+    pub (crate) fn new(kind : & str, value : & str, reason : & str) -> Option<DenylistEntry> {
+        match kind {
+            "url" if ! value.is_empty() => {},
+            "project" if value.parse::<u64>().is_ok() => {},
+            _ => return None,
+        }
+        let pattern = if kind == "url" { Some(helpers::UrlPattern::new(value)) } else { None };
+        return Some(DenylistEntry{ kind : kind.to_owned(), value : value.to_owned(), reason : reason.to_owned(), pattern });
+    }
+
+    fn matches(& self, id : Option<ProjectId>, url : & str) -> bool {
+        match self.kind.as_str() {
+            "url" => return self.pattern.as_ref().map(|x| x.matches(url)).unwrap_or(false),
+            _ => return id.map(|id| u64::from(id).to_string() == self.value).unwrap_or(false),
+        }
+    }
+}
+
+impl std::fmt::Display for DenylistEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.reason.is_empty() {
+            return write!(f, "{} {}", self.kind, self.value);
+        } else {
+            return write!(f, "{} {} ({})", self.kind, self.value, self.reason);
+        }
+    }
+}
+
+/** An update campaign, i.e. a named set of projects whose updates since given savepoint are tracked until all of them complete. 
+ 
+    The projects of the campaign are selected by the filter, which is either `all`, `substore=KIND`, `label=LABEL`, or `url=PATTERN` (see `helpers::UrlPattern`), from the projects that existed when the campaign started. 
+ */
+#[derive(Clone, Debug)]
+pub (crate) struct Campaign {
+    pub (crate) name : String,
+    pub (crate) filter : String,
+    /** Name of the savepoint the campaign started at. 
+     */
+    pub (crate) savepoint : String,
+    /** Time of the savepoint, project updates from this time on complete the projects. 
+     */
+    pub (crate) time : i64,
+    /** Number of projects when the campaign started, projects added later are not part of the campaign. 
+     */
+    pub (crate) projects : usize,
+}
+
+impl Campaign {
+    /** Creates new campaign, returns None if the name is not a single word, or the filter is invalid. 
+     */
+    pub (crate) fn new(name : & str, filter : & str, savepoint : & str, time : i64, projects : usize) -> Option<Campaign> {
+        if name.is_empty() || name.contains(|x : char| x == ',' || x.is_whitespace()) || savepoint.contains(',') {
+            return None;
+        }
+        let (kind, value) = filter.split_once("=").unwrap_or((filter, ""));
+        match kind {
+            "all" if value.is_empty() => {},
+            "substore" if StoreKind::from_string(value).is_some() => {},
+            "label" | "url" if ! value.is_empty() => {},
+            _ => return None,
+        }
+        return Some(Campaign{ name : name.to_owned(), filter : filter.to_owned(), savepoint : savepoint.to_owned(), time, projects });
+    }
+
+    fn filter_kind(& self) -> & str {
+        return self.filter.split_once("=").map(|(kind, _)| kind).unwrap_or(& self.filter);
+    }
+
+    fn filter_value(& self) -> & str {
+        return self.filter.split_once("=").map(|(_, value)| value).unwrap_or("");
+    }
+}
+
+impl std::fmt::Display for Campaign {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(f, "{} ({}, savepoint {} at {})", self.name, self.filter, self.savepoint, helpers::pretty_timestamp(self.time));
+    }
+}
+
+/** Progress of a single project of an update campaign. 
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub (crate) enum CampaignProgress {
+    /** The project has not been updated since the campaign started, or its update is being retried. 
+     */
+    Pending,
+    Updated,
+    Unchanged,
+    /** The update failed, or the project was deleted, denied, or skipped. 
+     */
+    Failed,
+}
+
+impl CampaignProgress {
+    /** Returns the progress of a project whose update ended with given record, or None if the record is not an outcome of an update. 
+     */
+    fn from(log : & ProjectLog) -> Option<CampaignProgress> {
+        match log {
+            ProjectLog::Ok{time : _, version : _, changes : _} => return Some(CampaignProgress::Updated),
+            ProjectLog::NoChange{time : _, version : _} => return Some(CampaignProgress::Unchanged),
+            ProjectLog::Retry{time : _, version : _, error : _, attempt : _} => return Some(CampaignProgress::Pending),
+            ProjectLog::Error{time : _, version : _, kind : _, error : _} |
+            ProjectLog::Deleted{time : _, version : _, reason : _} |
+            ProjectLog::Denied{time : _, version : _, reason : _} |
+            ProjectLog::Skipped{time : _, version : _, reason : _} => return Some(CampaignProgress::Failed),
+            // renames, substore changes, path filters and merges are recorded alongside updates, or outside of them, they are not updates
+            ProjectLog::Rename{time : _, version : _, old_offset : _} |
+            ProjectLog::ChangeStore{time : _, version : _, new_kind : _} |
+            ProjectLog::PathFilters{time : _, version : _, filters : _} |
+            ProjectLog::Merged{time : _, version : _, source : _, old_id : _} => return None,
+        }
+    }
+}
+
+/** An unfinished pass of substore updates. 
+ 
+    Passes that go through multiple substores remember the substore they are updating, so that a restarted pass continues with that substore. Projects whose latest update is newer than the start of the pass are not updated again by it. 
+ */
+#[derive(Clone, Debug)]
+pub (crate) struct UpdatePass {
+    /** Describes the pass, i.e. its mode and filter, so that only the same pass is continued. 
+     */
+    pub (crate) name : String,
+    pub (crate) start : i64,
+    pub (crate) substore : StoreKind,
+    /** The mode and filter of the substore update the pass continues with, where the maximum number of projects is what remains of it at the substore reached. 
+     */
+    pub (crate) mode : updater::UpdateMode,
+    pub (crate) filter : updater::UpdateFilter,
+}
+
+/** Contains information about a selected subset of projetcs from the datastore. 
+ 
+ */
+pub (crate) struct Substore {
+
+    /** Root folder where the dataset is located. 
+     */
+    root : String,
+    /** The prefix of the dataset. 
+     */
+    pub (crate) prefix : StoreKind,
+
+    /** Determines whether the substore's mappings are loaded in memory and therefore new items can be added to it. 
+     
+        Atomic flag and mutex for actually loading and clearing the substore.
+     */
+    loaded : AtomicBool,
+    load_mutex : Mutex<()>,
+
+    /** Determines whether the substore exceeds its disk quota, as last checked by the updater. 
+     */
+    over_quota : AtomicBool,
+
+    /** Commits stored in the dataset. 
+     */
+    pub (crate) commits : Mutex<Mapping<SHA, CommitId>>,
+    pub (crate) commits_info : Mutex<Store<CommitInfo, CommitId>>,
+    pub (crate) commits_metadata : Mutex<LinkedStore<Metadata, CommitId>>,
+    /** Numbers of added and deleted lines per changed path of the commits. 
+     
+        Optional, only stored when line stats are enabled in the settings. The table is parallel to the commits info, which is left unchanged so that commits analyzed without line stats stay valid. 
+     */
+    pub (crate) commits_line_stats : Mutex<Store<LineStats, CommitId>>,
+    /** Submodules changed by the commits. 
+     
+        Only commits that change any gitlinks have a record. Commits stored before submodules were detected have none and their gitlinks may appear among their changes. 
+     */
+    pub (crate) commits_submodules : Mutex<Store<CommitSubmodules, CommitId>>,
+    /** Root trees of the commits. 
+     
+        Optional, only stored when trees are enabled in the settings. Commits analyzed without trees have no record. 
+     */
+    pub (crate) commits_trees : Mutex<Store<TreeId, CommitId>>,
+
+    /** Trees. 
+     
+        Trees are mapped by their git hashes so that each tree is stored only once, the entries of the trees are stored in the trees info. Tree ids are created before their entries are stored, so a tree id without info belongs to a tree whose storing has been interrupted. 
+     */
+    pub (crate) trees : Mutex<Mapping<SHA, TreeId>>,
+    pub (crate) trees_info : Mutex<Store<Tree, TreeId>>,
+
+    /** File hashes and their contents. 
+     
+        Every time a commit a file is changed, the hash of its contents is added to the hashes mapping. Some of these files may then have their contents stored and some won't.  
+
+        We use a split store based on the ContentsKind of the file to be stored. The index is kept for *all* file hashes, i.e. there will be a lot of empty holes in it, but these holes will be relatively cheap (20 bytes per hole, 10 bytes for contents and 10 bytes for metadata) and on disk, where it bothers us less. 
+     */
+    pub (crate) hashes : Mutex<Mapping<SHA, HashId>>,
+    pub (crate) contents : Mutex<SplitStore<FileContents, ContentsKind, HashId>>,
+    pub (crate) contents_metadata : Mutex<LinkedStore<Metadata, HashId>>,
+    /** Delta encoded contents. 
+     
+        When enabled, contents that are only small modifications of the previous version of the same path are stored as deltas against that version instead of in the contents split store. Readers should use the `ContentsReader` from `table_readers` which reconstructs the deltas transparently. 
+     */
+    pub (crate) contents_deltas : Mutex<Store<ContentsDelta, HashId>>,
+
+    /** Paths. 
+     
+        Path hash to ids is stored in a mapping at runtime, while path strings are stored separately in an indexable store on disk. 
+     */
+    pub (crate) paths : Mutex<Mapping<SHA, PathId>>,
+    pub (crate) path_strings : Mutex<Store<PathString, PathId>>,
+    /** Commits that changed each path. 
+     
+        Whenever commit information is stored, the commit is appended to the list of every path it changes, so that commits touching given paths can be found without scanning all commits. Commits stored before the index was introduced are not included. 
+     */
+    pub (crate) path_commits : Mutex<LinkedStore<CommitId, PathId>>,
+    /** Files renamed by each commit. 
+     
+        Only commits that rename any files have a record. Commits stored before renames were detected have none. 
+     */
+    pub (crate) path_renames : Mutex<Store<PathRenames, CommitId>>,
+
+    /** Users.
+     
+        Users are mapped by their email. 
+     */
+    pub (crate) users : Mutex<IndirectMapping<String, UserId>>,
+    pub (crate) users_metadata : Mutex<LinkedStore<Metadata, UserId>>,
+    /** Canonical ids of users that are believed to be the same person as another user. 
+     
+        Built by the `resolve-users` task, users without a record, or with a record of their own id, are canonical. 
+     */
+    pub (crate) user_aliases : Mutex<Store<UserId, UserId>>,
+
+    /** Issues and pull requests. 
+     
+        Keyed by the project id, every update of an issue is appended to the project's list. 
+     */
+    pub (crate) issues : Mutex<LinkedStore<Issue, ProjectId>>,
+    /** Commits of the projects ordered by their time. 
+     
+        Keyed by the project id, these are the full indices built by earlier versions whenever the project heads changed. They are no longer written, the commits added since are appended to the project new commit times instead. 
+     */
+    pub (crate) project_commit_times : Mutex<Store<ProjectCommitTimes, ProjectId>>,
+    /** Commits added to the projects ordered by their time. 
+     
+        Keyed by the project id, whenever the project heads change only the commits not yet in the project's index are appended as a new record, so that the index of a project is the union of its project commit times record and all its records here. Commits removed from a project by a force push are not removed from the index. Projects not updated since the index was introduced have no record. 
+     */
+    pub (crate) project_new_commit_times : Mutex<LinkedStore<ProjectCommitTimes, ProjectId>>,
+    /** Projects containing each commit. 
+     
+        Optional, built incrementally by the `index-commit-projects` task. The projects are appended to the lists of the commits found since the previous pass and the heads the projects have been indexed at are kept in the commit projects heads, so that the next pass only walks the new commits. Commits removed from a project by a force push are not removed from the index. 
+     */
+    pub (crate) commit_projects : Mutex<LinkedStore<CommitProjects, CommitId>>,
+    pub (crate) commit_projects_heads : Mutex<Store<ProjectHeads, ProjectId>>,
+    /** Commits and paths at which each file contents hash occurs. 
+     
+        Optional, built incrementally by the `index-files` task from the changes of the commits. The only record of the hash occurrences progress, with id 0, holds the id of the first commit not yet indexed, followed by the ids of commits that had no information stored when they were indexed. 
+     */
+    pub (crate) hash_occurrences : Mutex<LinkedStore<HashOccurrences, HashId>>,
+    pub (crate) hash_occurrences_progress : Mutex<Store<MessagePostings, u64>>,
+    /** Structural metrics of the commits. 
+     
+        Optional, built incrementally by the `commit-metrics` task. The metrics of all commits of a project are recalculated whenever its heads differ from the heads the metrics were last calculated at, which are kept in the commit metrics heads. A new record is only stored when the metrics of a commit change. 
+     */
+    pub (crate) commit_metrics : Mutex<Store<CommitMetrics, CommitId>>,
+    pub (crate) commit_metrics_heads : Mutex<Store<ProjectHeads, ProjectId>>,
+
+    /** Inverted index of commit messages. 
+     
+        Optional, built incrementally by the `index-messages` task. Words are mapped to their ids and the postings of each word are the commits whose messages contain the word. The postings of the sentinel empty word hold the id of the first commit not yet indexed, followed by the ids of commits that had no information stored when they were indexed. 
+     */
+    pub (crate) message_words : Mutex<IndirectMapping<String, WordId>>,
+    pub (crate) message_postings : Mutex<LinkedStore<MessagePostings, WordId>>,
+
+    /** The global index of the substores of commits and contents hashes shared with the datastore. 
+     */
+    sha_substores : Arc<Mutex<Option<BitmapIndex<SHA>>>>,
+
+    /** Commits and hashes added to the substore that are not yet written to the global hash index, with their bits. 
+     */
+    sha_pending : Mutex<HashMap<SHA, u64>>,
+
+    /** The key of the user email hashes if the emails are anonymized, shared with the datastore. 
+     */
+    email_key : Option<Vec<u8>>,
+}
+
+/** Writes the hashes buffered by the substore to the global hash index, so that the index is complete when the datastore is closed. 
+ */
+impl Drop for Substore {
+    fn drop(& mut self) {
+        if let Err(e) = self.flush_sha_index() {
+            LOG!("Failed to write hashes of substore {:?} to the global hash index: {}", self.prefix, e);
+        }
+    }
 }
 
 impl Substore {
 
+    /** The substore tables in the order they are verified and repaired. 
+     */
+    pub (crate) const VERIFIED_TABLES : [VerifiedTableInfo; 30] = [
+        VerifiedTableInfo{ name : "commits", table : |x| & x.commits },
+        VerifiedTableInfo{ name : "commits info", table : |x| & x.commits_info },
+        VerifiedTableInfo{ name : "commits metadata", table : |x| & x.commits_metadata },
+        VerifiedTableInfo{ name : "commits line stats", table : |x| & x.commits_line_stats },
+        VerifiedTableInfo{ name : "hashes", table : |x| & x.hashes },
+        VerifiedTableInfo{ name : "contents", table : |x| & x.contents },
+        VerifiedTableInfo{ name : "contents metadata", table : |x| & x.contents_metadata },
+        VerifiedTableInfo{ name : "contents deltas", table : |x| & x.contents_deltas },
+        VerifiedTableInfo{ name : "paths", table : |x| & x.paths },
+        VerifiedTableInfo{ name : "path strings", table : |x| & x.path_strings },
+        VerifiedTableInfo{ name : "path commits", table : |x| & x.path_commits },
+        VerifiedTableInfo{ name : "path renames", table : |x| & x.path_renames },
+        VerifiedTableInfo{ name : "users", table : |x| & x.users },
+        VerifiedTableInfo{ name : "users metadata", table : |x| & x.users_metadata },
+        VerifiedTableInfo{ name : "user aliases", table : |x| & x.user_aliases },
+        VerifiedTableInfo{ name : "issues", table : |x| & x.issues },
+        VerifiedTableInfo{ name : "message words", table : |x| & x.message_words },
+        VerifiedTableInfo{ name : "message postings", table : |x| & x.message_postings },
+        VerifiedTableInfo{ name : "commits submodules", table : |x| & x.commits_submodules },
+        VerifiedTableInfo{ name : "trees", table : |x| & x.trees },
+        VerifiedTableInfo{ name : "trees info", table : |x| & x.trees_info },
+        VerifiedTableInfo{ name : "commits trees", table : |x| & x.commits_trees },
+        VerifiedTableInfo{ name : "project commit times", table : |x| & x.project_commit_times },
+        VerifiedTableInfo{ name : "commit projects", table : |x| & x.commit_projects },
+        VerifiedTableInfo{ name : "commit projects heads", table : |x| & x.commit_projects_heads },
+        VerifiedTableInfo{ name : "hash occurrences", table : |x| & x.hash_occurrences },
+        VerifiedTableInfo{ name : "commit metrics", table : |x| & x.commit_metrics },
+        VerifiedTableInfo{ name : "commit metrics heads", table : |x| & x.commit_metrics_heads },
+        VerifiedTableInfo{ name : "project new commit times", table : |x| & x.project_new_commit_times },
+        VerifiedTableInfo{ name : "hash occurrences progress", table : |x| & x.hash_occurrences_progress },
+    ];
+
+    /** Indices of the tables in `VERIFIED_TABLES`. 
+     */
+    pub (crate) const VERIFY_COMMITS : usize = 0;
+    pub (crate) const VERIFY_COMMITS_INFO : usize = 1;
+    pub (crate) const VERIFY_COMMITS_METADATA : usize = 2;
+    pub (crate) const VERIFY_COMMITS_LINE_STATS : usize = 3;
+    pub (crate) const VERIFY_HASHES : usize = 4;
+    pub (crate) const VERIFY_CONTENTS : usize = 5;
+    pub (crate) const VERIFY_CONTENTS_METADATA : usize = 6;
+    pub (crate) const VERIFY_CONTENTS_DELTAS : usize = 7;
+    pub (crate) const VERIFY_PATHS : usize = 8;
+    pub (crate) const VERIFY_PATH_STRINGS : usize = 9;
+    pub (crate) const VERIFY_PATH_COMMITS : usize = 10;
+    pub (crate) const VERIFY_PATH_RENAMES : usize = 11;
+    pub (crate) const VERIFY_USERS : usize = 12;
+    pub (crate) const VERIFY_USERS_METADATA : usize = 13;
+    pub (crate) const VERIFY_USER_ALIASES : usize = 14;
+    pub (crate) const VERIFY_ISSUES : usize = 15;
+    pub (crate) const VERIFY_MESSAGE_WORDS : usize = 16;
+    pub (crate) const VERIFY_MESSAGE_POSTINGS : usize = 17;
+    pub (crate) const VERIFY_COMMITS_SUBMODULES : usize = 18;
+    pub (crate) const VERIFY_TREES : usize = 19;
+    pub (crate) const VERIFY_TREES_INFO : usize = 20;
+    pub (crate) const VERIFY_COMMITS_TREES : usize = 21;
+    pub (crate) const VERIFY_PROJECT_COMMIT_TIMES : usize = 22;
+    pub (crate) const VERIFY_COMMIT_PROJECTS : usize = 23;
+    pub (crate) const VERIFY_COMMIT_PROJECTS_HEADS : usize = 24;
+    pub (crate) const VERIFY_HASH_OCCURRENCES : usize = 25;
+    pub (crate) const VERIFY_COMMIT_METRICS : usize = 26;
+    pub (crate) const VERIFY_COMMIT_METRICS_HEADS : usize = 27;
+    pub (crate) const VERIFY_PROJECT_NEW_COMMIT_TIMES : usize = 28;
+    pub (crate) const VERIFY_HASH_OCCURRENCES_PROGRESS : usize = 29;
+
+    /** Minimal size of the chunks of the tables that are verified in parallel. 
+     */
+    pub (crate) const VERIFY_CHUNK_SIZE : u64 = 64 * 1024 * 1024;
+
+    /** Number of new commits and hashes a substore buffers before writing them to the global hash index. 
+     */
+    pub (crate) const SHA_INDEX_BATCH : usize = 4096;
+
+    /** Approximate memory taken by a single loaded value of the commits, hashes and paths mappings, and of the users mapping, including the overhead of the hashmaps. 
+     */
+    pub (crate) const SHA_ENTRY_MEMORY : u64 = 56;
+    pub (crate) const USER_ENTRY_MEMORY : u64 = 96;
+
     pub (crate) const COMMITS : &'static str = "commits";
     pub (crate) const COMMITS_INFO : &'static str = "commits-info";
     pub (crate) const COMMITS_METADATA : &'static str = "commits-metadata";
+    pub (crate) const COMMITS_LINE_STATS : &'static str = "commits-line-stats";
+    pub (crate) const COMMITS_SUBMODULES : &'static str = "commits-submodules";
+    pub (crate) const COMMITS_TREES : &'static str = "commits-trees";
+    pub (crate) const TREES : &'static str = "trees";
+    pub (crate) const TREES_INFO : &'static str = "trees-info";
     pub (crate) const HASHES : &'static str = "hashes";
     pub (crate) const CONTENTS : &'static str = "contents";
     pub (crate) const CONTENTS_METADATA : &'static str = "contents-metadata";
+    pub (crate) const CONTENTS_DELTAS : &'static str = "contents-deltas";
     pub (crate) const PATHS : &'static str = "paths";
     pub (crate) const PATHS_STRINGS : &'static str = "path-strings";
+    pub (crate) const PATH_COMMITS : &'static str = "path-commits";
+    pub (crate) const PATH_RENAMES : &'static str = "path-renames";
     pub (crate) const USERS : &'static str = "users";
     pub (crate) const USERS_METADATA : &'static str = "users-metadata";
+    pub (crate) const USER_ALIASES : &'static str = "user-aliases";
+    pub (crate) const ISSUES : &'static str = "issues";
+    pub (crate) const PROJECT_COMMIT_TIMES : &'static str = "project-commit-times";
+    pub (crate) const PROJECT_NEW_COMMIT_TIMES : &'static str = "project-new-commit-times";
+    pub (crate) const COMMIT_PROJECTS : &'static str = "commit-projects";
+    pub (crate) const COMMIT_PROJECTS_HEADS : &'static str = "commit-projects-heads";
+    pub (crate) const HASH_OCCURRENCES : &'static str = "hash-occurrences";
+    pub (crate) const HASH_OCCURRENCES_PROGRESS : &'static str = "hash-occurrences-progress";
+    pub (crate) const COMMIT_METRICS : &'static str = "commit-metrics";
+    pub (crate) const COMMIT_METRICS_HEADS : &'static str = "commit-metrics-heads";
+    pub (crate) const MESSAGE_WORDS : &'static str = "message-words";
+    pub (crate) const MESSAGE_POSTINGS : &'static str = "message-postings";
+
+    /** Manifest of the renames of a committed, but not yet finished contents collection. 
+     */
+    const CONTENTS_GC_MANIFEST : &'static str = "contents.gc-manifest";
+    /** The last line of a complete contents collection manifest. 
+     */
+    const CONTENTS_GC_MANIFEST_END : &'static str = "end\n";
+    /** The time of the last contents collection. 
+     */
+    const CONTENTS_GC_TIME : &'static str = "contents.gc-time";
+
+    /** Number of commits indexed before the postings are stored when building the commit messages index. 
+     */
+    pub (crate) const MESSAGE_INDEX_BATCH : usize = 100000;
+
+    /** Number of new commits found in the projects before they are stored when building the commit projects index. 
+     */
+    pub (crate) const COMMIT_PROJECTS_BATCH : usize = 1000000;
+
+    /** Number of commits indexed before the occurrences are stored when building the hash occurrences index. 
+     */
+    pub (crate) const HASH_OCCURRENCES_BATCH : usize = 100000;
     
 
-    pub fn new(root_path : & Path, kind : StoreKind, readonly : bool) -> Substore {
+    pub fn new(root_path : & Path, kind : StoreKind, readonly : bool, sha_substores : & Arc<Mutex<Option<BitmapIndex<SHA>>>>, email_key : Option<Vec<u8>>) -> Substore {
         //if the path root path does not exist, create it
         if ! root_path.exists() {
             std::fs::create_dir_all(root_path).unwrap();
@@ -514,32 +2127,63 @@ impl Substore {
         // and create the store
         let root = root_path.to_str().unwrap();
         LOG!("** Loading substore {:?}", kind);
+        if ! readonly {
+            Substore::finish_contents_collection(root).expect("Unable to finish interrupted contents collection");
+        }
         let result = Substore{
             root : root.to_owned(),
             prefix : kind,
             loaded : AtomicBool::new(false),
             load_mutex : Mutex::new(()), 
+            over_quota : AtomicBool::new(false),
 
             commits : Mutex::new(Mapping::new(root, & format!("{:?}-{}", kind, Substore::COMMITS), readonly)),
             commits_info : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::COMMITS_INFO), readonly)),
             commits_metadata : Mutex::new(LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::COMMITS_METADATA), readonly)),
+            commits_line_stats : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::COMMITS_LINE_STATS), readonly)),
+            commits_submodules : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::COMMITS_SUBMODULES), readonly)),
+            commits_trees : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::COMMITS_TREES), readonly)),
+
+            trees : Mutex::new(Mapping::new(root, & format!("{:?}-{}", kind, Substore::TREES), readonly)),
+            trees_info : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::TREES_INFO), readonly)),
 
             hashes : Mutex::new(Mapping::new(root, & format!("{:?}-{}", kind, Substore::HASHES), readonly)),
             contents : Mutex::new(SplitStore::new(root, & format!("{:?}-{}", kind, Substore::CONTENTS), readonly)),
             contents_metadata : Mutex::new(LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::CONTENTS_METADATA), readonly)),
+            contents_deltas : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::CONTENTS_DELTAS), readonly)),
 
             paths : Mutex::new(Mapping::new(root, & format!("{:?}-{}", kind, Substore::PATHS), readonly)),
             path_strings : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::PATHS_STRINGS), readonly)),
+            path_commits : Mutex::new(LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::PATH_COMMITS), readonly)),
+            path_renames : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::PATH_RENAMES), readonly)),
 
             users : Mutex::new(IndirectMapping::new(root, & format!("{:?}-{}", kind, Substore::USERS), readonly)),
             users_metadata : Mutex::new(LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::USERS_METADATA), readonly)),
-
+            user_aliases : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::USER_ALIASES), readonly)),
+
+            issues : Mutex::new(LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::ISSUES), readonly)),
+            project_commit_times : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::PROJECT_COMMIT_TIMES), readonly)),
+            project_new_commit_times : Mutex::new(LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::PROJECT_NEW_COMMIT_TIMES), readonly)),
+            commit_projects : Mutex::new(LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::COMMIT_PROJECTS), readonly)),
+            commit_projects_heads : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::COMMIT_PROJECTS_HEADS), readonly)),
+            hash_occurrences : Mutex::new(LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::HASH_OCCURRENCES), readonly)),
+            hash_occurrences_progress : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::HASH_OCCURRENCES_PROGRESS), readonly)),
+            commit_metrics : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::COMMIT_METRICS), readonly)),
+            commit_metrics_heads : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::COMMIT_METRICS_HEADS), readonly)),
+
+            message_words : Mutex::new(IndirectMapping::new(root, & format!("{:?}-{}", kind, Substore::MESSAGE_WORDS), readonly)),
+            message_postings : Mutex::new(LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::MESSAGE_POSTINGS), readonly)),
+
+            sha_substores : sha_substores.clone(),
+            sha_pending : Mutex::new(HashMap::new()),
+            email_key,
         };
         // add sentinels (0 index values) for commits, hashes, paths and users
         if !readonly && result.commits.lock().unwrap().len() == 0 {
-            result.get_or_create_commit_id(& SHA::zero());
-            result.get_or_create_hash_id(& SHA::zero());
-            result.get_or_create_path_id(& "".to_owned());
+            // the mappings are not loaded yet, so nothing can be merged into their sorted indices
+            result.get_or_create_commit_id(& SHA::zero()).unwrap();
+            result.get_or_create_hash_id(& SHA::zero()).unwrap();
+            result.get_or_create_path_id(& "".to_owned()).unwrap();
             result.get_or_create_user_id(& "".to_owned());
         }
         return result;
@@ -549,61 +2193,109 @@ impl Substore {
         self.commits.lock().unwrap().savepoint(savepoint);
         self.commits_info.lock().unwrap().savepoint(savepoint);
         self.commits_metadata.lock().unwrap().savepoint(savepoint);
+        self.commits_line_stats.lock().unwrap().savepoint(savepoint);
+        self.commits_submodules.lock().unwrap().savepoint(savepoint);
+        self.commits_trees.lock().unwrap().savepoint(savepoint);
+        self.trees.lock().unwrap().savepoint(savepoint);
+        self.trees_info.lock().unwrap().savepoint(savepoint);
         self.hashes.lock().unwrap().savepoint(savepoint);
         self.contents.lock().unwrap().savepoint(savepoint);
         self.contents_metadata.lock().unwrap().savepoint(savepoint);
+        self.contents_deltas.lock().unwrap().savepoint(savepoint);
         self.paths.lock().unwrap().savepoint(savepoint);
         self.path_strings.lock().unwrap().savepoint(savepoint);
+        self.path_commits.lock().unwrap().savepoint(savepoint);
+        self.path_renames.lock().unwrap().savepoint(savepoint);
         self.users.lock().unwrap().savepoint(savepoint);
         self.users_metadata.lock().unwrap().savepoint(savepoint);
+        self.user_aliases.lock().unwrap().savepoint(savepoint);
+        self.issues.lock().unwrap().savepoint(savepoint);
+        self.project_commit_times.lock().unwrap().savepoint(savepoint);
+        self.project_new_commit_times.lock().unwrap().savepoint(savepoint);
+        self.commit_projects.lock().unwrap().savepoint(savepoint);
+        self.commit_projects_heads.lock().unwrap().savepoint(savepoint);
+        self.hash_occurrences.lock().unwrap().savepoint(savepoint);
+        self.hash_occurrences_progress.lock().unwrap().savepoint(savepoint);
+        self.commit_metrics.lock().unwrap().savepoint(savepoint);
+        self.commit_metrics_heads.lock().unwrap().savepoint(savepoint);
+        self.message_words.lock().unwrap().savepoint(savepoint);
+        self.message_postings.lock().unwrap().savepoint(savepoint);
     }
 
-    fn revert_to_savepoint(& self, savepoint : & Savepoint) {
-        self.commits.lock().unwrap().revert_to_savepoint(savepoint);
+    fn revert_to_savepoint(& self, savepoint : & Savepoint) -> Result<(), std::io::Error> {
+        self.commits.lock().unwrap().revert_to_savepoint(savepoint)?;
         self.commits_info.lock().unwrap().revert_to_savepoint(savepoint);
         self.commits_metadata.lock().unwrap().revert_to_savepoint(savepoint);
-        self.hashes.lock().unwrap().revert_to_savepoint(savepoint);
+        self.commits_line_stats.lock().unwrap().revert_to_savepoint(savepoint);
+        self.commits_submodules.lock().unwrap().revert_to_savepoint(savepoint);
+        self.commits_trees.lock().unwrap().revert_to_savepoint(savepoint);
+        self.trees.lock().unwrap().revert_to_savepoint(savepoint)?;
+        self.trees_info.lock().unwrap().revert_to_savepoint(savepoint);
+        self.hashes.lock().unwrap().revert_to_savepoint(savepoint)?;
         self.contents.lock().unwrap().revert_to_savepoint(savepoint);
         self.contents_metadata.lock().unwrap().revert_to_savepoint(savepoint);
-        self.paths.lock().unwrap().revert_to_savepoint(savepoint);
+        self.contents_deltas.lock().unwrap().revert_to_savepoint(savepoint);
+        self.paths.lock().unwrap().revert_to_savepoint(savepoint)?;
         self.path_strings.lock().unwrap().revert_to_savepoint(savepoint);
+        self.path_commits.lock().unwrap().revert_to_savepoint(savepoint);
+        self.path_renames.lock().unwrap().revert_to_savepoint(savepoint);
         self.users.lock().unwrap().revert_to_savepoint(savepoint);
         self.users_metadata.lock().unwrap().revert_to_savepoint(savepoint);
+        self.user_aliases.lock().unwrap().revert_to_savepoint(savepoint);
+        self.issues.lock().unwrap().revert_to_savepoint(savepoint);
+        self.project_commit_times.lock().unwrap().revert_to_savepoint(savepoint);
+        self.project_new_commit_times.lock().unwrap().revert_to_savepoint(savepoint);
+        self.commit_projects.lock().unwrap().revert_to_savepoint(savepoint);
+        self.commit_projects_heads.lock().unwrap().revert_to_savepoint(savepoint);
+        self.hash_occurrences.lock().unwrap().revert_to_savepoint(savepoint);
+        self.hash_occurrences_progress.lock().unwrap().revert_to_savepoint(savepoint);
+        self.commit_metrics.lock().unwrap().revert_to_savepoint(savepoint);
+        self.commit_metrics_heads.lock().unwrap().revert_to_savepoint(savepoint);
+        self.message_words.lock().unwrap().revert_to_savepoint(savepoint);
+        self.message_postings.lock().unwrap().revert_to_savepoint(savepoint);
+        return Ok(());
     }
 
-    pub (crate) fn load(& self, task : & updater::TaskStatus) {
+    pub (crate) fn load(& self, task : & updater::TaskStatus) -> Result<(), std::io::Error> {
         task.info("Acquiring substore lock...");
-        task.progress(0, 4);
+        task.progress(0, 5);
         let mut _x = self.load_mutex.lock().unwrap();
         if self.loaded.load(Ordering::SeqCst) == false {
             task.info("Loading...");
-            self.commits.lock().unwrap().load();
-            task.progress(1, 4);
-            self.hashes.lock().unwrap().load();
-            task.progress(2, 4);
-            self.paths.lock().unwrap().load();
-            task.progress(3, 4);
+            // the memory budget is split evenly between the four largest mappings
+            let budget = SETTINGS.mapping_memory.map(|x| x / 4);
+            self.commits.lock().unwrap().load_with(SETTINGS.bloom_mappings, budget)?;
+            task.progress(1, 5);
+            self.hashes.lock().unwrap().load_with(SETTINGS.bloom_mappings, budget)?;
+            task.progress(2, 5);
+            self.paths.lock().unwrap().load_with(SETTINGS.bloom_mappings, budget)?;
+            task.progress(3, 5);
+            self.trees.lock().unwrap().load_with(SETTINGS.bloom_mappings, budget)?;
+            task.progress(4, 5);
             self.users.lock().unwrap().load();
-            task.progress(4, 4);
+            task.progress(5, 5);
             self.loaded.store(true, Ordering::SeqCst);
         }
+        return Ok(());
     }
 
     pub (crate) fn clear(& self, task : & updater::TaskStatus) {
         task.info("Acquiring substore lock...");
-        task.progress(0, 4);
+        task.progress(0, 5);
         let mut _x = self.load_mutex.lock().unwrap();
         if self.loaded.load(Ordering::SeqCst) == true {
             self.loaded.store(false, Ordering::SeqCst);
             task.info("Clearing...");
             self.commits.lock().unwrap().clear();
-            task.progress(1, 4);
+            task.progress(1, 5);
             self.hashes.lock().unwrap().clear();
-            task.progress(2, 4);
+            task.progress(2, 5);
             self.paths.lock().unwrap().clear();
-            task.progress(3, 4);
+            task.progress(3, 5);
+            self.trees.lock().unwrap().clear();
+            task.progress(4, 5);
             self.users.lock().unwrap().clear();
-            task.progress(4, 4);
+            task.progress(5, 5);
         }
     }
 
@@ -611,103 +2303,211 @@ impl Substore {
         return self.loaded.load(Ordering::SeqCst);
     }
 
+    /** Returns the total size of the substore's files on disk. 
+     */
+    pub (crate) fn disk_size(& self) -> u64 {
+        let mut result = 0;
+        if let Ok(entries) = std::fs::read_dir(& self.root) {
+            for entry in entries.flatten() {
+                if let Ok(metadata) = entry.metadata() {
+                    if metadata.is_file() {
+                        result += metadata.len();
+                    }
+                }
+            }
+        }
+        return result;
+    }
+
+    pub (crate) fn is_over_quota(& self) -> bool {
+        return self.over_quota.load(Ordering::SeqCst);
+    }
+
+    pub (crate) fn set_over_quota(& self, value : bool) {
+        self.over_quota.store(value, Ordering::SeqCst);
+    }
+
+    /** Verifies all tables of the substore one by one and their consistency. 
+     
+        Returns the number of verified items. The updater verifies the tables in parallel instead, see `task_verify_substore`. 
+     */
     pub (crate) fn verify(& self, task : & updater::TaskStatus) -> Result<usize, std::io::Error> {
-        self.load(task);
-        let mut progress = 0;
-        let max_progress = 10;
-        task.progress(progress, max_progress);
+        self.load(task)?;
+        let max_progress = Substore::VERIFIED_TABLES.len() + 1;
+        task.progress(0, max_progress);
         let mut items = 0;
-        self.commits.lock().unwrap().verify(& mut |_|{
-            items += 1;
-            if items % 1000 == 0 {
-                task.info(format!("{} items, checking commits...", helpers::pretty_value(items)));
+        for table in 0..Substore::VERIFIED_TABLES.len() {
+            let mut verified = Vec::new();
+            for chunk in self.split_for_verification(table, 1) {
+                let (chunk_items, x) = self.verify_chunk(table, & chunk, items, task)?;
+                items += chunk_items;
+                verified.push(x);
             }
-            return Ok(());
-        })?;
-        progress += 1;
-        task.progress(progress, max_progress);
-        self.commits_info.lock().unwrap().verify(& mut |_|{
+            self.verify_chunks_index(table, verified)?;
+            task.progress(table + 1, max_progress);
+        }
+        self.verify_consistency()?;
+        task.progress(max_progress, max_progress);
+        return Ok(items);
+    }
+
+    /** Splits given table into chunks that can be verified in parallel. 
+     
+        Only stores can be split, mappings and the contents split store are verified as a whole. 
+     */
+    pub (crate) fn split_for_verification(& self, table : usize, max_chunks : usize) -> Vec<VerifyChunk> {
+        return (Substore::VERIFIED_TABLES[table].table)(self).split_for_verification(max_chunks, Substore::VERIFY_CHUNK_SIZE);
+    }
+
+    /** Verifies a single chunk of given table. 
+     
+        Chunks of stores are verified without locking the store. Returns the number of items verified and the records found in the chunk. The items already verified are only used for reporting. 
+     */
+    pub (crate) fn verify_chunk(& self, table : usize, chunk : & VerifyChunk, verified_items : usize, task : & updater::TaskStatus) -> Result<(usize, VerifiedChunk), std::io::Error> {
+        let mut items = 0;
+        let mut count = || {
             items += 1;
             if items % 1000 == 0 {
-                task.info(format!("{} items, checking commits info...", helpers::pretty_value(items)));
+                task.info(format!("{} items, checking {}...", helpers::pretty_value(verified_items + items), Substore::VERIFIED_TABLES[table].name));
             }
             return Ok(());
-        })?;
-        progress += 1;
-        task.progress(progress, max_progress);
-        self.commits_metadata.lock().unwrap().verify(& mut |_|{
-            items += 1;
-            if items % 1000 == 0 {
-                task.info(format!("{} items, checking commits metadata...", helpers::pretty_value(items)));
+        };
+        let result = (Substore::VERIFIED_TABLES[table].table)(self).verify_chunk(chunk, & mut count)?;
+        return Ok((items, result));
+    }
+
+    /** Checks the index of given table against the records found in all its verified chunks. 
+     */
+    pub (crate) fn verify_chunks_index(& self, table : usize, chunks : Vec<VerifiedChunk>) -> Result<(), std::io::Error> {
+        return (Substore::VERIFIED_TABLES[table].table)(self).verify_chunks_index(chunks);
+    }
+
+    /** Checks that the tables of the substore are consistent with each other. 
+     
+        Records keyed by commits, hashes, paths and users must not have ids that are not in the respective mappings. The lengths of the dependent tables are taken before the lengths of the mappings, so that ids added by a concurrent update in between are not reported as inconsistent. 
+     */
+    pub (crate) fn verify_consistency(& self) -> Result<(), std::io::Error> {
+        if let Some((table, ids, known, mapping)) = self.inconsistent_tables().first() {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Substore {:?}: {} has {} ids, but only {} {} known", self.prefix, Substore::VERIFIED_TABLES[*table].name, ids, known, Substore::VERIFIED_TABLES[*mapping].name)));
+        }
+        return Ok(());
+    }
+
+    /** Returns the tables that have more ids than the mappings they are keyed by, together with the number of their ids, the number of ids known to the mapping and the mapping. 
+     
+        See `verify_consistency`. 
+     */
+    fn inconsistent_tables(& self) -> Vec<(usize, usize, usize, usize)> {
+        let checks = [
+            (Substore::VERIFY_COMMITS_INFO, self.commits_info.lock().unwrap().len(), Substore::VERIFY_COMMITS),
+            (Substore::VERIFY_COMMITS_METADATA, self.commits_metadata.lock().unwrap().len(), Substore::VERIFY_COMMITS),
+            (Substore::VERIFY_COMMITS_LINE_STATS, self.commits_line_stats.lock().unwrap().len(), Substore::VERIFY_COMMITS),
+            (Substore::VERIFY_COMMITS_SUBMODULES, self.commits_submodules.lock().unwrap().len(), Substore::VERIFY_COMMITS),
+            (Substore::VERIFY_COMMITS_TREES, self.commits_trees.lock().unwrap().len(), Substore::VERIFY_COMMITS),
+            (Substore::VERIFY_TREES_INFO, self.trees_info.lock().unwrap().len(), Substore::VERIFY_TREES),
+            (Substore::VERIFY_PATH_RENAMES, self.path_renames.lock().unwrap().len(), Substore::VERIFY_COMMITS),
+            (Substore::VERIFY_CONTENTS, self.contents.lock().unwrap().len(), Substore::VERIFY_HASHES),
+            (Substore::VERIFY_CONTENTS_METADATA, self.contents_metadata.lock().unwrap().len(), Substore::VERIFY_HASHES),
+            (Substore::VERIFY_CONTENTS_DELTAS, self.contents_deltas.lock().unwrap().len(), Substore::VERIFY_HASHES),
+            (Substore::VERIFY_PATH_STRINGS, self.path_strings.lock().unwrap().len(), Substore::VERIFY_PATHS),
+            (Substore::VERIFY_PATH_COMMITS, self.path_commits.lock().unwrap().len(), Substore::VERIFY_PATHS),
+            (Substore::VERIFY_USERS_METADATA, self.users_metadata.lock().unwrap().len(), Substore::VERIFY_USERS),
+            (Substore::VERIFY_USER_ALIASES, self.user_aliases.lock().unwrap().len(), Substore::VERIFY_USERS),
+            (Substore::VERIFY_MESSAGE_POSTINGS, self.message_postings.lock().unwrap().len(), Substore::VERIFY_MESSAGE_WORDS),
+            (Substore::VERIFY_COMMIT_PROJECTS, self.commit_projects.lock().unwrap().len(), Substore::VERIFY_COMMITS),
+            (Substore::VERIFY_HASH_OCCURRENCES, self.hash_occurrences.lock().unwrap().len(), Substore::VERIFY_HASHES),
+            (Substore::VERIFY_COMMIT_METRICS, self.commit_metrics.lock().unwrap().len(), Substore::VERIFY_COMMITS),
+        ];
+        // the mappings only grow, so their lengths taken after the dependent tables cover all ids seen in them
+        let commits = self.commits.lock().unwrap().len();
+        let hashes = self.hashes.lock().unwrap().len();
+        let paths = self.paths.lock().unwrap().len();
+        let users = self.users.lock().unwrap().len();
+        let words = self.message_words.lock().unwrap().len();
+        let trees = self.trees.lock().unwrap().len();
+        let mut result = Vec::new();
+        for (table, ids, mapping) in checks.iter() {
+            let known = match *mapping {
+                Substore::VERIFY_COMMITS => commits,
+                Substore::VERIFY_HASHES => hashes,
+                Substore::VERIFY_PATHS => paths,
+                Substore::VERIFY_USERS => users,
+                Substore::VERIFY_MESSAGE_WORDS => words,
+                Substore::VERIFY_TREES => trees,
+                _ => unreachable!(),
+            };
+            if *ids > known {
+                result.push((*table, *ids, known, *mapping));
             }
-            return Ok(());
-        })?;
-        progress += 1;
-        task.progress(progress, max_progress);
-        self.hashes.lock().unwrap().verify(& mut |_|{
-            items += 1;
-            if items % 1000 == 0 {
-                task.info(format!("{} items, checking hashes ...", helpers::pretty_value(items)));
+        }
+        return result;
+    }
+
+    /** Repairs recoverable corruption of the substore. 
+     
+        Partially written trailing records are truncated, indices are rebuilt from the stores and paths missing from the paths mapping are regenerated from the path strings. Returns the descriptions of the repairs made. Corruption that cannot be repaired without silently losing data is returned as an error and the remaining tables are left untouched. 
+
+        The other mappings cannot be regenerated, since the commits, hashes and trees mappings hold the hashes of git objects that are not stored in full anywhere else and the users and message words are only stored in their mappings. If truncating any of them leaves ids used by other tables without their values, the repair fails with an error listing these mappings. 
+
+        The substore must not be updated while being repaired. It is dropped from memory first as the loaded mappings would not reflect the repaired tables. 
+     */
+    pub (crate) fn repair(& self, task : & updater::TaskStatus) -> Result<Vec<String>, std::io::Error> {
+        self.clear(task);
+        let _x = self.load_mutex.lock().unwrap();
+        let mut repairs = Vec::<String>::new();
+        {
+            let mut report = |x : String| {
+                task.info(x.clone());
+                repairs.push(x);
+            };
+            let max_progress = Substore::VERIFIED_TABLES.len() + 1;
+            task.progress(0, max_progress);
+            for (i, table) in Substore::VERIFIED_TABLES.iter().enumerate() {
+                (table.table)(self).repair(& mut report)?;
+                task.progress(i + 1, max_progress);
             }
-            return Ok(());
-        })?;
-        progress += 1;
-        task.progress(progress, max_progress);
-        self.contents.lock().unwrap().verify(& mut |_|{
-            items += 1;
-            if items % 1000 == 0 {
-                task.info(format!("{} items, checking contents ...", helpers::pretty_value(items)));
-            }
-            return Ok(());
-        })?;
-        progress += 1;
-        task.progress(progress, max_progress);
-        self.contents_metadata.lock().unwrap().verify(& mut |_|{
-            items += 1;
-            if items % 1000 == 0 {
-                task.info(format!("{} items, checking contents metadata ...", helpers::pretty_value(items)));
-            }
-            return Ok(());
-        })?;
-        progress += 1;
-        task.progress(progress, max_progress);
-        self.paths.lock().unwrap().verify(& mut |_|{
-            items += 1;
-            if items % 1000 == 0 {
-                task.info(format!("{} items, checking paths ...", helpers::pretty_value(items)));
-            }
-            return Ok(());
-        })?;
-        progress += 1;
-        task.progress(progress, max_progress);
-        self.path_strings.lock().unwrap().verify(& mut |_|{
-            items += 1;
-            if items % 1000 == 0 {
-                task.info(format!("{} items, checking path strings ...", helpers::pretty_value(items)));
-            }
-            return Ok(());
-        })?;
-        progress += 1;
-        task.progress(progress, max_progress);
-        self.users.lock().unwrap().verify(& mut |_|{
-            items += 1;
-            if items % 1000 == 0 {
-                task.info(format!("{} items, checking users ...", helpers::pretty_value(items)));
-            }
-            return Ok(());
-        })?;
-        progress += 1;
-        task.progress(progress, max_progress);
-        self.users_metadata.lock().unwrap().verify(& mut |_|{
-            items += 1;
-            if items % 1000 == 0 {
-                task.info(format!("{} items, checking users metadata ...", helpers::pretty_value(items)));
+            self.regenerate_paths(& mut report)?;
+            task.progress(max_progress, max_progress);
+        }
+        let mut lost : Vec<usize> = self.inconsistent_tables().into_iter().map(|(_, _, _, mapping)| mapping).filter(|x| *x != Substore::VERIFY_PATHS).collect();
+        lost.sort();
+        lost.dedup();
+        if ! lost.is_empty() {
+            let lost : Vec<String> = lost.iter().map(|x| format!("{:?}-{}", self.prefix, Substore::VERIFIED_TABLES[*x].name)).collect();
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Substore {:?}: mappings {} lost ids still used by other tables and cannot be regenerated", self.prefix, lost.join(", "))));
+        }
+        return Ok(repairs);
+    }
+
+    /** Regenerates the paths mapping from the path strings. 
+     
+        Path ids are created in the mapping before their strings are stored, so the mapping can only lag behind the path strings if it was truncated during repair. Since the mapping stores the hashes of the path strings, the missing values can be recalculated. 
+     */
+    fn regenerate_paths(& self, report : & mut dyn FnMut(String)) -> Result<(), std::io::Error> {
+        let mut paths = self.paths.lock().unwrap();
+        let mut path_strings = self.path_strings.lock().unwrap();
+        if path_strings.len() > paths.len() {
+            paths.load();
+            let first = paths.len();
+            for id in first..path_strings.len() {
+                let path_id = PathId::from(id as u64);
+                if let Some(path) = path_strings.get(path_id) {
+                    let (new_id, is_new) = paths.get_or_create_mapping(& Datastore::hash_of(path.as_bytes()))?;
+                    if ! is_new || new_id != path_id {
+                        paths.clear();
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Cannot regenerate path id {}, path {} already mapped to id {}", id, path, new_id)));
+                    }
+                } else {
+                    paths.clear();
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Cannot regenerate path ids from {}, path id {} has no path string", first, id)));
+                }
             }
-            return Ok(());
-        })?;
-        progress += 1;
-        task.progress(progress, max_progress);
-        return Ok(items);
+            paths.clear();
+            report(format!("{:?}-{}: {} paths regenerated from path strings", self.prefix, Substore::PATHS, path_strings.len() - first));
+        } else if path_strings.len() < paths.len() {
+            report(format!("{:?}-{}: {} paths without path strings, cannot be recovered", self.prefix, Substore::PATHS_STRINGS, paths.len() - path_strings.len()));
+        }
+        return Ok(());
     }
 
     /** Returns the memory report for the substore. 
@@ -720,36 +2520,398 @@ impl Substore {
             let hashes = self.hashes.lock().unwrap().mapping_len();
             let paths = self.paths.lock().unwrap().mapping_len();
             let users = self.users.lock().unwrap().mapping_len();
-            return format!("{:?}:{}", self.prefix, helpers::pretty_value(commits + hashes + paths + users));
+            let trees = self.trees.lock().unwrap().mapping_len();
+            return format!("{:?}:{}", self.prefix, helpers::pretty_value(commits + hashes + paths + users + trees));
         } else {
             return String::new();
         }
     }
 
+    /** Returns the estimated memory (in bytes) the mappings of the substore take when loaded. 
+     
+        The estimate is based on the number of values in the mappings, or on the memory budget of the mappings if set. 
+     */
+    pub (crate) fn estimated_memory(& self) -> u64 {
+        let values = self.commits.lock().unwrap().len() + self.hashes.lock().unwrap().len() + self.paths.lock().unwrap().len() + self.trees.lock().unwrap().len();
+        let mut result = values as u64 * Substore::SHA_ENTRY_MEMORY;
+        if let Some(budget) = SETTINGS.mapping_memory {
+            result = std::cmp::min(result, budget);
+        }
+        return result + self.users.lock().unwrap().len() as u64 * Substore::USER_ENTRY_MEMORY;
+    }
+
+    /** Returns the memory (in bytes) taken by the mappings of the substore that are currently loaded. 
+     */
+    pub (crate) fn loaded_memory(& self) -> u64 {
+        let values = self.commits.lock().unwrap().mapping_len() + self.hashes.lock().unwrap().mapping_len() + self.paths.lock().unwrap().mapping_len() + self.trees.lock().unwrap().mapping_len();
+        return values as u64 * Substore::SHA_ENTRY_MEMORY + self.users.lock().unwrap().mapping_len() as u64 * Substore::USER_ENTRY_MEMORY;
+    }
+
+    /** Returns the statistics of the substore, given the number of projects assigned to it. 
+     
+        Reports the number of records in the main tables, the number of contents records with their size, the size of the substore on disk and the memory taken by its mappings, or the estimated memory if the substore is not loaded. 
+     */
+    pub (crate) fn stats(& self, projects : usize) -> String {
+        let (contents, contents_size) = self.contents.lock().unwrap().stored_stats();
+        let mut result = format!("Substore {:?}: {}\n", self.prefix, if self.is_loaded() { "loaded" } else { "not loaded" });
+        result.push_str(& format!("    projects:  {}\n", helpers::pretty_value(projects)));
+        result.push_str(& format!("    commits:   {}\n", helpers::pretty_value(self.commits.lock().unwrap().len())));
+        result.push_str(& format!("    users:     {}\n", helpers::pretty_value(self.users.lock().unwrap().len())));
+        result.push_str(& format!("    paths:     {}\n", helpers::pretty_value(self.paths.lock().unwrap().len())));
+        result.push_str(& format!("    hashes:    {}\n", helpers::pretty_value(self.hashes.lock().unwrap().len())));
+        result.push_str(& format!("    trees:     {}\n", helpers::pretty_value(self.trees.lock().unwrap().len())));
+        result.push_str(& format!("    contents:  {} records, {}\n", helpers::pretty_value(contents), helpers::pretty_size(contents_size)));
+        result.push_str(& format!("    disk size: {}\n", helpers::pretty_size(self.disk_size())));
+        if self.is_loaded() {
+            result.push_str(& format!("    memory:    {}", helpers::pretty_size(self.loaded_memory())));
+        } else {
+            result.push_str(& format!("    memory:    none, {} estimated when loaded", helpers::pretty_size(self.estimated_memory())));
+        }
+        return result;
+    }
+
     /** Returns and id of given commit. 
      
         The secord returned value determines whether the commit is new,  or already known.
      */
-    pub (crate) fn get_or_create_commit_id(& self, hash : & SHA) -> (CommitId, bool) {
-        return self.commits.lock().unwrap().get_or_create_mapping(hash);
+    pub (crate) fn get_or_create_commit_id(& self, hash : & SHA) -> Result<(CommitId, bool), std::io::Error> {
+        let result = self.commits.lock().unwrap().get_or_create_mapping(hash)?;
+        if result.1 {
+            self.index_sha(hash, self.commit_bit())?;
+        }
+        return Ok(result);
+    }
+
+    /** Bit of the substore in the bitmaps of commits in the global hash index. 
+     */
+    pub (crate) fn commit_bit(& self) -> u64 {
+        return 1 << self.prefix.to_number();
+    }
+
+    /** Bit of the substore in the bitmaps of contents in the global hash index. 
+     */
+    pub (crate) fn contents_bit(& self) -> u64 {
+        return 1 << (self.prefix.to_number() + 32);
+    }
+
+    /** Records in the global hash index that the substore contains the given hash. 
+     
+        The hashes are buffered and written to the index in batches of `SHA_INDEX_BATCH`, so that the shared index is not locked for every new hash. The index is marked incomplete while the buffer is not empty, so that the hashes of a process that did not flush its buffer are indexed again when the datastore is opened next. 
+     */
+    pub (crate) fn index_sha(& self, hash : & SHA, bit : u64) -> Result<(), std::io::Error> {
+        let mut pending = self.sha_pending.lock().unwrap();
+        if pending.is_empty() {
+            match self.sha_substores.lock().unwrap().as_mut() {
+                Some(index) => index.begin_batch()?,
+                None => return Ok(()),
+            }
+        }
+        *pending.entry(*hash).or_insert(0) |= bit;
+        if pending.len() >= Substore::SHA_INDEX_BATCH {
+            self.write_sha_index(& mut pending)?;
+        }
+        return Ok(());
+    }
+
+    /** Writes the buffered hashes of the substore to the global hash index. 
+     */
+    pub (crate) fn flush_sha_index(& self) -> Result<(), std::io::Error> {
+        return self.write_sha_index(& mut self.sha_pending.lock().unwrap());
+    }
+
+    fn write_sha_index(& self, pending : & mut HashMap<SHA, u64>) -> Result<(), std::io::Error> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if let Some(index) = self.sha_substores.lock().unwrap().as_mut() {
+            for (hash, bits) in pending.drain() {
+                index.set(& hash, bits)?;
+            }
+            index.end_batch()?;
+        }
+        return Ok(());
+    }
+
+    /** Returns the bits of given hash not yet written to the global hash index. 
+     */
+    pub (crate) fn pending_sha(& self, hash : & SHA) -> u64 {
+        return self.sha_pending.lock().unwrap().get(hash).copied().unwrap_or(0);
     }
 
+    /** Walks the commits reachable from given commits and calls the visitor for each of them. 
+     
+        The commits info is locked for each commit separately, so that walking the history of a large project does not stall the other workers. Commits whose information is not stored (such as commits that are not yet analyzed) are ignored and so are the commits for which the filter returns false, neither are their parents walked. 
+     */
+    pub (crate) fn walk_commits(& self, start : Vec<CommitId>, filter : & dyn Fn(CommitId) -> bool, visitor : & mut dyn FnMut(CommitId, & CommitInfo)) {
+        let mut visited = HashSet::<CommitId>::new();
+        let mut q = start;
+        while let Some(id) = q.pop() {
+            if ! visited.insert(id) || ! filter(id) {
+                continue;
+            }
+            let commit = self.commits_info.lock().unwrap().get(id);
+            if let Some(commit) = commit {
+                visitor(id, & commit);
+                q.extend(commit.parents.iter());
+            }
+        }
+    }
+
+    /** Summarizes the commits reachable from given heads. 
+     
+        Commits whose information is not stored (such as commits that are not yet analyzed) are ignored. 
+     */
+    pub (crate) fn summarize_commits(& self, heads : & ProjectHeads) -> ProjectSummary {
+        let mut result = ProjectSummary::new();
+        let mut authors = HashSet::<UserId>::new();
+        let mut committers = HashSet::<UserId>::new();
+        self.walk_commits(heads.values().map(|(id, _)| *id).collect(), & |_| true, & mut |_, commit| {
+            if result.commits == 0 || commit.committer_time < result.first_commit_time {
+                result.first_commit_time = commit.committer_time;
+            }
+            if result.commits == 0 || commit.committer_time > result.last_commit_time {
+                result.last_commit_time = commit.committer_time;
+            }
+            result.commits += 1;
+            authors.insert(commit.author);
+            committers.insert(commit.committer);
+        });
+        result.authors = authors.len() as u64;
+        result.committers = committers.len() as u64;
+        return result;
+    }
+
+    /** Adds the commits new to the commits by time index of given project. 
+     
+        The commits reachable from the heads that are not yet in the project's index are ordered by their committer times and appended as a new record of the project, nothing is stored if there are none. Only the new commits are walked. 
+     */
+    pub (crate) fn update_project_commit_times(& self, id : ProjectId, heads : & ProjectHeads) {
+        let mut indexed = HashSet::<CommitId>::new();
+        if let Some(times) = self.project_commit_times.lock().unwrap().get(id) {
+            indexed.extend(times.iter().map(|(commit_id, _, _)| *commit_id));
+        }
+        for times in self.project_new_commit_times.lock().unwrap().iter_id(id) {
+            indexed.extend(times.iter().map(|(commit_id, _, _)| *commit_id));
+        }
+        let mut result = ProjectCommitTimes::new();
+        self.walk_commits(heads.values().map(|(id, _)| *id).collect(), & |commit_id| ! indexed.contains(& commit_id), & mut |commit_id, commit| {
+            result.push((commit_id, commit.author_time, commit.committer_time));
+        });
+        if ! result.is_empty() {
+            result.sort_by_key(|(id, _, committer_time)| (*committer_time, u64::from(*id)));
+            self.project_new_commit_times.lock().unwrap().set(id, & result);
+        }
+    }
+
+    /** Returns true if the commits by time index of given project has been built in the substore. 
+     */
+    pub (crate) fn has_project_commit_times(& self, id : ProjectId) -> bool {
+        return self.project_commit_times.lock().unwrap().has(id) || self.project_new_commit_times.lock().unwrap().has(id);
+    }
+
+    /** Returns the commits of given project that are not yet in the commit projects index. 
+     
+        These are the commits reachable from the given heads, but not from the heads the project has been indexed at. Returns None if the project has already been indexed at the given heads. 
+     */
+    pub (crate) fn unindexed_project_commits(& self, id : ProjectId, heads : & ProjectHeads) -> Option<Vec<CommitId>> {
+        let indexed = self.commit_projects_heads.lock().unwrap().get(id);
+        if indexed.as_ref() == Some(heads) {
+            return None;
+        }
+        let mut cinfo = self.commits_info.lock().unwrap();
+        let mut visited = HashSet::<CommitId>::new();
+        let mut q : Vec<CommitId> = indexed.map(|x| x.values().map(|(id, _)| *id).collect()).unwrap_or_default();
+        while let Some(id) = q.pop() {
+            if visited.insert(id) {
+                if let Some(commit) = cinfo.get(id) {
+                    q.extend(commit.parents.iter());
+                }
+            }
+        }
+        let mut result = Vec::new();
+        q.extend(heads.values().map(|(id, _)| *id));
+        while let Some(id) = q.pop() {
+            if visited.insert(id) {
+                if let Some(commit) = cinfo.get(id) {
+                    result.push(id);
+                    q.extend(commit.parents.iter());
+                }
+            }
+        }
+        return Some(result);
+    }
+
+    /** Appends a batch of projects to the commit projects index. 
+     
+        The projects are appended to the lists of the commits first and only then are the heads the projects have been indexed at updated, so that an interrupted pass indexes the projects of the batch again. 
+     */
+    pub (crate) fn add_commit_projects(& self, batch : HashMap<CommitId, CommitProjects>, heads : & Vec<(ProjectId, ProjectHeads)>) {
+        {
+            let mut commit_projects = self.commit_projects.lock().unwrap();
+            let mut commits : Vec<(CommitId, CommitProjects)> = batch.into_iter().collect();
+            commits.sort_by_key(|(id, _)| u64::from(*id));
+            for (id, mut projects) in commits {
+                projects.sort_by_key(|x| u64::from(*x));
+                commit_projects.set(id, & projects);
+            }
+        }
+        let mut commit_projects_heads = self.commit_projects_heads.lock().unwrap();
+        for (id, project_heads) in heads.iter() {
+            commit_projects_heads.set(*id, project_heads);
+        }
+    }
+
+    /** Returns the heads at which the commit metrics of given project were last calculated, if any. 
+     */
+    pub (crate) fn commit_metrics_heads(& self, id : ProjectId) -> Option<ProjectHeads> {
+        return self.commit_metrics_heads.lock().unwrap().get(id);
+    }
+
+    /** Returns the ids and parents of all stored commits reachable from given heads. 
+     */
+    pub (crate) fn project_commit_parents(& self, heads : & ProjectHeads) -> Vec<(CommitId, Vec<CommitId>)> {
+        let mut cinfo = self.commits_info.lock().unwrap();
+        let mut visited = HashSet::<CommitId>::new();
+        let mut result = Vec::new();
+        let mut q : Vec<CommitId> = heads.values().map(|(id, _)| *id).collect();
+        while let Some(id) = q.pop() {
+            if visited.insert(id) {
+                if let Some(commit) = cinfo.get(id) {
+                    q.extend(commit.parents.iter());
+                    result.push((id, commit.parents));
+                }
+            }
+        }
+        return result;
+    }
+
+    /** Stores the commit metrics calculated for a project and the heads they were calculated at. 
+     
+        Commits already having metrics keep the larger number of descendants, since the descendants of other projects containing the commit may be more. Records are only stored for commits whose metrics changed. The heads are stored last, so that an interrupted calculation is repeated. Returns the number of stored records. 
+     */
+    pub (crate) fn add_commit_metrics(& self, id : ProjectId, heads : & ProjectHeads, metrics : Vec<(CommitId, CommitMetrics)>) -> usize {
+        let mut result = 0;
+        {
+            let mut commit_metrics = self.commit_metrics.lock().unwrap();
+            for (commit_id, mut m) in metrics {
+                let old = commit_metrics.get(commit_id);
+                if let Some(old) = old.as_ref() {
+                    m.descendants = m.descendants.max(old.descendants);
+                }
+                if old.as_ref() != Some(& m) {
+                    commit_metrics.set(commit_id, & m);
+                    result += 1;
+                }
+            }
+        }
+        self.commit_metrics_heads.lock().unwrap().set(id, heads);
+        return result;
+    }
+
+    /** Stores the commit information unless already stored. 
+     
+        When stored, the commit is also added to the path commits index of all paths it changes. 
+     */
     pub (crate) fn add_commit_info_if_missing(& self, id : CommitId, commit_info : & CommitInfo) {
         let mut cinfo = self.commits_info.lock().unwrap();
         if ! cinfo.has(id) {
             cinfo.set(id, commit_info);
+            let mut path_commits = self.path_commits.lock().unwrap();
+            for path_id in commit_info.changes.keys() {
+                path_commits.set(*path_id, & id);
+            }
         }
     }
 
-    pub (crate) fn get_or_create_hash_id(& self, hash : & SHA) -> (HashId, bool) {
-        return self.hashes.lock().unwrap().get_or_create_mapping(hash);
+    /** Stores the files renamed by the commit unless already stored. 
+     */
+    pub (crate) fn add_commit_renames_if_missing(& self, id : CommitId, renames : & PathRenames) {
+        let mut path_renames = self.path_renames.lock().unwrap();
+        if ! path_renames.has(id) {
+            path_renames.set(id, renames);
+        }
     }
 
-    pub (crate) fn convert_hashes_to_ids(& self, hashes : & Vec<SHA>) -> Vec<(HashId, bool)> {
-        let mut mapping = self.hashes.lock().unwrap();
-        return hashes.iter().map(|hash| {
-            return mapping.get_or_create_mapping(hash);
-        }).collect();
+    /** Stores the submodules changed by the commit unless already stored. 
+     */
+    pub (crate) fn add_commit_submodules_if_missing(& self, id : CommitId, submodules : & CommitSubmodules) {
+        let mut commits_submodules = self.commits_submodules.lock().unwrap();
+        if ! commits_submodules.has(id) {
+            commits_submodules.set(id, submodules);
+        }
+    }
+
+    /** Returns the id of given tree and whether the tree is new. 
+     */
+    pub (crate) fn get_or_create_tree_id(& self, hash : & SHA) -> Result<(TreeId, bool), std::io::Error> {
+        return self.trees.lock().unwrap().get_or_create_mapping(hash);
+    }
+
+    /** Determines whether the entries of given tree are stored. 
+     */
+    pub (crate) fn has_tree(& self, id : TreeId) -> bool {
+        return self.trees_info.lock().unwrap().has(id);
+    }
+
+    /** Stores the entries of the tree unless already stored. 
+     */
+    pub (crate) fn add_tree_if_missing(& self, id : TreeId, tree : & Tree) {
+        let mut trees_info = self.trees_info.lock().unwrap();
+        if ! trees_info.has(id) {
+            trees_info.set(id, tree);
+        }
+    }
+
+    /** Stores the root tree of the commit unless already stored. 
+     */
+    pub (crate) fn add_commit_tree_if_missing(& self, id : CommitId, tree : TreeId) {
+        let mut commits_trees = self.commits_trees.lock().unwrap();
+        if ! commits_trees.has(id) {
+            commits_trees.set(id, & tree);
+        }
+    }
+
+    /** Stores the line stats of the commit unless already stored. 
+     */
+    pub (crate) fn add_commit_line_stats_if_missing(& self, id : CommitId, line_stats : & LineStats) {
+        let mut stats = self.commits_line_stats.lock().unwrap();
+        if ! stats.has(id) {
+            stats.set(id, line_stats);
+        }
+    }
+
+    /** Adds commit metadata unless the latest value for the key is the same. 
+     */
+    pub (crate) fn update_commit_metadata_if_differ(& self, id : CommitId, key : String, value : String) {
+        let mut metadata = self.commits_metadata.lock().unwrap();
+        for kv in metadata.iter_id(id) {
+            if kv.key == key {
+                if kv.value == value {
+                    return;
+                } else {
+                    break;
+                }
+            }
+        }
+        metadata.set(id, & Metadata{key, value});
+    }
+
+    pub (crate) fn get_or_create_hash_id(& self, hash : & SHA) -> Result<(HashId, bool), std::io::Error> {
+        let result = self.hashes.lock().unwrap().get_or_create_mapping(hash)?;
+        if result.1 {
+            self.index_sha(hash, self.contents_bit())?;
+        }
+        return Ok(result);
+    }
+
+    pub (crate) fn convert_hashes_to_ids(& self, hashes : & Vec<SHA>) -> Result<Vec<(HashId, bool)>, std::io::Error> {
+        let result = self.hashes.lock().unwrap().get_or_create_mappings(hashes)?;
+        for (hash, (_, is_new)) in hashes.iter().zip(result.iter()) {
+            if *is_new {
+                self.index_sha(hash, self.contents_bit())?;
+            }
+        }
+        return Ok(result);
     }
 
     /** Stores contents for given id. 
@@ -760,34 +2922,531 @@ impl Substore {
         self.contents.lock().unwrap().set(id, kind, contents);
     }
 
+    /** Stores the metadata of the contents for given id. 
+     */
+    pub (crate) fn add_file_contents_metadata(& self, id : HashId, metadata : & Vec<Metadata>) {
+        let mut contents_metadata = self.contents_metadata.lock().unwrap();
+        for mtd in metadata {
+            contents_metadata.set(id, mtd);
+        }
+    }
+
+    /** Returns true if the contents stored for given id are the blob itself, i.e. they are neither redacted, nor the downloaded object of an LFS pointer. 
+
+        Decided by the metadata stored with the contents, so that contents stored with different settings are recognized as well. 
+     */
+    pub (crate) fn is_contents_blob(& self, id : HashId) -> bool {
+        return ! self.contents_metadata.lock().unwrap().iter_id(id).any(|mtd| mtd.key == Metadata::CONTENTS_REDACTED || mtd.key == Metadata::CONTENTS_LFS);
+    }
+
+    /** Stores delta encoded contents for given id. 
+     */
+    pub (crate) fn add_file_contents_delta(& self, id : HashId, delta : & ContentsDelta) {
+        self.contents_deltas.lock().unwrap().set(id, delta);
+    }
+
+    /** Returns the id of given hash, if the hash is already known. 
+     */
+    pub (crate) fn get_hash_id(& self, hash : & SHA) -> Result<Option<HashId>, std::io::Error> {
+        return self.hashes.lock().unwrap().get_mapping(hash);
+    }
+
+    /** Returns the delta depth of the contents stored for given id. 
+     
+        Fully stored contents have depth of 0, delta encoded contents the depth of their delta. If no contents are stored for the id, returns `None`. 
+     */
+    pub (crate) fn get_file_contents_depth(& self, id : HashId) -> Option<u16> {
+        if self.contents.lock().unwrap().has(id) {
+            return Some(0);
+        }
+        return self.contents_deltas.lock().unwrap().get(id).map(|delta| delta.depth);
+    }
+
     /** Returns an id of given path. 
      
         Returns a tuple of the id and whether the path is new, or already existing one. 
      */
-    pub (crate) fn get_or_create_path_id(& self, path : & String) -> (PathId, bool) {
+    pub (crate) fn get_or_create_path_id(& self, path : & String) -> Result<(PathId, bool), std::io::Error> {
         let hash = Datastore::hash_of(path.as_bytes());
-        let (id, is_new) = self.paths.lock().unwrap().get_or_create_mapping(& hash);
+        let (id, is_new) = self.paths.lock().unwrap().get_or_create_mapping(& hash)?;
         if is_new {
             self.path_strings.lock().unwrap().set(id, path);
         }
-        return (id, is_new);
+        return Ok((id, is_new));
     }
 
-    pub (crate) fn convert_paths_to_ids(& self, paths : & Vec<String>) -> Vec<(PathId, bool)> {
+    pub (crate) fn convert_paths_to_ids(& self, paths : & Vec<String>) -> Result<Vec<(PathId, bool)>, std::io::Error> {
+        let hashes : Vec<SHA> = paths.iter().map(|path| Datastore::hash_of(path.as_bytes())).collect();
         let mut mapping = self.paths.lock().unwrap();
+        let ids = mapping.get_or_create_mappings(& hashes)?;
         let mut path_strings = self.path_strings.lock().unwrap();
-        return paths.iter().map(|path| {
-            let hash = Datastore::hash_of(path.as_bytes());
-            let (id, is_new) = mapping.get_or_create_mapping(& hash);
-            if is_new {
-                path_strings.set(id, path);
+        for (path, (id, is_new)) in paths.iter().zip(ids.iter()) {
+            if *is_new {
+                path_strings.set(*id, path);
             }
-            return (id, is_new);
-        }).collect();
+        }
+        return Ok(ids);
     }
 
+    /** Returns the user id of given email, creating new user if the email is not known. 
+     
+        If the emails are anonymized, the user is mapped by the anonymized email instead. 
+     */
     pub (crate) fn get_or_create_user_id(& self, email : & String) -> (UserId, bool) {
-        return self.users.lock().unwrap().get_or_create_mapping(email);
+        return self.users.lock().unwrap().get_or_create_mapping(& self.user_email(email));
+    }
+
+    /** Returns the email as stored for the users of the substore, i.e. anonymized if the emails are anonymized. 
+     
+        Empty emails, such as that of the sentinel user, carry no identity and are kept as they are. 
+     */
+    pub (crate) fn user_email(& self, email : & str) -> String {
+        match & self.email_key {
+            Some(key) if ! email.is_empty() => return Datastore::anonymize_email(key, email),
+            _ => return email.to_owned(),
+        }
+    }
+
+    /** Adds user metadata unless the latest value for the key is the same. 
+     */
+    pub (crate) fn update_user_metadata_if_differ(& self, id : UserId, key : String, value : String) {
+        let mut metadata = self.users_metadata.lock().unwrap();
+        for kv in metadata.iter_id(id) {
+            if kv.key == key {
+                if kv.value == value {
+                    return;
+                } else {
+                    break;
+                }
+            }
+        }
+        metadata.set(id, & Metadata{key, value});
+    }
+
+    /** Rewrites the contents of the substore so that only contents with given ids are kept. 
+     
+        The kept contents and their deltas are written into new tables which then replace the contents and contents deltas tables. The ids of the contents do not change. The replaced files are renamed with the `.gc-backup` suffix if the backups should be kept, or deleted otherwise. The kept ids must include the bases of all kept deltas. Returns the sizes of the contents tables before and after the rewrite. 
+
+        The replacement is committed by writing a manifest of all the renames it consists of before any file is renamed, so that a collection interrupted while renaming is finished when the substore is opened next time (see `finish_contents_collection`), while a collection interrupted earlier leaves the original tables intact. The time of the collection is recorded as well, since the sizes of the contents tables in older savepoints no longer match the rewritten tables and the datastore cannot be reverted to them. 
+
+        The substore must not be updated while its contents are collected. Fails without changing anything if any view pinned to a savepoint reads the datastore, since the rewritten tables no longer match its savepoint. 
+     */
+    pub (crate) fn collect_contents(& self, keep : & HashSet<HashId>, keep_backup : bool, task : & updater::TaskStatus) -> Result<(u64, u64), std::io::Error> {
+        let _pins = DatastoreLock::exclude_pins(& self.root).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("Cannot collect contents of substore {:?}: {}", self.prefix, e)))?;
+        let mut contents = self.contents.lock().unwrap();
+        let mut contents_deltas = self.contents_deltas.lock().unwrap();
+        let contents_name = format!("{:?}-{}", self.prefix, Substore::CONTENTS);
+        let deltas_name = format!("{:?}-{}", self.prefix, Substore::CONTENTS_DELTAS);
+        // the new tables are named by the tables they replace with a prefix
+        let prefix = "gc-";
+        // remove leftovers of an interrupted collection that has not been committed
+        Substore::finish_contents_collection(& self.root)?;
+        for name in list_table_files(& self.root)? {
+            if name.starts_with(prefix) {
+                remove_table_file(& format!("{}/{}", self.root, name))?;
+            }
+        }
+        {
+            let mut new_contents = SplitStore::<FileContents, ContentsKind, HashId>::new(& self.root, & format!("{}{}", prefix, contents_name), false);
+            let mut new_deltas = Store::<ContentsDelta, HashId>::new(& self.root, & format!("{}{}", prefix, deltas_name), false);
+            let mut ids : Vec<HashId> = keep.iter().map(|x| *x).collect();
+            ids.sort_by_key(|x| u64::from(*x));
+            for (i, id) in ids.iter().enumerate() {
+                if let Some((kind, bytes)) = contents.get(*id) {
+                    new_contents.set(*id, kind, & bytes);
+                } else if let Some(delta) = contents_deltas.get(*id) {
+                    new_deltas.set(*id, & delta);
+                }
+                if i % 1000 == 0 {
+                    task.info(format!("{} of {} contents kept...", helpers::pretty_value(i), helpers::pretty_value(ids.len())));
+                    task.progress(i, ids.len());
+                }
+            }
+        }
+        sync_tables()?;
+        // commit the replacement
+        let mut before = 0;
+        let mut after = 0;
+        let mut manifest = format!("{}\t{}\n", helpers::now(), keep_backup as u8);
+        for name in list_table_files(& self.root)? {
+            if let Some(old) = name.strip_prefix(prefix) {
+                after += open_table_file(& format!("{}/{}", self.root, name), true)?.len()?;
+                let old_path = format!("{}/{}", self.root, old);
+                if table_file_exists(& old_path) {
+                    before += open_table_file(& old_path, true)?.len()?;
+                }
+                manifest.push_str(& format!("{}\t{}\n", name, old));
+            }
+        }
+        manifest.push_str(Substore::CONTENTS_GC_MANIFEST_END);
+        {
+            let mut f = open_table_file(& format!("{}/{}", self.root, Substore::CONTENTS_GC_MANIFEST), false)?;
+            f.set_len(0)?;
+            f.write_all(manifest.as_bytes())?;
+            f.sync()?;
+        }
+        // and replace the tables
+        Substore::finish_contents_collection(& self.root)?;
+        *contents = SplitStore::new(& self.root, & contents_name, false);
+        *contents_deltas = Store::new(& self.root, & deltas_name, false);
+        return Ok((before, after));
+    }
+
+    /** Finishes a contents collection interrupted after it has been committed. 
+
+        The renames recorded in the manifest that have not been done yet are done, the time of the collection is recorded and the manifest is removed. A manifest that is not complete belongs to a collection that has not been committed and is only removed, leaving the original tables in place. Does nothing if there is no manifest. 
+     */
+    fn finish_contents_collection(root : & str) -> Result<(), std::io::Error> {
+        let manifest_path = format!("{}/{}", root, Substore::CONTENTS_GC_MANIFEST);
+        if ! table_file_exists(& manifest_path) {
+            return Ok(());
+        }
+        let mut manifest = String::new();
+        open_table_file(& manifest_path, true)?.read_to_string(& mut manifest)?;
+        if manifest.ends_with(Substore::CONTENTS_GC_MANIFEST_END) {
+            let mut lines = manifest.lines();
+            let header : Vec<& str> = lines.next().unwrap_or("").split('\t').collect();
+            let keep_backup = header.get(1) == Some(& "1");
+            for line in lines {
+                if let Some((new, old)) = line.split_once('\t') {
+                    let new_path = format!("{}/{}", root, new);
+                    let old_path = format!("{}/{}", root, old);
+                    if table_file_exists(& new_path) {
+                        if keep_backup && table_file_exists(& old_path) {
+                            rename_table_file(& old_path, & format!("{}.gc-backup", old_path))?;
+                        }
+                        rename_table_file(& new_path, & old_path)?;
+                    }
+                }
+            }
+            let mut f = open_table_file(& format!("{}/{}", root, Substore::CONTENTS_GC_TIME), false)?;
+            f.set_len(0)?;
+            f.write_all(header[0].as_bytes())?;
+            f.sync()?;
+        }
+        return remove_table_file(& manifest_path);
+    }
+
+    /** Returns the time of the last contents collection of the substore, if any. 
+     */
+    pub (crate) fn contents_collected(& self) -> Option<i64> {
+        let mut time = String::new();
+        open_table_file(& format!("{}/{}", self.root, Substore::CONTENTS_GC_TIME), true).ok()?.read_to_string(& mut time).ok()?;
+        return time.trim().parse().ok();
+    }
+
+    /** Determines which users are the same person and updates the user aliases accordingly. 
+     
+        Users are grouped together if they have the same GitHub login (taken from the GitHub noreply emails), or if they have the same name and the same email address before the domain (ignoring `+` suffixes), where the login is used instead of the address for the noreply emails. The user with the smallest id in each group becomes the canonical user of the group. Users whose names were never stored can only be grouped by their login. 
+        
+        Only the aliases that changed are stored. Returns the number of users that are aliases of other users. 
+     */
+    pub (crate) fn resolve_user_aliases(& self, task : & updater::TaskStatus) -> usize {
+        task.info("loading users...");
+        let users : Vec<(UserId, String)> = self.users.lock().unwrap().iter().collect();
+        let mut names = HashMap::<UserId, String>::new();
+        for (id, kv) in self.users_metadata.lock().unwrap().iter_all() {
+            if kv.key == Metadata::USER_NAME {
+                names.insert(id, kv.value.split_whitespace().collect::<Vec<& str>>().join(" ").to_lowercase());
+            }
+        }
+        // union-find over user ids, where the root of each group is always its smallest id
+        fn find(parents : & mut HashMap<UserId, UserId>, id : UserId) -> UserId {
+            let parent = *parents.get(& id).unwrap_or(& id);
+            if parent == id {
+                return id;
+            }
+            let root = find(parents, parent);
+            parents.insert(id, root);
+            return root;
+        }
+        let mut parents = HashMap::<UserId, UserId>::new();
+        let mut keys = HashMap::<String, UserId>::new();
+        task.info("grouping users...");
+        for (i, (id, email)) in users.iter().enumerate() {
+            if *id == UserId::INVALID {
+                continue;
+            }
+            let email = email.to_lowercase();
+            let (local, domain) = email.split_at(email.rfind('@').unwrap_or(email.len()));
+            let mut user_keys = Vec::new();
+            let local = if domain == "@users.noreply.github.com" {
+                let login = local.rsplit('+').next().unwrap();
+                user_keys.push(format!("login:{}", login));
+                login
+            } else {
+                local.split('+').next().unwrap()
+            };
+            if let Some(name) = names.get(id) {
+                if ! name.is_empty() && ! local.is_empty() {
+                    user_keys.push(format!("name:{}:{}", name, local));
+                }
+            }
+            for key in user_keys {
+                let other = *keys.entry(key).or_insert(*id);
+                let (a, b) = (find(& mut parents, *id), find(& mut parents, other));
+                if u64::from(a) < u64::from(b) {
+                    parents.insert(b, a);
+                } else if a != b {
+                    parents.insert(a, b);
+                }
+            }
+            if i % 1000 == 0 {
+                task.progress(i, users.len());
+            }
+        }
+        task.info("storing aliases...");
+        let mut aliases = self.user_aliases.lock().unwrap();
+        let mut result = 0;
+        for (id, _) in users.iter() {
+            let canonical = find(& mut parents, *id);
+            if canonical != *id {
+                result += 1;
+            }
+            if aliases.get(*id).unwrap_or(*id) != canonical {
+                aliases.set(*id, & canonical);
+            }
+        }
+        return result;
+    }
+
+    /** Returns the latest update time of all issues stored for the given project, or None if the project has no issues yet. 
+     
+        Since issues are retrieved ordered by their update time, this is the time of the first record in the project's issues list. 
+     */
+    pub (crate) fn get_project_issues_last_update(& self, id : ProjectId) -> Option<i64> {
+        return self.issues.lock().unwrap().iter_id(id).next().map(|x| x.updated_at);
+    }
+
+    pub (crate) fn add_project_issue(& self, id : ProjectId, issue : & Issue) {
+        self.issues.lock().unwrap().set(id, issue);
+    }
+
+    /** Adds the commits that are not yet indexed to the commit messages index. 
+     
+        The commits are indexed in batches of `Substore::MESSAGE_INDEX_BATCH` commits, after each batch the postings of all words found in the batch are appended and the indexing progress is updated, so that an interrupted indexing can be resumed. The tables are locked only for the duration of each batch so that the substore can be updated while being indexed. The given function is called before each batch, so that the indexing can pause, e.g. while a savepoint is created. Commits whose information is not yet stored when they are indexed are remembered in the progress record and retried by the next indexing. Returns the number of commits indexed. 
+     */
+    pub (crate) fn index_commit_messages(& self, task : & updater::TaskStatus, before_batch : & dyn Fn()) -> usize {
+        task.info("loading words...");
+        {
+            let mut words = self.message_words.lock().unwrap();
+            words.load();
+            // the sentinel empty word is never found in messages, its postings keep the indexing progress instead
+            words.get_or_create_mapping(& String::new());
+        }
+        let progress = self.message_postings.lock().unwrap().iter_id(WordId::PROGRESS).next().unwrap_or_default();
+        let last = self.commits_info.lock().unwrap().len();
+        let (first, todo) = Substore::commits_to_index(& progress, last);
+        let total = todo.len();
+        let mut unindexed = MessagePostings::new();
+        let mut indexed = 0;
+        for (n, chunk) in todo.chunks(Substore::MESSAGE_INDEX_BATCH).enumerate() {
+            before_batch();
+            let mut words = self.message_words.lock().unwrap();
+            let mut postings = self.message_postings.lock().unwrap();
+            let mut batch = HashMap::<WordId, MessagePostings>::new();
+            {
+                let mut cinfo = self.commits_info.lock().unwrap();
+                for (j, id) in chunk.iter().enumerate() {
+                    if let Some(commit) = cinfo.get(*id) {
+                        for word in CommitInfo::message_words(& commit.message) {
+                            let (word_id, _) = words.get_or_create_mapping(& word);
+                            batch.entry(word_id).or_insert_with(MessagePostings::new).push(*id);
+                        }
+                        indexed += 1;
+                    } else {
+                        unindexed.push(*id);
+                    }
+                    if j % 1000 == 999 {
+                        task.info(format!("indexing commit messages, {} words...", helpers::pretty_value(words.mapping_len())));
+                        task.progress(n * Substore::MESSAGE_INDEX_BATCH + j + 1, total);
+                    }
+                }
+            }
+            for (word_id, commits) in batch.iter() {
+                postings.set(*word_id, commits);
+            }
+            postings.set(WordId::PROGRESS, & Substore::index_progress(& todo, first, (n + 1) * Substore::MESSAGE_INDEX_BATCH, & unindexed));
+        }
+        self.message_words.lock().unwrap().clear();
+        return indexed;
+    }
+
+    /** Adds the commits not yet indexed to the hash occurrences index. 
+     
+        Every path changed by a commit to a contents hash, i.e. not deleted, is an occurrence of the hash. The commits are indexed in batches of `Substore::HASH_OCCURRENCES_BATCH` commits in the same way the commit messages are, so that an interrupted indexing can be resumed, the substore can be updated while being indexed and the indexing can pause before each batch. The progress is kept in the hash occurrences progress table. Datastores indexed before the table existed kept it in the occurrences of the deleted hash, from which it is taken over. Commits whose information is not yet stored when they are indexed are retried by the next indexing. Returns the number of commits indexed. 
+     */
+    pub (crate) fn index_hash_occurrences(& self, task : & updater::TaskStatus, before_batch : & dyn Fn()) -> usize {
+        let progress = match self.hash_occurrences_progress.lock().unwrap().get(0) {
+            Some(progress) => progress,
+            None => self.hash_occurrences.lock().unwrap().iter_id(HashId::DELETED).next().and_then(|x| x.first().map(|(id, _)| vec![*id])).unwrap_or_default(),
+        };
+        let last = self.commits_info.lock().unwrap().len();
+        let (first, todo) = Substore::commits_to_index(& progress, last);
+        let total = todo.len();
+        let mut unindexed = Vec::new();
+        let mut indexed = 0;
+        for (n, chunk) in todo.chunks(Substore::HASH_OCCURRENCES_BATCH).enumerate() {
+            before_batch();
+            let mut batch = HashMap::<HashId, HashOccurrences>::new();
+            {
+                let mut cinfo = self.commits_info.lock().unwrap();
+                for (j, id) in chunk.iter().enumerate() {
+                    if let Some(commit) = cinfo.get(*id) {
+                        for (path, hash) in commit.changes.iter() {
+                            if *hash != HashId::DELETED {
+                                batch.entry(*hash).or_insert_with(HashOccurrences::new).push((*id, *path));
+                            }
+                        }
+                        indexed += 1;
+                    } else {
+                        unindexed.push(*id);
+                    }
+                    if j % 1000 == 999 {
+                        task.info(format!("indexing hash occurrences, {} hashes in batch...", helpers::pretty_value(batch.len())));
+                        task.progress(n * Substore::HASH_OCCURRENCES_BATCH + j + 1, total);
+                    }
+                }
+            }
+            let mut hashes : Vec<(HashId, HashOccurrences)> = batch.into_iter().collect();
+            hashes.sort_by_key(|(id, _)| u64::from(*id));
+            let mut occurrences = self.hash_occurrences.lock().unwrap();
+            for (hash, mut hash_occurrences) in hashes {
+                hash_occurrences.sort_by_key(|(commit, path)| (u64::from(*commit), u64::from(*path)));
+                occurrences.set(hash, & hash_occurrences);
+            }
+            self.hash_occurrences_progress.lock().unwrap().set(0, & Substore::index_progress(& todo, first, (n + 1) * Substore::HASH_OCCURRENCES_BATCH, & unindexed));
+        }
+        return indexed;
     }
 
+    /** Returns the first commit not yet seen by an incremental index with given progress and the commits it should index next. 
+     
+        The progress of the commit messages and hash occurrences indices is the id of the first commit not yet seen, followed by the ids of commits that had no information stored when they were seen. These are retried first, followed by all commits up to given number of commits. 
+     */
+    fn commits_to_index(progress : & [CommitId], last : usize) -> (usize, Vec<CommitId>) {
+        let first = progress.first().map(|id| u64::from(*id) as usize).unwrap_or(0).min(last);
+        let mut result : Vec<CommitId> = progress.iter().skip(1).copied().collect();
+        result.extend((first..last).map(|i| CommitId::from(i as u64)));
+        return (first, result);
+    }
+
+    /** Returns the progress of an incremental index after the given number of commits to index (see `commits_to_index`) has been processed. 
+     
+        The commits that are not processed yet and precede the first commit not yet seen originally must be remembered as well. 
+     */
+    fn index_progress(todo : & [CommitId], first : usize, processed : usize, unindexed : & [CommitId]) -> Vec<CommitId> {
+        let processed = processed.min(todo.len());
+        // commits not yet seen follow the last processed commit, the remembered ones always precede those
+        let next = first.max(todo[..processed].last().map(|x| u64::from(*x) as usize + 1).unwrap_or(0));
+        let mut result = vec![CommitId::from(next as u64)];
+        result.extend(unindexed.iter().copied());
+        result.extend(todo[processed..].iter().take_while(|x| (u64::from(**x) as usize) < first));
+        return result;
+    }
+
+}
+
+/** Describes a table of the substore that is verified and repaired, see `Substore::VERIFIED_TABLES`. 
+ */
+pub (crate) struct VerifiedTableInfo {
+    pub (crate) name : &'static str,
+    /** Returns the table of given substore. 
+     */
+    table : fn(& Substore) -> & dyn VerifiedTable,
+}
+
+/** Verification and repair of the different kinds of substore tables. 
+ 
+    Stores are split into chunks that are verified in parallel without locking the store, the other tables are verified as a whole. The checker is called for every verified item. 
+ */
+trait VerifiedTable {
+    fn split_for_verification(& self, _max_chunks : usize, _min_size : u64) -> Vec<VerifyChunk> {
+        return vec![VerifyChunk::Table];
+    }
+
+    fn verify_chunk(& self, chunk : & VerifyChunk, checker : & mut dyn FnMut() -> Result<(), std::io::Error>) -> Result<VerifiedChunk, std::io::Error>;
+
+    fn verify_chunks_index(& self, _chunks : Vec<VerifiedChunk>) -> Result<(), std::io::Error> {
+        return Ok(());
+    }
+
+    fn repair(& self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error>;
+}
+
+impl<T : Serializable<Item = T>, ID : Id> VerifiedTable for Mutex<Store<T, ID>> {
+    fn split_for_verification(& self, max_chunks : usize, min_size : u64) -> Vec<VerifyChunk> {
+        return self.lock().unwrap().split_for_verification(max_chunks, min_size);
+    }
+
+    fn verify_chunk(& self, chunk : & VerifyChunk, checker : & mut dyn FnMut() -> Result<(), std::io::Error>) -> Result<VerifiedChunk, std::io::Error> {
+        return Store::<T, ID>::verify_chunk(chunk, & mut |_| checker());
+    }
+
+    fn verify_chunks_index(& self, chunks : Vec<VerifiedChunk>) -> Result<(), std::io::Error> {
+        return self.lock().unwrap().verify_chunks_index(chunks);
+    }
+
+    fn repair(& self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        return self.lock().unwrap().repair(report);
+    }
+}
+
+impl<T : Serializable<Item = T>, ID : Id> VerifiedTable for Mutex<LinkedStore<T, ID>> {
+    fn split_for_verification(& self, max_chunks : usize, min_size : u64) -> Vec<VerifyChunk> {
+        return self.lock().unwrap().split_for_verification(max_chunks, min_size);
+    }
+
+    fn verify_chunk(& self, chunk : & VerifyChunk, checker : & mut dyn FnMut() -> Result<(), std::io::Error>) -> Result<VerifiedChunk, std::io::Error> {
+        return LinkedStore::<T, ID>::verify_chunk(chunk, & mut |_| checker());
+    }
+
+    fn verify_chunks_index(& self, chunks : Vec<VerifiedChunk>) -> Result<(), std::io::Error> {
+        return self.lock().unwrap().verify_chunks_index(chunks);
+    }
+
+    fn repair(& self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        return self.lock().unwrap().repair(report);
+    }
+}
+
+/* The values of indirect mappings are kept in a store, whose chunks are verified the same way. */
+impl<T : Serializable<Item = T> + Eq + std::hash::Hash + Clone, ID : Id> VerifiedTable for Mutex<IndirectMapping<T, ID>> {
+    fn split_for_verification(& self, max_chunks : usize, min_size : u64) -> Vec<VerifyChunk> {
+        return self.lock().unwrap().split_for_verification(max_chunks, min_size);
+    }
+
+    fn verify_chunk(& self, chunk : & VerifyChunk, checker : & mut dyn FnMut() -> Result<(), std::io::Error>) -> Result<VerifiedChunk, std::io::Error> {
+        return Store::<T, ID>::verify_chunk(chunk, & mut |_| checker());
+    }
+
+    fn verify_chunks_index(& self, chunks : Vec<VerifiedChunk>) -> Result<(), std::io::Error> {
+        return self.lock().unwrap().verify_chunks_index(chunks);
+    }
+
+    fn repair(& self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        return self.lock().unwrap().repair(report);
+    }
+}
+
+impl<T : FixedSizeSerializable<Item = T> + Eq + std::hash::Hash + Clone, ID : Id> VerifiedTable for Mutex<Mapping<T, ID>> {
+    fn verify_chunk(& self, _chunk : & VerifyChunk, checker : & mut dyn FnMut() -> Result<(), std::io::Error>) -> Result<VerifiedChunk, std::io::Error> {
+        self.lock().unwrap().verify(& mut |_| checker())?;
+        return Ok(VerifiedChunk::default());
+    }
+
+    fn repair(& self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        return self.lock().unwrap().repair(report);
+    }
+}
+
+impl<T : Serializable<Item = T>, KIND : SplitKind<Item = KIND>, ID : Id> VerifiedTable for Mutex<SplitStore<T, KIND, ID>> {
+    fn verify_chunk(& self, _chunk : & VerifyChunk, checker : & mut dyn FnMut() -> Result<(), std::io::Error>) -> Result<VerifiedChunk, std::io::Error> {
+        self.lock().unwrap().verify(& mut |_| checker())?;
+        return Ok(VerifiedChunk::default());
+    }
+
+    fn repair(& self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        return self.lock().unwrap().repair(report);
+    }
 }
@@ -2,60 +2,376 @@ use crate::updater::*;
 use crate::records::*;
 use crate::helpers;
 use crate::datastore::*;
+use crate::csv_input::*;
+use crate::settings::SETTINGS;
+use crate::LOG;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 
 /** Adds projects to the datastore. 
  
     To do this we must check the project urls for which the datastore needs to load all urls it knows. If the hashmap is not populated, it is loaded first. Then projects from the source can be added. 
+
+    If enabled in the settings, the redirects of the urls are resolved first and urls that redirect to projects already in the datastore are skipped as duplicates. Projects that are not added are written to the rejects file in the datastore root with the reason, see `AddStats`. 
  */
 pub (crate) fn task_add_projects(ds : & Datastore, source : String,  task : TaskStatus) -> Result<(), std::io::Error> {
     ds.load_project_urls(| progress | {
         task.info(format!("loading datastore project urls ({}) ", helpers::pretty_value(progress)));
     });
-    let mut added = 0;
-    let mut existing = 0;
-    let mut invalid = 0;
+    let mut stats = AddStats::new(ds)?;
     if source.ends_with(".csv") {
-        add_projects_from_csv(ds, source, & task, & mut added, & mut existing, & mut invalid)?;
+        add_projects_from_csv(ds, source, & task, & mut stats)?;
     } else {
-        add_project(ds, & source, & mut added, & mut existing, & mut invalid);
+        add_resolved_project(ds, & source, & mut stats);
+    }
+    stats.finish(& task)?;
+    return Ok(());
+}
+
+/** Imports projects from a project dump. 
+ 
+    Unlike plain csv files, the dumps have known format so that the project urls can be found without guessing and the metadata from the dump (stars and language, whichever are available) are attached to the newly added projects as their initial metadata. Projects that already exist in the datastore are left intact. Projects that are not added are written to the rejects file in the datastore root with the reason. 
+ */
+pub (crate) fn task_import_projects(ds : & Datastore, source : String, dump : ProjectsDump, task : TaskStatus) -> Result<(), std::io::Error> {
+    ds.load_project_urls(| progress | {
+        task.info(format!("loading datastore project urls ({}) ", helpers::pretty_value(progress)));
+    });
+    let mut stats = AddStats::new(ds)?;
+    match dump {
+        ProjectsDump::GHTorrent => import_ghtorrent(ds, source, & task, & mut stats)?,
+        ProjectsDump::Seart => import_seart(ds, source, & task, & mut stats)?,
     }
-    task.info(format!("Finished: {} added, {} existing, {} invalid", added, existing, invalid));
+    stats.finish(& task)?;
     return Ok(());
 }
 
-fn add_project(ds : & Datastore, url : & str, added : & mut usize, existing : & mut usize, invalid : & mut usize) {
+/** Reasons why projects are not added. 
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Reject {
+    /** The project is already in the datastore. 
+     */
+    Existing,
+    /** The project is given more than once in the source, or redirects to a project already in the datastore. 
+     */
+    Duplicate,
+    /** The url is not an url at all. 
+     */
+    InvalidUrl,
+    /** The url is valid, but its host, or protocol is not supported. 
+     */
+    UnsupportedHost,
+    /** The project is denied by the denylist. 
+     */
+    Denied,
+    /** The project is marked as deleted by the dump. 
+     */
+    Deleted,
+    /** The record of the project cannot be read, or does not contain the url. 
+     */
+    Malformed,
+}
+
+impl Reject {
+    const ALL : [Reject; 7] = [Reject::Existing, Reject::Duplicate, Reject::InvalidUrl, Reject::UnsupportedHost, Reject::Denied, Reject::Deleted, Reject::Malformed];
+
+    fn name(& self) -> &'static str {
+        match self {
+            Reject::Existing => return "existing",
+            Reject::Duplicate => return "duplicate",
+            Reject::InvalidUrl => return "invalid url",
+            Reject::UnsupportedHost => return "unsupported host",
+            Reject::Denied => return "denied",
+            Reject::Deleted => return "deleted",
+            Reject::Malformed => return "malformed",
+        }
+    }
+}
+
+/** Statistics of adding projects, i.e. the number of added projects and of the projects rejected for each reason. 
+ 
+    The rejected projects are also written to the rejects file in the datastore root, which is created anew for every add, with the line of the project in the source, if known, its url, the reason and details of the reason, if any. 
+ */
+struct AddStats {
+    added : usize,
+    rejected : HashMap<Reject, usize>,
+    /** Projects added from the current source, so that projects given more than once can be told apart from those that were in the datastore before. 
+     */
+    added_projects : HashSet<ProjectUrl>,
+    /** Line of the source the currently added project is on, if known. 
+     */
+    line : Option<u64>,
+    rejects : std::io::BufWriter<std::fs::File>,
+    /** The first error writing the rejects file, reported when the add finishes. 
+     */
+    rejects_error : Option<std::io::Error>,
+}
+
+impl AddStats {
+    fn new(ds : & Datastore) -> Result<AddStats, std::io::Error> {
+        let mut rejects = std::io::BufWriter::new(std::fs::File::create(format!("{}/{}", ds.root_folder(), Updater::ADD_REJECTS))?);
+        writeln!(rejects, "line,url,reason,detail")?;
+        return Ok(AddStats{
+            added : 0,
+            rejected : HashMap::new(),
+            added_projects : HashSet::new(),
+            line : None,
+            rejects,
+            rejects_error : None,
+        });
+    }
+
+    fn add(& mut self, project : & ProjectUrl) {
+        self.added += 1;
+        self.added_projects.insert(project.clone());
+    }
+
+    /** Records a rejected project. 
+     
+        Failures to write the rejects file do not stop the add, the first of them is remembered and returned when the add finishes. 
+     */
+    fn reject(& mut self, url : & str, reason : Reject, detail : & str) {
+        *self.rejected.entry(reason).or_insert(0) += 1;
+        if self.rejects_error.is_some() {
+            return;
+        }
+        if let Err(e) = writeln!(self.rejects, "{},\"{}\",{},\"{}\"", self.line.map(|x| x.to_string()).unwrap_or_default(), url.replace("\"", "\"\""), reason.name(), detail.replace("\"", "\"\"")) {
+            self.rejects_error = Some(e);
+        }
+    }
+
+    /** Records a project that was not added because it already exists, either in the datastore, or earlier in the source. 
+     */
+    fn reject_existing(& mut self, url : & str, project : & ProjectUrl) {
+        if self.added_projects.contains(project) {
+            self.reject(url, Reject::Duplicate, "");
+        } else {
+            self.reject(url, Reject::Existing, "");
+        }
+    }
+
+    fn total(& self) -> usize {
+        return self.added + self.rejected.values().sum::<usize>();
+    }
+
+    /** Returns the number of added projects followed by the numbers of projects rejected for each reason, skipping the reasons with no projects. 
+     */
+    fn summary(& self) -> String {
+        let mut result = format!("{} added", self.added);
+        for reason in Reject::ALL.iter() {
+            if let Some(n) = self.rejected.get(reason) {
+                result.push_str(& format!(", {} {}", n, reason.name()));
+            }
+        }
+        return result;
+    }
+
+    /** Reports the final statistics to the task and makes sure all rejects are written. 
+     
+        The statistics are reported even if the rejects file could not be written, in which case the first error writing it is returned. 
+     */
+    fn finish(& mut self, task : & TaskStatus) -> Result<(), std::io::Error> {
+        if self.rejects_error.is_none() {
+            if let Err(e) = self.rejects.flush() {
+                self.rejects_error = Some(e);
+            }
+        }
+        if self.rejected.is_empty() {
+            task.info(format!("Finished: {}", self.summary()));
+        } else if self.rejects_error.is_some() {
+            task.info(format!("Finished: {}, rejects file {} incomplete", self.summary(), Updater::ADD_REJECTS));
+        } else {
+            task.info(format!("Finished: {}, rejects written to {}", self.summary(), Updater::ADD_REJECTS));
+        }
+        return match self.rejects_error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        };
+    }
+}
+
+/** Returns the reason why given url, which is not a valid project url, is rejected. 
+ 
+    Urls with a scheme and a host, or ssh urls are valid urls of unsupported hosts, everything else is not an url at all. 
+ */
+fn unparsed_url_reject(url : & str) -> Reject {
+    if let Some(i) = url.find("://") {
+        let scheme = & url[..i];
+        let host = url[i + 3..].split('/').next().unwrap_or("");
+        if ! scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.') && ! host.is_empty() && ! host.contains(char::is_whitespace) {
+            return Reject::UnsupportedHost;
+        }
+    } else if url.starts_with("git@") && url.contains(':') {
+        return Reject::UnsupportedHost;
+    }
+    return Reject::InvalidUrl;
+}
+
+fn add_project(ds : & Datastore, url : & str, stats : & mut AddStats) -> Option<ProjectId> {
     match ProjectUrl::from_url(url) {
         Some(project) => {
+            // denied projects are not added at all
+            if let Some(entry) = ds.find_denied(None, & project) {
+                LOG!("Project {} not added, denied by {}", url, entry);
+                stats.reject(url, Reject::Denied, & entry.to_string());
+                return None;
+            }
             match ds.add_project(& project) {
-                Some(_id) => {
+                Some(id) => {
                     // don't actually schedule the update, it has to be explicitly enabled by the user
                     //updater.schedule(Task::UpdateRepo{ id, last_update_time : Updater::NEVER });
-                    *added += 1;
+                    stats.add(& project);
+                    return Some(id);
                 },
                 _ => {
-                    *existing += 1;
+                    stats.reject_existing(url, & project);
                 },
             }
         }, 
-        None => *invalid += 1,
+        None => stats.reject(url, unparsed_url_reject(url), ""),
     }
+    return None;
 } 
 
-fn add_projects_from_csv(ds : & Datastore, source : String, task : & TaskStatus, added : & mut usize, existing : & mut usize, invalid : & mut usize) -> Result<(), std::io::Error>{
+/** Adds project with given url, resolving its redirects first if enabled in the settings. 
+
+    Renamed, or moved repositories redirect their old urls to the new ones. If the url redirects to a project already in the datastore, the project is not added again and is counted as a duplicate, otherwise the project is added with the url it redirects to. Urls whose redirects cannot be resolved, e.g. because of network errors, are added as they are. 
+ */
+fn add_resolved_project(ds : & Datastore, url : & str, stats : & mut AddStats) -> Option<ProjectId> {
+    if SETTINGS.resolve_redirects {
+        if let Some(project) = ProjectUrl::from_url(url) {
+            // no need to resolve urls that are already known
+            if ! ds.project_urls.lock().unwrap().contains(& project) {
+                if let Some(resolved) = resolve_redirect(& project.clone_url()).and_then(|x| ProjectUrl::from_url(& x)) {
+                    if resolved != project {
+                        if ds.project_urls.lock().unwrap().contains(& resolved) {
+                            LOG!("skipping {}, redirects to existing project {}", url, resolved.clone_url());
+                            stats.reject(url, Reject::Duplicate, & format!("redirects to {}", resolved.clone_url()));
+                            return None;
+                        }
+                        LOG!("{} redirects to {}", url, resolved.clone_url());
+                        return add_project(ds, & resolved.clone_url(), stats);
+                    }
+                }
+            }
+        }
+    }
+    return add_project(ds, url, stats);
+}
+
+/** Returns the url given url redirects to, or None if the url cannot be reached. 
+ 
+    Only the headers are requested, all redirects are followed. 
+ */
+fn resolve_redirect(url : & str) -> Option<String> {
+    let mut conn = curl::easy::Easy::new();
+    conn.url(url).ok()?;
+    conn.proxy(SETTINGS.curl_proxy()).ok()?;
+    conn.nobody(true).ok()?;
+    conn.follow_location(true).ok()?;
+    conn.timeout(std::time::Duration::from_secs(30)).ok()?;
+    conn.perform().ok()?;
+    let code = conn.response_code().ok()?;
+    if code < 200 || code >= 400 {
+        return None;
+    }
+    return conn.effective_url().ok()?.map(|x| x.to_owned());
+}
+
+/** Adds project from a dump and if the project is new, attaches the metadata from the dump to it. 
+ */
+fn add_project_with_metadata(ds : & Datastore, url : & str, metadata : Vec<(& str, String)>, stats : & mut AddStats) {
+    if let Some(id) = add_project(ds, url, stats) {
+        for (key, value) in metadata {
+            ds.update_project_metadata_if_differ(id, key.to_owned(), value);
+        }
+    }
+}
+
+/** Imports GHTorrent's `projects.csv`. 
+
+    The file has no headers and contains the following columns: `id, url, owner_id, name, description, language, created_at, forked_from, deleted, updated_at`, where the url is the GitHub API url of the project and missing values are `\N`. Deleted projects are rejected. 
+ */
+fn import_ghtorrent(ds : & Datastore, source : String, task : & TaskStatus, stats : & mut AddStats) -> Result<(), std::io::Error> {
     let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
+        .has_headers(false)
         .double_quote(false)
         .escape(Some(b'\\'))
+        .flexible(true)
         .from_path(source)?;
-    let headers = reader.headers()?;
+    for x in reader.records() {
+        let record = x?;
+        if record.len() < 9 {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Invalid GHTorrent projects record: {:?}", record)));
+        }
+        stats.line = record.position().map(|x| x.line());
+        if & record[8] == "1" {
+            stats.reject(& record[1], Reject::Deleted, "");
+        } else {
+            let mut metadata = Vec::new();
+            if & record[5] != "\\N" && ! record[5].is_empty() {
+                metadata.push((Metadata::LANGUAGE, record[5].to_owned()));
+            }
+            add_project_with_metadata(ds, & record[1], metadata, stats);
+        }
+        if stats.total() % 1000 == 0 {
+            task.info(stats.summary());
+        }
+    }
+    return Ok(());
+}
+
+/** Imports the JSON export of the SEART GitHub search. 
+
+    The export is an object whose `items` array contains the projects. Each project's `name` is the GitHub user and repository, while `stargazers` and `mainLanguage` give the metadata. 
+ */
+fn import_seart(ds : & Datastore, source : String, task : & TaskStatus, stats : & mut AddStats) -> Result<(), std::io::Error> {
+    let contents = std::fs::read_to_string(source)?;
+    let json = json::parse(& contents).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("Cannot parse SEART export: {}", e)))?;
+    let items = if json.is_array() { & json } else { & json["items"] };
+    if ! items.is_array() {
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, "SEART export does not contain any items"));
+    }
+    for item in items.members() {
+        if let Some(name) = item["name"].as_str() {
+            let mut metadata = Vec::new();
+            if let Some(stars) = item["stargazers"].as_u64() {
+                metadata.push((Metadata::STARS, stars.to_string()));
+            }
+            if let Some(language) = item["mainLanguage"].as_str() {
+                metadata.push((Metadata::LANGUAGE, language.to_owned()));
+            }
+            add_project_with_metadata(ds, & format!("https://github.com/{}", name), metadata, stats);
+        } else {
+            stats.reject("", Reject::Malformed, "item without name");
+        }
+        if stats.total() % 1000 == 0 {
+            task.info(stats.summary());
+        }
+    }
+    return Ok(());
+}
+
+/** Adds projects from a csv file. 
+ 
+    The file is read with the CSV options from the settings. Malformed records and records too short to contain the url are rejected as malformed and skipped. 
+ */
+fn add_projects_from_csv(ds : & Datastore, source : String, task : & TaskStatus, stats : & mut AddStats) -> Result<(), std::io::Error>{
+    let mut reader = SETTINGS.csv.from_path(& source)?;
+    let headers = reader.headers()?.clone();
     let mut col_id = if let Some(id) = find_repo_url_column(& headers) {
-        add_project(ds, & headers[id], added, existing, invalid);
+        stats.line = headers.position().map(|x| x.line());
+        add_resolved_project(ds, & headers[id], stats);
         id
     } else {
         std::usize::MAX
     };
-    for x in reader.records() {
-        let record = x.unwrap();
+    let mut malformed = Vec::new();
+    let records = TolerantRecords::new(& mut reader, |e| {
+        task.info(format!("skipping malformed record at {}", e));
+        malformed.push(e.to_string());
+    });
+    for record in records {
         if col_id == std::usize::MAX {
             if let Some(id) = find_repo_url_column(& record) {
                 col_id = id;
@@ -63,11 +379,24 @@ fn add_projects_from_csv(ds : & Datastore, source : String, task : & TaskStatus,
                 return Err(std::io::Error::new(std::io::ErrorKind::Other, "Cannot determine column containing project urls"));
             }
         }
-        add_project(ds, & record[col_id], added, existing, invalid);
-        if (*added + *existing + *invalid) % 1000 == 0 {
-            task.info(format!("{} added, {} existing, {} invalid, using column {}", added, existing, invalid, col_id));
+        stats.line = record.position().map(|x| x.line());
+        match record.get(col_id) {
+            Some(url) => {
+                add_resolved_project(ds, url, stats);
+            },
+            None => {
+                stats.reject("", Reject::Malformed, & format!("no column {}", col_id));
+                task.info(format!("skipping record without column {} at line {}", col_id, record.position().map(|x| x.line()).unwrap_or(0)));
+            }
+        }
+        if stats.total() % 1000 == 0 {
+            task.info(format!("{}, using column {}", stats.summary(), col_id));
         }
     }
+    stats.line = None;
+    for e in malformed {
+        stats.reject("", Reject::Malformed, & e);
+    }
     return Ok(());
 }
 
@@ -102,7 +431,7 @@ fn find_repo_url_column(row : & csv::StringRecord) -> Option<usize> {
  */
 pub (crate) fn task_create_savepoint(ds : & Datastore, task : TaskStatus) -> Result<(), std::io::Error> {
     if let Task::CreateSavepoint{name} = & task.task {
-        let sp = ds.create_and_save_savepoint(name.to_owned());
+        let sp = ds.create_and_save_savepoint(name.to_owned(), & task)?;
         task.info(format!("Created savepoint {}, total size {}", sp.name(), helpers::pretty_size(sp.size())));
      } else {
         panic!("Invalid task kind");
@@ -110,8 +439,22 @@ pub (crate) fn task_create_savepoint(ds : & Datastore, task : TaskStatus) -> Res
     return Ok(());
 }
 
+/** Creates a savepoint while the updater is running. 
+ 
+    The workers are held first so that the savepoint is not created in the middle of a project update, and released once the savepoint is stored. 
+ */
+pub (crate) fn task_create_savepoint_held(updater : & Updater, task : TaskStatus) -> Result<(), std::io::Error> {
+    if ! updater.hold_workers(& task) {
+        return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "Cancelled while waiting for running project tasks"));
+    }
+    // the workers must be released even if the savepoint creation panics
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| task_create_savepoint(& updater.ds, task)));
+    updater.release_workers();
+    return result.unwrap_or_else(|cause| std::panic::resume_unwind(cause));
+}
+
 pub (crate) fn task_load_substore(ds : & Datastore, store : StoreKind,  task : TaskStatus) -> Result<(), std::io::Error> {
-    ds.substore(store).load(& task);
+    ds.substore(store).load(& task)?;
     task.info(format!("{:?}", store));
     return Ok(());
 }
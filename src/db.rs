@@ -28,6 +28,737 @@ use crate::LOG;
 
 pub (crate) const MAX_BUFFER_LENGTH : u64 = 10 * 1024 * 1024 * 1024; // 10GB
 
+/** Storage of a single table file. 
+ 
+    The tables (indexers, stores, linked stores, mappings and their checksums) only access their files through this trait, so that the files can be kept elsewhere than in the local filesystem, such as in memory mapped, compressed, or remote storage. The tables append records, read them at arbitrary offsets, overwrite fixed size index entries in place and truncate the files when reverting to savepoints and repairing, all of which the storage must support. Positions are set via `Seek`, where `SeekFrom::End(0)` returns the length of the stored data. 
+ */
+pub trait TableStorage : Read + Write + Seek + Send {
+    /** Truncates, or extends the stored data to given length. 
+     */
+    fn set_len(& mut self, len : u64) -> Result<(), std::io::Error>;
+
+    /** Returns the length of the stored data in bytes, keeping the current position. 
+     */
+    fn len(& mut self) -> Result<u64, std::io::Error> {
+        let position = self.seek(SeekFrom::Current(0))?;
+        let len = self.seek(SeekFrom::End(0))?;
+        self.seek(SeekFrom::Start(position))?;
+        return Ok(len);
+    }
+
+    /** Fills given buffer with the data at given offset, leaving the position right after them. 
+     */
+    fn read_at(& mut self, offset : u64, buffer : & mut [u8]) -> Result<(), std::io::Error> {
+        self.seek(SeekFrom::Start(offset))?;
+        return self.read_exact(buffer);
+    }
+
+    /** Appends given bytes to the stored data and returns the offset at which they were written. 
+     */
+    fn append(& mut self, bytes : & [u8]) -> Result<u64, std::io::Error> {
+        let offset = self.seek(SeekFrom::End(0))?;
+        self.write_all(bytes)?;
+        return Ok(offset);
+    }
+
+    /** Makes the data written so far durable, i.e. ensures they survive a crash, or power failure. 
+     
+        Only flushes the buffered data by default, which is all that storages without a persistent medium can do. 
+     */
+    fn sync(& mut self) -> Result<(), std::io::Error> {
+        return self.flush();
+    }
+}
+
+/* Readers memory map the table files and hold them shared locked while mapped (see `table_readers::MappedFile`). Truncating a mapped file would kill the reader with `SIGBUS` when it touches the removed pages, so files are only truncated when they can be locked exclusively. */
+impl TableStorage for File {
+    #[cfg(unix)]
+    fn set_len(& mut self, len : u64) -> Result<(), std::io::Error> {
+        use std::os::unix::io::AsRawFd;
+        if len >= self.metadata()?.len() {
+            return File::set_len(self, len);
+        }
+        if unsafe { libc::flock(self.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "table file is mapped by a reader, cannot truncate"));
+        }
+        let result = File::set_len(self, len);
+        unsafe { libc::flock(self.as_raw_fd(), libc::LOCK_UN); }
+        return result;
+    }
+
+    /* The readers only map the files on unix, so there is nothing to lock elsewhere. */
+    #[cfg(not(unix))]
+    fn set_len(& mut self, len : u64) -> Result<(), std::io::Error> {
+        return File::set_len(self, len);
+    }
+
+    fn sync(& mut self) -> Result<(), std::io::Error> {
+        return self.sync_data();
+    }
+}
+
+/* Records are serialized in memory before they are appended to their stores, see `Store::write_record`. */
+impl TableStorage for std::io::Cursor<Vec<u8>> {
+    fn set_len(& mut self, len : u64) -> Result<(), std::io::Error> {
+        self.get_mut().resize(len as usize, 0);
+        return Ok(());
+    }
+}
+
+/* Tables keep their storage boxed, the box forwards to the storage so that its own implementations of the provided methods are used. */
+impl TableStorage for Box<dyn TableStorage> {
+    fn set_len(& mut self, len : u64) -> Result<(), std::io::Error> {
+        return (**self).set_len(len);
+    }
+
+    fn len(& mut self) -> Result<u64, std::io::Error> {
+        return (**self).len();
+    }
+
+    fn read_at(& mut self, offset : u64, buffer : & mut [u8]) -> Result<(), std::io::Error> {
+        return (**self).read_at(offset, buffer);
+    }
+
+    fn append(& mut self, bytes : & [u8]) -> Result<u64, std::io::Error> {
+        return (**self).append(bytes);
+    }
+
+    fn sync(& mut self) -> Result<(), std::io::Error> {
+        return (**self).sync();
+    }
+}
+
+/** Opens the table files. 
+ 
+    The paths are those the files have in the local filesystem, i.e. the table's folder followed by its name and extension, which other backends may map to their own locations. A single backend is used for all tables of the process, see `set_storage_backend`. 
+ 
+    Derived indices that can be rebuilt from the tables (bloom filters, sorted indices of the mappings and bitmap indices) are stored via the backend as well, see `open_index_file`. The memory mapped readers of `table_readers` map the local table files directly. 
+ */
+pub trait StorageBackend : Send + Sync {
+    /** Opens the table file at given path. 
+     
+        Files opened readonly must exist and are never written to, files opened for writing are created if they do not exist. 
+     */
+    fn open(& self, path : & str, readonly : bool) -> Result<Box<dyn TableStorage>, std::io::Error>;
+
+    /** Returns true if the table file at given path exists. 
+     */
+    fn exists(& self, path : & str) -> bool;
+
+    /** Removes the table file at given path. 
+     */
+    fn remove(& self, path : & str) -> Result<(), std::io::Error>;
+
+    /** Renames the table file, replacing the file at the new path if it exists. 
+     */
+    fn rename(& self, from : & str, to : & str) -> Result<(), std::io::Error>;
+
+    /** Returns the names (without the folder) of all files in given folder. 
+     */
+    fn list(& self, folder : & str) -> Result<Vec<String>, std::io::Error>;
+}
+
+/** The default storage backend, which keeps the table files in the local filesystem. 
+ */
+pub struct FileBackend {}
+
+impl StorageBackend for FileBackend {
+    fn open(& self, path : & str, readonly : bool) -> Result<Box<dyn TableStorage>, std::io::Error> {
+        let f = if readonly {
+            OpenOptions::new().read(true).open(path)?
+        } else {
+            OpenOptions::new().read(true).write(true).create(true).open(path)?
+        };
+        return Ok(Box::new(f));
+    }
+
+    fn exists(& self, path : & str) -> bool {
+        return std::path::Path::new(path).exists();
+    }
+
+    fn remove(& self, path : & str) -> Result<(), std::io::Error> {
+        return std::fs::remove_file(path);
+    }
+
+    fn rename(& self, from : & str, to : & str) -> Result<(), std::io::Error> {
+        return std::fs::rename(from, to);
+    }
+
+    fn list(& self, folder : & str) -> Result<Vec<String>, std::io::Error> {
+        let mut result = Vec::new();
+        for entry in std::fs::read_dir(folder)? {
+            result.push(entry?.file_name().to_string_lossy().to_string());
+        }
+        return Ok(result);
+    }
+}
+
+lazy_static! {
+    static ref STORAGE_BACKEND : std::sync::RwLock<std::sync::Arc<dyn StorageBackend>> = std::sync::RwLock::new(std::sync::Arc::new(FileBackend{}));
+}
+
+/** Sets the storage backend of the tables opened from now on. 
+ 
+    Tables that are already open keep using the backend they were opened with, so the backend should be set before any datastore is opened. 
+ */
+pub fn set_storage_backend(backend : std::sync::Arc<dyn StorageBackend>) {
+    *STORAGE_BACKEND.write().unwrap() = backend;
+}
+
+/** Opens the table file at given path via the current storage backend. 
+ 
+    When syncing is enabled (`--fsync`), files opened for writing are tracked so that `sync_tables` can make their data durable. Otherwise the files are given to the tables directly, avoiding the overhead of the tracking. 
+ */
+pub fn open_table_file(path : & str, readonly : bool) -> Result<Box<dyn TableStorage>, std::io::Error> {
+    let storage = STORAGE_BACKEND.read().unwrap().open(path, readonly)?;
+    if readonly || SETTINGS.fsync.is_none() {
+        return Ok(storage);
+    }
+    return Ok(Box::new(TrackedStorage{ table : std::sync::Arc::new(TrackedTable{ storage : std::sync::Mutex::new(storage), dirty : std::sync::atomic::AtomicBool::new(false) }) }));
+}
+
+/** Opens the file of a derived index (bloom filter, sorted, or bitmap index) at given path via the current storage backend. 
+ 
+    The derived indices can always be rebuilt from the tables, so unlike the table files they are never tracked for syncing. If `truncate` is set, the file is opened for writing and emptied. 
+ */
+fn open_index_file(path : & str, readonly : bool, truncate : bool) -> Result<Box<dyn TableStorage>, std::io::Error> {
+    let mut f = STORAGE_BACKEND.read().unwrap().open(path, readonly && ! truncate)?;
+    if truncate {
+        f.set_len(0)?;
+        f.seek(SeekFrom::Start(0))?;
+    }
+    return Ok(f);
+}
+
+/** Returns true if the table file at given path exists in the current storage backend. 
+ */
+pub fn table_file_exists(path : & str) -> bool {
+    return STORAGE_BACKEND.read().unwrap().exists(path);
+}
+
+/** Removes the table file at given path from the current storage backend. 
+ */
+pub fn remove_table_file(path : & str) -> Result<(), std::io::Error> {
+    return STORAGE_BACKEND.read().unwrap().remove(path);
+}
+
+/** Renames the table file in the current storage backend. 
+ */
+pub fn rename_table_file(from : & str, to : & str) -> Result<(), std::io::Error> {
+    return STORAGE_BACKEND.read().unwrap().rename(from, to);
+}
+
+/** Lists the files in given folder of the current storage backend. 
+ */
+pub fn list_table_files(folder : & str) -> Result<Vec<String>, std::io::Error> {
+    return STORAGE_BACKEND.read().unwrap().list(folder);
+}
+
+/** A table file opened for writing whose unsynced changes are tracked. 
+ */
+struct TrackedTable {
+    storage : std::sync::Mutex<Box<dyn TableStorage>>,
+    /** Set when the file is changed and cleared when it is synced. The file is registered in `UNSYNCED_TABLES` whenever the flag is set. 
+     */
+    dirty : std::sync::atomic::AtomicBool,
+}
+
+/* Tables that are closed, e.g. when a substore is dropped, are no longer reachable by the syncs, so their changes are synced when closing them instead. */
+impl Drop for TrackedTable {
+    fn drop(& mut self) {
+        if SETTINGS.fsync.is_some() && *self.dirty.get_mut() {
+            if let Ok(storage) = self.storage.get_mut() {
+                let _ = storage.sync();
+            }
+        }
+    }
+}
+
+/** The storage of tracked table files given to the tables. 
+ 
+    The file itself is shared with `UNSYNCED_TABLES` so that it can be synced by other threads than the one writing to it, hence the lock. The lock is only held for the duration of a single operation, which never waits for other files. 
+ */
+struct TrackedStorage {
+    table : std::sync::Arc<TrackedTable>,
+}
+
+impl TrackedStorage {
+    /** Records that given number of bytes has been written to the file, registering it for the next sync if it is not registered already. 
+     */
+    fn changed(& self, bytes : usize) {
+        UNSYNCED_BYTES.fetch_add(bytes as u64, std::sync::atomic::Ordering::SeqCst);
+        if ! self.table.dirty.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            UNSYNCED_TABLES.lock().unwrap().push(std::sync::Arc::downgrade(& self.table));
+        }
+    }
+}
+
+impl Read for TrackedStorage {
+    fn read(& mut self, buf : & mut [u8]) -> Result<usize, std::io::Error> {
+        return self.table.storage.lock().unwrap().read(buf);
+    }
+}
+
+impl Write for TrackedStorage {
+    fn write(& mut self, buf : & [u8]) -> Result<usize, std::io::Error> {
+        let written = self.table.storage.lock().unwrap().write(buf)?;
+        self.changed(written);
+        return Ok(written);
+    }
+
+    fn flush(& mut self) -> Result<(), std::io::Error> {
+        return self.table.storage.lock().unwrap().flush();
+    }
+}
+
+impl Seek for TrackedStorage {
+    fn seek(& mut self, pos : SeekFrom) -> Result<u64, std::io::Error> {
+        return self.table.storage.lock().unwrap().seek(pos);
+    }
+}
+
+impl TableStorage for TrackedStorage {
+    fn set_len(& mut self, len : u64) -> Result<(), std::io::Error> {
+        self.table.storage.lock().unwrap().set_len(len)?;
+        self.changed(0);
+        return Ok(());
+    }
+
+    fn len(& mut self) -> Result<u64, std::io::Error> {
+        return self.table.storage.lock().unwrap().len();
+    }
+
+    fn read_at(& mut self, offset : u64, buffer : & mut [u8]) -> Result<(), std::io::Error> {
+        return self.table.storage.lock().unwrap().read_at(offset, buffer);
+    }
+
+    fn append(& mut self, bytes : & [u8]) -> Result<u64, std::io::Error> {
+        let offset = self.table.storage.lock().unwrap().append(bytes)?;
+        self.changed(bytes.len());
+        return Ok(offset);
+    }
+
+    fn sync(& mut self) -> Result<(), std::io::Error> {
+        self.table.dirty.store(false, std::sync::atomic::Ordering::SeqCst);
+        return self.table.storage.lock().unwrap().sync();
+    }
+}
+
+lazy_static! {
+    /** Table files changed since they were last synced. Files closed in the meantime are skipped by the sync. 
+     */
+    static ref UNSYNCED_TABLES : std::sync::Mutex<Vec<std::sync::Weak<TrackedTable>>> = std::sync::Mutex::new(Vec::new());
+    /** Serializes the syncs so that a sync returns only after all changes made before it are durable, including those being synced by a concurrent sync. 
+     */
+    static ref SYNC_LOCK : std::sync::Mutex<()> = std::sync::Mutex::new(());
+}
+
+/** Number of bytes written to the table files since the last sync. 
+ */
+static UNSYNCED_BYTES : std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/** Returns the number of bytes written to the table files since they were last synced. 
+ */
+pub fn unsynced_bytes() -> u64 {
+    return UNSYNCED_BYTES.load(std::sync::atomic::Ordering::SeqCst);
+}
+
+/** Makes all changes made to the table files so far durable and returns the number of files synced. 
+ 
+    The files are synced one by one while the tables may still be written to, so the changes made during the sync may, or may not be synced by it, but they are registered for the next sync in either case. 
+ */
+pub fn sync_tables() -> Result<usize, std::io::Error> {
+    let _guard = SYNC_LOCK.lock().unwrap();
+    let bytes = UNSYNCED_BYTES.swap(0, std::sync::atomic::Ordering::SeqCst);
+    let tables = std::mem::take(& mut *UNSYNCED_TABLES.lock().unwrap());
+    let mut synced = 0;
+    for (i, table) in tables.iter().enumerate() {
+        if let Some(table) = table.upgrade() {
+            // cleared before the sync so that changes made during it register the file again
+            table.dirty.store(false, std::sync::atomic::Ordering::SeqCst);
+            if let Err(e) = table.storage.lock().unwrap().sync() {
+                // the files not synced yet are kept for the next sync
+                table.dirty.store(true, std::sync::atomic::Ordering::SeqCst);
+                UNSYNCED_TABLES.lock().unwrap().extend(tables[i..].iter().cloned());
+                UNSYNCED_BYTES.fetch_add(bytes, std::sync::atomic::Ordering::SeqCst);
+                return Err(e);
+            }
+            synced += 1;
+        }
+    }
+    return Ok(synced);
+}
+
+/** Scans the records of a file for repair. 
+ 
+    Calls the reader for each record in the file with the offset of the record. If reading a record fails and the record is the torn last record of the file, i.e. it was only partially written, the file is truncated at the start of the record and the truncation is reported. The record is torn only if it starts at, or after the last record known from the index and its reading ran into the end of the file. Any other record that cannot be read is a corruption in the middle of the file that cannot be repaired without silently losing the valid records that follow it, so an error is returned and the file is left untouched. 
+
+    Returns the number of repairs made. 
+ */
+fn repair_records(f : & mut dyn TableStorage, name : & str, last_indexed : Option<u64>, report : & mut dyn FnMut(String), reader : & mut dyn FnMut(& mut dyn TableStorage, u64) -> Result<(), std::io::Error>) -> Result<usize, std::io::Error> {
+    let end = f.seek(SeekFrom::End(0))?;
+    f.seek(SeekFrom::Start(0))?;
+    loop {
+        let offset = f.seek(SeekFrom::Current(0))?;
+        if offset == end {
+            return Ok(0);
+        }
+        if let Err(e) = reader(f, offset) {
+            let position = f.seek(SeekFrom::Current(0))?;
+            let followed_by_indexed = last_indexed.map(|x| offset < x).unwrap_or(false);
+            if ! followed_by_indexed && (e.kind() == std::io::ErrorKind::UnexpectedEof || position >= end) {
+                f.set_len(offset)?;
+                f.seek(SeekFrom::End(0))?;
+                report(format!("{}: truncated torn last record at offset {} ({} bytes)", name, offset, end - offset));
+                return Ok(1);
+            } else {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{}: corrupted record at offset {} of {} bytes, cannot repair without losing the records that follow: {}", name, offset, end, e)));
+            }
+        }
+    }
+}
+
+
+/** A part of a table that can be verified independently of the other parts. 
+ 
+    Stores are split into ranges of their files that start at the records found in their indices, so that the ranges can be verified in parallel. Once all ranges are verified, the index of the store is checked against the records found in them. Other tables are verified as a whole. 
+ */
+#[derive(Clone, Debug)]
+pub enum VerifyChunk {
+    Table,
+    Range{ path : String, checksums : String, start : u64, end : u64, ids : u64 },
+}
+
+/** Records found when verifying a range of a store. 
+ */
+#[derive(Default)]
+pub struct VerifiedChunk {
+    pub end : u64,
+    /** Offsets of the latest records of the ids found in the range. 
+     */
+    pub latest : HashMap<u64, u64>,
+    /** Back links of linked store records whose previous records are not in the range, as the id, offset of the record and the previous offset. 
+     */
+    pub backlinks : Vec<(u64, u64, u64)>,
+}
+
+/** Returns the number of chunks store file of given size should be split into for verification, so that there is at most given number of chunks, each at least of given size. 
+ */
+fn verification_chunks(end : u64, max_chunks : usize, min_size : u64) -> usize {
+    return ((end / min_size.max(1)) as usize).min(max_chunks).max(1);
+}
+
+/** Returns the sorted offsets of the records the index points to. 
+ 
+    These are the only offsets known to be record starts without reading the whole file. 
+ */
+fn indexed_records<ID : Id>(indexer : & mut Indexer<u64, ID>, end : u64) -> Vec<u64> {
+    let mut result = indexer.iter().map(|(_, offset)| offset).filter(|offset| *offset != u64::EMPTY && *offset < end).collect::<Vec<u64>>();
+    result.sort_unstable();
+    return result;
+}
+
+/** Returns true if for each of the evenly spaced boundaries of the chunks there is a known record start not too far after it. 
+ */
+fn covers_chunks(starts : & Vec<u64>, end : u64, chunks : usize) -> bool {
+    let spacing = end / chunks as u64;
+    return (1..chunks).all(|i| {
+        let target = spacing * i as u64;
+        return starts.get(starts.partition_point(|x| *x < target)).map(|x| *x < target + spacing / 2).unwrap_or(false);
+    });
+}
+
+/** Splits the store file into given number of chunks at the known record starts closest to evenly spaced boundaries. 
+ 
+    If there are not enough record starts known, fewer chunks are returned. 
+ */
+fn split_at_records<ID : Id>(indexer : & Indexer<u64, ID>, end : u64, chunks : usize, starts : & Vec<u64>) -> Vec<VerifyChunk> {
+    let path = format!("{}/{}.store", indexer.root, indexer.name);
+    let checksums = Checksums::path(& indexer.root, & indexer.name);
+    let ids = indexer.size;
+    let mut result = Vec::new();
+    let mut start = 0;
+    for i in 1..chunks {
+        let target = end / chunks as u64 * i as u64;
+        if let Some(boundary) = starts.get(starts.partition_point(|x| *x < target)) {
+            if *boundary > start {
+                result.push(VerifyChunk::Range{ path : path.clone(), checksums : checksums.clone(), start, end : *boundary, ids });
+                start = *boundary;
+            }
+        }
+    }
+    result.push(VerifyChunk::Range{ path, checksums, start, end, ids });
+    return result;
+}
+
+/** Checks that the index points to the latest records found in the verified chunks. 
+ 
+    Records appended after the chunks were verified are ignored. 
+ */
+fn verify_index<ID : Id>(indexer : & mut Indexer<u64, ID>, kind : & str, end : u64, latest_mappings : & HashMap<u64, u64>) -> Result<(), std::io::Error> {
+    for (id, offset) in indexer.iter() {
+        if offset == u64::EMPTY {
+            if latest_mappings.contains_key(& id.into()) {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{} index id {:?}, has empty index, but offset {} found in the store", kind, id, latest_mappings[& id.into()])));
+            }
+        } else if offset < end {
+            match latest_mappings.get(& id.into()) {
+                Some(found_offset) => {
+                    if offset != *found_offset {
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{} index id {:?}, has indexed offset {}, but offset {} found in store", kind, id, offset, found_offset)));
+                    }
+                },
+                None => {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{} index id {:?}, has indexed offset {} but none found", kind, id, offset)));
+                }
+            }
+        }
+    }
+    return Ok(());
+}
+
+lazy_static! {
+    /** Roots of the datastores whose stores create new checksums files if they do not have them yet. 
+     
+        Set by each datastore when it is opened, see `enable_checksums`. 
+     */
+    static ref CHECKSUMS_ENABLED : std::sync::Mutex<Vec<std::path::PathBuf>> = std::sync::Mutex::new(Vec::new());
+}
+
+/** Enables, or disables the creation of checksums files for stores of the datastore in given root opened from now on. 
+ 
+    Stores that already have their checksums file always keep updating it. Other datastores opened by the same process, such as the target of a merge, are not affected. 
+ */
+pub fn enable_checksums(root : & str, enabled : bool) {
+    let mut roots = CHECKSUMS_ENABLED.lock().unwrap();
+    roots.retain(|x| x != std::path::Path::new(root));
+    if enabled {
+        roots.push(std::path::PathBuf::from(root));
+    }
+}
+
+/** Returns true if checksums are enabled for the datastore that contains the given table root. 
+ */
+fn checksums_enabled(root : & str) -> bool {
+    return CHECKSUMS_ENABLED.lock().unwrap().iter().any(|x| std::path::Path::new(root).starts_with(x));
+}
+
+/** Returns the CRC32 checksum of given bytes. 
+ */
+pub fn checksum(bytes : & [u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(bytes);
+    return crc.sum();
+}
+
+/** Per-record checksums of a store. 
+ 
+    The checksums are kept in a `.crc` file next to the store file. Each entry consists of the offset of a record in the store and the CRC32 of the whole record, i.e. including its id (and back link for linked stores). Records are only ever appended to stores, so the entries are sorted by their offsets. 
+
+    Records written before the checksums file was created have no entries and are not checked, which keeps stores created without checksums readable. New checksums files are only created when checksums are enabled for the datastore. 
+ */
+pub struct Checksums {
+    f : Box<dyn TableStorage>,
+}
+
+impl Checksums {
+    /** Size of a single entry, the offset of the record followed by its checksum. 
+     */
+    pub const ENTRY_SIZE : u64 = 12;
+
+    /** Returns the path of the checksums file of given store. 
+     */
+    pub fn path(root : & str, name : & str) -> String {
+        return format!("{}/{}.crc", root, name);
+    }
+
+    /** Opens the checksums file of given store. 
+     
+        Returns None if the file does not exist and either the store is readonly, or checksums are not enabled. 
+     */
+    pub fn open(root : & str, name : & str, readonly : bool) -> Option<Checksums> {
+        let path = Checksums::path(root, name);
+        if readonly {
+            return open_table_file(& path, true).ok().map(|f| Checksums{ f });
+        }
+        if ! table_file_exists(& path) && ! checksums_enabled(root) {
+            return None;
+        }
+        let f = open_table_file(& path, false)
+            .expect(&format!("Error creating file {}", path));
+        return Some(Checksums{ f });
+    }
+
+    /** Appends the checksum of given record, which has just been written to the store at given offset. 
+     
+        The checksum is calculated from the bytes written rather than read back from the store, so that appending does not add reads to the writes. 
+     */
+    pub fn append(& mut self, offset : u64, record : & [u8]) {
+        let mut entry = Vec::with_capacity(Self::ENTRY_SIZE as usize);
+        entry.write_u64::<LittleEndian>(offset).unwrap();
+        entry.write_u32::<LittleEndian>(checksum(record)).unwrap();
+        self.f.append(& entry).unwrap();
+    }
+
+    /** Recalculates the checksum of a record that has been changed in place, if the record has a checksum. 
+     */
+    pub fn update(& mut self, store : & mut dyn TableStorage, offset : u64, end : u64) -> Result<(), std::io::Error> {
+        if let Some(index) = self.find(offset)? {
+            let crc = Checksums::record_checksum(store, offset, end)?;
+            self.f.seek(SeekFrom::Start(index * Self::ENTRY_SIZE + 8))?;
+            self.f.write_u32::<LittleEndian>(crc)?;
+        }
+        return Ok(());
+    }
+
+    pub fn savepoint(& mut self, name : & str, savepoint : & mut Savepoint) {
+        savepoint.add_entry(
+            format!("{}.crc", name),
+            self.f.seek(SeekFrom::End(0)).unwrap()
+        );
+    }
+
+    /** Reverts the checksums to given savepoint. 
+     
+        Savepoints created before the checksums file have no entry for it and the whole file is truncated as all its records are newer than the savepoint. 
+     */
+    pub fn revert_to_savepoint(& mut self, name : & str, savepoint : & Savepoint) {
+        self.f.set_len(savepoint.limit_for(& format!("{}.crc", name))).unwrap();
+    }
+
+    /** Repairs the checksums file after its store has been repaired. 
+     
+        Truncates a partially written trailing entry and any entries for records at, or after the end of the store, whose records have been truncated. Returns the number of repairs made. 
+     */
+    pub fn repair(& mut self, name : & str, end : u64, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        let mut repairs = 0;
+        let len = self.f.seek(SeekFrom::End(0))?;
+        let entries = len / Self::ENTRY_SIZE;
+        if len % Self::ENTRY_SIZE != 0 {
+            self.f.set_len(entries * Self::ENTRY_SIZE)?;
+            report(format!("{}.crc: truncated partial entry", name));
+            repairs += 1;
+        }
+        let mut valid = entries;
+        while valid > 0 {
+            self.f.seek(SeekFrom::Start((valid - 1) * Self::ENTRY_SIZE))?;
+            if self.f.read_u64::<LittleEndian>()? < end {
+                break;
+            }
+            valid -= 1;
+        }
+        if valid < entries {
+            self.f.set_len(valid * Self::ENTRY_SIZE)?;
+            report(format!("{}.crc: removed {} entries of truncated records", name, entries - valid));
+            repairs += 1;
+        }
+        return Ok(repairs);
+    }
+
+    /** Loads the checksums of all records before given end of the store. 
+     */
+    pub fn load(& mut self, end : u64) -> Result<Vec<(u64, u32)>, std::io::Error> {
+        let mut result = Vec::new();
+        let entries = self.entries()?;
+        self.f.seek(SeekFrom::Start(0))?;
+        let mut f = std::io::BufReader::new(& mut self.f);
+        for _ in 0..entries {
+            let offset = f.read_u64::<LittleEndian>()?;
+            let crc = f.read_u32::<LittleEndian>()?;
+            if offset >= end {
+                break;
+            }
+            result.push((offset, crc));
+        }
+        return Ok(result);
+    }
+
+    /** Loads the checksums of records in given range of the store whose checksums file is at given path. 
+     
+        Returns an empty vector if there is no checksums file. 
+     */
+    pub fn load_range(path : & str, start : u64, end : u64) -> Result<Vec<(u64, u32)>, std::io::Error> {
+        let mut result = Vec::new();
+        if ! table_file_exists(path) {
+            return Ok(result);
+        }
+        let mut checksums = Checksums{ f : open_table_file(path, true)? };
+        let entries = checksums.entries()?;
+        let mut index = checksums.lower_bound(start)?;
+        checksums.f.seek(SeekFrom::Start(index * Self::ENTRY_SIZE))?;
+        let mut f = std::io::BufReader::new(& mut checksums.f);
+        while index < entries {
+            let offset = f.read_u64::<LittleEndian>()?;
+            let crc = f.read_u32::<LittleEndian>()?;
+            if offset >= end {
+                break;
+            }
+            result.push((offset, crc));
+            index += 1;
+        }
+        return Ok(result);
+    }
+
+    /** Checks the record in given range of the store against the loaded checksums of its chunk. 
+     
+        The checksums are consumed as records are checked, any checksum whose offset is passed without a record found is an error. 
+     */
+    pub fn check_record(checksums : & mut std::iter::Peekable<std::vec::IntoIter<(u64, u32)>>, store : & mut dyn TableStorage, offset : u64, end : u64) -> Result<(), std::io::Error> {
+        if let Some((crc_offset, crc)) = checksums.peek().cloned() {
+            if crc_offset < offset {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Checksum for offset {} which is not a start of a record", crc_offset)));
+            } else if crc_offset == offset {
+                checksums.next();
+                let actual = Checksums::record_checksum(store, offset, end)?;
+                if actual != crc {
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Record at offset {} has checksum {:08x}, but {:08x} expected", offset, actual, crc)));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /** Returns the checksum of the record in given range of the store, leaving the store positioned at the end of the record. 
+     */
+    fn record_checksum(store : & mut dyn TableStorage, offset : u64, end : u64) -> Result<u32, std::io::Error> {
+        let mut buffer = vec![0; (end - offset) as usize];
+        store.read_at(offset, & mut buffer)?;
+        return Ok(checksum(& buffer));
+    }
+
+    fn entries(& mut self) -> Result<u64, std::io::Error> {
+        return Ok(self.f.seek(SeekFrom::End(0))? / Self::ENTRY_SIZE);
+    }
+
+    /** Returns the index of the first entry whose offset is not smaller than given offset. 
+     */
+    fn lower_bound(& mut self, offset : u64) -> Result<u64, std::io::Error> {
+        let (mut low, mut high) = (0, self.entries()?);
+        while low < high {
+            let mid = (low + high) / 2;
+            self.f.seek(SeekFrom::Start(mid * Self::ENTRY_SIZE))?;
+            if self.f.read_u64::<LittleEndian>()? < offset {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        return Ok(low);
+    }
+
+    /** Returns the index of the entry for given offset, if any. 
+     */
+    fn find(& mut self, offset : u64) -> Result<Option<u64>, std::io::Error> {
+        let index = self.lower_bound(offset)?;
+        if index < self.entries()? {
+            self.f.seek(SeekFrom::Start(index * Self::ENTRY_SIZE))?;
+            if self.f.read_u64::<LittleEndian>()? == offset {
+                return Ok(Some(index));
+            }
+        }
+        return Ok(None);
+    }
+}
 
 /** Marker trait for readonly datastore records. 
  
@@ -51,10 +782,10 @@ impl Id for u64 {
 pub trait Serializable {
     type Item;
 
-    fn serialize(f : & mut File, value : & Self::Item);
-    fn deserialize(f : & mut File) -> Self::Item;
+    fn serialize(f : & mut dyn TableStorage, value : & Self::Item);
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> Self::Item;
 
-    fn verify(f : & mut File) -> Result<Self::Item, std::io::Error>;
+    fn verify(f : & mut dyn TableStorage) -> Result<Self::Item, std::io::Error>;
 }
 
 pub trait FixedSizeSerializable : Serializable {
@@ -69,14 +800,14 @@ pub trait Indexable : FixedSizeSerializable + Eq {
  */
 impl Serializable for u64 {
     type Item = u64;
-    fn serialize(f : & mut File, value : & u64) {
+    fn serialize(f : & mut dyn TableStorage, value : & u64) {
         f.write_u64::<LittleEndian>(*value).unwrap();
     }
-    fn deserialize(f : & mut File) -> u64 {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> u64 {
         return f.read_u64::<LittleEndian>().unwrap();
     }
 
-    fn verify(f : & mut File) -> Result<u64, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<u64, std::io::Error> {
         return f.read_u64::<LittleEndian>();
     }
 
@@ -92,14 +823,14 @@ impl Indexable for u64 {
 
 impl Serializable for i64 {
     type Item = i64;
-    fn serialize(f : & mut File, value : & i64) {
+    fn serialize(f : & mut dyn TableStorage, value : & i64) {
         f.write_i64::<LittleEndian>(*value).unwrap();
     }
-    fn deserialize(f : & mut File) -> i64 {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> i64 {
         return f.read_i64::<LittleEndian>().unwrap();
     }
 
-    fn verify(f : & mut File) -> Result<i64, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<i64, std::io::Error> {
         return f.read_i64::<LittleEndian>();
     }
 }
@@ -110,14 +841,14 @@ impl FixedSizeSerializable for i64 {
 
 impl Serializable for u32 {
     type Item = u32;
-    fn serialize(f : & mut File, value : & u32) {
+    fn serialize(f : & mut dyn TableStorage, value : & u32) {
         f.write_u32::<LittleEndian>(*value).unwrap();
     }
-    fn deserialize(f : & mut File) -> u32 {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> u32 {
         return f.read_u32::<LittleEndian>().unwrap();
     }
 
-    fn verify(f : & mut File) -> Result<u32, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<u32, std::io::Error> {
         return f.read_u32::<LittleEndian>();
     }
 
@@ -129,14 +860,14 @@ impl FixedSizeSerializable for u32 {
 
 impl Serializable for u16 {
     type Item = u16;
-    fn serialize(f : & mut File, value : & u16) {
+    fn serialize(f : & mut dyn TableStorage, value : & u16) {
         f.write_u16::<LittleEndian>(*value).unwrap();
     }
-    fn deserialize(f : & mut File) -> u16 {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> u16 {
         return f.read_u16::<LittleEndian>().unwrap();
     }
 
-    fn verify(f : & mut File) -> Result<u16, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<u16, std::io::Error> {
         return f.read_u16::<LittleEndian>();
     }
 
@@ -148,13 +879,13 @@ impl FixedSizeSerializable for u16 {
 
 impl Serializable for u8 {
     type Item = u8;
-    fn serialize(f : & mut File, value : & u8) {
+    fn serialize(f : & mut dyn TableStorage, value : & u8) {
         f.write_u8(*value).unwrap();
     }
-    fn deserialize(f : & mut File) -> u8 {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> u8 {
         return f.read_u8().unwrap();
     }
-    fn verify(f : & mut File) -> Result<u8, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<u8, std::io::Error> {
         return f.read_u8();
     }
 
@@ -169,12 +900,12 @@ impl FixedSizeSerializable for u8 {
 impl Serializable for String {
     type Item = String;
 
-    fn serialize(f : & mut File, value : & String) {
+    fn serialize(f : & mut dyn TableStorage, value : & String) {
         f.write_u32::<LittleEndian>(value.len() as u32).unwrap();
         f.write(value.as_bytes()).unwrap();
     }
 
-    fn deserialize(f : & mut File) -> String {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> String {
         let len = f.read_u32::<LittleEndian>().unwrap();
         let mut buf = vec![0; len as usize];
         if f.read(& mut buf).unwrap() as u32 != len {
@@ -182,7 +913,7 @@ impl Serializable for String {
         }
         return String::from_utf8(buf).unwrap();
     }
-    fn verify(f : & mut File) -> Result<String, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<String, std::io::Error> {
         let len = u32::verify(f)?;
         if len as u64 > MAX_BUFFER_LENGTH {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid buffer size"));
@@ -244,8 +975,9 @@ impl<T : Table> Iterator for TableOwningIterator<T> {
     The indexer is usually not used alone, but as part of more complex structures. 
  */
 pub struct Indexer<T : Indexable + Serializable<Item = T> = u64, ID : Id = u64 > {
+    root : String,
     name : String, 
-    f : File, 
+    f : Box<dyn TableStorage>, 
     size : u64,
     why_oh_why : std::marker::PhantomData<(T, ID)>
 }
@@ -258,14 +990,9 @@ impl<T : Indexable + Serializable<Item = T>, ID : Id> TableImplementation for In
 
 impl<T : Indexable + Serializable<Item = T>, ID : Id> Indexer<T, ID> {
     pub fn new(root : & str, name : & str, readonly : bool) -> Indexer<T, ID> {
-        let mut f;
-        if readonly {
-            f = OpenOptions::new().read(true).open(format!("{}/{}.idx", root, name)).unwrap();
-        } else {
-            f = OpenOptions::new().read(true).write(true).create(true).open(format!("{}/{}.idx", root, name)).unwrap();
-        }
+        let mut f = open_table_file(& format!("{}/{}.idx", root, name), readonly).unwrap();
         let size = f.seek(SeekFrom::End(0)).unwrap() / T::SIZE;
-        return Indexer{ name : name.to_owned(), f, size, why_oh_why : std::marker::PhantomData{} };
+        return Indexer{ root : root.to_owned(), name : name.to_owned(), f, size, why_oh_why : std::marker::PhantomData{} };
     } 
 
     pub fn get(& mut self, id : ID) -> Option<T> {
@@ -300,6 +1027,23 @@ impl<T : Indexable + Serializable<Item = T>, ID : Id> Indexer<T, ID> {
         return self.size as usize;
     }
 
+    /** Returns the number of ids that are not empty. 
+     
+        Reads the whole index file, buffered, so that the indexer itself is not affected. 
+     */
+    pub fn count_non_empty(& self) -> usize {
+        let mut result = 0;
+        if let Ok(f) = open_table_file(& format!("{}/{}.idx", self.root, self.name), true) {
+            let mut reader = std::io::BufReader::new(f);
+            for _ in 0..self.size {
+                if T::deserialize(& mut reader) != T::EMPTY {
+                    result += 1;
+                }
+            }
+        }
+        return result;
+    }
+
     pub fn savepoint(& mut self, savepoint : & mut Savepoint) {
         savepoint.add_entry(
             format!("{}.idx", self.name),
@@ -317,6 +1061,50 @@ impl<T : Indexable + Serializable<Item = T>, ID : Id> Indexer<T, ID> {
         return IndexerIterator{indexer : self, id : 0, max_offset: u64::MAX};
     }
 
+    /** Repairs the index file. 
+     
+        Truncates partially written trailing index entry, if any. Returns the number of repairs made. 
+     */
+    pub fn repair(& mut self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        let end = self.f.seek(SeekFrom::End(0))?;
+        if end != self.size * T::SIZE {
+            self.f.set_len(self.size * T::SIZE)?;
+            self.f.seek(SeekFrom::End(0))?;
+            report(format!("{}.idx: truncated partial index entry ({} bytes)", self.name, end - self.size * T::SIZE));
+            return Ok(1);
+        }
+        return Ok(0);
+    }
+
+    /** Rebuilds the index so that it contains exactly the given values.
+     
+        Ids not present in the values are set to empty, ids past the end of the index are added. Returns the number of repairs made. 
+     */
+    pub (crate) fn rebuild(& mut self, mut values : HashMap<u64, T>, report : & mut dyn FnMut(String)) -> usize {
+        let mut repairs = 0;
+        for id in 0..self.size {
+            let expected = values.remove(& id).unwrap_or(T::EMPTY);
+            let differs = match self.get(ID::from(id)) {
+                Some(value) => value != expected,
+                None => expected != T::EMPTY,
+            };
+            if differs {
+                self.set(ID::from(id), & expected);
+                repairs += 1;
+            }
+        }
+        let mut missing : Vec<(u64, T)> = values.into_iter().collect();
+        missing.sort_by_key(|(id, _)| *id);
+        for (id, value) in missing {
+            self.set(ID::from(id), & value);
+            repairs += 1;
+        }
+        if repairs > 0 {
+            report(format!("{}.idx: {} index entries rebuilt", self.name, repairs));
+        }
+        return repairs;
+    }
+
     pub fn savepoint_iter(& mut self, sp : & Savepoint) -> IndexerIterator<T, ID> {
         let max_offset = sp.limit_for(& self.name);
         self.f.seek(SeekFrom::Start(0)).unwrap();
@@ -356,7 +1144,8 @@ impl<'a, T : Indexable + Serializable<Item = T>, ID : Id> Iterator for IndexerIt
  */
 pub struct Store<T : Serializable<Item = T>, ID : Id = u64> {
     pub (crate) indexer : Indexer<u64, ID>,
-    pub (crate) f : File,
+    pub (crate) f : Box<dyn TableStorage>,
+    checksums : Option<Checksums>,
     why_oh_why : std::marker::PhantomData<T>,
 }
 
@@ -400,17 +1189,12 @@ impl<T:Serializable<Item = T>, ID : Id> IntoIterator for Store<T, ID> {
 impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
 
     pub fn new(root : & str, name : & str, readonly : bool) -> Store<T, ID> {
-        let f;
-        if readonly {
-            f = OpenOptions::new().read(true).open(format!("{}/{}.store", root, name))
-                .expect(&format!("Error opening file {}/{}.store", root, name))
-        } else {
-            f = OpenOptions::new().read(true).write(true).create(true).open(format!("{}/{}.store", root, name))
-                .expect(&format!("Error creating file {}/{}.store", root, name));
-        }
+        let f = open_table_file(& format!("{}/{}.store", root, name), readonly)
+            .expect(&format!("Error opening file {}/{}.store", root, name));
         let mut result = Store{
             indexer : Indexer::new(root, name, readonly),
             f,
+            checksums : Checksums::open(root, name, readonly),
             why_oh_why : std::marker::PhantomData{}
         };
         LOG!("    {}: indices {}, size {}", name, result.indexer.len(), result.f.seek(SeekFrom::End(0)).unwrap());
@@ -428,12 +1212,18 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
             format!("{}.store",self.name()),
             self.f.seek(SeekFrom::End(0)).unwrap()
         );
+        if let Some(checksums) = self.checksums.as_mut() {
+            checksums.savepoint(self.indexer.name.as_str(), savepoint);
+        }
         self.indexer.savepoint(savepoint);
     }
 
     pub fn revert_to_savepoint(& mut self, savepoint : & Savepoint) {
         self.f.set_len(savepoint.limit_for(& format!("{}.store", self.name()))).unwrap();
         self.f.seek(SeekFrom::End(0)).unwrap();
+        if let Some(checksums) = self.checksums.as_mut() {
+            checksums.revert_to_savepoint(self.indexer.name.as_str(), savepoint);
+        }
         self.indexer.revert_to_savepoint(savepoint);
     }
 
@@ -447,43 +1237,91 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
         - if there is a missing slot in the index then no id is defined
      */
     pub fn verify(& mut self, checker : & mut dyn FnMut(T) -> Result<(), std::io::Error>) -> Result<(), std::io::Error> {
-        let end = self.f.seek(SeekFrom::End(0))?;
-        self.f.seek(SeekFrom::Start(0))?;
-        // first check all the items in the store, including the old ones
-        let mut latest_mappings = HashMap::<u64, u64>::new();
-        loop {
-            let offset = self.f.seek(SeekFrom::Current(0))?;
-            if offset == end {
-                break;
-            }
-            let id = self.f.read_u64::<LittleEndian>()?;
-            if id >= self.indexer.size {
-                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Store id {:?}, but only {} ids known at offset {}", ID::from(id), self.indexer.size, offset)));
-            }
-            latest_mappings.insert(id, offset);
-            let item = T::verify(& mut self.f)?;
-            checker(item)?;
-        }
-        // then check the index's integrity
-        for (id, offset) in self.indexer.iter() {
-            if offset == u64::EMPTY {
-                if latest_mappings.contains_key(& id.into()) {
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Store index id {:?}, has empty index, but offset {} found in the store", id, latest_mappings[& id.into()])));
+        let chunks = self.split_for_verification(1, u64::MAX);
+        let verified = Self::verify_chunk(& chunks[0], checker)?;
+        return self.verify_chunks_index(vec![verified]);
+    }
+
+    /** Splits the store into ranges that can be verified in parallel. 
+     
+        Returns at most the given number of ranges, each at least of given size, unless the store itself is smaller. The ranges start at the records in the index. 
+     */
+    pub fn split_for_verification(& mut self, max_chunks : usize, min_size : u64) -> Vec<VerifyChunk> {
+        let end = self.f.seek(SeekFrom::End(0)).unwrap();
+        let chunks = verification_chunks(end, max_chunks, min_size);
+        let starts = if chunks > 1 { indexed_records(& mut self.indexer, end) } else { Vec::new() };
+        return split_at_records(& self.indexer, end, chunks, & starts);
+    }
+
+    /** Verifies the records in given range of the store. 
+     
+        The store file is opened anew so that the ranges can be verified without locking the store. 
+     */
+    pub fn verify_chunk(chunk : & VerifyChunk, checker : & mut dyn FnMut(T) -> Result<(), std::io::Error>) -> Result<VerifiedChunk, std::io::Error> {
+        if let VerifyChunk::Range{path, checksums, start, end, ids} = chunk {
+            let mut f = open_table_file(path, true)?;
+            let mut checksums = Checksums::load_range(checksums, *start, *end)?.into_iter().peekable();
+            f.seek(SeekFrom::Start(*start))?;
+            let mut result = VerifiedChunk{ end : *end, ..Default::default() };
+            loop {
+                let offset = f.seek(SeekFrom::Current(0))?;
+                if offset == *end {
+                    break;
+                } else if offset > *end {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Store record ends at offset {} after the end of verified range {}", offset, end)));
                 }
-            } else {
-                match latest_mappings.get(& id.into()) {
-                    Some(found_offset) => {
-                        if offset != *found_offset {
-                            return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Store index id {:?}, has indexed offset {}, but offset {} found in store", id, offset, found_offset)));
-                        }
-                    },
-                    None => {
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Store index id {:?}, has indexed offset {} but none found", id, offset)));
-                    }
+                let id = f.read_u64::<LittleEndian>()?;
+                if id >= *ids {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Store id {:?}, but only {} ids known at offset {}", ID::from(id), ids, offset)));
                 }
+                result.latest.insert(id, offset);
+                let item = T::verify(& mut f)?;
+                let next = f.seek(SeekFrom::Current(0))?;
+                Checksums::check_record(& mut checksums, & mut f, offset, next)?;
+                checker(item)?;
+            }
+            if let Some((offset, _)) = checksums.next() {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Checksum for offset {} which is not a start of a record", offset)));
             }
+            return Ok(result);
+        } else {
+            panic!("Stores are verified in ranges");
+        }
+    }
+
+    /** Checks the store's index against the records found in all of its verified chunks. 
+     */
+    pub fn verify_chunks_index(& mut self, mut chunks : Vec<VerifiedChunk>) -> Result<(), std::io::Error> {
+        chunks.sort_by_key(|x| x.end);
+        let end = chunks.last().map(|x| x.end).unwrap_or(0);
+        let mut latest_mappings = HashMap::<u64, u64>::new();
+        for chunk in chunks {
+            latest_mappings.extend(chunk.latest);
         }
-        return Ok(());
+        return verify_index(& mut self.indexer, "Store", end, & latest_mappings);
+    }
+
+    /** Repairs the store. 
+     
+        Truncates partially written trailing record and index entry and then rebuilds the index from the records found in the store. Returns the number of repairs made, or an error if the store contains corruption that cannot be repaired without losing data. 
+     */
+    pub fn repair(& mut self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        let name = format!("{}.store", self.name());
+        let mut repairs = self.indexer.repair(report)?;
+        let end = self.f.seek(SeekFrom::End(0))?;
+        let last_indexed = indexed_records(& mut self.indexer, end).last().copied();
+        let mut latest_mappings = HashMap::<u64, u64>::new();
+        repairs += repair_records(& mut self.f, & name, last_indexed, report, & mut |f, offset| {
+            let id = f.read_u64::<LittleEndian>()?;
+            T::verify(f)?;
+            latest_mappings.insert(id, offset);
+            return Ok(());
+        })?;
+        if let Some(checksums) = self.checksums.as_mut() {
+            repairs += checksums.repair(self.indexer.name.as_str(), self.f.seek(SeekFrom::End(0))?, report)?;
+        }
+        repairs += self.indexer.rebuild(latest_mappings, report);
+        return Ok(repairs);
     }
 
     /** Returns true if there is a valid record for sgiven id. 
@@ -495,7 +1333,8 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
     /** Sets the value for given id. 
      */
     pub fn set(& mut self, id : ID, value : & T) {
-        self.indexer.set(id, & Self::write_record(& mut self.f, id, value));
+        let offset = Self::write_record(& mut self.f, self.checksums.as_mut(), id, value);
+        self.indexer.set(id, & offset);
     }
 
     /** Returns the number of indexed ids. 
@@ -533,7 +1372,7 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
      
         Returns tuple of the id associated with the record and the value stored. 
      */
-    fn read_record(f : & mut File) -> Option<(ID, T)> {
+    fn read_record(f : & mut dyn TableStorage) -> Option<(ID, T)> {
         if let Ok(id) = f.read_u64::<LittleEndian>() {
             return Some((ID::from(id), T::deserialize(f)));
         } else {
@@ -541,10 +1380,18 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
         }
     }
 
-    fn write_record(f : & mut File, id : ID, value : & T) -> u64 {
-        let offset = f.seek(SeekFrom::End(0)).unwrap();
-        f.write_u64::<LittleEndian>(id.into()).unwrap();
-        T::serialize(f, value);
+    /** Appends the record to the store and its checksum to the checksums, if any, returns the offset of the record. 
+     
+        The record is serialized in memory first, so that its checksum can be calculated from the written bytes. 
+     */
+    fn write_record(f : & mut dyn TableStorage, checksums : Option<& mut Checksums>, id : ID, value : & T) -> u64 {
+        let mut record = std::io::Cursor::new(Vec::new());
+        record.write_u64::<LittleEndian>(id.into()).unwrap();
+        T::serialize(& mut record, value);
+        let offset = f.append(record.get_ref()).unwrap();
+        if let Some(checksums) = checksums {
+            checksums.append(offset, record.get_ref());
+        }
         return offset;
     }
 }
@@ -552,13 +1399,13 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
 /** Latest store iterator does not support savepoints since the indices can be udpated. 
  */
 pub struct StoreIter<'a, T: Serializable<Item = T>, ID : Id> {
-    f : &'a mut File,
+    f : &'a mut dyn TableStorage,
     iiter : IndexerIterator<'a, u64,ID>,
     why_oh_why : std::marker::PhantomData<T>,
 }
 
 impl<'a, T : Serializable<Item = T>, ID : Id> StoreIter<'a, T, ID> {
-    fn new(f : &'a mut File, indexer : &'a mut Indexer<u64, ID>) -> StoreIter<'a, T, ID> {
+    fn new(f : &'a mut dyn TableStorage, indexer : &'a mut Indexer<u64, ID>) -> StoreIter<'a, T, ID> {
         return StoreIter{
             f : f,
             iiter : indexer.iter(),
@@ -607,7 +1454,8 @@ impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for StoreIterAll<'a, T, I
  */
 pub struct LinkedStore<T : Serializable<Item = T>, ID : Id = u64> {
     pub (crate) indexer : Indexer<u64, ID>,
-    pub (crate) f : File,
+    pub (crate) f : Box<dyn TableStorage>,
+    checksums : Option<Checksums>,
     why_oh_why : std::marker::PhantomData<T>,
 }
 
@@ -651,16 +1499,16 @@ impl<T: Serializable<Item = T>, ID : Id> IntoIterator for LinkedStore<T, ID> {
 
 impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
 
+    /** Maximal number of back links followed from the latest records when the linked store is split for verification. 
+     */
+    pub const SPLIT_BACKLINK_ROUNDS : usize = 8;
+
     pub fn new(root : & str, name : & str, readonly : bool) -> LinkedStore<T, ID> {
-        let f;
-        if readonly {
-            f = OpenOptions::new().read(true).open(format!("{}/{}.store", root, name)).unwrap();
-        } else {
-            f = OpenOptions::new().read(true).write(true).create(true).open(format!("{}/{}.store", root, name)).unwrap();
-        }
+        let f = open_table_file(& format!("{}/{}.store", root, name), readonly).unwrap();
         let mut result = LinkedStore{
             indexer : Indexer::new(root, name, readonly),
             f,
+            checksums : Checksums::open(root, name, readonly),
             why_oh_why : std::marker::PhantomData{}
         };
         LOG!("    {}: indices {}, size {}", name, result.indexer.len(), result.f.seek(SeekFrom::End(0)).unwrap());
@@ -678,12 +1526,18 @@ impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
             format!("{}.store", self.name()),
             self.f.seek(SeekFrom::End(0)).unwrap()
         );
+        if let Some(checksums) = self.checksums.as_mut() {
+            checksums.savepoint(self.indexer.name.as_str(), savepoint);
+        }
         self.indexer.savepoint(savepoint);
     }
 
     pub fn revert_to_savepoint(& mut self, savepoint : & Savepoint) {
         self.f.set_len(savepoint.limit_for(& format!("{}.store", self.name()))).unwrap();
         self.f.seek(SeekFrom::End(0)).unwrap();
+        if let Some(checksums) = self.checksums.as_mut() {
+            checksums.revert_to_savepoint(self.indexer.name.as_str(), savepoint);
+        }
         self.indexer.revert_to_savepoint(savepoint);
     }
 
@@ -698,67 +1552,165 @@ impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
         - if there is a missing slot in the index then no id is defined
      */
     pub fn verify(& mut self, checker : & mut dyn FnMut(T) -> Result<(), std::io::Error>) -> Result<(), std::io::Error> {
-        let end = self.f.seek(SeekFrom::End(0))?;
-        self.f.seek(SeekFrom::Start(0))?;
-        // first check all the items in the store, including the old ones
-        let mut latest_mappings = HashMap::<u64, u64>::new();
-        loop {
-            let offset = self.f.seek(SeekFrom::Current(0))?;
-            if offset == end {
-                break;
-            }
-            let id = self.f.read_u64::<LittleEndian>()?;
-            if id >= self.indexer.size {
-                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore id {:?}, but only {} ids known at offset {}", ID::from(id), self.indexer.size, offset)));
+        let chunks = self.split_for_verification(1, u64::MAX);
+        let verified = Self::verify_chunk(& chunks[0], checker)?;
+        return self.verify_chunks_index(vec![verified]);
+    }
+
+    /** Splits the linked store into ranges that can be verified in parallel. 
+     
+        Returns at most the given number of ranges, each at least of given size, unless the store itself is smaller. The ranges start at the records in the index, but as the latest records of frequently updated ids tend to be at the end of the store, the back links of the records are followed to older records until the records found cover the store evenly enough, or for at most `SPLIT_BACKLINK_ROUNDS` records back. 
+     */
+    pub fn split_for_verification(& mut self, max_chunks : usize, min_size : u64) -> Vec<VerifyChunk> {
+        let end = self.f.seek(SeekFrom::End(0)).unwrap();
+        let chunks = verification_chunks(end, max_chunks, min_size);
+        let mut starts = Vec::new();
+        if chunks > 1 {
+            starts = indexed_records(& mut self.indexer, end);
+            let mut previous = starts.clone();
+            for _ in 0..Self::SPLIT_BACKLINK_ROUNDS {
+                if previous.is_empty() || covers_chunks(& starts, end, chunks) {
+                    break;
+                }
+                previous = previous.iter().filter_map(|offset| {
+                    self.f.seek(SeekFrom::Start(offset + 8)).ok()?;
+                    return self.f.read_u64::<LittleEndian>().ok();
+                }).filter(|offset| *offset != u64::EMPTY && *offset < end).collect();
+                starts.extend(previous.iter());
+                starts.sort_unstable();
             }
-            let previous_offset = self.f.read_u64::<LittleEndian>()?;
-            if previous_offset == u64::EMPTY {
-                if latest_mappings.contains_key(& id) {
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore index id {:?} at offset {} has empty backlink, but offset {} found", ID::from(id), offset, latest_mappings[& id])));
+            self.f.seek(SeekFrom::End(0)).unwrap();
+        }
+        return split_at_records(& self.indexer, end, chunks, & starts);
+    }
+
+    /** Verifies the records in given range of the linked store. 
+     
+        Back links pointing to records in the range are checked immediately, the remaining back links are returned and checked together with the index. 
+     */
+    pub fn verify_chunk(chunk : & VerifyChunk, checker : & mut dyn FnMut(T) -> Result<(), std::io::Error>) -> Result<VerifiedChunk, std::io::Error> {
+        if let VerifyChunk::Range{path, checksums, start, end, ids} = chunk {
+            let mut f = open_table_file(path, true)?;
+            let mut checksums = Checksums::load_range(checksums, *start, *end)?.into_iter().peekable();
+            f.seek(SeekFrom::Start(*start))?;
+            let mut result = VerifiedChunk{ end : *end, ..Default::default() };
+            loop {
+                let offset = f.seek(SeekFrom::Current(0))?;
+                if offset == *end {
+                    break;
+                } else if offset > *end {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore record ends at offset {} after the end of verified range {}", offset, end)));
                 }
-            } else {
-                match latest_mappings.get(& id) {
+                let id = f.read_u64::<LittleEndian>()?;
+                if id >= *ids {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore id {:?}, but only {} ids known at offset {}", ID::from(id), ids, offset)));
+                }
+                let previous_offset = f.read_u64::<LittleEndian>()?;
+                match result.latest.get(& id) {
                     Some(found_offset) => {
                         if previous_offset != *found_offset {
-                            return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore index id {:?} at offset {} has previous offset {} but offset {} found in the store", ID::from(id), offset, previous_offset, found_offset)));
+                            return Err(LinkedStore::<T, ID>::backlink_error(id, offset, previous_offset, Some(*found_offset)));
                         }
                     },
                     None => {
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore index id {:?} at offset {} has previous offset {} but no offset found in the store", ID::from(id), offset, previous_offset)));
+                        result.backlinks.push((id, offset, previous_offset));
                     }
                 }
+                result.latest.insert(id, offset);
+                let item = T::verify(& mut f)?;
+                let next = f.seek(SeekFrom::Current(0))?;
+                Checksums::check_record(& mut checksums, & mut f, offset, next)?;
+                checker(item)?;
             }
-            latest_mappings.insert(id, offset);
-            let item = T::verify(& mut self.f)?;
-            checker(item)?;
+            if let Some((offset, _)) = checksums.next() {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Checksum for offset {} which is not a start of a record", offset)));
+            }
+            return Ok(result);
+        } else {
+            panic!("Linked stores are verified in ranges");
         }
-        // then check the index's integrity
-        for (id, offset) in self.indexer.iter() {
-            if offset == u64::EMPTY {
-                if latest_mappings.contains_key(& id.into()) {
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore index id {:?}, has empty index, but offset {} found in the store", id, latest_mappings[& id.into()])));
+    }
+
+    /** Checks the back links that point outside of their chunks and the linked store's index against the records found in all of its verified chunks. 
+     */
+    pub fn verify_chunks_index(& mut self, mut chunks : Vec<VerifiedChunk>) -> Result<(), std::io::Error> {
+        chunks.sort_by_key(|x| x.end);
+        let end = chunks.last().map(|x| x.end).unwrap_or(0);
+        let mut latest_mappings = HashMap::<u64, u64>::new();
+        for chunk in chunks {
+            for (id, offset, previous_offset) in chunk.backlinks {
+                let found_offset = latest_mappings.get(& id).cloned();
+                if found_offset != if previous_offset == u64::EMPTY { None } else { Some(previous_offset) } {
+                    return Err(LinkedStore::<T, ID>::backlink_error(id, offset, previous_offset, found_offset));
                 }
-            } else {
-                match latest_mappings.get(& id.into()) {
-                    Some(found_offset) => {
-                        if offset != *found_offset {
-                            return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore index id {:?}, has indexed offset {}, but offset {} found in store", id, offset, found_offset)));
-                        }
-                    },
-                    None => {
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore index id {:?}, has indexed offset {} but none found", id, offset)));
-                    }
+            }
+            latest_mappings.extend(chunk.latest);
+        }
+        return verify_index(& mut self.indexer, "LinkedStore", end, & latest_mappings);
+    }
+
+    fn backlink_error(id : u64, offset : u64, previous_offset : u64, found_offset : Option<u64>) -> std::io::Error {
+        match found_offset {
+            Some(found_offset) if previous_offset == u64::EMPTY => return std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore index id {:?} at offset {} has empty backlink, but offset {} found", ID::from(id), offset, found_offset)),
+            Some(found_offset) => return std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore index id {:?} at offset {} has previous offset {} but offset {} found in the store", ID::from(id), offset, previous_offset, found_offset)),
+            None => return std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore index id {:?} at offset {} has previous offset {} but no offset found in the store", ID::from(id), offset, previous_offset)),
+        }
+    }
+
+    /** Repairs the linked store. 
+     
+        Like store repair, but also fixes the back links of the records so that they point to the previous record of the same id found in the store. Returns the number of repairs made, or an error if the store contains corruption that cannot be repaired without losing data. 
+     */
+    pub fn repair(& mut self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        let name = format!("{}.store", self.name());
+        let mut repairs = self.indexer.repair(report)?;
+        let mut latest_mappings = HashMap::<u64, u64>::new();
+        let mut backlinks = Vec::<(u64, u64, u64)>::new();
+        let end = self.f.seek(SeekFrom::End(0))?;
+        let last_indexed = indexed_records(& mut self.indexer, end).last().copied();
+        repairs += repair_records(& mut self.f, & name, last_indexed, report, & mut |f, offset| {
+            let id = f.read_u64::<LittleEndian>()?;
+            let previous_offset = f.read_u64::<LittleEndian>()?;
+            T::verify(f)?;
+            let expected = latest_mappings.get(& id).map(|x| *x).unwrap_or(u64::EMPTY);
+            if previous_offset != expected {
+                backlinks.push((offset, expected, f.seek(SeekFrom::Current(0))?));
+            }
+            latest_mappings.insert(id, offset);
+            return Ok(());
+        })?;
+        if let Some(checksums) = self.checksums.as_mut() {
+            repairs += checksums.repair(self.indexer.name.as_str(), self.f.seek(SeekFrom::End(0))?, report)?;
+        }
+        if ! backlinks.is_empty() {
+            for (offset, previous_offset, end) in backlinks.iter() {
+                self.f.seek(SeekFrom::Start(offset + 8))?;
+                self.f.write_u64::<LittleEndian>(*previous_offset)?;
+                // the fixed records have changed so their checksums must be updated too
+                if let Some(checksums) = self.checksums.as_mut() {
+                    checksums.update(& mut self.f, *offset, *end)?;
                 }
             }
+            self.f.seek(SeekFrom::End(0))?;
+            report(format!("{}: {} back links fixed", name, backlinks.len()));
+            repairs += backlinks.len();
         }
-        return Ok(());
+        repairs += self.indexer.rebuild(latest_mappings, report);
+        return Ok(repairs);
+    }
+
+    /** Returns true if there is at least one record for given id. 
+     */
+    pub fn has(& mut self, id : ID) -> bool {
+        return self.indexer.get(id).is_some();
     }
 
     /** Sets the value for given id. 
      */
     pub fn set(& mut self, id : ID, value : & T) {
         let previous_offset = self.indexer.get(id);
-        self.indexer.set(id, & Self::write_record(& mut self.f, id, previous_offset, value));
+        let offset = Self::write_record(& mut self.f, self.checksums.as_mut(), id, previous_offset, value);
+        self.indexer.set(id, & offset);
     }
 
     /** Returns the number of indexed ids. 
@@ -805,7 +1757,7 @@ impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
      
         Returns tuple of the id associated with the record, offset of the previous record associated with the id and the value stored. 
      */
-    fn read_record(f : & mut File) -> Option<(ID, Option<u64>, T)> {
+    fn read_record(f : & mut dyn TableStorage) -> Option<(ID, Option<u64>, T)> {
         if let Ok(id) = f.read_u64::<LittleEndian>() {
             let previous_offset = f.read_u64::<LittleEndian>().unwrap();
             return Some((ID::from(id), if previous_offset == u64::EMPTY { None } else { Some(previous_offset) }, T::deserialize(f)));
@@ -814,26 +1766,29 @@ impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
         }
     }
 
-    fn write_record(f : & mut File, id : ID, previous_offset : Option<u64>, value : & T) -> u64 {
-        let offset = f.seek(SeekFrom::End(0)).unwrap();
-        f.write_u64::<LittleEndian>(id.into()).unwrap();
-        match previous_offset {
-            Some(offset) => f.write_u64::<LittleEndian>(offset).unwrap(),
-            None => f.write_u64::<LittleEndian>(u64::EMPTY).unwrap(),
+    /** Appends the record to the store and its checksum to the checksums, if any, returns the offset of the record. 
+     */
+    fn write_record(f : & mut dyn TableStorage, checksums : Option<& mut Checksums>, id : ID, previous_offset : Option<u64>, value : & T) -> u64 {
+        let mut record = std::io::Cursor::new(Vec::new());
+        record.write_u64::<LittleEndian>(id.into()).unwrap();
+        record.write_u64::<LittleEndian>(previous_offset.unwrap_or(u64::EMPTY)).unwrap();
+        T::serialize(& mut record, value);
+        let offset = f.append(record.get_ref()).unwrap();
+        if let Some(checksums) = checksums {
+            checksums.append(offset, record.get_ref());
         }
-        T::serialize(f, value);
         return offset;
     }
 }
 
 pub struct LinkedStoreIter<'a, T: Serializable<Item = T>, ID : Id> {
-    f : &'a mut File,
+    f : &'a mut dyn TableStorage,
     iiter : IndexerIterator<'a, u64, ID>,
     why_oh_why : std::marker::PhantomData<T>,
 }
 
 impl<'a, T : Serializable<Item = T>, ID : Id> LinkedStoreIter<'a, T, ID> {
-    fn new(f : &'a mut File, indexer : &'a mut Indexer<u64, ID>) -> LinkedStoreIter<'a, T, ID> {
+    fn new(f : &'a mut dyn TableStorage, indexer : &'a mut Indexer<u64, ID>) -> LinkedStoreIter<'a, T, ID> {
         return LinkedStoreIter{
             f : f,
             iiter : indexer.iter(),
@@ -862,50 +1817,505 @@ pub struct LinkedStoreIterAll<'a, T : Serializable<Item = T>, ID : Id> {
     max_offset : u64,
 }
 
-impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for LinkedStoreIterAll<'a, T, ID> {
-    type Item = (ID, T);
+impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for LinkedStoreIterAll<'a, T, ID> {
+    type Item = (ID, T);
+
+    fn next(& mut self) -> Option<(ID, T)> {
+        if self.store.f.seek(SeekFrom::Current(0)).unwrap() >= self.max_offset {
+            return None;
+        } else {
+            match LinkedStore::<T, ID>::read_record(& mut self.store.f) {
+                Some((id, _, value)) => Some((id, value)),
+                None => None
+            }
+        }
+    }
+}
+
+pub struct LinkedStoreIterId<'a, T : Serializable<Item = T>, ID : Id> {
+    store : &'a mut LinkedStore<T, ID>,
+    offset : Option<u64>,
+}
+
+impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for LinkedStoreIterId<'a, T, ID> {
+    type Item = T;
+
+    fn next(& mut self) -> Option<T> {
+        match self.offset {
+            Some(offset) => {
+                self.store.f.seek(SeekFrom::Start(offset)).unwrap();
+                let (_, previous_offset, value) = LinkedStore::<T, ID>::read_record(& mut self.store.f).unwrap(); 
+                self.offset = previous_offset;
+                return Some(value);
+            }, 
+            None => None
+        }
+    }
+}
+
+/** A bloom filter over the values of a mapping. 
+ 
+    The filter answers whether a value is definitely not in the mapping without the mapping being loaded, which is the common case for new commits and hashes. Positive answers may be false and must be confirmed by the mapping itself. The filter is sized for a capacity of values, with 10 bits and 7 hash functions per value the false positive rate stays below 1% until the capacity is reached. 
+ */
+struct BloomFilter {
+    bits : Vec<u64>,
+    capacity : u64,
+}
+
+impl BloomFilter {
+    const BITS_PER_VALUE : u64 = 10;
+    const HASHES : u64 = 7;
+    const MIN_CAPACITY : u64 = 1 << 20;
+
+    /** Creates an empty filter that can hold at least given number of values. 
+     */
+    fn new(capacity : u64) -> BloomFilter {
+        let capacity = std::cmp::max(capacity, BloomFilter::MIN_CAPACITY);
+        let words = (capacity * BloomFilter::BITS_PER_VALUE + 63) / 64;
+        return BloomFilter{ bits : vec![0; words as usize], capacity };
+    }
+
+    fn num_bits(& self) -> u64 {
+        return self.bits.len() as u64 * 64;
+    }
+
+    /** Returns the two base hashes of the value that are combined to obtain the positions of its bits. 
+     
+        The values are hashed with FNV-1a, which unlike the default hasher of rust is guaranteed to produce the same results across runs, so that the filters can be persisted. 
+     */
+    fn base_hashes<T : Hash>(value : & T) -> (u64, u64) {
+        let h1 = stable_hash(value);
+        // the second hash is derived from the first one by the splitmix64 finalizer
+        let mut h2 = h1.wrapping_add(0x9e3779b97f4a7c15);
+        h2 = (h2 ^ (h2 >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        h2 = (h2 ^ (h2 >> 27)).wrapping_mul(0x94d049bb133111eb);
+        h2 = h2 ^ (h2 >> 31);
+        return (h1, h2 | 1);
+    }
+
+    fn insert<T : Hash>(& mut self, value : & T) {
+        let (h1, h2) = BloomFilter::base_hashes(value);
+        let n = self.num_bits();
+        for i in 0..BloomFilter::HASHES {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % n;
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    fn may_contain<T : Hash>(& self, value : & T) -> bool {
+        let (h1, h2) = BloomFilter::base_hashes(value);
+        let n = self.num_bits();
+        for i in 0..BloomFilter::HASHES {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % n;
+            if self.bits[(bit / 64) as usize] & (1 << (bit % 64)) == 0 {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    /** Reads the filter from given file. 
+     
+        The file starts with the capacity of the filter and the number of mapping values it contains, followed by the bits of the filter. Returns the filter and the number of values, or `None` if the file does not exist, or is not valid. 
+     */
+    fn read(filename : & str) -> Option<(BloomFilter, u64)> {
+        let mut f = std::io::BufReader::new(open_index_file(filename, true, false).ok()?);
+        let capacity = f.read_u64::<LittleEndian>().ok()?;
+        let values = f.read_u64::<LittleEndian>().ok()?;
+        let mut result = BloomFilter::new(capacity);
+        if result.capacity != capacity {
+            return None;
+        }
+        f.read_u64_into::<LittleEndian>(& mut result.bits).ok()?;
+        return Some((result, values));
+    }
+
+    /** Writes the filter, which contains given number of mapping values, to given file. 
+     */
+    fn write(& self, filename : & str, values : u64) -> Result<(), std::io::Error> {
+        let mut f = std::io::BufWriter::new(open_index_file(filename, false, true)?);
+        f.write_u64::<LittleEndian>(self.capacity)?;
+        f.write_u64::<LittleEndian>(values)?;
+        for word in self.bits.iter() {
+            f.write_u64::<LittleEndian>(*word)?;
+        }
+        return f.flush();
+    }
+}
+
+/** The FNV-1a hasher used by the bloom filters. 
+ */
+struct FnvHasher(u64);
+
+impl Hasher for FnvHasher {
+    fn finish(& self) -> u64 {
+        return self.0;
+    }
+
+    fn write(& mut self, bytes : & [u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ (*b as u64)).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/** Sorted index of mapping values stored on disk. 
+ 
+    The index file starts with the number of mapping values it covers, followed by records of the stable hash of a value and its id, sorted by the hash. Ids of a value are found by binary search of its hash and the values of the ids must then be compared since different values may have the same hash. The index is built by external sorting of the mapping in runs that fit the memory budget and then updated by merging the values added since. 
+ */
+struct SortedIndex {
+    f : Box<dyn TableStorage>,
+    indexed : u64,
+    records : u64,
+}
+
+impl SortedIndex {
+    const HEADER_SIZE : u64 = 8;
+    const RECORD_SIZE : u64 = 16;
+
+    /** Opens index in given file, returns `None` if the file does not exist, or is not valid. 
+     */
+    fn open(filename : & str) -> Option<SortedIndex> {
+        let mut f = open_index_file(filename, true, false).ok()?;
+        let len = f.seek(SeekFrom::End(0)).ok()?;
+        if len < SortedIndex::HEADER_SIZE || (len - SortedIndex::HEADER_SIZE) % SortedIndex::RECORD_SIZE != 0 {
+            return None;
+        }
+        f.seek(SeekFrom::Start(0)).ok()?;
+        let indexed = f.read_u64::<LittleEndian>().ok()?;
+        return Some(SortedIndex{ f, indexed, records : (len - SortedIndex::HEADER_SIZE) / SortedIndex::RECORD_SIZE });
+    }
+
+    fn record(& mut self, index : u64) -> Result<(u64, u64), std::io::Error> {
+        self.f.seek(SeekFrom::Start(SortedIndex::HEADER_SIZE + index * SortedIndex::RECORD_SIZE))?;
+        let hash = self.f.read_u64::<LittleEndian>()?;
+        let id = self.f.read_u64::<LittleEndian>()?;
+        return Ok((hash, id));
+    }
+
+    /** Returns the ids of all values with given hash. 
+     */
+    fn find(& mut self, hash : u64) -> Result<Vec<u64>, std::io::Error> {
+        let mut lo = 0;
+        let mut hi = self.records;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.record(mid)?.0 < hash {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let mut result = Vec::new();
+        while lo < self.records {
+            let (h, id) = self.record(lo)?;
+            if h != hash {
+                break;
+            }
+            result.push(id);
+            lo += 1;
+        }
+        return Ok(result);
+    }
+
+    /** Returns iterator over the records of given index, or run file, starting at given offset. 
+     */
+    fn records_of(filename : & str, offset : u64) -> Result<Box<dyn Iterator<Item = (u64, u64)>>, std::io::Error> {
+        let mut f = open_index_file(filename, true, false)?;
+        f.seek(SeekFrom::Start(offset))?;
+        let mut reader = std::io::BufReader::with_capacity(1 << 20, f);
+        return Ok(Box::new(std::iter::from_fn(move || {
+            let hash = reader.read_u64::<LittleEndian>().ok()?;
+            let id = reader.read_u64::<LittleEndian>().ok()?;
+            return Some((hash, id));
+        })));
+    }
+
+    /** Merges the given sorted runs of records into a new index covering given number of mapping values. 
+     
+        The index is written to a temporary file first which then replaces the index file, so that an interrupted merge keeps the previous index valid. 
+     */
+    fn write(filename : & str, indexed : u64, runs : Vec<Box<dyn Iterator<Item = (u64, u64)>>>) -> Result<(), std::io::Error> {
+        let tmp = format!("{}.tmp", filename);
+        {
+            let mut f = std::io::BufWriter::with_capacity(1 << 20, open_index_file(& tmp, false, true)?);
+            f.write_u64::<LittleEndian>(indexed)?;
+            let mut runs = runs;
+            let mut heap = BinaryHeap::new();
+            for (i, run) in runs.iter_mut().enumerate() {
+                if let Some(record) = run.next() {
+                    heap.push(std::cmp::Reverse((record, i)));
+                }
+            }
+            while let Some(std::cmp::Reverse(((hash, id), i))) = heap.pop() {
+                f.write_u64::<LittleEndian>(hash)?;
+                f.write_u64::<LittleEndian>(id)?;
+                if let Some(record) = runs[i].next() {
+                    heap.push(std::cmp::Reverse((record, i)));
+                }
+            }
+            f.flush()?;
+        }
+        return rename_table_file(& tmp, filename);
+    }
+}
+
+/** Looks up given value in the persisted sorted index of a mapping stored in given file. 
+ 
+    Returns the number of mapping values the index covers and the ids of the covered values that have the same hash as the value, which must be compared with the value itself. Returns `None` if the mapping has no valid index, or the index cannot be read. Allows the mapping readers, which never load the mapping, to find values without scanning the whole mapping file. 
+ */
+pub fn sorted_index_find<T : Hash>(filename : & str, value : & T) -> Option<(u64, Vec<u64>)> {
+    let mut index = SortedIndex::open(filename)?;
+    let ids = index.find(stable_hash(value)).ok()?;
+    return Some((index.indexed, ids));
+}
+
+/** Returns a hash of the value that is the same across runs, unlike the default hasher of rust, so that it can be persisted. 
+ */
+fn stable_hash<T : Hash>(value : & T) -> u64 {
+    let mut hasher = FnvHasher(0xcbf29ce484222325);
+    value.hash(& mut hasher);
+    return hasher.finish();
+}
+
+/** Persistent index from values to 64 bit bitmaps. 
+ 
+    The index is an open addressing hash table stored in a single file so that it does not have to be loaded in memory. The file starts with the capacity of the table and the number of values in it, followed by the slots, each containing the value and its bitmap. Empty slots have no bits set. Bits are only ever added to the bitmaps. When the table becomes half full, its capacity is doubled and the values are moved to their new slots in place. 
+
+    Unlike the other tables the index is updated in place and is therefore not part of savepoints. While the index is being built, grown, or while its owner holds values not yet written to it (see `begin_batch`), an `.incomplete` marker file is kept next to it so that an index left behind by an interrupted process can be told apart from a complete one and rebuilt. 
+ */
+pub struct BitmapIndex<T : FixedSizeSerializable<Item = T> + Eq + Hash> {
+    name : String,
+    filename : String,
+    f : Box<dyn TableStorage>,
+    capacity : u64,
+    len : u64,
+    batches : usize,
+    why_oh_why : std::marker::PhantomData<T>,
+}
+
+impl<T : FixedSizeSerializable<Item = T> + Eq + Hash> BitmapIndex<T> {
+    const HEADER_SIZE : u64 = 16;
+    const INITIAL_CAPACITY : u64 = 1 << 16;
+
+    /** Opens the index, creating it if it does not exist. 
+     */
+    pub fn new(root : & str, name : & str) -> Result<BitmapIndex<T>, std::io::Error> {
+        let filename = format!("{}/{}.bitmaps", root, name);
+        let f = if table_file_exists(& filename) {
+            open_index_file(& filename, false, false)?
+        } else {
+            BitmapIndex::<T>::create(& filename, BitmapIndex::<T>::INITIAL_CAPACITY)?
+        };
+        let result = BitmapIndex::from_file(name, filename, f)?;
+        LOG!("    {}: values {}, capacity {}", name, result.len, result.capacity);
+        return Ok(result);
+    }
+
+    /** Opens existing index for reading, returns `None` if the index does not exist. 
+     */
+    pub fn open(root : & str, name : & str) -> Option<BitmapIndex<T>> {
+        let filename = format!("{}/{}.bitmaps", root, name);
+        let f = open_index_file(& filename, true, false).ok()?;
+        return BitmapIndex::from_file(name, filename, f).ok();
+    }
+
+    fn from_file(name : & str, filename : String, f : Box<dyn TableStorage>) -> Result<BitmapIndex<T>, std::io::Error> {
+        let mut f = f;
+        f.seek(SeekFrom::Start(0))?;
+        let capacity = f.read_u64::<LittleEndian>()?;
+        let len = f.read_u64::<LittleEndian>()?;
+        return Ok(BitmapIndex{ name : name.to_owned(), filename, f, capacity, len, batches : 0, why_oh_why : std::marker::PhantomData{} });
+    }
+
+    /** Returns true if the index exists and is complete, i.e. it is not being built or grown and it was not left with values missing by an interrupted process. 
+     */
+    pub fn is_complete(root : & str, name : & str) -> bool {
+        let filename = format!("{}/{}.bitmaps", root, name);
+        return table_file_exists(& filename) && ! table_file_exists(& BitmapIndex::<T>::marker_filename(& filename));
+    }
+
+    /** Marks the index in given root as incomplete so that it is rebuilt when the datastore is opened next time. 
+     
+        Used when the tables the index is built from are replaced while the datastore is not open, such as when a substore is unpacked, or migrated. 
+     */
+    pub fn invalidate(root : & str, name : & str) -> Result<(), std::io::Error> {
+        open_index_file(& BitmapIndex::<T>::marker_filename(& format!("{}/{}.bitmaps", root, name)), false, true)?;
+        return Ok(());
+    }
+
+    fn marker_filename(filename : & str) -> String {
+        return format!("{}.incomplete", filename);
+    }
+
+    /** Marks the index as incomplete until the matching `end_batch` call. 
+     
+        Batches nest, the index is complete again only when all of them are ended. 
+     */
+    pub fn begin_batch(& mut self) -> Result<(), std::io::Error> {
+        if self.batches == 0 {
+            open_index_file(& BitmapIndex::<T>::marker_filename(& self.filename), false, true)?;
+        }
+        self.batches += 1;
+        return Ok(());
+    }
+
+    /** Ends a batch started by `begin_batch`. 
+     */
+    pub fn end_batch(& mut self) -> Result<(), std::io::Error> {
+        assert!(self.batches > 0, "No batch to end");
+        self.batches -= 1;
+        if self.batches == 0 {
+            remove_table_file(& BitmapIndex::<T>::marker_filename(& self.filename))?;
+        }
+        return Ok(());
+    }
+
+    /** Creates an empty index file with given capacity. 
+     */
+    fn create(filename : & str, capacity : u64) -> Result<Box<dyn TableStorage>, std::io::Error> {
+        let mut f = open_index_file(filename, false, true)?;
+        f.write_u64::<LittleEndian>(capacity)?;
+        f.write_u64::<LittleEndian>(0)?;
+        f.set_len(BitmapIndex::<T>::HEADER_SIZE + capacity * BitmapIndex::<T>::slot_size())?;
+        return Ok(f);
+    }
+
+    fn slot_size() -> u64 {
+        return T::SIZE + 8;
+    }
+
+    pub fn name<'a>(&'a self) -> &'a str {
+        return self.name.as_str();
+    }
+
+    /** Returns the number of values in the index. 
+     */
+    pub fn len(& self) -> usize {
+        return self.len as usize;
+    }
+
+    fn read_slot(f : & mut dyn TableStorage, index : u64) -> Result<(T, u64), std::io::Error> {
+        f.seek(SeekFrom::Start(BitmapIndex::<T>::HEADER_SIZE + index * BitmapIndex::<T>::slot_size()))?;
+        let value = T::verify(f)?;
+        let bitmap = f.read_u64::<LittleEndian>()?;
+        return Ok((value, bitmap));
+    }
+
+    fn write_slot(f : & mut dyn TableStorage, index : u64, value : & T, bitmap : u64) -> Result<(), std::io::Error> {
+        f.seek(SeekFrom::Start(BitmapIndex::<T>::HEADER_SIZE + index * BitmapIndex::<T>::slot_size()))?;
+        T::serialize(f, value);
+        f.write_u64::<LittleEndian>(bitmap)?;
+        return Ok(());
+    }
 
-    fn next(& mut self) -> Option<(ID, T)> {
-        if self.store.f.seek(SeekFrom::Current(0)).unwrap() >= self.max_offset {
-            return None;
-        } else {
-            match LinkedStore::<T, ID>::read_record(& mut self.store.f) {
-                Some((id, _, value)) => Some((id, value)),
-                None => None
+    /** Returns the slot of given value, or of the empty slot where the value should be stored, together with the bitmap stored in the slot. 
+     */
+    fn find_slot(f : & mut dyn TableStorage, capacity : u64, value : & T) -> Result<(u64, u64), std::io::Error> {
+        let mut index = stable_hash(value) % capacity;
+        loop {
+            let (v, bitmap) = BitmapIndex::<T>::read_slot(f, index)?;
+            if bitmap == 0 || v == *value {
+                return Ok((index, bitmap));
             }
+            index = (index + 1) % capacity;
         }
     }
-}
 
-pub struct LinkedStoreIterId<'a, T : Serializable<Item = T>, ID : Id> {
-    store : &'a mut LinkedStore<T, ID>,
-    offset : Option<u64>,
-}
+    /** Returns the bitmap of given value, which is empty if the value is not in the index. 
+     */
+    pub fn get(& mut self, value : & T) -> Result<u64, std::io::Error> {
+        return Ok(BitmapIndex::<T>::find_slot(& mut self.f, self.capacity, value)?.1);
+    }
 
-impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for LinkedStoreIterId<'a, T, ID> {
-    type Item = T;
+    /** Sets given bits in the bitmap of the value, adding the value to the index if not present. 
+     */
+    pub fn set(& mut self, value : & T, bits : u64) -> Result<(), std::io::Error> {
+        assert!(bits != 0, "Cannot set empty bitmap");
+        let (index, bitmap) = BitmapIndex::<T>::find_slot(& mut self.f, self.capacity, value)?;
+        if bitmap & bits == bits {
+            return Ok(());
+        }
+        BitmapIndex::<T>::write_slot(& mut self.f, index, value, bitmap | bits)?;
+        if bitmap == 0 {
+            self.len += 1;
+            self.f.seek(SeekFrom::Start(8))?;
+            self.f.write_u64::<LittleEndian>(self.len)?;
+            if self.len * 2 > self.capacity {
+                self.grow()?;
+            }
+        }
+        return Ok(());
+    }
 
-    fn next(& mut self) -> Option<T> {
-        match self.offset {
-            Some(offset) => {
-                self.store.f.seek(SeekFrom::Start(offset)).unwrap();
-                let (_, previous_offset, value) = LinkedStore::<T, ID>::read_record(& mut self.store.f).unwrap(); 
-                self.offset = previous_offset;
-                return Some(value);
-            }, 
-            None => None
+    /** Doubles the capacity of the index in place. 
+     
+        The file is extended and the clusters of the old table (runs of occupied slots) are moved one at a time: the values of a cluster are read, their slots emptied and the values inserted again with the new capacity. With twice the capacity a value can only end up in its own cluster, or in the new half of the table, so that the values not yet moved are never in the way. The iteration starts after an empty slot, so that no cluster wraps around it. The index is marked incomplete while growing, readers of the file opened before may see it inconsistent. 
+     */
+    fn grow(& mut self) -> Result<(), std::io::Error> {
+        let old_capacity = self.capacity;
+        let capacity = old_capacity * 2;
+        LOG!("    {}: growing to capacity {}", self.name, capacity);
+        self.begin_batch()?;
+        self.f.set_len(BitmapIndex::<T>::HEADER_SIZE + capacity * BitmapIndex::<T>::slot_size())?;
+        // the table is at most half full, so there always is an empty slot
+        let mut start = 0;
+        while BitmapIndex::<T>::read_slot(& mut self.f, start)?.1 != 0 {
+            start += 1;
         }
+        let mut cluster = Vec::new();
+        for i in 1..(old_capacity + 1) {
+            let index = (start + i) % old_capacity;
+            let (value, bitmap) = BitmapIndex::<T>::read_slot(& mut self.f, index)?;
+            if bitmap != 0 {
+                cluster.push((index, value, bitmap));
+                continue;
+            }
+            for (index, value, _) in cluster.iter() {
+                BitmapIndex::<T>::write_slot(& mut self.f, *index, value, 0)?;
+            }
+            for (_, value, bitmap) in cluster.drain(..) {
+                let (index, _) = BitmapIndex::<T>::find_slot(& mut self.f, capacity, & value)?;
+                BitmapIndex::<T>::write_slot(& mut self.f, index, & value, bitmap)?;
+            }
+        }
+        self.f.seek(SeekFrom::Start(0))?;
+        self.f.write_u64::<LittleEndian>(capacity)?;
+        self.capacity = capacity;
+        return self.end_batch();
+    }
+
+    /** Removes all values from the index. 
+     
+        Call within a batch (see `begin_batch`) when the index is to be rebuilt, so that an interrupted rebuild is detected. 
+     */
+    pub fn clear(& mut self) -> Result<(), std::io::Error> {
+        self.f = BitmapIndex::<T>::create(& self.filename, BitmapIndex::<T>::INITIAL_CAPACITY)?;
+        self.capacity = BitmapIndex::<T>::INITIAL_CAPACITY;
+        self.len = 0;
+        return Ok(());
     }
 }
 
 /** Mapping from values to ids. 
  
     Unlike store, mapping does not allow updates to added values. 
+
+    Instead of loading the whole mapping into memory, a bloom filter persisted next to the mapping can be loaded. The hashmap then only holds the values added since the sorted index (see below) was last updated, values not in the filter are new and values that may be in the filter are looked up in the index. The filter is always loaded together with the index, since looking up the values in the mapping file itself would require scanning it. 
+
+    For mappings larger than the available memory, a sorted index of the mapping persisted on disk can be used together with a memory budget. The hashmap then only holds the values added after the index was last updated and when these exceed the budget, they are merged into the index. 
  */
 pub struct Mapping<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id = u64> {
     name : String,
-    f : File,
+    bloom_filename : String,
+    index_filename : String,
+    f : Box<dyn TableStorage>,
     mapping : HashMap<T, ID>,
+    bloom : Option<BloomFilter>,
+    index : Option<SortedIndex>,
+    /** Maximum number of values in the hashmap when the sorted index is used. 
+     */
+    max_frontier : u64,
     size : u64,
     /** Because seeking a file in rust is really expensive, the read index is cached.
      */
@@ -963,18 +2373,22 @@ impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> IntoItera
 
 impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Mapping<T, ID> {
 
+    /** Maximum number of values kept in the hashmap when the bloom filter is loaded without a memory budget. 
+     */
+    const BLOOM_FRONTIER : u64 = 1 << 20;
+
     pub fn new(root : & str, name : & str, readonly : bool) -> Mapping<T, ID> {
-        let mut f;
-        if readonly {
-            f = OpenOptions::new().read(true).open(format!("{}/{}.mapping", root, name)).unwrap();
-        } else {
-            f = OpenOptions::new().read(true).write(true).create(true).open(format!("{}/{}.mapping", root, name)).unwrap();
-        }
+        let mut f = open_table_file(& format!("{}/{}.mapping", root, name), readonly).unwrap();
         let size = f.seek(SeekFrom::End(0)).unwrap() / T::SIZE;
         let mut result = Mapping{
             name : name.to_owned(),
+            bloom_filename : format!("{}/{}.bloom", root, name),
+            index_filename : format!("{}/{}.index", root, name),
             f, 
             mapping : HashMap::new(),
+            bloom : None,
+            index : None,
+            max_frontier : 0,
             size,
             read_index : 0,
 
@@ -996,9 +2410,24 @@ impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Mapping<T
         );
     }
 
-    pub fn revert_to_savepoint(& mut self, savepoint : & Savepoint) {
-        self.f.set_len(savepoint.limit_for(& format!("{}.mapping", self.name))).unwrap();
-        self.f.seek(SeekFrom::End(0)).unwrap();
+    pub fn revert_to_savepoint(& mut self, savepoint : & Savepoint) -> Result<(), std::io::Error> {
+        self.f.set_len(savepoint.limit_for(& format!("{}.mapping", self.name)))?;
+        self.size = self.f.seek(SeekFrom::End(0))? / T::SIZE;
+        self.read_index = self.size;
+        // the persisted bloom filter is rebuilt when loaded next time since it no longer matches the size, but the sorted index has to be removed if it contains the reverted values
+        if let Some(index) = SortedIndex::open(& self.index_filename) {
+            if index.indexed > self.size {
+                remove_table_file(& self.index_filename)?;
+            }
+        }
+        if self.bloom.is_some() {
+            self.mapping.clear();
+            self.rebuild_bloom();
+        }
+        if self.index.is_some() {
+            self.open_index()?;
+        }
+        return Ok(());
     }
 
     /** Verifies the mapping's integrity. 
@@ -1019,6 +2448,32 @@ impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Mapping<T
         return Ok(());
     }
 
+    /** Repairs the mapping. 
+     
+        Truncates partially written trailing value, if any. Values in the middle of the mapping that are not valid cannot be repaired since the ids of all values that follow would change. Returns the number of repairs made. 
+
+        The mapping must not be loaded. 
+     */
+    pub fn repair(& mut self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        assert!(self.mapping.is_empty(), "Cannot repair loaded mapping");
+        let mut repairs = 0;
+        let end = self.f.seek(SeekFrom::End(0))?;
+        if end != self.size * T::SIZE {
+            self.f.set_len(self.size * T::SIZE)?;
+            report(format!("{}.mapping: truncated partial value ({} bytes)", self.name, end - self.size * T::SIZE));
+            repairs += 1;
+        }
+        self.f.seek(SeekFrom::Start(0))?;
+        for id in 0..self.size {
+            if let Err(e) = T::verify(& mut self.f) {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{}.mapping: invalid value for id {} of {}, cannot repair without changing ids: {}", self.name, id, self.size, e)));
+            }
+        }
+        self.f.seek(SeekFrom::End(0))?;
+        self.read_index = self.size;
+        return Ok(repairs);
+    }
+
     /** Loads the mapping into from disk to the hashmap. 
      */
     pub fn load(& mut self) {
@@ -1026,52 +2481,321 @@ impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Mapping<T
         self.f.seek(SeekFrom::Start(0)).unwrap();
         let iter = MappingIter{f : & mut self.f, index : 0, size : self.size, why_oh_why : std::marker::PhantomData{} };
         self.mapping.clear();
+        self.bloom = None;
+        self.index = None;
         for (id, value) in iter {
             self.mapping.insert(value, id);
         }
     }
 
+    /** Loads the bloom filter of the mapping instead of the whole mapping. 
+     
+        If the persisted filter does not exist, or does not match the mapping (such as when the mapping was updated without the filter), the filter is rebuilt from the mapping file, which does not require the mapping to be loaded either. Unless already loaded with a memory budget, the sorted index is loaded as well with the default budget of `BLOOM_FRONTIER` values so that the values that may be in the filter can be confirmed without scanning the mapping file. 
+     */
+    pub fn load_bloom(& mut self) -> Result<(), std::io::Error> {
+        match BloomFilter::read(& self.bloom_filename) {
+            Some((bloom, values)) if values == self.size && self.size <= bloom.capacity => {
+                self.bloom = Some(bloom);
+            },
+            _ => {
+                self.rebuild_bloom();
+            }
+        }
+        if self.index.is_none() {
+            self.max_frontier = Self::BLOOM_FRONTIER;
+            self.open_index()?;
+        }
+        return Ok(());
+    }
+
+    /** Rebuilds the bloom filter from the mapping file, with capacity for twice the current number of values. 
+     */
+    fn rebuild_bloom(& mut self) {
+        LOG!("    {}: rebuilding bloom filter for {} values", self.name, self.size);
+        let mut bloom = BloomFilter::new(self.size * 2);
+        self.f.seek(SeekFrom::Start(0)).unwrap();
+        {
+            let mut reader = std::io::BufReader::with_capacity(1 << 20, & mut self.f);
+            for _ in 0..self.size {
+                bloom.insert(& T::deserialize(& mut reader));
+            }
+        }
+        self.f.seek(SeekFrom::End(0)).unwrap();
+        self.read_index = self.size;
+        self.bloom = Some(bloom);
+    }
+
+    /** Loads the sorted index of the mapping instead of the whole mapping, keeping the values in memory within given budget (in bytes). 
+     */
+    pub fn load_with_budget(& mut self, budget : u64) -> Result<(), std::io::Error> {
+        self.max_frontier = std::cmp::max(budget / Self::entry_memory(), 1024);
+        return self.open_index();
+    }
+
+    /** Loads either the whole mapping, or its bloom filter, sorted index, or both. 
+     */
+    pub fn load_with(& mut self, bloom : bool, budget : Option<u64>) -> Result<(), std::io::Error> {
+        match budget {
+            Some(budget) => self.load_with_budget(budget)?,
+            None => {
+                if ! bloom {
+                    self.load();
+                }
+            }
+        }
+        if bloom {
+            self.load_bloom()?;
+        }
+        return Ok(());
+    }
+
+    /** Approximate memory taken by a single value in the hashmap, including the overhead of the hashmap itself. 
+     */
+    fn entry_memory() -> u64 {
+        return ((std::mem::size_of::<T>() + std::mem::size_of::<ID>()) * 2) as u64;
+    }
+
+    /** Opens the persisted sorted index and loads the values not yet indexed into the hashmap. 
+     
+        The index is rebuilt if it does not exist, does not match the mapping, or if there are more values not yet indexed than the memory budget allows. 
+     */
+    fn open_index(& mut self) -> Result<(), std::io::Error> {
+        self.mapping.clear();
+        self.index = None;
+        let index = match SortedIndex::open(& self.index_filename) {
+            Some(index) if index.indexed <= self.size && self.size - index.indexed <= self.max_frontier => index,
+            _ => {
+                self.rebuild_index()?;
+                SortedIndex::open(& self.index_filename).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Cannot open rebuilt sorted index {}", self.index_filename)))?
+            }
+        };
+        self.f.seek(SeekFrom::Start(index.indexed * T::SIZE))?;
+        {
+            let mut reader = std::io::BufReader::with_capacity(1 << 20, & mut self.f);
+            for id in index.indexed..self.size {
+                self.mapping.insert(T::deserialize(& mut reader), ID::from(id));
+            }
+        }
+        self.f.seek(SeekFrom::End(0))?;
+        self.read_index = self.size;
+        self.index = Some(index);
+        return Ok(());
+    }
+
+    /** Rebuilds the sorted index from the mapping file. 
+     
+        The hashes of the values are sorted in runs of the size of the memory budget that are stored in temporary files and then merged into the index. 
+     */
+    fn rebuild_index(& mut self) -> Result<(), std::io::Error> {
+        LOG!("    {}: rebuilding sorted index for {} values", self.name, self.size);
+        let mut runs = Vec::new();
+        self.f.seek(SeekFrom::Start(0))?;
+        {
+            let mut reader = std::io::BufReader::with_capacity(1 << 20, & mut self.f);
+            let mut id = 0;
+            while id < self.size {
+                let mut run = Vec::new();
+                while id < self.size && (run.len() as u64) < self.max_frontier {
+                    run.push((stable_hash(& T::deserialize(& mut reader)), id));
+                    id += 1;
+                }
+                run.sort_unstable();
+                let filename = format!("{}.run{}", self.index_filename, runs.len());
+                let mut f = std::io::BufWriter::with_capacity(1 << 20, open_index_file(& filename, false, true)?);
+                for (hash, id) in run {
+                    f.write_u64::<LittleEndian>(hash)?;
+                    f.write_u64::<LittleEndian>(id)?;
+                }
+                f.flush()?;
+                runs.push(filename);
+            }
+        }
+        self.f.seek(SeekFrom::End(0))?;
+        self.read_index = self.size;
+        let mut inputs = Vec::new();
+        for filename in runs.iter() {
+            inputs.push(SortedIndex::records_of(filename, 0)?);
+        }
+        SortedIndex::write(& self.index_filename, self.size, inputs)?;
+        for filename in runs.iter() {
+            remove_table_file(filename)?;
+        }
+        return Ok(());
+    }
+
+    /** Merges the values in the hashmap into the sorted index and clears the hashmap. 
+     */
+    fn spill(& mut self) -> Result<(), std::io::Error> {
+        LOG!("    {}: merging {} values into sorted index", self.name, self.mapping.len());
+        let mut frontier : Vec<(u64, u64)> = self.mapping.iter().map(|(value, id)| (stable_hash(value), (*id).into())).collect();
+        frontier.sort_unstable();
+        let inputs = vec![SortedIndex::records_of(& self.index_filename, SortedIndex::HEADER_SIZE)?, Box::new(frontier.into_iter()) as Box<dyn Iterator<Item = (u64, u64)>>];
+        SortedIndex::write(& self.index_filename, self.size, inputs)?;
+        self.index = SortedIndex::open(& self.index_filename);
+        self.mapping.clear();
+        return Ok(());
+    }
+
+    /** Looks up the given values that are not in the hashmap and returns the ids of those found. 
+     
+        If the sorted index is loaded, which is always the case when the bloom filter is, the values are looked up in the index. Otherwise the mapping file is read, at most once for all values, and the values found are added to the hashmap. 
+     */
+    fn lookup(& mut self, values : HashSet<T>) -> Result<HashMap<T, ID>, std::io::Error> {
+        let mut result = HashMap::new();
+        if self.index.is_some() {
+            for value in values {
+                let ids = self.index.as_mut().unwrap().find(stable_hash(& value))?;
+                for id in ids {
+                    if self.get(ID::from(id)).as_ref() == Some(& value) {
+                        result.insert(value, ID::from(id));
+                        break;
+                    }
+                }
+            }
+            return Ok(result);
+        }
+        let mut values = values;
+        self.f.seek(SeekFrom::Start(0))?;
+        {
+            let mut reader = std::io::BufReader::with_capacity(1 << 20, & mut self.f);
+            let mut id = 0;
+            while ! values.is_empty() && id < self.size {
+                let value = T::deserialize(& mut reader);
+                if values.remove(& value) {
+                    result.insert(value.clone(), ID::from(id));
+                    self.mapping.insert(value, ID::from(id));
+                }
+                id += 1;
+            }
+        }
+        self.f.seek(SeekFrom::End(0))?;
+        self.read_index = self.size;
+        return Ok(result);
+    }
+
+    /** Returns true if the value may be stored in the mapping file, but is not in the hashmap. 
+     */
+    fn needs_lookup(& self, value : & T) -> bool {
+        if self.mapping.contains_key(value) {
+            return false;
+        }
+        if let Some(bloom) = & self.bloom {
+            if ! bloom.may_contain(value) {
+                return false;
+            }
+        }
+        return self.bloom.is_some() || self.index.is_some();
+    }
+
     /** Clears the loaded mapping and shrinks the hashmap to free up as much memory as possible. 
+     
+        If the bloom filter was loaded instead, it is persisted so that it does not have to be rebuilt next time. 
      */
     pub fn clear(& mut self) {
         self.mapping.clear();
         self.mapping.shrink_to_fit();
+        self.index = None;
+        if let Some(bloom) = self.bloom.take() {
+            if let Err(e) = bloom.write(& self.bloom_filename, self.size) {
+                LOG!("    {}: cannot write bloom filter: {}", self.name, e);
+            }
+        }
     }
 
-    pub fn get_mapping(& mut self, value : & T) -> Option<ID> {
-        match self.mapping.get(value) {
-            Some(id) => Some(*id),
-            None => None
+    pub fn get_mapping(& mut self, value : & T) -> Result<Option<ID>, std::io::Error> {
+        if self.needs_lookup(value) {
+            return Ok(self.lookup(std::iter::once(value.to_owned()).collect())?.remove(value));
         }
+        return Ok(self.mapping.get(value).copied());
     }
 
-    pub fn get_or_create_mapping(& mut self, value : & T) -> (ID, bool) {
+    /** Returns the id of given value and whether the value is new, creating new id if the value is not yet in the mapping. 
+     
+        Fails if the values in memory must be merged into the sorted index and the index cannot be written. 
+     */
+    pub fn get_or_create_mapping(& mut self, value : & T) -> Result<(ID, bool), std::io::Error> {
+        if self.needs_lookup(value) {
+            if let Some(id) = self.lookup(std::iter::once(value.to_owned()).collect())?.remove(value) {
+                return Ok((id, false));
+            }
+        }
         match self.mapping.get(value) {
-            Some(id) => (*id, false),
-            None => {
-                let next_id = ID::from(self.mapping.len() as u64);
-                self.mapping.insert(value.to_owned(), next_id);
-                // serialize the value and increase size
-                T::serialize(& mut self.f, value);
-                self.size += 1;
-                self.read_index = self.size;
-                return (next_id, true);
+            Some(id) => Ok((*id, false)),
+            None => Ok((self.append(value)?, true)),
+        }
+    }
+
+    /** Returns the ids of all given values, creating new ids for values not yet in the mapping. 
+     
+        Same as calling `get_or_create_mapping` on each value, but when only the bloom filter is loaded, values that may already be stored are looked up in the mapping file together. 
+     */
+    pub fn get_or_create_mappings(& mut self, values : & [T]) -> Result<Vec<(ID, bool)>, std::io::Error> {
+        let lookup : HashSet<T> = values.iter().filter(|x| self.needs_lookup(x)).cloned().collect();
+        let found = if lookup.is_empty() { HashMap::new() } else { self.lookup(lookup)? };
+        let mut result = Vec::with_capacity(values.len());
+        for x in values {
+            if let Some(id) = found.get(x) {
+                result.push((*id, false));
+                continue;
+            }
+            match self.mapping.get(x) {
+                Some(id) => result.push((*id, false)),
+                None => result.push((self.append(x)?, true)),
+            }
+        }
+        return Ok(result);
+    }
+
+    /** Appends new value to the mapping and returns its id. 
+     */
+    fn append(& mut self, value : & T) -> Result<ID, std::io::Error> {
+        let next_id = ID::from(self.size);
+        self.mapping.insert(value.to_owned(), next_id);
+        // serialize the value and increase size
+        T::serialize(& mut self.f, value);
+        self.size += 1;
+        self.read_index = self.size;
+        if let Some(bloom) = & mut self.bloom {
+            bloom.insert(value);
+            if self.size > bloom.capacity {
+                self.rebuild_bloom();
             }
         }
+        if self.index.is_some() && self.mapping.len() as u64 > self.max_frontier {
+            self.spill()?;
+        }
+        return Ok(next_id);
     }
 
     /** Updates the already stored mapping. 
+     
+        The entry of the old value in the persisted sorted index would no longer be valid if the value is already indexed, in which case the index is rebuilt if loaded, or deleted so that it is rebuilt when loaded next time. 
      */
-    pub fn update(& mut self, id : ID, value : & T) {
+    pub fn update(& mut self, id : ID, value : & T) -> Result<(), std::io::Error> {
         assert!(id.into() < self.size);
+        let old = self.get(id);
         let offset = T::SIZE * id.into();
-        self.f.seek(SeekFrom::Start(offset)).unwrap();
+        self.f.seek(SeekFrom::Start(offset))?;
         T::serialize(& mut self.f, value);
-        self.f.seek(SeekFrom::End(0)).unwrap();
+        self.f.seek(SeekFrom::End(0))?;
         self.read_index = self.size;
         // now that the file has been changed, update the mapping
-        self.mapping.remove(value);
+        if let Some(old) = old {
+            self.mapping.remove(& old);
+        }
         self.mapping.insert(value.to_owned(), id);
+        if let Some(bloom) = & mut self.bloom {
+            bloom.insert(value);
+        }
+        if SortedIndex::open(& self.index_filename).map(|x| id.into() < x.indexed).unwrap_or(false) {
+            if self.index.is_some() {
+                self.rebuild_index()?;
+                self.open_index()?;
+            } else {
+                remove_table_file(& self.index_filename)?;
+            }
+        }
+        return Ok(());
     }
 
     pub fn len(& self) -> usize {
@@ -1095,7 +2819,7 @@ impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Mapping<T
 }
 
 pub struct MappingIter<'a, T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id = u64> {
-    f : &'a mut File,
+    f : &'a mut dyn TableStorage,
     index : u64,
     size : u64,
     why_oh_why : std::marker::PhantomData<(T, ID)>
@@ -1203,6 +2927,27 @@ impl<T : Serializable<Item = T> + Eq + Hash + Clone, ID : Id> IndirectMapping<T,
         return self.store.verify(checker);
     }
 
+    /** Splits the underlying store into ranges that can be verified in parallel. 
+     
+        The ranges are verified by the store's `verify_chunk`. 
+     */
+    pub fn split_for_verification(& mut self, max_chunks : usize, min_size : u64) -> Vec<VerifyChunk> {
+        return self.store.split_for_verification(max_chunks, min_size);
+    }
+
+    pub fn verify_chunks_index(& mut self, chunks : Vec<VerifiedChunk>) -> Result<(), std::io::Error> {
+        return self.store.verify_chunks_index(chunks);
+    }
+
+    /** Repairs the underlying store. 
+     
+        The mapping must not be loaded. 
+     */
+    pub fn repair(& mut self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        assert!(self.mapping.is_empty(), "Cannot repair loaded mapping");
+        return self.store.repair(report);
+    }
+
     pub fn load(& mut self) {
         self.mapping.clear();
         for (id, value) in self.store.iter() {
@@ -1307,28 +3052,36 @@ impl<T : SplitKind> Iterator for SplitKindIter<T> {
  
     Each kind has its own store. The SplitOffset is serializable with fixed size calculated from the offset (8 bytes) and serialized kind size. 
  */
-#[derive(Eq, PartialEq)]
 pub (crate) struct SplitOffset<KIND : SplitKind<Item = KIND>> {
     pub (crate) offset : u64,
     pub (crate) kind : KIND, 
 }
 
+impl<KIND : SplitKind<Item = KIND>> PartialEq for SplitOffset<KIND> {
+    fn eq(& self, other : & SplitOffset<KIND>) -> bool {
+        return self.offset == other.offset && self.kind == other.kind;
+    }
+}
+
+impl<KIND : SplitKind<Item = KIND>> Eq for SplitOffset<KIND> {
+}
+
 impl<KIND : SplitKind<Item = KIND>> Serializable for SplitOffset<KIND> {
     type Item = SplitOffset<KIND>;
 
-    fn serialize(f : & mut File, value : & SplitOffset<KIND>) {
+    fn serialize(f : & mut dyn TableStorage, value : & SplitOffset<KIND>) {
         u64::serialize(f, & value.offset);
         KIND::serialize(f, & value.kind);
     }
 
-    fn deserialize(f : & mut File) -> SplitOffset<KIND> {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> SplitOffset<KIND> {
         return SplitOffset{
             offset : u64::deserialize(f),
             kind : KIND::deserialize(f)
         };
     }
 
-    fn verify(f : & mut File) -> Result<SplitOffset<KIND>, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<SplitOffset<KIND>, std::io::Error> {
         return Ok(SplitOffset{
             offset : u64::verify(f)?,
             kind : KIND::verify(f)?
@@ -1350,22 +3103,27 @@ impl<KIND : SplitKind<Item = KIND>> Indexable for SplitOffset<KIND> {
     TODO the internal code does not yet use this type, update the code.
  */
 pub struct SplitStorePart<T : Serializable<Item = T>, ID : Id = u64> {
-    pub (crate) f : File,
+    pub (crate) f : Box<dyn TableStorage>,
+    /** Checksums of the split, named after the split file, i.e. by its kind. 
+     */
+    checksums : Option<Checksums>,
     why_oh_why : std::marker::PhantomData<(T,ID)>
 }
 
 impl<T : Serializable<Item = T>, ID : Id> SplitStorePart<T, ID> {
     fn new<KIND : SplitKind<Item = KIND>>(root : & str, name : & str, kind : KIND, readonly : bool) -> SplitStorePart<T,ID> {
         let path = format!("{}/{}-{:?}.splitstore", root, name, kind);
-        let f;
-        if readonly {
-            f = OpenOptions::new().read(true).open(path).unwrap();
-        } else {
-            f = OpenOptions::new().read(true).write(true).create(true).open(path).unwrap();
-        }
-        return SplitStorePart::<T,ID>{f, why_oh_why : std::marker::PhantomData{}};
+        let f = open_table_file(& path, readonly).unwrap();
+        let checksums = Checksums::open(root, & SplitStorePart::<T,ID>::checksums_name(name, kind), readonly);
+        return SplitStorePart::<T,ID>{f, checksums, why_oh_why : std::marker::PhantomData{}};
     } 
 
+    /** Returns the name of the checksums file of the split of given kind, which is also its name in savepoints. 
+     */
+    pub fn checksums_name<KIND : SplitKind<Item = KIND>>(name : & str, kind : KIND) -> String {
+        return format!("{}-{:?}", name, kind);
+    }
+
     fn get_reset(& mut self) {
         self.f.seek(SeekFrom::Start(0)).unwrap();
     }
@@ -1500,6 +3258,9 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
                 format!("{}-{}.store", self.name, i),
                 f.f.seek(SeekFrom::End(0)).unwrap()
             );
+            if let Some(checksums) = f.checksums.as_mut() {
+                checksums.savepoint(& SplitStorePart::<T,ID>::checksums_name(& self.name, KIND::from_number(i)), savepoint);
+            }
             i += 1;
         }
         self.indexer.savepoint(savepoint);
@@ -1510,6 +3271,9 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
         for f in self.files.iter_mut() {
             f.f.set_len(savepoint.limit_for(& format!("{}-{}.store", self.name, i))).unwrap();
             f.f.seek(SeekFrom::End(0)).unwrap();
+            if let Some(checksums) = f.checksums.as_mut() {
+                checksums.revert_to_savepoint(& SplitStorePart::<T,ID>::checksums_name(& self.name, KIND::from_number(i)), savepoint);
+            }
             i += 1;
         }
         self.indexer.revert_to_savepoint(savepoint);
@@ -1520,6 +3284,7 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
         For a split store, this means:
 
         - look at all files and verify that the things stored in them are valid
+        - check the records against the checksums of their split, if any
 
 
      */
@@ -1529,6 +3294,10 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
         for f in self.files.iter_mut() {
             latest_mappings.push(HashMap::<u64, u64>::new());
             let end = f.f.seek(SeekFrom::End(0))?;
+            let mut checksums = match f.checksums.as_mut() {
+                Some(checksums) => checksums.load(end)?.into_iter().peekable(),
+                None => Vec::new().into_iter().peekable(),
+            };
             f.f.seek(SeekFrom::Start(0))?;
             loop {
                 let offset = f.f.seek(SeekFrom::Current(0))?;
@@ -1540,10 +3309,15 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
                     return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("SplitStore id {:?}, but only {} ids known at offset {} in split {:?}", ID::from(id), self.indexer.size, offset, KIND::from_number(i))));
                 }
                 let item = T::verify(& mut f.f)?;
+                let next = f.f.seek(SeekFrom::Current(0))?;
+                Checksums::check_record(& mut checksums, & mut f.f, offset, next)?;
                 checker(item)?;
                 // now we need to add this to the mappings, but only to those valid for current id
                 latest_mappings.get_mut(i as usize).unwrap().insert(id, offset);
             }
+            if let Some((offset, _)) = checksums.next() {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Checksum for offset {} which is not a start of a record in split {:?}", offset, KIND::from_number(i))));
+            }
             i += 1;
         }
         // then check the index's integrity
@@ -1572,6 +3346,40 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
         return Ok(());
     }
 
+    /** Repairs the split store. 
+     
+        Truncates partially written trailing records in all splits and the index and then rebuilds the index from the records found in the splits. Returns the number of repairs made, or an error if any split contains corruption that cannot be repaired without losing data. 
+     */
+    pub fn repair(& mut self, report : & mut dyn FnMut(String)) -> Result<usize, std::io::Error> {
+        let mut repairs = self.indexer.repair(report)?;
+        // the last record of each split known from the index
+        let mut last_indexed = HashMap::<u64, u64>::new();
+        for (_, x) in self.indexer.iter() {
+            if x.offset != u64::EMPTY {
+                let last = last_indexed.entry(x.kind.to_number()).or_insert(0);
+                *last = (*last).max(x.offset);
+            }
+        }
+        let mut latest_mappings = HashMap::<u64, SplitOffset<KIND>>::new();
+        let mut i = 0;
+        for f in self.files.iter_mut() {
+            let kind = KIND::from_number(i);
+            let name = format!("{}-{:?}.splitstore", self.name, kind);
+            repairs += repair_records(& mut f.f, & name, last_indexed.get(& i).copied(), report, & mut |f, offset| {
+                let id = f.read_u64::<LittleEndian>()?;
+                T::verify(f)?;
+                latest_mappings.insert(id, SplitOffset{ offset, kind : KIND::from_number(i) });
+                return Ok(());
+            })?;
+            if let Some(checksums) = f.checksums.as_mut() {
+                repairs += checksums.repair(& SplitStorePart::<T,ID>::checksums_name(& self.name, kind), f.f.seek(SeekFrom::End(0))?, report)?;
+            }
+            i += 1;
+        }
+        repairs += self.indexer.rebuild(latest_mappings, report);
+        return Ok(repairs);
+    }
+
     /** Determines if given id is stored without actually reading its contents, which is slighjtly faster.
      */
     pub fn has(& mut self, id : ID) -> bool {
@@ -1595,7 +3403,7 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
         }
         let f = self.files.get_mut(kind.to_number() as usize).unwrap();
         self.indexer.set(id, & SplitOffset{
-            offset : Store::<T, ID>::write_record(& mut f.f, id, value),
+            offset : Store::<T, ID>::write_record(& mut f.f, f.checksums.as_mut(), id, value),
             kind
         });
     }
@@ -1608,17 +3416,30 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
             None => {}
         }
         let f = self.files.get_mut(kind.to_number() as usize).unwrap();
-        let offset = f.f.seek(SeekFrom::End(0)).unwrap();
+        let mut record = Vec::with_capacity(16 + data.len());
+        record.write_u64::<LittleEndian>(id.into()).unwrap();
+        record.write_u64::<LittleEndian>(length as u64).unwrap();
+        record.extend_from_slice(data);
+        let offset = f.f.append(& record).unwrap();
+        if let Some(checksums) = f.checksums.as_mut() {
+            checksums.append(offset, & record);
+        }
         self.indexer.set(id, & SplitOffset{offset, kind});
-        f.f.write_u64::<LittleEndian>(id.into()).unwrap();
-        f.f.write_u64::<LittleEndian>(length as u64).unwrap();
-        f.f.write(data).unwrap();
     }
 
     pub fn len(&self) -> usize {
         return self.indexer.len();
     }
 
+    /** Returns the number of ids with stored values and the total size of the files the values are stored in. 
+     
+        Unlike `len`, ids whose values are not stored are not counted. Note that this reads the entire index. 
+     */
+    pub fn stored_stats(& mut self) -> (usize, u64) {
+        let size = self.files.iter_mut().map(|f| f.filesize()).sum();
+        return (self.indexer.count_non_empty(), size);
+    }
+
     pub fn savepoint_iter(& mut self, sp : & Savepoint) -> SplitStoreIterAll<T,KIND,ID> {
         let mut max_offsets = Vec::new();
         let mut i = 0;
@@ -1663,6 +3484,7 @@ impl<'a, T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> Iter
 /** Savepoint for the entire datastore. 
  
  */
+#[derive(Clone)]
 pub struct Savepoint {
     name : String,
     time : i64,
@@ -1679,6 +3501,29 @@ impl Savepoint {
         };
     }
 
+    /** Executes the savepoints command with given arguments on the given savepoints and returns its output. 
+     
+        `list` (or no arguments) lists all savepoints in the given order with their times and total sizes, `show NAME` displays a single savepoint with the sizes of all its tables. 
+     */
+    pub fn command(savepoints : Vec<Savepoint>, args : & [& str]) -> Result<String, std::io::Error> {
+        match args.get(0).cloned() {
+            None | Some("list") if args.len() <= 1 => {
+                let mut result = format!("{} savepoints", savepoints.len());
+                for sp in savepoints.iter() {
+                    result.push_str(& format!("\n{}", sp.to_string().trim_end()));
+                }
+                return Ok(result);
+            },
+            Some("show") if args.len() == 2 => {
+                match savepoints.iter().find(|sp| sp.name() == args[1]) {
+                    Some(sp) => return Ok(format!("{:#}", sp).trim_end().to_owned()),
+                    None => return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("Savepoint {} not found", args[1]))),
+                }
+            },
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Usage: savepoints list, or savepoints show NAME")),
+        }
+    }
+
     pub fn add_entry(& mut self, name : String, size : u64) {
         assert!(! self.sizes.contains_key(&name), "weird  {}", name);
         self.sizes.insert(name, size);
@@ -1699,6 +3544,12 @@ impl Savepoint {
         return self.sizes.iter().map(|(_, size)| size).sum();
     }
 
+    /** Returns the names of all files in the savepoint together with their sizes. 
+     */
+    pub fn files(& self) -> impl Iterator<Item = (& String, & u64)> {
+        return self.sizes.iter();
+    }
+
     /** Returns the time at which the savepoint has been created. 
      */    
     pub fn time(& self) -> i64 {
@@ -1708,7 +3559,7 @@ impl Savepoint {
 
 /** Simple formatter for a savepoint. 
  
-    Displays the name and time as well as the sizes for all stored files
+    Displays the name and time as well as the number and total size of the stored files. The alternate form (`{:#}`) also displays the sizes of all tables, i.e. of their files together, ordered by the table names. 
  */
 impl std::fmt::Display for Savepoint {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -1716,11 +3567,16 @@ impl std::fmt::Display for Savepoint {
         writeln!(f, "    files: {}", self.sizes.len())?;
         let size = self.sizes.iter().fold(0, |a, (_,b)| a + b);
         writeln!(f, "    size:  {}", size)?;
-        /*
-        for (_file, file_size) in self.sizes.iter() {
-            writeln!(f, "    {} {}", size, file)?;
+        if f.alternate() {
+            let mut tables = BTreeMap::<& str, u64>::new();
+            for (file, file_size) in self.sizes.iter() {
+                let table = file.rsplitn(2, '.').last().unwrap();
+                *tables.entry(table).or_insert(0) += file_size;
+            }
+            for (table, table_size) in tables {
+                writeln!(f, "    {}: {}", table, table_size)?;
+            }
         }
-        */
         return Ok(());
     }
 }
@@ -1728,7 +3584,7 @@ impl std::fmt::Display for Savepoint {
 impl Serializable for Savepoint {
     type Item = Savepoint;
 
-    fn serialize(f : & mut File, value : & Savepoint) {
+    fn serialize(f : & mut dyn TableStorage, value : & Savepoint) {
         i64::serialize(f, & value.time);
         String::serialize(f, & value.name);
         u32::serialize(f, & (value.sizes.len() as u32));
@@ -1738,7 +3594,7 @@ impl Serializable for Savepoint {
         }
     }
 
-    fn deserialize(f : & mut File) -> Savepoint {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> Savepoint {
         let time = i64::deserialize(f);
         let name = String::deserialize(f);
         let mut records = u32::deserialize(f);
@@ -1756,7 +3612,7 @@ impl Serializable for Savepoint {
         return result;
     }
 
-    fn verify(f : & mut File) -> Result<Savepoint, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<Savepoint, std::io::Error> {
         let time = i64::verify(f)?;
         let name = String::verify(f)?;
         let mut records = u32::verify(f)?;
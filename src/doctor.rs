@@ -0,0 +1,265 @@
+use std::os::unix::fs::MetadataExt;
+
+use crate::datastore::*;
+use crate::github::*;
+use crate::helpers;
+use crate::settings::SETTINGS;
+use crate::task_update_repo::check_git_remote;
+use crate::updater::Updater;
+
+/** Number of files each worker thread is expected to keep open on top of the datastore tables, i.e. the packfiles and indices of the repository it analyzes. 
+ */
+const FILES_PER_THREAD : u64 = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+/** Checks the environment parasite runs in for the datastore at given root and prints the results with advice for the problems found. 
+ 
+    The checks are the version of the datastore, write permissions of the datastore and temporary directories, their free space, the limit of open files versus the number of files of the datastore, libgit2 (by cloning a local repository it creates), the `git` binary, whether git repositories can be reached (through the proxy, if any) and the validity of the GitHub tokens, for which a single API request per token is made. Apart from temporary files removed right after the checks nothing in the datastore is changed, so the checks can run while the datastore is being updated. Returns the number of errors and warnings. 
+ */
+pub fn doctor(root : & str) -> (usize, usize) {
+    let mut doctor = Doctor{ errors : 0, warnings : 0 };
+    println!("Checking datastore {}", root);
+    doctor.check_datastore(root);
+    doctor.check_disks(root);
+    doctor.check_open_files(root);
+    doctor.check_libgit2(root);
+    doctor.check_git();
+    doctor.check_network(root);
+    doctor.check_tokens();
+    println!("{} errors, {} warnings", doctor.errors, doctor.warnings);
+    return (doctor.errors, doctor.warnings);
+}
+
+struct Doctor {
+    errors : usize,
+    warnings : usize,
+}
+
+impl Doctor {
+
+    /** Prints the result of a single check with the advice for fixing it, if any. 
+     */
+    fn report(& mut self, severity : Severity, check : & str, message : String, advice : Option<String>) {
+        let tag = match severity {
+            Severity::Ok => "\x1b[92m  ok \x1b[0m",
+            Severity::Warning => {
+                self.warnings += 1;
+                "\x1b[93mwarn \x1b[0m"
+            },
+            Severity::Error => {
+                self.errors += 1;
+                "\x1b[91merror\x1b[0m"
+            },
+        };
+        println!("[{}] {}: {}", tag, check, message);
+        if let Some(advice) = advice {
+            println!("        {}", advice);
+        }
+    }
+
+    fn check_datastore(& mut self, root : & str) {
+        let root_path = std::path::Path::new(root);
+        if ! root_path.exists() {
+            self.report(Severity::Warning, "datastore", format!("{} does not exist", root), Some("An empty datastore will be created when parasite is run with it, make sure that the path is correct.".to_owned()));
+            let parent = root_path.parent().filter(|x| ! x.as_os_str().is_empty()).and_then(|x| x.to_str()).unwrap_or(".");
+            self.check_writable("permissions", parent);
+            return;
+        }
+        let version = Datastore::stored_version(root);
+        if version == Datastore::VERSION {
+            self.report(Severity::Ok, "version", format!("{}", version), None);
+        } else if version < Datastore::VERSION {
+            self.report(Severity::Error, "version", format!("{}, but this parasite requires version {}", version, Datastore::VERSION), Some(format!("Migrate the datastore by `parasite -ds {} migrate`.", root)));
+        } else {
+            self.report(Severity::Error, "version", format!("{}, which is newer than version {} of this parasite", version, Datastore::VERSION), Some("Use a newer version of parasite.".to_owned()));
+        }
+        self.check_writable("permissions", root);
+        if let Some(tmp_dir) = SETTINGS.tmp_dir.as_ref() {
+            self.check_writable("temporary directory", tmp_dir);
+        }
+    }
+
+    /** Checks that files can be created in given folder by creating and removing one. 
+     */
+    fn check_writable(& mut self, check : & str, folder : & str) {
+        let filename = format!("{}/.parasite-doctor-{}", folder, std::process::id());
+        match std::fs::write(& filename, "").and_then(|_| std::fs::remove_file(& filename)) {
+            Ok(()) => self.report(Severity::Ok, check, format!("{} is writable", folder), None),
+            Err(e) => self.report(Severity::Error, check, format!("cannot write to {}: {}", folder, e), Some(format!("Make sure that {} exists and is writable by the user running parasite.", folder))),
+        }
+    }
+
+    /** Checks the free space and inodes of the datastore volume and, if different, of the temporary directory against the thresholds from the settings. 
+     */
+    fn check_disks(& mut self, root : & str) {
+        let root = if std::path::Path::new(root).exists() { root.to_owned() } else { ".".to_owned() };
+        let temp = SETTINGS.tmp_dir.as_ref().map(std::path::PathBuf::from).unwrap_or_else(std::env::temp_dir);
+        let device = |path : & str| std::fs::metadata(path).map(|x| x.dev()).ok();
+        let mut volumes = vec![("datastore disk", root.clone())];
+        if let Some(temp) = temp.to_str() {
+            if device(temp) != device(& root) {
+                volumes.push(("temporary disk", temp.to_owned()));
+            }
+        }
+        for (check, path) in volumes {
+            match helpers::disk_resources(& path) {
+                Some((space, inodes)) => {
+                    let message = match inodes {
+                        Some(inodes) => format!("{} free, {} inodes free", helpers::pretty_size(space), helpers::pretty_value(inodes as usize)),
+                        None => format!("{} free", helpers::pretty_size(space)),
+                    };
+                    if space < SETTINGS.min_free_space || inodes.is_some_and(|x| x < SETTINGS.min_free_inodes) {
+                        self.report(Severity::Warning, check, message, Some(format!("Free some space on the volume of {}, updates need space for the clones of the repositories as well as for the datastore.", path)));
+                    } else {
+                        self.report(Severity::Ok, check, message, None);
+                    }
+                },
+                None => self.report(Severity::Warning, check, format!("cannot determine free space of {}", path), None),
+            }
+        }
+    }
+
+    /** Checks that the limit of open files suffices for the files of the datastore and the repositories analyzed by the worker threads. 
+     */
+    fn check_open_files(& mut self, root : & str) {
+        let mut limit = libc::rlimit{ rlim_cur : 0, rlim_max : 0 };
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, & mut limit) } != 0 {
+            self.report(Severity::Warning, "open files", "cannot determine the limit of open files".to_owned(), None);
+            return;
+        }
+        let files = count_files(std::path::Path::new(root));
+        let required = files + FILES_PER_THREAD * SETTINGS.num_threads as u64;
+        let message = format!("limit {} (hard {}), {} datastore files and {} threads need about {}", limit.rlim_cur, limit.rlim_max, helpers::pretty_value(files as usize), SETTINGS.num_threads, required);
+        if (limit.rlim_cur as u64) >= required {
+            self.report(Severity::Ok, "open files", message, None);
+        } else if (limit.rlim_max as u64) >= required {
+            self.report(Severity::Error, "open files", message, Some(format!("Raise the limit by `ulimit -n {}` before running parasite.", required)));
+        } else {
+            self.report(Severity::Error, "open files", message, Some(format!("The hard limit must be raised too, e.g. by `* hard nofile {}` in /etc/security/limits.conf, or use fewer threads.", required)));
+        }
+    }
+
+    /** Checks that libgit2 works by creating a repository with a single commit and cloning it in the folder where the repositories are cloned. 
+     */
+    fn check_libgit2(& mut self, root : & str) {
+        let folder = match SETTINGS.tmp_dir.as_ref() {
+            Some(tmp_dir) => tmp_dir.to_owned(),
+            None => if std::path::Path::new(root).exists() { root.to_owned() } else { std::env::temp_dir().to_string_lossy().to_string() },
+        };
+        let test_folder = format!("{}/parasite-doctor-{}", folder, std::process::id());
+        let result = libgit2_self_test(& test_folder);
+        let _ = std::fs::remove_dir_all(& test_folder);
+        match result {
+            Ok(()) => self.report(Severity::Ok, "libgit2", format!("repository created and cloned in {}", folder), None),
+            Err(e) => self.report(Severity::Error, "libgit2", format!("{}", e), Some(format!("Make sure that {} is writable and that parasite was built against a working libgit2.", folder))),
+        }
+    }
+
+    /** Checks that the git binary is available, which is required for partial clones and git LFS objects. 
+     */
+    fn check_git(& mut self) {
+        match std::process::Command::new("git").arg("--version").output() {
+            Ok(output) if output.status.success() => {
+                self.report(Severity::Ok, "git", helpers::to_string(& output.stdout).trim().to_owned(), None);
+            },
+            _ => {
+                let severity = if SETTINGS.partial_clone_threshold.is_some() || SETTINGS.lfs_fetch_limit.is_some() { Severity::Error } else { Severity::Warning };
+                self.report(severity, "git", "the git binary was not found".to_owned(), Some("Install git, it is required by --partial-clone-threshold and --lfs-fetch.".to_owned()));
+            }
+        }
+    }
+
+    /** Checks that git repositories can be reached, through the proxy if there is one, by listing the references of a small repository. 
+     */
+    fn check_network(& mut self, root : & str) {
+        let folder = match SETTINGS.tmp_dir.as_ref() {
+            Some(tmp_dir) => tmp_dir.to_owned(),
+            None => if std::path::Path::new(root).exists() { root.to_owned() } else { std::env::temp_dir().to_string_lossy().to_string() },
+        };
+        let via = SETTINGS.proxy.as_ref().map(|x| format!(" via proxy {}", x)).unwrap_or_default();
+        match check_git_remote(& format!("{}/parasite-doctor-{}", folder, std::process::id()), Updater::SELF_TEST_REPOSITORY) {
+            Ok(refs) => self.report(Severity::Ok, "network", format!("{} references of {} listed{}", refs, Updater::SELF_TEST_REPOSITORY, via), None),
+            Err(e) => self.report(Severity::Error, "network", format!("cannot reach {}{}: {}", Updater::SELF_TEST_REPOSITORY, via, e), Some("Check the network connection, if the machine is behind a proxy, give it by --proxy, or the https_proxy environment variable.".to_owned())),
+        }
+    }
+
+    /** Checks each of the GitHub tokens by a single API request. 
+ 
+        If GitHub cannot be reached, the remaining tokens are not checked. 
+     */
+    fn check_tokens(& mut self) {
+        let tokens = match Github::load_tokens(& SETTINGS.github_tokens) {
+            Ok(x) => x,
+            Err(e) => {
+                self.report(Severity::Error, "tokens", format!("cannot read {}: {}", SETTINGS.github_tokens, e), Some("Give the csv file with a header and the GitHub tokens in its first column by --github-tokens.".to_owned()));
+                return;
+            }
+        };
+        if tokens.is_empty() {
+            self.report(Severity::Error, "tokens", format!("no tokens in {}", SETTINGS.github_tokens), Some("Add GitHub tokens to the file, one per line after the header.".to_owned()));
+            return;
+        }
+        for (i, token) in tokens.iter().enumerate() {
+            let check = format!("token {} ({}...)", i + 1, token.chars().take(4).collect::<String>());
+            if tokens[..i].contains(token) {
+                self.report(Severity::Warning, & check, "duplicate token".to_owned(), Some("Remove the duplicate, it does not increase the number of requests.".to_owned()));
+                continue;
+            }
+            match Github::check_token(token) {
+                Ok((0, limit)) => self.report(Severity::Warning, & check, format!("0 of {} requests remaining", limit), Some("The token is exhausted until its rate limit resets within an hour.".to_owned())),
+                Ok((remaining, limit)) => self.report(Severity::Ok, & check, format!("{} of {} requests remaining", remaining, limit), None),
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    self.report(Severity::Error, & check, format!("rejected by GitHub: {}", e), Some(format!("The token is invalid, expired, or revoked, replace it in {}.", SETTINGS.github_tokens)));
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionAborted => {
+                    self.report(Severity::Error, & check, format!("cannot reach GitHub: {}", e), Some("Check the network connection and proxy settings of the machine.".to_owned()));
+                    return;
+                },
+                Err(e) => self.report(Severity::Warning, & check, format!("{}", e), None),
+            }
+        }
+    }
+}
+
+/** Counts the files in the given folder and its subfolders, except the clones of the repositories, which are only temporary. 
+ */
+fn count_files(folder : & std::path::Path) -> u64 {
+    let mut result = 0;
+    if let Ok(entries) = std::fs::read_dir(folder) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if ! path.file_name().map(|x| x.to_string_lossy().starts_with("repo_clones")).unwrap_or(false) {
+                    result += count_files(& path);
+                }
+            } else {
+                result += 1;
+            }
+        }
+    }
+    return result;
+}
+
+/** Creates a repository with a single commit in given folder, clones it and checks that the clone has the commit. 
+ */
+fn libgit2_self_test(folder : & str) -> Result<(), git2::Error> {
+    let origin_path = format!("{}/origin", folder);
+    let origin = git2::Repository::init(& origin_path)?;
+    std::fs::write(format!("{}/README", origin_path), "parasite doctor\n").map_err(|e| git2::Error::from_str(& format!("{}", e)))?;
+    let mut index = origin.index()?;
+    index.add_path(std::path::Path::new("README"))?;
+    let tree = origin.find_tree(index.write_tree()?)?;
+    let signature = git2::Signature::now("parasite", "parasite@localhost")?;
+    let commit = origin.commit(Some("HEAD"), & signature, & signature, "parasite doctor", & tree, & [])?;
+    let clone = git2::Repository::clone(& origin_path, format!("{}/clone", folder))?;
+    if clone.head()?.target() != Some(commit) {
+        return Err(git2::Error::from_str("the cloned repository does not have the commit of its origin"));
+    }
+    return Ok(());
+}
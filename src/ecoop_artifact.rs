@@ -13,17 +13,34 @@ mod db;
 #[allow(dead_code)]
 mod datastore;
 #[allow(dead_code)]
+mod substore_classifier;
+#[allow(dead_code)]
+mod license_classifier;
+#[allow(dead_code)]
 mod records;
 #[allow(dead_code)]
 mod updater;
 mod datastore_maintenance_tasks;
 mod task_update_repo;
+mod task_update_issues;
 mod task_update_substore;
 mod task_verify_substore;
+#[allow(dead_code)]
+mod task_pack_substore;
+#[allow(dead_code)]
+mod task_index_messages;
+mod task_index_commit_projects;
+mod task_index_files;
+mod task_commit_metrics;
+#[allow(dead_code)]
+mod task_resolve_users;
+#[allow(dead_code)]
 mod github;
 mod settings;
 #[allow(dead_code)]
 mod reporter;
+#[allow(dead_code)]
+mod csv_input;
 
 use datastore::*;
 
@@ -164,7 +181,7 @@ fn convert_project(id : u64, source_path : & str, ds : & Datastore, target_subst
             }*/
 
             // and add the log
-            ds.update_project_update_status(target_id, records::ProjectLog::Ok{time : helpers::now(), version : Datastore::VERSION});
+            ds.update_project_update_status(target_id, records::ProjectLog::Ok{time : helpers::now(), version : Datastore::VERSION, changes : None});
 
         } else {
             ok = false;
@@ -401,7 +418,7 @@ fn convert_1(source_path : & str, target_substore : & str) {
             let id = PathId::from(record[1].parse::<u64>().unwrap());
             match paths.entry(id) {
                 hash_map::Entry::Occupied(mut e) => {
-                    let target_id = substore.get_or_create_path_id(& path).0;
+                    let target_id = substore.get_or_create_path_id(& path).unwrap().0;
                     e.insert(target_id);
                     records += 1;
                 },
@@ -425,7 +442,7 @@ fn convert_1(source_path : & str, target_substore : & str) {
             let id = HashId::from(record[1].parse::<u64>().unwrap());
             match hashes.entry(id) {
                 hash_map::Entry::Occupied(mut e) => {
-                    let target_id = substore.get_or_create_hash_id(& sha).0;
+                    let target_id = substore.get_or_create_hash_id(& sha).unwrap().0;
                     e.insert(target_id);
                     records += 1;
                 },
@@ -437,7 +454,7 @@ fn convert_1(source_path : & str, target_substore : & str) {
     }
     // now store all commits
     for (id, sha) in commit_ids.iter() {
-        let target_id = substore.get_or_create_commit_id(sha).0;
+        let target_id = substore.get_or_create_commit_id(sha).unwrap().0;
         commit_mapping.insert(CommitId::from(*id), target_id);
     }
     println!("    {} commit mappings", commit_mapping.len());
@@ -618,7 +635,7 @@ fn export(projects : & str, output_file : & str, max_time : Option<& std::string
         for (project_id, h) in dcd.project_heads() {
             heads.insert(project_id, h);
         }
-        export_projects(& dcd, heads, output_file, max_t);
+        export_projects(& dcd, heads, output_file, "--all", max_t);
     } else {
         if std::path::Path::new(& projects_file).is_dir() {
             for entry in read_dir(& projects_file).unwrap() {
@@ -653,16 +670,38 @@ fn export_filtered_projects(dcd : & DatastoreView, filter : & str, output : & st
             heads.insert(project_id, h);
         }
     }
-    export_projects(dcd, heads, output, max_t);
+    export_projects(dcd, heads, output, filter, max_t);
 }
 
-fn export_projects(dcd : & DatastoreView, heads : HashMap<ProjectId, ProjectHeads>, output : & str, max_t : i64) {
+/** Names and types of the columns of the exported csv files. 
+ 
+    Columns of the original dataset that are not calculated by the artifact are left empty. 
+ */
+const EXPORT_COLUMNS : [(&str, &str); 19] = [
+    ("language", "string"), ("typeclass", "string"), ("langclass", "string"), ("memoryclass", "string"), ("compileclass", "string"),
+    ("project", "u64"), ("sha", "string"), ("files", "u64"), ("committer", "u64"), ("commit_date", "i64"), ("commit_age", "string"),
+    ("insertion", "u64"), ("deletion", "u64"), ("isbug", "u8"), ("bug_type", "string"), ("phase", "string"), ("domain", "string"),
+    ("btype1", "string"), ("btype2", "string"),
+];
+
+/** Exports the commits reachable from given heads into the output file. 
+
+    The manifest of the export is written next to the output with the `.manifest.json` suffix, recording the projects selection and the maximum time. 
+ */
+fn export_projects(dcd : & DatastoreView, heads : HashMap<ProjectId, ProjectHeads>, output : & str, projects : & str, max_t : i64) {
+    let mut manifest = Manifest::new("ecoop-artifact export", dcd);
+    manifest.filter("projects", projects).filter("max_time", max_t);
+    for (name, kind) in EXPORT_COLUMNS.iter() {
+        manifest.column(name, kind);
+    }
+    manifest.write(& format!("{}.manifest.json", output)).unwrap();
     let mut f = File::create(output).unwrap();
-    writeln!(& mut f, "language,typeclass,langclass,memoryclass,compileclass,project,sha,files,committer,commit_date,commit_age,insertion,deletion,isbug,bug_type,phase,domain,btype1,btype2").unwrap();
-    let mut commits = dcd.commits(StoreKind::Generic);
-    let mut commits_info = dcd.commits_info(StoreKind::Generic);
-    let mut paths = dcd.paths_strings(StoreKind::Generic);
-    let mut project_urls = dcd.project_urls();
+    writeln!(& mut f, "{}", EXPORT_COLUMNS.iter().map(|(name, _)| *name).collect::<Vec<& str>>().join(",")).unwrap();
+    let commits = dcd.commits(StoreKind::Generic);
+    let commits_info = dcd.commits_info(StoreKind::Generic);
+    let commits_line_stats = dcd.commits_line_stats(StoreKind::Generic);
+    let paths = dcd.paths_strings(StoreKind::Generic);
+    let project_urls = dcd.project_urls();
     let mut path_langs = HashMap::<PathId, String>::new();
     for (pid, heads) in heads {
         if pid != ProjectId::from(152834) {
@@ -693,23 +732,32 @@ fn export_projects(dcd : & DatastoreView, heads : HashMap<ProjectId, ProjectHead
                 }
                 visited.insert(id);
                 // and analyze the commit
-                analyze_commit(pid, hash, ci, & mut f, id, max_t, & path_langs);
+                let line_stats = commits_line_stats.get(id).unwrap_or_default();
+                analyze_commit(pid, hash, ci, & line_stats, & mut f, id, max_t, & path_langs);
             }
         }
     }
 }
 
-fn analyze_commit(pid : ProjectId, hash : SHA, ci : CommitInfo, f : & mut File, _id : CommitId, _max_t : i64, path_langs : & HashMap<PathId, String> ) {
+/** Analyzes the commit and writes a row for each language of its changed files. 
+ 
+    Additions and deletions are summed from the line stats of the commit, which are zero for commits analyzed without line stats. 
+ */
+fn analyze_commit(pid : ProjectId, hash : SHA, ci : CommitInfo, line_stats : & LineStats, f : & mut File, _id : CommitId, _max_t : i64, path_langs : & HashMap<PathId, String> ) {
     let is_bug = is_bugfixing_commit(& ci);
-    let mut language_counts = HashMap::<String, u64>::new();
+    let mut language_counts = HashMap::<String, (u64, u64, u64)>::new();
     for (path_id, _) in ci.changes.iter() {
         //println!("path_id: {}", path_id);
         let lang = path_langs.get(path_id).unwrap().to_owned();
         if !lang.is_empty() {
-            (*language_counts.entry(lang).or_insert(0)) += 1;
+            let (additions, deletions) = line_stats.get(path_id).cloned().unwrap_or((0, 0));
+            let counts = language_counts.entry(lang).or_insert((0, 0, 0));
+            counts.0 += 1;
+            counts.1 += additions as u64;
+            counts.2 += deletions as u64;
         }
     }
-    for (lang, num_files) in language_counts {
+    for (lang, (num_files, additions, deletions)) in language_counts {
         writeln!(f,"{},,,,,{},{},{},{},{},,{},{},{},,,,,", 
             lang,
             pid,
@@ -717,8 +765,8 @@ fn analyze_commit(pid : ProjectId, hash : SHA, ci : CommitInfo, f : & mut File,
             num_files,
             ci.committer,
             ci.committer_time,
-            0, // additions
-            0, // deletions 
+            additions,
+            deletions,
             if is_bug { 1 } else { 0 }
         ).unwrap();
     }    
@@ -8,20 +8,26 @@ use crate::LOG;
 /** Access to github api. 
  
     - rotate tokens
+    - all requests are performed by a single I/O thread that multiplexes up to `SETTINGS.github_max_requests` concurrent transfers, regardless of how many workers issue them 
  */
 
- use curl::easy::*;
+use curl::easy::*;
+use curl::multi::*;
 
 
 pub struct Github {
     tokens : Mutex<TokensManager>,
+    requests : RequestPool,
 }
 
 impl Github {
 
+    pub const ISSUES_PER_PAGE : usize = 100;
+
     pub fn new(tokens : & str) -> Github {
         return Github{
             tokens : Mutex::new(TokensManager::new(tokens)),
+            requests : RequestPool::new(SETTINGS.github_max_requests),
         }
     }
 
@@ -31,75 +37,342 @@ impl Github {
         return self.request(& format!("https://api.github.com/repos/{}", user_and_repo), task);
     }
 
+    /** Gets the repository information for multiple repositories at once. 
+     
+        The requests overlap, so this is much faster than getting the repositories one by one. Results are returned in the order of the repositories. 
+     */
+    pub fn get_repos(& self, users_and_repos : & [String], task : Option<& TaskStatus>) -> Vec<Result<json::JsonValue, std::io::Error>> {
+        let urls : Vec<String> = users_and_repos.iter().map(|x| format!("https://api.github.com/repos/{}", x)).collect();
+        return self.request_many(& urls, task);
+    }
+
+    /** Gets the information about a single branch of the given repository, including whether the branch is protected.
+     */
+    pub fn get_branch(& self, user_and_repo : & str, branch : & str, task : Option<& TaskStatus>) -> Result<json::JsonValue, std::io::Error> {
+        return self.request(& format!("https://api.github.com/repos/{}/branches/{}", user_and_repo, branch), task);
+    }
+
+    /** Compares pairs of commits of the given repository. 
+     
+        Each pair consists of the base and head commit hashes. The comparisons are requested at once and returned in the order of the pairs. 
+     */
+    pub fn get_comparisons(& self, user_and_repo : & str, pairs : & [(String, String)], task : Option<& TaskStatus>) -> Vec<Result<json::JsonValue, std::io::Error>> {
+        let urls : Vec<String> = pairs.iter().map(|(base, head)| format!("https://api.github.com/repos/{}/compare/{}...{}", user_and_repo, base, head)).collect();
+        return self.request_many(& urls, task);
+    }
+
+    /** Gets a single page of issues and pull requests of the given repository. 
+     
+        Issues are sorted by their update time in ascending order and only issues updated since the given time (unix epoch) are returned. Pages are numbered from 1 and contain up to `Github::ISSUES_PER_PAGE` issues. 
+     */
+    pub fn get_issues(& self, user_and_repo : & str, since : i64, page : usize, task : Option<& TaskStatus>) -> Result<json::JsonValue, std::io::Error> {
+        let since = chrono::DateTime::<chrono::Utc>::from(std::time::UNIX_EPOCH + std::time::Duration::from_secs(since as u64));
+        return self.request(& format!("https://api.github.com/repos/{}/issues?state=all&sort=updated&direction=asc&per_page={}&page={}&since={}", 
+            user_and_repo, 
+            Github::ISSUES_PER_PAGE, 
+            page, 
+            since.format("%Y-%m-%dT%H:%M:%SZ")
+        ), task);
+    }
+
+    /** Loads the access tokens from given file. 
+     
+        The file is a csv file with a header and the tokens in its first column. 
+     */
+    pub fn load_tokens(filename : & str) -> Result<Vec<String>, std::io::Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .double_quote(false)
+            .escape(Some(b'\\'))
+            .from_path(filename)?;
+        let mut tokens = Vec::<String>::new();
+        for x in reader.records() {
+            tokens.push(String::from(& x?[0]));
+        }
+        return Ok(tokens);
+    }
+
+    /** Checks the given token by requesting its rate limit, which does not count against the limit. 
+     
+        Returns the number of remaining requests and the limit of the token. Tokens rejected by GitHub are reported as `PermissionDenied` errors, failures of the transfer itself as `ConnectionAborted`. The request is performed directly, so that it can be used without the request pool. 
+     */
+    pub fn check_token(token : & str) -> Result<(u64, u64), std::io::Error> {
+        return Github::rate_limit(Some(token));
+    }
+
+    /** Checks that the GitHub API can be reached by requesting the rate limit without any token. 
+     
+        Returns the number of remaining unauthenticated requests and their limit, failures of the transfer are reported as `ConnectionAborted` errors. 
+     */
+    pub fn check_api() -> Result<(u64, u64), std::io::Error> {
+        return Github::rate_limit(None);
+    }
+
+    fn rate_limit(token : Option<& str>) -> Result<(u64, u64), std::io::Error> {
+        let mut conn = Easy2::new(Response{ headers : Vec::new(), body : Vec::new() });
+        conn.url("https://api.github.com/rate_limit")?;
+        conn.proxy(SETTINGS.curl_proxy())?;
+        conn.follow_location(true)?;
+        conn.timeout(std::time::Duration::from_secs(30))?;
+        let mut headers = List::new();
+        headers.append("User-Agent: dcd").unwrap();
+        if let Some(token) = token {
+            headers.append(& format!("Authorization: token {}", token)).unwrap();
+        }
+        conn.http_headers(headers)?;
+        conn.perform().map_err(transfer_error)?;
+        match conn.response_code()? {
+            200 => {
+                let value = json::parse(& helpers::to_string(& conn.get_ref().body)).map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Cannot parse json result"))?;
+                return Ok((value["rate"]["remaining"].as_u64().unwrap_or(0), value["rate"]["limit"].as_u64().unwrap_or(0)));
+            },
+            401 => return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "Bad credentials")),
+            code => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("HTTP status {}", code))),
+        }
+    }
+
     /** Performs a github request of the specified url and returns the result string.  
      */
     pub fn request(& self, url : & str, task : Option<& TaskStatus>) -> Result<json::JsonValue, std::io::Error> {
         let mut attempts = 0;
         let max_attempts = self.tokens.lock().unwrap().len();
         loop {
-            let mut response = Vec::new();
-            let mut response_headers = Vec::new();
-            let mut conn = Easy::new();
-            conn.url(url)?;
-            conn.follow_location(true)?;
-            let mut headers = List::new();
-            headers.append("User-Agent: dcd").unwrap();
             let token = self.tokens.lock().unwrap().get_token();
-            headers.append(& format!("Authorization: token {}", token.0)).unwrap();
-            conn.http_headers(headers)?;
-            {
-                let mut ct = conn.transfer();
-                ct.write_function(|data| {
-                    response.extend_from_slice(data);
-                    return Ok(data.len());
-                })?;
-                ct.header_function(|data| {
-                    response_headers.extend_from_slice(data);
-                    return true;
-                })?;
-                ct.perform()?;
+            let response = RequestPool::receive(self.requests.submit(url, & token.0));
+            if let Some(result) = self.process_response(response, token.1, task) {
+                return result;
+            }
+            attempts += 1;
+            // if we have too many attempts, it likely means that the tokens are all used up, wait 10 minutes is primitive and should work alright...
+            if attempts == max_attempts {
+                task.map(|t| { t.info(format!("all Github API tokens ({}) exhausted, sleeping for 10 minutes", max_attempts)) });
+                std::thread::sleep(std::time::Duration::from_millis(1000 * 60 * 10));
+                attempts = 0;
+            }
+        }
+    }
+
+    /** Performs multiple github requests at once and returns their results in the order of the urls. 
+     
+        All requests are submitted before waiting for any of them so that they are performed concurrently. The requests are spread across the available tokens so that a large batch does not exhaust a single token. Requests that have to be repeated with a different token are retried one by one. 
+     */
+    pub fn request_many(& self, urls : & [String], task : Option<& TaskStatus>) -> Vec<Result<json::JsonValue, std::io::Error>> {
+        let tokens = self.tokens.lock().unwrap().get_tokens(urls.len());
+        let pending : Vec<_> = urls.iter().enumerate().map(|(i, url)| {
+            let token = & tokens[i % tokens.len()];
+            return (token.1, self.requests.submit(url, & token.0));
+        }).collect();
+        let mut result = Vec::new();
+        for (url, (token, response)) in urls.iter().zip(pending) {
+            match self.process_response(RequestPool::receive(response), token, task) {
+                Some(x) => result.push(x),
+                None => result.push(self.request(url, task)),
+            }
+        }
+        return result;
+    }
+
+    /** Processes the response to a request made with given token. 
+     
+        Returns the result of the request, or None if the token's rate limit has been exceeded and the request should be repeated with another token. 
+     */
+    fn process_response(& self, response : Result<Response, std::io::Error>, token : usize, task : Option<& TaskStatus>) -> Option<Result<json::JsonValue, std::io::Error>> {
+        let (response_headers, response) = match response {
+            Ok(response) => (response.headers, response.body),
+            Err(e) => return Some(Err(e)),
+        };
+        let rhdr = helpers::to_string(& response_headers).to_lowercase();
+        if rhdr.starts_with("http/1.1 200") || rhdr.starts_with("http/1.1 301") || rhdr.starts_with("http/2 200") || rhdr.starts_with("http/2 301") {
+            let result = json::parse(& helpers::to_string(& response));
+            match result {
+                Ok(value) => return Some(Ok(value)),
+                Err(_) => {
+                    return Some(Err(std::io::Error::new(std::io::ErrorKind::Other, "Cannot parse json result")));
+                }
             }
-            let rhdr = helpers::to_string(& response_headers).to_lowercase();
-            if rhdr.starts_with("http/1.1 200") || rhdr.starts_with("http/1.1 301") || rhdr.starts_with("http/2 200") || rhdr.starts_with("http/2 301") {
+        } else if rhdr.starts_with("http/1.1 401") || rhdr.starts_with("http/1.1 403") || rhdr.starts_with("http/2 401") || rhdr.starts_with("http/2 403") {
+            if rhdr.contains("x-ratelimit-remaining: 0") {
+                // move to next token
+                self.tokens.lock().unwrap().next_token(token);
+                task.map(|t| { t.info("moving to next Github API token") });
+                return None;
+            // check for the secondary rate limit:)
+            } else {
                 let result = json::parse(& helpers::to_string(& response));
                 match result {
-                    Ok(value) => return Ok(value),
+                    Ok(value) => {
+                        if value["message"].is_string() && value["message"].as_str().unwrap() == "You have exceeded a secondary rate limit. Please wait a few minutes before you try again." {
+                            println!("Secondary rate limit: sleep 1m");
+                            std::thread::sleep(std::time::Duration::from_millis(1000 * 60));
+                        }
+                    }
                     Err(_) => {
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Cannot parse json result"));
                     }
                 }
-            } else if rhdr.starts_with("http/1.1 401") || rhdr.starts_with("http/1.1 403") || rhdr.starts_with("http/2 401") || rhdr.starts_with("http/2 403") {
-                if rhdr.contains("x-ratelimit-remaining: 0") {
-                    // move to next token
-                    self.tokens.lock().unwrap().next_token(token.1);
-                    task.map(|t| { t.info("moving to next Github API token") });
-                // check for the secondary rate limit:)
-                } else {
-                    let result = json::parse(& helpers::to_string(& response));
-                    match result {
-                        Ok(value) => {
-                            if value["message"].is_string() && value["message"].as_str().unwrap() == "You have exceeded a secondary rate limit. Please wait a few minutes before you try again." {
-                                println!("Secondary rate limit: sleep 1m");
-                                std::thread::sleep(std::time::Duration::from_millis(1000 * 60));
-                            }
+                return Some(Err(std::io::Error::new(std::io::ErrorKind::Other, rhdr.split("\n").next().unwrap())));
+            }
+        } else if rhdr.starts_with("http/1.1 404") || rhdr.starts_with("http/1.1 410") || rhdr.starts_with("http/2 404") || rhdr.starts_with("http/2 410") {
+            // not found and gone are reported separately so that deleted repositories can be told apart from other errors
+            return Some(Err(std::io::Error::new(std::io::ErrorKind::NotFound, rhdr.split("\n").next().unwrap())));
+        } else if rhdr.starts_with("http/1.1 5") || rhdr.starts_with("http/2 5") {
+            // server errors are transient too
+            return Some(Err(std::io::Error::new(std::io::ErrorKind::ConnectionAborted, rhdr.split("\n").next().unwrap())));
+        } else{
+            return Some(Err(std::io::Error::new(std::io::ErrorKind::Other, rhdr.split("\n").next().unwrap())));
+        }
+    }
+}
+
+/** Raw response to a request, i.e. its headers and body. 
+ */
+struct Response {
+    headers : Vec<u8>,
+    body : Vec<u8>,
+}
+
+impl Handler for Response {
+    fn write(& mut self, data : & [u8]) -> Result<usize, WriteError> {
+        self.body.extend_from_slice(data);
+        return Ok(data.len());
+    }
+
+    fn header(& mut self, data : & [u8]) -> bool {
+        self.headers.extend_from_slice(data);
+        return true;
+    }
+}
+
+struct PendingRequest {
+    url : String,
+    token : String,
+    reply : crossbeam_channel::Sender<Result<Response, std::io::Error>>,
+}
+
+/** Pool of concurrent requests. 
+ 
+    The requests are performed by a dedicated I/O thread using curl's multi interface, which multiplexes all transfers on a single thread. At most `max_requests` transfers are active at any time, further requests wait in a queue until a transfer finishes. The thread terminates when the pool is dropped and all its requests are finished. 
+ */
+struct RequestPool {
+    tx : crossbeam_channel::Sender<PendingRequest>,
+}
+
+impl RequestPool {
+    fn new(max_requests : usize) -> RequestPool {
+        let (tx, rx) = crossbeam_channel::unbounded::<PendingRequest>();
+        std::thread::spawn(move || {
+            RequestPool::perform_requests(rx, std::cmp::max(max_requests, 1));
+        });
+        return RequestPool{ tx };
+    }
+
+    /** Submits the request and returns the channel on which its response will be delivered. 
+     */
+    fn submit(& self, url : & str, token : & str) -> crossbeam_channel::Receiver<Result<Response, std::io::Error>> {
+        let (reply, result) = crossbeam_channel::bounded(1);
+        self.tx.send(PendingRequest{ url : url.to_owned(), token : token.to_owned(), reply }).unwrap();
+        return result;
+    }
+
+    /** Waits for the response delivered on given channel. 
+     
+        If the I/O thread has terminated without delivering the response, an error is returned instead. 
+     */
+    fn receive(response : crossbeam_channel::Receiver<Result<Response, std::io::Error>>) -> Result<Response, std::io::Error> {
+        return response.recv().unwrap_or_else(|e| Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Github request pool terminated: {}", e))));
+    }
+
+    fn perform_requests(rx : crossbeam_channel::Receiver<PendingRequest>, max_requests : usize) {
+        let multi = Multi::new();
+        let mut waiting = std::collections::VecDeque::<PendingRequest>::new();
+        let mut active = std::collections::HashMap::<usize, (Easy2Handle<Response>, crossbeam_channel::Sender<Result<Response, std::io::Error>>)>::new();
+        let mut next_token = 0;
+        let mut disconnected = false;
+        loop {
+            // get new requests, block if there is nothing to do
+            if active.is_empty() && waiting.is_empty() {
+                if disconnected {
+                    return;
+                }
+                match rx.recv() {
+                    Ok(request) => waiting.push_back(request),
+                    Err(_) => return,
+                }
+            }
+            loop {
+                match rx.try_recv() {
+                    Ok(request) => waiting.push_back(request),
+                    Err(crossbeam_channel::TryRecvError::Empty) => break,
+                    Err(crossbeam_channel::TryRecvError::Disconnected) => {
+                        disconnected = true;
+                        break;
+                    }
+                }
+            }
+            // start as many waiting requests as we can
+            while active.len() < max_requests {
+                if let Some(request) = waiting.pop_front() {
+                    match RequestPool::start_request(& multi, & request, next_token) {
+                        Ok(handle) => {
+                            active.insert(next_token, (handle, request.reply));
+                            next_token += 1;
+                        },
+                        Err(e) => {
+                            let _ = request.reply.send(Err(e));
                         }
-                        Err(_) => {
+                    }
+                } else {
+                    break;
+                }
+            }
+            // perform the transfers and deliver responses of those finished
+            let _ = multi.perform();
+            let mut finished = Vec::new();
+            multi.messages(|msg| {
+                if let Ok(token) = msg.token() {
+                    if let Some((handle, _)) = active.get(& token) {
+                        if let Some(result) = msg.result_for2(handle) {
+                            finished.push((token, result));
                         }
                     }
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, rhdr.split("\n").next().unwrap()));
                 }
-            } else{
-                return Err(std::io::Error::new(std::io::ErrorKind::Other, rhdr.split("\n").next().unwrap()));
+            });
+            for (token, result) in finished {
+                let (handle, reply) = active.remove(& token).unwrap();
+                let response = multi.remove2(handle).map(|easy| {
+                    let response = easy.get_ref();
+                    return Response{ headers : response.headers.clone(), body : response.body.clone() };
+                });
+                let _ = match (result, response) {
+                    (Ok(()), Ok(response)) => reply.send(Ok(response)),
+                    (Err(e), _) => reply.send(Err(transfer_error(e))),
+                    (Ok(()), Err(e)) => reply.send(Err(transfer_error(e))),
+                };
             }
-            attempts += 1;
-            // if we have too many attempts, it likely means that the tokens are all used up, wait 10 minutes is primitive and should work alright...
-            if attempts == max_attempts {
-                task.map(|t| { t.info(format!("all Github API tokens ({}) exhausted, sleeping for 10 minutes", max_attempts)) });
-                std::thread::sleep(std::time::Duration::from_millis(1000 * 60 * 10));
-                attempts = 0;
+            if ! active.is_empty() {
+                let _ = multi.wait(& mut [], std::time::Duration::from_millis(100));
             }
         }
     }
+
+    fn start_request(multi : & Multi, request : & PendingRequest, token : usize) -> Result<Easy2Handle<Response>, std::io::Error> {
+        let mut conn = Easy2::new(Response{ headers : Vec::new(), body : Vec::new() });
+        conn.url(& request.url)?;
+        conn.proxy(SETTINGS.curl_proxy())?;
+        conn.follow_location(true)?;
+        let mut headers = List::new();
+        headers.append("User-Agent: dcd").unwrap();
+        headers.append(& format!("Authorization: token {}", request.token)).unwrap();
+        conn.http_headers(headers)?;
+        let mut handle = multi.add2(conn).map_err(transfer_error)?;
+        handle.set_token(token)?;
+        return Ok(handle);
+    }
+}
+
+/** Failures of the transfer itself are transient, so that the update can be retried later. 
+ */
+fn transfer_error<E : Into<Box<dyn std::error::Error + Send + Sync>>>(e : E) -> std::io::Error {
+    return std::io::Error::new(std::io::ErrorKind::ConnectionAborted, e);
 }
 
 struct TokensManager {
@@ -110,15 +383,7 @@ struct TokensManager {
 impl TokensManager {
     fn new(filename : & str) -> TokensManager {
         LOG!("Loading github access tokens from {}", filename);
-        let mut reader = csv::ReaderBuilder::new()
-            .has_headers(true)
-            .double_quote(false)
-            .escape(Some(b'\\'))
-            .from_path(filename).unwrap();
-        let mut tokens = Vec::<String>::new();
-        for x in reader.records() {
-            tokens.push(String::from(& x.unwrap()[0]));
-        }
+        let tokens = Github::load_tokens(filename).unwrap();
         LOG!("    {} tokens found", tokens.len());
         return TokensManager{
             tokens, 
@@ -136,6 +401,16 @@ impl TokensManager {
         return (self.tokens[self.current].clone(), self.current);
     }
 
+    /** Returns up to given number of distinct tokens and their ids, starting with the token `get_token` would return. 
+     */
+    fn get_tokens(& mut self, n : usize) -> Vec<(String, usize)> {
+        let n = std::cmp::max(std::cmp::min(n, self.tokens.len()), 1);
+        return (0..n).map(|i| {
+            let id = (self.current + i) % self.tokens.len();
+            return (self.tokens[id].clone(), id);
+        }).collect();
+    }
+
     fn next_token(& mut self, id : usize) {
         if self.current == id {
             self.current += 1;
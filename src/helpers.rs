@@ -71,6 +71,24 @@ pub fn pretty_duration(mut seconds : i64) -> String {
     }
 }
 
+/** Parses a duration given as a number followed by a unit, i.e. `s`, `m`, `h`, `d`, or `w`, and returns it in seconds. Numbers without unit are seconds. 
+ */
+pub fn parse_duration(duration : & str) -> Option<i64> {
+    let (value, unit) = match duration.find(|c : char| ! c.is_ascii_digit()) {
+        Some(i) => duration.split_at(i),
+        None => (duration, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 24 * 3600,
+        "w" => 7 * 24 * 3600,
+        _ => return None,
+    };
+    return value.parse::<i64>().ok().map(|x| x * multiplier);
+}
+
 pub fn pretty_value(mut value : usize) -> String {
     if value < 1000 {
         return format!("{}", value);
@@ -103,6 +121,103 @@ pub fn pretty_size(mut value : u64) -> String {
     return format!("{}gb", value);
 }
 
+/** Returns the total size of all files in given folder and its subfolders. 
+ 
+    Files that cannot be read are ignored. 
+ */
+pub fn folder_size(path : & std::path::Path) -> u64 {
+    let mut result = 0;
+    if let Ok(entries) = std::fs::read_dir(path) {
+        for entry in entries.filter_map(|x| x.ok()) {
+            match entry.metadata() {
+                Ok(m) if m.is_dir() => result += folder_size(& entry.path()),
+                Ok(m) => result += m.len(),
+                Err(_) => {},
+            }
+        }
+    }
+    return result;
+}
+
+/** Determines whether the path matches given glob pattern. 
+ 
+    `*` matches any characters except `/`, `**` matches any characters including `/` and `?` matches any single character except `/`. Same as in `.gitignore`, patterns without a `/` match the name of the file or of any folder on the path, while patterns with a `/` match the path from the root, or any folder on it, i.e. `vendor` matches `lib/vendor/x.c` and `lib/vendor` matches `lib/vendor/x.c`, but not `src/lib/vendor/x.c`. 
+ */
+pub fn path_matches(pattern : & str, path : & str) -> bool {
+    let pattern = pattern.trim_end_matches('/');
+    if pattern.contains('/') {
+        let pattern = pattern.trim_start_matches('/').as_bytes();
+        return path.match_indices('/').map(|(i, _)| & path[..i]).chain(std::iter::once(path))
+            .any(|x| glob_matches(pattern, x.as_bytes()));
+    } else {
+        return path.split('/').any(|x| glob_matches(pattern.as_bytes(), x.as_bytes()));
+    }
+}
+
+/** Url glob pattern prepared once for matching many urls. 
+ 
+    The pattern must match the whole url, with `*` and `?` not matching `/` and `**` matching anything, so that e.g. the pattern `https://github.com/user/` followed by `*` matches all repositories of the user. 
+ 
+    The literal prefix of the pattern before its first wildcard is compared first, so that urls which do not match, i.e. most urls checked against the denylist, are rejected without the recursive matching. Patterns without any wildcards are compared for equality. 
+ */
+#[derive(Clone, Debug)]
+pub struct UrlPattern {
+    prefix : String,
+    /** The rest of the pattern from its first wildcard on, None if the pattern has no wildcards. 
+     */
+    rest : Option<Vec<u8>>,
+}
+
+impl UrlPattern {
+    pub fn new(pattern : & str) -> UrlPattern {
+        match pattern.find(& ['*', '?'][..]) {
+            Some(i) => return UrlPattern{ prefix : pattern[..i].to_owned(), rest : Some(pattern.as_bytes()[i..].to_vec()) },
+            None => return UrlPattern{ prefix : pattern.to_owned(), rest : None },
+        }
+    }
+
+    pub fn matches(& self, url : & str) -> bool {
+        match & self.rest {
+            Some(rest) => return url.starts_with(self.prefix.as_str()) && glob_matches(rest, & url.as_bytes()[self.prefix.len()..]),
+            None => return url == self.prefix,
+        }
+    }
+}
+
+fn glob_matches(pattern : & [u8], text : & [u8]) -> bool {
+    if pattern.is_empty() {
+        return text.is_empty();
+    }
+    if pattern.starts_with(b"**/") {
+        // matches zero or more folders
+        return glob_matches(& pattern[3..], text) || (0..text.len()).any(|i| text[i] == b'/' && glob_matches(& pattern[3..], & text[i + 1..]));
+    } else if pattern.starts_with(b"**") {
+        return (0..=text.len()).any(|i| glob_matches(& pattern[2..], & text[i..]));
+    }
+    match pattern[0] {
+        b'*' => {
+            let end = text.iter().position(|x| *x == b'/').unwrap_or(text.len());
+            return (0..=end).any(|i| glob_matches(& pattern[1..], & text[i..]));
+        },
+        b'?' => return ! text.is_empty() && text[0] != b'/' && glob_matches(& pattern[1..], & text[1..]),
+        x => return ! text.is_empty() && text[0] == x && glob_matches(& pattern[1..], & text[1..]),
+    }
+}
+
+/** Returns the free space in bytes and the number of free inodes of the volume on which given path resides. 
+ 
+    Only the space and inodes available to unprivileged users are reported. The inodes are None if the filesystem does not have a fixed number of them (such as btrfs, which reports zero). Returns None if the path does not exist. 
+ */
+pub fn disk_resources(path : & str) -> Option<(u64, Option<u64>)> {
+    let path = std::ffi::CString::new(path).ok()?;
+    let mut stats : libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), & mut stats) } != 0 {
+        return None;
+    }
+    let inodes = if stats.f_files == 0 { None } else { Some(stats.f_favail as u64) };
+    return Some((stats.f_bavail as u64 * stats.f_frsize as u64, inodes));
+}
+
 /** Returns the process usage of memory and cpu. 
  
     Just use ps. i.e. ps -x -o pid,%mem,%cpu and then grep for our pid.
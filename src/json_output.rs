@@ -0,0 +1,196 @@
+use crate::*;
+use crate::table_readers::CachedReader;
+
+/** Returns the project of given id as json, or None if there is no such project. 
+
+    The object contains the project `id`, `url`, `substore` (null if not assigned), its update `log` (see `log_json`), `heads` and `tags` by their names, each with the `id` and `hash` of the commit, the `default_branch` and `license`, current `labels`, the latest `summary` and the latest values of its `metadata`. Metadata values which are json themselves, such as the GitHub metadata, are embedded as such. 
+ */
+pub fn project_json(ds : & DatastoreView, pid : ProjectId) -> Option<json::JsonValue> {
+    let url = ds.project_urls().get(pid)?;
+    let mut result = json::object!{
+        "id" => u64::from(pid),
+        "url" => url.clone_url(),
+        "substore" => json::JsonValue::Null,
+        "log" => json::JsonValue::new_array(),
+        "heads" => json::JsonValue::new_object(),
+        "tags" => json::JsonValue::new_object(),
+        "default_branch" => ds.project_default_branch(pid),
+        "license" => ds.project_license(pid),
+        "labels" => ds.project_labels_reader().get(pid).unwrap_or_default(),
+        "summary" => json::JsonValue::Null,
+        "metadata" => json::JsonValue::new_object(),
+    };
+    if let Some(substore) = ds.project_substore_of(pid).filter(|x| *x != StoreKind::Unspecified) {
+        result["substore"] = format!("{:?}", substore).into();
+    }
+    for log in ds.project_log_history(pid) {
+        result["log"].push(log_json(& log)).unwrap();
+    }
+    if let Some(heads) = ds.project_heads_of(pid) {
+        result["heads"] = refs_json(& heads);
+    }
+    if let Some(tags) = ds.project_tags_reader().get(pid) {
+        result["tags"] = refs_json(& tags);
+    }
+    if let Some(summary) = ds.project_summaries_reader().get(pid) {
+        result["summary"] = json::object!{
+            "time" => summary.time,
+            "commits" => summary.commits,
+            "authors" => summary.authors,
+            "committers" => summary.committers,
+            "first_commit_time" => summary.first_commit_time,
+            "last_commit_time" => summary.last_commit_time,
+        };
+    }
+    for md in ds.project_metadata_of(pid) {
+        result["metadata"][md.key.as_str()] = json::parse(& md.value).unwrap_or(md.value.into());
+    }
+    return Some(result);
+}
+
+fn refs_json(refs : & ProjectHeads) -> json::JsonValue {
+    let mut result = json::JsonValue::new_object();
+    for (name, (id, hash)) in refs.iter() {
+        result[name.as_str()] = json::object!{ "id" => u64::from(*id), "hash" => hash.to_string() };
+    }
+    return result;
+}
+
+/** Returns the project log record as json. 
+
+    Each record is an object with the `time` and datastore `version` of the record and its `kind`, i.e. `no_change`, `ok`, `rename`, `change_store`, `retry`, `skipped`, `merged`, `path_filters`, `deleted`, `denied`, or `error`, followed by the fields of the record of given kind. 
+ */
+pub fn log_json(log : & ProjectLog) -> json::JsonValue {
+    let mut result = json::object!{
+        "time" => log.time(),
+        "version" => log.version(),
+    };
+    match log {
+        ProjectLog::NoChange{..} => {
+            result["kind"] = "no_change".into();
+        },
+        ProjectLog::Ok{time : _, version : _, changes} => {
+            result["kind"] = "ok".into();
+            result["changes"] = match changes {
+                Some(changes) => json::object!{
+                    "new_commits" => changes.new_commits,
+                    "changed_heads" => changes.changed_heads,
+                    "new_contents" => changes.new_contents,
+                },
+                None => json::JsonValue::Null,
+            };
+        },
+        ProjectLog::Rename{time : _, version : _, old_offset} => {
+            result["kind"] = "rename".into();
+            result["old_offset"] = (*old_offset).into();
+        },
+        ProjectLog::ChangeStore{time : _, version : _, new_kind} => {
+            result["kind"] = "change_store".into();
+            result["substore"] = format!("{:?}", new_kind).into();
+        },
+        ProjectLog::Retry{time : _, version : _, error, attempt} => {
+            result["kind"] = "retry".into();
+            result["attempt"] = (*attempt).into();
+            result["error"] = error.as_str().into();
+        },
+        ProjectLog::Skipped{time : _, version : _, reason} => {
+            result["kind"] = "skipped".into();
+            result["reason"] = reason.as_str().into();
+        },
+        ProjectLog::Merged{time : _, version : _, source, old_id} => {
+            result["kind"] = "merged".into();
+            result["source"] = source.as_str().into();
+            result["old_id"] = (*old_id).into();
+        },
+        ProjectLog::PathFilters{time : _, version : _, filters} => {
+            result["kind"] = "path_filters".into();
+            result["filters"] = filters.as_str().into();
+        },
+        ProjectLog::Deleted{time : _, version : _, reason} => {
+            result["kind"] = "deleted".into();
+            result["reason"] = reason.as_str().into();
+        },
+        ProjectLog::Denied{time : _, version : _, reason} => {
+            result["kind"] = "denied".into();
+            result["reason"] = reason.as_str().into();
+        },
+        ProjectLog::Error{time : _, version : _, kind, error} => {
+            result["kind"] = "error".into();
+            result["error_kind"] = kind.name().into();
+            if let UpdateError::GitError{code} = kind {
+                result["git_error_code"] = (*code).into();
+            }
+            result["error"] = error.as_str().into();
+        },
+    }
+    return result;
+}
+
+/** Converts commits of a substore to json. 
+
+    The hashes, users and paths referenced by the commits are read through caches of given size (see `CachedReader`), since commits of the same project share most of them. Zero entries disable the caches. 
+ */
+pub struct CommitJson {
+    substore : StoreKind,
+    commit_hashes : CachedReader<table_readers::MappingReader<SHA, CommitId>>,
+    users : CachedReader<table_readers::IndirectMappingReader<String, UserId>>,
+    paths : CachedReader<table_readers::StoreReader<PathString, PathId>>,
+    hashes : CachedReader<table_readers::MappingReader<SHA, HashId>>,
+    submodules : Option<table_readers::StoreReader<CommitSubmodules, CommitId>>,
+}
+
+impl CommitJson {
+
+    pub fn new(ds : & DatastoreView, substore : StoreKind, cache_entries : usize, cache_bytes : usize) -> CommitJson {
+        return CommitJson{
+            substore,
+            commit_hashes : CachedReader::new(ds.commits(substore), cache_entries, cache_bytes),
+            users : CachedReader::new(ds.users(substore), cache_entries, cache_bytes),
+            paths : CachedReader::new(ds.paths_strings(substore), cache_entries, cache_bytes),
+            hashes : CachedReader::new(ds.hashes(substore), cache_entries, cache_bytes),
+            submodules : ds.commits_submodules_if_recorded(substore),
+        };
+    }
+
+    /** Returns the given commit as json. 
+
+        The object contains the `substore`, `id` and `hash` of the commit, the emails and ids of its `committer` and `author` with their times, the hashes of its `parents`, its `message`, the `changes` with the `path` and `hash` of the new contents (null for deleted files) together with their ids, and the `submodules` it changed with their `path`, `commit` (null if the submodule was removed) and `url`. 
+     */
+    pub fn get(& self, id : CommitId, commit : & CommitInfo) -> json::JsonValue {
+        let mut result = json::object!{
+            "substore" => format!("{:?}", self.substore),
+            "id" => u64::from(id),
+            "hash" => self.commit_hashes.get(id).map(|x| x.to_string()),
+            "committer" => self.users.get(commit.committer),
+            "committer_id" => u64::from(commit.committer),
+            "committer_time" => commit.committer_time,
+            "author" => self.users.get(commit.author),
+            "author_id" => u64::from(commit.author),
+            "author_time" => commit.author_time,
+            "parents" => json::JsonValue::new_array(),
+            "message" => commit.message.as_str(),
+            "changes" => json::JsonValue::new_array(),
+            "submodules" => json::JsonValue::new_array(),
+        };
+        for parent in commit.parents.iter() {
+            result["parents"].push(self.commit_hashes.get(*parent).map(|x| x.to_string())).unwrap();
+        }
+        for (path_id, hash_id) in commit.changes.iter() {
+            result["changes"].push(json::object!{
+                "path" => self.paths.get(*path_id),
+                "path_id" => u64::from(*path_id),
+                "hash" => if *hash_id == HashId::DELETED { None } else { self.hashes.get(*hash_id).map(|x| x.to_string()) },
+                "hash_id" => u64::from(*hash_id),
+            }).unwrap();
+        }
+        for submodule in self.submodules.as_ref().and_then(|x| x.get(id)).unwrap_or_default() {
+            result["submodules"].push(json::object!{
+                "path" => self.paths.get(submodule.path),
+                "path_id" => u64::from(submodule.path),
+                "commit" => if submodule.commit.is_zero() { None } else { Some(submodule.commit.to_string()) },
+                "url" => submodule.url,
+            }).unwrap();
+        }
+        return result;
+    }
+}
@@ -17,19 +17,49 @@ pub mod records;
 #[allow(dead_code)]
 mod datastore;
 #[allow(dead_code)]
+mod substore_classifier;
+#[allow(dead_code)]
+mod license_classifier;
+#[allow(dead_code)]
 mod updater;
 #[allow(dead_code)]
 mod datastore_maintenance_tasks;
 mod task_update_repo;
+mod task_update_issues;
 mod task_update_substore;
 mod task_verify_substore;
+#[allow(dead_code)]
+mod task_pack_substore;
+mod task_index_messages;
+mod task_index_commit_projects;
+mod task_index_files;
+mod task_commit_metrics;
+mod task_resolve_users;
+#[allow(dead_code)]
 mod github;
 #[allow(dead_code)]
 mod settings;
 #[allow(dead_code)]
 mod reporter;
+pub mod table_readers;
+pub mod csv_input;
+pub mod script_filter;
+/** Json representation of projects, their logs and commits. 
+
+    Shared by all commands printing projects and commits as json, i.e. `query` of parasite and `show-project` and `show-commits` of mistletoe, so that they print the same schema. 
+ */
+pub mod json_output;
+/** Stable API over the datastore. 
+
+    The internal records and tables change their layout between datastore versions, the types in this module do not. They use typed ids, strings and bytes and are converted from the records of every supported datastore version, so that analyses built against this module keep working when the on-disk format is bumped. 
+ */
+pub mod api;
 
 pub use db::Id;
+pub use db::TableStorage;
+pub use db::StorageBackend;
+pub use db::FileBackend;
+pub use db::set_storage_backend;
 pub use db::Table;
 pub use db::TableOwningIterator;
 pub use db::SplitTable;
@@ -43,86 +73,815 @@ use crate::datastore::*;
 
 /** A simple, read-only view into the datastore. 
  
+    By default the view reads whatever is currently on disk. A view pinned to a savepoint (see `at_savepoint`) only sees the data that existed when the savepoint was created, which gives analyses a consistent snapshot even if an updater is writing to the datastore at the same time.
+
+    Views that are not pinned hold a shared lock of the datastore, so that no other process can open the datastore for writing while they read it. 
  */
 pub struct DatastoreView {
-    root : String
+    root : String,
+    savepoint : Option<db::Savepoint>,
+    restriction : Option<std::sync::Arc<Restriction>>,
+    lock : Option<std::sync::Arc<DatastoreLock>>,
+    pin : Option<std::sync::Arc<DatastoreLock>>,
 }
 
 
 impl DatastoreView {
+    /** Number of records read at once by the iterators of restricted views. 
+     */
+    const RESTRICTED_CHUNK : usize = 65536;
+
     /** Returns new datastore with given root.
+
+        If another process is writing to the datastore, the view is pinned to the latest savepoint instead, so that it does not read partially written data. If there are no savepoints, the view reads whatever is on disk. Pinned views lock the datastore's pins file shared, so that the datastore cannot be reverted, nor its contents collected while they are in use (see `DatastoreLock`). 
      */
     pub fn from(root : & str) -> DatastoreView {
         // TODO check that there is a valid datastore on the path first
-        return DatastoreView{
-            root : root.to_owned()
+        let mut result = DatastoreView{
+            root : root.to_owned(),
+            savepoint : None,
+            restriction : None,
+            lock : None,
+            pin : None,
         };
+        match DatastoreLock::acquire(root, false) {
+            Ok(lock) => result.lock = Some(std::sync::Arc::new(lock)),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                result.savepoint = result.savepoints().max_by_key(|sp| sp.time());
+                if result.savepoint.is_some() {
+                    result.pin = DatastoreView::pin(root);
+                }
+            },
+            // the lock file cannot be created, e.g. on readonly media, where no writer could lock the datastore either
+            Err(_) => {},
+        }
+        return result;
+    }
+
+    /** Returns a view of the same datastore pinned to the savepoint of given name, or None if no such savepoint exists.  
+
+        All tables returned by the pinned view are capped at the sizes recorded in the savepoint. The returned view is never restricted, a view should first be pinned and then restricted so that the restriction is computed from the pinned data. Like the views pinned by `from`, the view locks the pins file of the datastore while in use. 
+     */
+    pub fn at_savepoint(& self, name : & str) -> Option<DatastoreView> {
+        // the savepoint itself is not part of the tables it describes, so all savepoints must be searched
+        let savepoint = table_readers::LinkedStoreReader::<db::Savepoint, u64>::new(& self.root, & DatastoreView::table_filename(Datastore::SAVEPOINTS), None).into_iter()
+            .map(|(_, sp)| sp)
+            .find(|sp| sp.name() == name)?;
+        return Some(DatastoreView{
+            root : self.root.clone(),
+            savepoint : Some(savepoint),
+            restriction : None,
+            lock : self.lock.clone(),
+            pin : DatastoreView::pin(& self.root),
+        });
+    }
+
+    /** Locks the pins file of the datastore for a pinned view. 
+     
+        The lock is only missing if the file cannot be locked at all, e.g. on readonly media, where the datastore cannot be reverted either. 
+     */
+    fn pin(root : & str) -> Option<std::sync::Arc<DatastoreLock>> {
+        match DatastoreLock::pin(root) {
+            Ok(lock) => return Some(std::sync::Arc::new(lock)),
+            Err(e) => {
+                println!("WARNING: Unable to pin datastore in {}: {}", root, e);
+                return None;
+            }
+        }
+    }
+
+    /** Returns the savepoint the view is pinned to, if any. 
+     */
+    pub fn savepoint(& self) -> Option<& db::Savepoint> {
+        return self.savepoint.as_ref();
     } 
 
-    pub fn project_urls(& self) -> impl Table<Id = ProjectId, Value = ProjectUrl>  {// impl Iterator<Item = (ProjectId, ProjectUrl)> {
-        return db::Store::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECTS), true); //.into_iter();
+    /** Returns a view of the same datastore restricted to the given projects. 
+
+        The commits reachable from the heads of the projects, and the paths and contents hashes changed by these commits are precomputed when the view is created. Project iterators of the restricted view then skip all other projects, commit, path and contents iterators of the substores (`commits_metadata`, `contents_metadata`, `iter_commits_info`, `iter_paths_strings` and `iter_contents`) skip all other commits, paths and contents. The latter read only the records of the subset via the indices, which is much faster than scanning the whole substore when the subset is small. The readers returned by the view are not restricted, random access to them is not affected. 
+
+        Projects that have no substore or heads are part of the restriction, but contribute no commits. 
+     */
+    pub fn restricted(& self, project_ids : & [ProjectId]) -> DatastoreView {
+        let substores = table_readers::StoreReader::<StoreKind, ProjectId>::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_SUBSTORES), self.savepoint.as_ref());
+        let heads = table_readers::StoreReader::<ProjectHeads, ProjectId>::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_HEADS), self.savepoint.as_ref());
+        let mut restriction = Restriction{
+            projects : project_ids.iter().map(|x| *x).collect(),
+            substores : HashMap::new(),
+        };
+        let mut commits_info = HashMap::<StoreKind, table_readers::StoreReader<CommitInfo, CommitId>>::new();
+        for pid in project_ids {
+            let (substore, project_heads) = match (substores.get(*pid), heads.get(*pid)) {
+                (Some(substore), Some(project_heads)) if substore != StoreKind::Unspecified => (substore, project_heads),
+                _ => continue,
+            };
+            let commits = commits_info.entry(substore).or_insert_with(|| self.commits_info(substore));
+            let sr = restriction.substores.entry(substore).or_insert_with(SubstoreRestriction::new);
+            // commits already reached from other projects (such as forks) are not walked again
+            let mut q : Vec<CommitId> = project_heads.values().map(|(id, _)| *id).filter(|id| *id != CommitId::NONE).collect();
+            while let Some(id) = q.pop() {
+                if ! sr.commits.insert(id) {
+                    continue;
+                }
+                if let Some(commit) = commits.get(id) {
+                    for (path, hash) in commit.changes.iter() {
+                        sr.paths.insert(*path);
+                        if *hash != HashId::DELETED {
+                            sr.hashes.insert(*hash);
+                        }
+                    }
+                    q.extend(commit.parents.iter());
+                }
+            }
+        }
+        return DatastoreView{
+            root : self.root.clone(),
+            savepoint : self.savepoint.clone(),
+            restriction : Some(std::sync::Arc::new(restriction)),
+            lock : self.lock.clone(),
+            pin : self.pin.clone(),
+        };
+    }
+
+    /** Returns the restriction of the view, or None if the view sees all projects. 
+     */
+    pub fn restriction(& self) -> Option<& Restriction> {
+        return self.restriction.as_ref().map(|x| x.as_ref());
+    }
+
+    /** Returns true if given project is visible in the view, i.e. the view is not restricted, or the project is in its restriction. 
+     */
+    pub fn includes_project(& self, id : ProjectId) -> bool {
+        return self.restriction.as_ref().map(|x| x.projects.contains(& id)).unwrap_or(true);
+    }
+
+    pub fn project_urls(& self) -> table_readers::StoreReader<ProjectUrl, ProjectId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECTS), self.savepoint.as_ref());
     }
 
     pub fn project_substores(& self) -> impl Iterator<Item = (ProjectId, StoreKind)> {
-        return db::Store::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_SUBSTORES), true).into_iter();
+        return self.restrict_projects(table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_SUBSTORES), self.savepoint.as_ref()).into_iter());
     }
 
     pub fn project_updates(& self) -> impl Iterator<Item = (ProjectId, ProjectLog)> {
-        return db::LinkedStore::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_UPDATES), true).into_iter();
+        return self.restrict_projects(table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_UPDATES), self.savepoint.as_ref()).into_iter());
+    }
+
+    /** Returns the full update log of given project, oldest records first. 
+     
+        Unlike filtering `project_updates`, only the records of the project are read by following the links of the store, so that renames, errors and substore changes of a single project can be reconstructed cheaply. 
+     */
+    pub fn project_log_history(& self, id : ProjectId) -> Vec<ProjectLog> {
+        let updates = table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_UPDATES), self.savepoint.as_ref());
+        let mut result : Vec<ProjectLog> = updates.iter_id(id).collect();
+        result.reverse();
+        return result;
+    }
+
+    /** Returns the project updates recorded after given savepoint, up to the savepoint of the view, if any. 
+     
+        The savepoint must be older than the view. Only the part of the project updates store added since the savepoint is read. 
+     */
+    pub fn project_updates_since(& self, since : & db::Savepoint) -> Vec<(ProjectId, ProjectLog)> {
+        let updates = table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_UPDATES), self.savepoint.as_ref());
+        let offset = since.limit_for(& format!("{}.store", Datastore::PROJECT_UPDATES));
+        return updates.iter_from(offset).collect();
     }
 
     pub fn project_heads(& self) -> impl Iterator<Item = (ProjectId, ProjectHeads)> {
-        return db::Store::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_HEADS), true).into_iter();
+        return self.restrict_projects(table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_HEADS), self.savepoint.as_ref()).into_iter());
+    }
+
+    pub fn project_tags(& self) -> impl Iterator<Item = (ProjectId, ProjectTags)> {
+        return self.restrict_projects(table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_TAGS), self.savepoint.as_ref()).into_iter());
+    }
+
+    pub fn project_labels(& self) -> impl Iterator<Item = (ProjectId, ProjectLabels)> {
+        return self.restrict_projects(table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_LABELS), self.savepoint.as_ref()).into_iter());
+    }
+
+    /** Returns ids of all projects that currently have the given label. 
+     
+        The ids are sorted so that exports of labelled subsets are reproducible. 
+     */
+    pub fn projects_with_label(& self, label : & str) -> Vec<ProjectId> {
+        let mut labelled = HashMap::<ProjectId, bool>::new();
+        for (id, labels) in self.project_labels() {
+            labelled.insert(id, labels.iter().any(|x| x == label));
+        }
+        let mut result : Vec<ProjectId> = labelled.into_iter().filter(|(_, has_label)| *has_label).map(|(id, _)| id).collect();
+        result.sort_by_key(|id| u64::from(*id));
+        return result;
     }
 
     pub fn project_metadata(& self) -> impl Iterator<Item = (ProjectId, Metadata)> {
-        return db::LinkedStore::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_METADATA), true).into_iter();
+        return self.restrict_projects(table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_METADATA), self.savepoint.as_ref()).into_iter());
+    }
+
+    /** Summaries of the projects, calculated whenever the project heads change. 
+     
+        Every recalculation is stored as a new record, the last one for each project being the current summary. 
+     */
+    pub fn project_summaries(& self) -> impl Iterator<Item = (ProjectId, ProjectSummary)> {
+        return self.restrict_projects(table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_SUMMARIES), self.savepoint.as_ref()).into_iter());
+    }
+
+    /** Popularity of the GitHub projects, recorded every time their metadata are checked. 
+     
+        Records of each project are returned in the order they were recorded, i.e. the popularity over time. 
+     */
+    pub fn project_popularity(& self) -> impl Iterator<Item = (ProjectId, ProjectPopularity)> {
+        return self.restrict_projects(table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_POPULARITY), self.savepoint.as_ref()).into_iter());
+    }
+
+    /** Default branches of the projects, recorded whenever they change. 
+     */
+    pub fn project_default_branches(& self) -> impl Iterator<Item = (ProjectId, DefaultBranch)> {
+        return self.restrict_projects(table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_DEFAULT_BRANCHES), self.savepoint.as_ref()).into_iter());
+    }
+
+    /** Heads of the projects as seen by heads only updates, recorded whenever they change. 
+     
+        The commits of the heads have not been analyzed, so their ids are `CommitId::NONE` and only their hashes are valid. 
+     */
+    pub fn project_remote_heads(& self) -> impl Iterator<Item = (ProjectId, ProjectHeads)> {
+        return self.restrict_projects(table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_REMOTE_HEADS), self.savepoint.as_ref()).into_iter());
+    }
+
+    /** Returns the name of the default branch of given project, if known. 
+     
+        The name is without the `refs/heads/` prefix. 
+     */
+    pub fn project_default_branch(& self, id : ProjectId) -> Option<String> {
+        return table_readers::StoreReader::<DefaultBranch, ProjectId>::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_DEFAULT_BRANCHES), self.savepoint.as_ref()).get(id).map(|branch| branch.name);
+    }
+
+    /** README and LICENSE files of the projects, as stored in their summaries. 
+     
+        Like the summaries, every recalculation is returned, the last one for each project being the current files. Summaries whose files could not be determined are skipped. The hash ids of the files are from the substore the project belonged to when the files were recorded. 
+     */
+    pub fn project_files(& self) -> impl Iterator<Item = (ProjectId, ProjectFiles)> {
+        return self.project_summaries().filter_map(|(id, summary)| summary.files.map(|files| (id, files)));
+    }
+
+    /** Returns the SPDX identifier of the license of given project, if it was recognized. 
+     */
+    pub fn project_license(& self, id : ProjectId) -> Option<String> {
+        return table_readers::LinkedStoreReader::<ProjectSummary, ProjectId>::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_SUMMARIES), self.savepoint.as_ref()).get(id)
+            .and_then(|summary| summary.files)
+            .and_then(|files| files.license_id);
+    }
+
+    /** Returns the current substore of given project, if the project has been assigned one. 
+     
+        Unlike filtering `project_substores`, the latest record is found via the index of the store, so that single project queries do not have to scan all projects. The same holds for the other single project getters below. 
+     */
+    pub fn project_substore_of(& self, id : ProjectId) -> Option<StoreKind> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_SUBSTORES), self.savepoint.as_ref()).get(id);
+    }
+
+    /** Returns the heads of given project as of its last update, if it has been updated. 
+     */
+    pub fn project_heads_of(& self, id : ProjectId) -> Option<ProjectHeads> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_HEADS), self.savepoint.as_ref()).get(id);
+    }
+
+    /** Returns the heads records of given project with the times they were stored at and the substore of the project at the time, oldest first. 
+     
+        The heads store does not record times, so the heads records of the project are paired with the records of its update log that store heads, i.e. the substore changes, which reset the heads, and the ok records of updates that changed the heads (or of all updates before the changes of updates were recorded). The pairing goes from the latest records backwards, heads records older than any such log record, such as of projects merged from other datastores, which only keep their latest log record, are not returned. 
+     */
+    pub fn project_heads_history(& self, id : ProjectId) -> Vec<(i64, StoreKind, ProjectHeads)> {
+        let heads : Vec<ProjectHeads> = table_readers::StoreReader::<ProjectHeads, ProjectId>::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_HEADS), self.savepoint.as_ref())
+            .iter()
+            .filter(|(pid, _)| *pid == id)
+            .map(|(_, heads)| heads)
+            .collect();
+        let mut substore = None;
+        let mut times = Vec::new();
+        for log in self.project_log_history(id) {
+            match log {
+                ProjectLog::ChangeStore{time, version : _, new_kind} => {
+                    substore = Some(new_kind);
+                    times.push((time, new_kind));
+                },
+                ProjectLog::Ok{time, version : _, changes} if changes.map(|x| x.changed_heads > 0).unwrap_or(true) => {
+                    times.push((time, substore.unwrap_or(StoreKind::Unspecified)));
+                },
+                _ => {},
+            }
+        }
+        let current = self.project_substore_of(id).unwrap_or(StoreKind::Unspecified);
+        let mut result : Vec<(i64, StoreKind, ProjectHeads)> = heads.into_iter().rev().zip(times.into_iter().rev())
+            .map(|(heads, (time, substore))| (time, if substore == StoreKind::Unspecified { current } else { substore }, heads))
+            .collect();
+        result.reverse();
+        return result;
+    }
+
+    /** Returns all metadata records of given project, oldest first. 
+     */
+    pub fn project_metadata_of(& self, id : ProjectId) -> Vec<Metadata> {
+        let metadata = table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_METADATA), self.savepoint.as_ref());
+        let mut result : Vec<Metadata> = metadata.iter_id(id).collect();
+        result.reverse();
+        return result;
+    }
+
+    /** Returns the latest update log record of given project, if any. 
+     
+        The whole log of the project is returned by `project_log_history`. 
+     */
+    pub fn project_latest_update(& self, id : ProjectId) -> Option<ProjectLog> {
+        return table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_UPDATES), self.savepoint.as_ref()).get(id);
+    }
+
+    /** Returns the unique id of the datastore, if it has one. 
+     */
+    pub fn id(& self) -> Option<String> {
+        return Datastore::stored_id(& self.root);
+    }
+
+    /** Returns true if the user emails in the datastore are anonymized. 
+     
+        The emails of anonymized datastores are keyed hashes of the actual emails, so users can still be told apart, but their identities are pseudonymous. 
+     */
+    pub fn anonymized(& self) -> bool {
+        return Datastore::email_key_fingerprint(& self.root).is_some();
     }
 
     pub fn savepoints(& self) -> impl Iterator<Item = db::Savepoint> {
-        return db::LinkedStore::<db::Savepoint, u64>::new(& self.root, & DatastoreView::table_filename(Datastore::SAVEPOINTS), true).into_iter().map(|(_, sp)| sp);
+        return table_readers::LinkedStoreReader::<db::Savepoint, u64>::new(& self.root, & DatastoreView::table_filename(Datastore::SAVEPOINTS), self.savepoint.as_ref()).into_iter().map(|(_, sp)| sp);
+    }
+
+    /** Executes the savepoints command of parasite with given arguments and returns its output, see `Savepoint::command`. 
+     
+        Like `at_savepoint`, all savepoints are listed even if the view is pinned to one of them. 
+     */
+    pub fn savepoints_command(& self, args : & [& str]) -> Result<String, std::io::Error> {
+        let savepoints = table_readers::LinkedStoreReader::<db::Savepoint, u64>::new(& self.root, & DatastoreView::table_filename(Datastore::SAVEPOINTS), None).into_iter()
+            .map(|(_, sp)| sp)
+            .collect();
+        return db::Savepoint::command(savepoints, args);
     }
 
     /* Substore contents getters and iterators. 
      */
-    pub fn commits(& self, substore : StoreKind) -> impl Table<Id = CommitId, Value = SHA> {
-        return db::Mapping::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS), true);
+    /** Returns the substores that contain a commit and the substores that contain file contents of given hash. 
+     
+        Uses the global hash index, which is not pinned to savepoints. If the datastore has no index, nothing is found. 
+     */
+    pub fn find_sha(& self, hash : & SHA) -> (Vec<StoreKind>, Vec<StoreKind>) {
+        let bitmap = db::BitmapIndex::<SHA>::open(& self.root, Datastore::SHA_SUBSTORES).and_then(|mut x| x.get(hash).ok()).unwrap_or(0);
+        return (Datastore::substores_in_bitmap(bitmap), Datastore::substores_in_bitmap(bitmap >> 32));
+    }
+
+    pub fn commits(& self, substore : StoreKind) -> table_readers::MappingReader<SHA, CommitId> {
+        return table_readers::MappingReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS), self.savepoint.as_ref());
+    }
+
+    pub fn commits_info(& self, substore : StoreKind) -> table_readers::StoreReader<CommitInfo, CommitId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_INFO), self.savepoint.as_ref());
+    }
+
+    /** Returns the numbers of added and deleted lines per changed path of the commits in given substore. 
+     
+        Only commits analyzed with line stats enabled have them. 
+     */
+    pub fn commits_line_stats(& self, substore : StoreKind) -> table_readers::StoreReader<LineStats, CommitId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_LINE_STATS), self.savepoint.as_ref());
+    }
+
+    /** Returns the submodules changed by the commits in given substore. 
+     
+        Only commits that change any gitlinks have them. 
+     */
+    pub fn commits_submodules(& self, substore : StoreKind) -> table_readers::StoreReader<CommitSubmodules, CommitId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_SUBMODULES), self.savepoint.as_ref());
     }
 
-    pub fn commits_info(& self, substore : StoreKind) -> impl Table<Id = CommitId, Value = CommitInfo> {
-        return db::Store::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_INFO), true);
+    /** Returns the submodules of the commits in given substore like `commits_submodules`, or None if the substore has no submodules table, such as substores of datastores created before the submodules were recorded. 
+     */
+    pub fn commits_submodules_if_recorded(& self, substore : StoreKind) -> Option<table_readers::StoreReader<CommitSubmodules, CommitId>> {
+        let name = DatastoreView::substore_table_filename(substore, Substore::COMMITS_SUBMODULES);
+        if ! std::path::Path::new(& format!("{}/{}.store", self.root, name)).exists() {
+            return None;
+        }
+        return Some(self.commits_submodules(substore));
+    }
+
+    /** Returns the root trees of the commits in given substore. 
+     
+        Only commits analyzed with trees enabled have them. 
+     */
+    pub fn commits_trees(& self, substore : StoreKind) -> table_readers::StoreReader<TreeId, CommitId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_TREES), self.savepoint.as_ref());
+    }
+
+    /** Returns the structural metrics of the commits in given substore. 
+     
+        Only commits of projects whose metrics were calculated by the `commit-metrics` task have them. 
+     */
+    pub fn commit_metrics(& self, substore : StoreKind) -> table_readers::StoreReader<CommitMetrics, CommitId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMIT_METRICS), self.savepoint.as_ref());
+    }
+
+    pub fn trees(& self, substore : StoreKind) -> table_readers::MappingReader<SHA, TreeId> {
+        return table_readers::MappingReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::TREES), self.savepoint.as_ref());
+    }
+
+    /** Returns the entries of the trees in given substore. 
+     */
+    pub fn trees_info(& self, substore : StoreKind) -> table_readers::StoreReader<Tree, TreeId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::TREES_INFO), self.savepoint.as_ref());
+    }
+
+    /** Returns all files in given tree and its subtrees with their full paths. 
+     
+        This is the snapshot of the files at a commit when called with the root tree of the commit. Submodules are not included. 
+     */
+    pub fn tree_files(& self, substore : StoreKind, tree : TreeId) -> Vec<(String, HashId)> {
+        let trees_info = self.trees_info(substore);
+        let mut result = Vec::new();
+        let mut q = vec![(String::new(), tree)];
+        while let Some((prefix, id)) = q.pop() {
+            for entry in trees_info.get(id).unwrap_or_default() {
+                let path = if prefix.is_empty() { entry.name } else { format!("{}/{}", prefix, entry.name) };
+                match entry.kind {
+                    TreeEntryKind::File(hash) => result.push((path, hash)),
+                    TreeEntryKind::Tree(subtree) => q.push((path, subtree)),
+                    TreeEntryKind::Submodule(_) => {},
+                }
+            }
+        }
+        return result;
     }
 
     pub fn commits_metadata(& self, substore : StoreKind) -> impl Iterator<Item = (CommitId, Metadata)> {
-        return db::LinkedStore::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_METADATA), true).into_iter();
+        let commits = self.restricted_ids(substore, |x| & x.commits);
+        return table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_METADATA), self.savepoint.as_ref()).into_iter()
+            .filter(move |(id, _)| commits.as_ref().map(|x| x.contains(id)).unwrap_or(true));
     }
 
-    pub fn hashes(& self, substore : StoreKind) -> impl Table<Id = HashId, Value = SHA> {
-        return db::Mapping::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::HASHES), true);
+    pub fn hashes(& self, substore : StoreKind) -> table_readers::MappingReader<SHA, HashId> {
+        return table_readers::MappingReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::HASHES), self.savepoint.as_ref());
     }
 
-    pub fn contents(& self, substore : StoreKind) -> impl SplitTable<Id = HashId, Value = (ContentsKind, FileContents), Kind = ContentsKind, SplitIterator = db::SplitStorePart<FileContents, HashId>> {
-        return db::SplitStore::<FileContents, ContentsKind, HashId>::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::CONTENTS),true);
+    /** Returns the contents of the substore. 
+     
+        Delta encoded contents are reconstructed transparently, so that all contents appear as if they were stored in full. 
+     */
+    pub fn contents(& self, substore : StoreKind) -> table_readers::ContentsReader {
+        return table_readers::ContentsReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::CONTENTS), & DatastoreView::substore_table_filename(substore, Substore::CONTENTS_DELTAS), self.savepoint.as_ref());
     }
 
+    /** Iterates over the contents of the substore visible in the view. 
+     
+        Unrestricted views iterate over all contents, restricted views only get the contents changed by the commits of the restriction, ordered by their ids. 
+     */
+    pub fn iter_contents(& self, substore : StoreKind) -> Box<dyn Iterator<Item = (HashId, (ContentsKind, FileContents))>> {
+        let contents = self.contents(substore);
+        match self.restricted_ids(substore, |x| & x.hashes) {
+            Some(hashes) => {
+                let mut ids : Vec<HashId> = hashes.iter().map(|x| *x).collect();
+                ids.sort_by_key(|x| u64::from(*x));
+                return Box::new(ids.into_iter().filter_map(move |id| contents.get(id).map(|x| (id, x))));
+            },
+            None => return Box::new(contents.into_iter()),
+        }
+    }
+
+    /** Iterates over the commits of the substore visible in the view. 
+     
+        Unrestricted views iterate over all records of the commits info table, restricted views only get the latest records of the commits of the restriction, ordered by their ids. 
+     */
+    pub fn iter_commits_info(& self, substore : StoreKind) -> Box<dyn Iterator<Item = (CommitId, CommitInfo)>> {
+        return DatastoreView::restricted_iter(self.commits_info(substore), self.restricted_ids(substore, |x| & x.commits));
+    }
+
+    /** Returns the metadata of the stored contents. 
+     
+        Contents snapshotted by the updater have their size, number of lines, encoding and whether they are binary stored under the `Metadata::CONTENTS_*` keys, so that the contents can be filtered without reading them. 
+     */
     pub fn contents_metadata(& self, substore : StoreKind) -> impl Iterator<Item = (HashId, Metadata)> {
-        return db::LinkedStore::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::CONTENTS_METADATA), true).into_iter().into_iter();
+        let hashes = self.restricted_ids(substore, |x| & x.hashes);
+        return table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::CONTENTS_METADATA), self.savepoint.as_ref()).into_iter()
+            .filter(move |(id, _)| hashes.as_ref().map(|x| x.contains(id)).unwrap_or(true));
     }
 
-    pub fn paths(& self, substore : StoreKind) -> impl Table<Id = PathId, Value = SHA> {
-        return db::Mapping::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PATHS), true);
+    pub fn paths(& self, substore : StoreKind) -> table_readers::MappingReader<SHA, PathId> {
+        return table_readers::MappingReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PATHS), self.savepoint.as_ref());
     }
 
-    pub fn paths_strings(& self, substore : StoreKind) -> impl Table<Id = PathId, Value = PathString> {
-        return db::Store::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PATHS_STRINGS), true);
+    pub fn paths_strings(& self, substore : StoreKind) -> table_readers::StoreReader<PathString, PathId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PATHS_STRINGS), self.savepoint.as_ref());
+    }
+
+    /** Iterates over the path strings of the substore visible in the view. 
+     
+        Like `iter_commits_info`, restricted views only get the paths changed by the commits of the restriction. 
+     */
+    pub fn iter_paths_strings(& self, substore : StoreKind) -> Box<dyn Iterator<Item = (PathId, PathString)>> {
+        return DatastoreView::restricted_iter(self.paths_strings(substore), self.restricted_ids(substore, |x| & x.paths));
     }
 
-    pub fn users(& self, substore : StoreKind) -> impl Table<Id = UserId, Value = String> {
-        return db::IndirectMapping::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::USERS), true);
+    pub fn users(& self, substore : StoreKind) -> table_readers::IndirectMappingReader<String, UserId> {
+        return table_readers::indirect_mapping_reader(& self.root, & DatastoreView::substore_table_filename(substore, Substore::USERS), self.savepoint.as_ref());
     }
 
     pub fn users_metadata(& self, substore : StoreKind) -> impl Iterator<Item = (UserId, Metadata)> {
-        return db::LinkedStore::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::USERS_METADATA), true).into_iter();
+        return table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::USERS_METADATA), self.savepoint.as_ref()).into_iter();
+    }
+
+    /** Returns the canonical ids of users of the substore that are aliases of other users. 
+     
+        Users that are not in the returned map are canonical. The aliases are determined by the `resolve-users` command. 
+     */
+    pub fn user_aliases(& self, substore : StoreKind) -> HashMap<UserId, UserId> {
+        let mut result = HashMap::new();
+        for (id, canonical) in table_readers::StoreReader::<UserId, UserId>::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::USER_ALIASES), self.savepoint.as_ref()) {
+            if canonical == id {
+                result.remove(& id);
+            } else {
+                result.insert(id, canonical);
+            }
+        }
+        return result;
+    }
+
+    /** Issues and pull requests of projects in the given substore. 
+     
+        Each update of an issue is stored as a new record, so the same issue number may appear multiple times for a project, the last one being the most recent. 
+     */
+    pub fn project_issues(& self, substore : StoreKind) -> impl Iterator<Item = (ProjectId, Issue)> {
+        return self.restrict_projects(table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::ISSUES), self.savepoint.as_ref()).into_iter());
+    }
+
+    /** Commits of projects in the given substore ordered by their time, as fully indexed by earlier versions. 
+     
+        Every rebuild of the index is stored as a new record, the last one for each project being its index. The commits added since are in the project new commit times. 
+     */
+    pub fn project_commit_times(& self, substore : StoreKind) -> table_readers::StoreReader<ProjectCommitTimes, ProjectId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PROJECT_COMMIT_TIMES), self.savepoint.as_ref());
+    }
+
+    /** Commits added to projects in the given substore ordered by their time. 
+     
+        Every change of the project heads appends the commits not yet indexed as a new record, all records of a project together with its project commit times record being its index. 
+     */
+    pub fn project_new_commit_times(& self, substore : StoreKind) -> table_readers::LinkedStoreReader<ProjectCommitTimes, ProjectId> {
+        return table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PROJECT_NEW_COMMIT_TIMES), self.savepoint.as_ref());
+    }
+
+    /** Returns the commits by time index of given project from the given commit times tables, or None if the project has not been indexed. 
+     */
+    fn indexed_commit_times(commit_times : & table_readers::StoreReader<ProjectCommitTimes, ProjectId>, new_commit_times : & table_readers::LinkedStoreReader<ProjectCommitTimes, ProjectId>, id : ProjectId) -> Option<ProjectCommitTimes> {
+        let mut result = commit_times.get(id);
+        for times in new_commit_times.iter_id(id) {
+            result.get_or_insert_with(ProjectCommitTimes::new).extend(times);
+        }
+        if let Some(result) = result.as_mut() {
+            result.sort_by_key(|(id, _, committer_time)| (*committer_time, u64::from(*id)));
+            result.dedup_by_key(|(id, _, _)| *id);
+        }
+        return result;
+    }
+
+    /** Returns the commits of given project with their author and committer times, ordered by the committer time. 
+     
+        The commits are read from the commits by time index of the project's substore, to which the updater adds the new commits whenever the project heads change. Projects not updated since the index was introduced have no index and their commits are collected from their heads and sorted instead. Commits with the same committer time are ordered by their ids. 
+     */
+    pub fn project_commits_by_time(& self, id : ProjectId) -> ProjectCommitTimes {
+        let substore = match self.project_substore_of(id) {
+            Some(substore) => substore,
+            None => return ProjectCommitTimes::new(),
+        };
+        if let Some(times) = DatastoreView::indexed_commit_times(& self.project_commit_times(substore), & self.project_new_commit_times(substore), id) {
+            return times;
+        }
+        let heads = match self.project_heads_of(id) {
+            Some(heads) => heads,
+            None => return ProjectCommitTimes::new(),
+        };
+        let mut result : ProjectCommitTimes = ProjectCommitsIterator::new(& heads, self.commits_info(substore)).map(|(id, commit)| (id, commit.author_time, commit.committer_time)).collect();
+        result.sort_by_key(|(id, _, committer_time)| (*committer_time, u64::from(*id)));
+        return result;
+    }
+
+    /** Returns the commits of the substore that change any path starting with given prefix. 
+     
+        Uses the path commits index, so that only the path strings, not all commits of the substore, have to be scanned. An exact path therefore returns commits touching the path itself, a directory ending with `/` commits touching any file in it. Commits stored before the index was introduced are not found. The commits are returned ordered by their ids. 
+     */
+    pub fn commits_touching_path(& self, substore : StoreKind, prefix : & str) -> Vec<CommitId> {
+        let path_commits = table_readers::LinkedStoreReader::<CommitId, PathId>::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PATH_COMMITS), self.savepoint.as_ref());
+        let mut result = HashSet::<CommitId>::new();
+        for (path_id, path) in self.paths_strings(substore).iter() {
+            if path.starts_with(prefix) {
+                result.extend(path_commits.iter_id(path_id));
+            }
+        }
+        let mut result : Vec<CommitId> = result.into_iter().collect();
+        result.sort_by_key(|x| u64::from(*x));
+        return result;
+    }
+
+    /** Returns the projects that contain given commit of the substore. 
+     
+        Uses the commit projects index, so only projects indexed by the `index-commit-projects` task are found, at the heads they had when indexed. Projects that have since been moved to other substores are not returned. The projects are returned ordered by their ids. 
+     */
+    pub fn projects_with_commit(& self, substore : StoreKind, id : CommitId) -> Vec<ProjectId> {
+        return DatastoreView::indexed_projects_with_commit(& self.commit_projects(substore), & self.project_substores_reader(), substore, id);
+    }
+
+    fn commit_projects(& self, substore : StoreKind) -> table_readers::LinkedStoreReader<CommitProjects, CommitId> {
+        return table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMIT_PROJECTS), self.savepoint.as_ref());
+    }
+
+    pub (crate) fn project_substores_reader(& self) -> table_readers::StoreReader<StoreKind, ProjectId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_SUBSTORES), self.savepoint.as_ref());
+    }
+
+    pub (crate) fn project_updates_reader(& self) -> table_readers::LinkedStoreReader<ProjectLog, ProjectId> {
+        return table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_UPDATES), self.savepoint.as_ref());
+    }
+
+    pub (crate) fn project_tags_reader(& self) -> table_readers::StoreReader<ProjectTags, ProjectId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_TAGS), self.savepoint.as_ref());
+    }
+
+    pub (crate) fn project_labels_reader(& self) -> table_readers::StoreReader<ProjectLabels, ProjectId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_LABELS), self.savepoint.as_ref());
+    }
+
+    pub (crate) fn project_summaries_reader(& self) -> table_readers::LinkedStoreReader<ProjectSummary, ProjectId> {
+        return table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_SUMMARIES), self.savepoint.as_ref());
+    }
+
+    pub (crate) fn project_metadata_reader(& self) -> table_readers::LinkedStoreReader<Metadata, ProjectId> {
+        return table_readers::LinkedStoreReader::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_METADATA), self.savepoint.as_ref());
+    }
+
+    /** Returns the projects that contain given commit of the substore using already opened readers of the commit projects index and of the project substores. 
+     */
+    fn indexed_projects_with_commit(commit_projects : & table_readers::LinkedStoreReader<CommitProjects, CommitId>, project_substores : & table_readers::StoreReader<StoreKind, ProjectId>, substore : StoreKind, id : CommitId) -> Vec<ProjectId> {
+        let mut result : Vec<ProjectId> = commit_projects.iter_id(id).flatten()
+            .collect::<HashSet<ProjectId>>()
+            .into_iter()
+            .filter(|project| project_substores.get(*project) == Some(substore))
+            .collect();
+        result.sort_by_key(|x| u64::from(*x));
+        return result;
+    }
+
+    /** Returns the projects, paths and commits at which given file contents hash of the substore occurs. 
+     
+        Uses the hash occurrences index, so only hashes changed by commits indexed by the `index-files` task are found. The projects of the commits are taken from the commit projects index (see `projects_with_commit`), occurrences in commits without any indexed projects are not returned. The occurrences are returned ordered by their commits and paths. 
+     */
+    pub fn hash_occurrences(& self, substore : StoreKind, id : HashId) -> Vec<(ProjectId, PathId, CommitId)> {
+        let occurrences = table_readers::LinkedStoreReader::<HashOccurrences, HashId>::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::HASH_OCCURRENCES), self.savepoint.as_ref());
+        let mut occurrences : Vec<(CommitId, PathId)> = occurrences.iter_id(id).flatten().collect::<HashSet<(CommitId, PathId)>>().into_iter().collect();
+        occurrences.sort_by_key(|(commit, path)| (u64::from(*commit), u64::from(*path)));
+        let mut result = Vec::new();
+        let mut projects = HashMap::<CommitId, Vec<ProjectId>>::new();
+        let commit_projects = self.commit_projects(substore);
+        let project_substores = self.project_substores_reader();
+        for (commit, path) in occurrences {
+            let commit_projects = projects.entry(commit).or_insert_with(|| DatastoreView::indexed_projects_with_commit(& commit_projects, & project_substores, substore, commit));
+            for project in commit_projects.iter() {
+                result.push((*project, path, commit));
+            }
+        }
+        return result;
+    }
+
+    /** Returns the substores, projects, paths and commits at which the file contents of given hash occurs. 
+     
+        The substores are found using the global hash index (see `find_sha`), the hash ids in them using the sorted indices of their hashes mappings (see `MappingReader::find`) and the occurrences in each of them using `hash_occurrences`. 
+     */
+    pub fn find_file(& self, hash : & SHA) -> Vec<(StoreKind, ProjectId, PathId, CommitId)> {
+        let mut result = Vec::new();
+        for substore in self.find_sha(hash).1 {
+            if let Some(id) = self.hashes(substore).find(hash) {
+                result.extend(self.hash_occurrences(substore, id).into_iter().map(|(project, path, commit)| (substore, project, path, commit)));
+            }
+        }
+        return result;
+    }
+
+    /** Returns the files renamed by the commits of the substore. 
+     
+        Only commits that rename any files have a record. Following the renames of a path backwards in the history of a project allows file-history analyses to find the commits of the file before it was renamed. 
+     */
+    pub fn path_renames(& self, substore : StoreKind) -> table_readers::StoreReader<PathRenames, CommitId> {
+        return table_readers::StoreReader::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PATH_RENAMES), self.savepoint.as_ref());
+    }
+
+    /** Returns the history of the file at given path in given project as the commits that changed it and its contents hash after each of them. 
+     
+        The commits reachable from the heads of the project are walked from the heads to the oldest commits. Whenever a commit renamed the tracked path (see `path_renames`), its parents are searched for the old path instead, so that the history continues before the rename. Projects updated before the renames were recorded have no renames and only the commits of the given path itself are returned. Commits deleting the file have `HashId::DELETED` as their hash. Projects not assigned to a substore have empty history. The path is looked up via the sorted index of the paths mapping, if any. The history is ordered by the committer time of the commits, oldest first, commits with the same time by their ids. 
+     */
+    pub fn file_history(& self, pid : ProjectId, path : & str) -> Vec<(CommitId, HashId)> {
+        let (substore, heads) = match (self.project_substore_of(pid), self.project_heads_of(pid)) {
+            (Some(substore), Some(heads)) if substore != StoreKind::Unspecified => (substore, heads),
+            _ => return Vec::new(),
+        };
+        let hash = Datastore::hash_of(path.as_bytes());
+        let path_id = match self.paths(substore).find(& hash) {
+            Some(id) => id,
+            None => return Vec::new(),
+        };
+        let commits_info = self.commits_info(substore);
+        let path_renames = self.path_renames(substore);
+        let mut result = Vec::<(CommitId, HashId, i64)>::new();
+        // the same commit may be reached with different paths if the file has been renamed in one branch only
+        let mut visited = HashSet::<(CommitId, PathId)>::new();
+        let mut q : Vec<(CommitId, PathId)> = heads.values().map(|(id, _)| (*id, path_id)).filter(|(id, _)| *id != CommitId::NONE).collect();
+        while let Some((id, path_id)) = q.pop() {
+            if ! visited.insert((id, path_id)) {
+                continue;
+            }
+            let commit = match commits_info.get(id) {
+                Some(commit) => commit,
+                None => continue,
+            };
+            let mut parent_path_id = path_id;
+            if let Some(hash_id) = commit.changes.get(& path_id) {
+                result.push((id, *hash_id, commit.committer_time));
+                if let Some((old, _)) = path_renames.get(id).unwrap_or_default().into_iter().find(|(_, new)| *new == path_id) {
+                    parent_path_id = old;
+                }
+            }
+            q.extend(commit.parents.iter().map(|parent| (*parent, parent_path_id)));
+        }
+        result.sort_by_key(|(id, _, time)| (*time, u64::from(*id)));
+        result.dedup_by_key(|(id, _, _)| *id);
+        return result.into_iter().map(|(id, hash_id, _)| (id, hash_id)).collect();
+    }
+
+    /** Returns the commits of the substore whose messages contain all words of the query. 
+     
+        Uses the commit messages index, so only commits indexed by the `index-messages` task are found. The query is split into words the same way as the commit messages are (see `CommitInfo::message_words`). The commits are returned ordered by their ids. 
+     */
+    pub fn search_commits(& self, substore : StoreKind, query : & str) -> Vec<CommitId> {
+        let query_words = CommitInfo::message_words(query);
+        if query_words.is_empty() {
+            return Vec::new();
+        }
+        let words : Vec<WordId> = table_readers::indirect_mapping_reader::<String, WordId>(& self.root, & DatastoreView::substore_table_filename(substore, Substore::MESSAGE_WORDS), self.savepoint.as_ref())
+            .iter()
+            .filter(|(_, word)| query_words.contains(word))
+            .map(|(id, _)| id)
+            .collect();
+        // if any of the query words is not in the index, no commit can match
+        if words.len() != query_words.len() {
+            return Vec::new();
+        }
+        let postings = table_readers::LinkedStoreReader::<MessagePostings, WordId>::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::MESSAGE_POSTINGS), self.savepoint.as_ref());
+        let mut result : Option<HashSet<CommitId>> = None;
+        for word in words {
+            let commits : HashSet<CommitId> = postings.iter_id(word).flatten().collect();
+            result = Some(match result {
+                Some(x) => x.intersection(& commits).map(|x| *x).collect(),
+                None => commits,
+            });
+        }
+        let mut result : Vec<CommitId> = result.unwrap().into_iter().collect();
+        result.sort_by_key(|x| u64::from(*x));
+        return result;
+    }
+
+    /** Filters the records of a project table by the restriction of the view. 
+     */
+    fn restrict_projects<T>(& self, iter : impl Iterator<Item = (ProjectId, T)>) -> impl Iterator<Item = (ProjectId, T)> {
+        let restriction = self.restriction.clone();
+        return iter.filter(move |(id, _)| restriction.as_ref().map(|x| x.projects.contains(id)).unwrap_or(true));
+    }
+
+    /** Returns the ids of given kind of the substore visible in a restricted view, or None if the view is not restricted. 
+     
+        Substores without any projects of the restriction have no visible ids. 
+     */
+    fn restricted_ids<ID : Id>(& self, substore : StoreKind, ids : impl Fn(& SubstoreRestriction) -> & HashSet<ID>) -> Option<HashSet<ID>> {
+        let restriction = self.restriction.as_ref()?;
+        return Some(restriction.substores.get(& substore).map(|x| ids(x).clone()).unwrap_or_default());
+    }
+
+    /** Iterates over all records of the store, or only the latest records of given ids, ordered by the ids. 
+     
+        The ids are read in chunks using `get_many`, so that each chunk is a single sequential sweep over the store. 
+     */
+    fn restricted_iter<T : 'static + Serializable<Item = T>, ID : 'static + Id>(store : table_readers::StoreReader<T, ID>, ids : Option<HashSet<ID>>) -> Box<dyn Iterator<Item = (ID, T)>> {
+        match ids {
+            Some(ids) => {
+                let mut ids : Vec<ID> = ids.into_iter().collect();
+                ids.sort_by_key(|x| (*x).into());
+                let chunks : Vec<Vec<ID>> = ids.chunks(DatastoreView::RESTRICTED_CHUNK).map(|x| x.to_vec()).collect();
+                return Box::new(chunks.into_iter().flat_map(move |chunk| {
+                    let values = store.get_many(& chunk);
+                    return chunk.into_iter().zip(values).filter_map(|(id, value)| value.map(|x| (id, x))).collect::<Vec<(ID, T)>>();
+                }));
+            },
+            None => return Box::new(store.into_iter()),
+        }
     }
 
     fn table_filename(table : & str) -> String {
@@ -134,6 +893,30 @@ impl DatastoreView {
     }
 }
 
+/** Projects a restricted view is limited to, together with the commits, paths and contents hashes reachable from them in each substore. 
+ 
+    See `DatastoreView::restricted`. 
+ */
+pub struct Restriction {
+    pub projects : HashSet<ProjectId>,
+    pub substores : HashMap<StoreKind, SubstoreRestriction>,
+}
+
+/** Commits reachable from the projects of a restriction in a single substore and the paths and contents hashes they change. 
+ */
+#[derive(Default)]
+pub struct SubstoreRestriction {
+    pub commits : HashSet<CommitId>,
+    pub paths : HashSet<PathId>,
+    pub hashes : HashSet<HashId>,
+}
+
+impl SubstoreRestriction {
+    fn new() -> SubstoreRestriction {
+        return SubstoreRestriction::default();
+    }
+}
+
 pub struct ProjectCommitsIterator<T : Table<Id = CommitId, Value = CommitInfo>> {
     commits : T,
     visited : HashSet<CommitId>,
@@ -187,8 +970,8 @@ impl Project {
         return Project{
             url,
             substore,
-            latest_status : ProjectLog::Error{time : 0, version : datastore::Datastore::VERSION, error : "no_data".to_owned()},
-            latest_valid_status : ProjectLog::Error{time : 0, version : datastore::Datastore::VERSION, error : "no_data".to_owned()},
+            latest_status : ProjectLog::Error{time : 0, version : datastore::Datastore::VERSION, kind : UpdateError::Internal, error : "no_data".to_owned()},
+            latest_valid_status : ProjectLog::Error{time : 0, version : datastore::Datastore::VERSION, kind : UpdateError::Internal, error : "no_data".to_owned()},
             heads : ProjectHeads::new(),
         };
     }
@@ -196,7 +979,7 @@ impl Project {
     pub fn is_valid(& self) -> bool {
         match self.latest_status {
             ProjectLog::NoChange{time : _, version : _} => return true,
-            ProjectLog::Ok{time : _, version : _} => return true,
+            ProjectLog::Ok{time : _, version : _, changes : _} => return true,
             _ => return false,
         }
 
@@ -205,7 +988,7 @@ impl Project {
     pub fn latest_valid_update_time(& self) -> Option<i64> {
         match self.latest_valid_status {
             ProjectLog::NoChange{time, version : _} => return Some(time),
-            ProjectLog::Ok{time, version : _} => return Some(time),
+            ProjectLog::Ok{time, version : _, changes : _} => return Some(time),
             _ => return None,
         }
     }
@@ -215,6 +998,9 @@ impl Project {
         // we have to start with urls as these are the only ones guaranteed to exist
         LOG!("Loading latest project urls...");
         for (id, url) in ds.project_urls() {
+            if ! ds.includes_project(id) {
+                continue;
+            }
             projects.insert(id, Project::new(url, StoreKind::Unspecified));
         }
         LOG!("    {} projects found", projects.len());
@@ -238,6 +1024,165 @@ impl Project {
     }
 }
 
+/** Manifest of an export from the datastore. 
+ 
+    The manifest records which data an export used, i.e. the id and version of the datastore, the savepoint the export was pinned to, the criteria by which the exported items were selected and the columns of the exported files, together with the version of the exporting tool. It is written as json next to the exported file, so that papers can state exactly what data they are based on and `verify` can check later that a datastore still contains the exact same data. 
+
+    Only exports pinned to a savepoint can be verified, since the datastore only grows in between savepoints and the savepoint identifies the state of all its tables. 
+ */
+pub struct Manifest {
+    pub command : String,
+    pub tool_version : String,
+    pub time : i64,
+    pub datastore : String,
+    pub datastore_id : Option<String>,
+    pub datastore_version : u16,
+    /** True if the user emails of the datastore are anonymized. 
+     */
+    pub anonymized : bool,
+    /** Name, time and fingerprint of the savepoint the export was pinned to. 
+     */
+    pub savepoint : Option<(String, i64, String)>,
+    /** Criteria by which the exported items were selected, as given to the exporting command. 
+     */
+    pub filters : json::JsonValue,
+    /** Names and types of the exported columns, in order. 
+     */
+    pub columns : Vec<(String, String)>,
+}
+
+impl Manifest {
+
+    /** Creates manifest of an export by given command from the datastore view. 
+     */
+    pub fn new(command : & str, view : & DatastoreView) -> Manifest {
+        return Manifest{
+            command : command.to_owned(),
+            tool_version : env!("CARGO_PKG_VERSION").to_owned(),
+            time : helpers::now(),
+            datastore : view.root.clone(),
+            datastore_id : view.id(),
+            datastore_version : Datastore::stored_version(& view.root),
+            anonymized : view.anonymized(),
+            savepoint : view.savepoint().map(|sp| (sp.name().to_owned(), sp.time(), Manifest::fingerprint(sp))),
+            filters : json::JsonValue::new_object(),
+            columns : Vec::new(),
+        };
+    }
+
+    /** Records a selection criterion of the export. 
+     */
+    pub fn filter<T : Into<json::JsonValue>>(& mut self, name : & str, value : T) -> & mut Manifest {
+        self.filters[name] = value.into();
+        return self;
+    }
+
+    /** Adds exported column of given name and type. 
+     */
+    pub fn column(& mut self, name : & str, kind : & str) -> & mut Manifest {
+        self.columns.push((name.to_owned(), kind.to_owned()));
+        return self;
+    }
+
+    /** Returns the fingerprint of the savepoint, the hash of the sizes of all tables it records. 
+     */
+    pub fn fingerprint(savepoint : & db::Savepoint) -> String {
+        let mut files : Vec<(& String, & u64)> = savepoint.files().collect();
+        files.sort();
+        let text : String = files.iter().map(|(name, size)| format!("{}:{}\n", name, size)).collect();
+        return Datastore::hash_of(text.as_bytes()).to_string();
+    }
+
+    pub fn to_json(& self) -> json::JsonValue {
+        return json::object!{
+            "command" => self.command.as_str(),
+            "tool_version" => self.tool_version.as_str(),
+            "time" => self.time,
+            "datastore" => self.datastore.as_str(),
+            "datastore_id" => self.datastore_id.clone(),
+            "datastore_version" => self.datastore_version,
+            "anonymized" => self.anonymized,
+            "savepoint" => match & self.savepoint {
+                Some((name, time, fingerprint)) => json::object!{ "name" => name.as_str(), "time" => *time, "fingerprint" => fingerprint.as_str() },
+                None => json::JsonValue::Null,
+            },
+            "filters" => self.filters.clone(),
+            "columns" => self.columns.iter().map(|(name, kind)| json::object!{ "name" => name.as_str(), "type" => kind.as_str() }).collect::<Vec<json::JsonValue>>(),
+        };
+    }
+
+    pub fn from_json(value : & json::JsonValue) -> Result<Manifest, std::io::Error> {
+        let invalid = |what : & str| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid manifest, {} missing", what));
+        let savepoint = & value["savepoint"];
+        return Ok(Manifest{
+            command : value["command"].as_str().ok_or_else(|| invalid("command"))?.to_owned(),
+            tool_version : value["tool_version"].as_str().ok_or_else(|| invalid("tool version"))?.to_owned(),
+            time : value["time"].as_i64().ok_or_else(|| invalid("time"))?,
+            datastore : value["datastore"].as_str().ok_or_else(|| invalid("datastore"))?.to_owned(),
+            datastore_id : value["datastore_id"].as_str().map(|x| x.to_owned()),
+            datastore_version : value["datastore_version"].as_u16().ok_or_else(|| invalid("datastore version"))?,
+            anonymized : value["anonymized"].as_bool().unwrap_or(false),
+            savepoint : if savepoint.is_null() { None } else {
+                Some((
+                    savepoint["name"].as_str().ok_or_else(|| invalid("savepoint name"))?.to_owned(),
+                    savepoint["time"].as_i64().ok_or_else(|| invalid("savepoint time"))?,
+                    savepoint["fingerprint"].as_str().ok_or_else(|| invalid("savepoint fingerprint"))?.to_owned(),
+                ))
+            },
+            filters : value["filters"].clone(),
+            columns : value["columns"].members().map(|x| (x["name"].as_str().unwrap_or("").to_owned(), x["type"].as_str().unwrap_or("").to_owned())).collect(),
+        });
+    }
+
+    /** Writes the manifest as json into given file. 
+     */
+    pub fn write(& self, filename : & str) -> Result<(), std::io::Error> {
+        return std::fs::write(filename, self.to_json().pretty(4));
+    }
+
+    pub fn read(filename : & str) -> Result<Manifest, std::io::Error> {
+        let value = json::parse(& std::fs::read_to_string(filename)?).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid manifest: {}", e)))?;
+        return Manifest::from_json(& value);
+    }
+
+    /** Checks that the datastore of given view still matches the manifest. 
+     
+        Returns the list of mismatches, empty if the datastore contains the exact data the export was created from. 
+     */
+    pub fn verify(& self, view : & DatastoreView) -> Vec<String> {
+        let mut result = Vec::new();
+        match (& self.datastore_id, view.id()) {
+            (Some(expected), Some(actual)) => if *expected != actual {
+                result.push(format!("datastore id is {}, but {} expected", actual, expected));
+            },
+            (Some(expected), None) => result.push(format!("datastore has no id, but {} expected", expected)),
+            _ => {},
+        }
+        let version = Datastore::stored_version(& view.root);
+        if version != self.datastore_version {
+            result.push(format!("datastore version is {}, but {} expected", version, self.datastore_version));
+        }
+        if view.anonymized() != self.anonymized {
+            result.push(format!("datastore emails are {}anonymized", if self.anonymized { "not " } else { "" }));
+        }
+        match & self.savepoint {
+            Some((name, time, fingerprint)) => match view.savepoints().find(|sp| sp.name() == name) {
+                Some(sp) => {
+                    if sp.time() != *time {
+                        result.push(format!("savepoint {} created at {}, but {} expected", name, sp.time(), time));
+                    }
+                    if Manifest::fingerprint(& sp) != *fingerprint {
+                        result.push(format!("savepoint {} has different table sizes", name));
+                    }
+                },
+                None => result.push(format!("savepoint {} not found", name)),
+            },
+            None => result.push("export was not pinned to a savepoint, its data cannot be verified".to_owned()),
+        }
+        return result;
+    }
+}
+
 /** A class that facilitates merging one datastore into another. 
  
     TODO datastoreviews into the merged datastore will be invalidated by the merge. Do we care? 
@@ -267,11 +1212,23 @@ impl DatastoreMerger {
     }
 
     
+    /** Merges the entire source datastore into the target datastore. 
+     
+        Every source substore is merged into the same substore in the target and afterwards projects that have not been assigned to any substore yet are added too so that they can be updated by the target datastore's updater. Ids of all merged items are remapped to the target ids and items already present in the target (projects by their urls, commits, hashes and paths by their SHA and users by their emails) are not duplicated. All merged projects record their provenance in their update log. 
+     */
+    pub fn merge_datastore(& mut self) {
+        for substore in StoreKind::all() {
+            self.merge_substore(substore, substore, ValidateAll::new());
+        }
+        self.merge_unassigned_projects();
+    }
+
     /** Merges single substore from source datastore into selected substore in the target substore. 
      
         This may be the same substore, or multiple source substores can be joined in a single target substore by repeatedly calling the method for different source substores. 
     */
     pub fn merge_substore<T : MergeValidator>(& mut self, target_substore : StoreKind, source_substore : StoreKind, validator : T) {
+        self.check_anonymization();
         println!("Merging substore {:?} into {:?}", source_substore, target_substore);
         let mut context = MergeContext::new(& self.target, target_substore, source_substore, validator);
         self.merge_users(& mut context);
@@ -279,17 +1236,35 @@ impl DatastoreMerger {
         self.merge_hashes(& mut context);
         self.merge_contents(& mut context);
         self.merge_commits(& mut context);
+        self.merge_trees(& mut context);
         self.merge_projects(& mut context);
         println!("merging done.");
     }
 
+    /** Panics if the users of the source cannot be merged into the target because of their anonymization. 
+     
+        Anonymized emails can only be merged into a datastore anonymized with the same key, as they would otherwise never match the emails of the same users in the target, or be mixed with raw emails. Raw emails are anonymized when merged into an anonymized datastore. 
+     */
+    fn check_anonymization(& self) {
+        if let Some(source) = Datastore::email_key_fingerprint(& self.source.root) {
+            match Datastore::email_key_fingerprint(& self.target.root) {
+                Some(target) if target == source => {},
+                Some(_) => panic!("Cannot merge, the emails of the source and target datastores are anonymized with different keys"),
+                None => panic!("Cannot merge, the emails of the source datastore are anonymized, but those of the target datastore are not"),
+            }
+        }
+    }
+
     fn merge_users<T : MergeValidator>(& mut self, context : & mut MergeContext<T>) {
         println!("merging users...");
         let target_substore = context.target.substore(context.target_substore); 
         let mut users = target_substore.users.lock().unwrap();
         users.load();
+        // emails merged into an anonymized datastore must be anonymized too, unless they already are
+        let anonymize = ! self.source.anonymized();
         for (source_id, email) in self.source.users(context.source_substore) {
             if context.validator.valid_user(source_id) {
+                let email = if anonymize { target_substore.user_email(& email) } else { email };
                 let x = users.get_or_create_mapping(& email);
                 context.users.insert(source_id, x);
                 match x.1 {
@@ -317,11 +1292,16 @@ impl DatastoreMerger {
     fn merge_paths<T : MergeValidator>(& mut self, context : & mut MergeContext<T>) {
         println!("merging paths...");
         let target_substore = context.target.substore(context.target_substore); 
+        // submodule paths are not among the changes of the commits, so validators may skip them, but the submodules of the merged commits need them
+        let submodule_paths : HashSet<PathId> = self.source.commits_submodules(context.source_substore).into_iter()
+            .filter(|(id, _)| context.validator.valid_commit(*id))
+            .flat_map(|(_, submodules)| submodules.into_iter().map(|x| x.path))
+            .collect();
         let mut paths = target_substore.paths.lock().unwrap();
         paths.load();
         for (source_id, hash) in self.source.paths(context.source_substore) {
-            if context.validator.valid_path(source_id) {
-                let x = paths.get_or_create_mapping(& hash);
+            if context.validator.valid_path(source_id) || submodule_paths.contains(& source_id) {
+                let x = paths.get_or_create_mapping(& hash).unwrap();
                 context.paths.insert(source_id, x);
                 match x.1 {
                     true => context.paths_count.new += 1,
@@ -352,7 +1332,10 @@ impl DatastoreMerger {
         hashes.load();
         for (source_id, hash) in self.source.hashes(context.source_substore) {
             if context.validator.valid_hash(source_id) {
-                let x = hashes.get_or_create_mapping(& hash);
+                let x = hashes.get_or_create_mapping(& hash).unwrap();
+                if x.1 {
+                    target_substore.index_sha(& hash, target_substore.contents_bit()).unwrap();
+                }
                 context.hashes.insert(source_id, x);
                 match x.1 {
                     true => context.hashes_count.new += 1,
@@ -415,6 +1398,29 @@ impl DatastoreMerger {
             }
         }
 
+        // delta encoded contents are reconstructed and stored in full as their bases do not have to be merged 
+        println!("    deltas");
+        let source_contents = self.source.contents(context.source_substore);
+        let deltas = table_readers::StoreReader::<ContentsDelta, HashId>::new(& self.source.root, & DatastoreView::substore_table_filename(context.source_substore, Substore::CONTENTS_DELTAS), None);
+        for (source_id, _) in deltas {
+            context.contents_count.total += 1;
+            let target_id = match context.hashes.get(& source_id) {
+                Some((target_id, true)) => Some(*target_id),
+                Some((target_id, false)) if ! contents.has(*target_id) => Some(*target_id),
+                _ => None,
+            };
+            match (target_id, source_contents.get(source_id)) {
+                (Some(target_id), Some((contents_kind, raw_contents))) => {
+                    contents.set(target_id, contents_kind, & raw_contents);
+                    added_contents.insert(source_id, target_id);
+                    context.contents_count.new += 1;
+                },
+                _ => {
+                    context.contents_count.existing += 1;
+                }
+            }
+        }
+
         /*
         for (source_id, (contents_kind, raw_contents)) in self.source.contents(context.source_substore) {
             context.contents_count.total += 1;
@@ -464,7 +1470,10 @@ impl DatastoreMerger {
         commits.load();
         for (source_id, hash) in self.source.commits(context.source_substore) {
             if context.validator.valid_commit(source_id) {
-                let x = commits.get_or_create_mapping(& hash);
+                let x = commits.get_or_create_mapping(& hash).unwrap();
+                if x.1 {
+                    target_substore.index_sha(& hash, target_substore.commit_bit()).unwrap();
+                }
                 context.commits.insert(source_id,x);
                 match x.1 {
                     true => context.commits_count.new += 1,
@@ -481,6 +1490,7 @@ impl DatastoreMerger {
         // commits info for the new commits, where we need to update the ids where necessary
         println!("merging commits info...");
         let mut commits_info = target_substore.commits_info.lock().unwrap();
+        let mut path_commits = target_substore.path_commits.lock().unwrap();
         for (source_id, mut cinfo) in self.source.commits_info(context.source_substore) {
             // only add the information *if* there was a new mapping 
             if let Some((target_id, true)) = context.commits.get(& source_id) {
@@ -489,6 +1499,18 @@ impl DatastoreMerger {
                 cinfo.parents = cinfo.parents.iter().map(|x| context.translate_commit(*x)).collect();
                 cinfo.changes = cinfo.changes.iter().map(|x| context.translate_change((*x.0, *x.1))).collect();
                 commits_info.set(*target_id, & cinfo);
+                for path_id in cinfo.changes.keys() {
+                    path_commits.set(*path_id, target_id);
+                }
+            }
+        }
+        // merge path renames of the new commits
+        println!("merging path renames...");
+        let mut path_renames = target_substore.path_renames.lock().unwrap();
+        for (source_id, renames) in self.source.path_renames(context.source_substore) {
+            if let Some((target_id, true)) = context.commits.get(& source_id) {
+                let renames : PathRenames = renames.into_iter().map(|(old, new)| (context.translate_path(old), context.translate_path(new))).collect();
+                path_renames.set(*target_id, & renames);
             }
         }
         // merge commits metadata
@@ -500,6 +1522,73 @@ impl DatastoreMerger {
                 commits_metadata.set(*target_id, & mtd);
             }
         }
+        // merge the line stats of the new commits
+        println!("merging commits line stats...");
+        let mut commits_line_stats = target_substore.commits_line_stats.lock().unwrap();
+        for (source_id, stats) in self.source.commits_line_stats(context.source_substore) {
+            if let Some((target_id, true)) = context.commits.get(& source_id) {
+                let stats : LineStats = stats.into_iter().map(|(path_id, x)| (context.translate_path(path_id), x)).collect();
+                commits_line_stats.set(*target_id, & stats);
+            }
+        }
+        // merge the submodules of the new commits, their paths were merged with the paths of the changes
+        println!("merging commits submodules...");
+        let mut commits_submodules = target_substore.commits_submodules.lock().unwrap();
+        for (source_id, submodules) in self.source.commits_submodules(context.source_substore) {
+            if let Some((target_id, true)) = context.commits.get(& source_id) {
+                let submodules : CommitSubmodules = submodules.into_iter()
+                    .filter(|x| context.paths.contains_key(& x.path))
+                    .map(|x| Submodule{ path : context.translate_path(x.path), ..x })
+                    .collect();
+                commits_submodules.set(*target_id, & submodules);
+            }
+        }
+    }
+
+    /** Merges trees and the root trees of the new commits. 
+     
+        All trees of the source substore are merged, whether used by selected commits or not. Files whose hashes were not selected by the validator are dropped from the merged trees. 
+     */
+    fn merge_trees<T : MergeValidator>(& mut self, context : & mut MergeContext<T>) {
+        println!("merging trees...");
+        let target_substore = context.target.substore(context.target_substore); 
+        let mut trees = target_substore.trees.lock().unwrap();
+        trees.load();
+        for (source_id, hash) in self.source.trees(context.source_substore) {
+            let x = trees.get_or_create_mapping(& hash).unwrap();
+            context.trees.insert(source_id, x);
+            match x.1 {
+                true => context.trees_count.new += 1,
+                false => context.trees_count.existing += 1,
+            }
+            context.trees_count.total += 1;
+        }
+        trees.clear();
+        println!("    total:    {}", context.trees_count.total);
+        println!("    existing: {}", context.trees_count.existing);
+        println!("    new:      {}", context.trees_count.new);
+        println!("merging trees info...");
+        let mut trees_info = target_substore.trees_info.lock().unwrap();
+        for (source_id, tree) in self.source.trees_info(context.source_substore) {
+            if let Some((target_id, true)) = context.trees.get(& source_id) {
+                let tree : Tree = tree.into_iter().filter_map(|entry| {
+                    let kind = match entry.kind {
+                        TreeEntryKind::File(hash) => TreeEntryKind::File(context.hashes.get(& hash)?.0),
+                        TreeEntryKind::Tree(subtree) => TreeEntryKind::Tree(context.translate_tree(subtree)),
+                        submodule => submodule,
+                    };
+                    return Some(TreeEntry{ kind, ..entry });
+                }).collect();
+                trees_info.set(*target_id, & tree);
+            }
+        }
+        println!("merging commits trees...");
+        let mut commits_trees = target_substore.commits_trees.lock().unwrap();
+        for (source_id, tree) in self.source.commits_trees(context.source_substore) {
+            if let Some((target_id, true)) = context.commits.get(& source_id) {
+                commits_trees.set(*target_id, & context.translate_tree(tree));
+            }
+        }
     }
 
     /** Merges projects from the source dataset to. 
@@ -556,7 +1645,9 @@ impl DatastoreMerger {
                     if let Some(target_id) = projects.get(& project_id) {
                         context.target.update_project(* target_id, & url);
                     } else {
-                        projects.insert(project_id, context.target.add_project(&url).unwrap());
+                        let target_id = context.target.add_project(&url).unwrap();
+                        context.target.update_project_update_status(target_id, self.merged_log(project_id));
+                        projects.insert(project_id, target_id);
                     }
                 }
             }
@@ -622,6 +1713,94 @@ impl DatastoreMerger {
                 target_heads.set(projects[&source_id], & translated_heads);
             }
         }
+        println!("merging project remote heads...");
+        // remote heads have no commit ids to translate, only the latest are merged
+        {
+            let mut latest_heads = HashMap::<ProjectId, ProjectHeads>::new();
+            for (source_id, heads) in self.source.project_remote_heads() {
+                if projects.contains_key(& source_id) {
+                    latest_heads.insert(source_id, heads);
+                }
+            }
+            let mut target_heads = context.target.project_remote_heads.lock().unwrap();
+            for (source_id, heads) in latest_heads {
+                target_heads.set(projects[&source_id], & heads);
+            }
+        }
+        println!("merging project tags...");
+        // project tags - only take latest change, same as heads
+        {
+            let mut latest_tags = HashMap::<ProjectId, ProjectTags>::new();
+            for (source_id, tags) in self.source.project_tags() {
+                if projects.contains_key(& source_id) {
+                    latest_tags.insert(source_id, tags);
+                }
+            }
+            let mut target_tags = context.target.project_tags.lock().unwrap();
+            for (source_id, tags) in latest_tags {
+                let translated_tags : ProjectTags = tags.iter().map(|(name, (commit_id, sha))|{
+                    return (name.clone(),  (context.translate_commit(*commit_id), *sha));
+                }).collect();
+                target_tags.set(projects[&source_id], & translated_tags);
+            }
+        }
+        println!("merging project labels...");
+        // project labels - only take latest change as well
+        {
+            let mut latest_labels = HashMap::<ProjectId, ProjectLabels>::new();
+            for (source_id, labels) in self.source.project_labels() {
+                if projects.contains_key(& source_id) {
+                    latest_labels.insert(source_id, labels);
+                }
+            }
+            let mut target_labels = context.target.project_labels.lock().unwrap();
+            for (source_id, labels) in latest_labels {
+                target_labels.set(projects[&source_id], & labels);
+            }
+        }
+        println!("merging project summaries...");
+        // project summaries - only take latest change, with the hash ids of their files translated
+        {
+            let mut latest_summaries = HashMap::<ProjectId, ProjectSummary>::new();
+            for (source_id, summary) in self.source.project_summaries() {
+                if projects.contains_key(& source_id) {
+                    latest_summaries.insert(source_id, summary);
+                }
+            }
+            let mut target_summaries = context.target.project_summaries.lock().unwrap();
+            for (source_id, mut summary) in latest_summaries {
+                summary.files = summary.files.map(|files| ProjectFiles{
+                    readme : files.readme.map(|(path, hash_id)| (path, context.translate_hash(hash_id))),
+                    license : files.license.map(|(path, hash_id)| (path, context.translate_hash(hash_id))),
+                    license_id : files.license_id,
+                });
+                target_summaries.set(projects[&source_id], & summary);
+            }
+        }
+        println!("merging project default branches...");
+        // only the latest default branch of each project is merged
+        {
+            let mut latest_branches = HashMap::<ProjectId, DefaultBranch>::new();
+            for (source_id, branch) in self.source.project_default_branches() {
+                if projects.contains_key(& source_id) {
+                    latest_branches.insert(source_id, branch);
+                }
+            }
+            let mut target_branches = context.target.project_default_branches.lock().unwrap();
+            for (source_id, branch) in latest_branches {
+                target_branches.set(projects[&source_id], & branch);
+            }
+        }
+        println!("merging project popularity...");
+        // the popularity records contain no ids either, all of them are merged to keep the time series
+        {
+            let mut target_popularity = context.target.project_popularity.lock().unwrap();
+            for (source_id, popularity) in self.source.project_popularity() {
+                if let Some(target_id) = projects.get(& source_id) {
+                    target_popularity.set(*target_id, & popularity);
+                }
+            }
+        }
         println!("merging project metadata...");
         // and finally, merge metadata, since we do not know what is in metadata, we'll merge them all
         {
@@ -633,6 +1812,110 @@ impl DatastoreMerger {
                 }
             }
         }
+        println!("merging project issues...");
+        // issues are stored in the substore, but keyed by project ids 
+        {
+            let mut target_issues = context.target.substore(context.target_substore).issues.lock().unwrap();
+            for (source_id, issue) in self.source.project_issues(context.source_substore) {
+                if let Some(target_id) = projects.get(& source_id) {
+                    target_issues.set(*target_id, & issue);
+                }
+            }
+        }
+        println!("merging project commit times...");
+        // the whole index of each project is merged as a single record, reordered as the translated commit ids may break ties differently
+        {
+            let commit_times = self.source.project_commit_times(context.source_substore);
+            let new_commit_times = self.source.project_new_commit_times(context.source_substore);
+            let mut target_times = context.target.substore(context.target_substore).project_new_commit_times.lock().unwrap();
+            for (source_id, target_id) in projects.iter() {
+                if let Some(times) = DatastoreView::indexed_commit_times(& commit_times, & new_commit_times, *source_id) {
+                    let mut translated_times : ProjectCommitTimes = times.iter().map(|(commit_id, author_time, committer_time)| {
+                        return (context.translate_commit(*commit_id), *author_time, *committer_time);
+                    }).collect();
+                    translated_times.sort_by_key(|(id, _, committer_time)| (*committer_time, u64::from(*id)));
+                    target_times.set(*target_id, & translated_times);
+                }
+            }
+        }
+    }
+
+    /** Merges projects from the source datastore that have not been assigned to any substore. 
+     
+        Such projects have no data other than their urls and possibly metadata, so only new urls are added together with their metadata. 
+     */
+    fn merge_unassigned_projects(& mut self) {
+        println!("merging unassigned projects...");
+        let target = Datastore::new(self.target.root.as_str(), false);
+        target.load_all_project_urls();
+        let mut assigned = HashSet::<ProjectId>::new();
+        for (source_id, substore) in self.source.project_substores() {
+            if substore != StoreKind::Unspecified {
+                assigned.insert(source_id);
+            }
+        }
+        let mut projects = HashMap::<ProjectId, ProjectId>::new();
+        for (source_id, url) in self.source.project_urls() {
+            if assigned.contains(& source_id) || projects.contains_key(& source_id) {
+                continue;
+            }
+            if let Some(target_id) = target.add_project(& url) {
+                target.update_project_update_status(target_id, self.merged_log(source_id));
+                projects.insert(source_id, target_id);
+            }
+        }
+        {
+            let mut projects_metadata = target.project_metadata.lock().unwrap();
+            for (source_id, mtd) in self.source.project_metadata() {
+                if let Some(target_id) = projects.get(& source_id) {
+                    projects_metadata.set(*target_id, & mtd);
+                }
+            }
+        }
+        {
+            let mut target_popularity = target.project_popularity.lock().unwrap();
+            for (source_id, popularity) in self.source.project_popularity() {
+                if let Some(target_id) = projects.get(& source_id) {
+                    target_popularity.set(*target_id, & popularity);
+                }
+            }
+        }
+        {
+            let mut latest_branches = HashMap::<ProjectId, DefaultBranch>::new();
+            for (source_id, branch) in self.source.project_default_branches() {
+                if projects.contains_key(& source_id) {
+                    latest_branches.insert(source_id, branch);
+                }
+            }
+            let mut target_branches = target.project_default_branches.lock().unwrap();
+            for (source_id, branch) in latest_branches {
+                target_branches.set(projects[&source_id], & branch);
+            }
+        }
+        {
+            let mut latest_heads = HashMap::<ProjectId, ProjectHeads>::new();
+            for (source_id, heads) in self.source.project_remote_heads() {
+                if projects.contains_key(& source_id) {
+                    latest_heads.insert(source_id, heads);
+                }
+            }
+            let mut target_heads = target.project_remote_heads.lock().unwrap();
+            for (source_id, heads) in latest_heads {
+                target_heads.set(projects[&source_id], & heads);
+            }
+        }
+        println!("    new:      {}", projects.len());
+    }
+
+    /** Returns the update log record that marks the project as merged from the source datastore. 
+     */
+    fn merged_log(& self, source_id : ProjectId) -> ProjectLog {
+        return ProjectLog::Merged{
+            time : helpers::now(),
+            version : Datastore::VERSION,
+            source : self.source.root.clone(),
+            old_id : source_id.into(),
+        };
     }
 
 }
@@ -668,11 +1951,13 @@ struct MergeContext<T : MergeValidator> {
     paths : HashMap<PathId, (PathId, bool)>,
     hashes : HashMap<HashId, (HashId, bool)>,
     commits : HashMap<CommitId, (CommitId, bool)>,
+    trees : HashMap<TreeId, (TreeId, bool)>,
     users_count : MergeCount,
     paths_count : MergeCount,
     hashes_count : MergeCount,
     contents_count : MergeCount,
     commits_count : MergeCount,
+    trees_count : MergeCount,
     projects_count : MergeCount,
 }
 
@@ -687,11 +1972,13 @@ impl<T : MergeValidator> MergeContext<T> {
             paths : HashMap::new(),
             hashes : HashMap::new(),
             commits : HashMap::new(),
+            trees : HashMap::new(),
             users_count : MergeCount::new(),
             paths_count : MergeCount::new(),
             hashes_count : MergeCount::new(),
             contents_count : MergeCount::new(),
             commits_count : MergeCount::new(),
+            trees_count : MergeCount::new(),
             projects_count : MergeCount::new(),
         };
     }
@@ -714,6 +2001,15 @@ impl<T : MergeValidator> MergeContext<T> {
         }
     }
 
+    fn translate_tree(& self, src_id : TreeId) -> TreeId {
+        if let Some((target_id, _)) = self.trees.get(& src_id) {
+            return *target_id;
+        } else {
+            println!("Required tree id {} not selected. Target will not be consistent", src_id);
+            return TreeId::NONE;
+        }
+    }
+
     fn translate_path(& self, src_id : PathId) -> PathId {
         if let Some((target_id, _)) = self.paths.get(& src_id) {
             return *target_id;
@@ -0,0 +1,68 @@
+/** Known licenses with the phrases that identify them.
+
+    The phrases are normalized as the texts they are matched against, i.e. lowercase words separated by single spaces. A license matches if its text contains all of its phrases as whole words. The licenses are checked in order, so licenses whose texts contain the phrases of other licenses (such as the LGPL, whose text mentions the GPL) must precede them.
+ */
+const LICENSES : [(& str, & [& str]); 19] = [
+    ("AGPL-3.0", & ["gnu affero general public license", "version 3"]),
+    ("LGPL-3.0", & ["gnu lesser general public license", "version 3"]),
+    ("LGPL-2.1", & ["gnu lesser general public license", "version 2 1"]),
+    ("LGPL-2.0", & ["gnu library general public license", "version 2"]),
+    ("GPL-3.0", & ["gnu general public license", "version 3"]),
+    ("GPL-2.0", & ["gnu general public license", "version 2"]),
+    ("Apache-2.0", & ["apache license", "version 2 0"]),
+    ("MPL-2.0", & ["mozilla public license", "2 0"]),
+    ("EPL-2.0", & ["eclipse public license", "2 0"]),
+    ("EPL-1.0", & ["eclipse public license", "1 0"]),
+    ("BSD-3-Clause", & ["redistribution and use in source and binary forms", "neither the name of"]),
+    ("BSD-2-Clause", & ["redistribution and use in source and binary forms"]),
+    ("MIT", & ["permission is hereby granted free of charge to any person obtaining a copy"]),
+    ("ISC", & ["permission to use copy modify and or distribute this software for any purpose with or without fee is hereby granted"]),
+    ("Unlicense", & ["this is free and unencumbered software released into the public domain"]),
+    ("CC0-1.0", & ["creative commons", "cc0 1 0"]),
+    ("BSL-1.0", & ["boost software license", "version 1 0"]),
+    ("Zlib", & ["the origin of this software must not be misrepresented"]),
+    ("WTFPL", & ["do what the fuck you want to public license"]),
+];
+
+/** Marker of the license identifier lines as recommended by SPDX.
+ */
+const SPDX_IDENTIFIER : & str = "SPDX-License-Identifier:";
+
+/** Determines the SPDX identifier of the license in given text, typically the contents of a LICENSE or README file.
+
+    An explicit `SPDX-License-Identifier:` line takes precedence and its license expression is returned verbatim. Otherwise the normalized text is matched against the phrases of the known licenses. Returns None if the license is not recognized.
+ */
+pub (crate) fn classify_license(contents : & [u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(contents);
+    for line in text.lines() {
+        if let Some(start) = line.find(SPDX_IDENTIFIER) {
+            // the identifier may be in a comment, whose end is not part of the expression
+            let expression = line[start + SPDX_IDENTIFIER.len()..].trim().trim_end_matches("*/").trim_end_matches("-->").trim();
+            if ! expression.is_empty() {
+                return Some(expression.to_owned());
+            }
+        }
+    }
+    let normalized = normalize(& text);
+    return LICENSES.iter()
+        .find(|(_, phrases)| phrases.iter().all(|phrase| normalized.contains(& format!(" {} ", phrase))))
+        .map(|(id, _)| id.to_string());
+}
+
+/** Normalizes the text for matching, i.e. converts it to lowercase words separated by single spaces.
+
+    Punctuation, markup and line breaks are all treated as word separators, so that the matching is not affected by the formatting of the license text. The result also starts and ends with a space so that the phrases can be matched as whole words.
+ */
+fn normalize(text : & str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push(' ');
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            result.extend(c.to_lowercase());
+        } else if ! result.ends_with(' ') {
+            result.push(' ');
+        }
+    }
+    result.push(' ');
+    return result;
+}
@@ -11,17 +11,32 @@ mod db;
 #[allow(dead_code)]
 mod datastore;
 #[allow(dead_code)]
+mod substore_classifier;
+mod license_classifier;
+#[allow(dead_code)]
 mod records;
 #[allow(dead_code)]
 mod updater;
 mod datastore_maintenance_tasks;
 mod task_update_repo;
+mod task_update_issues;
 mod task_update_substore;
 mod task_verify_substore;
+mod task_pack_substore;
+mod task_index_messages;
+mod task_index_commit_projects;
+mod task_index_files;
+mod task_commit_metrics;
+mod task_resolve_users;
+mod task_migrate;
+mod task_gc_contents;
+mod doctor;
+#[allow(dead_code)]
 mod github;
 mod settings;
 #[allow(dead_code)]
 mod reporter;
+mod csv_input;
 
 use datastore::*;
 use updater::*;
@@ -76,14 +91,34 @@ fn execute_command() {
         // maintenance commands 
         "size" => datastore_size(),
         "summary" => datastore_summary(),
-        "savepoints" => datastore_savepoints(),
+        "savepoints" => datastore_savepoints(& SETTINGS.command[1..]),
+        "stats" => datastore_stats(& SETTINGS.command[1..]),
+        "doctor" => datastore_doctor(SETTINGS.command.get(1)),
         "add" => datastore_add(SETTINGS.command.get(1).unwrap()),
+        "add-ghtorrent" => datastore_import(SETTINGS.command.get(1).unwrap(), ProjectsDump::GHTorrent),
+        "add-seart" => datastore_import(SETTINGS.command.get(1).unwrap(), ProjectsDump::Seart),
         "create-savepoint" => datastore_create_savepoint(SETTINGS.command.get(1).unwrap()),
         "revert-to-savepoint" => datastore_revert_to_savepoint(SETTINGS.command.get(1).unwrap()),
+        "pack-substore" => datastore_pack_substore(
+            SETTINGS.command.get(1).unwrap(),
+            SETTINGS.command.get(2).unwrap(),
+            SETTINGS.command.get(3)
+        ),
+        "unpack-substore" => datastore_unpack_substore(SETTINGS.command.get(1).unwrap()),
+        "migrate" => datastore_migrate(SETTINGS.command.get(1), SETTINGS.command.get(2)),
+        "gc-contents" => datastore_gc_contents(SETTINGS.command.get(1).unwrap(), SETTINGS.command.get(2)),
+        "index-messages" => datastore_index_messages(SETTINGS.command.get(1).unwrap()),
+        "resolve-users" => datastore_resolve_users(SETTINGS.command.get(1).unwrap()),
+        "index-commit-projects" => datastore_index_commit_projects(SETTINGS.command.get(1).unwrap()),
+        "index-files" => datastore_index_files(SETTINGS.command.get(1).unwrap()),
+        "commit-metrics" => datastore_commit_metrics(SETTINGS.command.get(1).unwrap()),
         "update-project" => datastore_update_project(
             SETTINGS.command.get(1).unwrap(),
             SETTINGS.command.get(2),
         ),
+        "merge" if SETTINGS.command.get(1).map(|x| x.as_str()) == Some("--from") => datastore_merge_datastore(
+            SETTINGS.command.get(2).unwrap() // source path
+        ),
         "merge" => datastore_merge(
             SETTINGS.command.get(1).unwrap(), // source path
             SETTINGS.command.get(2).unwrap(), // source substore
@@ -103,6 +138,11 @@ fn execute_command() {
         "show-project" => example_show_project(
             SETTINGS.command.get(1).unwrap(),
         ),
+        "query" => datastore_query(& SETTINGS.command[1..]),
+        "list-deleted" => datastore_list_deleted(),
+        "blacklist" | "denylist" => datastore_denylist(& SETTINGS.command[1..]),
+        "campaign" => datastore_campaign(& SETTINGS.command[1..]),
+        "index-shas" => datastore_index_shas(),
         // debug commands
         "contents-compression" => datastore_contents_compression(),
         "debug" => datastore_debug(),
@@ -167,14 +207,60 @@ fn detect_version() {
 
 }
 
-fn datastore_savepoints() {
+fn datastore_savepoints(args : & [String]) {
     let ds = DatastoreView::from(& SETTINGS.datastore_root);
-    let mut num = 0;
-    for sp in ds.savepoints() {
-        println!("{}", sp);
-        num += 1;
+    match ds.savepoints_command(& args.iter().map(|x| x.as_str()).collect::<Vec<& str>>()) {
+        Ok(msg) => println!("{}", msg),
+        Err(e) => println!("ERROR: {}", e),
+    }
+}
+
+/** Prints the statistics of given substore. 
+ */
+fn datastore_stats(args : & [String]) {
+    let ds = Datastore::new(& SETTINGS.datastore_root, true);
+    match ds.stats_command(& args.iter().map(|x| x.as_str()).collect::<Vec<& str>>()) {
+        Ok(msg) => println!("{}", msg),
+        Err(e) => println!("ERROR: {}", e),
+    }
+}
+
+/** Checks the environment of the datastore, i.e. its version, permissions, disks, limits, libgit2, git and GitHub tokens, and prints advice for the problems found. 
+ 
+    The datastore given to the command takes precedence over the one from the global arguments. Exits with a non-zero code if any of the checks failed, so that the command can be used in scripts. 
+ */
+fn datastore_doctor(root : Option<& String>) {
+    let (errors, _) = doctor::doctor(root.unwrap_or(& SETTINGS.datastore_root));
+    if errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+/** Adds, removes, or lists the entries of the denylist. 
+ */
+fn datastore_denylist(args : & [String]) {
+    // the denylist of a running updater must be edited through its interactive mode, so that it takes effect right away
+    if let Err(e) = DatastoreLock::acquire(& SETTINGS.datastore_root, true) {
+        if e.kind() == std::io::ErrorKind::WouldBlock {
+            println!("ERROR: Datastore is used by another process, use the blacklist command of its interactive mode instead");
+            return;
+        }
+    }
+    let ds = Datastore::new(& SETTINGS.datastore_root, false);
+    match ds.denylist_command(& args.iter().map(|x| x.as_str()).collect::<Vec<& str>>()) {
+        Ok(msg) => println!("{}", msg),
+        Err(e) => println!("ERROR: {}", e),
+    }
+}
+
+/** Starts, removes, lists, or reports the status of update campaigns. 
+ */
+fn datastore_campaign(args : & [String]) {
+    let ds = Datastore::new(& SETTINGS.datastore_root, false);
+    match ds.campaign_command(& args.iter().map(|x| x.as_str()).collect::<Vec<& str>>()) {
+        Ok(msg) => println!("{}", msg),
+        Err(e) => println!("ERROR: {}", e),
     }
-    println!("Total {} savepoints found.", num);
 }
 
 /** Adds the given project or projects specified in a csv file to the datastore. 
@@ -188,6 +274,17 @@ fn datastore_add(url_or_file : & str) {
     });
 }
 
+/** Imports projects from given GHTorrent or SEART project dump. 
+ */
+fn datastore_import(source : & str, dump : ProjectsDump) {
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        let ds = Datastore::new(& SETTINGS.datastore_root, false);
+        reporter.run_task(Task::ImportProjects{source : source.to_owned(), dump}, |ts| {
+            return datastore_maintenance_tasks::task_import_projects(& ds, source.to_owned(), dump, ts);
+        });
+    });
+}
+
 /** Creates a savepoint of given name from the current datastore state. 
  */
 fn datastore_create_savepoint(name : & str) {
@@ -205,11 +302,137 @@ fn datastore_revert_to_savepoint(name : & str) {
     {
         let ds = Datastore::new(& SETTINGS.datastore_root, false);
         let sp = ds.get_savepoint(name).unwrap();
-        ds.revert_to_savepoint(&sp);
+        if let Err(e) = ds.revert_to_savepoint(&sp) {
+            println!("ERROR: {}", e);
+            return;
+        }
     }
     datastore_size();
 }
 
+/** Packs given substore into a tar archive, optionally as of given savepoint. 
+ */
+fn datastore_pack_substore(store : & str, output : & str, savepoint : Option<& String>) {
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        let ds = Datastore::new(& SETTINGS.datastore_root, true);
+        reporter.run_task(Task::PackSubstore{
+            store : records::StoreKind::from_string(store).expect(& format!("Unknown substore {}", store)),
+            output : output.to_owned(),
+            savepoint : savepoint.cloned(),
+        }, |ts| {
+            return task_pack_substore::task_pack_substore(& ds, ts);
+        });
+    });
+}
+
+/** Unpacks a substore from given archive into the datastore. 
+ */
+fn datastore_unpack_substore(input : & str) {
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        reporter.run_task(Task::UnpackSubstore{input : input.to_owned()}, |ts| {
+            return task_pack_substore::task_unpack_substore(& SETTINGS.datastore_root, ts);
+        });
+    });
+}
+
+/** Migrates the datastore to the current version, in place or into a new root given by `--into`. 
+ */
+fn datastore_migrate(opt : Option<& String>, into : Option<& String>) {
+    let into = match opt.map(|x| x.as_str()) {
+        Some("--into") => Some(into.expect("Target datastore root missing").to_owned()),
+        Some(x) => panic!("Unknown option {}", x),
+        None => None,
+    };
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        reporter.run_task(Task::Migrate{into : into.clone()}, |ts| {
+            return task_migrate::task_migrate(& SETTINGS.datastore_root, ts);
+        });
+    });
+}
+
+/** Removes contents of given substore unreachable from its projects, optionally keeping backups of the replaced files. 
+ */
+fn datastore_gc_contents(store : & str, opt : Option<& String>) {
+    let keep_backup = match opt.map(|x| x.as_str()) {
+        Some("--keep-backup") => true,
+        Some(x) => panic!("Unknown option {}", x),
+        None => false,
+    };
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        reporter.run_task(Task::CollectContents{
+            store : records::StoreKind::from_string(store).expect(& format!("Unknown substore {}", store)),
+            keep_backup,
+        }, |ts| {
+            return task_gc_contents::task_gc_contents(& SETTINGS.datastore_root, ts);
+        });
+    });
+}
+
+/** Builds or updates the commit messages index of given substore. 
+ */
+fn datastore_index_messages(store : & str) {
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        let ds = Datastore::new(& SETTINGS.datastore_root, false);
+        reporter.run_task(Task::IndexMessages{
+            store : records::StoreKind::from_string(store).expect(& format!("Unknown substore {}", store)),
+        }, |ts| {
+            return task_index_messages::task_index_messages(& ds, ts, & |_| {});
+        });
+    });
+}
+
+/** Determines users of given substore that are the same person. 
+ */
+fn datastore_resolve_users(store : & str) {
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        let ds = Datastore::new(& SETTINGS.datastore_root, false);
+        reporter.run_task(Task::ResolveUsers{
+            store : records::StoreKind::from_string(store).expect(& format!("Unknown substore {}", store)),
+        }, |ts| {
+            return task_resolve_users::task_resolve_users(& ds, ts);
+        });
+    });
+}
+
+/** Builds or updates the index of projects containing the commits of given substore. 
+ */
+fn datastore_index_commit_projects(store : & str) {
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        let ds = Datastore::new(& SETTINGS.datastore_root, false);
+        reporter.run_task(Task::IndexCommitProjects{
+            store : records::StoreKind::from_string(store).expect(& format!("Unknown substore {}", store)),
+        }, |ts| {
+            return task_index_commit_projects::task_index_commit_projects(& ds, ts, & |_| {});
+        });
+    });
+}
+
+/** Builds or updates the hash occurrences index of given substore. 
+ */
+fn datastore_index_files(store : & str) {
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        let ds = Datastore::new(& SETTINGS.datastore_root, false);
+        reporter.run_task(Task::IndexFiles{
+            store : records::StoreKind::from_string(store).expect(& format!("Unknown substore {}", store)),
+        }, |ts| {
+            return task_index_files::task_index_files(& ds, ts, & |_| {});
+        });
+    });
+}
+
+/** Calculates or updates the commit metrics of given substore. 
+ */
+fn datastore_commit_metrics(store : & str) {
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        let ds = Datastore::new(& SETTINGS.datastore_root, false);
+        reporter.run_task(Task::CommitMetrics{
+            store : records::StoreKind::from_string(store).expect(& format!("Unknown substore {}", store)),
+        }, |ts| {
+            return task_commit_metrics::task_commit_metrics(& ds, ts);
+        });
+    });
+}
+
 /** Forces the update of given project. 
  */
 fn datastore_update_project(project : & str, force_opt : Option<& String>) {
@@ -224,13 +447,15 @@ fn datastore_update_project(project : & str, force_opt : Option<& String>) {
     TerminalReporter::report(|reporter : & TerminalReporter| {
         let ds = Datastore::new(& SETTINGS.datastore_root, false);
         let gh = Github::new(& SETTINGS.github_tokens);
+        let hooks = Updater::builtin_update_hooks();
         let p = ds.projects.lock().unwrap().iter_all().filter(|(_, p)| p.matches_url(project)).next();
         if let Some((id, _)) = p {
             reporter.run_task(Task::UpdateRepo{
                 id : id, 
-                last_update_time : ds.get_project_last_update(id).map(|x| x.time()).or(Some(0)).unwrap()
+                last_update_time : ds.get_project_last_update(id).map(|x| x.time()).or(Some(0)).unwrap(),
+                class : PriorityClass::Now,
             }, |ts| {
-                return task_update_repo(& ds, & gh, ts, force, true);
+                return task_update_repo(& ds, & gh, & hooks, ts, force, true);
             });
         } else {
             panic!("No project named {} found", project);
@@ -252,6 +477,13 @@ fn datastore_merge(source_path : & str, source_substore : & str, target_substore
     );
 }
 
+/** Merges the entire source datastore into the datastore. 
+ */
+fn datastore_merge_datastore(source_path : & str) {
+    let mut merger = DatastoreMerger::new(& SETTINGS.datastore_root, source_path);
+    merger.merge_datastore();
+}
+
 /** Merges all substores from source to given substore in target.
  */
 fn datastore_merge_all(source_path : & str, target_substore : & str) {
@@ -284,7 +516,7 @@ fn example_active_projects(max_age : i64) {
         let mut heads = HashMap::<CommitId, i64>::new();
         let mut valid = 0;
         let mut total = 0;
-        let mut commits = ds.commits_info(substore);
+        let commits = ds.commits_info(substore);
         for (_id, p) in projects.iter().filter(|(_, p)| { p.substore == substore }) {
             total += 1;
             if let Some(_) = p.latest_valid_update_time() {
@@ -341,18 +573,18 @@ fn example_show_project(url : & str) {
             println!("    {}", l);
         }
         // determine the project's substore
-        let substore = ds.project_substores().filter(|(id, _)| *id == pid).map(|(_, s)| s).last().unwrap();
+        let substore = ds.project_substore_of(pid).unwrap();
         println!("substore: {:?}", substore);
         // if they exist, print heads and then the rest of the commits and their changes
-        if let Some((_, heads)) =  ds.project_heads().filter(|(id, _)| *id == pid).last() {
+        if let Some(heads) = ds.project_heads_of(pid) {
             println!("heads: {} entries", heads.len());
             for (name, (id, hash)) in heads.iter() {
                 println!("    {}: {} (id {})", name, purl.get_commit_terminal_link(*hash), id);
             }
-            let mut commit_hashes = ds.commits(substore);
-            let mut users = ds.users(substore);
-            let mut paths = ds.paths_strings(substore);
-            let mut hashes = ds.hashes(substore);
+            let commit_hashes = ds.commits(substore);
+            let users = ds.users(substore);
+            let paths = ds.paths_strings(substore);
+            let hashes = ds.hashes(substore);
             for (commit_id, commit) in ProjectCommitsIterator::new(& heads, ds.commits_info(substore)) {
                 let commit_hash = commit_hashes.get(commit_id).unwrap();
                 println!("    {}", purl.get_commit_terminal_link(commit_hash));
@@ -378,7 +610,85 @@ fn example_show_project(url : & str) {
     }
 }
 
+/** Prints the given project, or commit as JSON to stdout. 
+
+    Unlike the interactive mode, or `show-project` the output is meant for scripts. The datastore can be given as the first argument, otherwise the datastore from the `-ds` option is used. Projects are identified by their id or url, commits by their hash and are searched for in all substores. If nothing matches, an error is printed to stderr and parasite exits with non-zero code.
+ */
+fn datastore_query(args : & [String]) {
+    let (root, args) = if args.len() > 2 { (args[0].as_str(), & args[1..]) } else { (SETTINGS.datastore_root.as_str(), args) };
+    let ds = DatastoreView::from(root);
+    let result = match (args.get(0).map(|x| x.as_str()), args.get(1)) {
+        (Some("project"), Some(project)) => query_project(& ds, project),
+        (Some("commit"), Some(hash)) => query_commit(& ds, hash),
+        _ => Err(format!("Usage: query [datastore] project url_or_id | commit hash")),
+    };
+    match result {
+        Ok(json) => println!("{}", json.pretty(4)),
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn query_project(ds : & DatastoreView, project : & str) -> Result<json::JsonValue, String> {
+    let pid = match project.parse::<u64>() {
+        Ok(id) => Some(ProjectId::from(id)),
+        Err(_) => ds.project_urls().into_iter().filter(|(_, p)| p.matches_url(project)).map(|(id, _)| id).next(),
+    };
+    return pid.and_then(|pid| json_output::project_json(ds, pid)).ok_or(format!("No project matches {}", project));
+}
+
+fn query_commit(ds : & DatastoreView, hash : & str) -> Result<json::JsonValue, String> {
+    let sha = SHA::from_str(hash).map_err(|_| format!("Invalid commit hash {}", hash))?;
+    let mut result = json::JsonValue::new_array();
+    // the same commit may be stored in multiple substores
+    for substore in StoreKind::all() {
+        let id = match ds.commits(substore).find(& sha) {
+            Some(id) => id,
+            None => continue,
+        };
+        if let Some(commit) = ds.commits_info(substore).get(id) {
+            result.push(json_output::CommitJson::new(ds, substore, 0, 0).get(id, & commit)).unwrap();
+        }
+    }
+    if result.is_empty() {
+        return Err(format!("No commit {} found", hash));
+    }
+    return Ok(result);
+}
+
+
+/** Prints the projects whose repositories were deleted as csv. 
 
+    A project is deleted if its latest update record is the deleted record, i.e. projects whose repositories reappeared and were updated since are not included. The time is the time the deletion was detected and the reason the error that was reported. 
+ */
+fn datastore_list_deleted() {
+    let ds = DatastoreView::from(& SETTINGS.datastore_root);
+    let mut latest = HashMap::<ProjectId, ProjectLog>::new();
+    for (pid, log) in ds.project_updates() {
+        latest.insert(pid, log);
+    }
+    let mut deleted : Vec<(ProjectId, ProjectLog)> = latest.into_iter().filter(|(_, log)| log.is_deleted()).collect();
+    deleted.sort_by_key(|(pid, _)| u64::from(*pid));
+    let urls = ds.project_urls();
+    println!("id,url,time,reason");
+    for (pid, log) in deleted {
+        if let ProjectLog::Deleted{time, version : _, reason} = log {
+            println!("{},\"{}\",{},\"{}\"", pid, urls.get(pid).map(|x| x.clone_url()).unwrap_or_default(), time, reason.replace("\"", "\"\""));
+        }
+    }
+}
+
+/** Rebuilds the global index of substores that contain given commits and contents hashes. 
+ */
+fn datastore_index_shas() {
+    let ds = Datastore::new(& SETTINGS.datastore_root, false);
+    match ds.rebuild_sha_index() {
+        Ok(indexed) => println!("{} hashes indexed", indexed),
+        Err(e) => println!("ERROR: {}", e),
+    }
+}
 
 fn datastore_contents_compression() {
     let ds = DatastoreView::from(& SETTINGS.datastore_root);
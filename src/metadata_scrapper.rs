@@ -1,7 +1,6 @@
 use std::collections::HashSet;
 use std::fs::{OpenOptions};
 use std::io::{Write};
-use std::sync::Mutex;
 
 #[macro_use]
 extern crate lazy_static;
@@ -23,15 +22,32 @@ mod reporter;
 #[allow(dead_code)]
 mod datastore;
 #[allow(dead_code)]
+mod substore_classifier;
+#[allow(dead_code)]
+mod license_classifier;
+#[allow(dead_code)]
 mod db;
 #[allow(dead_code)]
 mod task_verify_substore;
 #[allow(dead_code)]
+mod task_pack_substore;
+#[allow(dead_code)]
+mod task_index_messages;
+mod task_index_commit_projects;
+mod task_index_files;
+mod task_commit_metrics;
+#[allow(dead_code)]
+mod task_resolve_users;
+#[allow(dead_code)]
 mod datastore_maintenance_tasks;
 #[allow(dead_code)]
 mod task_update_substore;
 #[allow(dead_code)]
 mod task_update_repo;
+#[allow(dead_code)]
+mod task_update_issues;
+#[allow(dead_code)]
+mod csv_input;
 
 use settings::SETTINGS;
 use github::Github;
@@ -40,93 +56,65 @@ fn main() {
     let metadata_filename = format!("{}.with_metadata", & SETTINGS.datastore_root);
     let previous_results = load_previous_results(& metadata_filename);
     let write_header = previous_results.is_empty();
-    let todo = Mutex::new(load_project_urls(& SETTINGS.datastore_root, previous_results));
+    let mut todo = load_project_urls(& SETTINGS.datastore_root, previous_results);
     let mut f = OpenOptions::new().create(true).append(true).open(& metadata_filename).unwrap();
     if write_header {
         writeln!(& mut f, "{}", ProjectInfo::csv_header()).unwrap();
     }
-    // now that we have todos, spawn the threads and start downloading the metadata
-    let (tx, rx) = crossbeam_channel::unbounded::<UpdateInfo>();
+    // now that we have todos, start downloading the metadata in batches whose requests are performed concurrently
     let gh = Github::new(& SETTINGS.github_tokens);
-    crossbeam::thread::scope(|s| {
-        for _ in 0..SETTINGS.num_threads {
-            s.spawn(|_| {
-                metadata_scrapper(& todo, & gh, tx.clone());
-            });
+    println!("Batch size: {}", SETTINGS.num_threads);
+    let mut limit = 100000 * SETTINGS.num_threads;
+    let mut valid = 0;
+    let mut errors = 0;
+    loop {
+        let mut batch = Vec::new();
+        while batch.len() < SETTINGS.num_threads && limit > 0 {
+            if let Some(project) = next_project_to_update(& mut todo) {
+                batch.push(project);
+                limit -= 1;
+            } else {
+                break;
+            }
+        }
+        if batch.is_empty() {
+            if limit == 0 {
+                println!("limit reached!");
+            }
+            break;
         }
-        let mut active_threads = SETTINGS.num_threads;
-        println!("Active threads: {}", active_threads);
-        let mut valid = 0;
-        let mut errors = 0;
-        while let Ok(msg) = rx.recv() {
-            match msg {
-                UpdateInfo::Ok{id : _ , csv_row} => {
-                    //println!("{}", id);
-                    writeln!(& mut f, "{}", csv_row).unwrap();
-                    valid += 1;
+        let names : Vec<String> = batch.iter().map(|(_, full_name)| full_name.clone()).collect();
+        for ((id, _), result) in batch.iter().zip(gh.get_repos(& names, None)) {
+            match result {
+                Ok(json) => {
+                    if let Some(pinfo) = ProjectInfo::from_json(& json) {
+                        writeln!(& mut f, "{}", pinfo.to_csv()).unwrap();
+                        valid += 1;
+                    } else {
+                        println!("{}: error {}", id, json);
+                        writeln!(& mut f, "{}", ProjectInfo::error_row(*id, & format!("{}", json))).unwrap();
+                        errors += 1;
+                    }
                 },
-                UpdateInfo::Fail{id, err} => {
-                    println!("{}: error {}", id, err);
-                    writeln!(& mut f, "{}", ProjectInfo::error_row(id, & err)).unwrap();
+                Err(e) => {
+                    println!("{}: error {}", id, e);
+                    writeln!(& mut f, "{}", ProjectInfo::error_row(*id, & format!("{}", e))).unwrap();
                     errors += 1;
-                    
-                },
-                UpdateInfo::Done => {
-                    println!("Worker done.");
-                    active_threads -= 1;
-                    if active_threads == 0 {
-                        break;
-                    }
                 }
             }
             if (valid + errors) % 1000 == 0 {
                 println!("Valid: {}, errors: {}", valid, errors);
             }
         }
-    }).unwrap();
-    println!("ALL DONE.");
-}
-
-
-enum UpdateInfo {
-    Ok{id : i64, csv_row : String},
-    Fail{id : i64, err: String},
-    Done,
-}
-
-type Tx = crossbeam_channel::Sender<UpdateInfo>;
-//type Rx = crossbeam_channel::Receiver<UpdateInfo>;
-
-
-fn metadata_scrapper(projects : & Mutex<Vec<(i64, String)>>, gh : &Github, tx : Tx) {
-    let mut limit = 100000;
-    while let Some((id, full_name)) = next_project_to_update(projects) { 
-        let metadata_request = format!("https://api.github.com/repos/{}", full_name);
         std::thread::sleep(std::time::Duration::from_millis(1000));
-        match gh.request(& metadata_request, None) {
-            Ok(json) => {
-                if let Some(pinfo) = ProjectInfo::from_json(& json) {
-                    tx.send(UpdateInfo::Ok{id, csv_row : pinfo.to_csv()}).unwrap();
-                } else {
-                    tx.send(UpdateInfo::Fail{id, err : format!("{}",json)}).unwrap();
-                }
-            },
-            Err(e) =>{
-                tx.send(UpdateInfo::Fail{id, err : format!("{}",e)}).unwrap();
-            }
-        }
-        limit -= 1;
-        if limit == 0 {
-            println!("limit reached!");
-            break;
-        }
     }
-    tx.send(UpdateInfo::Done).unwrap()
-    ;
+    println!("ALL DONE.");
 }
 
-fn next_project_to_update(projects : & Mutex<Vec<(i64, String)>>) -> Option<(i64, String)> {
-    let mut v = projects.lock().unwrap();
+fn next_project_to_update(v : & mut Vec<(i64, String)>) -> Option<(i64, String)> {
+    if v.is_empty() {
+        return None;
+    }
     let i = rand::random::<usize>() % v.len();
     let mut idx = i;
     loop {
@@ -7,6 +7,7 @@ extern crate clap;
 use clap::{Arg, App, SubCommand};
 
 use parasite::*;
+use parasite::table_readers::CachedReader;
 
 fn main() {
     let cmdline = App::new("Mistletoe")
@@ -20,6 +21,16 @@ fn main() {
         .arg(Arg::with_name("v")
             .short("v")
             .help("Sets the level of verbosity"))
+        .arg(Arg::with_name("cache-entries")
+            .long("cache-entries")
+            .takes_value(true)
+            .default_value("65536")
+            .help("Maximum number of values cached by the table readers of commands with random access, 0 disables the caches"))
+        .arg(Arg::with_name("cache-bytes")
+            .long("cache-bytes")
+            .takes_value(true)
+            .default_value("67108864")
+            .help("Maximum total size of the values cached by each table reader"))
         .subcommand(SubCommand::with_name("show-project")
                     .about("Shows information about a given project in the datastore.")
                     .arg(Arg::with_name("project")
@@ -32,9 +43,23 @@ fn main() {
                         .short("id")
                         .takes_value(true)
                         .help("Id of the project to be exported"))
+                    .arg(Arg::with_name("canonical-users")
+                        .long("canonical-users")
+                        .takes_value(false)
+                        .help("Shows the canonical users instead of their aliases"))
+                    .arg(Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format, human readable text, or json"))
             )
         .subcommand(SubCommand::with_name("export-project")
             .about("Creates a copy of the given project storing all files in the datastore as they existed in the project")
+            .arg(Arg::with_name("filter")
+                .long("filter")
+                .takes_value(true)
+                .help("Filter script deciding which projects and commits are exported, executed by sh -c (see README)"))
             .arg(Arg::with_name("project")
                 .long("project")
                 .short("p")
@@ -49,11 +74,37 @@ fn main() {
                 .long("projects")
                 .takes_value(true)
                 .help("csv file that stores project ids to be exported"))
+            .arg(Arg::with_name("label")
+                .long("label")
+                .takes_value(true)
+                .help("exports all projects with given label"))
             .arg(Arg::with_name("column")
                 .long("column")
                 .short("col")
                 .takes_value(true)
                 .help("column in the projects csv file to be used for the ids"))
+            .arg(Arg::with_name("column-name")
+                .long("column-name")
+                .takes_value(true)
+                .conflicts_with_all(&["column", "url-column"])
+                .help("name of the column in the projects csv file header to be used for the ids"))
+            .arg(Arg::with_name("url-column")
+                .long("url-column")
+                .takes_value(true)
+                .conflicts_with("column")
+                .help("name or index of the column in the projects csv file that contains project urls instead of ids"))
+            .arg(Arg::with_name("delimiter")
+                .long("delimiter")
+                .takes_value(true)
+                .help("Field delimiter of the projects csv file (comma by default, tab can be given as `tab`)"))
+            .arg(Arg::with_name("quote")
+                .long("quote")
+                .takes_value(true)
+                .help("Quote character of the projects csv file (double quote by default)"))
+            .arg(Arg::with_name("escape")
+                .long("escape")
+                .takes_value(true)
+                .help("Escape character for quotes in the projects csv file, by default quotes are doubled"))
             .arg(Arg::with_name("into")
                 .long("into")
                 .takes_value(true)
@@ -78,56 +129,1903 @@ fn main() {
                 .long("hash")
                 .takes_value(true)
                 .help("Hash of the commit to be displayed"))
+            .arg(Arg::with_name("canonical-users")
+                .long("canonical-users")
+                .takes_value(false)
+                .help("Shows the canonical users instead of their aliases"))
+            .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("Output format, human readable text, or json"))
         )
         .subcommand(SubCommand::with_name("check-heads")
             .about("Checks the head mappings")
         )
         .subcommand(SubCommand::with_name("check-projects")
-            .about("Checks the projects, which are ok, and which are errors")
+            .about("Checks the projects, which are ok, and which are errors, grouped by the kinds of the errors")
+        )
+        .subcommand(SubCommand::with_name("summarize")
+            .about("Outputs the latest summaries of all summarized projects as csv")
+        )
+        .subcommand(SubCommand::with_name("search-commits")
+            .about("Outputs commits of a substore whose messages contain all given words, using the commit messages index")
+            .arg(Arg::with_name("substore")
+                .long("substore")
+                .short("s")
+                .takes_value(true)
+                .required(true)
+                .help("Substore whose commits are searched"))
+            .arg(Arg::with_name("query")
+                .required(true)
+                .multiple(true)
+                .help("Words to search for"))
+        )
+        .subcommand(SubCommand::with_name("find-sha")
+            .about("Outputs the substores that contain commits, or file contents of given hashes")
+            .arg(Arg::with_name("hash")
+                .required(true)
+                .multiple(true)
+                .help("Hashes to look up"))
+        )
+        .subcommand(SubCommand::with_name("find-file")
+            .about("Outputs the projects, paths and commits at which file contents of given hash occur")
+            .arg(Arg::with_name("sha")
+                .long("sha")
+                .takes_value(true)
+                .required(true)
+                .help("Hash of the file contents"))
+        )
+        .subcommand(SubCommand::with_name("file-history")
+            .about("Outputs the commits of a project that changed given file, following its renames")
+            .arg(Arg::with_name("project")
+                .long("project")
+                .short("p")
+                .takes_value(true)
+                .help("name/url of the project"))
+            .arg(Arg::with_name("id")
+                .long("id")
+                .takes_value(true)
+                .help("Id of the project"))
+            .arg(Arg::with_name("path")
+                .required(true)
+                .help("Path of the file in the latest commits of the project"))
+        )
+        .subcommand(SubCommand::with_name("reconstruct-repo")
+            .about("Replays the stored commits of a project into a new git repository")
+            .arg(Arg::with_name("project")
+                .long("project")
+                .short("p")
+                .takes_value(true)
+                .help("name/url of the project to be reconstructed"))
+            .arg(Arg::with_name("id")
+                .long("id")
+                .takes_value(true)
+                .help("Id of the project to be reconstructed"))
+            .arg(Arg::with_name("into")
+                .long("into")
+                .takes_value(true)
+                .required(true)
+                .help("Path of the new repository, must not exist"))
+        )
+        .subcommand(SubCommand::with_name("export-fast-import")
+            .about("Exports the stored commits of projects as a git fast-import stream")
+            .arg(Arg::with_name("project")
+                .long("project")
+                .short("p")
+                .takes_value(true)
+                .help("name/url of the project to be exported"))
+            .arg(Arg::with_name("id")
+                .long("id")
+                .takes_value(true)
+                .help("Id of the project to be exported"))
+            .arg(Arg::with_name("label")
+                .long("label")
+                .takes_value(true)
+                .conflicts_with_all(&["project", "id"])
+                .help("Exports all projects with given label, their heads are prefixed by refs/projects/ID"))
+            .arg(Arg::with_name("into")
+                .long("into")
+                .takes_value(true)
+                .help("File the stream is written to, standard output by default"))
+        )
+        .subcommand(SubCommand::with_name("diff-savepoints")
+            .about("Reports what changed in the datastore between two savepoints")
+            .arg(Arg::with_name("from")
+                .required(true)
+                .takes_value(true)
+                .help("Name of the older savepoint"))
+            .arg(Arg::with_name("to")
+                .required(true)
+                .takes_value(true)
+                .help("Name of the newer savepoint"))
+            .arg(Arg::with_name("into")
+                .long("into")
+                .takes_value(true)
+                .help("File the csv of projects updated between the savepoints is written to, diff-savepoints.csv by default"))
+        )
+        .subcommand(SubCommand::with_name("export-graph")
+            .about("Exports the commit graph of a project, or of a whole substore")
+            .arg(Arg::with_name("filter")
+                .long("filter")
+                .takes_value(true)
+                .help("Filter script deciding which projects and commits are exported, executed by sh -c (see README)"))
+            .arg(Arg::with_name("project")
+                .long("project")
+                .short("p")
+                .takes_value(true)
+                .help("name/url of the project whose commits are exported"))
+            .arg(Arg::with_name("id")
+                .long("id")
+                .takes_value(true)
+                .help("Id of the project whose commits are exported"))
+            .arg(Arg::with_name("substore")
+                .long("substore")
+                .short("s")
+                .takes_value(true)
+                .conflicts_with_all(&["project", "id"])
+                .help("Substore whose commits are exported"))
+            .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["csv", "dot", "graphml"])
+                .default_value("csv")
+                .help("Format of the graph, csv edge list of commit and parent hashes, graphviz dot, or GraphML"))
+            .arg(Arg::with_name("into")
+                .long("into")
+                .takes_value(true)
+                .help("File the graph is written to, standard output by default"))
+            .arg(Arg::with_name("savepoint")
+                .long("savepoint")
+                .takes_value(true)
+                .help("Name of the savepoint the export is pinned to, the current state by default"))
+            .arg(Arg::with_name("manifest")
+                .long("manifest")
+                .takes_value(false)
+                .requires("into")
+                .help("Writes the manifest of the export to the same file with .manifest.json suffix"))
+        )
+        .subcommand(SubCommand::with_name("export-popularity")
+            .about("Exports the stars, forks, watchers and open issues of projects over time")
+            .arg(Arg::with_name("filter")
+                .long("filter")
+                .takes_value(true)
+                .help("Filter script deciding which projects and commits are exported, executed by sh -c (see README)"))
+            .arg(Arg::with_name("project")
+                .long("project")
+                .short("p")
+                .takes_value(true)
+                .help("name/url of the project whose popularity is exported, all projects by default"))
+            .arg(Arg::with_name("id")
+                .long("id")
+                .takes_value(true)
+                .help("Id of the project whose popularity is exported, all projects by default"))
+            .arg(Arg::with_name("into")
+                .long("into")
+                .takes_value(true)
+                .help("File the csv is written to, standard output by default"))
+            .arg(Arg::with_name("savepoint")
+                .long("savepoint")
+                .takes_value(true)
+                .help("Name of the savepoint the export is pinned to, the current state by default"))
+            .arg(Arg::with_name("manifest")
+                .long("manifest")
+                .takes_value(false)
+                .requires("into")
+                .help("Writes the manifest of the export to the same file with .manifest.json suffix"))
+        )
+        .subcommand(SubCommand::with_name("project-delta")
+            .about("Reports commits, paths and contributors added to a project between two points in time")
+            .arg(Arg::with_name("project")
+                .long("project")
+                .short("p")
+                .takes_value(true)
+                .help("name/url of the project"))
+            .arg(Arg::with_name("id")
+                .long("id")
+                .takes_value(true)
+                .help("Id of the project"))
+            .arg(Arg::with_name("from")
+                .long("from")
+                .takes_value(true)
+                .help("Savepoint name, or unix time of the older state, project creation by default"))
+            .arg(Arg::with_name("to")
+                .long("to")
+                .takes_value(true)
+                .help("Savepoint name, or unix time of the newer state, the current state by default"))
+        )
+        .subcommand(SubCommand::with_name("sample")
+            .about("Draws a reproducible stratified sample of projects")
+            .arg(Arg::with_name("substore")
+                .long("substore")
+                .short("s")
+                .takes_value(true)
+                .help("Substore whose projects are sampled, all projects by default"))
+            .arg(Arg::with_name("strata")
+                .long("strata")
+                .takes_value(true)
+                .help("Comma separated criteria the projects are stratified by, each optionally with its binning, e.g. stars:log,language. Criteria are stars, forks, language, commits, authors, committers and age (days between the first and last commit), binnings are log (powers of ten, default) and lin:WIDTH"))
+            .arg(Arg::with_name("n")
+                .long("n")
+                .required(true)
+                .takes_value(true)
+                .help("Size of the sample"))
+            .arg(Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("Seed of the random generator, current time by default"))
+            .arg(Arg::with_name("allocation")
+                .long("allocation")
+                .takes_value(true)
+                .possible_values(&["proportional", "equal"])
+                .default_value("proportional")
+                .help("Whether the sample size of each stratum is proportional to its size, or the same for all strata"))
+            .arg(Arg::with_name("into")
+                .long("into")
+                .takes_value(true)
+                .default_value("sample.csv")
+                .help("File the sample is written to, the manifest is written to the same file with .manifest.json suffix"))
+            .arg(Arg::with_name("savepoint")
+                .long("savepoint")
+                .takes_value(true)
+                .help("Name of the savepoint the export is pinned to, the current state by default"))
+        )
+        .subcommand(SubCommand::with_name("verify-manifest")
+            .about("Checks that the datastore still contains the exact data an export was created from")
+            .arg(Arg::with_name("manifest")
+                .required(true)
+                .takes_value(true)
+                .help("Manifest of the export"))
+        )
+        .subcommand(SubCommand::with_name("dump")
+            .about("Streams a whole table of the datastore as csv, or newline delimited json")
+            .arg(Arg::with_name("table")
+                .long("table")
+                .short("t")
+                .required(true)
+                .takes_value(true)
+                .possible_values(&["projects", "project_heads", "commits", "commit_parents", "changes", "paths", "users"])
+                .help("Table of the dataset schema to be dumped, all but projects and project heads are substore tables"))
+            .arg(Arg::with_name("substore")
+                .long("substore")
+                .short("s")
+                .takes_value(true)
+                .required_ifs(&[("table", "commits"), ("table", "commit_parents"), ("table", "changes"), ("table", "paths"), ("table", "users")])
+                .help("Substore whose table is dumped, or whose project heads are dumped (all by default)"))
+            .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["csv", "ndjson"])
+                .default_value("csv")
+                .help("Output format, csv with header, or one json object per line"))
+            .arg(Arg::with_name("into")
+                .long("into")
+                .takes_value(true)
+                .help("File the table is written to, standard output by default"))
+            .arg(Arg::with_name("savepoint")
+                .long("savepoint")
+                .takes_value(true)
+                .help("Name of the savepoint the dump is pinned to, the current state by default"))
+            .arg(Arg::with_name("manifest")
+                .long("manifest")
+                .takes_value(false)
+                .requires("into")
+                .help("Writes the manifest of the dump to the same file with .manifest.json suffix"))
+        )
+        .subcommand(SubCommand::with_name("savepoints")
+            .about("Lists the savepoints of the datastore, or shows the table sizes of a savepoint")
+            .subcommand(SubCommand::with_name("list")
+                .about("Lists all savepoints with their times and total sizes, oldest first"))
+            .subcommand(SubCommand::with_name("show")
+                .about("Shows given savepoint with the sizes of all its tables")
+                .arg(Arg::with_name("name")
+                    .required(true)
+                    .takes_value(true)
+                    .help("Name of the savepoint")))
+        )
+        .subcommand(SubCommand::with_name("export-dataset")
+            .about("Exports all core tables in the versioned dataset schema together with a schema.json describing them")
+            .arg(Arg::with_name("filter")
+                .long("filter")
+                .takes_value(true)
+                .help("Filter script deciding which projects and commits are exported, executed by sh -c (see README)"))
+            .arg(Arg::with_name("label")
+                .long("label")
+                .takes_value(true)
+                .help("Exports only projects with given label and the commits, paths and contents reachable from them"))
+            .arg(Arg::with_name("into")
+                .long("into")
+                .required(true)
+                .takes_value(true)
+                .help("Folder the tables, the schema and the manifest are written to"))
+            .arg(Arg::with_name("substore")
+                .long("substore")
+                .short("s")
+                .takes_value(true)
+                .help("Substore whose tables are exported, all substores with any projects by default"))
+            .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["csv", "ndjson"])
+                .default_value("csv")
+                .help("Format of the tables, csv with header, or one json object per line"))
+            .arg(Arg::with_name("savepoint")
+                .long("savepoint")
+                .takes_value(true)
+                .help("Name of the savepoint the export is pinned to, the current state by default"))
         )
         .get_matches();
     match cmdline.subcommand() {
         ("show-project",  Some(args)) => {
             show_project(& cmdline, args);
         },
-        ("export-project",  Some(args)) => {
-            export_project(& cmdline, args);
+        ("export-project",  Some(args)) => {
+            export_project(& cmdline, args);
+        },
+        ("show-commits", Some(args)) => {
+            show_commits(& cmdline, args);
+        },
+        ("check-heads", Some(args)) => {
+            check_heads(& cmdline, args);
+        },
+        ("check-projects", Some(args)) => {
+            check_projects(& cmdline, args);
+        },
+        ("summarize", Some(args)) => {
+            summarize(& cmdline, args);
+        },
+        ("search-commits", Some(args)) => {
+            search_commits(& cmdline, args);
+        },
+        ("find-sha", Some(args)) => {
+            find_sha(& cmdline, args);
+        },
+        ("find-file", Some(args)) => {
+            find_file(& cmdline, args);
+        },
+        ("file-history", Some(args)) => {
+            file_history(& cmdline, args);
+        },
+        ("reconstruct-repo", Some(args)) => {
+            reconstruct_repo(& cmdline, args);
+        },
+        ("export-fast-import", Some(args)) => {
+            export_fast_import(& cmdline, args);
+        },
+        ("diff-savepoints", Some(args)) => {
+            diff_savepoints(& cmdline, args);
+        },
+        ("export-graph", Some(args)) => {
+            export_graph(& cmdline, args);
+        },
+        ("export-popularity", Some(args)) => {
+            export_popularity(& cmdline, args);
+        },
+        ("project-delta", Some(args)) => {
+            project_delta(& cmdline, args);
+        },
+        ("sample", Some(args)) => {
+            sample(& cmdline, args);
+        },
+        ("verify-manifest", Some(args)) => {
+            verify_manifest(& cmdline, args);
+        },
+        ("dump", Some(args)) => {
+            dump(& cmdline, args);
+        },
+        ("savepoints", Some(args)) => {
+            savepoints(& cmdline, args);
+        },
+        ("export-dataset", Some(args)) => {
+            export_dataset(& cmdline, args);
+        },
+        
+        _                       => {}, // Either no subcommand or one not tested for...
+    }        
+}
+
+fn check_projects(cmdline : & clap::ArgMatches, _args : & clap::ArgMatches) {
+    // create the datastore and savepoint
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    let updates = ds.project_updates();
+    let mut errors = HashMap::<ProjectId, UpdateError>::new();
+    let mut max_id = 0;
+    for (pid, update) in updates.into_iter() {
+        if max_id < u64::from(pid) {
+            max_id = u64::from(pid);
+        }
+        match update.error_kind() {
+            Some(kind) => {
+                errors.insert(pid, kind);
+            },
+            None => {
+                errors.remove(&pid);
+            }
+        }
+    }
+    // the errors are grouped by their kinds, git errors regardless of their codes
+    let mut kinds = HashMap::<& str, usize>::new();
+    for kind in errors.values() {
+        *kinds.entry(kind.name()).or_insert(0) += 1;
+    }
+    println!("total,errors,{}", UpdateError::NAMES.join(","));
+    println!("{},{},{}", max_id, errors.len(), UpdateError::NAMES.iter().map(|x| kinds.get(x).unwrap_or(& 0).to_string()).collect::<Vec<String>>().join(","));
+}
+
+/* Outputs the latest summary of each project. 
+ 
+   Projects that have not been summarized yet (i.e. not updated since the summaries were introduced) are not reported. 
+ */
+fn summarize(cmdline : & clap::ArgMatches, _args : & clap::ArgMatches) {
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    let mut summaries = HashMap::<ProjectId, ProjectSummary>::new();
+    for (pid, summary) in ds.project_summaries() {
+        summaries.insert(pid, summary);
+    }
+    let mut project_substores = HashMap::new();
+    for (pid, substore) in ds.project_substores() {
+        project_substores.insert(pid, substore);
+    }
+    let mut ids : Vec<ProjectId> = summaries.keys().map(|x| *x).collect();
+    ids.sort_by_key(|x| u64::from(*x));
+    println!("pid,substore,commits,authors,committers,first_commit_time,last_commit_time,summary_time");
+    for pid in ids {
+        let summary = & summaries[& pid];
+        println!("{},{:?},{},{},{},{},{},{}", pid, project_substores.get(& pid).map(|x| *x).unwrap_or(StoreKind::Unspecified), summary.commits, summary.authors, summary.committers, summary.first_commit_time, summary.last_commit_time, summary.time);
+    }
+}
+
+/* Outputs the commits whose messages contain all words of the query as csv. 
+ 
+   Only commits already indexed by the `index-messages` command of parasite are searched. 
+ */
+fn search_commits(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    let substore = args.value_of("substore").unwrap();
+    let substore = match StoreKind::from_string(substore) {
+        Some(x) => x,
+        None => {
+            println!("ERROR: Unknown substore {}", substore);
+            return;
+        }
+    };
+    let query = args.values_of("query").unwrap().collect::<Vec<& str>>().join(" ");
+    let commits = ds.commits(substore);
+    let commits_info = ds.commits_info(substore);
+    println!("commit_id,hash,committer_time");
+    for id in ds.search_commits(substore, & query) {
+        println!("{},{},{}", id, commits.get(id).unwrap(), commits_info.get(id).map(|x| x.committer_time).unwrap_or(0));
+    }
+}
+
+/* Outputs the substores that contain commits and file contents of given hashes using the global hash index. 
+ */
+fn find_sha(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    println!("hash,kind,substore");
+    for hash in args.values_of("hash").unwrap() {
+        let sha = match SHA::from_str(hash) {
+            Ok(x) => x,
+            Err(_) => {
+                println!("ERROR: Invalid hash {}", hash);
+                return;
+            }
+        };
+        let (commits, contents) = ds.find_sha(& sha);
+        for substore in commits {
+            println!("{},commit,{:?}", sha, substore);
+        }
+        for substore in contents {
+            println!("{},contents,{:?}", sha, substore);
+        }
+    }
+}
+
+/* Outputs the projects, paths and commits at which file contents of given hash occur as csv. 
+ 
+   Only commits already indexed by the `index-files` command of parasite, whose projects are indexed by its `index-commit-projects` command, are found. 
+ */
+fn find_file(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    let hash = args.value_of("sha").unwrap();
+    let sha = match SHA::from_str(hash) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("ERROR: Invalid hash {}", hash);
+            return;
+        }
+    };
+    println!("substore,project_id,path,commit_id,commit");
+    let mut readers = HashMap::new();
+    for (substore, project, path, commit) in ds.find_file(& sha) {
+        let (paths, commits) = readers.entry(substore).or_insert_with(|| (ds.paths_strings(substore), ds.commits(substore)));
+        println!("{:?},{},\"{}\",{},{}", substore, project, paths.get(path).unwrap_or_default().replace("\"", "\"\""), commit, commits.get(commit).unwrap());
+    }
+}
+
+/* Outputs the history of a file in a project, oldest commits first. 
+ 
+   Commits deleting the file have no contents hash. 
+ */
+fn file_history(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    let pid = match get_project_id(& ds, args) {
+        Some(pid) => pid,
+        None => {
+            println!("ERROR: No matching project found");
+            return;
+        }
+    };
+    println!("commit_id,commit,committer_time,hash_id,hash");
+    // projects not yet assigned to a substore have no history
+    let substore = match ds.project_substore_of(pid) {
+        Some(substore) if substore != StoreKind::Unspecified => substore,
+        _ => return,
+    };
+    let commits = ds.commits(substore);
+    let commits_info = ds.commits_info(substore);
+    let hashes = ds.hashes(substore);
+    for (id, hash_id) in ds.file_history(pid, args.value_of("path").unwrap()) {
+        let time = commits_info.get(id).map(|x| x.committer_time).unwrap_or_default();
+        let hash = if hash_id == HashId::DELETED { String::new() } else { hashes.get(hash_id).map(|x| x.to_string()).unwrap_or_default() };
+        println!("{},{},{},{},{}", id, commits.get(id).map(|x| x.to_string()).unwrap_or_default(), time, hash_id, hash);
+    }
+}
+
+/* Reconstructs a git repository of the project from the datastore. 
+ 
+   The commits of the project are replayed in topological order, each commit's tree is the tree of its first parent with the commit's changes applied. Only files whose contents are stored in the datastore are present in the trees, as the datastore only has hashes of the others. The authors, committers, times and messages of the commits are preserved, but since the trees differ from the original ones, the hashes of the reconstructed commits differ too. The mapping from the original hashes to the reconstructed ones is written to `parasite-commits.csv` in the `.git` directory. Commits whose information is not stored are skipped, their children are reconstructed without them and heads pointing to them are not created. 
+
+   All heads of the project are created as branches in the new repository and the main branch is checked out. 
+ */
+fn reconstruct_repo(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    let into = args.value_of("into").unwrap();
+    if Path::new(into).exists() {
+        println!("ERROR: {} already exists", into);
+        return;
+    }
+    let pid = match get_project_id(& ds, args) {
+        Some(pid) => pid,
+        None => {
+            println!("ERROR: No matching project found");
+            return;
+        }
+    };
+    let substore = ds.project_substore_of(pid).unwrap_or(StoreKind::Unspecified);
+    let heads = match ds.project_heads_of(pid) {
+        Some(heads) => heads,
+        None => {
+            println!("ERROR: Project {} has no heads", pid);
+            return;
+        }
+    };
+    let commits : HashMap<CommitId, CommitInfo> = ProjectCommitsIterator::new(& heads, ds.commits_info(substore)).collect();
+    println!("Reconstructing {} commits of project {} into {}", commits.len(), pid, into);
+    // names of the users are stored as their metadata
+    let mut user_names = HashMap::<UserId, String>::new();
+    for (id, mtd) in ds.users_metadata(substore) {
+        if mtd.key == Metadata::USER_NAME {
+            user_names.insert(id, mtd.value);
+        }
+    }
+    let users = ds.users(substore);
+    let paths = ds.paths_strings(substore);
+    let contents = ds.contents(substore);
+    let hashes = ds.commits(substore);
+    let repo = git2::Repository::init(into).unwrap();
+    let signature = |id : UserId, time : i64| {
+        let email = users.get(id).unwrap_or_default();
+        let name = user_names.get(& id).filter(|x| ! x.is_empty()).cloned().unwrap_or(if email.is_empty() { "unknown".to_owned() } else { email.clone() });
+        return git2::Signature::new(& name, & email, & git2::Time::new(time, 0)).unwrap();
+    };
+    let mut blobs = HashMap::<HashId, Option<git2::Oid>>::new();
+    let mut reconstructed = HashMap::<CommitId, (git2::Oid, git2::Oid)>::new();
+    let mut missing_contents = 0;
+    // parents must be reconstructed before their children
+    for id in topological_order(& heads, & commits) {
+        let info = & commits[& id];
+        let mut changes = Vec::<(Vec<String>, Option<git2::Oid>)>::new();
+        for (path_id, hash_id) in info.changes.iter() {
+            let path : Vec<String> = paths.get(*path_id).unwrap().split('/').map(|x| x.to_owned()).collect();
+            let blob = if *hash_id == HashId::DELETED { None } else {
+                *blobs.entry(*hash_id).or_insert_with(|| contents.get(*hash_id).map(|(_, bytes)| repo.blob(& bytes).unwrap()))
+            };
+            if blob.is_none() && *hash_id != HashId::DELETED {
+                missing_contents += 1;
+            }
+            changes.push((path, blob));
+        }
+        // parents whose information is not stored were not reconstructed
+        let base = info.parents.iter().find_map(|p| reconstructed.get(p)).map(|(_, tree_id)| repo.find_tree(*tree_id).unwrap());
+        let tree_id = match update_tree(& repo, base, changes).unwrap() {
+            Some(tree_id) => tree_id,
+            None => repo.treebuilder(None).unwrap().write().unwrap(),
+        };
+        let tree = repo.find_tree(tree_id).unwrap();
+        let parents : Vec<git2::Commit> = info.parents.iter().filter_map(|p| reconstructed.get(p)).map(|(commit_id, _)| repo.find_commit(*commit_id).unwrap()).collect();
+        let parents : Vec<& git2::Commit> = parents.iter().collect();
+        let commit_id = repo.commit(None, & signature(info.author, info.author_time), & signature(info.committer, info.committer_time), & info.message, & tree, & parents).unwrap();
+        reconstructed.insert(id, (commit_id, tree_id));
+    }
+    // write the commit mapping
+    let mut f = File::create(repo.path().join("parasite-commits.csv")).unwrap();
+    writeln!(f, "hash,reconstructed").unwrap();
+    for (id, (commit_id, _)) in reconstructed.iter() {
+        writeln!(f, "{},{}", hashes.get(*id).unwrap(), commit_id).unwrap();
+    }
+    // create the branches and checkout the main one
+    for (name, (id, _)) in heads.iter() {
+        match reconstructed.get(id) {
+            Some((commit_id, _)) => { repo.reference(name, *commit_id, true, "reconstructed by mistletoe").unwrap(); },
+            None => println!("WARNING: Head {} has no stored commit information and was not created", name),
+        }
+    }
+    if let Some(main_branch) = get_project_main_branch(& ds, pid).map(|x| format!("refs/heads/{}", x)) {
+        if heads.get(& main_branch).map(|(id, _)| reconstructed.contains_key(id)).unwrap_or(false) {
+            repo.set_head(& main_branch).unwrap();
+            repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+        }
+    }
+    println!("{} commits reconstructed, {} changes without stored contents omitted", reconstructed.len(), missing_contents);
+}
+
+/* Exports the commits of a project, or of all projects with a label as a `git fast-import` stream. 
+ 
+   Commits are replayed in topological order in the same way as by `reconstruct-repo`, i.e. each commit starts from the tree of its first parent with its changes applied and files whose contents are not stored are omitted. All files have the regular file mode and times are in UTC as the datastore has neither. Each commit carries its original hash as `original-oid`. Commits shared by the exported projects are exported only once. Once all commits are written, the heads of the projects are set to their commits. Since the stream may be written to the standard output, progress is reported on the standard error. 
+ */
+fn export_fast_import(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    let (pids, prefixed) = match args.value_of("label") {
+        Some(label) => (ds.projects_with_label(label), true),
+        None => match get_project_id(& ds, args) {
+            Some(pid) => (vec![pid], false),
+            None => {
+                eprintln!("ERROR: No matching project found");
+                return;
+            }
+        },
+    };
+    let output : Box<dyn Write> = match args.value_of("into") {
+        Some(path) => Box::new(File::create(path).unwrap()),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut output = std::io::BufWriter::new(output);
+    let mut exporter = FastImportExporter{ ds : & ds, marks : HashMap::new(), blobs : HashMap::new(), next_mark : 1, commits : 0, missing_contents : 0 };
+    let mut refs = Vec::<(String, (StoreKind, CommitId))>::new();
+    for pid in pids {
+        let (substore, heads) = match (ds.project_substore_of(pid), ds.project_heads_of(pid)) {
+            (Some(substore), Some(heads)) if ! heads.is_empty() => (substore, heads),
+            _ => {
+                eprintln!("WARNING: project {} has no heads, skipping", pid);
+                continue;
+            }
+        };
+        let ref_name = |name : & String| if prefixed { format!("refs/projects/{}/{}", pid, name.trim_start_matches("refs/")) } else { name.clone() };
+        // all commits are written to the first head, which is set to its actual commit at the end
+        let commit_ref = ref_name(heads.keys().min().unwrap());
+        exporter.export_project(substore, & heads, & commit_ref, & mut output).unwrap();
+        for (name, (id, _)) in heads.iter() {
+            refs.push((ref_name(name), (substore, *id)));
+        }
+        eprintln!("{}: {} commits exported", pid, exporter.commits);
+    }
+    refs.sort_by(|a, b| a.0.cmp(& b.0));
+    for (name, commit) in refs {
+        if let Some(mark) = exporter.marks.get(& commit) {
+            write!(output, "reset {}\nfrom :{}\n\n", name, mark).unwrap();
+        }
+    }
+    write!(output, "done\n").unwrap();
+    output.flush().unwrap();
+    eprintln!("{} commits exported, {} changes without stored contents, or paths omitted", exporter.commits, exporter.missing_contents);
+}
+
+/* State of a fast-import export shared by all exported projects. 
+ 
+   Blobs and commits are identified by marks, the marks of already exported commits and contents are kept so that objects shared by projects are written only once. 
+ */
+struct FastImportExporter<'a> {
+    ds : &'a DatastoreView,
+    marks : HashMap<(StoreKind, CommitId), u64>,
+    /* Marks of the exported blobs, or None for contents that are not stored. */
+    blobs : HashMap<(StoreKind, HashId), Option<u64>>,
+    next_mark : u64,
+    commits : usize,
+    missing_contents : usize,
+}
+
+impl<'a> FastImportExporter<'a> {
+
+    fn export_project(& mut self, substore : StoreKind, heads : & ProjectHeads, commit_ref : & str, output : & mut dyn Write) -> Result<(), std::io::Error> {
+        let commits : HashMap<CommitId, CommitInfo> = ProjectCommitsIterator::new(heads, self.ds.commits_info(substore)).collect();
+        let mut user_names = HashMap::<UserId, String>::new();
+        for (id, mtd) in self.ds.users_metadata(substore) {
+            if mtd.key == Metadata::USER_NAME {
+                user_names.insert(id, mtd.value);
+            }
+        }
+        let users = self.ds.users(substore);
+        let paths = self.ds.paths_strings(substore);
+        let contents = self.ds.contents(substore);
+        let hashes = self.ds.commits(substore);
+        let signature = |id : UserId, time : i64| {
+            let email = users.get(id).unwrap_or_default();
+            let name = user_names.get(& id).filter(|x| ! x.is_empty()).cloned().unwrap_or(if email.is_empty() { "unknown".to_owned() } else { email.clone() });
+            // angle brackets and newlines would break the identity line
+            let clean = |x : & str| x.replace(|c| c == '<' || c == '>' || c == '\n', "");
+            return format!("{} <{}> {} +0000", clean(& name), clean(& email), time);
+        };
+        for id in topological_order(heads, & commits) {
+            if self.marks.contains_key(& (substore, id)) {
+                continue;
+            }
+            let info = & commits[& id];
+            let mut changes : Vec<(String, Option<u64>)> = Vec::new();
+            for (path_id, hash_id) in info.changes.iter() {
+                let path = match paths.get(*path_id) {
+                    Some(path) => path,
+                    None => {
+                        self.missing_contents += 1;
+                        continue;
+                    },
+                };
+                if *hash_id == HashId::DELETED {
+                    changes.push((path, None));
+                    continue;
+                }
+                if ! self.blobs.contains_key(& (substore, *hash_id)) {
+                    let mark = match contents.get(*hash_id) {
+                        Some((_, bytes)) => {
+                            let mark = self.next_mark;
+                            self.next_mark += 1;
+                            write!(output, "blob\nmark :{}\ndata {}\n", mark, bytes.len())?;
+                            output.write_all(& bytes)?;
+                            write!(output, "\n")?;
+                            Some(mark)
+                        },
+                        None => None,
+                    };
+                    self.blobs.insert((substore, *hash_id), mark);
+                }
+                match self.blobs[& (substore, *hash_id)] {
+                    Some(mark) => changes.push((path, Some(mark))),
+                    None => self.missing_contents += 1,
+                }
+            }
+            // changes are ordered by paths so that exports of the same data are identical
+            changes.sort();
+            let mark = self.next_mark;
+            self.next_mark += 1;
+            // parents whose information is not stored were not exported
+            let parents : Vec<u64> = info.parents.iter().filter_map(|p| self.marks.get(& (substore, *p)).cloned()).collect();
+            if parents.is_empty() {
+                // otherwise the commit would get the current tip of the ref as its parent
+                write!(output, "reset {}\n", commit_ref)?;
+            }
+            write!(output, "commit {}\nmark :{}\n", commit_ref, mark)?;
+            if let Some(hash) = hashes.get(id) {
+                write!(output, "original-oid {}\n", hash)?;
+            }
+            write!(output, "author {}\ncommitter {}\n", signature(info.author, info.author_time), signature(info.committer, info.committer_time))?;
+            write!(output, "data {}\n", info.message.len())?;
+            output.write_all(info.message.as_bytes())?;
+            write!(output, "\n")?;
+            for (i, parent) in parents.iter().enumerate() {
+                write!(output, "{} :{}\n", if i == 0 { "from" } else { "merge" }, parent)?;
+            }
+            for (path, blob) in changes {
+                match blob {
+                    Some(blob) => write!(output, "M 100644 :{} {}\n", blob, quote_fast_import_path(& path))?,
+                    None => write!(output, "D {}\n", quote_fast_import_path(& path))?,
+                }
+            }
+            write!(output, "\n")?;
+            self.marks.insert((substore, id), mark);
+            self.commits += 1;
+        }
+        return Ok(());
+    }
+}
+
+/* Quotes a path for a fast-import file command if it contains characters that would be misinterpreted. 
+ */
+fn quote_fast_import_path(path : & str) -> String {
+    if ! path.starts_with('"') && ! path.contains('\n') {
+        return path.to_owned();
+    }
+    return format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"));
+}
+
+/* Returns the commits reachable from the heads ordered so that parents precede their children. 
+
+   Commits whose information is not stored (see `ProjectCommitsIterator`) are skipped together with the history reachable only through them, their children are then treated as having no such parents. 
+ */
+fn topological_order(heads : & ProjectHeads, commits : & HashMap<CommitId, CommitInfo>) -> Vec<CommitId> {
+    let mut result = Vec::<CommitId>::new();
+    let mut visited = HashSet::<CommitId>::new();
+    // the queue contains the commits and whether their parents have already been visited
+    let mut q : Vec<(CommitId, bool)> = heads.iter().map(|(_, (id, _))| (*id, false)).collect();
+    while let Some((id, parents_done)) = q.pop() {
+        if parents_done {
+            result.push(id);
+        } else if visited.insert(id) {
+            let info = match commits.get(& id) {
+                Some(info) => info,
+                None => continue,
+            };
+            q.push((id, true));
+            for p in info.parents.iter() {
+                if ! visited.contains(p) {
+                    q.push((*p, false));
+                }
+            }
+        }
+    }
+    return result;
+}
+
+/* Applies the changes to given tree and returns the id of the new tree, or None if the tree is empty. 
+ 
+   The changes are paths split into their components with the new blob of the file, or None if the file has been deleted. 
+ */
+fn update_tree(repo : & git2::Repository, base : Option<git2::Tree>, changes : Vec<(Vec<String>, Option<git2::Oid>)>) -> Result<Option<git2::Oid>, git2::Error> {
+    let mut builder = repo.treebuilder(base.as_ref())?;
+    let mut subtrees = HashMap::<String, Vec<(Vec<String>, Option<git2::Oid>)>>::new();
+    for (mut path, blob) in changes {
+        let name = path.remove(0);
+        if path.is_empty() {
+            match blob {
+                Some(blob) => { builder.insert(& name, blob, 0o100644)?; },
+                None => if builder.get(& name)?.is_some() {
+                    builder.remove(& name)?;
+                },
+            }
+        } else {
+            subtrees.entry(name).or_insert_with(Vec::new).push((path, blob));
+        }
+    }
+    for (name, changes) in subtrees {
+        let subtree = builder.get(& name)?.filter(|x| x.kind() == Some(git2::ObjectType::Tree)).map(|x| x.id());
+        let base = match subtree {
+            Some(id) => Some(repo.find_tree(id)?),
+            None => None,
+        };
+        match update_tree(repo, base, changes)? {
+            Some(tree) => { builder.insert(& name, tree, 0o040000)?; },
+            None => if builder.get(& name)?.is_some() {
+                builder.remove(& name)?;
+            },
+        }
+    }
+    if builder.len() == 0 {
+        return Ok(None);
+    }
+    return Ok(Some(builder.write()?));
+}
+
+/* Reports the differences between two savepoints. 
+ 
+   Prints the number of new projects and for each substore the number of new commits and the growth of its contents, which is negative if the contents were collected in between. The projects updated between the savepoints are written as csv with the number of their updates, updates that changed the project and errors. Only the records stored between the two savepoints are read. 
+ */
+fn diff_savepoints(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    let (from, to) = match (ds.at_savepoint(args.value_of("from").unwrap()), ds.at_savepoint(args.value_of("to").unwrap())) {
+        (Some(from), Some(to)) => (from, to),
+        _ => {
+            println!("ERROR: Savepoint not found");
+            return;
+        }
+    };
+    let from_savepoint = from.savepoint().unwrap();
+    let to_savepoint = to.savepoint().unwrap();
+    if from_savepoint.time() > to_savepoint.time() {
+        println!("ERROR: Savepoint {} is newer than {}", from_savepoint.name(), to_savepoint.name());
+        return;
+    }
+    println!("from: {} ({})", from_savepoint.name(), pretty_timestamp(from_savepoint.time()));
+    println!("to:   {} ({})", to_savepoint.name(), pretty_timestamp(to_savepoint.time()));
+    // the tables only grow between savepoints, except the contents that shrink when collected, so the differences are signed
+    println!("new projects: {}", to.project_urls().len() as i64 - from.project_urls().len() as i64);
+    println!("substore,new_commits,new_contents_bytes");
+    for kind in StoreKind::all() {
+        let commits = to.commits(kind).len() as i64 - from.commits(kind).len() as i64;
+        let contents = to.contents(kind).filesize() as i64 - from.contents(kind).filesize() as i64;
+        if commits != 0 || contents != 0 {
+            println!("{:?},{},{}", kind, commits, contents);
+        }
+    }
+    // count the updates of each project in the interval
+    let mut updates = HashMap::<ProjectId, (u64, u64, u64, i64)>::new();
+    for (pid, log) in to.project_updates_since(from_savepoint) {
+        let x = updates.entry(pid).or_insert((0, 0, 0, 0));
+        x.0 += 1;
+        if let ProjectLog::Ok{ .. } = log {
+            x.1 += 1;
+        }
+        if log.is_error() {
+            x.2 += 1;
+        }
+        x.3 = log.time();
+    }
+    let mut ids : Vec<ProjectId> = updates.keys().map(|x| *x).collect();
+    ids.sort_by_key(|x| u64::from(*x));
+    let urls = to.project_urls();
+    let mut f = File::create(args.value_of("into").unwrap_or("diff-savepoints.csv")).unwrap();
+    writeln!(f, "project_id,url,updates,changed,errors,last_update_time").unwrap();
+    for pid in ids {
+        let (total, changed, errors, time) = updates[& pid];
+        writeln!(f, "{},\"{}\",{},{},{},{}", pid, urls.get(pid).map(|x| x.clone_url()).unwrap_or_default(), total, changed, errors, time).unwrap();
+    }
+    println!("updated projects: {}", updates.len());
+}
+
+/* Exports the commit graph of a project, or of all commits in a substore. 
+ 
+   The commits of a project are those reachable from its latest heads, the same as shown by `show-project`. Commits are identified by their hashes and edges go from commits to their parents. Root commits appear in the csv edge list with empty parent, in the other formats the committer time is exported as a node attribute. 
+ */
+fn export_graph(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = match export_view(cmdline, args) {
+        Some(ds) => ds,
+        None => return,
+    };
+    let mut filter = match ExportFilter::new(args) {
+        Some(x) => x,
+        None => return,
+    };
+    let mut manifest = Manifest::new("mistletoe export-graph", & ds);
+    manifest.filter("format", args.value_of("format").unwrap());
+    filter.describe(& mut manifest);
+    let mut commits = Vec::<(CommitId, CommitInfo)>::new();
+    let substore;
+    if let Some(name) = args.value_of("substore") {
+        substore = match StoreKind::from_string(name) {
+            Some(x) => x,
+            None => {
+                println!("ERROR: Unknown substore {}", name);
+                return;
+            }
+        };
+        commits.extend(ds.commits_info(substore));
+        manifest.filter("substore", format!("{:?}", substore));
+    } else if let Some(pid) = get_project_id(& ds, args) {
+        manifest.filter("project", u64::from(pid));
+        substore = ds.project_substore_of(pid).unwrap_or(StoreKind::Unspecified);
+        if let Some(heads) = ds.project_heads_of(pid) {
+            commits.extend(ProjectCommitsIterator::new(& heads, ds.commits_info(substore)));
+        }
+    } else {
+        println!("ERROR: No matching project found");
+        return;
+    }
+    if filter.is_active() {
+        let mut accepted = Vec::new();
+        for (id, info) in commits {
+            match filter.accepts_commit(& ds, substore, id, & info) {
+                Ok(true) => accepted.push((id, info)),
+                Ok(false) => {},
+                Err(e) => {
+                    println!("ERROR: {}", e);
+                    return;
+                }
+            }
+        }
+        filter.report();
+        commits = accepted;
+    }
+    let mut output : Box<dyn Write> = match args.value_of("into") {
+        Some(path) => Box::new(File::create(path).unwrap()),
+        None => Box::new(std::io::stdout()),
+    };
+    if args.value_of("format") == Some("csv") {
+        manifest.column("commit", "string").column("parent", "string");
+    }
+    write_manifest(args, & manifest);
+    let hashes = ds.commits(substore);
+    let hash = |id : CommitId| hashes.get(id).map(|x| x.to_string()).unwrap_or_default();
+    match args.value_of("format").unwrap() {
+        "dot" => {
+            writeln!(output, "digraph commits {{").unwrap();
+            for (id, info) in commits.iter() {
+                writeln!(output, "    \"{}\" [time={}];", hash(*id), info.committer_time).unwrap();
+                for parent in info.parents.iter() {
+                    writeln!(output, "    \"{}\" -> \"{}\";", hash(*id), hash(*parent)).unwrap();
+                }
+            }
+            writeln!(output, "}}").unwrap();
+        },
+        "graphml" => {
+            writeln!(output, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+            writeln!(output, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">").unwrap();
+            writeln!(output, "  <key id=\"time\" for=\"node\" attr.name=\"committer_time\" attr.type=\"long\"/>").unwrap();
+            writeln!(output, "  <graph id=\"commits\" edgedefault=\"directed\">").unwrap();
+            for (id, info) in commits.iter() {
+                writeln!(output, "    <node id=\"{}\"><data key=\"time\">{}</data></node>", hash(*id), info.committer_time).unwrap();
+            }
+            for (id, info) in commits.iter() {
+                for parent in info.parents.iter() {
+                    writeln!(output, "    <edge source=\"{}\" target=\"{}\"/>", hash(*id), hash(*parent)).unwrap();
+                }
+            }
+            writeln!(output, "  </graph>").unwrap();
+            writeln!(output, "</graphml>").unwrap();
+        },
+        _ => {
+            writeln!(output, "commit,parent").unwrap();
+            for (id, info) in commits.iter() {
+                if info.parents.is_empty() {
+                    writeln!(output, "{},", hash(*id)).unwrap();
+                }
+                for parent in info.parents.iter() {
+                    writeln!(output, "{},{}", hash(*id), hash(*parent)).unwrap();
+                }
+            }
+        },
+    }
+}
+
+/* Outputs the popularity records of the projects as csv, ordered by project ids and time. 
+ 
+   Each record is the popularity of the project at the time its metadata were checked, so only GitHub projects have records. 
+ */
+fn export_popularity(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = match export_view(cmdline, args) {
+        Some(ds) => ds,
+        None => return,
+    };
+    let project = get_project_id(& ds, args);
+    if project.is_none() && (args.is_present("id") || args.is_present("project")) {
+        println!("ERROR: No matching project found");
+        return;
+    }
+    let mut filter = match ExportFilter::new(args) {
+        Some(x) => x,
+        None => return,
+    };
+    let mut manifest = Manifest::new("mistletoe export-popularity", & ds);
+    if let Some(pid) = project {
+        manifest.filter("project", u64::from(pid));
+    }
+    filter.describe(& mut manifest);
+    for (name, kind) in [("pid", "u64"), ("time", "i64"), ("stars", "u64"), ("forks", "u64"), ("watchers", "u64"), ("open_issues", "u64")].iter() {
+        manifest.column(name, kind);
+    }
+    write_manifest(args, & manifest);
+    let mut records : Vec<(ProjectId, ProjectPopularity)> = ds.project_popularity()
+        .filter(|(pid, _)| project.map(|x| x == *pid).unwrap_or(true))
+        .collect();
+    // the sort is stable so records of the same project and time stay in the order they were recorded
+    records.sort_by_key(|(pid, popularity)| (u64::from(*pid), popularity.time));
+    if filter.is_active() {
+        let mut accepted = HashMap::<ProjectId, bool>::new();
+        for (pid, _) in records.iter() {
+            if ! accepted.contains_key(pid) {
+                match filter.accepts_project(& ds, *pid) {
+                    Ok(x) => accepted.insert(*pid, x),
+                    Err(e) => {
+                        println!("ERROR: {}", e);
+                        return;
+                    }
+                };
+            }
+        }
+        filter.report();
+        records.retain(|(pid, _)| accepted[pid]);
+    }
+    let mut output : Box<dyn Write> = match args.value_of("into") {
+        Some(path) => Box::new(File::create(path).unwrap()),
+        None => Box::new(std::io::stdout()),
+    };
+    writeln!(output, "pid,time,stars,forks,watchers,open_issues").unwrap();
+    for (pid, popularity) in records {
+        writeln!(output, "{},{},{},{},{},{}", pid, popularity.time, popularity.stars, popularity.forks, popularity.watchers, popularity.open_issues).unwrap();
+    }
+}
+
+/* Lists the savepoints, or shows a single savepoint with its table sizes. 
+ 
+   Listing is the default when no subcommand is given. 
+ */
+fn savepoints(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    if let ("show", Some(show_args)) = args.subcommand() {
+        let name = show_args.value_of("name").unwrap();
+        match ds.savepoints().find(|sp| sp.name() == name) {
+            Some(sp) => print!("{:#}", sp),
+            None => println!("ERROR: Savepoint {} not found", name),
+        }
+    } else {
+        let mut num = 0;
+        for sp in ds.savepoints() {
+            print!("{}", sp);
+            num += 1;
+        }
+        println!("Total {} savepoints found.", num);
+    }
+}
+
+/* Streams a whole table of the datastore to csv, or newline delimited json. 
+ 
+   The tables and their columns are those of the dataset schema (see `DATASET_TABLES`) and are written by the same functions as `export-dataset` writes them, so that a dumped table is the same as the table of an unfiltered dataset export, but for a single substore. The tables are read sequentially and written row by row through a buffered writer, so that the memory used does not grow with the size of the table and a slow consumer of the output simply slows the dump down. 
+ */
+fn dump(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = match export_view(cmdline, args) {
+        Some(ds) => ds,
+        None => return,
+    };
+    let table = DATASET_TABLES.iter().find(|x| Some(x.name) == args.value_of("table")).unwrap();
+    let mut manifest = Manifest::new("mistletoe dump", & ds);
+    manifest.filter("table", table.name);
+    let substore = match args.value_of("substore") {
+        Some(name) => match StoreKind::from_string(name) {
+            Some(x) => {
+                manifest.filter("substore", format!("{:?}", x));
+                x
+            },
+            None => {
+                println!("ERROR: Unknown substore {}", name);
+                return;
+            }
+        },
+        None => StoreKind::Unspecified,
+    };
+    for (name, kind, _, _) in table.columns.iter() {
+        manifest.column(name, kind);
+    }
+    write_manifest(args, & manifest);
+    let output : Box<dyn Write> = match args.value_of("into") {
+        Some(path) => Box::new(File::create(path).unwrap()),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut writer = DumpWriter{
+        output : std::io::BufWriter::new(output),
+        columns : table.columns.iter().map(|(name, _, _, _)| *name).collect(),
+        ndjson : args.value_of("format") == Some("ndjson"),
+    };
+    let mut project_substores = HashMap::<ProjectId, StoreKind>::new();
+    if table.name == "projects" || table.name == "project_heads" {
+        for (pid, substore) in ds.project_substores() {
+            project_substores.insert(pid, substore);
+        }
+    }
+    let result = writer.header().and_then(|_| match table.name {
+        "projects" => export_dataset_projects(& ds, & project_substores, None, & mut writer),
+        "project_heads" => {
+            let substores : Vec<StoreKind> = if substore == StoreKind::Unspecified { StoreKind::all().collect() } else { vec![substore] };
+            export_dataset_heads(& ds, & project_substores, & substores, None, & mut writer)
+        },
+        _ => export_dataset_substore_table(& ds, table.name, substore, None, & mut writer),
+    });
+    // a closed output, such as a pipe to head, simply ends the dump
+    if let Err(e) = result.and_then(|_| writer.output.flush()) {
+        if e.kind() != std::io::ErrorKind::BrokenPipe {
+            eprintln!("ERROR: {}", e);
+        }
+    }
+}
+
+/** Writes the rows of a dumped table either as csv, or as newline delimited json objects. 
+ */
+struct DumpWriter {
+    output : std::io::BufWriter<Box<dyn Write>>,
+    columns : Vec<&'static str>,
+    ndjson : bool,
+}
+
+impl DumpWriter {
+    /** Writes the csv header, json objects need none. 
+     */
+    fn header(& mut self) -> Result<(), std::io::Error> {
+        if ! self.ndjson {
+            writeln!(self.output, "{}", self.columns.join(","))?;
+        }
+        return Ok(());
+    }
+
+    /** Writes a single row whose values are in the order of the columns. 
+     
+        Strings are quoted in csv, with quotes inside them doubled, and nulls are empty fields. 
+     */
+    fn row(& mut self, values : Vec<json::JsonValue>) -> Result<(), std::io::Error> {
+        if self.ndjson {
+            let mut object = json::JsonValue::new_object();
+            for (name, value) in self.columns.iter().zip(values.into_iter()) {
+                object[*name] = value;
+            }
+            writeln!(self.output, "{}", object.dump())?;
+        } else {
+            let fields : Vec<String> = values.iter().map(|value| match value.as_str() {
+                Some(x) => format!("\"{}\"", x.replace("\"", "\"\"")),
+                None if value.is_null() => String::new(),
+                None => value.dump(),
+            }).collect();
+            writeln!(self.output, "{}", fields.join(","))?;
+        }
+        return Ok(());
+    }
+}
+
+/** Version of the dataset schema written by `export-dataset`. 
+ 
+    Must be increased whenever a table, or a column is added, removed, reordered, or changes its type or meaning, so that loaders can detect exports they do not understand. 
+ */
+const DATASET_SCHEMA_VERSION : u64 = 1;
+
+/** A table of the dataset schema. 
+ 
+    Columns are always written in the given order and are given as name, type (`u64`, `i64`, or `string`), whether the column may be null and a description. 
+ */
+struct DatasetTable {
+    name : &'static str,
+    description : &'static str,
+    columns : &'static [(&'static str, &'static str, bool, &'static str)],
+}
+
+static DATASET_TABLES : [DatasetTable; 7] = [
+    DatasetTable{
+        name : "projects",
+        description : "All projects in the datastore with their latest urls.",
+        columns : &[
+            ("project_id", "u64", false, "Id of the project, stable for the lifetime of the datastore"),
+            ("url", "string", false, "Latest clone url of the project"),
+            ("substore", "string", true, "Substore of the project, null if not assigned to any yet"),
+            ("last_update_time", "i64", true, "Unix time of the latest update attempt, null if never updated"),
+        ],
+    },
+    DatasetTable{
+        name : "project_heads",
+        description : "Heads of the projects as of their latest update.",
+        columns : &[
+            ("project_id", "u64", false, "Id of the project"),
+            ("substore", "string", false, "Substore the commit ids refer to"),
+            ("name", "string", false, "Full name of the head, such as refs/heads/master"),
+            ("commit_id", "u64", true, "Id of the head commit in the substore, null if the commit was not analyzed"),
+            ("hash", "string", false, "Hash of the head commit"),
+        ],
+    },
+    DatasetTable{
+        name : "commits",
+        description : "Commits of the exported substores.",
+        columns : &[
+            ("substore", "string", false, "Substore of the commit"),
+            ("commit_id", "u64", false, "Id of the commit, unique within its substore"),
+            ("hash", "string", false, "Hash of the commit"),
+            ("author_id", "u64", false, "Id of the author in the users table of the substore"),
+            ("author_time", "i64", false, "Unix time the commit was authored"),
+            ("committer_id", "u64", false, "Id of the committer in the users table of the substore"),
+            ("committer_time", "i64", false, "Unix time the commit was committed"),
+            ("message", "string", false, "Commit message"),
+        ],
+    },
+    DatasetTable{
+        name : "commit_parents",
+        description : "Parents of the commits, one row per parent.",
+        columns : &[
+            ("substore", "string", false, "Substore of the commit"),
+            ("commit_id", "u64", false, "Id of the commit"),
+            ("parent_index", "u64", false, "Position of the parent, 0 for the first parent"),
+            ("parent_id", "u64", false, "Id of the parent commit"),
+        ],
+    },
+    DatasetTable{
+        name : "changes",
+        description : "Files changed by the commits, one row per changed path.",
+        columns : &[
+            ("substore", "string", false, "Substore of the commit"),
+            ("commit_id", "u64", false, "Id of the commit"),
+            ("path_id", "u64", false, "Id of the changed path in the paths table of the substore"),
+            ("hash_id", "u64", true, "Id of the new contents hash in the substore, null if the file was deleted"),
+        ],
+    },
+    DatasetTable{
+        name : "paths",
+        description : "Paths of the changed files.",
+        columns : &[
+            ("substore", "string", false, "Substore of the path"),
+            ("path_id", "u64", false, "Id of the path, unique within its substore"),
+            ("path", "string", false, "Path of the file relative to the repository root"),
+        ],
+    },
+    DatasetTable{
+        name : "users",
+        description : "Authors and committers of the commits.",
+        columns : &[
+            ("substore", "string", false, "Substore of the user"),
+            ("user_id", "u64", false, "Id of the user, unique within its substore"),
+            ("email", "string", false, "Email of the user, a keyed hash of it if the datastore is anonymized"),
+        ],
+    },
+];
+
+/* Exports all core tables of the datastore in the versioned dataset schema. 
+ 
+   Each table of `DATASET_TABLES` is written into its own file in the output folder with the columns in the order of the schema, together with `schema.json` describing the tables and their columns and the manifest of the export. Ids of projects are stable for the lifetime of the datastore, ids of commits, paths and users only within their substore, so substore tables always contain the substore column and rows are identified by the substore and id pair. Ids are not stable across different datastores, even if one was merged from the other. 
+ */
+fn export_dataset(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let ds = match export_view(cmdline, args) {
+        Some(ds) => ds,
+        None => return,
+    };
+    // restricting the view to the labelled projects up front makes the substore tables read only their commits
+    let ds = match args.value_of("label") {
+        Some(label) => {
+            println!("restricting to projects with label {}...", label);
+            ds.restricted(& ds.projects_with_label(label))
+        },
+        None => ds,
+    };
+    let into = args.value_of("into").unwrap();
+    let ndjson = args.value_of("format") == Some("ndjson");
+    let mut filter = match ExportFilter::new(args) {
+        Some(x) => x,
+        None => return,
+    };
+    let mut manifest = Manifest::new("mistletoe export-dataset", & ds);
+    manifest.filter("schema_version", DATASET_SCHEMA_VERSION);
+    manifest.filter("format", args.value_of("format").unwrap());
+    if let Some(label) = args.value_of("label") {
+        manifest.filter("label", label);
+    }
+    filter.describe(& mut manifest);
+    let mut project_substores = HashMap::<ProjectId, StoreKind>::new();
+    for (pid, substore) in ds.project_substores() {
+        project_substores.insert(pid, substore);
+    }
+    let substores : Vec<StoreKind> = match args.value_of("substore") {
+        Some(name) => match StoreKind::from_string(name) {
+            Some(x) => {
+                manifest.filter("substore", format!("{:?}", x));
+                vec![x]
+            },
+            None => {
+                println!("ERROR: Unknown substore {}", name);
+                return;
+            }
+        },
+        None => StoreKind::all().filter(|x| project_substores.values().any(|s| s == x)).collect(),
+    };
+    let selection = if filter.is_active() {
+        println!("filtering...");
+        match DatasetSelection::new(& ds, & mut filter, & substores) {
+            Ok(x) => Some(x),
+            Err(e) => {
+                println!("ERROR: {}", e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    filter.report();
+    std::fs::create_dir_all(into).unwrap();
+    let mut schema = json::object!{
+        "version" => DATASET_SCHEMA_VERSION,
+        "format" => args.value_of("format").unwrap(),
+        "ids" => "Project ids are stable for the lifetime of the datastore. Commit, path, user and hash ids are stable within their substore, rows of substore tables are identified by the substore and id pair. Ids are not stable across datastores.",
+        "nulls" => "Empty fields in csv, null in ndjson. Only columns marked as nullable may be null.",
+        "tables" => json::JsonValue::new_array(),
+    };
+    for table in DATASET_TABLES.iter() {
+        let mut columns = json::JsonValue::new_array();
+        for (name, kind, nullable, description) in table.columns.iter() {
+            columns.push(json::object!{
+                "name" => *name,
+                "type" => *kind,
+                "nullable" => *nullable,
+                "description" => *description,
+            }).unwrap();
+        }
+        schema["tables"].push(json::object!{
+            "name" => table.name,
+            "file" => format!("{}.{}", table.name, if ndjson { "ndjson" } else { "csv" }),
+            "description" => table.description,
+            "columns" => columns,
+        }).unwrap();
+    }
+    std::fs::write(format!("{}/schema.json", into), schema.pretty(4)).unwrap();
+    manifest.write(& format!("{}/manifest.json", into)).unwrap();
+    for table in DATASET_TABLES.iter() {
+        println!("exporting {}...", table.name);
+        let mut writer = DumpWriter{
+            output : std::io::BufWriter::new(Box::new(File::create(format!("{}/{}.{}", into, table.name, if ndjson { "ndjson" } else { "csv" })).unwrap())),
+            columns : table.columns.iter().map(|(name, _, _, _)| *name).collect(),
+            ndjson,
+        };
+        writer.header().unwrap();
+        match table.name {
+            "projects" => export_dataset_projects(& ds, & project_substores, selection.as_ref(), & mut writer).unwrap(),
+            "project_heads" => export_dataset_heads(& ds, & project_substores, & substores, selection.as_ref(), & mut writer).unwrap(),
+            _ => {
+                for substore in substores.iter() {
+                    export_dataset_substore_table(& ds, table.name, *substore, selection.as_ref(), & mut writer).unwrap();
+                }
+            },
+        }
+        writer.output.flush().unwrap();
+    }
+    println!("dataset exported into {}", into);
+}
+
+/** Projects and commits of a dataset export accepted by its filter script. 
+ 
+    The script is asked about every project only once, before any table is written, so that all tables of the dataset agree on the exported commits. The commits are derived from the accepted projects: only the commits reachable from the heads of the accepted projects are given to the script, which may reject some of them further. Paths and users are not filtered. 
+ */
+struct DatasetSelection {
+    projects : HashSet<ProjectId>,
+    commits : HashMap<StoreKind, HashSet<CommitId>>,
+}
+
+impl DatasetSelection {
+
+    fn new(ds : & DatastoreView, filter : & mut ExportFilter, substores : & Vec<StoreKind>) -> Result<DatasetSelection, std::io::Error> {
+        let mut result = DatasetSelection{ projects : HashSet::new(), commits : HashMap::new() };
+        for id in 0..ds.project_urls().len() {
+            let pid = ProjectId::from(id as u64);
+            if ds.includes_project(pid) && filter.accepts_project(ds, pid)? {
+                result.projects.insert(pid);
+            }
+        }
+        let projects : Vec<ProjectId> = result.projects.iter().map(|x| *x).collect();
+        let reachable = ds.restricted(& projects);
+        for substore in substores.iter() {
+            let mut accepted = HashSet::new();
+            for (id, commit) in reachable.iter_commits_info(*substore) {
+                if filter.accepts_commit(ds, *substore, id, & commit)? {
+                    accepted.insert(id);
+                }
+            }
+            result.commits.insert(*substore, accepted);
+        }
+        return Ok(result);
+    }
+
+    fn project(& self, id : ProjectId) -> bool {
+        return self.projects.contains(& id);
+    }
+
+    fn commit(& self, substore : StoreKind, id : CommitId) -> bool {
+        return self.commits.get(& substore).map(|x| x.contains(& id)).unwrap_or(false);
+    }
+}
+
+fn export_dataset_projects(ds : & DatastoreView, project_substores : & HashMap<ProjectId, StoreKind>, selection : Option<& DatasetSelection>, writer : & mut DumpWriter) -> Result<(), std::io::Error> {
+    let mut updates = HashMap::<ProjectId, i64>::new();
+    for (pid, log) in ds.project_updates() {
+        updates.insert(pid, log.time());
+    }
+    let urls = ds.project_urls();
+    for id in 0..urls.len() {
+        let pid = ProjectId::from(id as u64);
+        if ! ds.includes_project(pid) || selection.map(|x| ! x.project(pid)).unwrap_or(false) {
+            continue;
+        }
+        if let Some(url) = urls.get(pid) {
+            let substore = match project_substores.get(& pid) {
+                Some(x) if *x != StoreKind::Unspecified => format!("{:?}", x).into(),
+                _ => json::JsonValue::Null,
+            };
+            let time = updates.get(& pid).map(|x| json::JsonValue::from(*x)).unwrap_or(json::JsonValue::Null);
+            writer.row(vec![id.into(), url.clone_url().into(), substore, time])?;
+        }
+    }
+    return Ok(());
+}
+
+/** Exports the latest heads of projects assigned to the exported substores. 
+ */
+fn export_dataset_heads(ds : & DatastoreView, project_substores : & HashMap<ProjectId, StoreKind>, substores : & Vec<StoreKind>, selection : Option<& DatasetSelection>, writer : & mut DumpWriter) -> Result<(), std::io::Error> {
+    let mut latest_heads = HashMap::<ProjectId, ProjectHeads>::new();
+    for (pid, heads) in ds.project_heads() {
+        if project_substores.get(& pid).map(|x| substores.contains(x)).unwrap_or(false) && selection.map(|x| x.project(pid)).unwrap_or(true) {
+            latest_heads.insert(pid, heads);
+        }
+    }
+    let mut ids : Vec<ProjectId> = latest_heads.keys().map(|x| *x).collect();
+    ids.sort_by_key(|x| u64::from(*x));
+    for pid in ids {
+        if let Some(project_heads) = latest_heads.get(& pid) {
+            let substore = format!("{:?}", project_substores[& pid]);
+            let mut names : Vec<& String> = project_heads.keys().collect();
+            names.sort();
+            for name in names {
+                let (id, hash) = project_heads[name];
+                let id = if id == CommitId::NONE { json::JsonValue::Null } else { u64::from(id).into() };
+                writer.row(vec![u64::from(pid).into(), substore.clone().into(), name.clone().into(), id, hash.to_string().into()])?;
+            }
+        }
+    }
+    return Ok(());
+}
+
+fn export_dataset_substore_table(ds : & DatastoreView, table : & str, substore : StoreKind, selection : Option<& DatasetSelection>, writer : & mut DumpWriter) -> Result<(), std::io::Error> {
+    let name = format!("{:?}", substore);
+    let accepted = |id : CommitId| selection.map(|x| x.commit(substore, id)).unwrap_or(true);
+    match table {
+        "commits" => {
+            let hashes = ds.commits(substore);
+            for (id, commit) in ds.iter_commits_info(substore) {
+                if ! accepted(id) {
+                    continue;
+                }
+                writer.row(vec![
+                    name.clone().into(),
+                    u64::from(id).into(),
+                    hashes.get(id).map(|x| x.to_string()).unwrap_or_default().into(),
+                    u64::from(commit.author).into(),
+                    commit.author_time.into(),
+                    u64::from(commit.committer).into(),
+                    commit.committer_time.into(),
+                    commit.message.into(),
+                ])?;
+            }
+        },
+        "commit_parents" => {
+            for (id, commit) in ds.iter_commits_info(substore) {
+                if ! accepted(id) {
+                    continue;
+                }
+                for (i, parent) in commit.parents.iter().enumerate() {
+                    writer.row(vec![name.clone().into(), u64::from(id).into(), i.into(), u64::from(*parent).into()])?;
+                }
+            }
         },
-        ("show-commits", Some(args)) => {
-            show_commits(& cmdline, args);
+        "changes" => {
+            for (id, commit) in ds.iter_commits_info(substore) {
+                if ! accepted(id) {
+                    continue;
+                }
+                // changes are ordered by paths so that exports of the same data are identical
+                let mut changes : Vec<(& PathId, & HashId)> = commit.changes.iter().collect();
+                changes.sort_by_key(|(path_id, _)| u64::from(**path_id));
+                for (path_id, hash_id) in changes {
+                    let hash_id = if *hash_id == HashId::DELETED { json::JsonValue::Null } else { u64::from(*hash_id).into() };
+                    writer.row(vec![name.clone().into(), u64::from(id).into(), u64::from(*path_id).into(), hash_id])?;
+                }
+            }
         },
-        ("check-heads", Some(args)) => {
-            check_heads(& cmdline, args);
+        "paths" => {
+            for (id, path) in ds.iter_paths_strings(substore) {
+                writer.row(vec![name.clone().into(), u64::from(id).into(), path.into()])?;
+            }
         },
-        ("check-projects", Some(args)) => {
-            check_projects(& cmdline, args);
+        "users" => {
+            for (id, email) in ds.users(substore) {
+                writer.row(vec![name.clone().into(), u64::from(id).into(), email.into()])?;
+            }
         },
-        
-        _                       => {}, // Either no subcommand or one not tested for...
-    }        
+        _ => panic!("Unknown dataset table {}", table),
+    }
+    return Ok(());
 }
 
-fn check_projects(cmdline : & clap::ArgMatches, _args : & clap::ArgMatches) {
-    // create the datastore and savepoint
+/* Reports what was added to a project between two points. 
+ 
+   The points are either savepoints, or unix times. The state of the project at a savepoint are the commits reachable from its heads as of the savepoint. At a unix time, the heads are the last heads record stored at or before the time, whose time is taken from the update log of the project (see `DatastoreView::project_heads_history`), and the commits are read from the current state of the datastore, since commits are never removed. Commits, changed paths and contributors (authors and committers) present in the newer state but not the older one are printed. Commits are compared by their hashes so that projects which changed their substore in between are reported correctly. 
+ */
+fn project_delta(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
     let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
-    let updates = ds.project_updates();
-    let mut errors = HashSet::<ProjectId>::new();
-    let mut max_id = 0;
-    for (pid, update) in updates.into_iter() {
-        if max_id < u64::from(pid) {
-            max_id = u64::from(pid);
+    let pid = match get_project_id(& ds, args) {
+        Some(pid) => pid,
+        None => {
+            println!("ERROR: No matching project found");
+            return;
         }
-        match update {
-            ProjectLog::Error{ .. } => {
-                errors.insert(pid);
-            },
-            _ => {
-                errors.remove(&pid);
+    };
+    let from = match args.value_of("from") {
+        Some(point) => match resolve_point(& ds, pid, point) {
+            Some(state) => state,
+            None => {
+                println!("ERROR: Savepoint {} not found", point);
+                return;
+            }
+        },
+        None => ("empty project".to_owned(), None),
+    };
+    let to = match args.value_of("to") {
+        Some(point) => match resolve_point(& ds, pid, point) {
+            Some(state) => state,
+            None => {
+                println!("ERROR: Savepoint {} not found", point);
+                return;
+            }
+        },
+        None => ("current state".to_owned(), ds.project_substore_of(pid).zip(ds.project_heads_of(pid)).map(|x| (None, x))),
+    };
+    println!("project: {} ({})", pid, get_project_url(& ds, pid).clone_url());
+    println!("from: {}", from.0);
+    println!("to:   {}", to.0);
+    let (old_commits, old_paths, old_users) = project_state(& ds, from.1);
+    let (new_commits, new_paths, new_users) = project_state(& ds, to.1);
+    let mut commits : Vec<(& SHA, & i64)> = new_commits.iter().filter(|(hash, _)| ! old_commits.contains_key(hash)).collect();
+    commits.sort_by_key(|(hash, time)| (**time, hash.to_string()));
+    let mut paths : Vec<& String> = new_paths.difference(& old_paths).collect();
+    paths.sort();
+    let mut users : Vec<& String> = new_users.difference(& old_users).collect();
+    users.sort();
+    println!("commits: {} -> {}, {} new", old_commits.len(), new_commits.len(), commits.len());
+    for (hash, time) in commits {
+        println!("    {} {}", hash, pretty_timestamp(*time));
+    }
+    println!("paths: {} -> {}, {} new", old_paths.len(), new_paths.len(), paths.len());
+    for path in paths {
+        println!("    {}", path);
+    }
+    println!("contributors: {} -> {}, {} new", old_users.len(), new_users.len(), users.len());
+    for user in users {
+        println!("    {}", user);
+    }
+}
+
+/** Returns the description of the point and the substore and heads of the project at given savepoint, or at given unix time. 
+ 
+    The heads at a savepoint are read from the view at the savepoint, which is returned as well. The heads at a time are the last heads record of the project stored at or before the time, if any. Returns `None` if the point is neither a savepoint, nor a time. 
+ */
+fn resolve_point(ds : & DatastoreView, pid : ProjectId, point : & str) -> Option<(String, Option<(Option<DatastoreView>, (StoreKind, ProjectHeads))>)> {
+    if let Some(view) = ds.at_savepoint(point) {
+        let sp = view.savepoint().unwrap();
+        let description = format!("savepoint {} ({})", sp.name(), pretty_timestamp(sp.time()));
+        let state = view.project_substore_of(pid).zip(view.project_heads_of(pid));
+        return Some((description, state.map(|x| (Some(view), x))));
+    }
+    let time = point.parse::<i64>().ok()?;
+    return match ds.project_heads_history(pid).into_iter().filter(|(stored, _, _)| *stored <= time).last() {
+        Some((stored, substore, heads)) => Some((format!("heads stored at {}", pretty_timestamp(stored)), Some((None, (substore, heads))))),
+        None => Some((format!("no heads before {}", pretty_timestamp(time)), None)),
+    };
+}
+
+/** Returns the commits (with their committer times), paths changed by the commits and their authors and committers reachable from given heads of the project. 
+ 
+    The commits are read from the view the heads were taken from, if any, or from given datastore. 
+ */
+fn project_state(ds : & DatastoreView, state : Option<(Option<DatastoreView>, (StoreKind, ProjectHeads))>) -> (HashMap<SHA, i64>, HashSet<String>, HashSet<String>) {
+    let mut commits = HashMap::new();
+    let mut paths = HashSet::new();
+    let mut users = HashSet::new();
+    if let Some((view, (substore, heads))) = state {
+        let view = view.as_ref().unwrap_or(ds);
+        let commit_hashes = view.commits(substore);
+        let user_names = view.users(substore);
+        let path_strings = view.paths_strings(substore);
+        let mut user_ids = HashSet::new();
+        let mut path_ids = HashSet::new();
+        for (commit_id, commit) in ProjectCommitsIterator::new(& heads, view.commits_info(substore)) {
+            if let Some(hash) = commit_hashes.get(commit_id) {
+                commits.insert(hash, commit.committer_time);
+            }
+            user_ids.insert(commit.author);
+            user_ids.insert(commit.committer);
+            path_ids.extend(commit.changes.keys());
+        }
+        users.extend(user_ids.into_iter().filter_map(|id| user_names.get(id)));
+        paths.extend(path_ids.into_iter().filter_map(|id| path_strings.get(id)));
+    }
+    return (commits, paths, users);
+}
+
+/* Draws a stratified sample of projects. 
+ 
+   The projects (of given substore, or all of them) are divided into strata by the values of the criteria, numeric values binned as specified, and the sample size is allocated to the strata either proportionally to their sizes (largest remainder method), or equally. Projects with unknown value of a criterion (e.g. stars of non GitHub projects, or commits of projects not yet summarized) form their own `NA` bin. Within each stratum the projects are sampled uniformly by a generator with given seed. Since the projects and strata are always processed in the same order, the same seed on the same datastore yields the same sample. 
+
+   The sample is written as csv with the project ids, urls, substores, strata and the criteria values. The seed, criteria and sizes of the strata are recorded in the manifest. 
+ */
+fn sample(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    use rand::SeedableRng;
+    use rand::seq::SliceRandom;
+    let ds = match export_view(cmdline, args) {
+        Some(ds) => ds,
+        None => return,
+    };
+    let substore = match args.value_of("substore") {
+        Some(name) => match StoreKind::from_string(name) {
+            Some(x) => Some(x),
+            None => {
+                println!("ERROR: Unknown substore {}", name);
+                return;
+            }
+        },
+        None => None,
+    };
+    let mut strata = Vec::new();
+    for spec in args.value_of("strata").unwrap_or("").split(',').filter(|x| ! x.is_empty()) {
+        match SampleCriterion::parse(spec) {
+            Some(x) => strata.push(x),
+            None => {
+                println!("ERROR: Invalid stratification criterion {}", spec);
+                return;
+            }
+        }
+    }
+    let n = args.value_of("n").unwrap().parse::<usize>().expect("Invalid sample size");
+    let seed = args.value_of("seed").map(|x| x.parse::<u64>().expect("Invalid seed")).unwrap_or(chrono::Utc::now().timestamp() as u64);
+    let equal = args.value_of("allocation") == Some("equal");
+    // determine the population, ordered by project ids
+    let mut project_substores = BTreeMap::new();
+    for (pid, s) in ds.project_substores() {
+        project_substores.insert(u64::from(pid), s);
+    }
+    let population : Vec<(u64, StoreKind)> = project_substores.into_iter().filter(|(_, s)| substore.map(|x| x == *s).unwrap_or(true)).collect();
+    // get the values of the criteria, only the latest metadata and summaries matter
+    let mut summaries = HashMap::<u64, ProjectSummary>::new();
+    if strata.iter().any(|x| x.uses_summary()) {
+        for (pid, summary) in ds.project_summaries() {
+            summaries.insert(u64::from(pid), summary);
+        }
+    }
+    let mut metadata = HashMap::<(u64, String), String>::new();
+    if strata.iter().any(|x| ! x.uses_summary()) {
+        for (pid, md) in ds.project_metadata() {
+            metadata.insert((u64::from(pid), md.key), md.value);
+        }
+    }
+    let mut groups = BTreeMap::<String, Vec<(u64, StoreKind, Vec<String>)>>::new();
+    for (pid, s) in population.iter() {
+        let github = metadata.get(& (*pid, Metadata::GITHUB_METADATA.to_owned())).and_then(|x| json::parse(x).ok());
+        let values : Vec<Option<String>> = strata.iter().map(|x| x.value(summaries.get(pid), github.as_ref(), |key| metadata.get(& (*pid, key.to_owned())))).collect();
+        let stratum = strata.iter().zip(values.iter()).map(|(c, v)| format!("{}={}", c.name, c.bin(v.as_ref()))).collect::<Vec<String>>().join("/");
+        groups.entry(stratum).or_insert(Vec::new()).push((*pid, *s, values.into_iter().map(|x| x.unwrap_or_default()).collect()));
+    }
+    let sizes : Vec<usize> = groups.values().map(|x| x.len()).collect();
+    let allocation = allocate_sample(& sizes, n, equal);
+    // sample the strata
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let urls = ds.project_urls();
+    let into = args.value_of("into").unwrap();
+    let mut f = File::create(into).unwrap();
+    let mut header = "pid,url,substore,stratum".to_owned();
+    for c in strata.iter() {
+        header.push_str(& format!(",{}", c.name));
+    }
+    writeln!(f, "{}", header).unwrap();
+    let mut manifest_strata = json::JsonValue::new_object();
+    let mut sampled = 0;
+    for ((stratum, mut projects), k) in groups.into_iter().zip(allocation.into_iter()) {
+        manifest_strata[stratum.as_str()] = json::object!{ "population" => projects.len(), "sampled" => k };
+        let (selected, _) = projects.partial_shuffle(& mut rng, k);
+        selected.sort_by_key(|(pid, _, _)| *pid);
+        for (pid, s, values) in selected.iter() {
+            let url = urls.get(ProjectId::from(*pid)).map(|x| x.clone_url()).unwrap_or_default();
+            let values : String = values.iter().map(|x| format!(",\"{}\"", x.replace("\"", "\"\""))).collect();
+            writeln!(f, "{},\"{}\",{:?},\"{}\"{}", pid, url, s, stratum, values).unwrap();
+        }
+        sampled += k;
+    }
+    let mut manifest = Manifest::new("mistletoe sample", & ds);
+    manifest.filter("substore", substore.map(|x| format!("{:?}", x)))
+        .filter("strata", args.value_of("strata").unwrap_or(""))
+        .filter("allocation", if equal { "equal" } else { "proportional" })
+        .filter("n", n)
+        .filter("seed", seed);
+    manifest.column("pid", "u64").column("url", "string").column("substore", "string").column("stratum", "string");
+    for c in strata.iter() {
+        manifest.column(& c.name, "string");
+    }
+    // the sizes of the strata are recorded together with the manifest so that the sample can be checked when drawn again
+    let mut manifest = manifest.to_json();
+    manifest["population"] = population.len().into();
+    manifest["sampled"] = sampled.into();
+    manifest["sizes"] = manifest_strata;
+    std::fs::write(format!("{}.manifest.json", into), manifest.pretty(4)).unwrap();
+    println!("sampled {} of {} projects in {} strata, seed {}", sampled, population.len(), sizes.len(), seed);
+}
+
+/** Returns the number of projects to be sampled from each stratum of given size. 
+ 
+    Proportional allocation uses the largest remainder method, equal allocation divides the sample evenly and gives what small strata cannot take to the larger ones. 
+ */
+fn allocate_sample(sizes : & [usize], n : usize, equal : bool) -> Vec<usize> {
+    let total : usize = sizes.iter().sum();
+    if n >= total {
+        return sizes.to_vec();
+    }
+    let mut result = vec![0; sizes.len()];
+    if equal {
+        let mut remaining = n;
+        loop {
+            let open : Vec<usize> = (0..sizes.len()).filter(|i| result[*i] < sizes[*i]).collect();
+            if remaining == 0 || open.is_empty() {
+                break;
+            }
+            let share = std::cmp::max(remaining / open.len(), 1);
+            for i in open {
+                let x = share.min(sizes[i] - result[i]).min(remaining);
+                result[i] += x;
+                remaining -= x;
+            }
+        }
+    } else {
+        let mut remainders = Vec::new();
+        for (i, size) in sizes.iter().enumerate() {
+            let quota = (*size as u128) * (n as u128);
+            result[i] = (quota / total as u128) as usize;
+            remainders.push((quota % total as u128, i));
+        }
+        // ties are broken by the order of the strata so that the allocation is deterministic
+        remainders.sort_by(|a, b| b.0.cmp(& a.0).then(a.1.cmp(& b.1)));
+        let missing = n - result.iter().sum::<usize>();
+        for (_, i) in remainders.into_iter().take(missing) {
+            result[i] += 1;
+        }
+    }
+    return result;
+}
+
+/** A stratification criterion of the `sample` command and the binning of its numeric values. 
+ */
+struct SampleCriterion {
+    name : String,
+    /** Width of the bins, or None for the logarithmic bins. Ignored for the language. 
+     */
+    width : Option<u64>,
+}
+
+impl SampleCriterion {
+    fn parse(spec : & str) -> Option<SampleCriterion> {
+        let (name, binning) = match spec.find(':') {
+            Some(i) => (& spec[..i], & spec[(i + 1)..]),
+            None => (spec, "log"),
+        };
+        if ! ["stars", "forks", "language", "commits", "authors", "committers", "age"].contains(& name) {
+            return None;
+        }
+        let width = if binning == "log" {
+            None
+        } else if binning.starts_with("lin:") {
+            Some(binning[4..].parse::<u64>().ok().filter(|x| *x > 0)?)
+        } else {
+            return None;
+        };
+        return Some(SampleCriterion{ name : name.to_owned(), width });
+    }
+
+    fn uses_summary(& self) -> bool {
+        return ["commits", "authors", "committers", "age"].contains(& self.name.as_str());
+    }
+
+    /** Returns the value of the criterion from the project's summary, GitHub metadata, or other metadata, if known. 
+     */
+    fn value<'a>(& self, summary : Option<& ProjectSummary>, github : Option<& json::JsonValue>, metadata : impl Fn(& str) -> Option<&'a String>) -> Option<String> {
+        match self.name.as_str() {
+            "stars" => return github.and_then(|x| x["stargazers_count"].as_u64()).map(|x| x.to_string()).or(metadata(Metadata::STARS).cloned()),
+            "forks" => return github.and_then(|x| x["forks_count"].as_u64()).map(|x| x.to_string()),
+            "language" => return github.and_then(|x| x["language"].as_str()).map(|x| x.to_owned()).or(metadata(Metadata::LANGUAGE).cloned()),
+            "commits" => return summary.map(|x| x.commits.to_string()),
+            "authors" => return summary.map(|x| x.authors.to_string()),
+            "committers" => return summary.map(|x| x.committers.to_string()),
+            "age" => return summary.map(|x| ((x.last_commit_time - x.first_commit_time).max(0) / (24 * 3600)).to_string()),
+            _ => unreachable!(),
+        }
+    }
+
+    /** Returns the bin of given value. 
+     */
+    fn bin(& self, value : Option<& String>) -> String {
+        let value = match value {
+            Some(x) if ! x.is_empty() => x,
+            _ => return "NA".to_owned(),
+        };
+        if self.name == "language" {
+            return value.to_owned();
+        }
+        let x = match value.parse::<u64>() {
+            Ok(x) => x,
+            Err(_) => return "NA".to_owned(),
+        };
+        match self.width {
+            Some(w) => return format!("{}-{}", x / w * w, x / w * w + w - 1),
+            None => {
+                if x == 0 {
+                    return "0".to_owned();
+                }
+                let mut low = 1;
+                while low <= x / 10 {
+                    low *= 10;
+                }
+                return format!("{}-{}", low, low * 10 - 1);
             }
         }
     }
-    println!("total,errors");
-    println!("{},{}", max_id, errors.len());
 }
 
 fn check_heads(cmdline : & clap::ArgMatches, _args : & clap::ArgMatches) {
@@ -164,40 +2062,46 @@ fn check_heads(cmdline : & clap::ArgMatches, _args : & clap::ArgMatches) {
 
     Then all commits of the project are printed, for each commit the parents, commit & author info, message and changes are printed. Each change shows the path and hash. 
 
-    Change paths and commit hashes are displayed as terminal links, where supported. 
+    Change paths and commit hashes are displayed as terminal links, where supported. With `--format json` the same information is printed as a single json object instead, see `project_json`.
  */
 fn show_project(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
     // create the datastore and savepoint
     let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
     let project = get_project_id(& ds, args);
     if let Some(pid) = project {
+        if args.value_of("format") == Some("json") {
+            println!("{}", project_json(cmdline, & ds, pid, args).pretty(4));
+            return;
+        }
         // get the project
         let purl = get_project_url(& ds, pid);
         println!("Project id: {}, url: {}", pid, purl.clone_url());
         // now get all log entries and filter those of our project
-        let log : Vec<ProjectLog> = ds.project_updates().filter(|(log_id, _)| pid == *log_id ).map(|(_, p)| p).collect();
+        let log = ds.project_log_history(pid);
         println!("log: {} entries", log.len());
         for l in log {
             println!("    {}", l);
         }
         // show the metadata
-        if let Some(md) = ds.project_metadata().filter(|(id, _)| *id == pid).map(|(_, s)| s).last() {
+        if let Some(md) = ds.project_metadata_of(pid).pop() {
             println!("Metadata: {}", md.value);
         }
         // determine the project's substore
-        let substore = ds.project_substores().filter(|(id, _)| *id == pid).map(|(_, s)| s).last().unwrap();
+        let substore = ds.project_substore_of(pid).unwrap();
         println!("substore: {:?}", substore);
         // if they exist, print heads and then the rest of the commits and their changes
-        if let Some((_, heads)) =  ds.project_heads().filter(|(id, _)| *id == pid).last() {
+        if let Some(heads) = ds.project_heads_of(pid) {
             println!("heads: {} entries", heads.len());
             for (name, (id, hash)) in heads.iter() {
                 println!("    {}: {} (id {})", name, purl.get_commit_terminal_link(*hash), id);
             }
-            let mut commit_hashes = ds.commits(substore);
-            let mut users = ds.users(substore);
-            let mut paths = ds.paths_strings(substore);
-            let mut hashes = ds.hashes(substore);
-            for (commit_id, commit) in ProjectCommitsIterator::new(& heads, ds.commits_info(substore)) {
+            let commit_hashes = cached(cmdline, ds.commits(substore));
+            let users = cached(cmdline, ds.users(substore));
+            let paths = cached(cmdline, ds.paths_strings(substore));
+            let hashes = cached(cmdline, ds.hashes(substore));
+            let aliases = get_user_aliases(& ds, substore, args);
+            for (commit_id, mut commit) in ProjectCommitsIterator::new(& heads, ds.commits_info(substore)) {
+                canonicalize_users(& mut commit, & aliases);
                 let commit_hash = commit_hashes.get(commit_id).unwrap();
                 println!("    {} (id {})", purl.get_commit_terminal_link(commit_hash), commit_id);
                 println!("        committer: {} (id {}), time {}", users.get(commit.committer).unwrap(), commit.committer, pretty_timestamp(commit.committer_time));
@@ -217,11 +2121,37 @@ fn show_project(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
     
             }
         }       
+    } else if args.value_of("format") == Some("json") {
+        eprintln!("ERROR: No matching project found");
     } else {
         println!("ERROR: No matching project found");
     }
 }
 
+/** Returns the project information shown by `show-project` as json. 
+
+    The project is serialized by `json_output::project_json`, the same as by the `query` command of parasite, and the commits reachable from its heads are added as the `commits` array, see `json_output::CommitJson` for their format. 
+ */
+fn project_json(cmdline : & clap::ArgMatches, ds : & DatastoreView, pid : ProjectId, args : & clap::ArgMatches) -> json::JsonValue {
+    let mut result = json_output::project_json(ds, pid).unwrap();
+    result["commits"] = json::JsonValue::new_array();
+    if let (Some(substore), Some(heads)) = (ds.project_substore_of(pid), ds.project_heads_of(pid)) {
+        let commits = commit_json(cmdline, ds, substore);
+        let aliases = get_user_aliases(ds, substore, args);
+        for (commit_id, mut commit) in ProjectCommitsIterator::new(& heads, ds.commits_info(substore)) {
+            canonicalize_users(& mut commit, & aliases);
+            result["commits"].push(commits.get(commit_id, & commit)).unwrap();
+        }
+    }
+    return result;
+}
+
+/** Returns the json serializer of commits of given substore with caches of the size given by the commandline. 
+ */
+fn commit_json(cmdline : & clap::ArgMatches, ds : & DatastoreView, substore : StoreKind) -> json_output::CommitJson {
+    let (entries, bytes) = cache_size(cmdline);
+    return json_output::CommitJson::new(ds, substore, entries, bytes);
+}
 
 /** Exports all contents of a given project at given commit. 
  
@@ -239,33 +2169,127 @@ fn export_project(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
         o_dir = String::new();
         o_file = OpenOptions::new().write(true).create(true).open(args.value_of("into").unwrap_or("export-project.csv")).unwrap();        
     }
+    let mut filter = match ExportFilter::new(args) {
+        Some(x) => x,
+        None => return,
+    };
+    let mut commits = ExportedCommits::new(cmdline);
     writeln!(o_file, "pid,path,hash_id").unwrap();
     if let Some(projects) = args.value_of("projects") {
         println!("Exporting projects from {}", projects);
         // read the csv 
-        let col_id = args.value_of("column").unwrap_or("0").parse::<usize>().unwrap();
-        let mut reader = csv::ReaderBuilder::new()
-            .has_headers(true)
-            .double_quote(false)
-            .escape(Some(b'\\'))
-            .from_path(projects).unwrap();
-        for x in reader.records() {
-            let record = x.unwrap();
-            let pid = ProjectId::from(record[col_id].parse::<u64>().unwrap());
+        let mut reader = csv_options(args).from_path(projects).unwrap();
+        let headers = reader.headers().unwrap().clone();
+        // determine the column and whether it contains urls or ids
+        let col_id;
+        let mut urls = None;
+        if let Some(column) = args.value_of("url-column") {
+            col_id = find_column(& headers, column);
+            println!("Loading project urls...");
+            let mut x = HashMap::new();
+            for (id, url) in ds.project_urls() {
+                x.insert(url, id);
+            }
+            urls = Some(x);
+        } else if let Some(name) = args.value_of("column-name") {
+            col_id = find_column_by_name(& headers, name);
+        } else {
+            col_id = args.value_of("column").unwrap_or("0").parse::<usize>().unwrap();
+        }
+        for record in csv_input::TolerantRecords::new(& mut reader, |e| eprintln!("WARNING: skipping malformed record at {}", e)) {
+            let line = record.position().map(|x| x.line()).unwrap_or(0);
+            let value = match record.get(col_id) {
+                Some(x) => x.trim(),
+                None => {
+                    eprintln!("WARNING: skipping record at line {}: missing column {}", line, col_id);
+                    continue;
+                }
+            };
+            let pid = match & urls {
+                Some(urls) => ProjectUrl::from_url(value).and_then(|x| urls.get(& x).copied()),
+                None => value.parse::<u64>().ok().map(|x| ProjectId::from(x)),
+            };
+            if let Some(pid) = pid {
+                println!("{}", pid);
+                if ! export_project_if_accepted(& ds, & mut filter, & mut commits, pid, & mut o_file, & o_dir) {
+                    return;
+                }
+            } else {
+                eprintln!("WARNING: skipping record at line {}: unknown project {}", line, value);
+            }
+        }
+        filter.report();
+        return;
+    } else if let Some(label) = args.value_of("label") {
+        println!("Exporting projects with label {}", label);
+        for pid in ds.projects_with_label(label) {
             println!("{}", pid);
-            export_single_project(&ds, pid, & mut o_file, & o_dir);
+            if ! export_project_if_accepted(& ds, & mut filter, & mut commits, pid, & mut o_file, & o_dir) {
+                return;
+            }
         }
+        filter.report();
         return;
     } else {
         let project = get_project_id(& ds, args);
         if let Some(pid) = project {
-            export_single_project(& ds, pid, & mut o_file, & o_dir);
+            export_project_if_accepted(& ds, & mut filter, & mut commits, pid, & mut o_file, & o_dir);
             return;
         } 
     }
     println!("ERROR: No matching project found");
 }
 
+/** Exports the project unless the filter script of the export rejects it. 
+ 
+    Returns false if the filter script failed, the error is reported. 
+ */
+fn export_project_if_accepted(ds : & DatastoreView, filter : & mut ExportFilter, commits : & mut ExportedCommits, pid : ProjectId, output : & mut File, out_dir : & String) -> bool {
+    match filter.accepts_project(ds, pid) {
+        Ok(true) => export_single_project(ds, commits, pid, output, out_dir),
+        Ok(false) => {},
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return false;
+        }
+    }
+    return true;
+}
+
+/** Returns the index of the column with given name in the csv header. 
+ */
+fn find_column_by_name(headers : & csv::StringRecord, name : & str) -> usize {
+    match headers.iter().position(|x| x.trim() == name) {
+        Some(i) => return i,
+        None => panic!("Column {} not found in the header ({})", name, headers.iter().collect::<Vec<& str>>().join(", ")),
+    }
+}
+
+/** Returns the index of the column given either by its index, or by its name in the csv header. 
+ */
+fn find_column(headers : & csv::StringRecord, column : & str) -> usize {
+    match column.parse::<usize>() {
+        Ok(i) => return i,
+        Err(_) => return find_column_by_name(headers, column),
+    }
+}
+
+/** Returns the options of the projects csv file given by the arguments. 
+ */
+fn csv_options(args : & clap::ArgMatches) -> csv_input::CsvOptions {
+    let mut options = csv_input::CsvOptions::new();
+    if let Some(x) = args.value_of("delimiter") {
+        options.delimiter = csv_input::CsvOptions::parse_char(x).expect("Invalid delimiter");
+    }
+    if let Some(x) = args.value_of("quote") {
+        options.quote = csv_input::CsvOptions::parse_char(x).expect("Invalid quote");
+    }
+    if let Some(x) = args.value_of("escape") {
+        options.escape = Some(csv_input::CsvOptions::parse_char(x).expect("Invalid escape"));
+    }
+    return options;
+}
+
 /** Trivial pretty printer for unix epoch */
 fn pretty_timestamp(ts : i64) -> String {
     let d = UNIX_EPOCH + Duration::from_secs(ts as u64);
@@ -274,6 +2298,141 @@ fn pretty_timestamp(ts : i64) -> String {
 }
 
 
+/** Wraps given table reader in a cache of the size given by the commandline. 
+ */
+fn cached<R : table_readers::RandomAccess>(cmdline : & clap::ArgMatches, reader : R) -> CachedReader<R> {
+    let (entries, bytes) = cache_size(cmdline);
+    return CachedReader::new(reader, entries, bytes);
+}
+
+/** Returns the maximum number of entries and bytes of the caches given by the commandline. 
+ */
+fn cache_size(cmdline : & clap::ArgMatches) -> (usize, usize) {
+    let entries = cmdline.value_of("cache-entries").unwrap().parse::<usize>().expect("Invalid number of cache entries");
+    let bytes = cmdline.value_of("cache-bytes").unwrap().parse::<usize>().expect("Invalid cache size");
+    return (entries, bytes);
+}
+
+/** Returns the view of the datastore an export reads, pinned to the savepoint given to the command, if any. 
+ 
+    Prints an error and returns None if the savepoint does not exist. 
+ */
+fn export_view(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) -> Option<DatastoreView> {
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
+    match args.value_of("savepoint") {
+        Some(name) => {
+            let result = ds.at_savepoint(name);
+            if result.is_none() {
+                println!("ERROR: Unknown savepoint {}", name);
+            }
+            return result;
+        },
+        None => return Some(ds),
+    }
+}
+
+/** Writes the manifest next to the exported file if requested by the command. 
+ */
+fn write_manifest(args : & clap::ArgMatches, manifest : & Manifest) {
+    if args.is_present("manifest") {
+        manifest.write(& format!("{}.manifest.json", args.value_of("into").unwrap())).unwrap();
+    }
+}
+
+/** Filter script given to an export together with the records it is asked about. 
+ 
+    Without a script all projects and commits are accepted. The records are only created when first needed, since creating the project records reads all project tables. 
+ */
+struct ExportFilter {
+    script : Option<script_filter::ScriptFilter>,
+    projects : Option<script_filter::ProjectRecords>,
+    commits : HashMap<StoreKind, script_filter::CommitRecords>,
+}
+
+impl ExportFilter {
+
+    /** Starts the filter script given to the command, if any. 
+ 
+        Prints an error and returns None if the script could not be started. 
+     */
+    fn new(args : & clap::ArgMatches) -> Option<ExportFilter> {
+        let mut result = ExportFilter{ script : None, projects : None, commits : HashMap::new() };
+        if let Some(command) = args.value_of("filter") {
+            match script_filter::ScriptFilter::new(command) {
+                Ok(script) => result.script = Some(script),
+                Err(e) => {
+                    println!("ERROR: Unable to start filter script {}: {}", command, e);
+                    return None;
+                }
+            }
+        }
+        return Some(result);
+    }
+
+    fn is_active(& self) -> bool {
+        return self.script.is_some();
+    }
+
+    /** Records the filter script in the manifest of the export. 
+     */
+    fn describe(& self, manifest : & mut Manifest) {
+        if let Some(script) = & self.script {
+            manifest.filter("script", script.command());
+        }
+    }
+
+    fn accepts_project(& mut self, ds : & DatastoreView, id : ProjectId) -> Result<bool, std::io::Error> {
+        if let Some(script) = self.script.as_mut() {
+            let records = self.projects.get_or_insert_with(|| script_filter::ProjectRecords::new(ds));
+            return match records.get(id) {
+                Some(record) => script.accepts(& record),
+                None => Ok(false),
+            };
+        }
+        return Ok(true);
+    }
+
+    fn accepts_commit(& mut self, ds : & DatastoreView, substore : StoreKind, id : CommitId, commit : & CommitInfo) -> Result<bool, std::io::Error> {
+        if let Some(script) = self.script.as_mut() {
+            let records = self.commits.entry(substore).or_insert_with(|| script_filter::CommitRecords::new(ds, substore));
+            return script.accepts(& records.get(id, commit));
+        }
+        return Ok(true);
+    }
+
+    /** Prints how many records the filter script accepted and rejected. 
+     */
+    fn report(& self) {
+        if let Some(script) = & self.script {
+            eprintln!("filter script accepted {} and rejected {} records", script.accepted, script.rejected);
+        }
+    }
+}
+
+/* Checks that the datastore still contains the exact data the export of given manifest was created from. 
+ 
+   The datastore given on the command line is checked, or the datastore the manifest was created from if none is given. 
+ */
+fn verify_manifest(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
+    let manifest = match Manifest::read(args.value_of("manifest").unwrap()) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return;
+        }
+    };
+    let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or(& manifest.datastore));
+    let mismatches = manifest.verify(& ds);
+    if mismatches.is_empty() {
+        println!("OK: datastore matches the manifest of {} created at {}", manifest.command, manifest.time);
+    } else {
+        for x in mismatches.iter() {
+            println!("MISMATCH: {}", x);
+        }
+        std::process::exit(1);
+    }
+}
+
 fn get_project_id(ds : & DatastoreView, args : & clap::ArgMatches) -> Option<ProjectId> {
     if let Some(id) = args.value_of("id") {
         return Some(ProjectId::from(id.parse::<u64>().unwrap()));
@@ -285,11 +2444,32 @@ fn get_project_id(ds : & DatastoreView, args : & clap::ArgMatches) -> Option<Pro
     return None;
 }
 
+/** Returns the user aliases of the substore if the canonical users were requested, or an empty map otherwise. 
+ */
+fn get_user_aliases(ds : & DatastoreView, substore : StoreKind, args : & clap::ArgMatches) -> HashMap<UserId, UserId> {
+    if args.is_present("canonical-users") {
+        return ds.user_aliases(substore);
+    } else {
+        return HashMap::new();
+    }
+}
+
+/** Replaces the committer and author of the commit with their canonical users. 
+ */
+fn canonicalize_users(commit : & mut CommitInfo, aliases : & HashMap<UserId, UserId>) {
+    commit.committer = *aliases.get(& commit.committer).unwrap_or(& commit.committer);
+    commit.author = *aliases.get(& commit.author).unwrap_or(& commit.author);
+}
+
 fn get_project_url(ds : & DatastoreView, id : ProjectId) -> ProjectUrl {
     return ds.project_urls().get(id).unwrap();
 }
 
 fn get_project_main_branch(ds : & DatastoreView, pid : ProjectId) -> Option<String> {
+    if let Some(name) = ds.project_default_branch(pid) {
+        return Some(name);
+    }
+    // projects not updated since default branches are stored only have them in the metadata
     // since we do may not have an index available, just scan linearly
     if let Some(metadata) = ds.project_metadata().filter(|(id, metadata)| {
         return *id == pid && metadata.key == Metadata::GITHUB_METADATA;
@@ -306,16 +2486,42 @@ fn get_project_main_branch(ds : & DatastoreView, pid : ProjectId) -> Option<Stri
     return None;
 }
 
-fn export_single_project(ds : & DatastoreView, pid : ProjectId, output : & mut File, out_dir : & String) {
+/** Commits read by a single `export-project` command. 
+ 
+    Forks and mirrors exported together share most of their history, so the commits of each substore are read through a cache (see `cached`) kept for the whole export. 
+ */
+struct ExportedCommits<'a> {
+    cmdline : & 'a clap::ArgMatches<'a>,
+    readers : HashMap<StoreKind, CachedReader<table_readers::StoreReader<CommitInfo, CommitId>>>,
+}
+
+impl<'a> ExportedCommits<'a> {
+    fn new(cmdline : & 'a clap::ArgMatches<'a>) -> ExportedCommits<'a> {
+        return ExportedCommits{ cmdline, readers : HashMap::new() };
+    }
+
+    fn reader(& mut self, ds : & DatastoreView, substore : StoreKind) -> & CachedReader<table_readers::StoreReader<CommitInfo, CommitId>> {
+        let cmdline = self.cmdline;
+        return self.readers.entry(substore).or_insert_with(|| cached(cmdline, ds.commits_info(substore)));
+    }
+}
+
+fn export_single_project(ds : & DatastoreView, commits : & mut ExportedCommits, pid : ProjectId, output : & mut File, out_dir : & String) {
     // get the project
     // determine the project's substore
-    let substore = ds.project_substores().filter(|(id, _)| *id == pid).map(|(_, s)| s).last().unwrap();
+    let substore = ds.project_substore_of(pid).unwrap();
     // let latest metadata and determine main branch
-    let main_branch = format!("refs/heads/{}", get_project_main_branch(& ds, pid).unwrap_or("master".to_owned()));
+    let main_branch = match get_project_main_branch(& ds, pid) {
+        Some(name) => format!("refs/heads/{}", name),
+        None => {
+            println!("main branch of project {} not known, skipping", pid);
+            return;
+        }
+    };
     println!("main branch: {}", main_branch);
     // now get the head commit
     let mut commit : Option<CommitId> = None;
-    if let Some((_, heads)) = ds.project_heads().filter(|(id, _)| *id == pid).last() {
+    if let Some(heads) = ds.project_heads_of(pid) {
         for (name, (id, _hash)) in heads.iter() {
             if main_branch.eq(name) {
                 commit = Some(*id);
@@ -325,18 +2531,18 @@ fn export_single_project(ds : & DatastoreView, pid : ProjectId, output : & mut F
     }
     // we have the commit to checkout, perform the checkout
     if let Some(id) = commit {
-        let changes = checkout_commit(& ds, id, substore);
-        let mut contents = ds.contents(substore);
+        let changes = checkout_commit(& ds, commits.reader(ds, substore), id, substore);
+        let contents = ds.contents(substore);
         for (path, hash) in changes {
             writeln!(output, "{},\"{}\",{}", pid, path, hash).unwrap();
             // if given the output directory, we should also check if we have the contents and if so, store them appropriately
             if ! out_dir.is_empty() {
-                if let Some(bytes) = contents.get(hash) {
+                if let Some((_, mut reader)) = contents.stream(hash) {
                     let pstr = format!("{}/{}/{}", out_dir, pid, path);
                     let p = Path::new(pstr.as_str());
                     std::fs::create_dir_all(p.parent().unwrap()).unwrap();
                     let mut f = File::create(p).unwrap();
-                    f.write_all(& bytes.1).unwrap();
+                    std::io::copy(& mut reader, & mut f).unwrap();
                 }
             }
         }
@@ -345,12 +2551,11 @@ fn export_single_project(ds : & DatastoreView, pid : ProjectId, output : & mut F
 
 /** Iterate over all parent commits and determine the tree state. 
  */
-fn checkout_commit(ds : & DatastoreView, commit : CommitId, substore : StoreKind) -> HashMap<String, HashId> {
+fn checkout_commit(ds : & DatastoreView, commits : & CachedReader<table_readers::StoreReader<CommitInfo, CommitId>>, commit : CommitId, substore : StoreKind) -> HashMap<String, HashId> {
     let mut tree = HashMap::<PathId,HashId>::new();
     let mut q = Vec::<CommitId>::new();
     let mut visited = HashSet::<CommitId>::new();
     q.push(commit);
-    let mut commits = ds.commits_info(substore);
     while let Some(commit_id) = q.pop() {
         // ignore if already visited
         if visited.contains(& commit_id) {
@@ -370,29 +2575,41 @@ fn checkout_commit(ds : & DatastoreView, commit : CommitId, substore : StoreKind
             });
         }
     }
-    // now convert the tree to a hashmap with real paths, ignoring deleted files
-    let mut path_strings = ds.paths_strings(substore);
-    return tree.into_iter()
+    // now convert the tree to a hashmap with real paths, ignoring deleted files, the paths are read at once in the order they are stored
+    let (path_ids, hash_ids) : (Vec<PathId>, Vec<HashId>) = tree.into_iter()
         .filter(|(_path_id, hash_id)| HashId::DELETED != *hash_id)
-        .map(|(path_id, hash_id)| (path_strings.get(path_id).unwrap(), hash_id))
+        .unzip();
+    return ds.paths_strings(substore).get_many(& path_ids).into_iter()
+        .map(|x| x.unwrap())
+        .zip(hash_ids.into_iter())
         .collect();
 }
 
-/** Shows the commits */
+/** Shows the commits 
+
+    With `--format json` a single commit is printed as json object (see `json_output::CommitJson`) and the list of all commits as an array of their ids and hashes. 
+ */
 fn show_commits(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
     // create the datastore and savepoint
     let ds = DatastoreView::from(cmdline.value_of("datastore").unwrap_or("."));
     let substore = StoreKind::JavaScript;
+    let as_json = args.value_of("format") == Some("json");
     if args.is_present("id") {
         let id = CommitId::from(args.value_of("id").unwrap().parse::<u64>().unwrap());
         match ds.commits(substore).get(id) {
+            Some(_) if as_json => {
+                let mut commit = ds.commits_info(substore).get(id).unwrap();
+                canonicalize_users(& mut commit, & get_user_aliases(& ds, substore, args));
+                println!("{}", commit_json(cmdline, & ds, substore).get(id, & commit).pretty(4));
+            },
             Some(hash) => {
                 println!("Commit id: {}, hash {}", id, hash);
-                let mut commit_hashes = ds.commits(substore);
-                let mut users = ds.users(substore);
-                let mut paths = ds.paths_strings(substore);
-                let mut hashes = ds.hashes(substore);
-                let commit = ds.commits_info(substore).get(id).unwrap();
+                let commit_hashes = ds.commits(substore);
+                let users = ds.users(substore);
+                let paths = ds.paths_strings(substore);
+                let hashes = ds.hashes(substore);
+                let mut commit = ds.commits_info(substore).get(id).unwrap();
+                canonicalize_users(& mut commit, & get_user_aliases(& ds, substore, args));
                 println!("        committer: {} (id {}), time {}", users.get(commit.committer).unwrap(), commit.committer, pretty_timestamp(commit.committer_time));
                 println!("        author: {} (id {}), time {}", users.get(commit.author).unwrap(), commit.author, pretty_timestamp(commit.author_time));
                 print!("        parents:");
@@ -407,8 +2624,15 @@ fn show_commits(cmdline : & clap::ArgMatches, args : & clap::ArgMatches) {
                     println!("            {} : {} (id {} : id {})", & paths.get(path_id).unwrap(), hash, path_id, hash_id);
                 }
             },
+            None if as_json => eprintln!("Commit {} not found", id),
             None => println!("Commit {} not found", id),
         }
+    } else if as_json {
+        let mut result = json::JsonValue::new_array();
+        for (commit_id, hash) in ds.commits(StoreKind::JavaScript) {
+            result.push(json::object!{ "id" => u64::from(commit_id), "hash" => hash.to_string() }).unwrap();
+        }
+        println!("{}", result.pretty(4));
     } else {
         for (commit_id, hash) in ds.commits(StoreKind::JavaScript) {
             println!("Commit id: {}, hash {}", commit_id, hash);
@@ -1,4 +1,3 @@
-use std::fs::{File};
 use std::io::{Read, Write};
 use std::collections::*;
 use byteorder::*;
@@ -12,6 +11,7 @@ use crate::helpers;
 use std::fmt::Display;
 
 #[derive(std::fmt::Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash, std::marker::Copy, std::clone::Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct ProjectId {
     id : u64,
 }
@@ -39,6 +39,7 @@ impl std::fmt::Display for ProjectId {
 }
 
 #[derive(std::fmt::Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash, std::marker::Copy, std::clone::Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct CommitId {
     id : u64,
 }
@@ -70,7 +71,23 @@ impl std::fmt::Display for CommitId {
     }
 }
 
+impl Serializable for CommitId {
+    type Item = CommitId;
+    fn serialize(f : & mut dyn TableStorage, value : & CommitId) {
+        u64::serialize(f, & value.id);
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> CommitId {
+        return CommitId::from(u64::deserialize(f));
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<CommitId, std::io::Error> {
+        return Ok(CommitId::from(u64::verify(f)?));
+    }
+}
+
 #[derive(std::fmt::Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash, std::marker::Copy, std::clone::Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct HashId {
     id : u64,
 }
@@ -103,6 +120,7 @@ impl std::fmt::Display for HashId {
 
 
 #[derive(std::fmt::Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash, std::marker::Copy, std::clone::Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct PathId {
     id : u64,
 }
@@ -134,6 +152,7 @@ impl std::fmt::Display for PathId {
 }
 
 #[derive(std::fmt::Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash, std::marker::Copy, std::clone::Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct UserId {
     id : u64,
 }
@@ -164,6 +183,101 @@ impl std::fmt::Display for UserId {
     }
 }
 
+impl Serializable for UserId {
+    type Item = UserId;
+    fn serialize(f : & mut dyn TableStorage, value : & UserId) {
+        u64::serialize(f, & value.id);
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> UserId {
+        return UserId::from(u64::deserialize(f));
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<UserId, std::io::Error> {
+        return Ok(UserId::from(u64::verify(f)?));
+    }
+}
+
+/** Id of a word in the commit messages index. 
+ */
+#[derive(std::fmt::Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash, std::marker::Copy, std::clone::Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct WordId {
+    id : u64,
+}
+
+impl WordId {
+    /** The sentinel empty word, whose postings hold the indexing progress. 
+     */
+    pub const PROGRESS : WordId = WordId{id : 0};
+}
+
+impl std::convert::From<u64> for WordId {
+    fn from(id : u64) -> WordId {
+        return WordId{id};
+    }
+}
+
+impl std::convert::From<WordId> for u64 {
+    fn from(value : WordId) -> u64 {
+        return value.id;
+    }
+}
+
+impl Id for WordId {
+    const NONE : WordId = WordId{ id : std::u64::MAX };
+}
+
+impl std::fmt::Display for WordId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(f, "{}", self.id);
+    }
+}
+
+/** Id of a tree stored in a substore. 
+ */
+#[derive(std::fmt::Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash, std::marker::Copy, std::clone::Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct TreeId {
+    id : u64,
+}
+
+impl std::convert::From<u64> for TreeId {
+    fn from(id : u64) -> TreeId {
+        return TreeId{id};
+    }
+}
+
+impl std::convert::From<TreeId> for u64 {
+    fn from(value : TreeId) -> u64 {
+        return value.id;
+    }
+}
+
+impl Id for TreeId {
+    const NONE : TreeId = TreeId{ id : std::u64::MAX };
+}
+
+impl std::fmt::Display for TreeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(f, "{}", self.id);
+    }
+}
+
+impl Serializable for TreeId {
+    type Item = TreeId;
+    fn serialize(f : & mut dyn TableStorage, value : & TreeId) {
+        u64::serialize(f, & value.id);
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> TreeId {
+        return TreeId::from(u64::deserialize(f));
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<TreeId, std::io::Error> {
+        return Ok(TreeId::from(u64::verify(f)?));
+    }
+}
 
 /** Datastore kinds. 
  
@@ -171,6 +285,7 @@ impl std::fmt::Display for UserId {
  */
 #[repr(u16)]
 #[derive(Clone, Copy, Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StoreKind {
     Generic,
     SmallProjects,
@@ -307,15 +422,15 @@ impl SplitKind for StoreKind {
 
 impl Serializable for StoreKind {
     type Item = StoreKind;
-    fn serialize(f : & mut File, value : & StoreKind) {
+    fn serialize(f : & mut dyn TableStorage, value : & StoreKind) {
         f.write_u16::<LittleEndian>(value.to_number() as u16).unwrap();
     }
 
-    fn deserialize(f : & mut File) -> StoreKind {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> StoreKind {
         return StoreKind::from_number(f.read_u16::<LittleEndian>().unwrap() as u64);
     }
 
-    fn verify(f : & mut File) -> Result<StoreKind, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<StoreKind, std::io::Error> {
         let index = u16::verify(f)? as u64;
         if index >= Self::COUNT {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid store kind index"));
@@ -338,6 +453,7 @@ impl FixedSizeSerializable for StoreKind {
     ProjectKind::Github : the id is the username and repo name.
  */
 #[derive(Clone,Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ProjectUrl{
     Git{url : String},
     GitHub{user_and_repo : String},
@@ -367,6 +483,19 @@ impl ProjectUrl {
         }
     }
 
+    /** Returns the host the project is cloned from. 
+     */
+    pub fn host(& self) -> String {
+        match self {
+            ProjectUrl::Git{url} => {
+                return url.split('/').next().unwrap_or("").to_lowercase();
+            },
+            ProjectUrl::GitHub{user_and_repo : _} => {
+                return "github.com".to_owned();
+            }
+        }
+    }
+
     pub fn from_url(url : & str) -> Option<ProjectUrl> {
         if url.starts_with("https://github.com/") {
             if url.ends_with(".git") {
@@ -448,7 +577,7 @@ impl ProjectUrl {
 
 impl Serializable for ProjectUrl {
     type Item = ProjectUrl;
-    fn serialize(f : & mut File, value : & ProjectUrl) {
+    fn serialize(f : & mut dyn TableStorage, value : & ProjectUrl) {
         match value {
             ProjectUrl::Git{url} => {
                 u8::serialize(f, & 0);
@@ -461,7 +590,7 @@ impl Serializable for ProjectUrl {
         }
     }
 
-    fn deserialize(f : & mut File) -> ProjectUrl {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> ProjectUrl {
         match u8::deserialize(f) {
             0 => {
                 let url = String::deserialize(f);
@@ -475,7 +604,7 @@ impl Serializable for ProjectUrl {
         }
     }
 
-    fn verify(f : & mut File) -> Result<ProjectUrl, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<ProjectUrl, std::io::Error> {
         match u8::verify(f)? {
             0 => {
                 let url = String::verify(f)?;
@@ -498,67 +627,163 @@ impl Serializable for ProjectUrl {
 
     # Ok
 
+    Issued when the update changed the project. Updates that changed the heads of the project record what changed (see `UpdateChanges`), so that the growth of the project between updates is known without comparing its heads. 
+
     # Rename
 
     Issued when project url change is detected by the updater. Although project kind change is not expected during the rename, it may change as well. The `old_offset` argument is the old offset in the projects table that contains the old identification of the project.  
 
     # Tombstone
 
+    # Retry
+
+    Issued when the update failed with an error that is likely transient (network failure, server error, etc.). The project will be retried later with exponential backoff based on the `attempt` number. When too many attempts fail, the error is recorded as permanent. 
+
+    # Skipped
+
+    Issued when the update of the project was aborted because the project exceeded one of the clone limits (time, number of objects, or size) given in the settings. The `reason` describes the exceeded limit. Like errored projects, skipped projects are not updated again unless errors are explicitly updated. 
+
+    # Merged
+
+    Issued when the project has been merged from another datastore. The `source` is the root of the source datastore and `old_id` the id of the project there. The merged project's latest update record from the source datastore follows. 
+
+    # Deleted
+
+    Issued when the repository of the project no longer exists, i.e. GitHub reports its metadata as not found or gone (404 / 410), or cloning it fails with these HTTP statuses. The `reason` is the reported error. All data of the project are kept, but deleted projects are no longer scheduled for updates. 
+
+    # Denied
+
+    Issued when the update of the project was suppressed because the project is on the denylist of the datastore. The `reason` is the matching denylist entry. The record is only added once for as long as the project stays denied, when it is removed from the denylist, the project is updated as usual. 
+
     # Error
+
+    Issued when the update failed with a permanent error, or when a transient error failed too many times. The `kind` classifies the error (see `UpdateError`) so that the errors can be aggregated, the `error` is the reported error itself. Errors recorded before the kinds were introduced have their kind guessed from the error message. Like skipped projects, errored projects are not updated again unless errors are explicitly updated. 
  */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ProjectLog {
     NoChange{time : i64, version : u16}, // 0
-    Ok{time : i64, version : u16},  // 1
+    Ok{time : i64, version : u16, changes : Option<UpdateChanges>},  // 1 (11 with the changes)
     /** Project url changes. Although project kind change is not expected when issuing project renames, it is technically possible. 
      */
     Rename{time : i64, version : u16, old_offset : u64}, // 2
     ChangeStore{time : i64, version : u16, new_kind : StoreKind }, // 3
-    Error{time : i64, version : u16, error : String }, // 255
+    Retry{time : i64, version : u16, error : String, attempt : u16 }, // 4
+    Skipped{time : i64, version : u16, reason : String }, // 5
+    Merged{time : i64, version : u16, source : String, old_id : u64 }, // 6
+    /** The path filters that determine which contents of the project are stored, recorded whenever they change. 
+     */
+    PathFilters{time : i64, version : u16, filters : String }, // 7
+    Deleted{time : i64, version : u16, reason : String }, // 8
+    Denied{time : i64, version : u16, reason : String }, // 9
+    Error{time : i64, version : u16, kind : UpdateError, error : String }, // 10 (255 without the kind)
 }
 
 impl ProjectLog {
     pub fn version(& self) -> u16 {
         match self {
             ProjectLog::NoChange{time : _, version } => return *version,
-            ProjectLog::Ok{time : _, version} => return *version,
+            ProjectLog::Ok{time : _, version, changes : _} => return *version,
             ProjectLog::Rename{time : _, version, old_offset: _} => return *version,
             ProjectLog::ChangeStore{time : _, version, new_kind : _ } => return *version,
-            ProjectLog::Error{time : _, version, error: _ } => return *version,
+            ProjectLog::Retry{time : _, version, error : _, attempt : _ } => return *version,
+            ProjectLog::Skipped{time : _, version, reason : _ } => return *version,
+            ProjectLog::Merged{time : _, version, source : _, old_id : _ } => return *version,
+            ProjectLog::PathFilters{time : _, version, filters : _ } => return *version,
+            ProjectLog::Deleted{time : _, version, reason : _ } => return *version,
+            ProjectLog::Denied{time : _, version, reason : _ } => return *version,
+            ProjectLog::Error{time : _, version, kind : _, error: _ } => return *version,
         }
     }
 
     pub fn time(& self) -> i64 {
         match self {
             ProjectLog::NoChange{time, version: _ } => return *time,
-            ProjectLog::Ok{time, version : _} => return *time,
+            ProjectLog::Ok{time, version : _, changes : _} => return *time,
             ProjectLog::Rename{time, version : _, old_offset: _} => return *time,
             ProjectLog::ChangeStore{time, version : _, new_kind : _ } => return *time,
-            ProjectLog::Error{time, version : _, error: _ } => return *time,
+            ProjectLog::Retry{time, version : _, error : _, attempt : _ } => return *time,
+            ProjectLog::Skipped{time, version : _, reason : _ } => return *time,
+            ProjectLog::Merged{time, version : _, source : _, old_id : _ } => return *time,
+            ProjectLog::PathFilters{time, version : _, filters : _ } => return *time,
+            ProjectLog::Deleted{time, version : _, reason : _ } => return *time,
+            ProjectLog::Denied{time, version : _, reason : _ } => return *time,
+            ProjectLog::Error{time, version : _, kind : _, error: _ } => return *time,
+        }
+    }
+
+    /** Returns true if the record marks the project as deleted. 
+     */
+    pub fn is_deleted(& self) -> bool {
+        match self {
+            ProjectLog::Deleted{time : _, version : _, reason : _} => return true, 
+            _ => return false,
+        }
+    }
+
+    /** Returns true if the record marks the project as suppressed by the denylist. 
+     */
+    pub fn is_denied(& self) -> bool {
+        match self {
+            ProjectLog::Denied{time : _, version : _, reason : _} => return true, 
+            _ => return false,
         }
     }
 
     pub fn is_error(& self) -> bool {
         match self {
-            ProjectLog::Error{time : _, version : _, error : _} => return true, 
+            ProjectLog::Error{time : _, version : _, kind : _, error : _} => return true, 
             _ => return false,
         }
     }
+
+    /** Returns the kind of the error recorded by an error record, None for all other records. 
+     */
+    pub fn error_kind(& self) -> Option<UpdateError> {
+        match self {
+            ProjectLog::Error{time : _, version : _, kind, error : _} => return Some(*kind),
+            _ => return None,
+        }
+    }
+
+    /** Returns the changes recorded by an ok record, None for all other records and for ok records without the changes. 
+     */
+    pub fn changes(& self) -> Option<UpdateChanges> {
+        match self {
+            ProjectLog::Ok{time : _, version : _, changes} => return *changes,
+            _ => return None,
+        }
+    }
+
+    /** Returns the number of failed update attempts recorded by a retry record, 0 for all other records. 
+     */
+    pub fn retry_attempts(& self) -> u16 {
+        match self {
+            ProjectLog::Retry{time : _, version : _, error : _, attempt} => return *attempt,
+            _ => return 0,
+        }
+    }
 }
 
 impl Serializable for ProjectLog {
     type Item = ProjectLog;
-    fn serialize(f : & mut File, value : & ProjectLog) {
+    fn serialize(f : & mut dyn TableStorage, value : & ProjectLog) {
         match value {
             ProjectLog::NoChange{time , version } => {
                 u8::serialize(f, & 0);
                 i64::serialize(f, time);
                 u16::serialize(f, version);
             },
-            ProjectLog::Ok{time , version} =>  {
+            ProjectLog::Ok{time , version, changes : None} =>  {
                 u8::serialize(f, & 1);
                 i64::serialize(f, time);
                 u16::serialize(f, version);
             },
+            ProjectLog::Ok{time , version, changes : Some(changes)} =>  {
+                u8::serialize(f, & 11);
+                i64::serialize(f, time);
+                u16::serialize(f, version);
+                UpdateChanges::serialize(f, changes);
+            },
             ProjectLog::Rename{time , version, old_offset} =>  {
                 u8::serialize(f, & 2);
                 i64::serialize(f, time);
@@ -571,16 +796,55 @@ impl Serializable for ProjectLog {
                 u16::serialize(f, version);
                 StoreKind::serialize(f, new_kind);
             },
-            ProjectLog::Error{time , version, error } =>  {
-                u8::serialize(f, & 255);
+            ProjectLog::Retry{time , version, error, attempt } =>  {
+                u8::serialize(f, & 4);
+                i64::serialize(f, time);
+                u16::serialize(f, version);
+                String::serialize(f, error);
+                u16::serialize(f, attempt);
+            },
+            ProjectLog::Skipped{time , version, reason } =>  {
+                u8::serialize(f, & 5);
+                i64::serialize(f, time);
+                u16::serialize(f, version);
+                String::serialize(f, reason);
+            },
+            ProjectLog::Merged{time , version, source, old_id } =>  {
+                u8::serialize(f, & 6);
+                i64::serialize(f, time);
+                u16::serialize(f, version);
+                String::serialize(f, source);
+                u64::serialize(f, old_id);
+            },
+            ProjectLog::PathFilters{time , version, filters } =>  {
+                u8::serialize(f, & 7);
+                i64::serialize(f, time);
+                u16::serialize(f, version);
+                String::serialize(f, filters);
+            },
+            ProjectLog::Deleted{time , version, reason } =>  {
+                u8::serialize(f, & 8);
+                i64::serialize(f, time);
+                u16::serialize(f, version);
+                String::serialize(f, reason);
+            },
+            ProjectLog::Denied{time , version, reason } =>  {
+                u8::serialize(f, & 9);
                 i64::serialize(f, time);
                 u16::serialize(f, version);
+                String::serialize(f, reason);
+            },
+            ProjectLog::Error{time , version, kind, error } =>  {
+                u8::serialize(f, & 10);
+                i64::serialize(f, time);
+                u16::serialize(f, version);
+                UpdateError::serialize(f, kind);
                 String::serialize(f, error);
             },
         }
     }
 
-    fn deserialize(f : & mut File) -> ProjectLog {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> ProjectLog {
         let kind = u8::deserialize(f);
         let time = i64::deserialize(f);
         let version = u16::deserialize(f);
@@ -589,7 +853,7 @@ impl Serializable for ProjectLog {
                 return ProjectLog::NoChange{time, version};
             },
             1 => {
-                return ProjectLog::Ok{time, version};
+                return ProjectLog::Ok{time, version, changes : None};
             },
             2 => {
                 return ProjectLog::Rename{time, version, old_offset : u64::deserialize(f)};
@@ -597,17 +861,45 @@ impl Serializable for ProjectLog {
             3 => {
                 return ProjectLog::ChangeStore{time, version, new_kind : StoreKind::deserialize(f)};
             },
+            4 => {
+                let error = String::deserialize(f);
+                return ProjectLog::Retry{time, version, error, attempt : u16::deserialize(f)};
+            },
+            5 => {
+                return ProjectLog::Skipped{time, version, reason : String::deserialize(f)};
+            },
+            6 => {
+                let source = String::deserialize(f);
+                return ProjectLog::Merged{time, version, source, old_id : u64::deserialize(f)};
+            },
+            7 => {
+                return ProjectLog::PathFilters{time, version, filters : String::deserialize(f)};
+            },
+            8 => {
+                return ProjectLog::Deleted{time, version, reason : String::deserialize(f)};
+            },
+            9 => {
+                return ProjectLog::Denied{time, version, reason : String::deserialize(f)};
+            },
+            10 => {
+                let kind = UpdateError::deserialize(f);
+                return ProjectLog::Error{time, version, kind, error : String::deserialize(f)};
+            },
+            11 => {
+                return ProjectLog::Ok{time, version, changes : Some(UpdateChanges::deserialize(f))};
+            },
             255 => {
-                return ProjectLog::Error{time, version, error : String::deserialize(f)};
+                let error = String::deserialize(f);
+                return ProjectLog::Error{time, version, kind : UpdateError::from_message(& error), error};
             },
             _ => panic!("Unknown project update status kind"),
         }
     }
 
-    fn verify(f : & mut File) -> Result<ProjectLog, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<ProjectLog, std::io::Error> {
         let kind = u8::verify(f)?;
         match kind {
-            0 | 1 | 2 | 3 | 255 => {
+            0 | 1 | 2 | 3 | 4 | 5 | 6 | 7 | 8 | 9 | 10 | 11 | 255 => {
                 let time = i64::verify(f)?;
                 let version = u16::verify(f)?;
                 match kind {
@@ -615,7 +907,7 @@ impl Serializable for ProjectLog {
                         return Ok(ProjectLog::NoChange{time, version});
                     },
                     1 => {
-                        return Ok(ProjectLog::Ok{time, version});
+                        return Ok(ProjectLog::Ok{time, version, changes : None});
                     },
                     2 => {
                         return Ok(ProjectLog::Rename{time, version, old_offset : u64::deserialize(f)});
@@ -623,8 +915,36 @@ impl Serializable for ProjectLog {
                     3 => {
                         return Ok(ProjectLog::ChangeStore{time, version, new_kind : StoreKind::deserialize(f)});
                     },
+                    4 => {
+                        let error = String::verify(f)?;
+                        return Ok(ProjectLog::Retry{time, version, error, attempt : u16::verify(f)?});
+                    },
+                    5 => {
+                        return Ok(ProjectLog::Skipped{time, version, reason : String::verify(f)?});
+                    },
+                    6 => {
+                        let source = String::verify(f)?;
+                        return Ok(ProjectLog::Merged{time, version, source, old_id : u64::verify(f)?});
+                    },
+                    7 => {
+                        return Ok(ProjectLog::PathFilters{time, version, filters : String::verify(f)?});
+                    },
+                    8 => {
+                        return Ok(ProjectLog::Deleted{time, version, reason : String::verify(f)?});
+                    },
+                    9 => {
+                        return Ok(ProjectLog::Denied{time, version, reason : String::verify(f)?});
+                    },
+                    10 => {
+                        let kind = UpdateError::verify(f)?;
+                        return Ok(ProjectLog::Error{time, version, kind, error : String::verify(f)?});
+                    },
+                    11 => {
+                        return Ok(ProjectLog::Ok{time, version, changes : Some(UpdateChanges::verify(f)?)});
+                    },
                     255 => {
-                        return Ok(ProjectLog::Error{time, version, error : String::deserialize(f)});
+                        let error = String::verify(f)?;
+                        return Ok(ProjectLog::Error{time, version, kind : UpdateError::from_message(& error), error});
                     },
                     _ => unreachable!(),
                 }
@@ -641,18 +961,209 @@ impl std::fmt::Display for ProjectLog {
             ProjectLog::NoChange{time , version } => {
                 return write!(f, "{}: no change (v {})", helpers::pretty_timestamp(*time), version);
             },
-            ProjectLog::Ok{time , version} =>  {
+            ProjectLog::Ok{time , version, changes : None} =>  {
                 return write!(f, "{}: ok (v {})", helpers::pretty_timestamp(*time), version);
             },
+            ProjectLog::Ok{time , version, changes : Some(changes)} =>  {
+                return write!(f, "{}: ok: {} (v {})", helpers::pretty_timestamp(*time), changes, version);
+            },
             ProjectLog::Rename{time , version, old_offset : _} =>  {
                 return write!(f, "{}: project renamed (v {})", helpers::pretty_timestamp(*time), version);
             },
             ProjectLog::ChangeStore{time , version, new_kind } =>  {
                 return write!(f, "{}: substore: {:?} (v {})", helpers::pretty_timestamp(*time), new_kind, version);
             },
-            ProjectLog::Error{time , version, error } =>  {
-                return write!(f, "{}: error: {} (v {})", helpers::pretty_timestamp(*time), error, version);
+            ProjectLog::Retry{time , version, error, attempt } =>  {
+                return write!(f, "{}: retry #{}: {} (v {})", helpers::pretty_timestamp(*time), attempt, error, version);
+            },
+            ProjectLog::Skipped{time , version, reason } =>  {
+                return write!(f, "{}: skipped: {} (v {})", helpers::pretty_timestamp(*time), reason, version);
+            },
+            ProjectLog::Merged{time , version, source, old_id } =>  {
+                return write!(f, "{}: merged from {} (id {}) (v {})", helpers::pretty_timestamp(*time), source, old_id, version);
+            },
+            ProjectLog::PathFilters{time , version, filters } =>  {
+                return write!(f, "{}: path filters: {} (v {})", helpers::pretty_timestamp(*time), filters, version);
+            },
+            ProjectLog::Deleted{time , version, reason } =>  {
+                return write!(f, "{}: deleted: {} (v {})", helpers::pretty_timestamp(*time), reason, version);
+            },
+            ProjectLog::Denied{time , version, reason } =>  {
+                return write!(f, "{}: denied: {} (v {})", helpers::pretty_timestamp(*time), reason, version);
+            },
+            ProjectLog::Error{time , version, kind, error } =>  {
+                return write!(f, "{}: error: {}: {} (v {})", helpers::pretty_timestamp(*time), kind, error, version);
+            },
+        }
+    }
+}
+
+/** Summary of what an update changed in the project, recorded with the ok record of the update. 
+ 
+    The `new_commits` is the growth of the number of commits reachable from the heads of the project (see `ProjectSummary`), 0 if the project has fewer commits than before. The `changed_heads` is the number of heads that are new, point to different commits, or were deleted. The `new_contents` is the size in bytes of the file contents stored by the update, i.e. of the contents not already in the substore. The sizes are before compression and delta encoded contents only count the size of their delta. 
+ */
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UpdateChanges {
+    pub new_commits : u64,
+    pub changed_heads : u32,
+    pub new_contents : u64,
+}
+
+impl std::fmt::Display for UpdateChanges {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(f, "{} new commits, {} changed heads, {} of new contents", self.new_commits, self.changed_heads, helpers::pretty_size(self.new_contents));
+    }
+}
+
+impl Serializable for UpdateChanges {
+    type Item = UpdateChanges;
+    fn serialize(f : & mut dyn TableStorage, value : & UpdateChanges) {
+        u64::serialize(f, & value.new_commits);
+        u32::serialize(f, & value.changed_heads);
+        u64::serialize(f, & value.new_contents);
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> UpdateChanges {
+        return UpdateChanges{
+            new_commits : u64::deserialize(f),
+            changed_heads : u32::deserialize(f),
+            new_contents : u64::deserialize(f),
+        };
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<UpdateChanges, std::io::Error> {
+        return Ok(UpdateChanges{
+            new_commits : u64::verify(f)?,
+            changed_heads : u32::verify(f)?,
+            new_contents : u64::verify(f)?,
+        });
+    }
+}
+
+/** Kind of the error that stopped the update of a project. 
+ 
+    The kinds are coarse so that the errors of many projects can be aggregated, the details of the error are kept in the error message of the project log record. 
+
+    # AuthFailed
+
+    The host refused the credentials, or required ones. GitHub reports private and some deleted repositories this way. 
+
+    # NotFound
+
+    The repository, or some of its objects, do not exist. 
+
+    # Timeout
+
+    The update took too long, either the host did not respond in time, or the update was cancelled by the watchdog. 
+
+    # TooLarge
+
+    The repository, or some of its objects, are too large to be processed. 
+
+    # GitError
+
+    Any other error reported by libgit2, the `code` is its (negative) error code. 
+
+    # Internal
+
+    Any other error, typically a failure of parasite itself. 
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UpdateError {
+    AuthFailed, // 0
+    NotFound, // 1
+    Timeout, // 2
+    TooLarge, // 3
+    GitError{code : i32}, // 4
+    Internal, // 255
+}
+
+impl UpdateError {
+    /** Names of all error kinds, in the order of their declaration. 
+     */
+    pub const NAMES : [& 'static str; 6] = ["auth_failed", "not_found", "timeout", "too_large", "git_error", "internal"];
+
+    /** Returns the name of the error kind, regardless of the git error code, used when aggregating the errors. 
+     */
+    pub fn name(& self) -> & 'static str {
+        match self {
+            UpdateError::AuthFailed => return "auth_failed",
+            UpdateError::NotFound => return "not_found",
+            UpdateError::Timeout => return "timeout",
+            UpdateError::TooLarge => return "too_large",
+            UpdateError::GitError{code : _} => return "git_error",
+            UpdateError::Internal => return "internal",
+        }
+    }
+
+    /** Guesses the kind of the error from its message. 
+     
+        Used for the error records written before the kinds were introduced, whose messages are debug prints of the io errors that stopped the updates. 
+     */
+    pub fn from_message(message : & str) -> UpdateError {
+        if message.contains("kind: TimedOut") || message.contains("cancelled by watchdog") {
+            return UpdateError::Timeout;
+        } else if message.contains("kind: NotFound") {
+            return UpdateError::NotFound;
+        } else if message.contains("kind: PermissionDenied") || message.contains(" 401") || message.contains(" 403") || message.contains("authentication") {
+            return UpdateError::AuthFailed;
+        } else if message.contains("kind: OutOfMemory") || message.contains("too large") {
+            return UpdateError::TooLarge;
+        } else {
+            return UpdateError::Internal;
+        }
+    }
+}
+
+impl std::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UpdateError::GitError{code} => return write!(f, "{} {}", self.name(), code),
+            _ => return write!(f, "{}", self.name()),
+        }
+    }
+}
+
+impl Serializable for UpdateError {
+    type Item = UpdateError;
+    fn serialize(f : & mut dyn TableStorage, value : & UpdateError) {
+        match value {
+            UpdateError::AuthFailed => u8::serialize(f, & 0),
+            UpdateError::NotFound => u8::serialize(f, & 1),
+            UpdateError::Timeout => u8::serialize(f, & 2),
+            UpdateError::TooLarge => u8::serialize(f, & 3),
+            UpdateError::GitError{code} => {
+                u8::serialize(f, & 4);
+                // libgit2 error codes are small negative numbers
+                u16::serialize(f, & (*code as i16 as u16));
             },
+            UpdateError::Internal => u8::serialize(f, & 255),
+        }
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> UpdateError {
+        match u8::deserialize(f) {
+            0 => return UpdateError::AuthFailed,
+            1 => return UpdateError::NotFound,
+            2 => return UpdateError::Timeout,
+            3 => return UpdateError::TooLarge,
+            4 => return UpdateError::GitError{code : u16::deserialize(f) as i16 as i32},
+            255 => return UpdateError::Internal,
+            _ => panic!("Unknown update error kind"),
+        }
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<UpdateError, std::io::Error> {
+        match u8::verify(f)? {
+            0 => return Ok(UpdateError::AuthFailed),
+            1 => return Ok(UpdateError::NotFound),
+            2 => return Ok(UpdateError::Timeout),
+            3 => return Ok(UpdateError::TooLarge),
+            4 => return Ok(UpdateError::GitError{code : u16::verify(f)? as i16 as i32}),
+            255 => return Ok(UpdateError::Internal),
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid update error kind")),
         }
     }
 }
@@ -667,7 +1178,7 @@ pub type ProjectHeads = HashMap<String, (CommitId, SHA)>;
 
 impl Serializable for ProjectHeads {
     type Item = ProjectHeads;
-    fn serialize(f : & mut File, value : & ProjectHeads) {
+    fn serialize(f : & mut dyn TableStorage, value : & ProjectHeads) {
         u32::serialize(f, & (value.len() as u32));
         for (name, (id, hash)) in value {
             String::serialize(f, name);
@@ -676,7 +1187,7 @@ impl Serializable for ProjectHeads {
         }
     }
 
-    fn deserialize(f : & mut File) -> ProjectHeads {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> ProjectHeads {
         let mut records = u32::deserialize(f);
         let mut result = ProjectHeads::new();
         while records > 0 {
@@ -689,7 +1200,7 @@ impl Serializable for ProjectHeads {
         return result;
     }
 
-    fn verify(f : & mut File) -> Result<ProjectHeads, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<ProjectHeads, std::io::Error> {
         let mut records = u32::verify(f)?;
         if records as u64 > MAX_BUFFER_LENGTH {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid length of project heads"));
@@ -706,48 +1217,540 @@ impl Serializable for ProjectHeads {
     }
 }
 
-pub type SHA = git2::Oid;
+/** Tags of a repository at any given update. 
+ 
+    Like heads, tags are a hashmap from the tag name to the id and hash of the commit the tag points to. For annotated tags, this is the commit the tag object refers to, not the tag object itself. Tags that do not point to commits are ignored. 
+ */
+pub type ProjectTags = ProjectHeads;
 
-impl Serializable for SHA {
-    type Item = SHA;
-    fn serialize(f : & mut File, value : & SHA) {
-        f.write(value.as_bytes()).unwrap();
+/** Labels of a project. 
+ 
+    Labels are arbitrary strings assigned to projects by the users of the datastore so that curated subsets of the projects (such as samples used in papers, or projects excluded from analyses) can be identified and exported reproducibly. Each update stores the complete list of the project's labels. 
+ */
+pub type ProjectLabels = Vec<String>;
+
+impl Serializable for ProjectLabels {
+    type Item = ProjectLabels;
+    fn serialize(f : & mut dyn TableStorage, value : & ProjectLabels) {
+        u16::serialize(f, & (value.len() as u16));
+        for label in value {
+            String::serialize(f, label);
+        }
     }
 
-    fn deserialize(f : & mut File) -> SHA {
-        let mut buffer = vec![0; 20];
-        f.read(& mut buffer).unwrap();
-        return git2::Oid::from_bytes(& buffer).unwrap();
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> ProjectLabels {
+        let records = u16::deserialize(f);
+        return (0..records).map(|_| String::deserialize(f)).collect();
     }
 
-    fn verify(f : & mut File) -> Result<SHA, std::io::Error> {
-        let mut buffer = vec![0; 20];
-        f.read(& mut buffer)?;
-        match git2::Oid::from_bytes(& buffer) {
-            Ok(oid) => return Ok(oid),
-            Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", err))),
+    fn verify(f : & mut dyn TableStorage) -> Result<ProjectLabels, std::io::Error> {
+        let records = u16::verify(f)?;
+        let mut result = ProjectLabels::new();
+        for _ in 0..records {
+            result.push(String::verify(f)?);
         }
+        return Ok(result);
     }
 }
 
-impl FixedSizeSerializable for SHA {
-    const SIZE : u64 = 20;
-}
-
-/** Content kinds.
+/** Summary of a project's commits and its README and LICENSE files. 
  
-    Up to 1024 content kinds are supported. 
+    The summary is recalculated whenever the heads of the project change so that the basic statistics about the projects are available without walking their commits. Only commits reachable from the heads (not tags) of the project are summarized and the first and last commit times are the committer times. Projects without any commits have both times set to 0. 
+ 
+    The files are None if they could not be determined yet, i.e. the head commit was not available when the summary was calculated. 
  */
-#[repr(u16)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, std::hash::Hash, FromPrimitive)]
-pub enum ContentsKind {
-    Generic,
-    SmallFiles,
-    C,
-    Cpp,
-    CSharp,
-    Clojure,
-    CoffeeScript,
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProjectSummary {
+    /** Time at which the summary was calculated. 
+     */
+    pub time : i64,
+    pub commits : u64,
+    pub authors : u64,
+    pub committers : u64,
+    pub first_commit_time : i64,
+    pub last_commit_time : i64,
+    pub files : Option<ProjectFiles>,
+}
+
+impl ProjectSummary {
+    pub fn new() -> ProjectSummary {
+        return ProjectSummary{
+            time : helpers::now(),
+            commits : 0,
+            authors : 0,
+            committers : 0,
+            first_commit_time : 0,
+            last_commit_time : 0,
+            files : None,
+        };
+    }
+}
+
+impl Serializable for ProjectSummary {
+    type Item = ProjectSummary;
+    fn serialize(f : & mut dyn TableStorage, value : & ProjectSummary) {
+        i64::serialize(f, & value.time);
+        u64::serialize(f, & value.commits);
+        u64::serialize(f, & value.authors);
+        u64::serialize(f, & value.committers);
+        i64::serialize(f, & value.first_commit_time);
+        i64::serialize(f, & value.last_commit_time);
+        match & value.files {
+            Some(files) => {
+                u8::serialize(f, & 1);
+                ProjectFiles::serialize(f, files);
+            },
+            None => {
+                u8::serialize(f, & 0);
+            }
+        }
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> ProjectSummary {
+        return ProjectSummary{
+            time : i64::deserialize(f),
+            commits : u64::deserialize(f),
+            authors : u64::deserialize(f),
+            committers : u64::deserialize(f),
+            first_commit_time : i64::deserialize(f),
+            last_commit_time : i64::deserialize(f),
+            files : if u8::deserialize(f) == 0 { None } else { Some(ProjectFiles::deserialize(f)) },
+        };
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<ProjectSummary, std::io::Error> {
+        return Ok(ProjectSummary{
+            time : i64::verify(f)?,
+            commits : u64::verify(f)?,
+            authors : u64::verify(f)?,
+            committers : u64::verify(f)?,
+            first_commit_time : i64::verify(f)?,
+            last_commit_time : i64::verify(f)?,
+            files : match u8::verify(f)? {
+                0 => None,
+                1 => Some(ProjectFiles::verify(f)?),
+                _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid project summary files tag")),
+            },
+        });
+    }
+}
+
+/** Popularity of a GitHub project at the time its metadata were checked. 
+ 
+    A new record is stored every time the metadata of the project are obtained, so that the popularity of the project over time is available without replaying the metadata of the project. Watchers are the users subscribed to the project's notifications, not the stargazers GitHub reports as watchers for historical reasons. 
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProjectPopularity {
+    pub time : i64,
+    pub stars : u64,
+    pub forks : u64,
+    pub watchers : u64,
+    pub open_issues : u64,
+}
+
+impl ProjectPopularity {
+    /** Extracts the popularity from the GitHub metadata of a project. 
+     
+        Returns None if the metadata do not contain the counts. 
+     */
+    pub fn from_metadata(metadata : & json::JsonValue) -> Option<ProjectPopularity> {
+        return Some(ProjectPopularity{
+            time : helpers::now(),
+            stars : metadata["stargazers_count"].as_u64()?,
+            forks : metadata["forks_count"].as_u64()?,
+            watchers : metadata["subscribers_count"].as_u64().unwrap_or(0),
+            open_issues : metadata["open_issues_count"].as_u64().unwrap_or(0),
+        });
+    }
+}
+
+impl Serializable for ProjectPopularity {
+    type Item = ProjectPopularity;
+    fn serialize(f : & mut dyn TableStorage, value : & ProjectPopularity) {
+        i64::serialize(f, & value.time);
+        u64::serialize(f, & value.stars);
+        u64::serialize(f, & value.forks);
+        u64::serialize(f, & value.watchers);
+        u64::serialize(f, & value.open_issues);
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> ProjectPopularity {
+        return ProjectPopularity{
+            time : i64::deserialize(f),
+            stars : u64::deserialize(f),
+            forks : u64::deserialize(f),
+            watchers : u64::deserialize(f),
+            open_issues : u64::deserialize(f),
+        };
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<ProjectPopularity, std::io::Error> {
+        return Ok(ProjectPopularity{
+            time : i64::verify(f)?,
+            stars : u64::verify(f)?,
+            forks : u64::verify(f)?,
+            watchers : u64::verify(f)?,
+            open_issues : u64::verify(f)?,
+        });
+    }
+}
+
+/** The default branch of a project.
+
+    For GitHub projects the branch is taken from the metadata and its protection is obtained from GitHub. For plain git projects the branch is the one the remote `HEAD` points to, if it can be determined, and its protection is not known.
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DefaultBranch {
+    /** Name of the branch without the `refs/heads/` prefix.
+     */
+    pub name : String,
+    pub protected : Option<bool>,
+}
+
+impl DefaultBranch {
+    /** Returns the full name of the branch's head, as used in project heads.
+     */
+    pub fn head(& self) -> String {
+        return format!("refs/heads/{}", self.name);
+    }
+}
+
+impl Serializable for DefaultBranch {
+    type Item = DefaultBranch;
+    fn serialize(f : & mut dyn TableStorage, value : & DefaultBranch) {
+        let protected = match value.protected {
+            None => 0,
+            Some(false) => 1,
+            Some(true) => 2,
+        };
+        u8::serialize(f, & protected);
+        String::serialize(f, & value.name);
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> DefaultBranch {
+        let protected = match u8::deserialize(f) {
+            0 => None,
+            1 => Some(false),
+            2 => Some(true),
+            _ => panic!("Invalid default branch protection"),
+        };
+        return DefaultBranch{
+            name : String::deserialize(f),
+            protected,
+        };
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<DefaultBranch, std::io::Error> {
+        let protected = match u8::verify(f)? {
+            0 => None,
+            1 => Some(false),
+            2 => Some(true),
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid default branch protection")),
+        };
+        return Ok(DefaultBranch{
+            name : String::verify(f)?,
+            protected,
+        });
+    }
+}
+
+/** The README and LICENSE files of a project, stored in its summary.
+
+    The files are looked up in the root tree of the default branch's head commit. Each file is stored as its path in the tree together with the id of its contents hash in the project's substore. The license id is the SPDX identifier determined by the license classifier from the license file, or from the README if the project has no license file, and is None if the license could not be recognized.
+ */
+#[derive(Clone, Debug, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProjectFiles {
+    pub readme : Option<(String, HashId)>,
+    pub license : Option<(String, HashId)>,
+    pub license_id : Option<String>,
+}
+
+impl ProjectFiles {
+    fn serialize_file(f : & mut dyn TableStorage, file : & Option<(String, HashId)>) {
+        match file {
+            Some((path, id)) => {
+                u8::serialize(f, & 1);
+                String::serialize(f, path);
+                u64::serialize(f, & u64::from(*id));
+            },
+            None => {
+                u8::serialize(f, & 0);
+            }
+        }
+    }
+
+    fn deserialize_file<R : Read + ?Sized>(f : & mut R) -> Option<(String, HashId)> {
+        if u8::deserialize(f) == 0 {
+            return None;
+        }
+        let path = String::deserialize(f);
+        return Some((path, HashId::from(u64::deserialize(f))));
+    }
+
+    fn verify_file(f : & mut dyn TableStorage) -> Result<Option<(String, HashId)>, std::io::Error> {
+        match u8::verify(f)? {
+            0 => return Ok(None),
+            1 => {
+                let path = String::verify(f)?;
+                return Ok(Some((path, HashId::from(u64::verify(f)?))));
+            },
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid project file tag")),
+        }
+    }
+}
+
+impl Serializable for ProjectFiles {
+    type Item = ProjectFiles;
+    fn serialize(f : & mut dyn TableStorage, value : & ProjectFiles) {
+        ProjectFiles::serialize_file(f, & value.readme);
+        ProjectFiles::serialize_file(f, & value.license);
+        String::serialize(f, value.license_id.as_ref().unwrap_or(& String::new()));
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> ProjectFiles {
+        let readme = ProjectFiles::deserialize_file(f);
+        let license = ProjectFiles::deserialize_file(f);
+        let license_id = String::deserialize(f);
+        return ProjectFiles{
+            readme,
+            license,
+            license_id : if license_id.is_empty() { None } else { Some(license_id) },
+        };
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<ProjectFiles, std::io::Error> {
+        let readme = ProjectFiles::verify_file(f)?;
+        let license = ProjectFiles::verify_file(f)?;
+        let license_id = String::verify(f)?;
+        return Ok(ProjectFiles{
+            readme,
+            license,
+            license_id : if license_id.is_empty() { None } else { Some(license_id) },
+        });
+    }
+}
+
+pub type SHA = git2::Oid;
+
+/** Serializes the hashes, which are foreign types, as their hex strings. 
+ */
+#[cfg(feature = "serde")]
+mod serde_sha {
+    use super::SHA;
+
+    pub fn serialize<S : serde::Serializer>(hash : & SHA, serializer : S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(& hash.to_string());
+    }
+
+    pub fn deserialize<'de, D : serde::Deserializer<'de>>(deserializer : D) -> Result<SHA, D::Error> {
+        let hex = <String as serde::Deserialize>::deserialize(deserializer)?;
+        return SHA::from_str(& hex).map_err(serde::de::Error::custom);
+    }
+}
+
+impl Serializable for SHA {
+    type Item = SHA;
+    fn serialize(f : & mut dyn TableStorage, value : & SHA) {
+        f.write(value.as_bytes()).unwrap();
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> SHA {
+        let mut buffer = vec![0; 20];
+        f.read(& mut buffer).unwrap();
+        return git2::Oid::from_bytes(& buffer).unwrap();
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<SHA, std::io::Error> {
+        let mut buffer = vec![0; 20];
+        f.read(& mut buffer)?;
+        match git2::Oid::from_bytes(& buffer) {
+            Ok(oid) => return Ok(oid),
+            Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", err))),
+        }
+    }
+}
+
+impl FixedSizeSerializable for SHA {
+    const SIZE : u64 = 20;
+}
+
+/** A git LFS pointer file. 
+ 
+    Files tracked by git LFS are replaced in the repository by small text files that identify the actual object by its SHA-256 and size, the object itself is downloaded from the LFS server of the repository. 
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LfsPointer {
+    pub oid : String,
+    pub size : u64,
+}
+
+impl LfsPointer {
+    /** Pointer files are smaller than this, so larger contents need not be parsed. 
+     */
+    pub const MAX_SIZE : usize = 1024;
+
+    /** Parses the contents of a file as LFS pointer, returns None if the contents are not a pointer. 
+     */
+    pub fn parse(contents : & [u8]) -> Option<LfsPointer> {
+        if contents.len() >= LfsPointer::MAX_SIZE || ! contents.starts_with(b"version https://git-lfs.github.com/spec/") {
+            return None;
+        }
+        let text = std::str::from_utf8(contents).ok()?;
+        let mut oid = None;
+        let mut size = None;
+        for line in text.lines() {
+            if let Some(x) = line.strip_prefix("oid sha256:") {
+                oid = Some(x.trim().to_owned());
+            } else if let Some(x) = line.strip_prefix("size ") {
+                size = x.trim().parse::<u64>().ok();
+            }
+        }
+        return Some(LfsPointer{ oid : oid?, size : size? });
+    }
+}
+
+/** A secret, such as an access token or a private key, found in file contents. 
+ 
+    Secrets are recognized by the formats of common tokens, i.e. a fixed prefix followed by a number of characters from a given set, and by the armored blocks of private keys. The secret spans the bytes from start (inclusive) to end (exclusive). 
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Secret {
+    pub kind : &'static str,
+    pub start : usize,
+    pub end : usize,
+}
+
+/** Format of a token, i.e. one of its prefixes followed by the minimum to maximum number of characters from the set. 
+ */
+struct SecretFormat {
+    kind : &'static str,
+    prefixes : &'static [&'static [u8]],
+    chars : fn(& u8) -> bool,
+    min : usize,
+    max : usize,
+}
+
+fn is_upper_alphanumeric(x : & u8) -> bool {
+    return x.is_ascii_uppercase() || x.is_ascii_digit();
+}
+
+fn is_token_char(x : & u8) -> bool {
+    return x.is_ascii_alphanumeric() || *x == b'_' || *x == b'-';
+}
+
+fn is_word_char(x : & u8) -> bool {
+    return x.is_ascii_alphanumeric() || *x == b'_';
+}
+
+fn is_slack_char(x : & u8) -> bool {
+    return x.is_ascii_alphanumeric() || *x == b'-';
+}
+
+impl Secret {
+    const FORMATS : [SecretFormat; 6] = [
+        SecretFormat{ kind : "aws-access-key", prefixes : & [b"AKIA", b"ASIA"], chars : is_upper_alphanumeric, min : 16, max : 16 },
+        SecretFormat{ kind : "github-token", prefixes : & [b"ghp_", b"gho_", b"ghu_", b"ghs_", b"ghr_"], chars : u8::is_ascii_alphanumeric, min : 36, max : 36 },
+        SecretFormat{ kind : "github-token", prefixes : & [b"github_pat_"], chars : is_word_char, min : 82, max : 82 },
+        SecretFormat{ kind : "slack-token", prefixes : & [b"xoxa-", b"xoxb-", b"xoxp-", b"xoxr-", b"xoxs-"], chars : is_slack_char, min : 10, max : 100 },
+        SecretFormat{ kind : "google-api-key", prefixes : & [b"AIza"], chars : is_token_char, min : 35, max : 35 },
+        SecretFormat{ kind : "stripe-key", prefixes : & [b"sk_live_", b"rk_live_"], chars : u8::is_ascii_alphanumeric, min : 24, max : 99 },
+    ];
+
+    const PRIVATE_KEY_BEGIN : &'static [u8] = b"-----BEGIN ";
+    const PRIVATE_KEY_END : &'static [u8] = b"-----END ";
+
+    /** Returns all secrets found in given contents, ordered by their position. 
+     
+        Tokens must not be preceded by a word character, nor followed by more characters than their format allows, so that they are not found inside longer identifiers. Private keys span from their begin line to their end line, or to the end of the contents if the end line is missing. 
+     */
+    pub fn find(contents : & [u8]) -> Vec<Secret> {
+        let mut result = Vec::new();
+        let mut i = 0;
+        'outer: while i < contents.len() {
+            if i > 0 && is_word_char(& contents[i - 1]) {
+                i += 1;
+                continue;
+            }
+            if let Some(end) = Secret::private_key_end(contents, i) {
+                result.push(Secret{ kind : "private-key", start : i, end });
+                i = end;
+                continue;
+            }
+            for format in Secret::FORMATS.iter() {
+                for prefix in format.prefixes {
+                    if contents[i..].starts_with(prefix) {
+                        let start = i + prefix.len();
+                        let n = contents[start..].iter().take(format.max + 1).take_while(|x| (format.chars)(x)).count();
+                        if n >= format.min && n <= format.max {
+                            result.push(Secret{ kind : format.kind, start : i, end : start + n });
+                            i = start + n;
+                            continue 'outer;
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+        return result;
+    }
+
+    /** Returns the contents with each of given secrets replaced by `[REDACTED:kind]`. 
+     */
+    pub fn redact(contents : & [u8], secrets : & [Secret]) -> Vec<u8> {
+        let mut result = Vec::with_capacity(contents.len());
+        let mut last = 0;
+        for secret in secrets {
+            result.extend_from_slice(& contents[last..secret.start]);
+            result.extend_from_slice(format!("[REDACTED:{}]", secret.kind).as_bytes());
+            last = secret.end;
+        }
+        result.extend_from_slice(& contents[last..]);
+        return result;
+    }
+
+    /** If a private key block begins at given position, returns the position after its end line. 
+     */
+    fn private_key_end(contents : & [u8], i : usize) -> Option<usize> {
+        if ! contents[i..].starts_with(Secret::PRIVATE_KEY_BEGIN) {
+            return None;
+        }
+        let line_end = contents[i..].iter().position(|x| *x == b'\n').map(|x| i + x).unwrap_or(contents.len());
+        if ! contents[i..line_end].ends_with(b"PRIVATE KEY-----") {
+            return None;
+        }
+        match Secret::find_bytes(& contents[line_end..], Secret::PRIVATE_KEY_END) {
+            Some(end) => {
+                let end = line_end + end;
+                return Some(contents[end..].iter().position(|x| *x == b'\n').map(|x| end + x).unwrap_or(contents.len()));
+            },
+            None => return Some(contents.len()),
+        }
+    }
+
+    fn find_bytes(haystack : & [u8], needle : & [u8]) -> Option<usize> {
+        return haystack.windows(needle.len()).position(|x| x == needle);
+    }
+}
+
+/** Content kinds.
+ 
+    Up to 1024 content kinds are supported. 
+ */
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, std::hash::Hash, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ContentsKind {
+    Generic,
+    SmallFiles,
+    C,
+    Cpp,
+    CSharp,
+    Clojure,
+    CoffeeScript,
     Erlang,
     Go,
     Haskell,
@@ -764,6 +1767,9 @@ pub enum ContentsKind {
     Scala,
     Shell,
     TypeScript,
+    /** Pointer files of git LFS, whose actual contents are stored elsewhere. 
+     */
+    LfsPointer,
 
     Sentinel // sentinel to denote number of content kinds
 }
@@ -823,10 +1829,12 @@ impl ContentsKind {
 
     /** Determines the contents kind from the actual contents of the file. 
      
-        For now, we only check if the file is really small, otherwise we keep the category as determined by its path.
+        Git LFS pointers have their own kind, otherwise we only check if the file is really small and keep the category as determined by its path.
      */
     pub fn from_contents(contents : & [u8], from_path : ContentsKind) -> Option<ContentsKind> {
-        if contents.len() < Datastore::SMALL_FILE_THRESHOLD {
+        if LfsPointer::parse(contents).is_some() {
+            return Some(ContentsKind::LfsPointer);
+        } else if contents.len() < Datastore::SMALL_FILE_THRESHOLD {
             return Some(ContentsKind::SmallFiles);
         } else {
             return Some(from_path);
@@ -837,6 +1845,7 @@ impl ContentsKind {
 impl SplitKind for ContentsKind {
     const COUNT : u64 = ContentsKind::Sentinel as u64;
 
+    /* The number of the sentinel changes when new kinds are added, so the kind of the empty offsets stored in the contents indexers must be migrated, see `task_migrate::migrate_from_v1`. */
     const EMPTY : ContentsKind = ContentsKind::Sentinel;
 
     fn to_number(& self) -> u64 {
@@ -850,15 +1859,15 @@ impl SplitKind for ContentsKind {
 
 impl Serializable for ContentsKind {
     type Item = ContentsKind;
-    fn serialize(f : & mut File, value : & ContentsKind) {
+    fn serialize(f : & mut dyn TableStorage, value : & ContentsKind) {
         f.write_u16::<LittleEndian>(value.to_number() as u16).unwrap();
     }
 
-    fn deserialize(f : & mut File) -> ContentsKind {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> ContentsKind {
         return ContentsKind::from_number(f.read_u16::<LittleEndian>().unwrap() as u64);
     }
 
-    fn verify(f : & mut File) -> Result<ContentsKind, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<ContentsKind, std::io::Error> {
         let index = u16::verify(f)? as u64;
         if index >= Self::COUNT {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid contents kind index"));
@@ -889,7 +1898,7 @@ impl ReadOnly for FileContents {
 
 impl Serializable for FileContents {
     type Item = FileContents;
-    fn serialize(f : & mut File, value : & FileContents) {
+    fn serialize(f : & mut dyn TableStorage, value : & FileContents) {
         let mut enc = flate2::write::GzEncoder::new(Vec::new(), Compression::best());
         enc.write_all(value).unwrap();
         let encoded = enc.finish().unwrap();
@@ -897,7 +1906,7 @@ impl Serializable for FileContents {
         f.write(& encoded).unwrap();
     }
 
-    fn deserialize(f : & mut File) -> FileContents {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> FileContents {
         let len = f.read_u64::<LittleEndian>().unwrap() as usize;
         let mut encoded = vec![0; len];
         f.read(& mut encoded).unwrap();
@@ -907,7 +1916,7 @@ impl Serializable for FileContents {
         return result;
     }
 
-    fn verify(f : & mut File) -> Result<FileContents, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<FileContents, std::io::Error> {
         let len = u64::verify(f)?;
         if len > MAX_BUFFER_LENGTH {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "Compressed file contents too large"));
@@ -921,10 +1930,169 @@ impl Serializable for FileContents {
     }
 }
 
+/** Delta encoded file contents. 
+ 
+    Instead of storing the full contents, a delta stores only the differences against a base contents, which is usually the previous version of the same path. The base can itself be delta encoded, the depth of the delta is the number of deltas that must be applied to a fully stored contents to reconstruct the contents. 
+
+    The delta itself is a sequence of operations, each of which either copies a range of the base contents, or inserts new bytes. The operations are stored compressed in the same way file contents are. 
+ */
+pub struct ContentsDelta {
+    pub base : HashId,
+    pub kind : ContentsKind,
+    pub depth : u16,
+    pub ops : Vec<u8>,
+}
+
+impl ContentsDelta {
+    /** Maximum depth of a delta chain. Longer chains make reconstruction slow, so once the depth is reached, full contents are stored instead. 
+     */
+    pub const MAX_DEPTH : u16 = 16;
+    /** Contents smaller than this are always stored in full as the savings would be negligible. 
+     */
+    pub const MIN_CONTENTS_SIZE : usize = 1024;
+    /** Size of the blocks matched between the base and the contents. 
+     */
+    const BLOCK_SIZE : usize = 32;
+
+    const OP_COPY : u8 = 0;
+    const OP_INSERT : u8 = 1;
+
+    /** Creates delta of given contents against the base contents if storing the delta is beneficial. 
+
+        The delta is beneficial if its encoded size is at most a quarter of the size of the contents. Returns `None` if the full contents should be stored instead. 
+     */
+    pub fn create(base_id : HashId, base_depth : u16, base : & [u8], kind : ContentsKind, contents : & [u8]) -> Option<ContentsDelta> {
+        if base_depth >= ContentsDelta::MAX_DEPTH || contents.len() < ContentsDelta::MIN_CONTENTS_SIZE || base.len() < ContentsDelta::BLOCK_SIZE {
+            return None;
+        }
+        let ops = ContentsDelta::encode(base, contents);
+        if ops.len() * 4 > contents.len() {
+            return None;
+        }
+        return Some(ContentsDelta{ base : base_id, kind, depth : base_depth + 1, ops });
+    }
+
+    /** Reconstructs the contents by applying the delta to given base contents. 
+     */
+    pub fn apply(& self, base : & [u8]) -> Result<FileContents, std::io::Error> {
+        let mut result = FileContents::new();
+        let mut ops = & self.ops[..];
+        while ! ops.is_empty() {
+            match ops.read_u8()? {
+                ContentsDelta::OP_COPY => {
+                    let offset = ops.read_u32::<LittleEndian>()? as usize;
+                    let len = ops.read_u32::<LittleEndian>()? as usize;
+                    if offset + len > base.len() {
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Delta copies outside of its base contents"));
+                    }
+                    result.extend_from_slice(& base[offset..(offset + len)]);
+                },
+                ContentsDelta::OP_INSERT => {
+                    let len = ops.read_u32::<LittleEndian>()? as usize;
+                    if len > ops.len() {
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Delta insert past its end"));
+                    }
+                    result.extend_from_slice(& ops[..len]);
+                    ops = & ops[len..];
+                },
+                _ => {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, "Unknown delta operation"));
+                }
+            }
+        }
+        return Ok(result);
+    }
+
+    /** Encodes the contents as a sequence of copy and insert operations against the base. 
+     
+        Blocks of the base at block aligned offsets are indexed and at each position of the contents we try to find a matching block, which is then extended as far as possible. Bytes not covered by any copies are inserted. 
+     */
+    fn encode(base : & [u8], contents : & [u8]) -> Vec<u8> {
+        let mut blocks = HashMap::<& [u8], usize>::new();
+        let mut i = 0;
+        while i + ContentsDelta::BLOCK_SIZE <= base.len() {
+            blocks.entry(& base[i..(i + ContentsDelta::BLOCK_SIZE)]).or_insert(i);
+            i += ContentsDelta::BLOCK_SIZE;
+        }
+        let mut ops = Vec::<u8>::new();
+        let mut insert_start = 0;
+        let mut i = 0;
+        while i + ContentsDelta::BLOCK_SIZE <= contents.len() {
+            if let Some(offset) = blocks.get(& contents[i..(i + ContentsDelta::BLOCK_SIZE)]) {
+                // extend the match backwards over the pending insert and forwards as far as possible
+                let mut start = *offset;
+                let mut from = i;
+                while start > 0 && from > insert_start && base[start - 1] == contents[from - 1] {
+                    start -= 1;
+                    from -= 1;
+                }
+                let mut end = *offset + ContentsDelta::BLOCK_SIZE;
+                i += ContentsDelta::BLOCK_SIZE;
+                while end < base.len() && i < contents.len() && base[end] == contents[i] {
+                    end += 1;
+                    i += 1;
+                }
+                ContentsDelta::encode_insert(& mut ops, & contents[insert_start..from]);
+                ops.write_u8(ContentsDelta::OP_COPY).unwrap();
+                ops.write_u32::<LittleEndian>(start as u32).unwrap();
+                ops.write_u32::<LittleEndian>((end - start) as u32).unwrap();
+                insert_start = i;
+            } else {
+                i += 1;
+            }
+        }
+        ContentsDelta::encode_insert(& mut ops, & contents[insert_start..]);
+        return ops;
+    }
+
+    fn encode_insert(ops : & mut Vec<u8>, bytes : & [u8]) {
+        if ! bytes.is_empty() {
+            ops.write_u8(ContentsDelta::OP_INSERT).unwrap();
+            ops.write_u32::<LittleEndian>(bytes.len() as u32).unwrap();
+            ops.extend_from_slice(bytes);
+        }
+    }
+}
+
+impl Serializable for ContentsDelta {
+    type Item = ContentsDelta;
+    fn serialize(f : & mut dyn TableStorage, value : & ContentsDelta) {
+        u64::serialize(f, & value.base.into());
+        ContentsKind::serialize(f, & value.kind);
+        u16::serialize(f, & value.depth);
+        FileContents::serialize(f, & value.ops);
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> ContentsDelta {
+        return ContentsDelta{
+            base : HashId::from(u64::deserialize(f)),
+            kind : ContentsKind::deserialize(f),
+            depth : u16::deserialize(f),
+            ops : FileContents::deserialize(f),
+        };
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<ContentsDelta, std::io::Error> {
+        let base = HashId::from(u64::verify(f)?);
+        let kind = ContentsKind::verify(f)?;
+        let depth = u16::verify(f)?;
+        if depth == 0 || depth > ContentsDelta::MAX_DEPTH {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Invalid contents delta depth {}", depth)));
+        }
+        return Ok(ContentsDelta{
+            base, 
+            kind,
+            depth,
+            ops : FileContents::verify(f)?,
+        });
+    }
+}
+
 /** Metadata values. 
  
     Metadata are encoded as simple key/value store. 
  */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Metadata {
     pub key : String, 
     pub value : String
@@ -932,23 +2100,92 @@ pub struct Metadata {
 
 impl Metadata {
     pub const GITHUB_METADATA : &'static str = "github_metadata";
+    /** Commit metadata key for signed commits. The value is the kind of the signature (`gpg`, `ssh`, or `x509`). Unsigned commits have no such metadata. 
+     */
+    pub const COMMIT_SIGNATURE : &'static str = "signature";
+    /** Project metadata keys for the number of stars and the main language of the project as found in the project dumps the project was imported from. 
+     */
+    pub const STARS : &'static str = "stars";
+    pub const LANGUAGE : &'static str = "language";
+    /** Project metadata key for the total number of secrets found in the contents of the project while secrets were scrubbed. 
+     */
+    pub const SECRETS : &'static str = "secrets";
+    /** User metadata key for the name of the user as found in the commit signatures. 
+     */
+    pub const USER_NAME : &'static str = "name";
+    /** Contents metadata keys, stored when the contents are snapshotted. 
+     
+        The size is in bytes, lines is the number of lines of text (0 for binary contents), binary is either `true` or `false` and encoding is the detected text encoding (`ascii`, `utf-8`, `utf-16le`, `utf-16be`, or `unknown`), or `binary` for binary contents. 
+     */
+    pub const CONTENTS_SIZE : &'static str = "size";
+    pub const CONTENTS_LINES : &'static str = "lines";
+    pub const CONTENTS_ENCODING : &'static str = "encoding";
+    pub const CONTENTS_BINARY : &'static str = "binary";
+    /** Set to the object id of git LFS objects that were downloaded and stored instead of their pointer files. 
+     */
+    pub const CONTENTS_LFS : &'static str = "lfs";
+    /** Set to the number of secrets redacted from the stored contents. Contents without secrets, or stored while secrets were not scrubbed, have no such metadata. 
+     */
+    pub const CONTENTS_REDACTED : &'static str = "redacted";
+
+    /** Number of leading bytes of contents inspected to determine whether the contents are binary. 
+     
+        Same as git, contents are considered binary if they contain a zero byte in the first 8000 bytes. 
+     */
+    const BINARY_CHECK_LENGTH : usize = 8000;
+
+    /** Calculates the metadata of given file contents. 
+     */
+    pub fn of_contents(contents : & [u8]) -> Vec<Metadata> {
+        let encoding = Metadata::contents_encoding(contents);
+        let is_binary = encoding == "binary";
+        let lines = if is_binary || contents.is_empty() { 0 } else {
+            contents.iter().filter(|x| **x == b'\n').count() + if contents.ends_with(b"\n") { 0 } else { 1 }
+        };
+        return vec![
+            Metadata{ key : Metadata::CONTENTS_SIZE.to_owned(), value : contents.len().to_string() },
+            Metadata{ key : Metadata::CONTENTS_LINES.to_owned(), value : lines.to_string() },
+            Metadata{ key : Metadata::CONTENTS_ENCODING.to_owned(), value : encoding.to_owned() },
+            Metadata{ key : Metadata::CONTENTS_BINARY.to_owned(), value : is_binary.to_string() },
+        ];
+    }
+
+    /** Detects the encoding of the contents. 
+     
+        UTF-16 is only recognized by its byte order mark, as it would otherwise look like binary contents due to the zero bytes. 
+     */
+    fn contents_encoding(contents : & [u8]) -> &'static str {
+        if contents.starts_with(& [0xff, 0xfe]) {
+            return "utf-16le";
+        } else if contents.starts_with(& [0xfe, 0xff]) {
+            return "utf-16be";
+        } else if contents[.. contents.len().min(Metadata::BINARY_CHECK_LENGTH)].contains(& 0) {
+            return "binary";
+        } else if contents.is_ascii() {
+            return "ascii";
+        } else if std::str::from_utf8(contents).is_ok() {
+            return "utf-8";
+        } else {
+            return "unknown";
+        }
+    }
 }
 
 impl Serializable for Metadata {
     type Item = Metadata;
-    fn serialize(f : & mut File, value : & Metadata) {
+    fn serialize(f : & mut dyn TableStorage, value : & Metadata) {
         String::serialize(f, & value.key);
         String::serialize(f, & value.value);
     }
 
-    fn deserialize(f : & mut File) -> Metadata {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> Metadata {
         return Metadata {
             key : String::deserialize(f),
             value : String::deserialize(f),
         };
     }
 
-    fn verify(f : & mut File) -> Result<Metadata, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<Metadata, std::io::Error> {
         return Ok(Metadata{
             key : String::verify(f)?,
             value : String::verify(f)?,
@@ -956,6 +2193,8 @@ impl Serializable for Metadata {
     }
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CommitInfo {
     pub committer : UserId,
     pub committer_time : i64,
@@ -985,7 +2224,7 @@ impl ReadOnly for CommitInfo {
 
 impl Serializable for CommitInfo {
     type Item = CommitInfo;
-    fn serialize(f : & mut File, value : & CommitInfo) {
+    fn serialize(f : & mut dyn TableStorage, value : & CommitInfo) {
         u64::serialize(f, & u64::from(value.committer));
         i64::serialize(f, & value.committer_time);
         u64::serialize(f, & u64::from(value.author));
@@ -1002,7 +2241,7 @@ impl Serializable for CommitInfo {
         String::serialize(f, & value.message);
     }
 
-    fn deserialize(f : & mut File) -> CommitInfo {
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> CommitInfo {
         let mut result = CommitInfo::new();
         result.committer = UserId::from(u64::deserialize(f));
         result.committer_time = i64::deserialize(f);
@@ -1024,7 +2263,7 @@ impl Serializable for CommitInfo {
         return result;
     }
 
-    fn verify(f : & mut File) -> Result<CommitInfo, std::io::Error> {
+    fn verify(f : & mut dyn TableStorage) -> Result<CommitInfo, std::io::Error> {
         let mut result = CommitInfo::new();
         result.committer = UserId::from(u64::verify(f)?);
         result.committer_time = i64::verify(f)?;
@@ -1053,5 +2292,573 @@ impl Serializable for CommitInfo {
     }
 }
 
+impl CommitInfo {
+    /** Maximum length of a word to be indexed. 
+     
+        Longer words are mostly hashes, urls and other noise that is not worth indexing. 
+     */
+    pub const MAX_WORD_LENGTH : usize = 32;
+
+    /** Splits given commit message (or search query) into the words used by the commit messages index. 
+     
+        Words are maximal runs of alphanumeric characters converted to lowercase, each word is returned only once. 
+     */
+    pub fn message_words(message : & str) -> HashSet<String> {
+        return message.split(|c : char| ! c.is_alphanumeric())
+            .filter(|w| ! w.is_empty() && w.chars().count() <= CommitInfo::MAX_WORD_LENGTH)
+            .map(|w| w.to_lowercase())
+            .collect();
+    }
+}
+
+/** Numbers of added and deleted lines of each path changed by a commit. 
+ 
+    Line stats are optional and stored in a table parallel to the commits info so that commits stored without them remain valid. Changes of binary files have no line stats and are not included. 
+ */
+pub type LineStats = HashMap<PathId, (u32, u32)>;
+
+impl Serializable for LineStats {
+    type Item = LineStats;
+    fn serialize(f : & mut dyn TableStorage, value : & LineStats) {
+        u32::serialize(f, & (value.len() as u32));
+        for (path, (additions, deletions)) in value {
+            u64::serialize(f, & u64::from(*path));
+            u32::serialize(f, additions);
+            u32::serialize(f, deletions);
+        }
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> LineStats {
+        let mut records = u32::deserialize(f);
+        let mut result = LineStats::new();
+        while records > 0 {
+            let path = PathId::from(u64::deserialize(f));
+            let additions = u32::deserialize(f);
+            let deletions = u32::deserialize(f);
+            result.insert(path, (additions, deletions));
+            records -= 1;
+        }
+        return result;
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<LineStats, std::io::Error> {
+        let mut records = u32::verify(f)?;
+        if records as u64 > MAX_BUFFER_LENGTH {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Too many line stats"));
+        }
+        let mut result = LineStats::new();
+        while records > 0 {
+            let path = PathId::from(u64::verify(f)?);
+            let additions = u32::verify(f)?;
+            let deletions = u32::verify(f)?;
+            result.insert(path, (additions, deletions));
+            records -= 1;
+        }
+        return Ok(result);
+    }
+}
+
+/** Files renamed by a commit as pairs of the old and new path ids. 
+ 
+    Renames are detected by git's similarity heuristics when the changes of a commit are calculated. In the commit's changes, a rename still appears as a deletion of the old path and an addition of the new one, the renames only record that the two paths are the same file. 
+ */
+pub type PathRenames = Vec<(PathId, PathId)>;
+
+impl Serializable for PathRenames {
+    type Item = PathRenames;
+    fn serialize(f : & mut dyn TableStorage, value : & PathRenames) {
+        u32::serialize(f, & (value.len() as u32));
+        for (old, new) in value {
+            u64::serialize(f, & u64::from(*old));
+            u64::serialize(f, & u64::from(*new));
+        }
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> PathRenames {
+        let records = u32::deserialize(f);
+        return (0..records).map(|_| {
+            let old = PathId::from(u64::deserialize(f));
+            let new = PathId::from(u64::deserialize(f));
+            return (old, new);
+        }).collect();
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<PathRenames, std::io::Error> {
+        let records = u32::verify(f)?;
+        if records as u64 > MAX_BUFFER_LENGTH {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Too many path renames"));
+        }
+        let mut result = PathRenames::new();
+        for _ in 0..records {
+            let old = PathId::from(u64::verify(f)?);
+            let new = PathId::from(u64::verify(f)?);
+            result.push((old, new));
+        }
+        return Ok(result);
+    }
+}
+
+/** A submodule changed by a commit.
+
+    Submodules are gitlink entries of the commit's tree, whose hash is the commit of the other repository the submodule is at. The url of that repository is taken from the `.gitmodules` file of the commit and is empty if the file does not describe the submodule, or its contents are not available.
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Submodule {
+    pub path : PathId,
+    /** The commit of the submodule's repository, zero if the submodule was removed.
+     */
+    #[cfg_attr(feature = "serde", serde(with = "serde_sha"))]
+    pub commit : SHA,
+    pub url : String,
+}
+
+/** Submodules changed by a commit.
+
+    Gitlinks are not files, so their changes are not included in the commit's changes.
+ */
+pub type CommitSubmodules = Vec<Submodule>;
+
+impl Serializable for CommitSubmodules {
+    type Item = CommitSubmodules;
+    fn serialize(f : & mut dyn TableStorage, value : & CommitSubmodules) {
+        u32::serialize(f, & (value.len() as u32));
+        for submodule in value {
+            u64::serialize(f, & u64::from(submodule.path));
+            SHA::serialize(f, & submodule.commit);
+            String::serialize(f, & submodule.url);
+        }
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> CommitSubmodules {
+        let records = u32::deserialize(f);
+        return (0..records).map(|_| {
+            return Submodule{
+                path : PathId::from(u64::deserialize(f)),
+                commit : SHA::deserialize(f),
+                url : String::deserialize(f),
+            };
+        }).collect();
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<CommitSubmodules, std::io::Error> {
+        let records = u32::verify(f)?;
+        if records as u64 > MAX_BUFFER_LENGTH {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Too many submodules"));
+        }
+        let mut result = CommitSubmodules::new();
+        for _ in 0..records {
+            result.push(Submodule{
+                path : PathId::from(u64::verify(f)?),
+                commit : SHA::verify(f)?,
+                url : String::verify(f)?,
+            });
+        }
+        return Ok(result);
+    }
+}
+
+/** What an entry of a stored tree refers to. 
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TreeEntryKind {
+    /** A file (or a symlink) with given contents hash. 
+     */
+    File(HashId),
+    /** A subdirectory. 
+     */
+    Tree(TreeId),
+    /** A submodule at given commit of its repository. 
+     */
+    Submodule(#[cfg_attr(feature = "serde", serde(with = "serde_sha"))] SHA),
+}
+
+/** An entry of a stored tree. 
+ 
+    The mode is the git file mode of the entry, which distinguishes executable files and symlinks from regular files. 
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreeEntry {
+    pub name : String,
+    pub mode : u32,
+    pub kind : TreeEntryKind,
+}
+
+/** A git tree, i.e. the contents of a directory in a commit. 
+ 
+    Trees are deduplicated by their git hashes, so the same directory contents are stored only once, regardless of how many commits and parent trees contain it. The entries are in the order git stores them. 
+ */
+pub type Tree = Vec<TreeEntry>;
+
+impl Serializable for Tree {
+    type Item = Tree;
+    fn serialize(f : & mut dyn TableStorage, value : & Tree) {
+        u32::serialize(f, & (value.len() as u32));
+        for entry in value {
+            String::serialize(f, & entry.name);
+            u32::serialize(f, & entry.mode);
+            match & entry.kind {
+                TreeEntryKind::File(id) => {
+                    u8::serialize(f, & 0);
+                    u64::serialize(f, & u64::from(*id));
+                },
+                TreeEntryKind::Tree(id) => {
+                    u8::serialize(f, & 1);
+                    u64::serialize(f, & u64::from(*id));
+                },
+                TreeEntryKind::Submodule(commit) => {
+                    u8::serialize(f, & 2);
+                    SHA::serialize(f, commit);
+                },
+            }
+        }
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> Tree {
+        let records = u32::deserialize(f);
+        return (0..records).map(|_| {
+            let name = String::deserialize(f);
+            let mode = u32::deserialize(f);
+            let kind = match u8::deserialize(f) {
+                0 => TreeEntryKind::File(HashId::from(u64::deserialize(f))),
+                1 => TreeEntryKind::Tree(TreeId::from(u64::deserialize(f))),
+                _ => TreeEntryKind::Submodule(SHA::deserialize(f)),
+            };
+            return TreeEntry{ name, mode, kind };
+        }).collect();
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<Tree, std::io::Error> {
+        let records = u32::verify(f)?;
+        if records as u64 > MAX_BUFFER_LENGTH {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Too many tree entries"));
+        }
+        let mut result = Tree::new();
+        for _ in 0..records {
+            let name = String::verify(f)?;
+            let mode = u32::verify(f)?;
+            let kind = match u8::verify(f)? {
+                0 => TreeEntryKind::File(HashId::from(u64::verify(f)?)),
+                1 => TreeEntryKind::Tree(TreeId::from(u64::verify(f)?)),
+                2 => TreeEntryKind::Submodule(SHA::verify(f)?),
+                x => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Invalid tree entry kind {}", x))),
+            };
+            result.push(TreeEntry{ name, mode, kind });
+        }
+        return Ok(result);
+    }
+}
+
+/** Postings of a word in the commit messages index. 
+ 
+    Each indexing pass appends the ids of the newly indexed commits whose messages contain the word, in ascending order. 
+ */
+pub type MessagePostings = Vec<CommitId>;
+
+impl Serializable for MessagePostings {
+    type Item = MessagePostings;
+    fn serialize(f : & mut dyn TableStorage, value : & MessagePostings) {
+        u32::serialize(f, & (value.len() as u32));
+        for id in value {
+            u64::serialize(f, & u64::from(*id));
+        }
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> MessagePostings {
+        let records = u32::deserialize(f);
+        return (0..records).map(|_| CommitId::from(u64::deserialize(f))).collect();
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<MessagePostings, std::io::Error> {
+        let records = u32::verify(f)?;
+        if records as u64 > MAX_BUFFER_LENGTH {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Too many message postings"));
+        }
+        let mut result = MessagePostings::new();
+        for _ in 0..records {
+            result.push(CommitId::from(u64::verify(f)?));
+        }
+        return Ok(result);
+    }
+}
+
+/** Commits of a project ordered by their time. 
+ 
+    Each entry is the id of a commit reachable from the project heads with its author and committer times. The entries are ordered by the committer time, commits with the same committer time by their ids. 
+ */
+pub type ProjectCommitTimes = Vec<(CommitId, i64, i64)>;
+
+impl Serializable for ProjectCommitTimes {
+    type Item = ProjectCommitTimes;
+    fn serialize(f : & mut dyn TableStorage, value : & ProjectCommitTimes) {
+        u32::serialize(f, & (value.len() as u32));
+        for (id, author_time, committer_time) in value {
+            u64::serialize(f, & u64::from(*id));
+            i64::serialize(f, author_time);
+            i64::serialize(f, committer_time);
+        }
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> ProjectCommitTimes {
+        let records = u32::deserialize(f);
+        return (0..records).map(|_| {
+            let id = CommitId::from(u64::deserialize(f));
+            let author_time = i64::deserialize(f);
+            let committer_time = i64::deserialize(f);
+            return (id, author_time, committer_time);
+        }).collect();
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<ProjectCommitTimes, std::io::Error> {
+        let records = u32::verify(f)?;
+        if records as u64 > MAX_BUFFER_LENGTH {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Too many project commits"));
+        }
+        let mut result = ProjectCommitTimes::new();
+        for _ in 0..records {
+            let id = CommitId::from(u64::verify(f)?);
+            let author_time = i64::verify(f)?;
+            let committer_time = i64::verify(f)?;
+            result.push((id, author_time, committer_time));
+        }
+        return Ok(result);
+    }
+}
+
+/** Projects containing a commit in the commit projects index. 
+ 
+    Each indexing pass appends the ids of the projects in which the commit has been found since the previous pass, in ascending order. 
+ */
+pub type CommitProjects = Vec<ProjectId>;
+
+impl Serializable for CommitProjects {
+    type Item = CommitProjects;
+    fn serialize(f : & mut dyn TableStorage, value : & CommitProjects) {
+        u32::serialize(f, & (value.len() as u32));
+        for id in value {
+            u64::serialize(f, & u64::from(*id));
+        }
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> CommitProjects {
+        let records = u32::deserialize(f);
+        return (0..records).map(|_| ProjectId::from(u64::deserialize(f))).collect();
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<CommitProjects, std::io::Error> {
+        let records = u32::verify(f)?;
+        if records as u64 > MAX_BUFFER_LENGTH {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Too many commit projects"));
+        }
+        let mut result = CommitProjects::new();
+        for _ in 0..records {
+            result.push(ProjectId::from(u64::verify(f)?));
+        }
+        return Ok(result);
+    }
+}
+
+/** Occurrences of a file contents hash in the hash occurrences index. 
+ 
+    Each occurrence is a commit that changed a path to the contents. Each indexing pass appends the occurrences found in the newly indexed commits, ordered by the commits. As most hashes occur only a few times, the occurrences are stored deflated without any header. 
+ */
+pub type HashOccurrences = Vec<(CommitId, PathId)>;
+
+impl Serializable for HashOccurrences {
+    type Item = HashOccurrences;
+    fn serialize(f : & mut dyn TableStorage, value : & HashOccurrences) {
+        let mut enc = flate2::write::DeflateEncoder::new(Vec::new(), Compression::default());
+        enc.write_u32::<LittleEndian>(value.len() as u32).unwrap();
+        for (commit, path) in value {
+            enc.write_u64::<LittleEndian>(u64::from(*commit)).unwrap();
+            enc.write_u64::<LittleEndian>(u64::from(*path)).unwrap();
+        }
+        let encoded = enc.finish().unwrap();
+        u32::serialize(f, & (encoded.len() as u32));
+        f.write_all(& encoded).unwrap();
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> HashOccurrences {
+        let len = u32::deserialize(f) as usize;
+        let mut encoded = vec![0; len];
+        f.read_exact(& mut encoded).unwrap();
+        let mut dec = flate2::read::DeflateDecoder::new(&encoded[..]);
+        let records = dec.read_u32::<LittleEndian>().unwrap();
+        return (0..records).map(|_| {
+            let commit = CommitId::from(dec.read_u64::<LittleEndian>().unwrap());
+            let path = PathId::from(dec.read_u64::<LittleEndian>().unwrap());
+            return (commit, path);
+        }).collect();
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<HashOccurrences, std::io::Error> {
+        let len = u32::verify(f)?;
+        if len as u64 > MAX_BUFFER_LENGTH {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Compressed hash occurrences too large"));
+        }
+        let mut encoded = vec![0; len as usize];
+        f.read_exact(& mut encoded)?;
+        let mut dec = flate2::read::DeflateDecoder::new(&encoded[..]);
+        let records = dec.read_u32::<LittleEndian>()?;
+        let mut result = HashOccurrences::new();
+        for _ in 0..records {
+            let commit = CommitId::from(dec.read_u64::<LittleEndian>()?);
+            let path = PathId::from(dec.read_u64::<LittleEndian>()?);
+            result.push((commit, path));
+        }
+        return Ok(result);
+    }
+}
+
+/** Structural metrics of a commit in the commit graph of the projects containing it. 
+ 
+    The depth is the length of the longest path from the commit to a root commit (a commit without parents), i.e. roots have depth 0. The descendants are the commits of a project that have the commit as their ancestor. As a commit may belong to many projects, the largest number of descendants over the projects containing the commit is stored. 
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommitMetrics {
+    pub parents : u32,
+    pub depth : u64,
+    pub descendants : u64,
+}
+
+impl CommitMetrics {
+    pub fn is_merge(& self) -> bool {
+        return self.parents > 1;
+    }
+}
+
+impl Serializable for CommitMetrics {
+    type Item = CommitMetrics;
+    fn serialize(f : & mut dyn TableStorage, value : & CommitMetrics) {
+        u32::serialize(f, & value.parents);
+        u64::serialize(f, & value.depth);
+        u64::serialize(f, & value.descendants);
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> CommitMetrics {
+        return CommitMetrics{
+            parents : u32::deserialize(f),
+            depth : u64::deserialize(f),
+            descendants : u64::deserialize(f),
+        };
+    }
 
+    fn verify(f : & mut dyn TableStorage) -> Result<CommitMetrics, std::io::Error> {
+        return Ok(CommitMetrics{
+            parents : u32::verify(f)?,
+            depth : u64::verify(f)?,
+            descendants : u64::verify(f)?,
+        });
+    }
+}
 
+/** An issue, or a pull request of a GitHub project. 
+ 
+    Issues are stored per project in a linked store in the substore the project belongs to. Whenever an issue is updated, its new version is appended so the latest record for each issue number is the current state of the issue. Timestamps are unix epoch seconds, with `closed_at` being 0 for open issues. 
+ */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Issue {
+    pub number : u64,
+    pub is_pull_request : bool,
+    pub state : String,
+    pub title : String,
+    pub author : String,
+    pub created_at : i64,
+    pub updated_at : i64,
+    pub closed_at : i64,
+    pub labels : Vec<String>,
+    pub body : String,
+}
+
+impl Issue {
+    /** Creates the issue record from the JSON object returned by the GitHub issues API. 
+     
+        Returns None if the json does not look like an issue. 
+     */
+    pub fn from_json(json : & json::JsonValue) -> Option<Issue> {
+        if ! json["number"].is_number() {
+            return None;
+        }
+        return Some(Issue{
+            number : json["number"].as_u64().unwrap(),
+            is_pull_request : ! json["pull_request"].is_null(),
+            state : json["state"].as_str().unwrap_or("").to_owned(),
+            title : json["title"].as_str().unwrap_or("").to_owned(),
+            author : json["user"]["login"].as_str().unwrap_or("").to_owned(),
+            created_at : Issue::parse_time(& json["created_at"]),
+            updated_at : Issue::parse_time(& json["updated_at"]),
+            closed_at : Issue::parse_time(& json["closed_at"]),
+            labels : json["labels"].members().filter_map(|x| x["name"].as_str().map(|x| x.to_owned())).collect(),
+            body : json["body"].as_str().unwrap_or("").to_owned(),
+        });
+    }
+
+    /** Converts the ISO 8601 timestamps used by GitHub to unix epoch, returning 0 for nulls and invalid values. 
+     */
+    fn parse_time(json : & json::JsonValue) -> i64 {
+        match json.as_str().map(|x| chrono::DateTime::parse_from_rfc3339(x)) {
+            Some(Ok(t)) => return t.timestamp(),
+            _ => return 0,
+        }
+    }
+}
+
+impl Serializable for Issue {
+    type Item = Issue;
+    fn serialize(f : & mut dyn TableStorage, value : & Issue) {
+        u64::serialize(f, & value.number);
+        u8::serialize(f, & (value.is_pull_request as u8));
+        String::serialize(f, & value.state);
+        String::serialize(f, & value.title);
+        String::serialize(f, & value.author);
+        i64::serialize(f, & value.created_at);
+        i64::serialize(f, & value.updated_at);
+        i64::serialize(f, & value.closed_at);
+        u16::serialize(f, & (value.labels.len() as u16));
+        for label in value.labels.iter() {
+            String::serialize(f, label);
+        }
+        String::serialize(f, & value.body);
+    }
+
+    fn deserialize<R : Read + ?Sized>(f : & mut R) -> Issue {
+        let number = u64::deserialize(f);
+        let is_pull_request = u8::deserialize(f) != 0;
+        let state = String::deserialize(f);
+        let title = String::deserialize(f);
+        let author = String::deserialize(f);
+        let created_at = i64::deserialize(f);
+        let updated_at = i64::deserialize(f);
+        let closed_at = i64::deserialize(f);
+        let mut num_labels = u16::deserialize(f);
+        let mut labels = Vec::new();
+        while num_labels > 0 {
+            labels.push(String::deserialize(f));
+            num_labels -= 1;
+        }
+        let body = String::deserialize(f);
+        return Issue{ number, is_pull_request, state, title, author, created_at, updated_at, closed_at, labels, body };
+    }
+
+    fn verify(f : & mut dyn TableStorage) -> Result<Issue, std::io::Error> {
+        let number = u64::verify(f)?;
+        let is_pull_request = u8::verify(f)? != 0;
+        let state = String::verify(f)?;
+        let title = String::verify(f)?;
+        let author = String::verify(f)?;
+        let created_at = i64::verify(f)?;
+        let updated_at = i64::verify(f)?;
+        let closed_at = i64::verify(f)?;
+        let mut num_labels = u16::verify(f)?;
+        let mut labels = Vec::new();
+        while num_labels > 0 {
+            labels.push(String::verify(f)?);
+            num_labels -= 1;
+        }
+        let body = String::verify(f)?;
+        return Ok(Issue{ number, is_pull_request, state, title, author, created_at, updated_at, closed_at, labels, body });
+    }
+}
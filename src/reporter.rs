@@ -51,7 +51,7 @@ impl TerminalReporter {
     fn reporter(& self) {
         while let Ok(msg) = self.rx.recv() {
             match msg {
-                TaskMessage::Start{name} => {
+                TaskMessage::Start{name, project : _, cancelled : _} => {
                     self.report_message(& name, format!("starting..."));
                 },
                 TaskMessage::Done{name} => {
@@ -82,6 +82,9 @@ impl TerminalReporter {
                 TaskMessage::Color{name, color} => {
                     let mut tasks = self.tasks.lock().unwrap();
                     *tasks.get_mut(& name).unwrap() = color;
+                },
+                TaskMessage::Deadline{name : _, deadline : _} => {
+                    // don't do anything 
                 }
             }
         }
@@ -21,15 +21,32 @@ mod reporter;
 #[allow(dead_code)]
 mod datastore;
 #[allow(dead_code)]
+mod substore_classifier;
+#[allow(dead_code)]
+mod license_classifier;
+#[allow(dead_code)]
 mod db;
 #[allow(dead_code)]
 mod task_verify_substore;
 #[allow(dead_code)]
+mod task_pack_substore;
+#[allow(dead_code)]
+mod task_index_messages;
+mod task_index_commit_projects;
+mod task_index_files;
+mod task_commit_metrics;
+#[allow(dead_code)]
+mod task_resolve_users;
+#[allow(dead_code)]
 mod datastore_maintenance_tasks;
 #[allow(dead_code)]
 mod task_update_substore;
 #[allow(dead_code)]
 mod task_update_repo;
+#[allow(dead_code)]
+mod task_update_issues;
+#[allow(dead_code)]
+mod csv_input;
 
 use settings::SETTINGS;
 use github::Github;
@@ -0,0 +1,185 @@
+use std::collections::*;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use crate::DatastoreView;
+use crate::table_readers;
+use crate::records::*;
+
+/** Filter of exported records evaluated by a user-provided script. 
+ 
+    The script is started once per export by `sh -c` and receives the records on its standard input, one json object per line, see `ProjectRecords` and `CommitRecords` for their fields. For each record it must print a single line to its standard output, `true` or `1` if the record is to be exported, `false` or `0` if not, and flush its output before reading the next record. Any other answer, or the script exiting before all records were answered is an error. 
+ 
+    Talking to the script over pipes keeps the filters independent of the language they are written in, so that selection logic of a study can be expressed without recompiling the exporters. 
+ */
+pub struct ScriptFilter {
+    command : String,
+    child : Child,
+    input : Option<BufWriter<ChildStdin>>,
+    output : BufReader<ChildStdout>,
+    pub accepted : usize,
+    pub rejected : usize,
+}
+
+impl ScriptFilter {
+
+    pub fn new(command : & str) -> Result<ScriptFilter, std::io::Error> {
+        let mut child = Command::new("sh").arg("-c").arg(command).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let input = BufWriter::new(child.stdin.take().unwrap());
+        let output = BufReader::new(child.stdout.take().unwrap());
+        return Ok(ScriptFilter{
+            command : command.to_owned(),
+            child,
+            input : Some(input),
+            output,
+            accepted : 0,
+            rejected : 0,
+        });
+    }
+
+    pub fn command(& self) -> & str {
+        return & self.command;
+    }
+
+    /** Sends the record to the script and returns whether the script accepts it. 
+     */
+    pub fn accepts(& mut self, record : & json::JsonValue) -> Result<bool, std::io::Error> {
+        let input = self.input.as_mut().unwrap();
+        if let Err(e) = writeln!(input, "{}", record.dump()).and_then(|_| input.flush()) {
+            return Err(std::io::Error::new(e.kind(), format!("Unable to send record to filter script {}: {}", self.command, e)));
+        }
+        let mut answer = String::new();
+        if self.output.read_line(& mut answer)? == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, format!("Filter script {} exited without answering", self.command)));
+        }
+        match answer.trim() {
+            "true" | "1" => {
+                self.accepted += 1;
+                return Ok(true);
+            },
+            "false" | "0" => {
+                self.rejected += 1;
+                return Ok(false);
+            },
+            x => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid answer of filter script {}: {}", self.command, x)));
+            }
+        }
+    }
+}
+
+impl Drop for ScriptFilter {
+    /** Closes the input of the script so that it terminates and waits for it. 
+     */
+    fn drop(& mut self) {
+        self.input.take();
+        let _ = self.child.wait();
+    }
+}
+
+/** Project records given to filter scripts. 
+ 
+    Each record is a json object with `kind` set to `project`, the `id`, clone `url` and `substore` of the project (null if the project is not assigned to any), the time of its latest update as `updated` (null if never updated), its current `labels`, its latest `summary` (an object with `commits`, `authors`, `committers`, `first_commit_time` and `last_commit_time`, or null) and the latest values of its metadata as the `metadata` object of strings. The GitHub metadata, if present, are also given already parsed as `github`. 
+ 
+    Only the project tables are opened when the records are created, the record of each project is read when asked for by following the links of the project in the tables, so that memory used by the records does not grow with the number of projects. 
+ */
+pub struct ProjectRecords {
+    urls : table_readers::StoreReader<ProjectUrl, ProjectId>,
+    substores : table_readers::StoreReader<StoreKind, ProjectId>,
+    updates : table_readers::LinkedStoreReader<ProjectLog, ProjectId>,
+    labels : table_readers::StoreReader<ProjectLabels, ProjectId>,
+    summaries : table_readers::LinkedStoreReader<ProjectSummary, ProjectId>,
+    metadata : table_readers::LinkedStoreReader<Metadata, ProjectId>,
+}
+
+impl ProjectRecords {
+
+    pub fn new(ds : & DatastoreView) -> ProjectRecords {
+        return ProjectRecords{
+            urls : ds.project_urls(),
+            substores : ds.project_substores_reader(),
+            updates : ds.project_updates_reader(),
+            labels : ds.project_labels_reader(),
+            summaries : ds.project_summaries_reader(),
+            metadata : ds.project_metadata_reader(),
+        };
+    }
+
+    /** Returns the record of given project, or None if there is no such project. 
+     */
+    pub fn get(& self, id : ProjectId) -> Option<json::JsonValue> {
+        let url = self.urls.get(id)?;
+        let mut result = json::object!{
+            "kind" => "project",
+            "id" => u64::from(id),
+            "url" => url.clone_url(),
+            "substore" => match self.substores.get(id) {
+                Some(x) if x != StoreKind::Unspecified => format!("{:?}", x).into(),
+                _ => json::JsonValue::Null,
+            },
+            "updated" => self.updates.get(id).map(|x| json::JsonValue::from(x.time())).unwrap_or(json::JsonValue::Null),
+            "labels" => self.labels.get(id).unwrap_or_default(),
+            "summary" => json::JsonValue::Null,
+            "metadata" => json::JsonValue::new_object(),
+        };
+        if let Some(summary) = self.summaries.get(id) {
+            result["summary"] = json::object!{
+                "commits" => summary.commits,
+                "authors" => summary.authors,
+                "committers" => summary.committers,
+                "first_commit_time" => summary.first_commit_time,
+                "last_commit_time" => summary.last_commit_time,
+            };
+        }
+        // the records of the project are read latest first, so only the first value of each key is kept
+        let mut metadata = HashMap::<String, String>::new();
+        for md in self.metadata.iter_id(id) {
+            metadata.entry(md.key).or_insert(md.value);
+        }
+        for (key, value) in metadata.iter() {
+            result["metadata"][key.as_str()] = value.as_str().into();
+        }
+        if let Some(github) = metadata.get(Metadata::GITHUB_METADATA).and_then(|x| json::parse(x).ok()) {
+            result["github"] = github;
+        }
+        return Some(result);
+    }
+}
+
+/** Commit records of a substore given to filter scripts. 
+ 
+    Each record is a json object with `kind` set to `commit`, the `substore`, `id` and `hash` of the commit, the emails of its `author` and `committer` together with the `author_time` and `committer_time`, its `message`, the hashes of its `parents` and the number of paths it `changes`. 
+ */
+pub struct CommitRecords {
+    substore : StoreKind,
+    hashes : table_readers::MappingReader<SHA, CommitId>,
+    users : table_readers::IndirectMappingReader<String, UserId>,
+}
+
+impl CommitRecords {
+
+    pub fn new(ds : & DatastoreView, substore : StoreKind) -> CommitRecords {
+        return CommitRecords{
+            substore,
+            hashes : ds.commits(substore),
+            users : ds.users(substore),
+        };
+    }
+
+    pub fn get(& self, id : CommitId, commit : & CommitInfo) -> json::JsonValue {
+        let hash = |id : CommitId| self.hashes.get(id).map(|x| x.to_string()).unwrap_or_default();
+        return json::object!{
+            "kind" => "commit",
+            "substore" => format!("{:?}", self.substore),
+            "id" => u64::from(id),
+            "hash" => hash(id),
+            "author" => self.users.get(commit.author).unwrap_or_default(),
+            "author_time" => commit.author_time,
+            "committer" => self.users.get(commit.committer).unwrap_or_default(),
+            "committer_time" => commit.committer_time,
+            "message" => commit.message.as_str(),
+            "parents" => commit.parents.iter().map(|x| hash(*x)).collect::<Vec<String>>(),
+            "changes" => commit.changes.len(),
+        };
+    }
+}
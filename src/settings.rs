@@ -1,3 +1,7 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::records::StoreKind;
+use crate::csv_input::CsvOptions;
 
 lazy_static! {
     pub static ref SETTINGS : Settings = Settings::parse_from_commandline();
@@ -9,6 +13,98 @@ pub struct Settings {
     pub datastore_root : String, 
     pub github_tokens : String,
     pub num_threads : usize,
+    /** Maximum number of GitHub API requests in flight at the same time, shared by all workers. 
+     */
+    pub github_max_requests : usize,
+    /** Number of seconds after which the metadata of GitHub projects are refreshed by the metadata updates, independently of their regular updates. One day by default. 
+     */
+    pub metadata_interval : u64,
+    pub delta_contents : bool,
+    pub line_stats : bool,
+    /** Stores the trees of analyzed commits, deduplicated across commits. 
+     */
+    pub trees : bool,
+    /** Enables per-record checksums for the datastore, which stays enabled once set. 
+     */
+    pub checksums : bool,
+    /** Enables the anonymization of user emails for new datastores, which stays enabled once set. 
+     */
+    pub anonymize_emails : bool,
+    /** File with the key of the anonymized user emails, `email-key` in the datastore root by default. 
+     */
+    pub email_key : Option<String>,
+    /** When the changes of the datastore tables are made durable. Tables are never synced explicitly by default, so a crash, or power failure may lose their latest changes. 
+     */
+    pub fsync : Option<FsyncPolicy>,
+    /** Limits of a single project clone, projects exceeding any of them are skipped. No limits by default. 
+     */
+    pub max_clone_time : Option<u64>,
+    pub max_clone_objects : Option<usize>,
+    pub max_clone_size : Option<usize>,
+    /** Repositories larger than the threshold (in bytes, as reported by GitHub) are cloned partially without file contents, which are fetched on demand. Requires the `git` binary. 
+     */
+    pub partial_clone_threshold : Option<u64>,
+    /** Maximum total size (in bytes) of the repository clones kept between updates of their projects. Clones are not kept by default. 
+     */
+    pub clone_cache : Option<u64>,
+    /** Directory where the repositories are cloned instead of the datastore root, e.g. on a faster scratch disk. None by default. 
+     */
+    pub tmp_dir : Option<String>,
+    /** Maximum size (in bytes) of git LFS objects that are downloaded and stored instead of their pointer files. LFS objects are not downloaded by default. 
+     */
+    pub lfs_fetch_limit : Option<u64>,
+    /** What happens to snapshotted contents that contain secrets, such as access tokens or private keys. Contents are not scanned for secrets by default. 
+     */
+    pub scrub_secrets : Option<ScrubSecrets>,
+    /** Whether the urls of added projects are checked for redirects, so that renamed repositories already in the datastore are not added again. Disabled by default. 
+     */
+    pub resolve_redirects : bool,
+    /** Whether only the bloom filters of the commits, hashes and paths mappings are loaded when updating a substore instead of the whole mappings. Disabled by default. 
+     */
+    pub bloom_mappings : bool,
+    /** Memory budget (in bytes) of the commits, hashes and paths mappings of a substore, which are then indexed on disk instead of loaded. None by default. 
+     */
+    pub mapping_memory : Option<u64>,
+    /** Memory budget (in bytes) of the loaded substores. When set, the updater loads and drops substores based on the queued updates. None by default. 
+     */
+    pub preload_memory : Option<u64>,
+    /** Disk quotas of the substores in bytes. Updates of projects in substores that exceed their quota are paused until space is freed, unless metadata only mode is enabled, in which case the updates continue, but no file contents are stored. 
+     */
+    pub substore_quotas : HashMap<StoreKind, u64>,
+    /** Glob patterns of paths whose contents are stored, and of paths whose contents are not stored. Patterns with a substore only apply to projects in that substore, the others apply to all substores. 
+     */
+    pub include_paths : Vec<(Option<StoreKind>, String)>,
+    pub exclude_paths : Vec<(Option<StoreKind>, String)>,
+    pub quota_metadata_only : bool,
+    /** Number of seconds without any progress reported after which the watchdog cancels a task. Disabled by default. 
+     */
+    pub task_timeout : Option<u64>,
+    /** Shell command and webhook url notified of crawl milestones (completed substore passes, high error rates and exceeded quotas). None by default. 
+     */
+    pub notify_command : Option<String>,
+    pub notify_url : Option<String>,
+    /** Percentage of failed tasks per notification window above which the hooks are notified. 
+     */
+    pub notify_error_rate : f64,
+    /** Free space (in bytes) and free inodes of the datastore and temporary volumes below which the status header turns red and the hooks are notified. 
+     */
+    pub min_free_space : u64,
+    pub min_free_inodes : u64,
+    /** Options of the CSV files projects are added from. 
+     */
+    pub csv : CsvOptions,
+    /** Names of the built-in hooks run after every project update that changed the project. 
+     */
+    pub update_hooks : Vec<String>,
+    /** Limits of the clones from each git host, i.e. the number of concurrent clones and their total bandwidth. The limits of `*` apply to the hosts without limits of their own except github.com, which is not limited unless given explicitly. By default, at most 2 concurrent clones are made from each such host. 
+     */
+    pub host_limits : HashMap<String, HostLimit>,
+    /** Proxy all network requests go through, i.e. the clones, the GitHub API, LFS downloads and notifications. Taken from the `https_proxy`, or `all_proxy` environment variables by default. 
+     */
+    pub proxy : Option<String>,
+    /** Config file the settings were read from before the commandline arguments, if any. 
+     */
+    pub config : Option<String>,
     pub command : Vec<String>,
 }
 
@@ -17,46 +113,491 @@ impl Settings {
         return Settings{
             interactive : false,
             verbose : false,
-            //datastore_root : ".".to_owned(),
-            //github_tokens : Some("github-tokens.csv".to_owned());
-            datastore_root : "/dejavuii/dcd3".to_owned(),
-            github_tokens : "/mnt/data/github-tokens.csv".to_owned(),
+            datastore_root : ".".to_owned(),
+            github_tokens : "github-tokens.csv".to_owned(),
             num_threads : 16,
+            github_max_requests : 32,
+            metadata_interval : 24 * 60 * 60,
+            delta_contents : false,
+            line_stats : false,
+            trees : false,
+            checksums : false,
+            anonymize_emails : false,
+            email_key : None,
+            fsync : None,
+            max_clone_time : None,
+            max_clone_objects : None,
+            max_clone_size : None,
+            partial_clone_threshold : None,
+            clone_cache : None,
+            tmp_dir : None,
+            lfs_fetch_limit : None,
+            scrub_secrets : None,
+            resolve_redirects : false,
+            bloom_mappings : false,
+            mapping_memory : None,
+            preload_memory : None,
+            substore_quotas : HashMap::new(),
+            include_paths : Vec::new(),
+            exclude_paths : Vec::new(),
+            quota_metadata_only : false,
+            task_timeout : None,
+            notify_command : None,
+            notify_url : None,
+            notify_error_rate : 50.0,
+            min_free_space : 10 * 1024 * 1024 * 1024,
+            min_free_inodes : 100000,
+            csv : CsvOptions::new(),
+            update_hooks : Vec::new(),
+            host_limits : vec![(HostLimit::DEFAULT_HOST.to_owned(), HostLimit{ connections : Some(2), bandwidth : None })].into_iter().collect(),
+            proxy : proxy_from_env(),
+            config : None,
             command : Vec::new(),
         };
     }
 
     /** Parses the commandline arguments into the global settings and returns the remaining command. 
+     
+        If a config file is given by `--config`, its options are applied first so that the options given on the commandline override them. 
      */
     fn parse_from_commandline() -> Settings {
+        let args : Vec<String> = std::env::args().skip(1).collect();
         let mut settings = Settings::default();
-        let args : Vec<String> = std::env::args().collect();
-        let mut arg_i = 1;
+        let command_start = settings.parse_args(& args).unwrap_or_else(|e| panic!("{}", e));
+        if let Some(config) = settings.config.clone() {
+            settings = Settings::default();
+            if let Err(e) = settings.apply_config(& config) {
+                panic!("Invalid config file {}", e);
+            }
+            // the commandline has already been parsed successfully above
+            settings.parse_args(& args).unwrap();
+        }
+        // the rest of arguments form the command (or commands)
+        settings.command = args[command_start..].iter().map(|x| { x.to_owned() }).collect();
+        return settings;
+    }
+
+    /** Applies the options of given config file to the settings. 
+     
+        Invalid values of the options are reported with the line of the file, just like the errors of the file itself (see `read_config`). 
+     */
+    fn apply_config(& mut self, filename : & str) -> Result<(), std::io::Error> {
+        for (line, args) in read_config(filename)? {
+            self.parse_args(& args).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}:{}: {}", filename, line, e)))?;
+        }
+        return Ok(());
+    }
+
+    /** Applies the options at the beginning of given arguments to the settings and returns the index of the first argument that is not an option. 
+     
+        Fails with the description of the first missing, or invalid option value. 
+     */
+    fn parse_args(& mut self, args : & [String]) -> Result<usize, String> {
+        let mut arg_i = 0;
         while arg_i < args.len() {
             let arg = & args[arg_i];
             if arg == "-ds" || arg == "--datastore" {
-                settings.datastore_root = args.get(arg_i + 1).expect("Datastore root path missing").to_owned();
+                self.datastore_root = args.get(arg_i + 1).ok_or("Datastore root path missing")?.to_owned();
                 arg_i += 2;
             } else if arg == "-i" || arg == "--interactive" {
-                settings.interactive = true;
+                self.interactive = true;
                 arg_i += 1;
             } else if arg == "-v" || arg == "--verbose" {
-                settings.verbose = true;
+                self.verbose = true;
                 arg_i += 1;
             } else if arg == "-ght" || arg == "--github-tokens" {
-                settings.github_tokens = args.get(arg_i + 1).expect("Github tokens path missing").to_owned();
+                self.github_tokens = args.get(arg_i + 1).ok_or("Github tokens path missing")?.to_owned();
+                arg_i += 2;
+            } else if arg == "--delta-contents" {
+                self.delta_contents = true;
+                arg_i += 1;
+            } else if arg == "--line-stats" {
+                self.line_stats = true;
+                arg_i += 1;
+            } else if arg == "--trees" {
+                self.trees = true;
+                arg_i += 1;
+            } else if arg == "--checksums" {
+                self.checksums = true;
+                arg_i += 1;
+            } else if arg == "--anonymize-emails" {
+                self.anonymize_emails = true;
+                arg_i += 1;
+            } else if arg == "--email-key" {
+                self.email_key = Some(args.get(arg_i + 1).ok_or("Email key file missing")?.to_owned());
+                arg_i += 2;
+            } else if arg == "--fsync" {
+                self.fsync = Some(match args.get(arg_i + 1).ok_or("Fsync policy missing")?.as_str() {
+                    "task" => FsyncPolicy::Task,
+                    "savepoint" => FsyncPolicy::Savepoint,
+                    x => FsyncPolicy::Written(x.parse::<u64>().ok().filter(|x| *x > 0).ok_or_else(|| format!("Unknown fsync policy {}, must be task, savepoint, or number of megabytes", x))? * 1024 * 1024),
+                });
+                arg_i += 2;
+            } else if arg == "--max-clone-time" {
+                self.max_clone_time = Some(parse_number::<u64>(args, arg_i, "Maximum clone time")?);
+                arg_i += 2;
+            } else if arg == "--max-clone-objects" {
+                self.max_clone_objects = Some(parse_number::<usize>(args, arg_i, "Maximum number of cloned objects")?);
+                arg_i += 2;
+            } else if arg == "--max-clone-size" {
+                self.max_clone_size = Some(parse_number::<usize>(args, arg_i, "Maximum clone size")?);
+                arg_i += 2;
+            } else if arg == "--partial-clone-threshold" {
+                self.partial_clone_threshold = Some(parse_number::<u64>(args, arg_i, "Partial clone threshold")?);
+                arg_i += 2;
+            } else if arg == "--clone-cache" {
+                self.clone_cache = Some(parse_number::<u64>(args, arg_i, "Clone cache size")?);
+                arg_i += 2;
+            } else if arg == "--tmp-dir" {
+                self.tmp_dir = Some(args.get(arg_i + 1).ok_or("Temporary directory missing")?.to_owned());
+                arg_i += 2;
+            } else if arg == "--lfs-fetch" {
+                self.lfs_fetch_limit = Some(parse_number::<u64>(args, arg_i, "LFS object size limit")?);
+                arg_i += 2;
+            } else if arg == "--scrub-secrets" {
+                self.scrub_secrets = Some(match args.get(arg_i + 1).ok_or("Secrets scrubbing policy missing")?.as_str() {
+                    "skip" => ScrubSecrets::Skip,
+                    "redact" => ScrubSecrets::Redact,
+                    x => return Err(format!("Unknown secrets scrubbing policy {}, must be skip or redact", x)),
+                });
+                arg_i += 2;
+            } else if arg == "--resolve-redirects" {
+                self.resolve_redirects = true;
+                arg_i += 1;
+            } else if arg == "--bloom-mappings" {
+                self.bloom_mappings = true;
+                arg_i += 1;
+            } else if arg == "--preload-memory" {
+                self.preload_memory = Some(parse_number::<u64>(args, arg_i, "Preload memory budget")?);
+                arg_i += 2;
+            } else if arg == "--mapping-memory" {
+                self.mapping_memory = Some(parse_number::<u64>(args, arg_i, "Mapping memory budget")?);
+                arg_i += 2;
+            } else if arg == "--quota" {
+                let quota = args.get(arg_i + 1).ok_or("Substore quota missing")?;
+                let (store, size) = quota.split_at(quota.find('=').ok_or("Substore quota must be in the form STORE=BYTES")?);
+                self.substore_quotas.insert(
+                    StoreKind::from_string(store).ok_or_else(|| format!("Unknown substore {}", store))?,
+                    size[1..].parse::<u64>().map_err(|_| format!("Invalid substore quota {}, must be in the form STORE=BYTES", quota))?
+                );
+                arg_i += 2;
+            } else if arg == "--include-paths" {
+                self.include_paths.push(parse_path_pattern(args.get(arg_i + 1).ok_or("Included paths pattern missing")?));
+                arg_i += 2;
+            } else if arg == "--exclude-paths" {
+                self.exclude_paths.push(parse_path_pattern(args.get(arg_i + 1).ok_or("Excluded paths pattern missing")?));
+                arg_i += 2;
+            } else if arg == "--quota-metadata-only" {
+                self.quota_metadata_only = true;
+                arg_i += 1;
+            } else if arg == "--task-timeout" {
+                self.task_timeout = Some(parse_number::<u64>(args, arg_i, "Task timeout")?);
+                arg_i += 2;
+            } else if arg == "--notify-command" {
+                self.notify_command = Some(args.get(arg_i + 1).ok_or("Notification command missing")?.to_owned());
+                arg_i += 2;
+            } else if arg == "--notify-url" {
+                self.notify_url = Some(args.get(arg_i + 1).ok_or("Notification url missing")?.to_owned());
+                arg_i += 2;
+            } else if arg == "--notify-error-rate" {
+                self.notify_error_rate = parse_number::<f64>(args, arg_i, "Notification error rate")?;
+                arg_i += 2;
+            } else if arg == "--min-free-space" {
+                self.min_free_space = parse_number::<u64>(args, arg_i, "Minimal free space")?;
+                arg_i += 2;
+            } else if arg == "--min-free-inodes" {
+                self.min_free_inodes = parse_number::<u64>(args, arg_i, "Minimal number of free inodes")?;
+                arg_i += 2;
+            } else if arg == "--csv-delimiter" {
+                self.csv.delimiter = CsvOptions::parse_char(args.get(arg_i + 1).ok_or("CSV delimiter missing")?).ok_or("Invalid CSV delimiter")?;
+                arg_i += 2;
+            } else if arg == "--csv-quote" {
+                self.csv.quote = CsvOptions::parse_char(args.get(arg_i + 1).ok_or("CSV quote missing")?).ok_or("Invalid CSV quote")?;
+                arg_i += 2;
+            } else if arg == "--csv-escape" {
+                self.csv.escape = Some(CsvOptions::parse_char(args.get(arg_i + 1).ok_or("CSV escape missing")?).ok_or("Invalid CSV escape")?);
+                arg_i += 2;
+            } else if arg == "--update-hook" {
+                self.update_hooks.push(args.get(arg_i + 1).ok_or("Update hook name missing")?.to_owned());
+                arg_i += 2;
+            } else if arg == "--host-connections" {
+                let (host, connections) = parse_host_limit(args.get(arg_i + 1).ok_or("Host connections limit missing")?)?;
+                self.host_limits.entry(host).or_default().connections = if connections == 0 { None } else { Some(connections as usize) };
+                arg_i += 2;
+            } else if arg == "--host-bandwidth" {
+                let (host, bandwidth) = parse_host_limit(args.get(arg_i + 1).ok_or("Host bandwidth limit missing")?)?;
+                self.host_limits.entry(host).or_default().bandwidth = if bandwidth == 0 { None } else { Some(bandwidth) };
+                arg_i += 2;
+            } else if arg == "--proxy" {
+                self.proxy = match args.get(arg_i + 1).ok_or("Proxy url missing")?.as_str() {
+                    "none" => None,
+                    x => Some(x.to_owned()),
+                };
+                arg_i += 2;
+            } else if arg == "--github-max-requests" {
+                self.github_max_requests = parse_number::<usize>(args, arg_i, "Number of GitHub requests")?;
+                arg_i += 2;
+            } else if arg == "--metadata-interval" {
+                self.metadata_interval = parse_number::<u64>(args, arg_i, "Metadata interval")?;
                 arg_i += 2;
             } else if arg == "-n" || arg == "--num-threads" {
-                settings.num_threads = args.get(arg_i + 1).expect("Number of threads missing").parse::<usize>().unwrap();
+                self.num_threads = parse_number::<usize>(args, arg_i, "Number of threads")?;
+                arg_i += 2;
+            } else if arg == "--config" {
+                self.config = Some(args.get(arg_i + 1).ok_or("Config file missing")?.to_owned());
                 arg_i += 2;
             } else {
                 break;
             }
         }
-        // the rest of arguments form the command (or commands)
-        settings.command = args[arg_i..].iter().map(|x| { x.to_owned() }).collect();
-        return settings;
+        return Ok(arg_i);
+    }
+
+    /** Returns the proxy to be given to curl, where empty string disables the proxy, including the one curl would otherwise take from the environment. 
+     */
+    pub fn curl_proxy(& self) -> & str {
+        return self.proxy.as_deref().unwrap_or("");
+    }
+
+    /** Returns the limits of the clones from given host. 
+     */
+    pub fn host_limit(& self, host : & str) -> HostLimit {
+        if let Some(limit) = self.host_limits.get(host) {
+            return *limit;
+        } else if host == "github.com" {
+            return HostLimit::default();
+        } else {
+            return self.host_limits.get(HostLimit::DEFAULT_HOST).cloned().unwrap_or_default();
+        }
+    }
+}
+
+/** Limits of the clones from a single git host. 
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HostLimit {
+    /** Maximum number of projects cloned from the host at the same time. 
+     */
+    pub connections : Option<usize>,
+    /** Maximum bandwidth in bytes per second, shared by all clones from the host. 
+     */
+    pub bandwidth : Option<u64>,
+}
+
+impl HostLimit {
+    /** Host whose limits apply to hosts without limits of their own. 
+     */
+    pub const DEFAULT_HOST : &'static str = "*";
+}
+
+/** Policies of making the changes of the datastore tables durable. 
+ 
+    Savepoints are always synced when any policy is selected, so that they never refer to data that may be lost. 
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /** The tables are synced after every task before the task is reported as done. 
+     */
+    Task,
+    /** The tables are synced in the background whenever given number of bytes has been written to them since the last sync. Tasks finishing while more than that is not synced wait for the sync. 
+     */
+    Written(u64),
+    /** The tables are only synced when savepoints are created. 
+     */
+    Savepoint,
+}
+
+/** Policies for snapshotted contents containing secrets. 
+ 
+    Contents with secrets are either not stored at all, or stored with the secrets replaced by their kind. 
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrubSecrets {
+    Skip,
+    Redact,
+}
+
+/** Returns the value of the option at given index parsed as a number. 
+ 
+    Fails with the name of the option if the value is missing, or invalid. 
+ */
+fn parse_number<T : std::str::FromStr>(args : & [String], arg_i : usize, what : & str) -> Result<T, String> {
+    let value = args.get(arg_i + 1).ok_or_else(|| format!("{} missing", what))?;
+    return value.parse::<T>().map_err(|_| format!("Invalid value of {}: {}", args[arg_i], value));
+}
+
+/** Returns the proxy from the environment variables, if any. 
+ 
+    The variables are checked in the same order as curl does for https urls, which are the only ones parasite accesses. 
+ */
+fn proxy_from_env() -> Option<String> {
+    for name in ["https_proxy", "HTTPS_PROXY", "all_proxy", "ALL_PROXY"].iter() {
+        if let Ok(value) = std::env::var(name) {
+            if ! value.is_empty() {
+                return Some(value);
+            }
+        }
+    }
+    return None;
+}
+
+/** Parses a host limit argument in the form `HOST=NUMBER`, where host may be `*` for the hosts without limits of their own. 
+ */
+fn parse_host_limit(arg : & str) -> Result<(String, u64), String> {
+    let (host, value) = arg.split_once('=').ok_or_else(|| format!("Host limit must be in the form HOST=NUMBER, not {}", arg))?;
+    return Ok((host.to_lowercase(), value.parse::<u64>().map_err(|_| format!("Invalid host limit {}, must be in the form HOST=NUMBER", arg))?));
+}
+
+/** Parses a path pattern argument, which is either a glob, or a substore followed by `=` and the glob. 
+ */
+fn parse_path_pattern(arg : & str) -> (Option<StoreKind>, String) {
+    if let Some(i) = arg.find('=') {
+        if let Some(substore) = StoreKind::from_string(& arg[..i]) {
+            return (Some(substore), arg[i + 1..].to_owned());
+        }
+    }
+    return (None, arg.to_owned());
+}
+
+/** Kinds of values of the options in the config file. 
+ */
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ConfigKind {
+    /** Boolean, true corresponds to giving the flag on the commandline. 
+     */
+    Flag,
+    Integer,
+    Float,
+    Text,
+    /** Array of strings, each of which corresponds to one occurrence of the option on the commandline. 
+     */
+    List,
+}
+
+/** Options that can be given in the config file, named as their long commandline forms. 
+ */
+const CONFIG_OPTIONS : [(& str, ConfigKind); 43] = [
+    ("datastore", ConfigKind::Text),
+    ("interactive", ConfigKind::Flag),
+    ("verbose", ConfigKind::Flag),
+    ("github-tokens", ConfigKind::Text),
+    ("num-threads", ConfigKind::Integer),
+    ("github-max-requests", ConfigKind::Integer),
+    ("metadata-interval", ConfigKind::Integer),
+    ("delta-contents", ConfigKind::Flag),
+    ("line-stats", ConfigKind::Flag),
+    ("trees", ConfigKind::Flag),
+    ("checksums", ConfigKind::Flag),
+    ("anonymize-emails", ConfigKind::Flag),
+    ("email-key", ConfigKind::Text),
+    ("fsync", ConfigKind::Text),
+    ("max-clone-time", ConfigKind::Integer),
+    ("max-clone-objects", ConfigKind::Integer),
+    ("max-clone-size", ConfigKind::Integer),
+    ("partial-clone-threshold", ConfigKind::Integer),
+    ("clone-cache", ConfigKind::Integer),
+    ("tmp-dir", ConfigKind::Text),
+    ("lfs-fetch", ConfigKind::Integer),
+    ("scrub-secrets", ConfigKind::Text),
+    ("resolve-redirects", ConfigKind::Flag),
+    ("bloom-mappings", ConfigKind::Flag),
+    ("mapping-memory", ConfigKind::Integer),
+    ("preload-memory", ConfigKind::Integer),
+    ("quota", ConfigKind::List),
+    ("quota-metadata-only", ConfigKind::Flag),
+    ("include-paths", ConfigKind::List),
+    ("exclude-paths", ConfigKind::List),
+    ("task-timeout", ConfigKind::Integer),
+    ("notify-command", ConfigKind::Text),
+    ("notify-url", ConfigKind::Text),
+    ("notify-error-rate", ConfigKind::Float),
+    ("min-free-space", ConfigKind::Integer),
+    ("min-free-inodes", ConfigKind::Integer),
+    ("csv-delimiter", ConfigKind::Text),
+    ("csv-quote", ConfigKind::Text),
+    ("csv-escape", ConfigKind::Text),
+    ("update-hook", ConfigKind::List),
+    ("host-connections", ConfigKind::List),
+    ("host-bandwidth", ConfigKind::List),
+    ("proxy", ConfigKind::Text),
+];
+
+/** Reads the config file and returns its options as the equivalent commandline arguments, each with the line of the file it is given on. 
+ 
+    The config file is parsed as TOML, with strings, integers, floats, booleans and arrays as values. Tables are not supported. Keys are the long names of the commandline options, with either dashes, or underscores. Syntax errors, unknown options, values of wrong kinds and options given more than once are reported with the line of the file. 
+ */
+fn read_config(filename : & str) -> Result<Vec<(usize, Vec<String>)>, std::io::Error> {
+    let contents = std::fs::read_to_string(filename).map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", filename, e)))?;
+    let invalid = |line : usize, msg : String| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}:{}: {}", filename, line, msg));
+    let entries : std::collections::BTreeMap<String, toml::Spanned<toml::Value>> = toml::from_str(& contents)
+        .map_err(|e| invalid(e.line_col().map(|(line, _)| line + 1).unwrap_or(1), e.to_string()))?;
+    // options are returned in the order of the file
+    let mut entries : Vec<(String, toml::Spanned<toml::Value>)> = entries.into_iter().collect();
+    entries.sort_by_key(|(_, value)| value.start());
+    let mut result = Vec::new();
+    let mut seen = HashSet::<String>::new();
+    for (key, value) in entries {
+        let line = contents[.. value.start()].matches('\n').count() + 1;
+        let error = |msg : String| invalid(line, msg);
+        let key = key.replace('_', "-");
+        let kind = match CONFIG_OPTIONS.iter().find(|(name, _)| *name == key) {
+            Some((_, kind)) => *kind,
+            None => return Err(error(format!("unknown option {}", key))),
+        };
+        if ! seen.insert(key.clone()) {
+            return Err(error(format!("option {} given more than once", key)));
+        }
+        let option = format!("--{}", key);
+        let mut args = Vec::new();
+        match (kind, value.into_inner()) {
+            (ConfigKind::Flag, toml::Value::Boolean(x)) => {
+                if x {
+                    args.push(option);
+                }
+            },
+            (ConfigKind::Integer, toml::Value::Integer(x)) if x >= 0 => {
+                args.push(option);
+                args.push(x.to_string());
+            },
+            (ConfigKind::Float, toml::Value::Integer(x)) => {
+                args.push(option);
+                args.push(x.to_string());
+            },
+            (ConfigKind::Float, toml::Value::Float(x)) => {
+                args.push(option);
+                args.push(x.to_string());
+            },
+            (ConfigKind::Text, toml::Value::String(x)) => {
+                args.push(option);
+                args.push(x);
+            },
+            (ConfigKind::List, toml::Value::Array(values)) => {
+                for x in values {
+                    match x {
+                        toml::Value::String(x) => {
+                            args.push(option.clone());
+                            args.push(x);
+                        },
+                        _ => return Err(error(format!("option {} expects an array of strings", key))),
+                    }
+                }
+            },
+            (_, toml::Value::Table(_)) => {
+                return Err(error(format!("tables are not supported, {} is a table", key)));
+            },
+            (kind, _) => {
+                return Err(error(format!("option {} expects {}", key, match kind {
+                    ConfigKind::Flag => "true or false",
+                    ConfigKind::Integer => "a non-negative integer",
+                    ConfigKind::Float => "a number",
+                    ConfigKind::Text => "a string",
+                    ConfigKind::List => "an array of strings",
+                })));
+            }
+        }
+        result.push((line, args));
     }
+    return Ok(result);
 }
 
 #[macro_export]
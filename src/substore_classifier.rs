@@ -0,0 +1,156 @@
+use std::collections::*;
+
+use crate::records::*;
+
+/** Information about a project available when its substore is determined.
+ */
+pub struct ProjectFeatures<'a> {
+    /** The GitHub metadata of the project, None for plain git projects.
+     */
+    pub metadata : Option<&'a json::JsonValue>,
+    /** Number of commits in the project, counted up to the classifier's commits limit.
+     */
+    pub commits : usize,
+    /** Number of files of each contents kind in the most recent head of the project.
+
+        Empty unless the classifier asks for the languages.
+     */
+    pub languages : HashMap<ContentsKind, usize>,
+}
+
+/** Assigns projects to substores.
+
+    The classifier is called by the repository updater every time new commits of a project are fetched, with the substore the project is currently in (`StoreKind::Unspecified` for new projects). Returning a different substore moves the project, so that it will be analyzed in the new substore from now on. The returned substore must be specified.
+ */
+pub trait SubstoreClassifier : Send + Sync {
+    /** The number of commits up to which the commits of the project should be counted.
+
+        Counting stops at the limit, so the classifier can only tell that a project has *at least* this many commits.
+     */
+    fn commits_limit(& self) -> usize;
+
+    /** Returns true if the classifier uses the languages of the project's files, which are otherwise not determined.
+     */
+    fn uses_languages(& self) -> bool {
+        return false;
+    }
+
+    fn classify(& self, current : StoreKind, project : & ProjectFeatures) -> StoreKind;
+}
+
+/** The default substore classifier.
+
+    Projects with fewer commits than the threshold are kept in the `SmallProjects` substore. Larger projects go to the substore of their GitHub language, if there is such a substore, otherwise they keep their current substore, or go to the fallback substore if they have none. Optionally, the most frequent language of the project's files can be used when the GitHub language is not known.
+
+    The classifier can be configured per datastore by the `substore-classifier.csv` file in the datastore root, each line of which contains a setting and its value(s):
+
+    - `small-projects,N` sets the small projects threshold, 0 disables the small projects substore
+    - `language,L,S` sends projects of GitHub language `L` to substore `S`, overriding the default mapping of languages to substores of the same name
+    - `path-languages,true` uses the most frequent language of the project's files when the GitHub language is unknown
+    - `fallback,S` sets the substore for projects that could not be classified otherwise, `Generic` by default
+ */
+pub struct DefaultSubstoreClassifier {
+    pub small_projects_threshold : usize,
+    pub languages : HashMap<String, StoreKind>,
+    pub path_languages : bool,
+    pub fallback : StoreKind,
+}
+
+impl DefaultSubstoreClassifier {
+
+    /** Name of the file in the datastore root that configures the classifier.
+     */
+    pub const CONFIG_FILE : &'static str = "substore-classifier.csv";
+
+    pub fn new(small_projects_threshold : usize) -> DefaultSubstoreClassifier {
+        return DefaultSubstoreClassifier{
+            small_projects_threshold,
+            languages : HashMap::new(),
+            path_languages : false,
+            fallback : StoreKind::Generic,
+        };
+    }
+
+    /** Creates the classifier for the datastore at given root.
+
+        If the datastore contains the configuration file, its settings are applied on top of the defaults.
+     */
+    pub fn from_datastore(root : & str, small_projects_threshold : usize) -> Result<DefaultSubstoreClassifier, std::io::Error> {
+        let mut result = DefaultSubstoreClassifier::new(small_projects_threshold);
+        let filename = format!("{}/{}", root, DefaultSubstoreClassifier::CONFIG_FILE);
+        if ! std::path::Path::new(& filename).exists() {
+            return Ok(result);
+        }
+        for (i, line) in std::fs::read_to_string(& filename)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+            let parts : Vec<& str> = line.split(",").map(|x| x.trim()).collect();
+            let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}, line {}: invalid setting {}", filename, i + 1, line));
+            match (parts[0], parts.len()) {
+                ("small-projects", 2) => {
+                    result.small_projects_threshold = parts[1].parse::<usize>().map_err(|_| invalid())?;
+                },
+                ("language", 3) => {
+                    let substore = StoreKind::from_string(parts[2]).ok_or_else(invalid)?;
+                    result.languages.insert(parts[1].to_lowercase(), substore);
+                },
+                ("path-languages", 2) => {
+                    result.path_languages = parts[1].parse::<bool>().map_err(|_| invalid())?;
+                },
+                ("fallback", 2) => {
+                    result.fallback = StoreKind::from_string(parts[1]).ok_or_else(invalid)?;
+                },
+                _ => return Err(invalid()),
+            }
+        }
+        return Ok(result);
+    }
+
+    /** Returns the substore for given language, if any.
+     */
+    fn language_substore(& self, language : & str) -> Option<StoreKind> {
+        return match self.languages.get(& language.to_lowercase()) {
+            Some(substore) => Some(*substore),
+            None => StoreKind::from_string(language),
+        };
+    }
+}
+
+impl SubstoreClassifier for DefaultSubstoreClassifier {
+
+    fn commits_limit(& self) -> usize {
+        return self.small_projects_threshold;
+    }
+
+    fn uses_languages(& self) -> bool {
+        return self.path_languages;
+    }
+
+    fn classify(& self, current : StoreKind, project : & ProjectFeatures) -> StoreKind {
+        // projects stay small projects as long as they are under the threshold
+        if (current == StoreKind::Unspecified || current == StoreKind::SmallProjects) && project.commits < self.small_projects_threshold {
+            return StoreKind::SmallProjects;
+        }
+        // the language from the metadata takes precedence
+        if let Some(language) = project.metadata.and_then(|x| x["language"].as_str()) {
+            if let Some(substore) = self.language_substore(language) {
+                return substore;
+            }
+        }
+        // projects that are already in a language substore stay there
+        if current.is_specified() && current != StoreKind::SmallProjects && current != StoreKind::Generic {
+            return current;
+        }
+        // otherwise use the most frequent language of the files with a substore, if enabled
+        if self.path_languages {
+            if let Some((_, substore)) = project.languages.iter()
+                .filter_map(|(kind, count)| self.language_substore(& format!("{:?}", kind)).map(|substore| (*count, substore)))
+                .max_by_key(|(count, substore)| (*count, std::cmp::Reverse(*substore as u64))) {
+                return substore;
+            }
+        }
+        return self.fallback;
+    }
+}
@@ -0,0 +1,1102 @@
+/** Read-only memory mapped table readers.
+
+    The readers provide the same functionality as the tables in `db.rs` when opened readonly, but instead of seeking a file per table, they memory map the table files and read from them via shared immutable references. All getters therefore take `& self` and the readers can be shared between multiple analysis threads without any locking, or duplicating the file descriptors (the files are closed once mapped).
+
+    Each reader sees the table as it was when the reader was created, records added afterwards by a running updater are not visible. Index entries that are updated to point to such records are ignored as well.
+
+    Readers can also be pinned to a savepoint, in which case every file is capped at the size recorded in the savepoint and the reader sees the table exactly as it was when the savepoint was created, regardless of what has been written since.
+
+    For compatibility with the existing API, all readers also implement the `Table` trait, whose iterator-like interface keeps its own cursor and therefore requires `& mut self`.
+ */
+use std::fs::File;
+use std::io::{Read, Write, Seek, SeekFrom};
+use std::os::unix::io::AsRawFd;
+use std::hash::Hash;
+
+use crate::db::*;
+use crate::records::*;
+
+/** Read-only memory mapped file.
+
+    The file stays open and shared locked (`flock`) for as long as it is mapped. Tables only truncate their files when they can lock them exclusively (see `TableStorage::set_len` for files), so that the mapped pages never disappear under the reader, which would otherwise be killed by `SIGBUS` when touching them. 
+ */
+pub struct MappedFile {
+    ptr : * const u8,
+    len : usize,
+    _file : Option<File>,
+}
+
+/* The mapping is read-only and never changes once created so it can be safely shared between threads. */
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    /** Maps given file. 
+
+        If limit is given, only the first limit bytes of the file are mapped. Files that do not exist, such as tables of substores that have not been written to yet, are treated as empty. 
+     */
+    pub fn open(path : & str, limit : Option<u64>) -> MappedFile {
+        let f = match File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return MappedFile{ ptr : std::ptr::null(), len : 0, _file : None },
+            Err(e) => panic!("Error opening file {}: {}", path, e),
+        };
+        // the lock must be taken before the length is determined so that the file cannot be truncated in between
+        unsafe { libc::flock(f.as_raw_fd(), libc::LOCK_SH); }
+        let mut len = f.metadata().unwrap().len() as usize;
+        if let Some(limit) = limit {
+            len = len.min(limit as usize);
+        }
+        // empty files cannot be mapped
+        if len == 0 {
+            return MappedFile{ ptr : std::ptr::null(), len, _file : None };
+        }
+        let ptr = unsafe { libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_SHARED, f.as_raw_fd(), 0) };
+        if ptr == libc::MAP_FAILED {
+            panic!("Error mapping file {}: {}", path, std::io::Error::last_os_error());
+        }
+        return MappedFile{ ptr : ptr as * const u8, len, _file : Some(f) };
+    }
+
+    pub fn len(& self) -> usize {
+        return self.len;
+    }
+
+    pub fn as_slice(& self) -> & [u8] {
+        if self.len == 0 {
+            return & [];
+        }
+        return unsafe { std::slice::from_raw_parts(self.ptr, self.len) };
+    }
+
+    /** Deserializes value stored at given offset.
+
+        Returns the value and the offset immediately after it, or None if there is no valid value at the offset, such as when the value is torn by the end of the file. 
+     */
+    fn read<T : Serializable<Item = T>>(& self, offset : usize) -> Option<(T, usize)> {
+        if offset > self.len {
+            return None;
+        }
+        let mut data = SliceStorage{ data : & self.as_slice()[offset..], position : 0 };
+        let result = T::verify(& mut data).ok()?;
+        return Some((result, offset + data.position));
+    }
+}
+
+/** Read-only table storage of a slice of the mapped file. 
+
+    The values are read via their `verify` function, which, unlike `deserialize`, reports values that extend past the end of the slice, or are otherwise malformed, as errors instead of panicking. 
+ */
+struct SliceStorage<'a> {
+    data : &'a [u8],
+    position : usize,
+}
+
+impl<'a> Read for SliceStorage<'a> {
+    fn read(& mut self, buf : & mut [u8]) -> Result<usize, std::io::Error> {
+        let len = buf.len().min(self.data.len().saturating_sub(self.position));
+        buf[..len].copy_from_slice(& self.data[self.position..self.position + len]);
+        self.position += len;
+        return Ok(len);
+    }
+}
+
+impl<'a> Write for SliceStorage<'a> {
+    fn write(& mut self, _buf : & [u8]) -> Result<usize, std::io::Error> {
+        return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "Mapped files are read-only"));
+    }
+
+    fn flush(& mut self) -> Result<(), std::io::Error> {
+        return Ok(());
+    }
+}
+
+impl<'a> Seek for SliceStorage<'a> {
+    fn seek(& mut self, pos : SeekFrom) -> Result<u64, std::io::Error> {
+        let position = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.data.len() as i64 + offset,
+            SeekFrom::Current(offset) => self.position as i64 + offset,
+        };
+        if position < 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek before the start of the mapped file"));
+        }
+        self.position = position as usize;
+        return Ok(position as u64);
+    }
+}
+
+impl<'a> TableStorage for SliceStorage<'a> {
+    fn set_len(& mut self, _len : u64) -> Result<(), std::io::Error> {
+        return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "Mapped files are read-only"));
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(& mut self) {
+        if self.len > 0 {
+            unsafe { libc::munmap(self.ptr as * mut libc::c_void, self.len); }
+        }
+    }
+}
+
+/* Returns the size limit of given table file in the savepoint, if any. 
+
+   The savepoint entries are keyed by the table name without the substore directory and with the file's extension. 
+ */
+fn savepoint_limit(savepoint : Option<& Savepoint>, name : & str, extension : & str) -> Option<u64> {
+    let table = name.rsplit('/').next().unwrap();
+    return savepoint.map(|sp| sp.limit_for(& format!("{}.{}", table, extension)));
+}
+
+/** Reader for the checksums file of a store.
+
+    Checks the records read from the store against their checksums, records without checksums are not checked. A mismatch means the store is corrupted. The store readers treat a corrupted record as missing and keep the first mismatch they found, see `StoreReader::checksum_error`.
+ */
+pub struct ChecksumsReader {
+    path : String,
+    f : MappedFile,
+    error : std::sync::Mutex<Option<std::io::Error>>,
+}
+
+impl ChecksumsReader {
+    /** Opens the checksums file of given store, if there is one.
+     */
+    pub fn open(root : & str, name : & str, savepoint : Option<& Savepoint>) -> Option<ChecksumsReader> {
+        let path = Checksums::path(root, name);
+        if ! std::path::Path::new(& path).exists() {
+            return None;
+        }
+        return Some(ChecksumsReader{ f : MappedFile::open(& path, savepoint_limit(savepoint, name, "crc")), path, error : std::sync::Mutex::new(None) });
+    }
+
+    /** Checks the record at given offset of the store data, returns an error if its checksum does not match.
+     */
+    pub fn check(& self, data : & [u8], offset : usize, next : usize) -> Result<(), std::io::Error> {
+        let entries = self.f.len() / Checksums::ENTRY_SIZE as usize;
+        let entry_offset = |i : usize| self.f.read::<u64>(i * Checksums::ENTRY_SIZE as usize).map(|(x, _)| x).unwrap_or(u64::MAX);
+        let (mut low, mut high) = (0, entries);
+        while low < high {
+            let mid = (low + high) / 2;
+            if entry_offset(mid) < offset as u64 {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        if low < entries && entry_offset(low) == offset as u64 {
+            let crc = self.f.read::<u32>(low * Checksums::ENTRY_SIZE as usize + 8).map(|(x, _)| x).unwrap_or(0);
+            let actual = checksum(& data[offset..next]);
+            if crc != actual {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Corrupted store, checksum mismatch at offset {} (expected {:x}, found {:x}, checksums {})", offset, crc, actual, self.path)));
+            }
+        }
+        return Ok(());
+    }
+
+    /** Checks the record like `check`, but instead of returning the error keeps the first one found for `take_error`. Returns true if the record is valid.
+     */
+    fn is_valid(& self, data : & [u8], offset : usize, next : usize) -> bool {
+        if let Err(e) = self.check(data, offset, next) {
+            let mut error = self.error.lock().unwrap();
+            if error.is_none() {
+                *error = Some(e);
+            }
+            return false;
+        }
+        return true;
+    }
+
+    /** Returns the first checksum mismatch found since the last call, if any.
+     */
+    pub fn take_error(& self) -> Result<(), std::io::Error> {
+        return match self.error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        };
+    }
+}
+
+/** Reader for the indexer's file.
+ */
+pub struct IndexReader<T : Indexable + Serializable<Item = T>, ID : Id> {
+    f : MappedFile,
+    why_oh_why : std::marker::PhantomData<(T, ID)>,
+}
+
+impl<T : Indexable + Serializable<Item = T>, ID : Id> IndexReader<T, ID> {
+    pub fn new(root : & str, name : & str, savepoint : Option<& Savepoint>) -> IndexReader<T, ID> {
+        return IndexReader{ f : MappedFile::open(& format!("{}/{}.idx", root, name), savepoint_limit(savepoint, name, "idx")), why_oh_why : std::marker::PhantomData{} };
+    }
+
+    pub fn get(& self, id : ID) -> Option<T> {
+        if id.into() < self.len() as u64 {
+            let (result, _) = self.f.read::<T>((T::SIZE * id.into()) as usize)?;
+            if result != T::EMPTY {
+                return Some(result);
+            }
+        }
+        return None;
+    }
+
+    pub fn len(& self) -> usize {
+        return self.f.len() / T::SIZE as usize;
+    }
+}
+
+/** Reader for a `Store`.
+
+    Random access returns the latest record for given id, iteration returns all records in the order they were stored, i.e. multiple records for the same id may be returned, the last one being the latest.
+ */
+pub struct StoreReader<T : Serializable<Item = T>, ID : Id> {
+    indexer : IndexReader<u64, ID>,
+    f : MappedFile,
+    checksums : Option<ChecksumsReader>,
+    position : usize,
+    why_oh_why : std::marker::PhantomData<T>,
+}
+
+impl<T : Serializable<Item = T>, ID : Id> StoreReader<T, ID> {
+    pub fn new(root : & str, name : & str, savepoint : Option<& Savepoint>) -> StoreReader<T, ID> {
+        return StoreReader{
+            indexer : IndexReader::new(root, name, savepoint),
+            f : MappedFile::open(& format!("{}/{}.store", root, name), savepoint_limit(savepoint, name, "store")),
+            checksums : ChecksumsReader::open(root, name, savepoint),
+            position : 0,
+            why_oh_why : std::marker::PhantomData{},
+        };
+    }
+
+    pub fn get(& self, id : ID) -> Option<T> {
+        if let Some(offset) = self.indexer.get(id) {
+            if (offset as usize) < self.f.len() {
+                let (record_id, value) = self.read_record(offset as usize)?.0;
+                assert_eq!(id, record_id, "Corrupted store or index");
+                return Some(value);
+            }
+            // the latest record is not visible to the reader, but an older one might be, which can only be found by scanning the store
+            return self.iter().filter(|(record_id, _)| *record_id == id).last().map(|(_, value)| value);
+        }
+        return None;
+    }
+
+    /** Returns the latest values of given ids, in the order of the ids.
+
+        The records are read in the order of their offsets, i.e. in a single sequential sweep over the store, which is much faster than random gets of many ids when the store is not cached in memory.
+     */
+    pub fn get_many(& self, ids : & [ID]) -> Vec<Option<T>> {
+        let mut result : Vec<Option<T>> = ids.iter().map(|_| None).collect();
+        let mut offsets = Vec::new();
+        for (i, id) in ids.iter().enumerate() {
+            if let Some(offset) = self.indexer.get(*id) {
+                if (offset as usize) < self.f.len() {
+                    offsets.push((offset, i));
+                } else {
+                    result[i] = self.get(*id);
+                }
+            }
+        }
+        offsets.sort();
+        for (offset, i) in offsets {
+            if let Some(((record_id, value), _)) = self.read_record(offset as usize) {
+                assert_eq!(ids[i], record_id, "Corrupted store or index");
+                result[i] = Some(value);
+            }
+        }
+        return result;
+    }
+
+    pub fn has(& self, id : ID) -> bool {
+        if let Some(offset) = self.indexer.get(id) {
+            return (offset as usize) < self.f.len() || self.iter().any(|(record_id, _)| record_id == id);
+        }
+        return false;
+    }
+
+    /** Returns the number of indexed ids.
+     */
+    pub fn len(& self) -> usize {
+        return self.indexer.len();
+    }
+
+    /** Iterates over all records in the store.
+     */
+    pub fn iter<'a>(&'a self) -> StoreReaderIter<'a, T, ID> {
+        return StoreReaderIter{ store : self, position : 0 };
+    }
+
+    /** Returns the first checksum mismatch the reader found since the last call, if any.
+
+        Records whose checksums do not match are treated as missing, i.e. they are not returned by the getters and iteration stops at them like at a torn record, so callers that must not miss records should check this after reading.
+     */
+    pub fn checksum_error(& self) -> Result<(), std::io::Error> {
+        return self.checksums.as_ref().map(|x| x.take_error()).unwrap_or(Ok(()));
+    }
+
+    /** Reads the record at given offset, returns the id and value and the offset of the next record, or None if the record is torn by the end of the file, or its checksum does not match.
+     */
+    fn read_record(& self, offset : usize) -> Option<((ID, T), usize)> {
+        let (id, next) = self.f.read::<u64>(offset)?;
+        let (value, next) = self.f.read::<T>(next)?;
+        if let Some(checksums) = self.checksums.as_ref() {
+            if ! checksums.is_valid(self.f.as_slice(), offset, next) {
+                return None;
+            }
+        }
+        return Some(((ID::from(id), value), next));
+    }
+}
+
+impl<T : Serializable<Item = T>, ID : Id> Table for StoreReader<T, ID> {
+    type Id = ID;
+    type Value = T;
+
+    fn get_reset(& mut self) {
+        self.position = 0;
+    }
+
+    fn get_next(& mut self) -> Option<(ID, T)> {
+        if self.position < self.f.len() {
+            if let Some((result, next)) = self.read_record(self.position) {
+                self.position = next;
+                return Some(result);
+            }
+            // a torn record can only be the last one
+            self.position = self.f.len();
+        }
+        return None;
+    }
+
+    fn get(& mut self, id : ID) -> Option<T> {
+        return StoreReader::get(self, id);
+    }
+
+    fn filesize(& mut self) -> u64 {
+        return self.f.len() as u64;
+    }
+}
+
+impl<T : Serializable<Item = T>, ID : Id> IntoIterator for StoreReader<T, ID> {
+    type Item = (ID, T);
+    type IntoIter = TableOwningIterator<StoreReader<T, ID>>;
+
+    fn into_iter(self) -> TableOwningIterator<StoreReader<T, ID>> {
+        return TableOwningIterator::new(self);
+    }
+}
+
+pub struct StoreReaderIter<'a, T : Serializable<Item = T>, ID : Id> {
+    store : &'a StoreReader<T, ID>,
+    position : usize,
+}
+
+impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for StoreReaderIter<'a, T, ID> {
+    type Item = (ID, T);
+
+    fn next(& mut self) -> Option<(ID, T)> {
+        if self.position < self.store.f.len() {
+            if let Some((result, next)) = self.store.read_record(self.position) {
+                self.position = next;
+                return Some(result);
+            }
+            self.position = self.store.f.len();
+        }
+        return None;
+    }
+}
+
+/** Reader for a `LinkedStore`.
+
+    Like the store reader, but also allows iterating over all records of given id, from the latest to the oldest.
+ */
+pub struct LinkedStoreReader<T : Serializable<Item = T>, ID : Id> {
+    indexer : IndexReader<u64, ID>,
+    f : MappedFile,
+    checksums : Option<ChecksumsReader>,
+    position : usize,
+    why_oh_why : std::marker::PhantomData<T>,
+}
+
+impl<T : Serializable<Item = T>, ID : Id> LinkedStoreReader<T, ID> {
+    pub fn new(root : & str, name : & str, savepoint : Option<& Savepoint>) -> LinkedStoreReader<T, ID> {
+        return LinkedStoreReader{
+            indexer : IndexReader::new(root, name, savepoint),
+            f : MappedFile::open(& format!("{}/{}.store", root, name), savepoint_limit(savepoint, name, "store")),
+            checksums : ChecksumsReader::open(root, name, savepoint),
+            position : 0,
+            why_oh_why : std::marker::PhantomData{},
+        };
+    }
+
+    pub fn get(& self, id : ID) -> Option<T> {
+        return self.iter_id(id).next();
+    }
+
+    pub fn len(& self) -> usize {
+        return self.indexer.len();
+    }
+
+    /** Iterates over all records of given id, starting from the latest one.
+     */
+    pub fn iter_id<'a>(&'a self, id : ID) -> LinkedStoreReaderIterId<'a, T, ID> {
+        let mut offset = self.indexer.get(id).unwrap_or(u64::EMPTY);
+        // if the latest record is not visible to the reader, the links cannot be followed and the latest visible record has to be found by scanning the store, which is slow, but rare
+        if offset != u64::EMPTY && offset as usize >= self.f.len() {
+            offset = u64::EMPTY;
+            let mut position = 0;
+            while let Some(((record_id, _, _), next)) = self.read_record(position) {
+                if record_id == id {
+                    offset = position as u64;
+                }
+                position = next;
+            }
+        }
+        return LinkedStoreReaderIterId{ store : self, id, offset };
+    }
+
+    /** Iterates over all records stored at, or after given offset in the order they were stored.
+
+        The offset must be a record boundary, such as the size of the store in an earlier savepoint, in which case only the records added since the savepoint are returned.
+     */
+    pub fn iter_from<'a>(&'a self, offset : u64) -> impl Iterator<Item = (ID, T)> + 'a {
+        let mut position = offset as usize;
+        return std::iter::from_fn(move || {
+            let ((id, _, value), next) = self.read_record(position)?;
+            position = next;
+            return Some((id, value));
+        });
+    }
+
+    /** Returns the first checksum mismatch the reader found since the last call, if any, see `StoreReader::checksum_error`.
+     */
+    pub fn checksum_error(& self) -> Result<(), std::io::Error> {
+        return self.checksums.as_ref().map(|x| x.take_error()).unwrap_or(Ok(()));
+    }
+
+    /** Reads the record at given offset, returns the id, offset of the previous record of the same id and value and the offset of the next record, or None if there is no complete record at the offset, or its checksum does not match.
+     */
+    fn read_record(& self, offset : usize) -> Option<((ID, u64, T), usize)> {
+        let (id, next) = self.f.read::<u64>(offset)?;
+        let (previous, next) = self.f.read::<u64>(next)?;
+        let (value, next) = self.f.read::<T>(next)?;
+        if let Some(checksums) = self.checksums.as_ref() {
+            if ! checksums.is_valid(self.f.as_slice(), offset, next) {
+                return None;
+            }
+        }
+        return Some(((ID::from(id), previous, value), next));
+    }
+}
+
+impl<T : Serializable<Item = T>, ID : Id> Table for LinkedStoreReader<T, ID> {
+    type Id = ID;
+    type Value = T;
+
+    fn get_reset(& mut self) {
+        self.position = 0;
+    }
+
+    fn get_next(& mut self) -> Option<(ID, T)> {
+        if let Some(((id, _, value), next)) = self.read_record(self.position) {
+            self.position = next;
+            return Some((id, value));
+        }
+        self.position = self.f.len();
+        return None;
+    }
+
+    fn get(& mut self, id : ID) -> Option<T> {
+        return LinkedStoreReader::get(self, id);
+    }
+
+    fn filesize(& mut self) -> u64 {
+        return self.f.len() as u64;
+    }
+}
+
+impl<T : Serializable<Item = T>, ID : Id> IntoIterator for LinkedStoreReader<T, ID> {
+    type Item = (ID, T);
+    type IntoIter = TableOwningIterator<LinkedStoreReader<T, ID>>;
+
+    fn into_iter(self) -> TableOwningIterator<LinkedStoreReader<T, ID>> {
+        return TableOwningIterator::new(self);
+    }
+}
+
+pub struct LinkedStoreReaderIterId<'a, T : Serializable<Item = T>, ID : Id> {
+    store : &'a LinkedStoreReader<T, ID>,
+    id : ID,
+    offset : u64,
+}
+
+impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for LinkedStoreReaderIterId<'a, T, ID> {
+    type Item = T;
+
+    fn next(& mut self) -> Option<T> {
+        if self.offset == u64::EMPTY {
+            return None;
+        }
+        let ((id, previous, value), _) = match self.store.read_record(self.offset as usize) {
+            Some(record) => record,
+            None => {
+                self.offset = u64::EMPTY;
+                return None;
+            },
+        };
+        assert_eq!(self.id, id, "Corrupted linked store or index");
+        self.offset = previous;
+        return Some(value);
+    }
+}
+
+/** Reader for a `Mapping`.
+
+    Values can be found by their ids, the reverse mapping is only available via `find`, which uses the sorted index of the mapping, if any.
+ */
+pub struct MappingReader<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> {
+    f : MappedFile,
+    index_filename : String,
+    position : u64,
+    why_oh_why : std::marker::PhantomData<(T, ID)>,
+}
+
+impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> MappingReader<T, ID> {
+    pub fn new(root : & str, name : & str, savepoint : Option<& Savepoint>) -> MappingReader<T, ID> {
+        return MappingReader{
+            f : MappedFile::open(& format!("{}/{}.mapping", root, name), savepoint_limit(savepoint, name, "mapping")),
+            index_filename : format!("{}/{}.index", root, name),
+            position : 0,
+            why_oh_why : std::marker::PhantomData{},
+        };
+    }
+
+    pub fn get(& self, id : ID) -> Option<T> {
+        if id.into() < self.len() as u64 {
+            return self.f.read::<T>((T::SIZE * id.into()) as usize).map(|(value, _)| value);
+        }
+        return None;
+    }
+
+    pub fn len(& self) -> usize {
+        return self.f.len() / T::SIZE as usize;
+    }
+
+    /** Returns the id of given value. 
+     
+        The candidate ids are looked up in the persisted sorted index of the mapping (see `Mapping`) and only the values added since the index was last updated are scanned. Mappings without the index are scanned whole. 
+     */
+    pub fn find(& self, value : & T) -> Option<ID> {
+        let (indexed, candidates) = sorted_index_find(& self.index_filename, value).unwrap_or((0, Vec::new()));
+        for id in candidates {
+            if self.get(ID::from(id)).as_ref() == Some(value) {
+                return Some(ID::from(id));
+            }
+        }
+        return (indexed..self.len() as u64).map(ID::from).find(|id| self.get(*id).as_ref() == Some(value));
+    }
+}
+
+impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Table for MappingReader<T, ID> {
+    type Id = ID;
+    type Value = T;
+
+    fn get_reset(& mut self) {
+        self.position = 0;
+    }
+
+    fn get_next(& mut self) -> Option<(ID, T)> {
+        let id = ID::from(self.position);
+        let result = MappingReader::get(self, id);
+        if result.is_some() {
+            self.position += 1;
+        }
+        return result.map(|x| (id, x));
+    }
+
+    fn get(& mut self, id : ID) -> Option<T> {
+        return MappingReader::get(self, id);
+    }
+
+    fn filesize(& mut self) -> u64 {
+        return self.f.len() as u64;
+    }
+}
+
+impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> IntoIterator for MappingReader<T, ID> {
+    type Item = (ID, T);
+    type IntoIter = TableOwningIterator<MappingReader<T, ID>>;
+
+    fn into_iter(self) -> TableOwningIterator<MappingReader<T, ID>> {
+        return TableOwningIterator::new(self);
+    }
+}
+
+/** Reader for an `IndirectMapping`, which is simply a store reader of the mapping's store.
+ */
+pub type IndirectMappingReader<T, ID> = StoreReader<T, ID>;
+
+pub fn indirect_mapping_reader<T : Serializable<Item = T>, ID : Id>(root : & str, name : & str, savepoint : Option<& Savepoint>) -> IndirectMappingReader<T, ID> {
+    return StoreReader::new(root, & format!("{}.mapping", name), savepoint);
+}
+
+/** Reader for a `SplitStore`.
+ */
+pub struct SplitStoreReader<T : Serializable<Item = T>, KIND : SplitKind<Item = KIND>, ID : Id> {
+    indexer : IndexReader<SplitOffset<KIND>, ID>,
+    files : Vec<MappedFile>,
+    checksums : Vec<Option<ChecksumsReader>>,
+    /* Split and offset of the iterator. */
+    split : usize,
+    position : usize,
+    why_oh_why : std::marker::PhantomData<T>,
+}
+
+impl<T : Serializable<Item = T>, KIND : SplitKind<Item = KIND>, ID : Id> SplitStoreReader<T, KIND, ID> {
+    pub fn new(root : & str, name : & str, savepoint : Option<& Savepoint>) -> SplitStoreReader<T, KIND, ID> {
+        let mut files = Vec::new();
+        let mut checksums = Vec::new();
+        for i in 0..KIND::COUNT {
+            // the savepoint identifies the split files by their number, not kind
+            let limit = savepoint_limit(savepoint, & format!("{}-{}", name, i), "store");
+            files.push(MappedFile::open(& format!("{}/{}-{:?}.splitstore", root, name, KIND::from_number(i)), limit));
+            checksums.push(ChecksumsReader::open(root, & SplitStorePart::<T, ID>::checksums_name(name, KIND::from_number(i)), savepoint));
+        }
+        return SplitStoreReader{
+            indexer : IndexReader::new(root, name, savepoint),
+            files,
+            checksums,
+            split : 0,
+            position : 0,
+            why_oh_why : std::marker::PhantomData{},
+        };
+    }
+
+    pub fn get(& self, id : ID) -> Option<(KIND, T)> {
+        if let Some(offset) = self.indexer.get(id) {
+            let split = offset.kind.to_number() as usize;
+            if (offset.offset as usize) < self.files[split].len() {
+                let ((record_id, value), _) = self.read_record(split, offset.offset as usize)?;
+                assert_eq!(id, record_id, "Corrupted store or index");
+                return Some((offset.kind, value));
+            }
+        }
+        return None;
+    }
+
+    /** Returns the values of given ids with their kinds, in the order of the ids.
+
+        Like `StoreReader::get_many`, the records are read in a single sweep over the split files ordered by their offsets.
+     */
+    pub fn get_many(& self, ids : & [ID]) -> Vec<Option<(KIND, T)>> {
+        let mut result : Vec<Option<(KIND, T)>> = ids.iter().map(|_| None).collect();
+        let mut offsets = Vec::new();
+        for (i, id) in ids.iter().enumerate() {
+            if let Some(offset) = self.indexer.get(*id) {
+                let split = offset.kind.to_number() as usize;
+                if (offset.offset as usize) < self.files[split].len() {
+                    offsets.push((split, offset.offset, i));
+                }
+            }
+        }
+        offsets.sort();
+        for (split, offset, i) in offsets {
+            if let Some(((record_id, value), _)) = self.read_record(split, offset as usize) {
+                assert_eq!(ids[i], record_id, "Corrupted store or index");
+                result[i] = Some((KIND::from_number(split as u64), value));
+            }
+        }
+        return result;
+    }
+
+    pub fn has(& self, id : ID) -> bool {
+        return self.indexer.get(id).map(|offset| (offset.offset as usize) < self.files[offset.kind.to_number() as usize].len()).unwrap_or(false);
+    }
+
+    pub fn len(& self) -> usize {
+        return self.indexer.len();
+    }
+
+    /** Returns the first checksum mismatch the reader found in any of the splits since the last call, if any, see `StoreReader::checksum_error`.
+     */
+    pub fn checksum_error(& self) -> Result<(), std::io::Error> {
+        for checksums in self.checksums.iter().flatten() {
+            checksums.take_error()?;
+        }
+        return Ok(());
+    }
+
+    /** Reads the record at given offset of given split, returns the id and value and the offset of the next record, or None if the record is torn by the end of the file, or its checksum does not match.
+     */
+    fn read_record(& self, split : usize, offset : usize) -> Option<((ID, T), usize)> {
+        let (id, next) = self.files[split].read::<u64>(offset)?;
+        let (value, next) = self.files[split].read::<T>(next)?;
+        if let Some(checksums) = self.checksums[split].as_ref() {
+            if ! checksums.is_valid(self.files[split].as_slice(), offset, next) {
+                return None;
+            }
+        }
+        return Some(((ID::from(id), value), next));
+    }
+}
+
+impl<T : Serializable<Item = T>, KIND : SplitKind<Item = KIND>, ID : Id> Table for SplitStoreReader<T, KIND, ID> {
+    type Id = ID;
+    type Value = (KIND, T);
+
+    fn get_reset(& mut self) {
+        self.split = 0;
+        self.position = 0;
+    }
+
+    fn get_next(& mut self) -> Option<(ID, (KIND, T))> {
+        while self.split < self.files.len() {
+            if let Some(((id, value), next)) = self.read_record(self.split, self.position) {
+                self.position = next;
+                return Some((id, (KIND::from_number(self.split as u64), value)));
+            }
+            self.split += 1;
+            self.position = 0;
+        }
+        return None;
+    }
+
+    fn get(& mut self, id : ID) -> Option<(KIND, T)> {
+        return SplitStoreReader::get(self, id);
+    }
+
+    fn filesize(& mut self) -> u64 {
+        return self.files.iter().fold(0, |sum, x| sum + x.len() as u64);
+    }
+}
+
+impl<T : Serializable<Item = T>, KIND : SplitKind<Item = KIND>, ID : Id> IntoIterator for SplitStoreReader<T, KIND, ID> {
+    type Item = (ID, (KIND, T));
+    type IntoIter = TableOwningIterator<SplitStoreReader<T, KIND, ID>>;
+
+    fn into_iter(self) -> TableOwningIterator<SplitStoreReader<T, KIND, ID>> {
+        return TableOwningIterator::new(self);
+    }
+}
+
+/** Reader for the contents of a substore.
+
+    Contents stored as deltas are reconstructed transparently so that all contents appear as if they were stored in full. When iterating, fully stored contents are returned first, followed by the reconstructed delta encoded contents.
+ */
+pub struct ContentsReader {
+    contents : SplitStoreReader<FileContents, ContentsKind, HashId>,
+    deltas : StoreReader<ContentsDelta, HashId>,
+    iterating_deltas : bool,
+}
+
+impl ContentsReader {
+    pub fn new(root : & str, contents_name : & str, deltas_name : & str, savepoint : Option<& Savepoint>) -> ContentsReader {
+        return ContentsReader{
+            contents : SplitStoreReader::new(root, contents_name, savepoint),
+            deltas : StoreReader::new(root, deltas_name, savepoint),
+            iterating_deltas : false,
+        };
+    }
+
+    /** Returns the first checksum mismatch found in the contents or their deltas since the last call, if any, see `StoreReader::checksum_error`.
+     */
+    pub fn checksum_error(& self) -> Result<(), std::io::Error> {
+        self.contents.checksum_error()?;
+        return self.deltas.checksum_error();
+    }
+
+    /** Returns the kind and full contents of given id.
+
+        Follows the delta chain until fully stored contents are found and then applies the deltas in reverse order. The kind is the kind of the contents itself, not of its bases. Contents whose delta chain is corrupted, i.e. longer than `ContentsDelta::MAX_DEPTH`, or with deltas that do not apply to their bases, are not returned.
+     */
+    pub fn get(& self, id : HashId) -> Option<(ContentsKind, FileContents)> {
+        let mut chain = Vec::<ContentsDelta>::new();
+        let mut current = id;
+        loop {
+            if let Some((kind, contents)) = self.contents.get(current) {
+                let kind = chain.first().map(|delta| delta.kind).unwrap_or(kind);
+                let mut contents = contents;
+                while let Some(delta) = chain.pop() {
+                    contents = delta.apply(& contents).ok()?;
+                }
+                return Some((kind, contents));
+            } else if let Some(delta) = self.deltas.get(current) {
+                if chain.len() > ContentsDelta::MAX_DEPTH as usize {
+                    return None;
+                }
+                current = delta.base;
+                chain.push(delta);
+            } else {
+                return None;
+            }
+        }
+    }
+
+    /** Returns the kinds and full contents of given ids, in the order of the ids.
+
+        Fully stored contents are read in a single sweep over the store, only the delta encoded contents are reconstructed one by one.
+     */
+    pub fn get_many(& self, ids : & [HashId]) -> Vec<Option<(ContentsKind, FileContents)>> {
+        let mut result = self.contents.get_many(ids);
+        for (i, id) in ids.iter().enumerate() {
+            if result[i].is_none() {
+                result[i] = self.get(*id);
+            }
+        }
+        return result;
+    }
+
+    /** Returns the kind and a reader of the contents of given id.
+
+        Fully stored contents are decompressed on the fly directly from the mapped store so that even very large contents can be copied without loading them into memory. Delta encoded contents must be reconstructed first, which is done in memory, but deltas are only created for small modifications of the contents anyways.
+     */
+    pub fn stream(& self, id : HashId) -> Option<(ContentsKind, Box<dyn Read + '_>)> {
+        if let Some(offset) = self.contents.indexer.get(id) {
+            let split = offset.kind.to_number() as usize;
+            if (offset.offset as usize) < self.contents.files[split].len() {
+                let file = & self.contents.files[split];
+                let (record_id, next) = file.read::<u64>(offset.offset as usize)?;
+                assert_eq!(id, HashId::from(record_id), "Corrupted store or index");
+                // the contents are stored as the length of the compressed bytes followed by the bytes, which may be torn by the end of the file
+                let (len, start) = file.read::<u64>(next)?;
+                let end = start.checked_add(len as usize)?;
+                let data = file.as_slice().get(start..end)?;
+                if let Some(checksums) = self.contents.checksums[split].as_ref() {
+                    if ! checksums.is_valid(file.as_slice(), offset.offset as usize, end) {
+                        return None;
+                    }
+                }
+                return Some((offset.kind, Box::new(flate2::read::GzDecoder::new(data))));
+            }
+        }
+        return self.get(id).map(|(kind, contents)| (kind, Box::new(std::io::Cursor::new(contents)) as Box<dyn Read>));
+    }
+
+    /** Returns true if the contents for given id are stored in full, or as a delta.
+     */
+    pub fn has(& self, id : HashId) -> bool {
+        return self.contents.has(id) || self.deltas.has(id);
+    }
+}
+
+impl Table for ContentsReader {
+    type Id = HashId;
+    type Value = (ContentsKind, FileContents);
+
+    fn get_reset(& mut self) {
+        self.contents.get_reset();
+        self.deltas.get_reset();
+        self.iterating_deltas = false;
+    }
+
+    fn get_next(& mut self) -> Option<(HashId, (ContentsKind, FileContents))> {
+        if ! self.iterating_deltas {
+            if let Some(x) = self.contents.get_next() {
+                return Some(x);
+            }
+            self.iterating_deltas = true;
+        }
+        while let Some((id, _)) = self.deltas.get_next() {
+            // deltas whose base contents are missing cannot be reconstructed and are skipped
+            if let Some(x) = ContentsReader::get(self, id) {
+                return Some((id, x));
+            }
+        }
+        return None;
+    }
+
+    fn get(& mut self, id : HashId) -> Option<(ContentsKind, FileContents)> {
+        return ContentsReader::get(self, id);
+    }
+
+    fn filesize(& mut self) -> u64 {
+        return self.contents.filesize() + self.deltas.filesize();
+    }
+}
+
+impl IntoIterator for ContentsReader {
+    type Item = (HashId, (ContentsKind, FileContents));
+    type IntoIter = TableOwningIterator<ContentsReader>;
+
+    fn into_iter(self) -> TableOwningIterator<ContentsReader> {
+        return TableOwningIterator::new(self);
+    }
+}
+
+impl SplitTable for ContentsReader {
+    type Kind = ContentsKind;
+    type SplitIterator = ContentsSplitIter;
+
+    fn split_iter(self, kind : ContentsKind) -> ContentsSplitIter {
+        return ContentsSplitIter{ reader : self, kind, position : 0 };
+    }
+}
+
+/** Iterator over all contents of given kind, including the delta encoded ones.
+ */
+pub struct ContentsSplitIter {
+    reader : ContentsReader,
+    kind : ContentsKind,
+    position : usize,
+}
+
+impl Iterator for ContentsSplitIter {
+    type Item = (HashId, FileContents);
+
+    fn next(& mut self) -> Option<(HashId, FileContents)> {
+        let split = self.kind.to_number() as usize;
+        if let Some(((id, contents), next)) = self.reader.contents.read_record(split, self.position) {
+            self.position = next;
+            return Some((id, contents));
+        }
+        self.position = self.reader.contents.files[split].len();
+        while let Some((id, delta)) = self.reader.deltas.get_next() {
+            if delta.kind == self.kind {
+                if let Some((_, contents)) = self.reader.get(id) {
+                    return Some((id, contents));
+                }
+            }
+        }
+        return None;
+    }
+}
+
+/** Random access to the values of a reader by their ids. 
+
+    Implemented by the readers that can be wrapped in a `CachedReader`. Together with the value, the number of bytes the value occupies in the table is returned so that the size of the cache can be bounded.
+ */
+pub trait RandomAccess {
+    type Id : Id;
+    type Value : Clone;
+
+    fn get_with_size(& self, id : Self::Id) -> Option<(Self::Value, usize)>;
+}
+
+impl<T : Serializable<Item = T> + Clone, ID : Id> RandomAccess for StoreReader<T, ID> {
+    type Id = ID;
+    type Value = T;
+
+    fn get_with_size(& self, id : ID) -> Option<(T, usize)> {
+        if let Some(offset) = self.indexer.get(id) {
+            if (offset as usize) < self.f.len() {
+                let ((record_id, value), next) = self.read_record(offset as usize)?;
+                assert_eq!(id, record_id, "Corrupted store or index");
+                return Some((value, next - offset as usize));
+            }
+        }
+        return self.get(id).map(|value| (value, std::mem::size_of::<T>()));
+    }
+}
+
+impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> RandomAccess for MappingReader<T, ID> {
+    type Id = ID;
+    type Value = T;
+
+    fn get_with_size(& self, id : ID) -> Option<(T, usize)> {
+        return self.get(id).map(|value| (value, T::SIZE as usize));
+    }
+}
+
+impl RandomAccess for ContentsReader {
+    type Id = HashId;
+    type Value = (ContentsKind, FileContents);
+
+    fn get_with_size(& self, id : HashId) -> Option<((ContentsKind, FileContents), usize)> {
+        return self.get(id).map(|(kind, contents)| { let size = contents.len(); ((kind, contents), size) });
+    }
+}
+
+/** A reader with a bounded LRU cache of the values it returned. 
+
+    Random access workloads, such as exporting projects, or showing their commits, get the same paths, users and hashes over and over again. While the tables are memory mapped, each such get still touches the mapped pages and deserializes (or decompresses and reconstructs from deltas) the value. The cache keeps at most given number of the most recently used values whose total size in the table does not exceed given number of bytes. Values larger than the byte limit are never cached and a cache with zero entries simply passes all requests to the reader. 
+
+    Like the readers themselves, the cached reader can be shared between threads, the cache is guarded by a mutex. 
+ */
+pub struct CachedReader<R : RandomAccess> {
+    reader : R,
+    max_entries : usize,
+    max_bytes : usize,
+    cache : std::sync::Mutex<ReadCache<R::Id, R::Value>>,
+}
+
+struct ReadCache<ID, T> {
+    /** The cached values with their sizes and the time of their last use. 
+     */
+    entries : std::collections::HashMap<ID, (T, usize, u64)>,
+    /** Ids of the cached values ordered by the time of their last use. 
+     */
+    order : std::collections::BTreeMap<u64, ID>,
+    time : u64,
+    bytes : usize,
+    hits : u64,
+    misses : u64,
+}
+
+impl<R : RandomAccess> CachedReader<R> {
+    pub fn new(reader : R, max_entries : usize, max_bytes : usize) -> CachedReader<R> {
+        return CachedReader{
+            reader,
+            max_entries,
+            max_bytes,
+            cache : std::sync::Mutex::new(ReadCache{
+                entries : std::collections::HashMap::new(),
+                order : std::collections::BTreeMap::new(),
+                time : 0,
+                bytes : 0,
+                hits : 0,
+                misses : 0,
+            }),
+        };
+    }
+
+    pub fn get(& self, id : R::Id) -> Option<R::Value> {
+        if self.max_entries == 0 {
+            return self.reader.get_with_size(id).map(|(value, _)| value);
+        }
+        {
+            let mut cache = self.cache.lock().unwrap();
+            cache.time += 1;
+            let time = cache.time;
+            if let Some((value, _, last_use)) = cache.entries.get_mut(& id) {
+                let (value, last_use) = (value.clone(), std::mem::replace(last_use, time));
+                cache.order.remove(& last_use);
+                cache.order.insert(time, id);
+                cache.hits += 1;
+                return Some(value);
+            }
+            cache.misses += 1;
+        }
+        // the lock is not held while reading so that other threads can use the cache meanwhile
+        let (value, size) = self.reader.get_with_size(id)?;
+        if size <= self.max_bytes {
+            let mut cache = self.cache.lock().unwrap();
+            if ! cache.entries.contains_key(& id) {
+                cache.time += 1;
+                let time = cache.time;
+                cache.entries.insert(id, (value.clone(), size, time));
+                cache.order.insert(time, id);
+                cache.bytes += size;
+                while cache.entries.len() > self.max_entries || cache.bytes > self.max_bytes {
+                    let (last_use, victim) = cache.order.iter().next().map(|(t, id)| (*t, *id)).unwrap();
+                    cache.order.remove(& last_use);
+                    if let Some((_, size, _)) = cache.entries.remove(& victim) {
+                        cache.bytes -= size;
+                    }
+                }
+            }
+        }
+        return Some(value);
+    }
+
+    /** Returns the underlying reader, e.g. for iteration. 
+     */
+    pub fn reader(& self) -> & R {
+        return & self.reader;
+    }
+
+    /** Returns the number of cache hits and misses so far. 
+     */
+    pub fn stats(& self) -> (u64, u64) {
+        let cache = self.cache.lock().unwrap();
+        return (cache.hits, cache.misses);
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::*;
+
+use crate::updater::*;
+use crate::helpers;
+use crate::datastore::*;
+use crate::records::*;
+
+/** Number of commits whose descendants are counted in a single sweep over the commits of a project.
+
+    Each commit of the project holds a bitmap of this many bits during the sweep.
+ */
+const DESCENDANTS_BLOCK : usize = 1024;
+
+/** Calculates, or incrementally updates the commit metrics of the given substore.
+
+    The metrics of all commits of the projects that belong to the substore are recalculated whenever their heads differ from the heads the metrics were last calculated at. Counting the descendants takes time quadratic in the number of commits of a project divided by `DESCENDANTS_BLOCK`, so the first pass over large projects is slow, later passes only recalculate projects whose heads changed.
+ */
+pub (crate) fn task_commit_metrics(ds : & Datastore, task : TaskStatus) -> Result<(), std::io::Error> {
+    if let Task::CommitMetrics{store} = task.task {
+        task.extra(format!("{:?}", store));
+        let substore = ds.substore(store);
+        let mut projects = 0;
+        let mut records = 0;
+        let num_projects = ds.num_projects();
+        for i in 0..num_projects {
+            let id = ProjectId::from(i as u64);
+            if ds.get_project_substore(id) != store {
+                continue;
+            }
+            if let Some(heads) = ds.get_project_heads(id) {
+                if substore.commit_metrics_heads(id).as_ref() != Some(& heads) {
+                    let metrics = project_commit_metrics(substore.project_commit_parents(& heads));
+                    records += substore.add_commit_metrics(id, & heads, metrics);
+                    projects += 1;
+                }
+            }
+            if i % 1000 == 0 {
+                task.info(format!("calculating, {} of {} projects, {} projects calculated...", helpers::pretty_value(i), helpers::pretty_value(num_projects), helpers::pretty_value(projects)));
+                task.progress(i, num_projects);
+            }
+        }
+        task.info(format!("{} projects calculated, {} commit metrics stored", helpers::pretty_value(projects), helpers::pretty_value(records)));
+        return Ok(());
+    } else {
+        panic!("Invalid task kind");
+    }
+}
+
+/** Calculates the metrics of the commits of a single project given the parents of its commits.
+
+    The commits are sorted topologically so that every commit precedes its parents. The depths are then calculated from the parents in the reverse order and the descendants of the commits are counted in blocks, where the bitmap of each commit holds which commits of the block are its descendants. Parents of the commits that are not stored are ignored.
+ */
+fn project_commit_metrics(commits : Vec<(CommitId, Vec<CommitId>)>) -> Vec<(CommitId, CommitMetrics)> {
+    let n = commits.len();
+    let index : HashMap<CommitId, usize> = commits.iter().enumerate().map(|(i, (id, _))| (*id, i)).collect();
+    let parents : Vec<Vec<usize>> = commits.iter().map(|(_, parents)| parents.iter().filter_map(|x| index.get(x).map(|i| *i)).collect()).collect();
+    // topological order, children first
+    let mut children = vec![0usize; n];
+    for p in parents.iter().flatten() {
+        children[*p] += 1;
+    }
+    let mut order = Vec::with_capacity(n);
+    let mut q : Vec<usize> = (0..n).filter(|i| children[*i] == 0).collect();
+    while let Some(i) = q.pop() {
+        order.push(i);
+        for p in parents[i].iter() {
+            children[*p] -= 1;
+            if children[*p] == 0 {
+                q.push(*p);
+            }
+        }
+    }
+    let mut depth = vec![0u64; n];
+    for i in order.iter().rev() {
+        depth[*i] = parents[*i].iter().map(|p| depth[*p] + 1).max().unwrap_or(0);
+    }
+    let mut position = vec![0usize; n];
+    for (pos, i) in order.iter().enumerate() {
+        position[*i] = pos;
+    }
+    // descendants of a commit precede it in the order, so a block only needs the bitmaps of the commits after its start
+    let words = DESCENDANTS_BLOCK / 64;
+    let mut descendants = vec![0u64; n];
+    for start in (0..order.len()).step_by(DESCENDANTS_BLOCK) {
+        let mut bitmaps = vec![0u64; (order.len() - start) * words];
+        for pos in start..order.len() {
+            let offset = (pos - start) * words;
+            let mut bitmap = bitmaps[offset..offset + words].to_vec();
+            descendants[order[pos]] += bitmap.iter().map(|x| x.count_ones() as u64).sum::<u64>();
+            if pos - start < DESCENDANTS_BLOCK {
+                bitmap[(pos - start) / 64] |= 1 << ((pos - start) % 64);
+            }
+            // parents preceding the commit can only be on a cycle of corrupted commits
+            for p in parents[order[pos]].iter().filter(|p| position[**p] > pos) {
+                let parent_offset = (position[*p] - start) * words;
+                for (w, x) in bitmap.iter().enumerate() {
+                    bitmaps[parent_offset + w] |= x;
+                }
+            }
+        }
+    }
+    return commits.iter().enumerate().map(|(i, (id, commit_parents))| (*id, CommitMetrics{
+        parents : commit_parents.len() as u32,
+        depth : depth[i],
+        descendants : descendants[i],
+    })).collect();
+}
@@ -0,0 +1,67 @@
+use std::collections::*;
+
+use crate::updater::*;
+use crate::helpers;
+use crate::datastore::*;
+use crate::records::*;
+use crate::db::Table;
+
+/** Removes the contents of given substore that are not reachable from any of its projects. 
+
+    First a savepoint of the datastore before the collection is created. Then the contents changed by commits reachable from the latest heads of projects that belong to the substore are marked, together with the bases of their deltas. Contents only reachable from projects that have been moved to other substores, or from commits no longer reachable from any heads are not marked. Finally, the contents and contents deltas tables of the substore are rewritten with only the marked contents. 
+
+    Since the tables are rewritten, savepoints created before the collection must no longer be used to read the contents of the substore and the datastore can no longer be reverted to them. Note that the datastore must not be opened by any other parasite instance while its contents are collected.
+ */
+pub (crate) fn task_gc_contents(root : & str, task : TaskStatus) -> Result<(), std::io::Error> {
+    if let Task::CollectContents{store, keep_backup} = & task.task {
+        task.extra(format!("{:?}", store));
+        let ds = Datastore::new(root, false);
+        let sp = ds.create_and_save_savepoint(format!("gc-contents-{:?}-{}", store, helpers::now()), & task)?;
+        task.info(format!("created savepoint {}", sp.name()));
+        let substore = ds.substore(*store);
+        // mark the contents reachable from the projects
+        let mut keep = HashSet::<HashId>::new();
+        {
+            let mut commits_info = substore.commits_info.lock().unwrap();
+            let mut visited = HashSet::<CommitId>::new();
+            let num_projects = ds.num_projects();
+            for i in 0..num_projects {
+                let id = ProjectId::from(i as u64);
+                if ds.get_project_substore(id) != *store {
+                    continue;
+                }
+                if let Some(heads) = ds.get_project_heads(id) {
+                    let mut q : Vec<CommitId> = heads.values().map(|(id, _)| *id).collect();
+                    while let Some(commit_id) = q.pop() {
+                        if visited.insert(commit_id) {
+                            if let Some(info) = commits_info.get(commit_id) {
+                                keep.extend(info.changes.values().filter(|x| **x != HashId::DELETED));
+                                q.extend(info.parents.iter());
+                            }
+                        }
+                    }
+                }
+                if i % 1000 == 0 {
+                    task.info(format!("marking, {} of {} projects, {} contents...", helpers::pretty_value(i), helpers::pretty_value(num_projects), helpers::pretty_value(keep.len())));
+                    task.progress(i, num_projects);
+                }
+            }
+            // deltas can only be reconstructed if their bases are kept too
+            let mut contents_deltas = substore.contents_deltas.lock().unwrap();
+            let mut q : Vec<HashId> = keep.iter().map(|x| *x).collect();
+            while let Some(id) = q.pop() {
+                if let Some(delta) = contents_deltas.get(id) {
+                    if keep.insert(delta.base) {
+                        q.push(delta.base);
+                    }
+                }
+            }
+        }
+        // and sweep
+        let (before, after) = substore.collect_contents(& keep, *keep_backup, & task)?;
+        task.info(format!("{} contents kept, {} reclaimed", helpers::pretty_value(keep.len()), helpers::pretty_size(before.saturating_sub(after))));
+        return Ok(());
+    } else {
+        panic!("Invalid task kind");
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::*;
+
+use crate::updater::*;
+use crate::helpers;
+use crate::datastore::*;
+use crate::records::*;
+
+/** Builds, or incrementally updates the commit projects index of the given substore.
+
+    All projects that belong to the substore are walked from their latest heads, skipping the commits already indexed for the project at its previously indexed heads. The projects are stored in batches once at least `Substore::COMMIT_PROJECTS_BATCH` new commits have been found, so that an interrupted pass only repeats the last batch. The pause function is called every 1000 projects outside of the batches, see `Updater::pause_if_held`.
+ */
+pub (crate) fn task_index_commit_projects(ds : & Datastore, task : TaskStatus, pause : & dyn Fn(& TaskStatus)) -> Result<(), std::io::Error> {
+    if let Task::IndexCommitProjects{store} = task.task {
+        task.extra(format!("{:?}", store));
+        let substore = ds.substore(store);
+        let mut batch = HashMap::<CommitId, CommitProjects>::new();
+        let mut batch_commits = 0;
+        let mut batch_heads = Vec::<(ProjectId, ProjectHeads)>::new();
+        let mut projects = 0;
+        let mut commits = 0;
+        let num_projects = ds.num_projects();
+        for i in 0..num_projects {
+            let id = ProjectId::from(i as u64);
+            if ds.get_project_substore(id) != store {
+                continue;
+            }
+            if let Some(heads) = ds.get_project_heads(id) {
+                if let Some(new_commits) = substore.unindexed_project_commits(id, & heads) {
+                    batch_commits += new_commits.len();
+                    for commit_id in new_commits {
+                        batch.entry(commit_id).or_insert_with(CommitProjects::new).push(id);
+                    }
+                    batch_heads.push((id, heads));
+                    if batch_commits >= Substore::COMMIT_PROJECTS_BATCH {
+                        substore.add_commit_projects(std::mem::take(& mut batch), & batch_heads);
+                        projects += batch_heads.len();
+                        commits += batch_commits;
+                        batch_heads.clear();
+                        batch_commits = 0;
+                    }
+                }
+            }
+            if i % 1000 == 0 {
+                task.info(format!("indexing, {} of {} projects, {} projects indexed...", helpers::pretty_value(i), helpers::pretty_value(num_projects), helpers::pretty_value(projects + batch_heads.len())));
+                task.progress(i, num_projects);
+                pause(& task);
+            }
+        }
+        projects += batch_heads.len();
+        commits += batch_commits;
+        substore.add_commit_projects(batch, & batch_heads);
+        task.info(format!("{} projects indexed, {} commits added", helpers::pretty_value(projects), helpers::pretty_value(commits)));
+        return Ok(());
+    } else {
+        panic!("Invalid task kind");
+    }
+}
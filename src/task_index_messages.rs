@@ -0,0 +1,18 @@
+use crate::updater::*;
+use crate::helpers;
+use crate::datastore::*;
+
+/** Builds, or incrementally updates the commit messages index of the given substore. 
+ 
+    Only commits added since the last indexing are indexed, together with the commits that had no information stored when reached by an earlier indexing. The pause function is called before each batch, see `Updater::pause_if_held`. 
+ */
+pub (crate) fn task_index_messages(ds : & Datastore, task : TaskStatus, pause : & dyn Fn(& TaskStatus)) -> Result<(), std::io::Error> {
+    if let Task::IndexMessages{store} = task.task {
+        task.extra(format!("{:?}", store));
+        let indexed = ds.substore(store).index_commit_messages(& task, & || pause(& task));
+        task.info(format!("{} commits indexed", helpers::pretty_value(indexed)));
+        return Ok(());
+    } else {
+        panic!("Invalid task kind");
+    }
+}
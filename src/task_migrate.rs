@@ -0,0 +1,162 @@
+use std::io::{Read, Write, Seek, SeekFrom};
+use byteorder::*;
+
+use crate::updater::*;
+use crate::helpers;
+use crate::datastore::*;
+use crate::records::{SHA, StoreKind, ContentsKind};
+use crate::db::{BitmapIndex, SplitKind, SplitKindIter, Indexable, TableStorage, open_table_file, table_file_exists};
+
+/** A migration of the datastore layout from one version to the next.
+ */
+pub (crate) struct Migration {
+    /** The version migrated from, the migration produces version `from + 1`.
+     */
+    pub from : u16,
+    pub description : &'static str,
+    /** Rewrites the tables of the datastore at given root in place.
+     */
+    pub migrate : fn(root : & str, task : & TaskStatus) -> Result<(), std::io::Error>,
+}
+
+/** All registered migrations, ordered by the version they migrate from.
+
+    When `Datastore::VERSION` is increased and the layout of any table changes, a migration from the previous version must be added here.
+ */
+pub (crate) static MIGRATIONS : [Migration; 2] = [
+    Migration{ from : 0, description : "project log records of path filters, deletions, denials, error kinds and update changes", migrate : migrate_from_v0 },
+    Migration{ from : 1, description : "kind of empty contents offsets after the LFS pointer contents kind", migrate : migrate_from_v1 },
+];
+
+/** Migrates the datastore from version 0 to version 1. 
+
+    Version 1 adds new kinds of project log records (path filters, deleted and denied projects, errors with their kinds and ok records with the update changes), which parasite of version 0 cannot read. The existing records are valid in version 1 as they are, so the migration only marks the datastore as no longer readable by the older parasite. 
+ */
+fn migrate_from_v0(_root : & str, task : & TaskStatus) -> Result<(), std::io::Error> {
+    task.info("project log records are unchanged");
+    return Ok(());
+}
+
+/** Migrates the datastore from version 1 to version 2. 
+
+    Version 2 adds the LFS pointer contents kind before the sentinel kind that the contents indexers store for ids without contents, so the empty offsets stored before have the kind that is now the LFS pointer. The kind of all empty offsets in the contents indexers of all substores is set to the current empty kind. 
+ */
+fn migrate_from_v1(root : & str, task : & TaskStatus) -> Result<(), std::io::Error> {
+    const RECORD_SIZE : usize = 10;
+    let empty = ContentsKind::EMPTY.to_number() as u16;
+    for store in SplitKindIter::<StoreKind>::new() {
+        let filename = format!("{}/{:?}/{:?}-{}.idx", root, store, store, Substore::CONTENTS);
+        if ! table_file_exists(& filename) {
+            continue;
+        }
+        task.info(format!("rewriting empty contents offsets of substore {:?}...", store));
+        let mut f = open_table_file(& filename, false)?;
+        let len = f.len()?;
+        let mut buffer = vec![0; RECORD_SIZE * 100000];
+        let mut offset = 0;
+        let mut rewritten = 0;
+        while offset < len {
+            let n = std::cmp::min(buffer.len() as u64, (len - offset) / RECORD_SIZE as u64 * RECORD_SIZE as u64) as usize;
+            if n == 0 {
+                break;
+            }
+            f.seek(SeekFrom::Start(offset))?;
+            f.read_exact(& mut buffer[0..n])?;
+            let mut changed = false;
+            for record in buffer[0..n].chunks_mut(RECORD_SIZE) {
+                if (& record[0..8]).read_u64::<LittleEndian>()? == u64::EMPTY && (& record[8..10]).read_u16::<LittleEndian>()? != empty {
+                    (& mut record[8..10]).write_u16::<LittleEndian>(empty)?;
+                    changed = true;
+                    rewritten += 1;
+                }
+            }
+            if changed {
+                f.seek(SeekFrom::Start(offset))?;
+                f.write_all(& buffer[0..n])?;
+            }
+            offset += n as u64;
+        }
+        f.flush()?;
+        task.info(format!("    {} empty offsets rewritten", helpers::pretty_value(rewritten)));
+    }
+    return Ok(());
+}
+
+/** Migrates the datastore at given root to the current datastore version.
+
+    The migrations are applied one by one in place, or to a copy of the datastore in a new root. After each migration the version of the datastore is updated and the migration is recorded in the migration log, so that an interrupted migration continues where it stopped. Finally the migrated datastore and all its substores are verified.
+
+    Note that the datastore must not be opened by any other parasite instance while it is being migrated.
+ */
+pub (crate) fn task_migrate(root : & str, task : TaskStatus) -> Result<(), std::io::Error> {
+    if let Task::Migrate{into} = & task.task {
+        let from = Datastore::stored_version(root);
+        if from > Datastore::VERSION {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Datastore version {} is newer than the supported version {}", from, Datastore::VERSION)));
+        }
+        // check that all migrations are available before touching anything
+        for version in from..Datastore::VERSION {
+            if ! MIGRATIONS.iter().any(|m| m.from == version) {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("No migration from version {}", version)));
+            }
+        }
+        let target = match into {
+            Some(into) => {
+                if std::path::Path::new(into).exists() {
+                    return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, format!("Target {} already exists", into)));
+                }
+                task.info(format!("copying datastore to {}...", into));
+                let mut copied = 0;
+                copy_folder(std::path::Path::new(root), std::path::Path::new(into), & mut copied, & task)?;
+                into.as_str()
+            },
+            None => root,
+        };
+        task.extra(target);
+        for version in from..Datastore::VERSION {
+            let migration = MIGRATIONS.iter().find(|m| m.from == version).unwrap();
+            task.info(format!("migrating from version {} to {}: {}", version, version + 1, migration.description));
+            (migration.migrate)(target, & task)?;
+            Datastore::write_version(target, version + 1)?;
+            let mut log = std::fs::OpenOptions::new().append(true).create(true).open(format!("{}/{}", target, Datastore::MIGRATION_LOG))?;
+            writeln!(log, "{},{},{},\"{}\"", helpers::now(), version, version + 1, migration.description)?;
+        }
+        // the migrations may have rewritten the substores, so the global hash index is rebuilt when the datastore is opened
+        if from < Datastore::VERSION {
+            BitmapIndex::<SHA>::invalidate(target, Datastore::SHA_SUBSTORES)?;
+        }
+        // verify the migrated datastore
+        let ds = Datastore::new(target, false);
+        task.info("verifying datastore...");
+        let mut items = ds.verify(& task)?;
+        for substore in ds.substores_iter() {
+            task.info(format!("verifying substore {:?}...", substore.prefix));
+            items += substore.verify(& task)?;
+            substore.clear(& task);
+        }
+        task.info(format!("migrated from version {} to {}, {} items verified", from, Datastore::VERSION, helpers::pretty_value(items)));
+        return Ok(());
+    } else {
+        panic!("Invalid task kind");
+    }
+}
+
+/** Recursively copies the datastore folder, skipping the repository clones.
+ */
+fn copy_folder(from : & std::path::Path, to : & std::path::Path, copied : & mut u64, task : & TaskStatus) -> Result<(), std::io::Error> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == "repo_clones" {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            copy_folder(& entry.path(), & to.join(& name), copied, task)?;
+        } else {
+            *copied += std::fs::copy(entry.path(), to.join(& name))?;
+            task.info(format!("copying datastore, {} copied...", helpers::pretty_size(*copied)));
+        }
+    }
+    return Ok(());
+}
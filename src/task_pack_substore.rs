@@ -0,0 +1,252 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use sha1::{Sha1, Digest};
+
+use crate::updater::*;
+use crate::records::*;
+use crate::helpers;
+use crate::datastore::*;
+use crate::db::{BitmapIndex, FixedSizeSerializable, SplitKind};
+
+/** Substore packages.
+
+    A package is a plain tar archive that contains a `manifest.json` file followed by the substore's table files. The manifest contains the parasite and datastore versions that created the package, the substore kind, the savepoint the package has been created from and the size and SHA-1 hash of each packaged file. The files are truncated to their sizes in the savepoint so that the package is consistent even if the substore is being updated while packaged.
+
+    Only the substore tables are packaged. The projects themselves live in the datastore tables, which means that a package should be unpacked into a datastore that has the same projects (such as a mirror of the original datastore). To combine substores of unrelated datastores, use `merge` instead.
+ */
+pub (crate) const MANIFEST : &'static str = "manifest.json";
+
+const BLOCK_SIZE : usize = 512;
+
+/** Packs the given substore into a tar archive.
+
+    If a savepoint name is given, the substore is packed as of that savepoint, otherwise a fresh savepoint of the current state is created (but not stored in the datastore).
+ */
+pub (crate) fn task_pack_substore(ds : & Datastore, task : TaskStatus) -> Result<(), std::io::Error> {
+    if let Task::PackSubstore{store, output, savepoint} = & task.task {
+        let sp = match savepoint {
+            Some(name) => ds.get_savepoint(name).ok_or(std::io::Error::new(std::io::ErrorKind::Other, format!("Savepoint {} not found", name)))?,
+            None => ds.create_savepoint(format!("pack-{:?}", store), & task)?,
+        };
+        let substore_root = Path::new(ds.root_folder()).join(format!("{:?}", store));
+        let prefix = format!("{:?}-", store);
+        let mut files : Vec<(String, u64)> = sp.files()
+            .filter(|(name, _)| name.starts_with(& prefix))
+            .map(|(name, size)| (file_for_entry(*store, name), *size))
+            .collect();
+        files.sort();
+        // hash the files first so that the manifest can be the first entry of the archive
+        let mut manifest_files = json::JsonValue::new_array();
+        let mut total_size = 0;
+        for (i, (name, size)) in files.iter().enumerate() {
+            task.progress(i, files.len() * 2);
+            task.info(format!("hashing {}", name));
+            let hash = hash_file(& substore_root.join(name), *size)?;
+            manifest_files.push(json::object!{
+                "name" => name.as_str(),
+                "size" => *size,
+                "sha1" => format!("{}", hash),
+            }).unwrap();
+            total_size += size;
+        }
+        let manifest = json::object!{
+            "parasite" => env!("CARGO_PKG_VERSION"),
+            "datastore_version" => Datastore::VERSION,
+            "substore" => format!("{:?}", store),
+            "savepoint" => json::object!{
+                "name" => sp.name(),
+                "time" => sp.time(),
+            },
+            "files" => manifest_files,
+        };
+        let mut archive = File::create(output)?;
+        write_tar_entry(& mut archive, MANIFEST, & mut manifest.pretty(4).as_bytes(), manifest.pretty(4).len() as u64)?;
+        for (i, (name, size)) in files.iter().enumerate() {
+            task.progress(files.len() + i, files.len() * 2);
+            task.info(format!("packing {}", name));
+            let mut f = File::open(substore_root.join(name))?.take(*size);
+            write_tar_entry(& mut archive, name, & mut f, *size)?;
+        }
+        // end of archive marker
+        archive.write_all(& [0; BLOCK_SIZE * 2])?;
+        task.info(format!("Packed {} files, total size {}, savepoint {}", files.len(), helpers::pretty_size(total_size), sp.name()));
+    } else {
+        panic!("Invalid task kind");
+    }
+    return Ok(());
+}
+
+/** Unpacks the substore from given archive into the datastore at the given root.
+
+    The package must have been created by the same datastore version and the target substore must be empty (i.e. it may only contain the sentinel values created when the datastore is initialized). The files are first extracted next to the substore's files and checked against the manifest. Only when all files match are the substore's files replaced.
+
+    Note that the datastore must not be opened by any other parasite instance while the substore is unpacked.
+ */
+pub (crate) fn task_unpack_substore(root : & str, task : TaskStatus) -> Result<(), std::io::Error> {
+    if let Task::UnpackSubstore{input} = & task.task {
+        let mut archive = File::open(input)?;
+        // read & validate the manifest
+        let manifest = match read_tar_header(& mut archive)? {
+            Some((name, size)) if name == MANIFEST => {
+                let mut buffer = Vec::new();
+                (& mut archive).take(size).read_to_end(& mut buffer)?;
+                skip_padding(& mut archive, size)?;
+                json::parse(& helpers::to_string(& buffer)).map_err(|e| invalid_package(& format!("Cannot parse manifest: {}", e)))?
+            },
+            _ => return Err(invalid_package("Manifest not found")),
+        };
+        if manifest["datastore_version"].as_u16() != Some(Datastore::VERSION) {
+            return Err(invalid_package(& format!("Incompatible datastore version {} (expected {}), created by parasite {}", manifest["datastore_version"], Datastore::VERSION, manifest["parasite"])));
+        }
+        let store = manifest["substore"].as_str().and_then(|x| StoreKind::from_string(x)).ok_or(invalid_package("Unknown substore"))?;
+        let substore_root = Path::new(root).join(format!("{:?}", store));
+        // the substore is empty if its commits and hashes mappings contain at most the sentinels
+        for table in [Substore::COMMITS, Substore::HASHES].iter() {
+            let mapping = substore_root.join(format!("{:?}-{}.mapping", store, table));
+            if mapping.exists() && std::fs::metadata(& mapping)?.len() > SHA::SIZE {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Substore {:?} is not empty", store)));
+            }
+        }
+        std::fs::create_dir_all(& substore_root)?;
+        // extract the files
+        let files = & manifest["files"];
+        let mut extracted = Vec::new();
+        while let Some((name, size)) = read_tar_header(& mut archive)? {
+            task.progress(extracted.len(), files.len());
+            task.info(format!("unpacking {}", name));
+            let expected = files.members().find(|x| x["name"].as_str() == Some(& name)).ok_or(invalid_package(& format!("File {} not in manifest", name)))?;
+            if expected["size"].as_u64() != Some(size) || name.contains('/') {
+                return Err(invalid_package(& format!("Invalid entry {}", name)));
+            }
+            let tmp = substore_root.join(format!("{}.unpack", name));
+            let mut f = File::create(& tmp)?;
+            let mut hasher = Sha1::new();
+            let mut remaining = size;
+            let mut buffer = vec![0; 1024 * 1024];
+            while remaining > 0 {
+                let n = std::cmp::min(remaining, buffer.len() as u64) as usize;
+                archive.read_exact(& mut buffer[0..n])?;
+                hasher.update(& buffer[0..n]);
+                f.write_all(& buffer[0..n])?;
+                remaining -= n as u64;
+            }
+            skip_padding(& mut archive, size)?;
+            let hash = format!("{}", SHA::from_bytes(& hasher.finalize()).unwrap());
+            if expected["sha1"].as_str() != Some(& hash) {
+                return Err(invalid_package(& format!("Hash mismatch for {}", name)));
+            }
+            extracted.push(name);
+        }
+        if extracted.len() != files.len() {
+            return Err(invalid_package(& format!("Expected {} files, but only {} found", files.len(), extracted.len())));
+        }
+        // everything is fine, replace the substore files
+        for name in extracted.iter() {
+            std::fs::rename(substore_root.join(format!("{}.unpack", name)), substore_root.join(name))?;
+        }
+        // the global hash index refers to the hashes of the replaced substore
+        BitmapIndex::<SHA>::invalidate(root, Datastore::SHA_SUBSTORES)?;
+        task.info(format!("Unpacked {} files of substore {:?}, savepoint {}", extracted.len(), store, manifest["savepoint"]["name"]));
+    } else {
+        panic!("Invalid task kind");
+    }
+    return Ok(());
+}
+
+/** Returns the name of the file that corresponds to given savepoint entry. 
+ 
+    This is the entry itself with the exception of the contents split store, whose splits are stored in the savepoint by their index, but their files are named by their kind. 
+ */
+fn file_for_entry(store : StoreKind, entry : & str) -> String {
+    let contents = format!("{:?}-{}-", store, Substore::CONTENTS);
+    if entry.starts_with(& contents) && entry.ends_with(".store") {
+        if let Ok(i) = entry[contents.len() .. entry.len() - 6].parse::<u64>() {
+            return format!("{}{:?}.splitstore", contents, ContentsKind::from_number(i));
+        }
+    }
+    return entry.to_owned();
+}
+
+fn invalid_package(msg : & str) -> std::io::Error {
+    return std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid substore package: {}", msg));
+}
+
+/** Calculates the SHA-1 hash of the first `size` bytes of given file.
+ */
+fn hash_file(path : & Path, size : u64) -> Result<SHA, std::io::Error> {
+    let mut f = File::open(path)?.take(size);
+    let mut hasher = Sha1::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    let mut total = 0;
+    loop {
+        let n = f.read(& mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(& buffer[0..n]);
+        total += n as u64;
+    }
+    if total != size {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, format!("File {} is smaller than its savepoint size", path.to_str().unwrap())));
+    }
+    return Ok(SHA::from_bytes(& hasher.finalize()).unwrap());
+}
+
+/** Writes a single file entry to the tar archive.
+
+    Only the bare minimum of the ustar format is supported, which is enough for the short file names parasite uses.
+ */
+fn write_tar_entry(archive : & mut File, name : & str, data : & mut impl Read, size : u64) -> Result<(), std::io::Error> {
+    assert!(name.len() < 100, "File name {} too long for tar archive", name);
+    let mut header = [0u8; BLOCK_SIZE];
+    header[0..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+    header[136..148].copy_from_slice(format!("{:011o}\0", helpers::now()).as_bytes());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    // the checksum is calculated with the checksum field itself set to spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum : u32 = header.iter().map(|x| *x as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    archive.write_all(& header)?;
+    let written = std::io::copy(data, archive)?;
+    if written != size {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, format!("File {} is smaller than expected", name)));
+    }
+    let padding = (BLOCK_SIZE - (size as usize % BLOCK_SIZE)) % BLOCK_SIZE;
+    archive.write_all(& vec![0; padding])?;
+    return Ok(());
+}
+
+/** Reads the header of next tar entry and returns its name and size, or None if the end of archive has been reached.
+ */
+fn read_tar_header(archive : & mut File) -> Result<Option<(String, u64)>, std::io::Error> {
+    let mut header = [0u8; BLOCK_SIZE];
+    archive.read_exact(& mut header)?;
+    if header.iter().all(|x| *x == 0) {
+        return Ok(None);
+    }
+    let field = |from : usize, to : usize| {
+        let end = header[from..to].iter().position(|x| *x == 0).map(|x| from + x).unwrap_or(to);
+        return helpers::to_string(& header[from..end]);
+    };
+    if header[156] != b'0' && header[156] != 0 {
+        return Err(invalid_package("Only regular files are supported"));
+    }
+    let name = field(0, 100);
+    let size = u64::from_str_radix(field(124, 136).trim(), 8).map_err(|_| invalid_package("Invalid entry size"))?;
+    return Ok(Some((name, size)));
+}
+
+fn skip_padding(archive : & mut File, size : u64) -> Result<(), std::io::Error> {
+    let padding = (BLOCK_SIZE - (size as usize % BLOCK_SIZE)) % BLOCK_SIZE;
+    let mut buffer = vec![0; padding];
+    archive.read_exact(& mut buffer)?;
+    return Ok(());
+}
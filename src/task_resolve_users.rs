@@ -0,0 +1,16 @@
+use crate::updater::*;
+use crate::helpers;
+use crate::datastore::*;
+
+/** Determines users of the given substore that are the same person and stores their canonical ids in the user aliases. 
+ */
+pub (crate) fn task_resolve_users(ds : & Datastore, task : TaskStatus) -> Result<(), std::io::Error> {
+    if let Task::ResolveUsers{store} = task.task {
+        task.extra(format!("{:?}", store));
+        let aliases = ds.substore(store).resolve_user_aliases(& task);
+        task.info(format!("{} aliased users", helpers::pretty_value(aliases)));
+        return Ok(());
+    } else {
+        panic!("Invalid task kind");
+    }
+}
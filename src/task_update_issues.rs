@@ -0,0 +1,55 @@
+use crate::updater::*;
+use crate::records::*;
+use crate::helpers;
+use crate::datastore::*;
+use crate::github::*;
+
+/** Downloads issues and pull requests of given project. 
+ 
+    Only GitHub projects that already belong to a substore can have their issues downloaded since the issues are stored in the project's substore. The update is incremental, i.e. only issues updated since the latest issue already stored are requested. Since GitHub's `since` filter is inclusive, the latest issue is downloaded again, which is harmless. 
+ */
+pub (crate) fn task_update_issues(ds : & Datastore, gh : & Github, task : TaskStatus) -> Result<(), std::io::Error> {
+    if let Task::UpdateIssues{id} = task.task {
+        let user_and_repo;
+        match ds.get_project(id) {
+            Some(ProjectUrl::GitHub{user_and_repo : x}) => {
+                user_and_repo = x;
+            },
+            Some(_) => {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, "Issues can only be downloaded for GitHub projects"));
+            },
+            None => {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Project {} does not exist", id)));
+            }
+        }
+        task.extra(& user_and_repo);
+        let substore = ds.get_project_substore(id);
+        if substore == StoreKind::Unspecified {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Project has no substore yet, update the project first"));
+        }
+        let ss = ds.substore(substore);
+        let since = ss.get_project_issues_last_update(id).unwrap_or(0);
+        let mut page = 1;
+        let mut issues = 0;
+        loop {
+            task.info(format!("downloading issues page {} ({} issues so far)", page, helpers::pretty_value(issues)));
+            let result = gh.get_issues(& user_and_repo, since, page, Some(& task))?;
+            let mut page_issues = 0;
+            for json in result.members() {
+                if let Some(issue) = Issue::from_json(json) {
+                    ss.add_project_issue(id, & issue);
+                    issues += 1;
+                }
+                page_issues += 1;
+            }
+            if page_issues < Github::ISSUES_PER_PAGE {
+                break;
+            }
+            page += 1;
+        }
+        task.info(format!("{} issues updated", helpers::pretty_value(issues)));
+        return Ok(());
+    } else {
+        panic!("Invalid task kind");
+    }
+}
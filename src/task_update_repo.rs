@@ -1,10 +1,17 @@
 use std::collections::*;
+use std::fs::OpenOptions;
+use std::io::Write;
 
 use crate::datastore::*;
 use crate::updater::*;
 use crate::records::*;
 use crate::helpers;
+use crate::db::Id;
 use crate::github::*;
+use crate::settings::{SETTINGS, ScrubSecrets};
+use crate::substore_classifier::*;
+use crate::license_classifier::*;
+use crate::LOG;
 
 
 /** Provides a full update of the given repository. 
@@ -17,16 +24,29 @@ use crate::github::*;
     - update the project
     
  */
-pub (crate) fn task_update_repo(ds : & Datastore, gh : & Github, task : TaskStatus, force : bool, load_substore : bool) -> Result<(), std::io::Error> {
+pub (crate) fn task_update_repo(ds : & Datastore, gh : & Github, hooks : & [Box<dyn UpdateHook>], task : TaskStatus, force : bool, load_substore : bool) -> Result<(), std::io::Error> {
     let mut ru = RepoUpdater::new(ds, gh, task, force, load_substore);
+    ru.hooks = hooks;
+    // the number of failed attempts must be determined before the update as the update itself may add new records to the project log
+    let attempts = ru.ds.get_project_last_update(ru.id).map(|x| x.retry_attempts()).unwrap_or(0);
     match ru.update() {
         Err(e) => {
-                // if there was an error, report the error and exit
-                ru.ds.update_project_update_status(ru.id, ProjectLog::Error{
-                    time : helpers::now(),
-                    version : Datastore::VERSION,
-                    error : format!("{:?}", e),
-                });
+                // if there was an error, report the error and exit, transient errors are retried later unless they failed too many times already
+                if is_transient_error(& e) && attempts < MAX_UPDATE_RETRIES {
+                    ru.ds.update_project_update_status(ru.id, ProjectLog::Retry{
+                        time : helpers::now(),
+                        version : Datastore::VERSION,
+                        error : format!("{:?}", e),
+                        attempt : attempts + 1,
+                    });
+                } else {
+                    ru.ds.update_project_update_status(ru.id, ProjectLog::Error{
+                        time : helpers::now(),
+                        version : Datastore::VERSION,
+                        kind : update_error_kind(& e),
+                        error : format!("{:?}", e),
+                    });
+                }
                 return Err(e);
         },
         Ok(()) => {
@@ -35,6 +55,391 @@ pub (crate) fn task_update_repo(ds : & Datastore, gh : & Github, task : TaskStat
     }
 }
 
+/** Counts the files of each contents kind in the most recent head of the repository. 
+ */
+pub (crate) fn repo_languages(repo : & git2::Repository) -> Result<HashMap<ContentsKind, usize>, git2::Error> {
+    let mut result = HashMap::new();
+    let mut latest : Option<git2::Commit> = None;
+    for reference in repo.references()? {
+        if let Ok(commit) = reference?.peel_to_commit() {
+            if latest.as_ref().map(|x| x.time().seconds() < commit.time().seconds()).unwrap_or(true) {
+                latest = Some(commit);
+            }
+        }
+    }
+    if let Some(commit) = latest {
+        commit.tree()?.walk(git2::TreeWalkMode::PreOrder, |_, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                if let Some(kind) = entry.name().and_then(|x| ContentsKind::from_path(x)) {
+                    *result.entry(kind).or_insert(0) += 1;
+                }
+            }
+            return git2::TreeWalkResult::Ok;
+        })?;
+    }
+    return Ok(result);
+}
+
+/** Performs a dry run of the project update. 
+ 
+    Determines what the update would do and appends the result to the dry run report without changing the datastore. 
+ */
+pub (crate) fn task_dry_run_repo(ds : & Datastore, gh : & Github, task : TaskStatus) -> Result<(), std::io::Error> {
+    let mut ru = RepoUpdater::new(ds, gh, task, false, false);
+    let line = ru.dry_run()?;
+    let mut f = OpenOptions::new().create(true).append(true).open(format!("{}/{}", ds.root_folder(), Updater::DRY_RUN_REPORT))?;
+    // a single write so that lines of concurrent dry runs do not interleave
+    f.write_all(format!("{}\n", line).as_bytes())?;
+    return Ok(());
+}
+
+/** Performs a heads only update of the project. 
+ 
+    Checks the metadata and remote heads of the project and records their changes, but neither clones the project, nor analyzes its commits, so that the liveness of many projects can be refreshed cheaply. Errors are only reported, they are not recorded in the project log so that the regular updates of the project are not affected. 
+ */
+pub (crate) fn task_update_heads(ds : & Datastore, gh : & Github, task : TaskStatus) -> Result<(), std::io::Error> {
+    let mut ru = RepoUpdater::new(ds, gh, task, false, false);
+    return ru.update_heads();
+}
+
+/** Refreshes the GitHub metadata of the project. 
+ 
+    Only the metadata are checked, which records the popularity of the project, its renames and default branch, so that these stay fresh without cloning the project. Projects that no longer exist are marked as deleted, other errors are only reported. 
+ */
+pub (crate) fn task_update_metadata(ds : & Datastore, gh : & Github, task : TaskStatus) -> Result<(), std::io::Error> {
+    let mut ru = RepoUpdater::new(ds, gh, task, false, false);
+    ru.task.extra_url(ru.project.name(), ru.project.clone_url());
+    match ru.check_metadata() {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return ru.mark_deleted(format!("metadata: {}", e)),
+        result => result?,
+    }
+    ru.task.info(if ru.changed { "metadata changed" } else { "no change" });
+    ru.task.color(if ru.changed { "\x1b[92m" } else { "\x1b[90m" });
+    return Ok(());
+}
+
+/** Maximum number of times a project whose update failed with a transient error is retried before the error is considered permanent. 
+ */
+pub (crate) const MAX_UPDATE_RETRIES : u16 = 6;
+
+/** Delay in seconds before the first retry of a failed update. Every subsequent retry doubles the delay. 
+ */
+pub (crate) const RETRY_BASE_DELAY : i64 = 60 * 60;
+
+/** Determines whether the project with given last update record should be updated now. 
+ 
+    Only retry records are subject to the exponential backoff, skipped projects are never due as they would very likely exceed the clone limits again and neither are deleted projects, all other records are always due. 
+ */
+pub (crate) fn is_update_due(last_update : & ProjectLog, now : i64) -> bool {
+    match last_update {
+        ProjectLog::Retry{time, version : _, error : _, attempt} => {
+            let delay = RETRY_BASE_DELAY << (std::cmp::max(*attempt, 1) - 1).min(30);
+            return now >= time + delay;
+        },
+        ProjectLog::Skipped{time : _, version : _, reason : _} => return false,
+        ProjectLog::Deleted{time : _, version : _, reason : _} => return false,
+        _ => return true,
+    }
+}
+
+/** Determines whether the error that stopped the project update is transient, i.e. whether the update may succeed if retried later. 
+ 
+    Network failures and server errors are transient, everything else (missing repositories, authorization errors, invalid data, etc.) is permanent. 
+ */
+fn is_transient_error(e : & std::io::Error) -> bool {
+    match e.kind() {
+        std::io::ErrorKind::ConnectionRefused |
+        std::io::ErrorKind::ConnectionReset |
+        std::io::ErrorKind::ConnectionAborted |
+        std::io::ErrorKind::NotConnected |
+        std::io::ErrorKind::BrokenPipe |
+        std::io::ErrorKind::TimedOut |
+        std::io::ErrorKind::Interrupted |
+        std::io::ErrorKind::UnexpectedEof => return true,
+        _ => return false,
+    }
+}
+
+/** Translates git error into an io error kind so that transient git errors can be told apart. 
+ 
+    Network and SSL errors are considered transient, as are HTTP errors unless they are the result of failed authentication (which is what GitHub returns for deleted or private repositories). HTTP 404 and 410 statuses mean that the repository does not exist and are reported as not found. 
+ */
+fn git_error_kind(e : & git2::Error) -> std::io::ErrorKind {
+    if e.code() == git2::ErrorCode::Auth {
+        return std::io::ErrorKind::Other;
+    }
+    if e.class() == git2::ErrorClass::Http && (e.message().contains("404") || e.message().contains("410")) {
+        return std::io::ErrorKind::NotFound;
+    }
+    match e.class() {
+        git2::ErrorClass::Net | git2::ErrorClass::Ssl | git2::ErrorClass::Http => return std::io::ErrorKind::ConnectionAborted,
+        _ => return std::io::ErrorKind::Other,
+    }
+}
+
+/** Returns the number of heads that are new, point to different commits, or were deleted. 
+ */
+fn count_changed_heads(last : & ProjectHeads, current : & ProjectHeads) -> u32 {
+    let changed = current.iter().filter(|(name, (_, hash))| last.get(*name).map(|(_, last_hash)| last_hash != hash).unwrap_or(true)).count();
+    let deleted = last.keys().filter(|name| ! current.contains_key(*name)).count();
+    return (changed + deleted) as u32;
+}
+
+/** Determines the kind of the error that stopped the update, as recorded in the project log. 
+ 
+    Errors of git operations carry their kind determined by `git_update_error` (see `git_io_error`), as their codes are lost once they are translated into io errors. GitHub reports rejected credentials as 401 or 403 statuses. 
+ */
+fn update_error_kind(e : & std::io::Error) -> UpdateError {
+    if let Some(failure) = e.get_ref().and_then(|x| x.downcast_ref::<GitFailure>()) {
+        return failure.kind;
+    }
+    match e.kind() {
+        std::io::ErrorKind::TimedOut => return UpdateError::Timeout,
+        std::io::ErrorKind::NotFound => return UpdateError::NotFound,
+        std::io::ErrorKind::PermissionDenied => return UpdateError::AuthFailed,
+        std::io::ErrorKind::OutOfMemory => return UpdateError::TooLarge,
+        _ => {
+            let message = e.to_string();
+            if message.contains(" 401") || message.contains(" 403") {
+                return UpdateError::AuthFailed;
+            }
+            return UpdateError::Internal;
+        }
+    }
+}
+
+/** Determines the kind of the git error that stopped the update. 
+ 
+    Failed authentication and missing repositories are told apart the same way as by `git_error_kind`, errors of objects too large to be processed by their message. All other errors are recorded with their libgit2 error codes. 
+ */
+fn git_update_error(e : & git2::Error) -> UpdateError {
+    if e.code() == git2::ErrorCode::Auth {
+        return UpdateError::AuthFailed;
+    }
+    if e.class() == git2::ErrorClass::Http && (e.message().contains("404") || e.message().contains("410")) {
+        return UpdateError::NotFound;
+    }
+    if e.class() == git2::ErrorClass::NoMemory || e.message().contains("too large") {
+        return UpdateError::TooLarge;
+    }
+    return UpdateError::GitError{code : e.raw_code()};
+}
+
+/** A git error translated into an io error, which keeps the kind of the git error for the project log. 
+ */
+struct GitFailure {
+    kind : UpdateError,
+    message : String,
+}
+
+/* Formatted as the message alone, so that the errors read the same as io errors created from strings. */
+impl std::fmt::Debug for GitFailure {
+    fn fmt(& self, f : & mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(f, "{:?}", self.message);
+    }
+}
+
+impl std::fmt::Display for GitFailure {
+    fn fmt(& self, f : & mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(f, "{}", self.message);
+    }
+}
+
+impl std::error::Error for GitFailure {}
+
+/** Translates git error into an io error of given kind that carries the kind of the git error, see `update_error_kind`. 
+ 
+    Errors translated as timeouts, such as when the update was cancelled by the watchdog, are recorded as timeouts regardless of the git error. 
+ */
+fn git_io_error(e : & git2::Error, kind : std::io::ErrorKind) -> std::io::Error {
+    let failure = if kind == std::io::ErrorKind::TimedOut { UpdateError::Timeout } else { git_update_error(e) };
+    return std::io::Error::new(kind, GitFailure{ kind : failure, message : e.message().to_owned() });
+}
+
+/** Translates an error of the datastore, such as when a mapping cannot be written, into a git error so that it can be returned from the analysis of the repository. 
+ */
+fn storage_error(e : std::io::Error) -> git2::Error {
+    return git2::Error::from_str(& format!("Cannot store in datastore: {}", e));
+}
+
+lazy_static! {
+    static ref CLONE_CACHE : std::sync::Mutex<CloneCache> = std::sync::Mutex::new(CloneCache::new());
+    static ref HOST_BANDWIDTH : HostBandwidth = HostBandwidth::new();
+}
+
+/** Bandwidth of the clones shared per host. 
+
+    Each host with a bandwidth limit has the time at which its bandwidth is free again, which every clone from the host moves forward by the time its received bytes take at the limit. Clones that are ahead of the limit by more than a second sleep in their progress callbacks, which stops reading from the connection and so slows down the host's sending as well. 
+ */
+struct HostBandwidth {
+    hosts : std::sync::Mutex<HashMap<String, std::time::Instant>>,
+}
+
+impl HostBandwidth {
+
+    /** Clones may get ahead of the host's bandwidth limit by this duration before they are slowed down, so that short bursts are not delayed. 
+     */
+    const BURST : std::time::Duration = std::time::Duration::from_secs(1);
+
+    fn new() -> HostBandwidth {
+        return HostBandwidth{ hosts : std::sync::Mutex::new(HashMap::new()) };
+    }
+
+    /** Accounts given number of bytes received from the host and sleeps as long as needed to keep the host's bandwidth under the limit. 
+     */
+    fn throttle(& self, host : & str, bytes : usize, bandwidth : u64) {
+        let wait = {
+            let mut hosts = self.hosts.lock().unwrap();
+            let now = std::time::Instant::now();
+            let free = hosts.entry(host.to_owned()).or_insert(now);
+            if *free < now {
+                *free = now;
+            }
+            *free += std::time::Duration::from_secs_f64(bytes as f64 / bandwidth as f64);
+            free.saturating_duration_since(now)
+        };
+        if wait > HostBandwidth::BURST {
+            std::thread::sleep(wait - HostBandwidth::BURST);
+        }
+    }
+}
+
+/** Repository clones kept between the updates of their projects. 
+
+    When enabled by the `--clone-cache` setting, full clones of successfully updated projects are kept in the datastore's clones folder instead of being deleted, so that the next update of the project only fetches the new objects into the existing repository. Once the total size of the kept clones exceeds the setting, the least recently used clones are deleted. 
+
+    Kept clones contain a marker file, so that only clones known to be complete are reused. When the cache is first used, it is loaded from the clones folder using the modification times of the markers as the last uses of the clones. 
+
+    The cache is shared by all update threads, so the clones are only measured and deleted outside of its lock. Evicted clones lose their marker and are moved aside under the lock so that a new clone of the same project cannot collide with their deletion. 
+ */
+struct CloneCache {
+    loaded : bool,
+    /** Size and time of last use of the kept clones. 
+     */
+    clones : HashMap<ProjectId, (u64, i64)>,
+    size : u64,
+}
+
+impl CloneCache {
+
+    /** Name of the marker file in the kept clones. 
+     */
+    const MARKER : &'static str = "parasite-clone-cache";
+
+    fn new() -> CloneCache {
+        return CloneCache{ loaded : false, clones : HashMap::new(), size : 0 };
+    }
+
+    /** Takes the clone of given project from the cache, so that it can be updated. 
+
+        Returns true if the project has a kept clone in given folder, in which case the clone is no longer accounted for by the cache until it is put back. 
+     */
+    fn take(folder : & str, id : ProjectId) -> bool {
+        let mut cache = CloneCache::lock(folder);
+        if let Some((size, _)) = cache.clones.remove(& id) {
+            cache.size -= size;
+            // the clone is not known to be complete until it is put back
+            return std::fs::remove_file(format!("{}/{}/{}", folder, u64::from(id), CloneCache::MARKER)).is_ok();
+        }
+        return false;
+    }
+
+    /** Puts the updated clone of given project in the cache and deletes the least recently used clones if the cache is too large. 
+     */
+    fn put(folder : & str, id : ProjectId, budget : u64) {
+        let path = format!("{}/{}", folder, u64::from(id));
+        if std::fs::write(format!("{}/{}", path, CloneCache::MARKER), "").is_err() {
+            std::fs::remove_dir_all(& path).ok();
+            return;
+        }
+        let size = helpers::folder_size(std::path::Path::new(& path));
+        let mut evicted = Vec::new();
+        {
+            let mut cache = CloneCache::lock(folder);
+            if let Some((old_size, _)) = cache.clones.insert(id, (size, helpers::now())) {
+                cache.size -= old_size;
+            }
+            cache.size += size;
+            while cache.size > budget {
+                let oldest = cache.clones.iter().min_by_key(|(_, (_, used))| *used).map(|(id, _)| *id).unwrap();
+                let (size, _) = cache.clones.remove(& oldest).unwrap();
+                cache.size -= size;
+                let victim = format!("{}/{}", folder, u64::from(oldest));
+                let aside = format!("{}.evicted", victim);
+                std::fs::remove_file(format!("{}/{}", victim, CloneCache::MARKER)).ok();
+                if std::fs::rename(& victim, & aside).is_ok() {
+                    evicted.push(aside);
+                } else {
+                    std::fs::remove_dir_all(& victim).ok();
+                }
+            }
+        }
+        for victim in evicted {
+            std::fs::remove_dir_all(victim).ok();
+        }
+    }
+
+    /** Locks the cache, loading it from given folder first if not loaded yet. 
+     
+        The clones are measured without holding the lock. Should another thread load the cache meanwhile, its result is used instead. 
+     */
+    fn lock(folder : & str) -> std::sync::MutexGuard<'static, CloneCache> {
+        {
+            let cache = CLONE_CACHE.lock().unwrap();
+            if cache.loaded {
+                return cache;
+            }
+        }
+        let (clones, size) = CloneCache::scan(folder);
+        let mut cache = CLONE_CACHE.lock().unwrap();
+        if ! cache.loaded {
+            cache.loaded = true;
+            cache.clones = clones;
+            cache.size = size;
+        }
+        return cache;
+    }
+
+    /** Returns the kept clones in given folder and their total size. 
+     */
+    fn scan(folder : & str) -> (HashMap<ProjectId, (u64, i64)>, u64) {
+        let mut clones = HashMap::new();
+        let mut total = 0;
+        if let Ok(entries) = std::fs::read_dir(folder) {
+            for entry in entries.filter_map(|x| x.ok()) {
+                if let Some(id) = entry.file_name().to_str().and_then(|x| x.parse::<u64>().ok()) {
+                    if let Ok(marker) = std::fs::metadata(entry.path().join(CloneCache::MARKER)) {
+                        let used = marker.modified().ok()
+                            .and_then(|x| x.duration_since(std::time::UNIX_EPOCH).ok())
+                            .map(|x| x.as_secs() as i64)
+                            .unwrap_or(0);
+                        let size = helpers::folder_size(& entry.path());
+                        clones.insert(ProjectId::from(id), (size, used));
+                        total += size;
+                    }
+                }
+            }
+        }
+        return (clones, total);
+    }
+}
+
+/** Deletes the clones left in the clones folder of the datastore by updates that never finished, such as when parasite was killed. 
+
+    Clones kept by the clone cache have their marker and are left alone. Must be called before any update starts. Returns the number of deleted clones. 
+ */
+pub (crate) fn clean_orphaned_clones(ds : & Datastore) -> usize {
+    let mut result = 0;
+    if let Ok(entries) = std::fs::read_dir(ds.clones_folder()) {
+        for entry in entries.filter_map(|x| x.ok()) {
+            if ! entry.path().join(CloneCache::MARKER).exists() && std::fs::remove_dir_all(entry.path()).is_ok() {
+                result += 1;
+            }
+        }
+    }
+    return result;
+}
+
 /** A convenience struct because I do not want to drag everything as function arguments.
  */
 struct RepoUpdater<'a> {
@@ -45,9 +450,9 @@ struct RepoUpdater<'a> {
     project : ProjectUrl,
     force : bool,
     load_substore : bool,
-    /** The substore, or tentative substore for the project (see check_repository_substore function for more details). 
+    /** The GitHub metadata of the project obtained in this update, used to determine the project's substore. 
      */
-    tentative_substore : StoreKind,
+    metadata : Option<json::JsonValue>,
     changed : bool,
     local_folder : String,
     visited_commits : HashMap<SHA, CommitId>,
@@ -55,10 +460,50 @@ struct RepoUpdater<'a> {
     paths : HashMap<String, PathId>,
     q : Vec<(SHA, CommitId)>,
     snapshots : usize,
+    /** Number of secrets found in the snapshotted contents, if secrets are scrubbed. 
+     */
+    secrets : usize,
+    /** What the update changed, recorded with the ok record of the update. 
+     */
+    changes : UpdateChanges,
+    /** If the clone was aborted because of the clone limits, the description of the exceeded limit. 
+     */
+    skipped : Option<String>,
+    /** Size of the repository in bytes, if known from the metadata. 
+     */
+    repo_size : Option<u64>,
+    /** True if the repository was cloned partially, i.e. without file contents. 
+     */
+    partial : bool,
+    partial_clone_failed : bool,
+    /** True if the project's clone from an earlier update was taken from the clone cache. 
+     */
+    cached : bool,
+    /** True if the clone should be put in the clone cache once the update is done, otherwise it is deleted. 
+     */
+    keep_clone : bool,
+    /** False for metadata updates, which never touch the local clone, so that they neither take the clone from the cache, nor delete the clone of a concurrent update of the project. 
+     */
+    uses_clone : bool,
+    /** Filters of the paths whose contents are stored, as set for the project's substore. 
+     */
+    path_filters : PathFilters,
+    /** Hooks to run once the project has been updated, none for other than full updates. 
+     */
+    hooks : &'a [Box<dyn UpdateHook>],
 }
 
 impl<'a> Drop for RepoUpdater<'a> {
     fn drop(& mut self) {
+        if ! self.uses_clone {
+            return;
+        }
+        if self.keep_clone {
+            if let Some(budget) = SETTINGS.clone_cache {
+                CloneCache::put(& self.ds.clones_folder(), self.id, budget);
+                return;
+            }
+        }
         match std::fs::remove_dir_all(& self.local_folder) {
             _ => {},
         }
@@ -70,7 +515,8 @@ impl<'a> RepoUpdater<'a> {
     /** Creates new repository updater. 
      */
     fn new(ds : &'a Datastore, gh : &'a Github, task : TaskStatus<'a>, force : bool, load_substore : bool) -> RepoUpdater<'a> {
-        if let Task::UpdateRepo{id, last_update_time : _ } = task.task {
+        if let Task::UpdateRepo{id, last_update_time : _, class : _ } | Task::DryRunRepo{id, last_update_time : _ } | Task::UpdateHeads{id, last_update_time : _ } | Task::UpdateMetadata{id, last_check_time : _ } = task.task {
+            let uses_clone = ! matches!(task.task, Task::UpdateMetadata{id : _, last_check_time : _});
             return RepoUpdater {
                 ds,
                 gh,
@@ -79,14 +525,25 @@ impl<'a> RepoUpdater<'a> {
                 project : ds.get_project(id).unwrap(),
                 force,
                 load_substore,
-                tentative_substore : StoreKind::Unspecified,
+                metadata : None,
                 changed : false,
-                local_folder : format!("{}/repo_clones/{}", ds.root_folder(), u64::from(id)),
+                local_folder : format!("{}/{}", ds.clones_folder(), u64::from(id)),
                 visited_commits : HashMap::new(),
                 users : HashMap::new(),
                 paths : HashMap::new(),
                 q : Vec::new(),
                 snapshots : 0,
+                secrets : 0,
+                changes : UpdateChanges::default(),
+                skipped : None,
+                repo_size : None,
+                partial : false,
+                partial_clone_failed : false,
+                cached : uses_clone && SETTINGS.clone_cache.is_some() && CloneCache::take(& ds.clones_folder(), id),
+                keep_clone : false,
+                uses_clone,
+                path_filters : PathFilters::default(),
+                hooks : & [],
             };
         } else {
             panic!("Invalid task kind");
@@ -99,21 +556,69 @@ impl<'a> RepoUpdater<'a> {
      */
     fn update(& mut self) -> Result<(), std::io::Error> {
         self.task.extra_url(self.project.name(), self.project.clone_url());
+        // projects may have been denied after they were scheduled
+        if self.ds.suppress_if_denied(self.id) {
+            self.task.info("denied");
+            self.task.color("\x1b[90m");
+            return Ok(());
+        }
         if self.can_be_updated() {
-            self.check_metadata()?;
+            match self.check_metadata() {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return self.mark_deleted(format!("metadata: {}", e)),
+                result => result?,
+            }
             // update the project contents
             match self.update_repository() {
                 Err(e) => {
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e.message())));
+                    // tasks cancelled by the watchdog are treated as timeouts so that they will be retried
+                    let kind = if self.task.is_cancelled() { 
+                        std::io::ErrorKind::TimedOut 
+                    } else if self.partial_clone_failed { 
+                        std::io::ErrorKind::ConnectionAborted 
+                    } else { 
+                        git_error_kind(& e) 
+                    };
+                    if kind == std::io::ErrorKind::NotFound {
+                        return self.mark_deleted(format!("clone: {}", e.message()));
+                    }
+                    return Err(git_io_error(& e, kind));
                 },
                 Ok(processed) => {
+                    // partial clones are not kept as their missing contents would have to be fetched on demand again anyway
+                    self.keep_clone = self.skipped.is_none() && ! self.partial;
+                    // projects exceeding the clone limits are reported as skipped
+                    if let Some(reason) = self.skipped.take() {
+                        self.ds.update_project_update_status(self.id, ProjectLog::Skipped{
+                            time : helpers::now(),
+                            version : Datastore::VERSION,
+                            reason : reason.clone(),
+                        });
+                        self.task.info(format!("skipped: {}", reason));
+                        self.task.color("\x1b[93m");
+                        return Ok(());
+                    }
                     // if there was no error and the task was not cancelled, report the change / no-change 
                     if processed {
+                        if self.secrets > 0 {
+                            let total = self.ds.get_project_metadata(self.id, Metadata::SECRETS).and_then(|x| x.parse::<usize>().ok()).unwrap_or(0);
+                            self.ds.update_project_metadata_if_differ(self.id, Metadata::SECRETS.to_owned(), (total + self.secrets).to_string());
+                        }
                         if self.changed {
+                            // the filters are recorded whenever they change, so that it is known which contents were stored, together with the record of the finished update
+                            let filters = self.path_filters.to_string();
+                            if self.ds.get_project_path_filters(self.id).unwrap_or_default() != filters {
+                                self.ds.update_project_update_status(self.id, ProjectLog::PathFilters{
+                                    time : helpers::now(),
+                                    version : Datastore::VERSION,
+                                    filters,
+                                });
+                            }
                             self.ds.update_project_update_status(self.id, ProjectLog::Ok{
                                 time : helpers::now(),
                                 version : Datastore::VERSION,
+                                changes : Some(self.changes),
                             });
+                            self.run_update_hooks();
                             self.task.info("ok");
                             self.task.color("\x1b[92m");
                         } else {
@@ -134,6 +639,208 @@ impl<'a> RepoUpdater<'a> {
         return Ok(());
     }
 
+    /** Runs the update hooks on the updated project and stores their results in the project metadata. 
+     
+        Errors and panics of the hooks are stored as their `error` results and otherwise ignored, the error of a hook is cleared once it succeeds again. 
+     */
+    fn run_update_hooks(& self) {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let project = UpdatedProject{
+            ds : self.ds,
+            id : self.id,
+            project : & self.project,
+            substore : self.ds.get_project_substore(self.id),
+            clone_folder : & self.local_folder,
+        };
+        for hook in self.hooks.iter() {
+            self.task.info(format!("running update hook {}...", hook.name()));
+            let error_key = format!("{}.error", hook.name());
+            let error = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook.run(& project))) {
+                Ok(Ok(results)) => {
+                    for (key, value) in results {
+                        self.ds.update_project_metadata_if_differ(self.id, format!("{}.{}", hook.name(), key), value);
+                    }
+                    String::new()
+                },
+                Ok(Err(e)) => format!("{}", e),
+                Err(cause) => match cause.downcast_ref::<&str>() {
+                    Some(msg) => format!("PANIC: {}", msg),
+                    None => format!("PANIC: {}", cause.downcast_ref::<String>().map(|x| x.as_str()).unwrap_or("unknown cause")),
+                },
+            };
+            if ! error.is_empty() {
+                LOG!("Update hook {} failed for project {}: {}", hook.name(), self.id, error);
+            }
+            // only projects whose hook failed before have the error key
+            if ! error.is_empty() || self.ds.get_project_metadata(self.id, & error_key).is_some() {
+                self.ds.update_project_metadata_if_differ(self.id, error_key, error);
+            }
+        }
+    }
+
+    /** Records that the repository of the project no longer exists. 
+     
+        The data of the project are kept, only the deleted record is added to its log, which stops further scheduling of its updates. 
+     */
+    fn mark_deleted(& mut self, reason : String) -> Result<(), std::io::Error> {
+        self.ds.update_project_update_status(self.id, ProjectLog::Deleted{
+            time : helpers::now(),
+            version : Datastore::VERSION,
+            reason : reason.clone(),
+        });
+        self.task.info(format!("deleted: {}", reason));
+        self.task.color("\x1b[90m");
+        return Ok(());
+    }
+
+    /** Determines what the update of the project would do without changing the datastore. 
+     
+        Gets the metadata of GitHub projects and the remote heads and tags and compares them to the last known ones. The number of new commits is estimated from GitHub comparisons of the changed heads with their last known commits, so new heads are not included and commits shared by multiple heads are counted multiple times. Returns the line of the dry run report. 
+     */
+    fn dry_run(& mut self) -> Result<String, std::io::Error> {
+        self.task.extra_url(self.project.name(), self.project.clone_url());
+        let mut changed = false;
+        let mut size = String::new();
+        if let ProjectUrl::GitHub{user_and_repo} = & self.project {
+            self.task.info("checking metadata...");
+            let metadata = self.gh.get_repo(user_and_repo, Some(& self.task))?;
+            if let Some(kb) = metadata["size"].as_u64() {
+                size = (kb * 1024).to_string();
+            }
+            // a rename would change the project
+            let new_url = format!("{}.git",metadata["html_url"]).to_lowercase();
+            changed = ProjectUrl::from_url(& new_url).map(|x| x != self.project).unwrap_or(false);
+        }
+        self.task.info("listing remote heads...");
+        let (remote_heads, remote_tags) = self.list_remote().map_err(|e| git_io_error(& e, git_error_kind(& e)))?;
+        let last_heads = self.get_latest_heads();
+        let last_tags = self.get_latest_tags();
+        let mut heads_new = 0;
+        let mut pairs = Vec::new();
+        for (name, (_, hash)) in remote_heads.iter() {
+            match last_heads.get(name) {
+                Some((_, last_hash)) => {
+                    if hash != last_hash {
+                        pairs.push((last_hash.to_string(), hash.to_string()));
+                    }
+                },
+                None => heads_new += 1,
+            }
+        }
+        let heads_deleted = last_heads.keys().filter(|x| ! remote_heads.contains_key(*x)).count();
+        let tags_changed = remote_tags.iter().filter(|(name, (_, hash))| last_tags.get(*name).map(|(_, x)| x != hash).unwrap_or(true)).count()
+            + last_tags.keys().filter(|x| ! remote_tags.contains_key(*x)).count();
+        changed = changed || ! pairs.is_empty() || heads_new > 0 || heads_deleted > 0 || tags_changed > 0;
+        let mut new_commits = String::new();
+        if let ProjectUrl::GitHub{user_and_repo} = & self.project {
+            if ! pairs.is_empty() {
+                self.task.info("comparing heads...");
+            }
+            // comparisons that fail, e.g. because of force pushes, are not included in the estimate
+            let commits : u64 = self.gh.get_comparisons(user_and_repo, & pairs, Some(& self.task)).iter()
+                .filter_map(|x| x.as_ref().ok().and_then(|json| json["ahead_by"].as_u64()))
+                .sum();
+            new_commits = commits.to_string();
+        }
+        self.task.info(if changed { "would change" } else { "no change" });
+        return Ok(format!("{},\"{}\",{},{},{},{},{},{},{}",
+            u64::from(self.id),
+            self.project.clone_url(),
+            changed,
+            pairs.len(),
+            heads_new,
+            heads_deleted,
+            tags_changed,
+            new_commits,
+            size
+        ));
+    }
+
+    /** Checks the metadata and remote heads of the project. 
+     
+        Projects that no longer exist are marked as deleted. The remote heads are recorded if their hashes differ from both the heads last seen by a heads only update and the analyzed heads. 
+     */
+    fn update_heads(& mut self) -> Result<(), std::io::Error> {
+        self.task.extra_url(self.project.name(), self.project.clone_url());
+        if self.ds.suppress_if_denied(self.id) {
+            self.task.info("denied");
+            self.task.color("\x1b[90m");
+            return Ok(());
+        }
+        match self.check_metadata() {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return self.mark_deleted(format!("metadata: {}", e)),
+            result => result?,
+        }
+        self.task.info("listing remote heads...");
+        let remote_heads = match self.list_remote() {
+            Ok((heads, _)) => heads,
+            Err(e) => {
+                let kind = if self.task.is_cancelled() { std::io::ErrorKind::TimedOut } else { git_error_kind(& e) };
+                if kind == std::io::ErrorKind::NotFound {
+                    return self.mark_deleted(format!("clone: {}", e.message()));
+                }
+                return Err(git_io_error(& e, kind));
+            },
+        };
+        // heads only updates do not analyze commits, so the heads are compared by their hashes only
+        let hashes = |heads : & ProjectHeads| heads.iter().map(|(name, (_, hash))| (name.clone(), *hash)).collect::<HashMap<String, SHA>>();
+        let last_heads = self.ds.get_project_remote_heads(self.id).unwrap_or_default();
+        let analyzed_heads = self.ds.get_project_heads(self.id).unwrap_or_default();
+        let remote_hashes = hashes(& remote_heads);
+        if remote_hashes != hashes(& last_heads) && remote_hashes != hashes(& analyzed_heads) {
+            self.ds.update_project_remote_heads(self.id, & remote_heads.iter().map(|(name, (_, hash))| (name.clone(), (CommitId::NONE, *hash))).collect());
+            self.changed = true;
+        }
+        if remote_hashes != hashes(& analyzed_heads) {
+            self.task.info("heads changed");
+            self.task.color("\x1b[92m");
+        } else {
+            self.task.info(if self.changed { "metadata changed" } else { "no change" });
+            self.task.color("\x1b[90m");
+        }
+        return Ok(());
+    }
+
+    /** Lists the current heads and tags of the remote. 
+     
+        An empty local repository is created for the remote, but nothing is fetched. 
+     */
+    fn list_remote(& mut self) -> Result<(ProjectHeads, ProjectTags), git2::Error> {
+        let repo = self.open_repository()?;
+        // nothing is fetched so a cached clone stays as it was
+        self.keep_clone = self.cached;
+        let mut remote = repo.find_remote("dcd")?;
+        let connection = remote.connect_auth(git2::Direction::Fetch, None, Some(proxy_options()))?;
+        let heads = self.get_remote_heads(& connection)?;
+        let tags = self.get_remote_tags(& connection)?;
+        return Ok((heads, tags));
+    }
+
+    /** Opens the local repository with the `dcd` remote pointing to the project. 
+     
+        Reuses the project's clone from the clone cache, if any. Otherwise any leftover repository is deleted and an empty one is created. 
+     */
+    fn open_repository(& mut self) -> Result<git2::Repository, git2::Error> {
+        if self.cached {
+            if let Ok(repo) = git2::Repository::open_bare(& self.local_folder) {
+                // the project might have been renamed since the clone was made
+                repo.remote_set_url("dcd", & self.project.clone_url())?;
+                self.task.info("reusing cached clone...");
+                return Ok(repo);
+            }
+            self.cached = false;
+        }
+        let path = std::path::Path::new(& self.local_folder);
+        if path.exists() {
+            std::fs::remove_dir_all(& path).unwrap();
+        } 
+        let repo = git2::Repository::init_bare(self.local_folder.clone())?;
+        repo.remote("dcd", & self.project.clone_url())?;
+        return Ok(repo);
+    }
+
     /** Checks whether the current project can be updated and whether the update should be forced. 
      
         TODO we should ideally do something smatrter when there is an error during the update, i.e. dependning on the error, etc. 
@@ -153,8 +860,11 @@ impl<'a> RepoUpdater<'a> {
      
         The default action is to do force update, which is technically not always what we want to do and different version situations should actually be covered here. 
      */
-    fn new_version_update(& mut self, _old : u16, _new : u16) {
-        self.force = true;
+    fn new_version_update(& mut self, _old : u16, new : u16) {
+        // version 1 only added kinds of project log records, so the updates of version 0 are complete
+        if new > 1 {
+            self.force = true;
+        }
     }
 
     fn check_metadata(& mut self) -> Result<(), std::io::Error> {
@@ -170,23 +880,48 @@ impl<'a> RepoUpdater<'a> {
                 self.task.info("checking metadata...");
                 let mut metadata = self.gh.get_repo(user_and_repo, Some(& self.task))
                 ?;
+                // github reports the size in kilobytes
+                self.repo_size = metadata["size"].as_u64().map(|x| x * 1024);
+                // the popularity is recorded on every check so that it is known over time
+                if let Some(popularity) = ProjectPopularity::from_metadata(& metadata) {
+                    self.ds.update_project_popularity(self.id, & popularity);
+                }
                 // check project rename
                 let new_url = format!("{}.git",metadata["html_url"]).to_lowercase();
                 self.check_url_change(& new_url)?;
                 // clean the metadata and store, if applicable
                 filter_github_metadata_keys(& mut metadata, true);
                 self.changed = self.ds.update_project_metadata_if_differ(self.id, Metadata::GITHUB_METADATA.to_owned(), metadata.to_string());
-                // update the project store if the language is provided in the metadata, i.e. hold the substore as provided by the metadata tentatively in the substore field, when the project is updated, the tentative value and the real value obtained from the datastore will be reconciled
-                if metadata["language"].is_string() {
-                    if let Some(substore) = StoreKind::from_string(metadata["language"].as_str().unwrap()) {
-                        self.tentative_substore = substore;
-                    }
+                if let Some(name) = metadata["default_branch"].as_str() {
+                    let metadata_changed = self.changed;
+                    self.check_default_branch(name, metadata_changed);
                 }
+                // keep the metadata for the substore classifier, which is called when the project is updated
+                self.metadata = Some(metadata);
             }
         }
         return Ok(());
     }
 
+    /** Stores the default branch of a GitHub project, if changed. 
+     
+        The protection of the branch costs an extra GitHub request, so it is only requested when the default branch is new, or when the metadata of the project have changed. If the request fails, the last known protection is kept. 
+     */
+    fn check_default_branch(& mut self, name : & str, metadata_changed : bool) {
+        let last = self.ds.get_project_default_branch(self.id).filter(|x| x.name == name);
+        let mut protected = last.and_then(|x| x.protected);
+        if protected.is_none() || metadata_changed {
+            if let ProjectUrl::GitHub{user_and_repo} = & self.project {
+                if let Ok(branch) = self.gh.get_branch(user_and_repo, name, Some(& self.task)) {
+                    protected = branch["protected"].as_bool().or(protected);
+                }
+            }
+        }
+        if self.ds.update_project_default_branch_if_differ(self.id, & DefaultBranch{ name : name.to_owned(), protected }) {
+            self.changed = true;
+        }
+    }
+
     /** Compares the newly obtained project url to the one stored and records project rename if applicable. 
      */
     fn check_url_change(& mut self, new_url : & str) -> Result<(), std::io::Error> {
@@ -211,32 +946,49 @@ impl<'a> RepoUpdater<'a> {
     fn update_repository(& mut self) -> Result<bool, git2::Error> {
         // determine the actual substore of the project from the datastore
         let mut substore = self.ds.get_project_substore(self.id);
-        // create local repository
-        // TODO reuse repository if found on disk already?, for now make sure there is no leftover repo present
-        let path = std::path::Path::new(& self.local_folder);
-        if path.exists() {
-            std::fs::remove_dir_all(& path).unwrap();
-        } 
-        // create the repository and add its remote
-        let repo = git2::Repository::init_bare(self.local_folder.clone())?;
-        let mut remote = repo.remote("dcd", & self.project.clone_url())?;
-        remote.connect(git2::Direction::Fetch)?;
-        // get own and remote heads and compare them 
+        // create local repository, or reuse the cached clone
+        let repo = self.open_repository()?;
+        let mut remote = repo.find_remote("dcd")?;
+        // get own and remote heads and tags, the connection is closed before the fetch, which connects again
         let last_heads = self.get_latest_heads();
-        let mut remote_heads = self.get_remote_heads(& mut remote)?;
+        let (mut remote_heads, default_branch, mut remote_tags) = {
+            let connection = remote.connect_auth(git2::Direction::Fetch, None, Some(proxy_options()))?;
+            (self.get_remote_heads(& connection)?, self.get_remote_default_branch(& connection)?, self.get_remote_tags(& connection)?)
+        };
+        // projects without metadata get the default branch from the remote
+        if self.metadata.as_ref().and_then(|x| x["default_branch"].as_str()).is_none() {
+            if let Some(name) = default_branch {
+                if self.ds.update_project_default_branch_if_differ(self.id, & DefaultBranch{ name, protected : None }) {
+                    self.changed = true;
+                }
+            }
+        }
         let heads_to_fetch = self.compare_project_heads(& last_heads, & mut remote_heads, substore);
+        // do the same for tags, which are compared exactly as heads are
+        let last_tags = self.get_latest_tags();
+        let tags_to_fetch = self.compare_project_heads(& last_tags, & mut remote_tags, substore);
         // fetch the repository from the remote and analyze its contents
-        if ! heads_to_fetch.is_empty() {
-            self.clone_repository(& mut remote, & heads_to_fetch)?;
+        if ! heads_to_fetch.is_empty() || ! tags_to_fetch.is_empty() {
+            let refs_to_fetch = heads_to_fetch.iter().chain(tags_to_fetch.iter()).map(|x| x.to_owned()).collect();
+            if self.should_clone_partially() {
+                self.clone_repository_partially(& refs_to_fetch)?;
+            } else {
+                self.clone_repository(& mut remote, & refs_to_fetch)?;
+            }
+            if self.skipped.is_some() {
+                return Ok(false);
+            }
             // check the repository's substore and terminate if the substore is not loaded should not be loaded
             substore = self.update_repository_substore(& repo, substore)?;
             if ! self.ds.substore(substore).is_loaded() {
                 if self.load_substore {
-                    self.ds.substore(substore).load(& self.task);
+                    self.ds.substore(substore).load(& self.task).map_err(storage_error)?;
                 } else {
                     return Ok(false);
                 }
             }
+            // the filters are recorded in the project log together with the successful update, see update
+            self.path_filters = PathFilters::for_substore(substore);
             // analyze the fetched heads
             let ds_s = self.ds.substore(substore);
             let mut i = 0;
@@ -249,44 +1001,140 @@ impl<'a> RepoUpdater<'a> {
                 i += 1;
                 self.task.progress(i, heads_to_fetch.len());
             }
+            // analyze the fetched tags, most of them will point to already analyzed commits so this is cheap
+            i = 0;
+            for tag in tags_to_fetch.iter() {
+                self.task.info(format!("analyzing tag {} ({} of {})", tag, i, tags_to_fetch.len()));
+                self.task.progress(i, tags_to_fetch.len());
+                let hash = remote_tags[tag].1;
+                // annotated tags whose peeled commit was not advertised by the remote must be peeled locally
+                let commit = repo.find_object(hash, None).and_then(|x| x.peel_to_commit());
+                match commit {
+                    Ok(commit) => {
+                        remote_tags.get_mut(tag).unwrap().0 = self.analyze_branch(& repo, commit.id(), ds_s)?;
+                    },
+                    Err(_) => {
+                        // tags of trees and blobs are ignored
+                        remote_tags.remove(tag);
+                    }
+                }
+                i += 1;
+                self.task.progress(i, tags_to_fetch.len());
+            }
         }
         // if either the heads to fetch were not empty (i.e. there was a content to download), or there was no content, but the number of heads is different (some heads were deleted), store the updated heads
         if ! heads_to_fetch.is_empty() || remote_heads.len() != last_heads.len() {
             self.ds.update_project_heads(self.id, & remote_heads);
             self.changed = true;
+            self.changes.changed_heads = count_changed_heads(& last_heads, & remote_heads);
+            let last_commits = self.ds.get_project_summary(self.id).map(|x| x.commits).unwrap_or(0);
+            self.update_summary(& repo, & remote_heads, substore);
+            self.changes.new_commits = self.ds.get_project_summary(self.id).map(|x| x.commits).unwrap_or(0).saturating_sub(last_commits);
+            self.update_commit_times(& remote_heads, substore);
+        } else if substore.is_specified() {
+            // projects updated before the summaries, their files and the commits by time index existed get them on their next update, projects without a substore have no commits to summarize
+            match self.ds.get_project_summary(self.id) {
+                None => self.update_summary(& repo, & remote_heads, substore),
+                Some(mut summary) if summary.files.is_none() => {
+                    if let Some(files) = self.find_files(& repo, & remote_heads, substore) {
+                        summary.files = Some(files);
+                        self.ds.update_project_summary(self.id, & summary);
+                        self.changed = true;
+                    }
+                },
+                Some(_) => {},
+            }
+            if ! self.ds.substore(substore).has_project_commit_times(self.id) {
+                self.update_commit_times(& remote_heads, substore);
+            }
+        }
+        if ! tags_to_fetch.is_empty() || remote_tags.len() != last_tags.len() {
+            self.ds.update_project_tags(self.id, & remote_tags);
+            self.changed = true;
         }
         return Ok(true);
     }
 
-    /** Check the repository to determine the substore that should be used for the update. 
-     
-        Returns the store kind for the project, taking the current  store kind as a hint. 
+    /** Recalculates the summary of the project from its current heads. 
      
+        If the files of the project cannot be determined, the files of its previous summary are kept. 
+     */
+    fn update_summary(& mut self, repo : & git2::Repository, heads : & ProjectHeads, substore : StoreKind) {
+        self.task.info("summarizing commits...");
+        let mut summary = self.ds.substore(substore).summarize_commits(heads);
+        summary.files = self.find_files(repo, heads, substore).or_else(|| self.ds.get_project_summary(self.id).and_then(|x| x.files));
+        self.ds.update_project_summary(self.id, & summary);
+    }
+
+    /** Adds the commits new since the last update to the commits by time index of the project. 
+     */
+    fn update_commit_times(& mut self, heads : & ProjectHeads, substore : StoreKind) {
+        self.task.info("indexing commits by time...");
+        self.ds.substore(substore).update_project_commit_times(self.id, heads);
+    }
+
+    /** Determines the README and LICENSE files of the project and classifies its license. 
      
+        The files are looked up in the root tree of the default branch's head, or of the `master`, or `main` branch if the default branch is not known. If neither exists, the first of the heads by name is used. Returns None when the head commit is not available in the repository, or the substore is not loaded so that the hashes of the files cannot be registered. 
      */
-    fn update_repository_substore(& mut self, repo : & git2::Repository, current_substore : StoreKind) -> Result<StoreKind, git2::Error> {
-        let mut substore = current_substore;
-        // all ubspecified projects start as small projects
-        if substore == StoreKind::Unspecified {
-            substore = StoreKind::SmallProjects; 
+    fn find_files(& self, repo : & git2::Repository, heads : & ProjectHeads, substore : StoreKind) -> Option<ProjectFiles> {
+        if ! self.ds.substore(substore).is_loaded() {
+            return None;
         }
-        // if the substore is that of small projects, we must verify that the project still has no more than N commits
-        if substore == StoreKind::SmallProjects {
-            if self.get_repo_commits(repo, Datastore::SMALL_PROJECT_THRESHOLD)? >= Datastore::SMALL_PROJECT_THRESHOLD {
-                substore = StoreKind::Unspecified;
+        let default_head = self.ds.get_project_default_branch(self.id).map(|x| x.head());
+        let head = default_head.iter().map(|x| x.as_str())
+            .chain(["refs/heads/master", "refs/heads/main"].iter().map(|x| *x))
+            .find_map(|x| heads.get(x))
+            .or_else(|| heads.keys().min().map(|x| & heads[x]));
+        let tree = head.and_then(|(_, hash)| repo.find_commit(*hash).ok()).and_then(|x| x.tree().ok())?;
+        self.task.info("looking for readme and license...");
+        let mut readme : Option<(usize, String, SHA)> = None;
+        let mut license : Option<(usize, String, SHA)> = None;
+        for entry in tree.iter() {
+            if entry.kind() != Some(git2::ObjectType::Blob) {
+                continue;
             }
-        }
-        // if the substore is not small project at this point, it is open to change
-        if substore != StoreKind::SmallProjects {
-            // if tentative substore has been found out, set the substore accordingly
-            if self.tentative_substore != StoreKind::Unspecified {
-                substore = self.tentative_substore;
-            // otherwise if the substore is unspecified, we must pick a substore, so determine one. 
-            } else if substore == StoreKind::Unspecified || substore == StoreKind::Generic {
-                // TODO Determine some better substore than this
-                substore = StoreKind::Generic;
+            if let Some(name) = entry.name() {
+                if let Some(rank) = project_file_rank(name, & ["readme"]) {
+                    if readme.as_ref().map(|(r, n, _)| (rank, name) < (*r, n.as_str())).unwrap_or(true) {
+                        readme = Some((rank, name.to_owned(), entry.id()));
+                    }
+                }
+                if let Some(rank) = project_file_rank(name, & ["license", "licence", "copying"]) {
+                    if license.as_ref().map(|(r, n, _)| (rank, name) < (*r, n.as_str())).unwrap_or(true) {
+                        license = Some((rank, name.to_owned(), entry.id()));
+                    }
+                }
             }
         }
+        // the license is classified from the license file, or from the readme if there is no license file
+        let license_id = license.as_ref().or(readme.as_ref())
+            .and_then(|(_, _, hash)| self.find_blob_contents(repo, *hash))
+            .and_then(|contents| classify_license(& contents));
+        let ds_s = self.ds.substore(substore);
+        return Some(ProjectFiles{
+            readme : readme.and_then(|(_, name, hash)| ds_s.get_or_create_hash_id(& hash).ok().map(|(id, _)| (name, id))),
+            license : license.and_then(|(_, name, hash)| ds_s.get_or_create_hash_id(& hash).ok().map(|(id, _)| (name, id))),
+            license_id,
+        });
+    }
+
+    /** Check the repository to determine the substore that should be used for the update. 
+     
+        Returns the store kind for the project as determined by the datastore's substore classifier from the project's metadata, commits and optionally the languages of its files, taking the current store kind as a hint. 
+     */
+    fn update_repository_substore(& mut self, repo : & git2::Repository, current_substore : StoreKind) -> Result<StoreKind, git2::Error> {
+        let classifier = self.ds.substore_classifier();
+        let limit = classifier.commits_limit();
+        let project = ProjectFeatures{
+            metadata : self.metadata.as_ref(),
+            commits : self.get_repo_commits(repo, limit)?,
+            languages : if classifier.uses_languages() { repo_languages(repo)? } else { HashMap::new() },
+        };
+        let mut substore = classifier.classify(current_substore, & project);
+        if ! substore.is_specified() {
+            substore = StoreKind::Generic;
+        }
         // check if the substore changed and if so, update the substore information. 
         if substore != current_substore {
             self.ds.update_project_substore(self.id, substore);
@@ -340,11 +1188,51 @@ impl<'a> RepoUpdater<'a> {
         }
     }
 
+    /** Returns the tags as of last analysis. 
+     
+        Behaves the same as heads, i.e. if the update is forced, or there are no tags recorded, returns empty tags. 
+     */
+    fn get_latest_tags(& mut self) -> ProjectTags {
+        if self.force {
+            return ProjectTags::new();
+        }
+        if let Some(tags) = self.ds.get_project_tags(self.id) {
+            return tags;
+        } else {
+            return ProjectTags::new();
+        }
+    }
+
+    /** Returns current tags from the remote. 
+     
+        For annotated tags, the remote also advertises the peeled `^{}` reference with the hash of the commit the tag points to. If present, this hash is used instead of the hash of the tag object itself so that the hash is the same for lightweight and annotated tags.
+     */
+    fn get_remote_tags(& mut self, remote : & git2::RemoteConnection) -> Result<ProjectTags, git2::Error> {
+        let mut result = ProjectTags::new();
+        let mut peeled = HashMap::<String, SHA>::new();
+        for x in remote.list()? {
+            let name = x.name().to_owned();
+            if name.starts_with("refs/tags/") {
+                if name.ends_with("^{}") {
+                    peeled.insert(name.trim_end_matches("^{}").to_owned(), x.oid());
+                } else {
+                    result.insert(name, (CommitId::INVALID, x.oid()));
+                }
+            }
+        }
+        for (name, hash) in peeled {
+            if let Some((_, tag_hash)) = result.get_mut(& name) {
+                *tag_hash = hash;
+            }
+        }
+        return Ok(result);
+    }
+
     /** Returns current heads from the remote. 
      
         Does not assign ids to the obtained heads, as these will be obtained later from the latest heads, or from the datastore itself. 
      */
-    fn get_remote_heads(& mut self, remote : & mut git2::Remote) -> Result<ProjectHeads, git2::Error> {
+    fn get_remote_heads(& mut self, remote : & git2::RemoteConnection) -> Result<ProjectHeads, git2::Error> {
         let mut result = ProjectHeads::new();
         for x in remote.list()? {
             // TODO this is an issue in libgit2 it seems that a branch must be valid utf8, otherwise we will fail. For now that seems ok as it affects only a really small amount of projects
@@ -356,6 +1244,21 @@ impl<'a> RepoUpdater<'a> {
         return Ok(result);
     }
 
+    /** Determines the default branch of the remote from the head its `HEAD` points to.
+
+        The remote only advertises the commit of its `HEAD`, so the default branch is the head with the same commit. If there are more such heads, the branch cannot be determined and None is returned. 
+     */
+    fn get_remote_default_branch(& self, remote : & git2::RemoteConnection) -> Result<Option<String>, git2::Error> {
+        let list = remote.list()?;
+        if let Some(head) = list.iter().find(|x| x.name() == "HEAD") {
+            let mut candidates = list.iter().filter(|x| x.name().starts_with("refs/heads/") && x.oid() == head.oid());
+            if let (Some(branch), None) = (candidates.next(), candidates.next()) {
+                return Ok(Some(branch.name()["refs/heads/".len()..].to_owned()));
+            }
+        }
+        return Ok(None);
+    }
+
     /** Compares the last heads of the repository with the new ones and returns the list of heads to be downloaded.
 
         For unchanged heads, updates their id from the last records. 
@@ -384,21 +1287,171 @@ impl<'a> RepoUpdater<'a> {
 
     /** Clones the repository from given remote. 
      
-        Clones the specified refs and reports the progress via the task message updates. 
+        Clones the specified refs and reports the progress via the task message updates. If the clone exceeds any of the clone limits from the settings, the fetch is aborted and the exceeded limit is recorded in the `skipped` field. The time limit is also passed to the watchdog as the task's deadline so that it is enforced even when the server stalls and no progress is reported. If the host of the project has a bandwidth limit, the clone is slowed down so that all clones from the host together stay under the limit. 
      */
     fn clone_repository(& mut self, remote : & mut git2::Remote, heads : & Vec<String>) -> Result<(), git2::Error> {
         self.task.info("downloading repository contents...");
-        let mut callbacks = git2::RemoteCallbacks::new();
-        callbacks.transfer_progress(|progress : git2::Progress| -> bool {
-            self.task.progress(
-                progress.received_objects() + progress.indexed_deltas() + progress.indexed_objects(),
-                progress.total_deltas() + progress.total_objects() * 2
-            );
-            return true;
+        let start = std::time::Instant::now();
+        let skipped = std::cell::RefCell::new(None);
+        let host = self.project.host();
+        let bandwidth = SETTINGS.host_limit(& host).bandwidth;
+        let mut received_bytes = 0;
+        if let Some(max_time) = SETTINGS.max_clone_time {
+            self.task.deadline(Some(helpers::now() + max_time as i64));
+        }
+        let result;
+        {
+            let mut callbacks = git2::RemoteCallbacks::new();
+            callbacks.transfer_progress(|progress : git2::Progress| -> bool {
+                if let Some(bandwidth) = bandwidth {
+                    HOST_BANDWIDTH.throttle(& host, progress.received_bytes().saturating_sub(received_bytes), bandwidth);
+                    received_bytes = progress.received_bytes();
+                }
+                self.task.progress(
+                    progress.received_objects() + progress.indexed_deltas() + progress.indexed_objects(),
+                    progress.total_deltas() + progress.total_objects() * 2
+                );
+                if let Some(reason) = exceeded_clone_limit(& progress, start.elapsed().as_secs()) {
+                    *skipped.borrow_mut() = Some(reason);
+                    return false;
+                }
+                // returning false aborts the fetch
+                return ! self.task.is_cancelled();
+            });
+            let mut opts = git2::FetchOptions::new();
+            opts.remote_callbacks(callbacks); 
+            opts.proxy_options(proxy_options());
+            if SETTINGS.clone_cache.is_some() {
+                // the fetched refs are stored locally so that the next fetch into the cached clone only downloads the new objects
+                let refspecs : Vec<String> = heads.iter().map(|x| format!("+{}:{}", x, x)).collect();
+                result = remote.fetch(& refspecs, Some(&mut opts), None);
+            } else {
+                result = remote.fetch(& heads, Some(&mut opts), None);
+            }
+        }
+        if SETTINGS.max_clone_time.is_some() {
+            self.task.deadline(None);
+        }
+        // aborting the fetch from the callback is reported as an error, which we do not want, the project is skipped instead
+        if let Some(reason) = skipped.into_inner() {
+            self.skipped = Some(reason);
+            return Ok(());
+        }
+        if self.task.is_cancelled() {
+            // cancelled by the watchdog because of the clone deadline, which is a clone limit as well
+            if let Some(max_time) = SETTINGS.max_clone_time {
+                if start.elapsed().as_secs() >= max_time {
+                    self.skipped = Some(format!("clone time exceeded {} seconds", max_time));
+                    return Ok(());
+                }
+            }
+            return Err(git2::Error::from_str("Clone cancelled by watchdog"));
+        }
+        return result;
+    }
+
+    /** Determines whether the repository is large enough to be cloned partially. 
+     */
+    fn should_clone_partially(& self) -> bool {
+        if let (Some(threshold), Some(size)) = (SETTINGS.partial_clone_threshold, self.repo_size) {
+            return size > threshold;
+        }
+        return false;
+    }
+
+    /** Clones the specified refs from the remote without any file contents. 
+     
+        As libgit2 does not support partial clones, the `git` binary is used to fetch only the commits and trees into the local repository, which is configured so that any missing file contents can later be fetched on demand (see `find_blob_contents`). libgit2 can then analyze the fetched commits and trees as usual. Only the clone time limit applies to partial clones. 
+     */
+    fn clone_repository_partially(& mut self, heads : & Vec<String>) -> Result<(), git2::Error> {
+        self.task.info("downloading repository commits and trees (partial clone)...");
+        // the git binary honors the partial clone extension in repositories of format version 0, which unlike version 1 can still be opened by libgit2
+        for (key, value) in [
+            ("core.repositoryformatversion", "0"), 
+            ("extensions.partialClone", "dcd"), 
+            ("remote.dcd.promisor", "true"),
+            ("remote.dcd.partialclonefilter", "blob:none"),
+            // the git binary uses the same proxy as libgit2, the empty proxy disables the one git would take from the environment
+            ("remote.dcd.proxy", SETTINGS.curl_proxy())
+        ].iter() {
+            self.run_git(& ["config", key, value])?;
+        }
+        let mut child = std::process::Command::new("git")
+            .arg("-C").arg(& self.local_folder)
+            .args(& ["fetch", "--quiet", "--no-tags", "--filter=blob:none", "dcd"])
+            .args(heads)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| git2::Error::from_str(& format!("Cannot execute git: {}", e)))?;
+        // the error output is drained while git runs so that git never blocks on a full pipe
+        let mut err = child.stderr.take().unwrap();
+        let stderr = std::thread::spawn(move || {
+            let mut result = String::new();
+            std::io::Read::read_to_string(& mut err, & mut result).ok();
+            return result;
         });
-        let mut opts = git2::FetchOptions::new();
-        opts.remote_callbacks(callbacks); 
-        return remote.fetch(& heads, Some(&mut opts), None);        
+        let start = std::time::Instant::now();
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    if status.success() {
+                        self.partial = true;
+                        return Ok(());
+                    }
+                    let stderr = stderr.join().unwrap_or_default();
+                    // errors of the git binary are not classified, but the network is the most likely culprit so the failure is treated as transient
+                    self.partial_clone_failed = true;
+                    return Err(git2::Error::from_str(& format!("Partial clone failed: {}", stderr.trim())));
+                },
+                Ok(None) => {
+                    if self.task.is_cancelled() {
+                        child.kill().ok();
+                        child.wait().ok();
+                        return Err(git2::Error::from_str("Partial clone cancelled by watchdog"));
+                    }
+                    if let Some(max_time) = SETTINGS.max_clone_time {
+                        if start.elapsed().as_secs() > max_time {
+                            child.kill().ok();
+                            child.wait().ok();
+                            self.skipped = Some(format!("clone time exceeded {} seconds", max_time));
+                            return Ok(());
+                        }
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                },
+                Err(e) => return Err(git2::Error::from_str(& format!("Cannot wait for git: {}", e))),
+            }
+        }
+    }
+
+    /** Runs the git binary with given arguments in the local repository and returns its output. 
+     */
+    fn run_git(& self, args : & [& str]) -> Result<Vec<u8>, git2::Error> {
+        let output = std::process::Command::new("git")
+            .arg("-C").arg(& self.local_folder)
+            .args(args)
+            .output()
+            .map_err(|e| git2::Error::from_str(& format!("Cannot execute git: {}", e)))?;
+        if ! output.status.success() {
+            return Err(git2::Error::from_str(& format!("git {} failed: {}", args.join(" "), helpers::to_string(& output.stderr).trim())));
+        }
+        return Ok(output.stdout);
+    }
+
+    /** Returns the contents of given blob. 
+     
+        If the repository was cloned partially and the blob is not present locally, it is fetched on demand from the remote. 
+     */
+    fn find_blob_contents(& self, repo : & git2::Repository, hash : SHA) -> Option<Vec<u8>> {
+        if let Ok(blob) = repo.find_blob(hash) {
+            return Some(Vec::from(blob.content()));
+        }
+        if self.partial {
+            // git fetches the missing blob from the promisor remote
+            return self.run_git(& ["cat-file", "blob", & hash.to_string()]).ok();
+        }
+        return None;
     }
 
     /** Analyzes given branch, starting at a head commit and returns the id of the head commit. 
@@ -406,9 +1459,12 @@ impl<'a> RepoUpdater<'a> {
      */
     fn analyze_branch(& mut self, repo : & git2::Repository, head : SHA, substore : & Substore) -> Result<CommitId, git2::Error> {
         // add head to the queue
-        let head_id = self.add_commit(& head, substore);
+        let head_id = self.add_commit(& head, substore)?;
         // process the queue
         while let Some((hash, id)) = self.q.pop() {
+            if self.task.is_cancelled() {
+                return Err(git2::Error::from_str("Analysis cancelled by watchdog"));
+            }
             // get the commit and process it
             let commit = repo.find_commit(hash)?;
             let mut commit_info = CommitInfo::new();
@@ -421,33 +1477,77 @@ impl<'a> RepoUpdater<'a> {
             // get commit message
             commit_info.message = helpers::to_string(commit.message_bytes());
             // get parent ids and add parents to the queue
-            commit_info.parents = commit.parents().map(|x| self.add_commit(& x.id(), substore)).collect();
+            commit_info.parents = commit.parents().map(|x| self.add_commit(& x.id(), substore)).collect::<Result<_, _>>()?;
             // and finally, calculate the changes
-            commit_info.changes = self.get_commit_changes(repo, & commit, substore)?;
+            let (changes, line_stats, renames, submodules) = self.get_commit_changes(repo, & commit, substore)?;
+            commit_info.changes = changes;
+            // record the signature kind for signed commits
+            if let Ok((signature, _)) = repo.extract_signature(& hash, None) {
+                substore.update_commit_metadata_if_differ(id, Metadata::COMMIT_SIGNATURE.to_owned(), signature_kind(& signature).to_owned());
+            }
             // store the commit info
             substore.add_commit_info_if_missing(id, & commit_info);
+            if let Some(line_stats) = line_stats {
+                substore.add_commit_line_stats_if_missing(id, & line_stats);
+            }
+            if ! renames.is_empty() {
+                substore.add_commit_renames_if_missing(id, & renames);
+            }
+            if ! submodules.is_empty() {
+                substore.add_commit_submodules_if_missing(id, & submodules);
+            }
+            if SETTINGS.trees {
+                let tree_id = self.store_tree(repo, & commit.tree()?, substore)?;
+                substore.add_commit_tree_if_missing(id, tree_id);
+            }
             // update the information
             self.update_task();
         }
         return Ok(head_id);
     }
 
+    /** Stores given tree and all its subtrees that are not already stored and returns the id of the tree. 
+     
+        Subtrees are stored before their parents, so that a stored tree always has all its subtrees stored as well and the recursion can stop at trees already in the substore. Files are referenced by their hashes, whose ids are created if necessary, but their contents are not stored. 
+     */
+    fn store_tree(& mut self, repo : & git2::Repository, tree : & git2::Tree, substore : & Substore) -> Result<TreeId, git2::Error> {
+        let (id, _) = substore.get_or_create_tree_id(& tree.id()).map_err(storage_error)?;
+        if substore.has_tree(id) {
+            return Ok(id);
+        }
+        let mut entries = Tree::new();
+        for entry in tree.iter() {
+            let kind = match entry.kind() {
+                Some(git2::ObjectType::Tree) => TreeEntryKind::Tree(self.store_tree(repo, & repo.find_tree(entry.id())?, substore)?),
+                Some(git2::ObjectType::Commit) => TreeEntryKind::Submodule(entry.id()),
+                _ => TreeEntryKind::File(substore.get_or_create_hash_id(& entry.id()).map_err(storage_error)?.0),
+            };
+            entries.push(TreeEntry{
+                name : helpers::to_string(entry.name_bytes()),
+                mode : entry.filemode() as u32,
+                kind,
+            });
+        }
+        substore.add_tree_if_missing(id, & entries);
+        return Ok(id);
+    }
+
     /** Adds the given commit to the queue.
      
         Returns the id assigned to the commit. Only adds the commit to the queue if the commit did not exist before. Before going to the datastore, local cache is consulted first. 
 
         If the update is forced, all commits are reanalyzed even if they exist in the datastore
      */ 
-    fn add_commit(& mut self, hash : & SHA, substore : & Substore) -> CommitId {
+    fn add_commit(& mut self, hash : & SHA, substore : & Substore) -> Result<CommitId, git2::Error> {
         if let Some(id) = self.visited_commits.get(hash) {
-            return *id;
+            return Ok(*id);
         }
-        let (id, is_new) = substore.get_or_create_commit_id(hash);
+        let (id, is_new) = substore.get_or_create_commit_id(hash).map_err(storage_error)?;
         self.visited_commits.insert(*hash, id);
         if is_new || self.force {
             self.q.push((*hash, id)); 
         }
-        return id;
+        return Ok(id);
     }
 
     fn get_or_create_user(& mut self, user : & git2::Signature, substore : & Substore) -> UserId {
@@ -456,41 +1556,170 @@ impl<'a> RepoUpdater<'a> {
             return *id;
         } else {
             let (id, _) = substore.get_or_create_user_id(& email);
+            // the names are used to determine users that are the same person
+            substore.update_user_metadata_if_differ(id, Metadata::USER_NAME.to_owned(), helpers::to_string(user.name_bytes()));
             // add to cache
             self.users.insert(email, id);
-            // TODO check the username against usernames in the metadata of the user and so on? 
             return id;
         }
     }
 
-    fn get_commit_changes(& mut self, repo : & git2::Repository, commit : & git2::Commit, substore : & Substore) -> Result<HashMap<PathId, HashId>, git2::Error> {
+    /** Calculates the changes of given commit and stores the contents of new files where appropriate. 
+     
+        Returns the changes, the line stats of the changed paths if enabled and the files renamed by the commit. Line stats are calculated against the first parent only and are not available for partial clones as the diffs would need the contents of all changed files. For the same reason, only renames of unchanged files are detected in partial clones. 
+     */
+    fn get_commit_changes(& mut self, repo : & git2::Repository, commit : & git2::Commit, substore : & Substore) -> Result<(HashMap<PathId, HashId>, Option<LineStats>, PathRenames, CommitSubmodules), git2::Error> {
         // first create the changes map and populate it by changes between the commit and its parents, or the full commit if the commit has no parents
         let mut changes = HashMap::<String, SHA>::new();
+        let mut path_line_stats = if SETTINGS.line_stats && ! self.partial { Some(HashMap::<String, (u32, u32)>::new()) } else { None };
+        let mut path_renames = HashSet::<(String, String)>::new();
+        let mut gitlinks = HashMap::<String, SHA>::new();
         if commit.parent_count() == 0 {
-            calculate_tree_diff(repo, None, Some(& commit.tree()?), & mut changes)?;
+            calculate_tree_diff(repo, None, Some(& commit.tree()?), self.partial, & mut changes, path_line_stats.as_mut(), & mut path_renames, & mut gitlinks)?;
         } else {
-            for p in commit.parents() {
-                calculate_tree_diff(repo, Some(& p.tree()?), Some(& commit.tree()?), & mut changes)?;
+            for (i, p) in commit.parents().enumerate() {
+                let line_stats = if i == 0 { path_line_stats.as_mut() } else { None };
+                calculate_tree_diff(repo, Some(& p.tree()?), Some(& commit.tree()?), self.partial, & mut changes, line_stats, & mut path_renames, & mut gitlinks)?;
             }
         }
+        let submodules = if gitlinks.is_empty() { CommitSubmodules::new() } else { self.get_commit_submodules(repo, commit, gitlinks, substore)? };
         // time to convert paths to hashes
-        let result = self.convert_and_register_changes(changes, substore);
-        // now let's look over the changes and see if there is any file that we should snapshot
+        let result = self.convert_and_register_changes(changes, substore)?;
+        let line_stats = path_line_stats.map(|stats| {
+            return result.iter()
+                .filter_map(|(path_id, _, path, _, _)| stats.get(path).map(|x| (*path_id, *x)))
+                .collect::<LineStats>();
+        });
+        // both paths of a rename are in the changes, so their ids are known
+        let renames = if path_renames.is_empty() { PathRenames::new() } else {
+            let path_ids : HashMap<& String, PathId> = result.iter().map(|(path_id, _, path, _, _)| (path, *path_id)).collect();
+            path_renames.iter().map(|(old, new)| (path_ids[old], path_ids[new])).collect()
+        };
+        // now let's look over the changes and see if there is any file that we should snapshot, unless the substore is over its quota and in metadata only mode
+        let store_contents = ! (SETTINGS.quota_metadata_only && substore.is_over_quota());
         for (_path_id, hash_id, path, hash, is_new_hash) in result.iter() {
-            if *is_new_hash {
-                if let Some(path_kind) = ContentsKind::from_path(path) {
-                    if let Ok(blob) = repo.find_blob(*hash) {
-                        let contents = blob.content();
-                        if let Some(kind) = ContentsKind::from_contents(contents, path_kind) {
-                            substore.add_file_contents(*hash_id, kind, & Vec::from(contents));
-                            self.snapshots += 1;
+            if *is_new_hash && store_contents {
+                if ! self.path_filters.allows(path) {
+                    continue;
+                }
+                let path_kind = ContentsKind::from_path(path);
+                // LFS pointers are recorded whatever the path, but only small files can be pointers so larger ones are not read
+                if path_kind.is_none() && ! is_small_blob(repo, *hash) {
+                    continue;
+                }
+                if let Some(mut contents) = self.find_blob_contents(repo, *hash) {
+                    let mut metadata = Vec::new();
+                    let kind = match LfsPointer::parse(& contents) {
+                        Some(pointer) => match (path_kind, self.fetch_lfs_object(& pointer)) {
+                            (Some(path_kind), Some(object)) => {
+                                contents = object;
+                                metadata.push(Metadata{ key : Metadata::CONTENTS_LFS.to_owned(), value : pointer.oid });
+                                ContentsKind::from_contents(& contents, path_kind)
+                            },
+                            _ => Some(ContentsKind::LfsPointer),
+                        },
+                        None => path_kind.and_then(|path_kind| ContentsKind::from_contents(& contents, path_kind)),
+                    };
+                    if let Some(kind) = kind {
+                        if let Some(policy) = SETTINGS.scrub_secrets {
+                            let secrets = Secret::find(& contents);
+                            if ! secrets.is_empty() {
+                                self.secrets += secrets.len();
+                                if policy == ScrubSecrets::Skip {
+                                    continue;
+                                }
+                                contents = Secret::redact(& contents, & secrets);
+                                metadata.push(Metadata{ key : Metadata::CONTENTS_REDACTED.to_owned(), value : secrets.len().to_string() });
+                            }
                         }
-                    } 
+                        // deltas are against the blob of the previous version, which is not what is stored for LFS objects or redacted contents
+                        let delta = if metadata.is_empty() { self.get_contents_delta(repo, commit, path, kind, & contents, substore) } else { None };
+                        match delta {
+                            Some(delta) => {
+                                substore.add_file_contents_delta(*hash_id, & delta);
+                                self.changes.new_contents += delta.ops.len() as u64;
+                            },
+                            None => {
+                                substore.add_file_contents(*hash_id, kind, & contents);
+                                self.changes.new_contents += contents.len() as u64;
+                            },
+                        }
+                        metadata.extend(Metadata::of_contents(& contents));
+                        substore.add_file_contents_metadata(*hash_id, & metadata);
+                        self.snapshots += 1;
+                    }
                 }
             }
         }
         // finally get only the things we need for changes and return
-        return Ok(result.into_iter().map(|(path_id, hash_id, _, _, _)| (path_id, hash_id)).collect());
+        return Ok((result.into_iter().map(|(path_id, hash_id, _, _, _)| (path_id, hash_id)).collect(), line_stats, renames, submodules));
+    }
+
+    /** Downloads the LFS object of given pointer from the LFS server of the project. 
+     
+        Returns None if LFS objects are not downloaded, the object is larger than the limit, or it cannot be downloaded. 
+     */
+    fn fetch_lfs_object(& self, pointer : & LfsPointer) -> Option<Vec<u8>> {
+        if pointer.size > SETTINGS.lfs_fetch_limit? {
+            return None;
+        }
+        self.task.info(format!("downloading LFS object of {}...", helpers::pretty_size(pointer.size)));
+        match download_lfs_object(& self.project.clone_url(), pointer) {
+            Ok(object) => return Some(object),
+            Err(e) => {
+                LOG!("Unable to download LFS object {} of project {}: {}", pointer.oid, self.project.clone_url(), e);
+                return None;
+            }
+        }
+    }
+
+    /** Converts the gitlinks changed by the commit to submodules. 
+     
+        The urls of the submodules are taken from the `.gitmodules` file of the commit, if its contents are available. 
+     */
+    fn get_commit_submodules(& self, repo : & git2::Repository, commit : & git2::Commit, gitlinks : HashMap<String, SHA>, substore : & Substore) -> Result<CommitSubmodules, git2::Error> {
+        let urls = match commit.tree()?.get_path(std::path::Path::new(".gitmodules")) {
+            Ok(entry) => self.find_blob_contents(repo, entry.id()).map(|x| parse_gitmodules(& helpers::to_string(& x))).unwrap_or_default(),
+            Err(_) => HashMap::new(),
+        };
+        let (paths, commits) : (Vec<String>, Vec<SHA>) = gitlinks.into_iter().unzip();
+        let path_ids = substore.convert_paths_to_ids(& paths).map_err(storage_error)?;
+        return Ok(paths.iter().zip(commits).zip(path_ids).map(|((path, commit), (path_id, _))| {
+            return Submodule{
+                path : path_id,
+                commit,
+                url : urls.get(path).cloned().unwrap_or_default(),
+            };
+        }).collect());
+    }
+
+    /** Creates a delta of the contents against the previous version of the same path, if delta encoding is enabled and beneficial. 
+     
+        The previous version is looked up in the parents of the commit. It must have its contents already stored in the substore (in full, or as a delta) so that the delta can be reconstructed later. 
+     */
+    fn get_contents_delta(& self, repo : & git2::Repository, commit : & git2::Commit, path : & str, kind : ContentsKind, contents : & [u8], substore : & Substore) -> Option<ContentsDelta> {
+        if ! SETTINGS.delta_contents {
+            return None;
+        }
+        for p in commit.parents() {
+            if let Ok(entry) = p.tree().and_then(|tree| tree.get_path(std::path::Path::new(path))) {
+                // the delta is only an optimization, so a failed lookup means no delta
+                if let Some(base_id) = substore.get_hash_id(& entry.id()).ok().flatten() {
+                    if let Some(depth) = substore.get_file_contents_depth(base_id) {
+                        // the stored contents of LFS pointers may be their downloaded objects and those of blobs with secrets may be redacted
+                        if ! substore.is_contents_blob(base_id) {
+                            return None;
+                        }
+                        if let Some(base) = self.find_blob_contents(repo, entry.id()) {
+                            if let Some(delta) = ContentsDelta::create(base_id, depth, & base, kind, contents) {
+                                return Some(delta);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        return None;
     }
 
     /** Converts the paths and hashes expressed as strings and SHA hashes to their respective ids and returns a vector containing all. 
@@ -499,10 +1728,10 @@ impl<'a> RepoUpdater<'a> {
 
         Returns : path id, hash id, path, hash, is hash new?
      */
-    fn convert_and_register_changes(& mut self, changes : HashMap<String, SHA>, substore : & Substore) -> Vec<(PathId, HashId, String, SHA, bool)> {
+    fn convert_and_register_changes(& mut self, changes : HashMap<String, SHA>, substore : & Substore) -> Result<Vec<(PathId, HashId, String, SHA, bool)>, git2::Error> {
         // contents hashes are easy, we just go straight to the substore to get us the hash ids and whether they are new or not
         let hashes = changes.iter().map(|(_, hash)| *hash ).collect::<Vec<SHA>>();
-        let hash_ids = substore.convert_hashes_to_ids(& hashes);
+        let hash_ids = substore.convert_hashes_to_ids(& hashes).map_err(storage_error)?;
         // for paths we use two stage process, first convert what we can from the local cache, then convert the others via the substore and merge
         let mut unknown_paths = Vec::<String>::new();
         let mut paths = changes.into_iter().map(|(path, hash)| { // keep the hash around so that we can zip once
@@ -515,7 +1744,7 @@ impl<'a> RepoUpdater<'a> {
         }).collect::<Vec<(PathId, String, SHA)>>();
         // get the missing path ids
         if ! unknown_paths.is_empty() {
-            let path_ids = substore.convert_paths_to_ids(& unknown_paths);
+            let path_ids = substore.convert_paths_to_ids(& unknown_paths).map_err(storage_error)?;
             let mut i = path_ids.iter();
             for (id, _, _) in paths.iter_mut() {
                 if *id == PathId::EMPTY {
@@ -523,9 +1752,9 @@ impl<'a> RepoUpdater<'a> {
                 }
             }
         }
-        return paths.into_iter().zip(hash_ids.into_iter()).map(|((path_id, path, hash), (hash_id, is_new_hash))| {
+        return Ok(paths.into_iter().zip(hash_ids.into_iter()).map(|((path_id, path, hash), (hash_id, is_new_hash))| {
             return (path_id, hash_id, path, hash, is_new_hash);
-        }).collect();
+        }).collect());
     } 
 
     /** Updates the task information. 
@@ -537,6 +1766,210 @@ impl<'a> RepoUpdater<'a> {
 }
 
 
+/** Include and exclude glob patterns of the paths whose contents are stored. 
+ 
+    Contents of paths matching any exclude pattern are never stored. If there are include patterns, only contents of paths matching any of them are stored. 
+ */
+#[derive(Default)]
+struct PathFilters {
+    include : Vec<String>,
+    exclude : Vec<String>,
+}
+
+impl PathFilters {
+    /** Returns the filters from the settings that apply to given substore. 
+     */
+    fn for_substore(substore : StoreKind) -> PathFilters {
+        let patterns = |x : & Vec<(Option<StoreKind>, String)>| x.iter()
+            .filter(|(kind, _)| kind.map(|kind| kind == substore).unwrap_or(true))
+            .map(|(_, pattern)| pattern.to_owned())
+            .collect();
+        return PathFilters{
+            include : patterns(& SETTINGS.include_paths),
+            exclude : patterns(& SETTINGS.exclude_paths),
+        };
+    }
+
+    fn allows(& self, path : & str) -> bool {
+        if self.exclude.iter().any(|x| helpers::path_matches(x, path)) {
+            return false;
+        }
+        return self.include.is_empty() || self.include.iter().any(|x| helpers::path_matches(x, path));
+    }
+}
+
+/** The filters as recorded in the project log, include patterns prefixed with `+` and exclude patterns with `-`, empty if there are no filters. 
+ */
+impl std::fmt::Display for PathFilters {
+    fn fmt(& self, f : & mut std::fmt::Formatter) -> std::fmt::Result {
+        let patterns : Vec<String> = self.include.iter().map(|x| format!("+{}", x))
+            .chain(self.exclude.iter().map(|x| format!("-{}", x)))
+            .collect();
+        return write!(f, "{}", patterns.join(" "));
+    }
+}
+
+/** Returns the rank of a root file as one of the project files with given name stems, or None if it is not one of them. 
+ 
+    Files named just by the stem, optionally with an extension such as `README.md`, rank before the files whose names only start with the stem, such as `LICENSE-MIT`. The names are compared case insensitively. 
+ */
+fn project_file_rank(name : & str, stems : & [& str]) -> Option<usize> {
+    let name = name.to_lowercase();
+    for stem in stems {
+        if let Some(rest) = name.strip_prefix(stem) {
+            if rest.is_empty() || rest.starts_with('.') {
+                return Some(0);
+            } else {
+                return Some(1);
+            }
+        }
+    }
+    return None;
+}
+
+/** Determines whether the blob is small enough to be a LFS pointer without reading its contents. 
+ 
+    Blobs missing from partial clones are not considered small. 
+ */
+fn is_small_blob(repo : & git2::Repository, hash : SHA) -> bool {
+    return match repo.odb().and_then(|odb| odb.read_header(hash)) {
+        Ok((size, _)) => size < LfsPointer::MAX_SIZE,
+        Err(_) => false,
+    };
+}
+
+/** Downloads the object of given LFS pointer from the LFS server of the repository with given clone url. 
+ 
+    Uses the batch API of git LFS to obtain the download link of the object and then downloads it. Only public repositories are supported. 
+ */
+fn download_lfs_object(clone_url : & str, pointer : & LfsPointer) -> Result<Vec<u8>, std::io::Error> {
+    let endpoint = if clone_url.ends_with(".git") { format!("{}/info/lfs/objects/batch", clone_url) } else { format!("{}.git/info/lfs/objects/batch", clone_url) };
+    let mut request = json::JsonValue::new_object();
+    request["operation"] = "download".into();
+    request["transfers"] = json::array!["basic"];
+    request["objects"] = json::array![json::object!{ "oid" => pointer.oid.as_str(), "size" => pointer.size }];
+    let batch = json::parse(& helpers::to_string(& lfs_request(& endpoint, Some(& request.dump()), & [])?))
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Cannot parse LFS batch response"))?;
+    let download = & batch["objects"][0]["actions"]["download"];
+    let href = download["href"].as_str().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, format!("LFS object {} not available", pointer.oid)))?;
+    let headers : Vec<String> = download["header"].entries().map(|(k, v)| format!("{}: {}", k, v)).collect();
+    let object = lfs_request(href, None, & headers)?;
+    if object.len() as u64 != pointer.size {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("LFS object {} has {} bytes, {} expected", pointer.oid, object.len(), pointer.size)));
+    }
+    return Ok(object);
+}
+
+/** Performs a request to the LFS server, POST with given JSON body, or GET if there is none. 
+ */
+fn lfs_request(url : & str, body : Option<& str>, headers : & [String]) -> Result<Vec<u8>, std::io::Error> {
+    let curl_error = |e : curl::Error| std::io::Error::new(std::io::ErrorKind::ConnectionAborted, e);
+    let mut conn = curl::easy::Easy::new();
+    conn.url(url).map_err(curl_error)?;
+    conn.proxy(SETTINGS.curl_proxy()).map_err(curl_error)?;
+    conn.follow_location(true).map_err(curl_error)?;
+    conn.timeout(std::time::Duration::from_secs(600)).map_err(curl_error)?;
+    let mut list = curl::easy::List::new();
+    for header in headers {
+        list.append(header).map_err(curl_error)?;
+    }
+    if let Some(body) = body {
+        conn.post(true).map_err(curl_error)?;
+        conn.post_fields_copy(body.as_bytes()).map_err(curl_error)?;
+        list.append("Accept: application/vnd.git-lfs+json").map_err(curl_error)?;
+        list.append("Content-Type: application/vnd.git-lfs+json").map_err(curl_error)?;
+    }
+    conn.http_headers(list).map_err(curl_error)?;
+    let mut result = Vec::new();
+    {
+        let mut transfer = conn.transfer();
+        transfer.write_function(|data| {
+            result.extend_from_slice(data);
+            return Ok(data.len());
+        }).map_err(curl_error)?;
+        transfer.perform().map_err(curl_error)?;
+    }
+    let code = conn.response_code().map_err(curl_error)?;
+    if code != 200 {
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LFS request {} failed with status {}", url, code)));
+    }
+    return Ok(result);
+}
+
+/** Returns the urls of the submodules described by the contents of a `.gitmodules` file, keyed by the submodule paths. 
+ */
+fn parse_gitmodules(contents : & str) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+    let mut path : Option<String> = None;
+    let mut url : Option<String> = None;
+    for line in contents.lines().map(|x| x.trim()) {
+        if line.starts_with("[") {
+            if let (Some(p), Some(u)) = (path.take(), url.take()) {
+                result.insert(p, u);
+            }
+        } else if let Some((key, value)) = line.split_once("=") {
+            match key.trim() {
+                "path" => path = Some(value.trim().to_owned()),
+                "url" => url = Some(value.trim().to_owned()),
+                _ => {},
+            }
+        }
+    }
+    if let (Some(p), Some(u)) = (path, url) {
+        result.insert(p, u);
+    }
+    return result;
+}
+
+/** Lists the references of the remote repository at given url without fetching anything and returns their number. 
+ 
+    Used to check that git repositories can be reached with the network settings. An empty repository for the remote is created in given folder, which is deleted afterwards. 
+ */
+pub (crate) fn check_git_remote(folder : & str, url : & str) -> Result<usize, git2::Error> {
+    let list_remote = || -> Result<usize, git2::Error> {
+        let repo = git2::Repository::init_bare(folder)?;
+        let mut remote = repo.remote_anonymous(url)?;
+        let connection = remote.connect_auth(git2::Direction::Fetch, None, Some(proxy_options()))?;
+        return Ok(connection.list()?.len());
+    };
+    let result = list_remote();
+    let _ = std::fs::remove_dir_all(folder);
+    return result;
+}
+
+/** Returns the libgit2 proxy options for the proxy from the settings. 
+ 
+    Without a proxy in the settings, libgit2 connects directly. 
+ */
+fn proxy_options() -> git2::ProxyOptions<'static> {
+    let mut result = git2::ProxyOptions::new();
+    if let Some(proxy) = SETTINGS.proxy.as_ref() {
+        result.url(proxy);
+    }
+    return result;
+}
+
+/** Checks the progress of a clone against the clone limits from the settings and returns the description of the first exceeded limit, if any. 
+ */
+fn exceeded_clone_limit(progress : & git2::Progress, elapsed : u64) -> Option<String> {
+    if let Some(max_time) = SETTINGS.max_clone_time {
+        if elapsed > max_time {
+            return Some(format!("clone time exceeded {} seconds", max_time));
+        }
+    }
+    if let Some(max_objects) = SETTINGS.max_clone_objects {
+        if progress.total_objects() > max_objects {
+            return Some(format!("{} objects exceed the limit of {}", progress.total_objects(), max_objects));
+        }
+    }
+    if let Some(max_size) = SETTINGS.max_clone_size {
+        if progress.received_bytes() > max_size {
+            return Some(format!("clone size exceeded {}", helpers::pretty_size(max_size as u64)));
+        }
+    }
+    return None;
+}
+
 /** Removes all redundant url records from github metadata JSON object. 
  
     Removes all `_url` suffixed fields from the metadata record with the exception of `html_url` 
@@ -557,11 +1990,39 @@ fn filter_github_metadata_keys(json : & mut json::JsonValue, is_root : bool) {
     }
 }
 
+/** Determines the kind of commit signature from its armor header. 
+ */
+fn signature_kind(signature : & [u8]) -> &'static str {
+    if signature.starts_with(b"-----BEGIN SSH SIGNATURE-----") {
+        return "ssh";
+    } else if signature.starts_with(b"-----BEGIN SIGNED MESSAGE-----") {
+        return "x509";
+    } else {
+        return "gpg";
+    }
+}
+
 /** Calculates the output of two git trees and adds / updates any changes in the given hashmap. 
  */
-fn calculate_tree_diff(repo : & git2::Repository,  parent : Option<& git2::Tree>, commit : Option<& git2::Tree>, changes : & mut HashMap<String, SHA>) -> Result<(), git2::Error> {
-    let diff = repo.diff_tree_to_tree(parent, commit, None)?;
+fn calculate_tree_diff(repo : & git2::Repository,  parent : Option<& git2::Tree>, commit : Option<& git2::Tree>, exact_renames_only : bool, changes : & mut HashMap<String, SHA>, line_stats : Option<& mut HashMap<String, (u32, u32)>>, renames : & mut HashSet<(String, String)>, gitlinks : & mut HashMap<String, SHA>) -> Result<(), git2::Error> {
+    let mut diff = repo.diff_tree_to_tree(parent, commit, None)?;
+    // detect renames, similarity of changed files requires their contents, which may not be available
+    let mut find_options = git2::DiffFindOptions::new();
+    find_options.renames(true).exact_match_only(exact_renames_only);
+    diff.find_similar(Some(& mut find_options))?;
     for delta in diff.deltas() {
+        // gitlinks point to commits of other repositories, they are recorded as submodules and not as changed files
+        if delta.old_file().mode() == git2::FileMode::Commit || delta.new_file().mode() == git2::FileMode::Commit {
+            if delta.status() == git2::Delta::Renamed {
+                if let Some(po) = delta.old_file().path().and_then(|x| x.to_str()) {
+                    gitlinks.insert(String::from(po), git2::Oid::zero());
+                }
+            }
+            if let Some(p) = delta.new_file().path().and_then(|x| x.to_str()) {
+                gitlinks.insert(String::from(p), delta.new_file().id());
+            }
+            continue;
+        }
         match delta.status() {
             git2::Delta::Added | git2::Delta::Modified | git2::Delta::Deleted | git2::Delta::Copied => {
                 if let Some(p) = delta.new_file().path().unwrap().to_str() {
@@ -573,6 +2034,7 @@ fn calculate_tree_diff(repo : & git2::Repository,  parent : Option<& git2::Tree>
                     changes.insert(String::from(po), git2::Oid::zero());
                     if let Some(p) = delta.new_file().path().unwrap().to_str() {
                         changes.insert(String::from(p), delta.new_file().id());
+                        renames.insert((String::from(po), String::from(p)));
                     }
                 }
             },
@@ -582,5 +2044,19 @@ fn calculate_tree_diff(repo : & git2::Repository,  parent : Option<& git2::Tree>
             }
         }
     }
+    // numbers of added and deleted lines are taken from the patches of the changed files, binary files are ignored
+    if let Some(line_stats) = line_stats {
+        for i in 0..diff.deltas().len() {
+            if let Some(patch) = git2::Patch::from_diff(& diff, i)? {
+                if patch.delta().flags().is_binary() {
+                    continue;
+                }
+                let (_, additions, deletions) = patch.line_stats()?;
+                if let Some(p) = patch.delta().new_file().path().and_then(|x| x.to_str()) {
+                    line_stats.insert(String::from(p), (additions as u32, deletions as u32));
+                }
+            }
+        }
+    }
     return Ok(());
 }    
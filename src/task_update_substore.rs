@@ -1,36 +1,88 @@
+use std::collections::HashSet;
 use crate::updater::*;
 use crate::records::*;
 use crate::db::*;
+use crate::helpers;
+use crate::task_update_repo::is_update_due;
+use crate::settings::SETTINGS;
+use crate::datastore::UpdatePass;
 
 /** Task that does an update of a given substore. 
  
     First the substore is loaded, then its own and unspecified projects are scheduled and then the task waits for completion of the scheduled queue and monitor the health of the datastore. 
+
+    In dry run mode the substore is not loaded and dry runs of the projects are scheduled instead of their updates. Unspecified projects are only scheduled with the first substore so that they are reported only once. The same holds for the heads only mode, which schedules heads only updates of the projects. 
+
+    Only projects passing the filter are scheduled. If the filter limits the number of projects, the least recently updated projects are scheduled and the limit of the following substores is lowered by the number of projects scheduled so far. 
+
+    Except for dry runs and error retries, the progress of the pass is persisted in the datastore (see `UpdatePass`), so that the pass can continue where it stopped when the updater is restarted. Projects updated since the pass started are not scheduled again. 
  */
-pub (crate) fn task_update_substore(updater : & Updater, store : StoreKind, mode : UpdateMode, task : TaskStatus) -> Result<(), std::io::Error> {
+pub (crate) fn task_update_substore(updater : & Updater, store : StoreKind, mode : UpdateMode, dry_run : bool, mut filter : UpdateFilter, task : TaskStatus) -> Result<(), std::io::Error> {
+    // neither dry runs, nor heads only updates use the substore
+    let uses_substore = ! dry_run && mode != UpdateMode::HeadsOnly;
     // load the substore
-    updater.ds.substore(store).load(& task);
+    if uses_substore {
+        updater.ds.substore(store).load(& task)?;
+    }
+    let first_substore = mode == UpdateMode::Single || store == StoreKind::from_number(0);
     let mut num_projects = 0;
+    // start the pass, or record that it reached this substore
+    let pass_name = update_pass_name(store, mode, dry_run, & filter);
+    let pass = match & pass_name {
+        Some(name) => {
+            let pass = match updater.ds.get_update_pass(name) {
+                Some(pass) => UpdatePass{ substore : store, filter : filter.clone(), ..pass },
+                None => UpdatePass{ name : name.to_owned(), start : helpers::now(), substore : store, mode, filter : filter.clone() },
+            };
+            updater.ds.set_update_pass(pass.clone())?;
+            Some(pass)
+        },
+        None => None,
+    };
     // schedule all projects
     {
+        let now = helpers::now();
         let total_projects = updater.ds.num_projects();
+        let labelled : Option<HashSet<ProjectId>> = filter.label.as_ref().map(|label| updater.ds.projects_with_label(label).into_iter().collect());
+        // with a maximum, the projects to be scheduled and their last update times are collected first so that the least recently updated can be selected
+        let mut scheduled = Vec::<(ProjectId, i64)>::new();
+        let mut schedule = |id : ProjectId, last_update_time : i64| {
+            if filter.max.is_some() {
+                scheduled.push((id, last_update_time));
+            } else {
+                if updater.schedule(update_task(id, last_update_time, mode, dry_run)) {
+                    num_projects += 1;
+                }
+            }
+        };
         task.info("scheduling projects...");
         task.progress(0, total_projects);
         let mut i = 0;
         while i < total_projects {
             let id = ProjectId::from(i as u64);
             let pstore = updater.ds.get_project_substore(id);
+            let filtered = (filter.substore.is_some() && pstore == StoreKind::Unspecified) || labelled.as_ref().map(|x| ! x.contains(& id)).unwrap_or(false);
             // errors take *all* stores at once, and updates if the store is loaded
-            if pstore == store || pstore == StoreKind::Unspecified || mode == UpdateMode::Errors {
-                // its a possibly valid project, so determine the last time it was updated
-                if let Some(last_update) = updater.ds.get_project_last_update(id) {
-                    if ! last_update.is_error() || mode == UpdateMode::Errors {
-                        updater.schedule(Task::UpdateRepo{id, last_update_time : last_update.time()});
-                        num_projects += 1;
+            if ! filtered && (pstore == store || (pstore == StoreKind::Unspecified && (first_substore || uses_substore)) || mode == UpdateMode::Errors) {
+                // denied projects are never scheduled, dry runs only skip them without recording anything
+                let denied = if dry_run { 
+                    updater.ds.get_project(id).and_then(|p| updater.ds.find_denied(Some(id), & p)).is_some() 
+                } else { 
+                    updater.ds.suppress_if_denied(id) 
+                };
+                if denied {
+                    // nothing to schedule
+                } else if let Some(last_update) = updater.ds.get_project_last_update(id) {
+                    // its a possibly valid project, so determine the last time it was updated
+                    // projects waiting for a retry are only scheduled when their backoff period has elapsed
+                    let old_enough = filter.older_than.map(|x| last_update.time() <= now - x).unwrap_or(true);
+                    let done_in_pass = pass.as_ref().map(|x| last_update.time() >= x.start).unwrap_or(false);
+                    if old_enough && ! done_in_pass && ((! last_update.is_error() && is_update_due(& last_update, now)) || (mode == UpdateMode::Errors && ! last_update.is_deleted())) {
+                        schedule(id, last_update.time());
                     }
                 } else {
                     if mode != UpdateMode::Errors {
-                        updater.schedule(Task::UpdateRepo{id, last_update_time : 0});
-                        num_projects += 1;
+                        schedule(id, 0);
                     }
                 }
             }
@@ -39,6 +91,18 @@ pub (crate) fn task_update_substore(updater : & Updater, store : StoreKind, mode
                 task.progress(i, total_projects);
             }
         }
+        if let Some(max) = filter.max {
+            if scheduled.len() > max {
+                scheduled.sort_by_key(|(_, time)| *time);
+                scheduled.truncate(max);
+            }
+            filter.max = Some(max - scheduled.len());
+        }
+        for (id, last_update_time) in scheduled {
+            if updater.schedule(update_task(id, last_update_time, mode, dry_run)) {
+                num_projects += 1;
+            }
+        }
     }
     // observe the update progress and report the state, in the future also observe the datastore & updater health and manage substores. 
     // we determine that the update has finished when the queue is empty and all threads but one are idle
@@ -49,7 +113,8 @@ pub (crate) fn task_update_substore(updater : & Updater, store : StoreKind, mode
             let progress;
             {
                 let pool = updater.pool.lock().unwrap();
-                if pool.running_workers == 1 && pool.queue.is_empty() {
+                // metadata updates run alongside and are not waited for
+                if pool.running_workers - pool.metadata_running == 1 && pool.queue.is_empty() {
                     break;
                 }
                 progress = num_projects - pool.queue.len();
@@ -59,15 +124,147 @@ pub (crate) fn task_update_substore(updater : & Updater, store : StoreKind, mode
             std::thread::sleep(std::time::Duration::from_millis(1000));
         }
     }
+    updater.notify("substore-updated", format!("{} of substore {:?} ({:?}) completed, {} projects", if dry_run { "dry run" } else { "update" }, store, mode, num_projects));
     // now that we have finished we can start update of other datastore. Technically we can do this earlier too, as long as the queue is empty and there are some idle threads, but that would require the necessity to have two substore mappings loaded in memory which we want to avoid. So this is less efficient but more robust solution
+    let mut finished = true;
     if mode != UpdateMode::Single {
         let mut next_substore = StoreKind::from_number(store.to_number() + 1);
+        // the continuous updates start a new pass with every wrap around
+        finished = next_substore == StoreKind::Unspecified;
         if next_substore == StoreKind::Unspecified && mode == UpdateMode::Continuous {
             next_substore = StoreKind::from_number(0);
         }
-        if next_substore != StoreKind::Unspecified && mode != UpdateMode::Errors {
-            updater.schedule(Task::UpdateSubstore{store : next_substore, mode});
+        // substores are not visited once the maximum number of projects is scheduled
+        if next_substore != StoreKind::Unspecified && mode != UpdateMode::Errors && filter.max != Some(0) {
+            updater.schedule(Task::UpdateSubstore{store : next_substore, mode, dry_run, filter});
+        } else {
+            finished = true;
+        }
+    }
+    if finished {
+        if let Some(name) = pass_name {
+            updater.ds.remove_update_pass(& name)?;
         }
     }
     return Ok(());
 }
+
+/** Returns the name of the update pass of given substore update, or None if the update does not persist its progress, i.e. for dry runs and error retries. 
+ 
+    Passes over all substores are named after their mode and filter, single substore updates after the substore as well. The maximum number of projects is not part of the name, since it decreases as the pass goes through the substores. 
+ */
+pub (crate) fn update_pass_name(store : StoreKind, mode : UpdateMode, dry_run : bool, filter : & UpdateFilter) -> Option<String> {
+    let filter = UpdateFilter{ max : None, ..filter.clone() };
+    match mode {
+        _ if dry_run => return None,
+        UpdateMode::Errors => return None,
+        UpdateMode::Single => return Some(format!("{:?} {:?}{}", mode, store, filter)),
+        _ => return Some(format!("{:?}{}", mode, filter)),
+    }
+}
+
+/** Task that periodically refreshes the metadata of GitHub projects. 
+ 
+    Every pass schedules metadata updates of all GitHub projects whose metadata were last checked (by any update) longer than the metadata interval ago into the metadata queue, least recently checked first, and waits for them to finish. The next pass starts one interval after the previous one started. Deleted and denied projects are not refreshed. The task runs until the updater stops, or the task is cancelled. 
+ */
+pub (crate) fn task_refresh_metadata(updater : & Updater, task : TaskStatus) -> Result<(), std::io::Error> {
+    let interval = SETTINGS.metadata_interval as i64;
+    loop {
+        let pass_start = helpers::now();
+        let total_projects = updater.ds.num_projects();
+        let mut num_projects = 0;
+        task.info("scheduling metadata updates...");
+        task.progress(0, total_projects);
+        for i in 0..total_projects {
+            let id = ProjectId::from(i as u64);
+            if let Some(ProjectUrl::GitHub{user_and_repo : _}) = updater.ds.get_project(id) {
+                let inactive = updater.ds.get_project_last_update(id).map(|x| x.is_deleted() || x.is_denied()).unwrap_or(false);
+                let last_check_time = updater.ds.get_project_latest_popularity(id).map(|x| x.time).unwrap_or(0);
+                // updates still queued from the previous pass are not scheduled again
+                if ! inactive && last_check_time + interval <= pass_start && updater.schedule_metadata(Task::UpdateMetadata{id, last_check_time}) {
+                    num_projects += 1;
+                }
+            }
+            if (i + 1) % 1000 == 0 {
+                task.progress(i + 1, total_projects);
+            }
+        }
+        // wait for the metadata updates to finish, the refresh itself is counted as running metadata task
+        task.info("Updating metadata...");
+        loop {
+            let progress;
+            {
+                let pool = updater.pool.lock().unwrap();
+                if pool.state == State::Stopped || task.is_cancelled() {
+                    return Ok(());
+                }
+                if pool.metadata_queue.is_empty() && pool.metadata_running == 1 {
+                    break;
+                }
+                progress = num_projects - pool.metadata_queue.len();
+            }
+            task.progress(progress, num_projects);
+            std::thread::sleep(std::time::Duration::from_millis(1000));
+        }
+        updater.notify("metadata-refreshed", format!("metadata refresh completed, {} projects", num_projects));
+        // and wait for the next pass
+        while helpers::now() < pass_start + interval {
+            if updater.pool.lock().unwrap().state == State::Stopped || task.is_cancelled() {
+                return Ok(());
+            }
+            task.info(format!("next metadata refresh in {}", helpers::pretty_duration(pass_start + interval - helpers::now())));
+            std::thread::sleep(std::time::Duration::from_millis(1000));
+        }
+    }
+}
+
+/** Schedules the updates of the pending projects of given campaign. 
+ 
+    The projects are fed to the queue lazily, only while it holds fewer tasks than twice the number of workers, so that a large campaign does not flood the queue and is ordered by the time of the last update of its projects at the moment they are scheduled. Projects whose updates are already queued, or running are not scheduled again. The task finishes once all pending projects have been scheduled, or when the updater stops, or the task is cancelled. 
+ */
+pub (crate) fn task_update_campaign(updater : & Updater, task : TaskStatus) -> Result<(), std::io::Error> {
+    if let Task::UpdateCampaign{name} = & task.task {
+        let pending = updater.ds.campaign_pending(name)?;
+        task.info("scheduling projects...");
+        task.progress(0, pending.len());
+        let mut next = 0;
+        let mut num_projects = 0;
+        while next < pending.len() {
+            let free = {
+                let pool = updater.pool.lock().unwrap();
+                if pool.state == State::Stopped || task.is_cancelled() {
+                    return Ok(());
+                }
+                (pool.target_workers as usize * 2).saturating_sub(pool.queue.len())
+            };
+            for id in pending.iter().skip(next).take(free) {
+                let last_update_time = updater.ds.get_project_last_update(*id).map(|x| x.time()).unwrap_or(0);
+                if updater.schedule(Task::UpdateRepo{id : *id, last_update_time, class : PriorityClass::Normal}) {
+                    num_projects += 1;
+                }
+                next += 1;
+            }
+            task.progress(next, pending.len());
+            if next < pending.len() {
+                std::thread::sleep(std::time::Duration::from_millis(1000));
+            }
+        }
+        task.info(format!("{} projects scheduled", num_projects));
+        task.extra(name);
+        return Ok(());
+    } else {
+        panic!("Invalid task kind");
+    }
+}
+
+/** Returns the update, dry run, or heads only update of given project. 
+ */
+fn update_task(id : ProjectId, last_update_time : i64, mode : UpdateMode, dry_run : bool) -> Task {
+    if dry_run {
+        return Task::DryRunRepo{id, last_update_time};
+    } else if mode == UpdateMode::HeadsOnly {
+        return Task::UpdateHeads{id, last_update_time};
+    } else {
+        return Task::UpdateRepo{id, last_update_time, class : PriorityClass::Normal};
+    }
+}
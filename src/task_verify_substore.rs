@@ -2,27 +2,164 @@ use crate::updater::*;
 use crate::records::*;
 use crate::helpers;
 use crate::db::*;
+use crate::datastore::*;
+use crate::settings::SETTINGS;
+use std::io::Write;
 
-pub (crate) fn task_verify_substore(updater : & Updater, store : StoreKind, mode : UpdateMode, task : TaskStatus) -> Result<(), std::io::Error> {
+/** State of a substore verification whose tables are verified in parallel. 
+ */
+pub (crate) struct SubstoreVerification {
+    mode : UpdateMode,
+    repair : bool,
+    /** Chunks of each table and the records found in them once verified. 
+     */
+    chunks : Vec<Vec<(VerifyChunk, Option<VerifiedChunk>)>>,
+    /** Number of chunks of each table that are yet to be verified. 
+     */
+    remaining : Vec<usize>,
+    /** Number of chunks and table indices that are yet to be verified. 
+     */
+    pending : usize,
+    items : usize,
+    errors : usize,
+}
+
+/** Verifies the given substore. 
+ 
+    In repair mode, recoverable corruption is repaired first so that the subsequent verification checks the repaired substore. 
+
+    The tables of the substore are split into chunks which are scheduled as separate tasks, so that they are verified in parallel by the updater's workers. When all chunks of a table are verified, its index is checked and when all tables are verified, the consistency of the tables with each other is checked. 
+ */
+pub (crate) fn task_verify_substore(updater : & Updater, store : StoreKind, mode : UpdateMode, repair : bool, task : TaskStatus) -> Result<(), std::io::Error> {
+    // claim the substore under a single lock so that concurrent verifications of the same substore are refused, the chunks are filled in once split
+    match updater.verifications.lock().unwrap().entry(store) {
+        std::collections::hash_map::Entry::Occupied(_) => {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Substore {:?} is already being verified", store)));
+        },
+        std::collections::hash_map::Entry::Vacant(e) => {
+            e.insert(SubstoreVerification{ mode, repair, chunks : Vec::new(), remaining : Vec::new(), pending : 1, items : 0, errors : 0 });
+        },
+    }
     // load the substore
     let substore = updater.ds.substore(store);
-    match substore.verify(& task) {
-        Ok(items) => {
-            task.info(format!("{}", helpers::pretty_value(items)));
-            task.extra(format!("{:?}", store));
-            substore.clear(& task);
-            verify_next(updater, store, mode);
-            return Ok(());
-        },
-        Err(e) => {
-            substore.clear(& task);
-            verify_next(updater, store, mode);
+    if repair {
+        if let Err(e) = substore.repair(& task).and_then(|repairs| report_repairs(updater, & repairs, & task)) {
+            updater.verifications.lock().unwrap().remove(& store);
+            verify_next(updater, store, mode, repair);
             return Err(e);
         }
     }
+    substore.load(& task)?;
+    task.info("splitting tables...");
+    let chunks : Vec<Vec<(VerifyChunk, Option<VerifiedChunk>)>> = (0..Substore::VERIFIED_TABLES.len())
+        .map(|table| substore.split_for_verification(table, SETTINGS.num_threads).into_iter().map(|x| (x, None)).collect())
+        .collect();
+    let remaining : Vec<usize> = chunks.iter().map(|x| x.len()).collect();
+    let num_chunks : usize = remaining.iter().sum();
+    updater.verifications.lock().unwrap().insert(store, SubstoreVerification{
+        mode, 
+        repair,
+        chunks,
+        remaining : remaining.clone(),
+        pending : num_chunks + remaining.len(),
+        items : 0,
+        errors : 0,
+    });
+    for (table, n) in remaining.iter().enumerate() {
+        for chunk in 0..*n {
+            updater.schedule(Task::VerifySubstoreChunk{store, table, chunk});
+        }
+    }
+    task.info(format!("{} chunks scheduled", num_chunks));
+    task.extra(format!("{:?}", store));
+    return Ok(());
+}
+
+/** Verifies a single chunk of a substore table. 
+ 
+    The task that verifies the last chunk of a table also checks the table's index and the task that finishes the verification of the whole substore checks the consistency of its tables, drops the substore from memory and schedules the verification of the next substore in the all substores mode. 
+ */
+pub (crate) fn task_verify_substore_chunk(updater : & Updater, store : StoreKind, table : usize, chunk : usize, task : TaskStatus) -> Result<(), std::io::Error> {
+    let substore = updater.ds.substore(store);
+    let (descriptor, verified_items) = match updater.verifications.lock().unwrap().get(& store) {
+        Some(v) => (v.chunks[table][chunk].0.clone(), v.items),
+        None => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Substore {:?} is not being verified", store))),
+    };
+    let verified = substore.verify_chunk(table, & descriptor, verified_items, & task);
+    // store the records found and if this was the last chunk of the table, take the records of all its chunks
+    let mut table_chunks = None;
+    let error = {
+        let mut verifications = updater.verifications.lock().unwrap();
+        let v = verifications.get_mut(& store).unwrap();
+        let error = match verified {
+            Ok((items, x)) => {
+                task.info(format!("{}", helpers::pretty_value(items)));
+                v.items += items;
+                v.chunks[table][chunk].1 = Some(x);
+                None
+            },
+            Err(e) => Some(e),
+        };
+        v.remaining[table] -= 1;
+        if v.remaining[table] == 0 {
+            // if any of the chunks failed, the index is not checked
+            table_chunks = Some(v.chunks[table].iter_mut().map(|(_, x)| x.take()).collect::<Option<Vec<VerifiedChunk>>>());
+        }
+        error
+    };
+    let mut result = complete_verification(updater, store, error, & task);
+    if let Some(chunks) = table_chunks {
+        let error = match chunks {
+            Some(chunks) => {
+                task.info(format!("checking {} index...", Substore::VERIFIED_TABLES[table].name));
+                substore.verify_chunks_index(table, chunks).err()
+            },
+            None => None,
+        };
+        result = result.and(complete_verification(updater, store, error, & task));
+    }
+    return result;
 }
 
-pub (crate) fn task_verify_datastore(updater : & Updater, task : TaskStatus) -> Result<(), std::io::Error> {
+/** Records the completion of a chunk or table index verification. 
+ 
+    If this was the last pending part of the substore, finishes the verification. Returns the error of the completed part, or the result of the whole verification if finished. 
+ */
+fn complete_verification(updater : & Updater, store : StoreKind, error : Option<std::io::Error>, task : & TaskStatus) -> Result<(), std::io::Error> {
+    let finished = {
+        let mut verifications = updater.verifications.lock().unwrap();
+        let v = verifications.get_mut(& store).unwrap();
+        v.pending -= 1;
+        if error.is_some() {
+            v.errors += 1;
+        }
+        if v.pending == 0 { verifications.remove(& store) } else { None }
+    };
+    let mut result = match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    };
+    if let Some(v) = finished {
+        let substore = updater.ds.substore(store);
+        if v.errors == 0 {
+            task.info("checking consistency...");
+            result = result.and(substore.verify_consistency());
+        } else {
+            result = result.and(Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Substore {:?}: {} errors found", store, v.errors))));
+        }
+        if result.is_ok() {
+            task.info(format!("{} verified", helpers::pretty_value(v.items)));
+        }
+        substore.clear(task);
+        verify_next(updater, store, v.mode, v.repair);
+    }
+    return result;
+}
+
+pub (crate) fn task_verify_datastore(updater : & Updater, repair : bool, task : TaskStatus) -> Result<(), std::io::Error> {
+    if repair {
+        updater.ds.repair(& task).and_then(|repairs| report_repairs(updater, & repairs, & task))?;
+    }
     match updater.ds.verify(& task) {
         Ok(items) => {
             task.info(format!("{}", helpers::pretty_value(items)));
@@ -36,13 +173,29 @@ pub (crate) fn task_verify_datastore(updater : & Updater, task : TaskStatus) ->
 }
 
 
-fn verify_next(updater : & Updater, store : StoreKind, mode : UpdateMode) {
+fn verify_next(updater : & Updater, store : StoreKind, mode : UpdateMode, repair : bool) {
     if mode == UpdateMode::All {
         let next_substore = StoreKind::from_number(store.to_number() + 1);
         if next_substore != StoreKind::Unspecified {
-            updater.schedule(Task::VerifySubstore{store : next_substore, mode});
+            updater.schedule(Task::VerifySubstore{store : next_substore, mode, repair});
         } else {
-            updater.schedule(Task::VerifyDatastore{});
+            updater.schedule(Task::VerifyDatastore{repair});
+        }
+    }
+}
+
+/** Reports the repairs made. 
+ 
+    Each repair is appended to the repair log in the datastore root together with the time of the repair, so that the repairs are not lost when the task finishes. 
+ */
+fn report_repairs(updater : & Updater, repairs : & Vec<String>, task : & TaskStatus) -> Result<(), std::io::Error> {
+    if ! repairs.is_empty() {
+        let mut f = std::fs::OpenOptions::new().append(true).create(true).open(format!("{}/{}", updater.ds.root_folder(), Updater::REPAIR_LOG))?;
+        let now = helpers::now();
+        for x in repairs {
+            writeln!(f, "{},{}", now, x)?;
         }
     }
+    task.info(format!("{} repairs, verifying...", repairs.len()));
+    return Ok(());
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
 use std::collections::*;
 use std::sync::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::fs::{File, OpenOptions};
 use std::io::{Write, stdout};
 //use sysinfo::{SystemExt, ProcessExt};
 
@@ -12,11 +14,19 @@ use crate::db::*;
 
 use crate::datastore_maintenance_tasks::*;
 use crate::task_update_repo::*;
+use crate::task_update_issues::*;
 use crate::task_update_substore::*;
 use crate::task_verify_substore::*;
+use crate::task_pack_substore::*;
+use crate::task_index_messages::*;
+use crate::task_index_commit_projects::*;
+use crate::task_commit_metrics::*;
+use crate::task_index_files::*;
+use crate::task_resolve_users::*;
 use crate::reporter::*;
+use crate::LOG;
 
-use crate::settings::SETTINGS;
+use crate::settings::{SETTINGS, FsyncPolicy};
 
 
 /** Convenience struct that brings together the tx end of a channel, task name and task itself and exposes the sending of task messages via a simple api. 
@@ -25,17 +35,35 @@ pub struct TaskStatus<'a> {
     pub tx : &'a Tx,
     pub name : String,
     pub task : Task,
+    /** Set when the task should stop as soon as possible, i.e. when cancelled by the watchdog. 
+     */
+    cancelled : Arc<AtomicBool>,
 }
 
 impl<'a> TaskStatus<'a> {
     pub fn new(tx : &'a Tx, task : Task) -> TaskStatus {
+        return TaskStatus::cancellable(tx, task, & Arc::new(AtomicBool::new(false)));
+    }
+
+    /** Creates the task status with given cancellation flag. 
+     */
+    pub fn cancellable(tx : &'a Tx, task : Task, cancelled : & Arc<AtomicBool>) -> TaskStatus<'a> {
         return TaskStatus {
             tx : tx, 
             name : task.name(),
-            task : task
+            task : task,
+            cancelled : cancelled.clone(),
         };
     }
 
+    /** Returns true if the task has been cancelled. 
+     
+        Long running tasks should check the flag periodically and terminate with an error when set. 
+     */
+    pub fn is_cancelled(& self) -> bool {
+        return self.cancelled.load(Ordering::SeqCst);
+    }
+
     pub fn info<S: Into<String>>(& self, info : S) {
         self.tx.send(TaskMessage::Info{name : self.name.to_owned(), info : info.into() }).unwrap();
     }
@@ -56,9 +84,61 @@ impl<'a> TaskStatus<'a> {
         self.tx.send(TaskMessage::Color{name : self.name.to_owned(), color : color.to_owned()}).unwrap();
     }
 
+    /** Sets the time (in seconds since epoch) after which the watchdog cancels the task, or clears it if `None`. 
+     
+        Unlike the progress timeout, the deadline cancels the task even if it keeps reporting progress. 
+     */
+    pub fn deadline(& self, deadline : Option<i64>) {
+        self.tx.send(TaskMessage::Deadline{name : self.name.to_owned(), deadline}).unwrap();
+    }
+
 }
 
 
+/** An analysis run right after a project has been updated. 
+
+    Hooks are registered in the updater and run by the repository update whenever the update changed the project, while the project's data is hot and its clone still available. The key/value results of a hook are stored in the project metadata with the name of the hook and a dot as the prefix of their keys. A hook that fails, or panics does not fail the update, its error is stored as the `error` result of the hook instead. 
+ */
+pub (crate) trait UpdateHook : Send + Sync {
+    fn name(& self) -> & str;
+
+    fn run(& self, project : & UpdatedProject) -> Result<Vec<(String, String)>, std::io::Error>;
+}
+
+/** The project an update hook is run on. 
+ */
+pub (crate) struct UpdatedProject<'a> {
+    pub ds : &'a Datastore,
+    pub id : ProjectId,
+    pub project : &'a ProjectUrl,
+    pub substore : StoreKind,
+    /** Folder with the clone of the project's repository. 
+     
+        The clone is partial, i.e. without file contents, if the repository was cloned partially. 
+     */
+    pub clone_folder : &'a str,
+}
+
+/** Built-in update hook that stores the number of files of each contents kind in the most recent head of the project as a JSON object. 
+ */
+pub (crate) struct LanguagesHook {}
+
+impl UpdateHook for LanguagesHook {
+    fn name(& self) -> & str {
+        return "languages";
+    }
+
+    fn run(& self, project : & UpdatedProject) -> Result<Vec<(String, String)>, std::io::Error> {
+        let repo = git2::Repository::open(project.clone_folder).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.message().to_owned()))?;
+        let languages = repo_languages(& repo).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.message().to_owned()))?;
+        let mut result = json::JsonValue::new_object();
+        for (kind, files) in languages {
+            result[format!("{:?}", kind)] = files.into();
+        }
+        return Ok(vec![("files".to_owned(), result.dump())]);
+    }
+}
+
 /** The updater. 
 
     
@@ -72,16 +152,26 @@ pub (crate) struct Updater {
 
     pub (crate) github : Github,
 
+    /** Analyses run after every project update that changed the project. 
+     */
+    pub (crate) update_hooks : Vec<Box<dyn UpdateHook>>,
+
     /** Incremental updater
      */
-    num_workers : usize, 
     pub (crate) pool : Mutex<Pool>,
     cv_workers : Condvar,
+    /** Wakes the supervisor when the size of the worker pool changes, or the updater stops. 
+     */
+    cv_supervisor : Condvar,
 
     /** List of all urls of projects in the datastore so that new projects can be checked against duplicates. 
      */
     pub (crate) project_urls : Mutex<HashSet<ProjectUrl>>,
 
+    /** Substores being verified and the state of their verification. 
+     */
+    pub (crate) verifications : Mutex<HashMap<StoreKind, SubstoreVerification>>,
+
 
     /** Mutex to guard console output.
      */
@@ -92,20 +182,103 @@ impl Updater {
 
     pub const NEVER : i64 = 0;
 
+    /** Name of the append-only file in the datastore root where outcomes of all finished tasks are logged. 
+     
+        Each line is a JSON object with the task name, project id (if any), start & end times, duration, result and error cause (if any). 
+     */
+    pub const TASK_LOG : &'static str = "task-log.jsonl";
+
+    /** Name of the append-only file in the datastore root where all repairs made by the verify tasks in repair mode are logged. 
+     
+        Each line contains the time of the repair and its description. 
+     */
+    pub const REPAIR_LOG : &'static str = "repair-log.csv";
+
+    /** Name of the file in the datastore root where the dry run updates report what the update would do. 
+     
+        The file is created anew for every dry run. Each line contains the project id and url, whether the project would change, the numbers of changed, new and deleted heads and changed tags, the estimated number of new commits and the repository size in bytes, if known. 
+     */
+    pub const DRY_RUN_REPORT : &'static str = "dry-run.csv";
+
+    /** Name of the file in the datastore root where adding, or importing projects writes the projects that were not added. 
+     
+        The file is created anew for every add. Each line contains the line of the project in the source, if known, its url, the reason why it was not added (existing, duplicate, invalid url, unsupported host, denied, deleted, or malformed) and details of the reason, such as the denylist entry, or the project the url redirects to. 
+     */
+    pub const ADD_REJECTS : &'static str = "add-rejects.csv";
+
+    /** File in the datastore root the output of the last interactive command that did not fit on the prompt line is written to. 
+     
+        The file is overwritten by every such command. 
+     */
+    pub const COMMAND_OUTPUT : &'static str = "command-output.txt";
+
+    /** How often (in seconds) the disk usage of substores with quotas is checked. 
+     */
+    pub const QUOTA_CHECK_INTERVAL : usize = 60;
+
+    /** How often (in seconds) the preloading policy decides which substores to load and drop. 
+     */
+    pub const PRELOAD_CHECK_INTERVAL : usize = 10;
+
+    /** How often (in milliseconds) the background flusher checks the amount of data written since the last sync of the tables. 
+     */
+    pub const FLUSH_CHECK_INTERVAL : u64 = 500;
+
+    /** Repository whose references are listed by the connectivity self-test at the start of every run. 
+     */
+    pub const SELF_TEST_REPOSITORY : &'static str = "https://github.com/octocat/Hello-World.git";
+
+    /** Number of seconds the connectivity self-test waits for its checks. 
+     */
+    pub const SELF_TEST_TIMEOUT : u64 = 30;
+
+    /** Length of the window (in seconds) over which the error rate of the tasks is calculated for notifications. 
+     */
+    pub const NOTIFY_ERROR_WINDOW : usize = 600;
+
+    /** Minimal number of tasks finished in the window for the error rate to be notified. 
+     */
+    pub const NOTIFY_ERROR_MIN_TASKS : usize = 10;
+
     /** Updater is initialized with an existing datastore. 
+     
+        The built-in update hooks selected in the settings are registered. 
      */
     pub fn new(ds : Datastore) -> Updater {
-        return Updater {
+        let mut result = Updater {
             ds, 
             github : Github::new(& SETTINGS.github_tokens),
-            num_workers : SETTINGS.num_threads,
-            pool : Mutex::new(Pool::new()),
+            update_hooks : Vec::new(),
+            pool : Mutex::new(Pool::new(SETTINGS.num_threads as u64)),
             cv_workers : Condvar::new(),
+            cv_supervisor : Condvar::new(),
 
             project_urls : Mutex::new(HashSet::new()),
+            verifications : Mutex::new(HashMap::new()),
 
             cout_lock : Mutex::new(()),
+        };
+        for hook in Updater::builtin_update_hooks() {
+            result.add_update_hook(hook);
         }
+        return result;
+    }
+
+    /** Registers a hook that will be run after every project update that changed the project. 
+     */
+    pub fn add_update_hook(& mut self, hook : Box<dyn UpdateHook>) {
+        self.update_hooks.push(hook);
+    }
+
+    /** Returns the built-in update hooks selected in the settings. 
+     */
+    pub fn builtin_update_hooks() -> Vec<Box<dyn UpdateHook>> {
+        return SETTINGS.update_hooks.iter().map(|name| -> Box<dyn UpdateHook> {
+            match name.as_str() {
+                "languages" => return Box::new(LanguagesHook{}),
+                _ => panic!("Unknown update hook {}", name),
+            }
+        }).collect();
     }
 
     /** Starts the worker threads, responder and status printer.  
@@ -113,6 +286,19 @@ impl Updater {
         Because of the condvars not being able to pass the catch_unwind function barrier we use to execute the tasks, 
      */
     pub fn run(& self, command : String) {
+        let orphans = clean_orphaned_clones(& self.ds);
+        if orphans > 0 {
+            println!("Deleted {} orphaned repository clones", orphans);
+        }
+        self.connectivity_self_test();
+        // unfinished update passes continue where they stopped
+        let passes = self.ds.update_passes();
+        if ! passes.is_empty() {
+            println!("Resuming {} unfinished update passes", passes.len());
+        }
+        for pass in passes {
+            self.schedule(Task::UpdateSubstore{store : pass.substore, mode : pass.mode, dry_run : false, filter : pass.filter});
+        }
         println!("Running updater...");
         // prepare status & control screen
         print!("\x1b[?1049h"); // switch to alternate mode
@@ -128,86 +314,297 @@ impl Updater {
                 self.controller(command);
             });
             // start the worker threads
-            for _ in 0.. self.num_workers {
-                s.spawn(|_| {
-                    self.worker(tx.clone());
+            s.spawn(|s| {
+                self.supervisor(s, tx.clone());
+            });
+            if let Some(FsyncPolicy::Written(limit)) = SETTINGS.fsync {
+                s.spawn(move |_| {
+                    self.flusher(limit);
                 });
             }
         }).unwrap();
+        if SETTINGS.fsync.is_some() {
+            if let Err(e) = sync_tables() {
+                println!("WARNING: Syncing the datastore tables failed: {}", e);
+            }
+        }
         print!("\x1b[?1049l"); // return to normal mode
         print!("\x1b[r"); // reset scroll region
         println!("Updater terminated.");
     }
 
+    /** Checks that the GitHub API and git repositories can be reached with the network settings of this run, i.e. through the proxy, if any. 
+     
+        Each check is given `SELF_TEST_TIMEOUT` seconds. Failures are only reported, since the updater may have work that does not need the network, or the network may recover later. Returns true if both checks passed. 
+     */
+    fn connectivity_self_test(& self) -> bool {
+        let via = SETTINGS.proxy.as_ref().map(|x| format!(" via proxy {}", x)).unwrap_or_default();
+        let (tx, rx) = crossbeam_channel::unbounded::<(& str, Result<String, String>)>();
+        let folder = format!("{}/self-test-{}", self.ds.clones_folder(), std::process::id());
+        let api_tx = tx.clone();
+        std::thread::spawn(move || {
+            let _ = api_tx.send(("GitHub API", Github::check_api().map(|(remaining, limit)| format!("{} of {} anonymous requests remaining", remaining, limit)).map_err(|e| format!("{}", e))));
+        });
+        std::thread::spawn(move || {
+            let _ = tx.send(("git", check_git_remote(& folder, Updater::SELF_TEST_REPOSITORY).map(|refs| format!("{} references of {}", refs, Updater::SELF_TEST_REPOSITORY)).map_err(|e| format!("{}", e))));
+        });
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(Updater::SELF_TEST_TIMEOUT);
+        let mut passed = 0;
+        for _ in 0..2 {
+            match rx.recv_deadline(deadline) {
+                Ok((check, Ok(msg))) => {
+                    println!("Connectivity: {} reachable{}, {}", check, via, msg);
+                    passed += 1;
+                },
+                Ok((check, Err(e))) => println!("WARNING: Connectivity: {} not reachable{}: {}", check, via, e),
+                Err(_) => {
+                    println!("WARNING: Connectivity: self-test timed out after {} seconds{}", Updater::SELF_TEST_TIMEOUT, via);
+                    break;
+                }
+            }
+        }
+        return passed == 2;
+    }
+
+    /** Keeps the number of worker threads at the target size of the pool. 
+     
+        Spawns new workers whenever the target size is larger than the number of workers. Workers over the target exit by themselves after finishing their current task. The supervisor terminates when the updater stops. 
+     */
+    fn supervisor<'a>(&'a self, s : & crossbeam::thread::Scope<'a>, tx : crossbeam_channel::Sender<TaskMessage>) {
+        let mut pool = self.pool.lock().unwrap();
+        while pool.state != State::Stopped {
+            while pool.workers() < pool.target_workers {
+                // the worker is counted as running immediately so that it is not spawned twice
+                pool.running_workers += 1;
+                let tx = tx.clone();
+                s.spawn(move |_| {
+                    self.worker(tx);
+                });
+            }
+            pool = self.cv_supervisor.wait(pool).unwrap();
+        }
+    }
+
     /** 
      
         
      */
     fn worker(& self, tx : crossbeam_channel::Sender<TaskMessage>) {
-        self.pool.lock().unwrap().running_workers += 1;
-        while let Some(task) = self.get_next_task() {
+        while let Some((task, busy_substore, host)) = self.get_next_task() {
             let task_name = task.name();
-            tx.send(TaskMessage::Start{name : task_name.to_owned()}).unwrap();
+            let metadata_task = task.is_metadata_task();
+            let guarded_task = task.is_guarded();
+            let cancelled = Arc::new(AtomicBool::new(false));
+            tx.send(TaskMessage::Start{name : task_name.to_owned(), project : task.project(), cancelled : cancelled.clone()}).unwrap();
             let result = std::panic::catch_unwind(|| {
                 match task {
-                    Task::UpdateRepo{last_update_time : _, id : _ } => {
-                        return task_update_repo(& self.ds, & self.github, TaskStatus::new(& tx, task), /* force */ false, /* load_substore */ false);
+                    Task::UpdateRepo{last_update_time : _, id : _, class} => {
+                        // prioritized projects do not wait for their substore to be updated and load it instead
+                        let load_substore = class != PriorityClass::Normal;
+                        return task_update_repo(& self.ds, & self.github, & self.update_hooks, TaskStatus::cancellable(& tx, task, & cancelled), /* force */ false, load_substore);
+                    }
+                    Task::UpdateIssues{id : _} => {
+                        return task_update_issues(& self.ds, & self.github, TaskStatus::cancellable(& tx, task, & cancelled));
                     }
                     Task::AddProjects{ref source} => {
-                        return task_add_projects(& self.ds, source.to_owned(), TaskStatus::new(& tx, task));
+                        return task_add_projects(& self.ds, source.to_owned(), TaskStatus::cancellable(& tx, task, & cancelled));
+                    },
+                    Task::ImportProjects{ref source, dump} => {
+                        return task_import_projects(& self.ds, source.to_owned(), dump, TaskStatus::cancellable(& tx, task, & cancelled));
                     },
-                    Task::UpdateSubstore{store, mode} => {
-                        return task_update_substore(self, store, mode, TaskStatus::new(& tx, task));
+                    Task::UpdateSubstore{store, mode, dry_run, ref filter} => {
+                        return task_update_substore(self, store, mode, dry_run, filter.clone(), TaskStatus::cancellable(& tx, task, & cancelled));
                     }, 
+                    Task::DryRunRepo{id : _, last_update_time : _} => {
+                        return task_dry_run_repo(& self.ds, & self.github, TaskStatus::cancellable(& tx, task, & cancelled));
+                    },
+                    Task::UpdateHeads{id : _, last_update_time : _} => {
+                        return task_update_heads(& self.ds, & self.github, TaskStatus::cancellable(& tx, task, & cancelled));
+                    },
+                    Task::UpdateMetadata{id : _, last_check_time : _} => {
+                        return task_update_metadata(& self.ds, & self.github, TaskStatus::cancellable(& tx, task, & cancelled));
+                    },
+                    Task::RefreshMetadata => {
+                        return task_refresh_metadata(self, TaskStatus::cancellable(& tx, task, & cancelled));
+                    },
+                    Task::UpdateCampaign{name : _} => {
+                        return task_update_campaign(self, TaskStatus::cancellable(& tx, task, & cancelled));
+                    },
                     Task::LoadSubstore{store} => {
-                        return task_load_substore(& self.ds, store, TaskStatus::new(& tx, task));
+                        return task_load_substore(& self.ds, store, TaskStatus::cancellable(& tx, task, & cancelled));
                     },
                     Task::DropSubstore{store} => {
-                        return task_drop_substore(& self.ds, store, TaskStatus::new(& tx, task));
+                        return task_drop_substore(& self.ds, store, TaskStatus::cancellable(& tx, task, & cancelled));
                     }
-                    Task::VerifySubstore{store, mode} => {
-                        return task_verify_substore(self, store, mode, TaskStatus::new(& tx, task));
+                    Task::VerifySubstore{store, mode, repair} => {
+                        return task_verify_substore(self, store, mode, repair, TaskStatus::cancellable(& tx, task, & cancelled));
                     }
-                    Task::VerifyDatastore{} => {
-                        return task_verify_datastore(self, TaskStatus::new(& tx, task));
+                    Task::VerifySubstoreChunk{store, table, chunk} => {
+                        return task_verify_substore_chunk(self, store, table, chunk, TaskStatus::cancellable(& tx, task, & cancelled));
+                    }
+                    Task::VerifyDatastore{repair} => {
+                        return task_verify_datastore(self, repair, TaskStatus::cancellable(& tx, task, & cancelled));
                     }
                     Task::CreateSavepoint{name : _} => {
-                        return task_create_savepoint(& self.ds, TaskStatus::new(& tx, task));
+                        return task_create_savepoint_held(self, TaskStatus::cancellable(& tx, task, & cancelled));
+                    }
+                    Task::PackSubstore{store : _, output : _, savepoint : _} => {
+                        return task_pack_substore(& self.ds, TaskStatus::cancellable(& tx, task, & cancelled));
+                    }
+                    Task::UnpackSubstore{input : _} => {
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Substores cannot be unpacked while the updater is running"));
+                    }
+                    Task::Migrate{into : _} => {
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Datastore cannot be migrated while the updater is running"));
+                    }
+                    Task::CollectContents{store : _, keep_backup : _} => {
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Contents cannot be collected while the updater is running"));
+                    }
+                    Task::IndexMessages{store : _} => {
+                        return task_index_messages(& self.ds, TaskStatus::cancellable(& tx, task, & cancelled), & |ts| self.pause_if_held(ts));
+                    }
+                    Task::ResolveUsers{store : _} => {
+                        return task_resolve_users(& self.ds, TaskStatus::cancellable(& tx, task, & cancelled));
+                    }
+                    Task::IndexCommitProjects{store : _} => {
+                        return task_index_commit_projects(& self.ds, TaskStatus::cancellable(& tx, task, & cancelled), & |ts| self.pause_if_held(ts));
+                    }
+                    Task::IndexFiles{store : _} => {
+                        return task_index_files(& self.ds, TaskStatus::cancellable(& tx, task, & cancelled), & |ts| self.pause_if_held(ts));
+                    }
+                    Task::CommitMetrics{store : _} => {
+                        return task_commit_metrics(& self.ds, TaskStatus::cancellable(& tx, task, & cancelled));
                     }
                 }
             });
             match result {
                 Ok(Ok(())) => {
-                    tx.send(TaskMessage::Done{ name : task_name }).unwrap();
+                    match self.wait_for_durability() {
+                        Ok(()) => tx.send(TaskMessage::Done{ name : task_name.clone() }).unwrap(),
+                        Err(cause) => tx.send(TaskMessage::Error{ name : task_name.clone(), cause : format!("Syncing tables failed: {}", cause).trim().to_owned() }).unwrap(),
+                    }
                 },
                 Ok(Err(cause)) => {
-                    tx.send(TaskMessage::Error{ name : task_name, cause : format!("{}", cause).trim().to_owned() }).unwrap();
+                    tx.send(TaskMessage::Error{ name : task_name.clone(), cause : format!("{}", cause).trim().to_owned() }).unwrap();
                 },
                 Err(cause) => {
-                    tx.send(TaskMessage::Error{ name : task_name, cause : format!("PANIC: {:?}", cause) }).unwrap();
+                    tx.send(TaskMessage::Error{ name : task_name.clone(), cause : format!("PANIC: {:?}", cause) }).unwrap();
+                }
+            }
+            // the task can be scheduled again only once the reporter has been told it is done
+            self.pool.lock().unwrap().scheduled.remove(& task_name);
+            if let Some(store) = busy_substore {
+                let mut pool = self.pool.lock().unwrap();
+                *pool.busy.get_mut(& store).unwrap() -= 1;
+            }
+            if metadata_task {
+                self.pool.lock().unwrap().metadata_running -= 1;
+            }
+            if guarded_task {
+                self.pool.lock().unwrap().guarded_running -= 1;
+            }
+            if let Some(host) = host {
+                let mut pool = self.pool.lock().unwrap();
+                *pool.hosts.get_mut(& host).unwrap() -= 1;
+                // the next task throttled by the host can now run
+                if let Some(task) = pool.throttled.get_mut(& host).and_then(|x| x.pop_front()) {
+                    pool.queue.push(task);
+                    self.cv_workers.notify_one();
+                }
+            }
+        }
+    }
+
+    /** Waits for the changes of a finished task to become durable as required by the fsync policy. 
+     
+        With the task policy the tables are synced after each task. With the written bytes policy the tables are synced only if the background flusher is behind, i.e. more than the limit has been written since the last sync. 
+     */
+    fn wait_for_durability(& self) -> Result<(), std::io::Error> {
+        match SETTINGS.fsync {
+            Some(FsyncPolicy::Task) => {
+                sync_tables()?;
+            },
+            Some(FsyncPolicy::Written(limit)) => {
+                if unsynced_bytes() >= limit {
+                    sync_tables()?;
+                }
+            },
+            Some(FsyncPolicy::Savepoint) | None => {},
+        }
+        return Ok(());
+    }
+
+    /** Syncs the tables in the background whenever more than given number of bytes has been written to them since the last sync. 
+     
+        Failed syncs are logged and retried at the next check. The flusher terminates when the updater stops. 
+     */
+    fn flusher(& self, limit : u64) {
+        while ! self.should_stop() {
+            if unsynced_bytes() >= limit {
+                if let Err(e) = sync_tables() {
+                    LOG!("Background sync of tables failed: {}", e);
                 }
             }
+            std::thread::sleep(std::time::Duration::from_millis(Updater::FLUSH_CHECK_INTERVAL));
         }
-        self.pool.lock().unwrap().running_workers -= 1;
     }
 
-    /** Returns the next project to be updated. 
+    /** Returns the next project to be updated, the substore it works on and the host it clones from, if any. 
      
-        Returns None if the updater should stop and blocks if there are no avilable projects, or the updater should pause. 
+        Returns None if the updater should stop, or the worker should exit because the pool has been shrunk, and blocks if there are no avilable projects, or the updater should pause. The substore of the returned task is marked busy so that the preloading policy does not drop it until the task finishes. Similarly, the clone from the host is counted against the host's limit of concurrent clones until the task finishes. 
      */
-    fn get_next_task(& self) -> Option<Task> {
+    fn get_next_task(& self) -> Option<(Task, Option<StoreKind>, Option<String>)> {
         let mut state = self.pool.lock().unwrap();
         loop {
-            if state.state == State::Stopped {
+            if state.state == State::Stopped || state.workers() > state.target_workers {
+                state.running_workers -= 1;
                 return None;
-            } else if state.state == State::Paused {
+            } else if state.state == State::Paused || state.holds > 0 {
                 state.running_workers -= 1;
                 state.paused_workers += 1;
                 state = self.cv_workers.wait(state).unwrap();
                 state.running_workers += 1;
                 state.paused_workers -= 1;
+            } else if ! state.metadata_queue.is_empty() && (state.queue.is_empty() || state.metadata_running * 2 < state.target_workers) {
+                // metadata updates take at most half of the workers, unless there is nothing else to do
+                let task = state.metadata_queue.pop().unwrap();
+                state.metadata_running += 1;
+                if task.is_guarded() {
+                    state.guarded_running += 1;
+                }
+                return Some((task, None, None));
             } else if !state.queue.is_empty() {
-                break;
+                let task = state.queue.pop().unwrap();
+                // tasks of substores over their quota are deferred until the quota is no longer exceeded
+                if self.is_paused_by_quota(& task) {
+                    state.deferred.push(task);
+                    continue;
+                }
+                // tasks of substores that are not loaded wait for the preloading policy to load them
+                if self.waiting_substore(& task).is_some() {
+                    state.waiting.push(task);
+                    continue;
+                }
+                // tasks of hosts that already have as many clones running as they allow wait for one of them to finish
+                let host = self.limited_host(& task);
+                if let Some((host, connections)) = host.as_ref() {
+                    if state.hosts.get(host).cloned().unwrap_or(0) >= *connections {
+                        state.throttled.entry(host.to_owned()).or_default().push_back(task);
+                        continue;
+                    }
+                    *state.hosts.entry(host.to_owned()).or_insert(0) += 1;
+                }
+                let busy_substore = self.busy_substore(& task);
+                if let Some(store) = busy_substore {
+                    *state.busy.entry(store).or_insert(0) += 1;
+                }
+                if let Task::RefreshMetadata = task {
+                    state.metadata_running += 1;
+                }
+                if task.is_guarded() {
+                    state.guarded_running += 1;
+                }
+                return Some((task, busy_substore, host.map(|(host, _)| host)));
             } else {
                 state.running_workers -= 1;
                 state.idle_workers += 1;
@@ -216,13 +613,409 @@ impl Updater {
                 state.idle_workers -= 1;
             }
         }
-        return state.queue.pop();
     }
 
-    pub fn schedule(& self, task : Task) {
+    /** Determines whether given task works on a substore that exceeds its quota and should therefore be paused. 
+     
+        In metadata only mode, no tasks are paused as contents are not stored for the substores over their quota. 
+     */
+    fn is_paused_by_quota(& self, task : & Task) -> bool {
+        if SETTINGS.substore_quotas.is_empty() || SETTINGS.quota_metadata_only {
+            return false;
+        }
+        // dry runs, heads only and metadata updates do not store any contents
+        if let Task::DryRunRepo{id : _, last_update_time : _} | Task::UpdateHeads{id : _, last_update_time : _} | Task::UpdateMetadata{id : _, last_check_time : _} = task {
+            return false;
+        }
+        match task.project() {
+            Some(id) => {
+                let substore = self.ds.get_project_substore(id);
+                return substore != StoreKind::Unspecified && self.ds.substore(substore).is_over_quota();
+            },
+            None => return false,
+        }
+    }
+
+    /** Returns the host the task clones from together with the host's limit of concurrent clones, if the task clones a project and its host is limited. 
+     */
+    fn limited_host(& self, task : & Task) -> Option<(String, usize)> {
+        match task {
+            Task::UpdateRepo{id, last_update_time : _, class : _} | Task::DryRunRepo{id, last_update_time : _} | Task::UpdateHeads{id, last_update_time : _} => {
+                let host = self.ds.get_project(*id)?.host();
+                let connections = SETTINGS.host_limit(& host).connections?;
+                return Some((host, connections));
+            },
+            _ => return None,
+        }
+    }
+
+    /** Returns the substore the task waits for to be loaded by the preloading policy, if any. 
+     
+        Only the updates of projects with normal priority and known substore wait, prioritized updates load their substores themselves and projects without substore may end up in any of them. 
+     */
+    fn waiting_substore(& self, task : & Task) -> Option<StoreKind> {
+        if SETTINGS.preload_memory.is_none() {
+            return None;
+        }
+        if let Task::UpdateRepo{id, last_update_time : _, class : PriorityClass::Normal} = task {
+            let substore = self.ds.get_project_substore(*id);
+            if substore != StoreKind::Unspecified && ! self.ds.substore(substore).is_loaded() {
+                return Some(substore);
+            }
+        }
+        return None;
+    }
+
+    /** Returns the substore whose mappings the task uses while running, if any. 
+     
+        Projects without substore are reported as busy in the `Unspecified` substore, since their substore is determined during the update. 
+     */
+    fn busy_substore(& self, task : & Task) -> Option<StoreKind> {
+        match task {
+            Task::UpdateRepo{id, last_update_time : _, class : _} => Some(self.ds.get_project_substore(*id)),
+            Task::UpdateSubstore{store, mode : _, dry_run : false, filter : _} => Some(*store),
+            Task::LoadSubstore{store} | Task::DropSubstore{store} => Some(*store),
+            Task::IndexMessages{store} | Task::ResolveUsers{store} | Task::IndexCommitProjects{store} | Task::IndexFiles{store} | Task::CommitMetrics{store} => Some(*store),
+            _ => None,
+        }
+    }
+
+    /** Decides which substores to load and which to drop based on the composition of the queue. 
+
+        The substores are ranked by the number of queued and waiting updates of their projects and as many of them as fit the memory budget are kept loaded, but always at least one. Loaded substores that are not kept are dropped once no running task uses them and substores that are kept are loaded once the drops finished and they fit the budget together with the substores already loaded. Waiting updates of loaded substores are moved back to the queue so that the loaded substores are worked on before the others. 
+     */
+    fn preload_substores(& self) {
+        let budget = match SETTINGS.preload_memory {
+            Some(budget) => budget,
+            None => return,
+        };
+        // the estimates lock the mappings, which may be held by a running load, so they are taken before the pool is locked
+        let estimates : HashMap<StoreKind, u64> = self.ds.substores_iter().map(|x| (x.prefix, x.estimated_memory())).collect();
+        let mut pool = self.pool.lock().unwrap();
+        let mut demand = HashMap::<StoreKind, usize>::new();
+        let waiting = std::mem::take(& mut pool.waiting);
+        let mut requeued = 0;
+        for task in waiting {
+            match self.waiting_substore(& task) {
+                Some(store) => {
+                    *demand.entry(store).or_insert(0) += 1;
+                    pool.waiting.push(task);
+                },
+                None => {
+                    pool.queue.push(task);
+                    requeued += 1;
+                }
+            }
+        }
+        // substores with load or drop already queued, or with running tasks are left as they are
+        let mut pending = HashSet::<StoreKind>::new();
+        for task in pool.queue.iter() {
+            match task {
+                Task::UpdateRepo{id, last_update_time : _, class : _} => {
+                    let store = self.ds.get_project_substore(*id);
+                    if store != StoreKind::Unspecified {
+                        *demand.entry(store).or_insert(0) += 1;
+                    }
+                },
+                Task::LoadSubstore{store} | Task::DropSubstore{store} => {
+                    pending.insert(*store);
+                },
+                _ => {},
+            }
+        }
+        let busy = |store : StoreKind| pool.busy.get(& store).map(|x| *x > 0).unwrap_or(false);
+        let mut ranked : Vec<(StoreKind, usize)> = demand.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.cmp(& a.1).then(a.0.to_number().cmp(& b.0.to_number())));
+        let mut keep = Vec::new();
+        let mut used = 0;
+        for (store, _) in ranked {
+            let memory = estimates[& store];
+            if keep.is_empty() || used + memory <= budget {
+                keep.push(store);
+                used += memory;
+            }
+        }
+        // projects without substore may be stored in any substore, so nothing is dropped while they are updated
+        let mut tasks = Vec::new();
+        let mut loaded = 0;
+        for substore in self.ds.substores_iter() {
+            if ! substore.is_loaded() {
+                continue;
+            }
+            if keep.contains(& substore.prefix) || busy(substore.prefix) || pending.contains(& substore.prefix) || busy(StoreKind::Unspecified) {
+                loaded += estimates[& substore.prefix];
+            } else {
+                tasks.push(Task::DropSubstore{store : substore.prefix});
+            }
+        }
+        if tasks.is_empty() && ! pending.iter().any(|x| ! self.ds.substore(*x).is_loaded() || ! keep.contains(x)) {
+            for store in keep {
+                let substore = self.ds.substore(store);
+                if substore.is_loaded() || pending.contains(& store) || busy(store) {
+                    continue;
+                }
+                let memory = estimates[& store];
+                if loaded == 0 || loaded + memory <= budget {
+                    tasks.push(Task::LoadSubstore{store});
+                    loaded += memory;
+                }
+            }
+        }
+        for task in tasks {
+            pool.queue.push(task);
+            requeued += 1;
+        }
+        if requeued > 0 {
+            self.cv_workers.notify_all();
+        }
+    }
+
+    /** Checks disk usage of all substores with quotas. 
+     
+        Returns the substores over their quota together with their sizes and quotas. If any substore is no longer over its quota, all deferred tasks are rescheduled (the tasks of substores still over their quotas will be deferred again). 
+     */
+    fn check_quotas(& self) -> Vec<(StoreKind, u64, u64)> {
+        let mut result = Vec::new();
+        let mut freed = false;
+        for (kind, quota) in SETTINGS.substore_quotas.iter() {
+            let substore = self.ds.substore(*kind);
+            let size = substore.disk_size();
+            let over_quota = size > *quota;
+            if substore.is_over_quota() && ! over_quota {
+                freed = true;
+            }
+            substore.set_over_quota(over_quota);
+            if over_quota {
+                result.push((*kind, size, *quota));
+            }
+        }
+        if freed {
+            let mut pool = self.pool.lock().unwrap();
+            let deferred = std::mem::take(& mut pool.deferred);
+            for task in deferred {
+                pool.queue.push(task);
+            }
+            self.cv_workers.notify_all();
+        }
+        return result;
+    }
+
+    /** Schedules given task. 
+     
+        Scheduling is idempotent per task name, a task that is already queued, or running is not scheduled again and false is returned. If the task is queued with a lower priority class than the new one, such as a project update requested with `--now`, the queued task is replaced by the new one instead. 
+     */
+    pub fn schedule(& self, task : Task) -> bool {
         let mut pool = self.pool.lock().unwrap();
-        pool.queue.push(task);
+        // projects with prioritized labels are promoted 
+        let task = match task {
+            Task::UpdateRepo{id, last_update_time, class : PriorityClass::Normal} if self.has_prioritized_label(& pool, id) => {
+                Task::UpdateRepo{id, last_update_time, class : PriorityClass::Label}
+            },
+            task => task,
+        };
+        let name = task.name();
+        if pool.scheduled.contains(& name) {
+            if task.class() == PriorityClass::Normal {
+                return false;
+            }
+            let mut task = Some(task);
+            let tasks = std::mem::take(& mut pool.queue).into_vec();
+            for queued in tasks {
+                if task.as_ref().map(|x| queued.class() < x.class() && queued.name() == name).unwrap_or(false) {
+                    pool.queue.push(task.take().unwrap());
+                } else {
+                    pool.queue.push(queued);
+                }
+            }
+            if task.is_some() {
+                return false;
+            }
+        } else {
+            pool.scheduled.insert(name);
+            pool.queue.push(task);
+        }
+        self.cv_workers.notify_one();
+        return true;
+    }
+
+    /** Schedules given metadata update in the metadata queue. 
+     
+        Like `schedule`, returns false if the update is already queued, or running. 
+     */
+    pub fn schedule_metadata(& self, task : Task) -> bool {
+        let mut pool = self.pool.lock().unwrap();
+        if ! pool.scheduled.insert(task.name()) {
+            return false;
+        }
+        pool.metadata_queue.push(task);
         self.cv_workers.notify_one();
+        return true;
+    }
+
+    /** Holds the workers before they start their next tasks and waits until no guarded task (see `Task::is_guarded`) runs. 
+     
+        Tasks that must not run in the middle of any project update, or index batch, such as savepoint creation, hold the workers so that they do not have to be paused manually. Long running tasks, such as substore updates, continue, but the project updates they scheduled wait. The incremental index tasks pause between their batches (see `pause_if_held`). Returns false, with the workers released, if the task is cancelled while waiting. Otherwise the hold must be ended by `release_workers`. 
+     */
+    pub (crate) fn hold_workers(& self, task : & TaskStatus) -> bool {
+        self.pool.lock().unwrap().holds += 1;
+        let mut last_running = 0;
+        loop {
+            let running = self.pool.lock().unwrap().guarded_running;
+            if running == 0 {
+                return true;
+            }
+            if task.is_cancelled() {
+                self.release_workers();
+                return false;
+            }
+            if running != last_running {
+                task.info(format!("waiting for {} running project and index tasks...", running));
+                last_running = running;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    }
+
+    /** Ends a hold of the workers and wakes them up. 
+     */
+    pub (crate) fn release_workers(& self) {
+        let mut pool = self.pool.lock().unwrap();
+        pool.holds -= 1;
+        self.cv_workers.notify_all();
+    }
+
+    /** Called by long running guarded tasks between their batches, waits while the workers are held. 
+     
+        While waiting, the task does not count as running, so that the hold can proceed. 
+     */
+    pub (crate) fn pause_if_held(& self, task : & TaskStatus) {
+        let mut pool = self.pool.lock().unwrap();
+        if pool.holds == 0 {
+            return;
+        }
+        task.info("paused while the workers are held...");
+        pool.guarded_running -= 1;
+        while pool.holds > 0 {
+            pool = self.cv_workers.wait(pool).unwrap();
+        }
+        pool.guarded_running += 1;
+    }
+
+    /** Returns true if given project has any of the prioritized labels. 
+     */
+    fn has_prioritized_label(& self, pool : & Pool, id : ProjectId) -> bool {
+        if pool.prioritized_labels.is_empty() {
+            return false;
+        }
+        return self.ds.get_project_labels(id).iter().any(|x| pool.prioritized_labels.contains(x));
+    }
+
+    /** Prioritizes the updates of all projects with given label. 
+     
+        Already queued updates of the labelled projects are promoted and the projects that are not queued are scheduled. Projects with the label that are scheduled later will be promoted as well until the label is deprioritized. Returns the number of affected projects. 
+     */
+    fn prioritize_label(& self, label : & str) -> usize {
+        let projects : HashSet<ProjectId> = self.ds.projects_with_label(label).into_iter().collect();
+        let mut pool = self.pool.lock().unwrap();
+        pool.prioritized_labels.insert(label.to_owned());
+        let mut queued = HashSet::new();
+        let tasks = std::mem::take(& mut pool.queue).into_vec();
+        for task in tasks {
+            match task {
+                Task::UpdateRepo{id, last_update_time, class : PriorityClass::Normal} if projects.contains(& id) => {
+                    queued.insert(id);
+                    pool.queue.push(Task::UpdateRepo{id, last_update_time, class : PriorityClass::Label});
+                },
+                Task::UpdateRepo{id, last_update_time, class} => {
+                    if projects.contains(& id) {
+                        queued.insert(id);
+                    }
+                    pool.queue.push(Task::UpdateRepo{id, last_update_time, class});
+                },
+                task => pool.queue.push(task),
+            }
+        }
+        for id in projects.iter() {
+            if ! queued.contains(id) {
+                let last_update_time = self.ds.get_project_last_update(*id).map(|x| x.time()).unwrap_or(0);
+                let task = Task::UpdateRepo{id : *id, last_update_time, class : PriorityClass::Label};
+                // updates of the project that are running, or waiting outside of the queue are not scheduled again
+                if pool.scheduled.insert(task.name()) {
+                    pool.queue.push(task);
+                }
+            }
+        }
+        self.cv_workers.notify_all();
+        return projects.len();
+    }
+
+    /** Stops prioritizing the projects with given label. 
+     
+        Queued updates of projects that no longer have any prioritized label are demoted. Returns false if the label was not prioritized. 
+     */
+    fn deprioritize_label(& self, label : & str) -> bool {
+        let mut pool = self.pool.lock().unwrap();
+        if ! pool.prioritized_labels.remove(label) {
+            return false;
+        }
+        let tasks = std::mem::take(& mut pool.queue).into_vec();
+        for task in tasks {
+            match task {
+                Task::UpdateRepo{id, last_update_time, class : PriorityClass::Label} if ! self.has_prioritized_label(& pool, id) => {
+                    pool.queue.push(Task::UpdateRepo{id, last_update_time, class : PriorityClass::Normal});
+                },
+                task => pool.queue.push(task),
+            }
+        }
+        return true;
+    }
+
+    /** Notifies the hooks from the settings of given event. 
+     
+        The hooks are executed in a separate thread so that they do not block the caller and their failures are ignored. 
+     */
+    pub (crate) fn notify(& self, event : & str, message : String) {
+        if SETTINGS.notify_command.is_none() && SETTINGS.notify_url.is_none() {
+            return;
+        }
+        let event = event.to_owned();
+        let root = self.ds.root_folder().to_owned();
+        std::thread::spawn(move || {
+            if let Some(cmd) = & SETTINGS.notify_command {
+                let _ = std::process::Command::new("sh")
+                    .arg("-c").arg(cmd)
+                    .env("PARASITE_EVENT", & event)
+                    .env("PARASITE_MESSAGE", & message)
+                    .env("PARASITE_DATASTORE", & root)
+                    .status();
+            }
+            if let Some(url) = & SETTINGS.notify_url {
+                let mut body = json::JsonValue::new_object();
+                body["event"] = event.as_str().into();
+                body["message"] = message.as_str().into();
+                body["datastore"] = root.as_str().into();
+                body["time"] = helpers::now().into();
+                let _ = post_json(url, & body.dump());
+            }
+        });
+    }
+
+    /** Returns the free space and inodes of the datastore volume and, if it is a different volume, of the temporary directory (the one from the settings, or the system one), together with whether they are below the thresholds from the settings. The inodes are only checked on volumes that report them. 
+     */
+    fn check_disks(& self) -> Vec<(&'static str, u64, Option<u64>, bool)> {
+        use std::os::unix::fs::MetadataExt;
+        let mut volumes = vec![("datastore", self.ds.root_folder().to_owned())];
+        let temp = SETTINGS.tmp_dir.as_ref().map(std::path::PathBuf::from).unwrap_or_else(std::env::temp_dir);
+        let device = |path : & str| std::fs::metadata(path).map(|x| x.dev()).ok();
+        if let Some(temp) = temp.to_str() {
+            if device(temp) != device(self.ds.root_folder()) {
+                volumes.push(("temp", temp.to_owned()));
+            }
+        }
+        return volumes.into_iter().filter_map(|(volume, path)| {
+            let (space, inodes) = helpers::disk_resources(& path)?;
+            return Some((volume, space, inodes, space < SETTINGS.min_free_space || inodes.is_some_and(|x| x < SETTINGS.min_free_inodes)));
+        }).collect();
     }
 
     /** Returns true if the non-worker thread should stop immediately, false otherwise. 
@@ -238,19 +1031,21 @@ impl Updater {
      */
     fn reporter(& self, rx : crossbeam_channel::Receiver<TaskMessage>) {
         let mut rinfo = ReporterInfo::new();
+        let mut log = OpenOptions::new().create(true).append(true).open(format!("{}/{}", self.ds.root_folder(), Updater::TASK_LOG)).unwrap();
         while ! self.should_stop() {
             // see how many messages are there and process them, otherwise we can just keep processing messages without ever printing anything 
             let mut msgs = rx.len();
             while msgs > 0 {
                 match rx.recv() {
-                    Ok(TaskMessage::Start{name}) => {
+                    Ok(TaskMessage::Start{name, project, cancelled}) => {
                         assert!(rinfo.tasks.contains_key(& name) == false, "Task already exists");
-                        rinfo.tasks.insert(name, TaskInfo::new());
+                        rinfo.tasks.insert(name, TaskInfo::new(project, cancelled));
                     },
                     Ok(TaskMessage::Done{name}) => {
                         assert!(rinfo.tasks.contains_key(& name) == true, "Task does not exist");
                         let mut task = rinfo.tasks.remove(& name).unwrap();
                         task.end_time = helpers::now();
+                        task.log_outcome(& mut log, & name, None);
                         rinfo.done.push((name, task));
                         rinfo.tick_tasks_done += 1;
                     },
@@ -258,6 +1053,7 @@ impl Updater {
                         assert!(rinfo.tasks.contains_key(& name) == true, "Task does not exist");
                         let mut task = rinfo.tasks.remove(& name).unwrap();
                         task.end_time = helpers::now();
+                        task.log_outcome(& mut log, & name, Some(& cause));
                         rinfo.errors.push((name, task, cause));
                         rinfo.tick_tasks_error += 1;
                     },
@@ -286,12 +1082,81 @@ impl Updater {
                         task.ping = 0;
                         task.color = color;
                     },
+                    Ok(TaskMessage::Deadline{name, deadline}) => {
+                        assert!(rinfo.tasks.contains_key(& name) == true, "Task does not exist");
+                        let task = rinfo.tasks.get_mut(& name).unwrap();    
+                        task.deadline = deadline;
+                    },
                     Err(_) => {
                         panic!("Oh noez, can't receive stuff");
                     }
                 }
                 msgs -= 1;
             }
+            // check the quotas every now and then 
+            if rinfo.quota_check == 0 {
+                let over_quota = self.check_quotas();
+                for (kind, size, quota) in over_quota.iter() {
+                    if ! rinfo.over_quota.iter().any(|(x, _, _)| x == kind) {
+                        self.notify("quota-exceeded", format!("substore {:?} exceeded its quota ({} of {})", kind, helpers::pretty_size(*size), helpers::pretty_size(*quota)));
+                    }
+                }
+                rinfo.over_quota = over_quota;
+                rinfo.quota_check = Updater::QUOTA_CHECK_INTERVAL;
+            }
+            // decide which substores to load and drop every now and then
+            if rinfo.preload_check == 0 {
+                self.preload_substores();
+                rinfo.preload_check = Updater::PRELOAD_CHECK_INTERVAL;
+            }
+            rinfo.preload_check -= 1;
+            // check the free space of the volumes and notify when they start running low
+            let disks = self.check_disks();
+            for (volume, space, inodes, low) in disks.iter() {
+                if *low && ! rinfo.disks.iter().any(|(x, _, _, was_low)| x == volume && *was_low) {
+                    let inodes = inodes.map(|x| format!(" and {} inodes", helpers::pretty_value(x as usize))).unwrap_or_default();
+                    self.notify("disk-low", format!("{} volume is running low, {}{} free", volume, helpers::pretty_size(*space), inodes));
+                }
+            }
+            rinfo.disks = disks;
+            // notify of high error rates once per window
+            if rinfo.notify_check == 0 {
+                let total = rinfo.window_tasks_done + rinfo.window_tasks_error;
+                if total >= Updater::NOTIFY_ERROR_MIN_TASKS {
+                    let rate = rinfo.window_tasks_error as f64 * 100.0 / total as f64;
+                    if rate > SETTINGS.notify_error_rate {
+                        self.notify("error-rate", format!("{} of {} tasks ({:.1}%) failed in the last {} seconds", rinfo.window_tasks_error, total, rate, Updater::NOTIFY_ERROR_WINDOW));
+                    }
+                }
+                rinfo.window_tasks_done = 0;
+                rinfo.window_tasks_error = 0;
+                rinfo.notify_check = Updater::NOTIFY_ERROR_WINDOW;
+            }
+            rinfo.notify_check -= 1;
+            rinfo.quota_check -= 1;
+            // pick up changes of the denylist file made outside of the updater
+            match self.ds.reload_denylist_if_changed() {
+                Ok(true) => LOG!("Denylist reloaded, {} entries", self.ds.denylist().len()),
+                Ok(false) => {},
+                Err(e) => LOG!("WARNING: Unable to reload the changed denylist, keeping the current one: {}", e),
+            }
+            // cancel the tasks that did not report any progress for too long
+            if let Some(timeout) = SETTINGS.task_timeout {
+                for (_, task) in rinfo.tasks.iter_mut() {
+                    if task.ping >= timeout && ! task.cancelled.swap(true, Ordering::SeqCst) {
+                        task.info = format!("cancelled by watchdog after {} seconds without progress", task.ping);
+                    }
+                }
+            }
+            // cancel the tasks that ran past their deadline
+            let now = helpers::now();
+            for (_, task) in rinfo.tasks.iter_mut() {
+                if let Some(deadline) = task.deadline {
+                    if now >= deadline && ! task.cancelled.swap(true, Ordering::SeqCst) {
+                        task.info = format!("cancelled by watchdog after deadline");
+                    }
+                }
+            }
             // now that the messages have been processed, redraw the status information
             self.status(& rinfo);
             // retire errored tasks that are too old
@@ -308,20 +1173,30 @@ impl Updater {
 
     fn status(& self, info : & ReporterInfo) {
         let _g = self.cout_lock.lock().unwrap();
+        // the header turns red when any of the volumes runs low
+        let background = if info.disks.iter().any(|(_, _, _, low)| *low) { "\x1b[101m" } else { "\x1b[104m" };
         print!("\x1b7"); // save cursor
         print!("\x1b[H"); // set cursor to top left corner
-        print!("\x1b[104;97m"); // set white on blue background
+        print!("{}\x1b[97m", background); // set white on blue (or red) background
         // the header 
         let queue_size;
+        let metadata_queue_size;
+        let deferred_size;
+        let waiting_size;
+        let throttled_size;
         {
             let threads = self.pool.lock().unwrap();
-            println!("{} DCD v3 (datastore version {}), uptime [ {} ], threads [ {}r, {}i, {}p ], status: [ {} ] \x1b[K",
+            println!("{} DCD v3 (datastore version {}), uptime [ {} ], threads [ {}r, {}i, {}p / {} ], status: [ {} ] \x1b[K",
                 info.get_tick_symbol(), 
                 Datastore::VERSION, 
                 helpers::pretty_duration(helpers::now() - info.start_time), 
-                threads.running_workers, threads.idle_workers, threads.paused_workers, 
+                threads.running_workers, threads.idle_workers, threads.paused_workers, threads.target_workers, 
                 threads.status());
             queue_size = threads.queue.len();
+            metadata_queue_size = threads.metadata_queue.len();
+            deferred_size = threads.deferred.len();
+            waiting_size = threads.waiting.len();
+            throttled_size = threads.throttled.values().map(|x| x.len()).sum::<usize>();
         }
         // datastore header
         let mut loaded = self.ds.project_urls_memory_report();
@@ -337,20 +1212,37 @@ impl Updater {
         );
         // server health
         // TODO get this from the process tables instead
-        // add disk info for temp and for datastore
         let (pid, mem, cpu) = helpers::process_resources();
-        println!("  Health: pid: {}, [cpu: {}%], [mem:{}%] \x1b[K",
+        let mut disks = String::new();
+        for (volume, space, inodes, _) in info.disks.iter() {
+            let inodes = inodes.map(|x| format!(", {} inodes", helpers::pretty_value(x as usize))).unwrap_or_default();
+            disks = format!("{}, [{}: {}{}]", disks, volume, helpers::pretty_size(*space), inodes);
+        }
+        let mut quotas = String::new();
+        for (kind, size, quota) in info.over_quota.iter() {
+            quotas = format!("{} {:?} ({} of {})", quotas, kind, helpers::pretty_size(*size), helpers::pretty_size(*quota));
+        }
+        if ! quotas.is_empty() {
+            quotas = format!(", \x1b[101mquota exceeded:{}, {}{}", quotas, if SETTINGS.quota_metadata_only { "not storing contents" } else { "updates paused" }, background);
+        }
+        println!("  Health: pid: {}, [cpu: {}%], [mem:{}%]{}{} \x1b[K",
             pid,
             cpu,
             mem,
+            disks,
+            quotas,
         );
 
         // tasks summary
         print!("\x1b[6H\x1b[104m");
-        println!(" tick [ {}a, {}d, {}e ] total [ {}d, {}e ] queue [{}]\x1b[K",
+        println!(" tick [ {}a, {}d, {}e ] total [ {}d, {}e ] queue [{}] metadata [{}] deferred [{}] waiting [{}] throttled [{}]\x1b[K",
             info.tasks.len(), info.tick_tasks_done, info.tick_tasks_error,
             helpers::pretty_value(info.total_tasks_done), helpers::pretty_value(info.total_tasks_error),
-            helpers::pretty_value(queue_size)
+            helpers::pretty_value(queue_size),
+            helpers::pretty_value(metadata_queue_size),
+            helpers::pretty_value(deferred_size),
+            helpers::pretty_value(waiting_size),
+            helpers::pretty_value(throttled_size)
         );
         // details for running tasks, ordered by their start time
         {
@@ -359,7 +1251,9 @@ impl Updater {
             let mut odd = true;
             for (name, task) in tasks {
                 let mut color = task.color.as_str();
-                if task.ping >= 10 {
+                if task.cancelled.load(Ordering::SeqCst) {
+                    color = "\x1b[48;2;192;0;0m";
+                } else if task.ping >= 10 {
                     color = "\x1b[48;2;255;165;0m";
                 }
                 if color.is_empty() {
@@ -420,7 +1314,48 @@ impl Updater {
                 }
             }
         }
-        self.display_prompt("Controller thread terminated. Command interface not available");
+        self.display_prompt("Controller thread terminated. Command interface not available");
+    }
+
+    /** Creates the dry run report with its header, overwriting any previous report. 
+     
+        Returns false and displays the error if the report cannot be created. 
+     */
+    fn start_dry_run_report(& self) -> bool {
+        let header = "project_id,url,changed,heads_changed,heads_new,heads_deleted,tags_changed,new_commits,size\n";
+        match std::fs::write(format!("{}/{}", self.ds.root_folder(), Updater::DRY_RUN_REPORT), header) {
+            Ok(()) => return true,
+            Err(e) => {
+                self.display_error(format!("Cannot create dry run report: {}", e));
+                return false;
+            }
+        }
+    }
+
+    /** Schedules a substore update pass starting at given substore. 
+     
+        If an unfinished pass of the same kind exists in the datastore, it is resumed from the substore it stopped at, unless restart is true in which case its progress is discarded. Returns true if the pass is resumed. 
+     */
+    fn schedule_update_pass(& self, store : StoreKind, mode : UpdateMode, dry_run : bool, filter : UpdateFilter, restart : bool) -> bool {
+        let mut store = store;
+        let mut resumed = false;
+        if let Some(name) = update_pass_name(store, mode, dry_run, & filter) {
+            if restart {
+                if let Err(e) = self.ds.remove_update_pass(& name) {
+                    self.display_error(format!("Cannot discard update pass {}: {}", name, e));
+                }
+            } else if let Some(pass) = self.ds.get_update_pass(& name) {
+                // the pass may have been resumed already when the updater started
+                let task = Task::UpdateSubstore{store : pass.substore, mode, dry_run, filter : pass.filter};
+                if self.pool.lock().unwrap().scheduled.contains(& task.name()) {
+                    return true;
+                }
+                store = pass.substore;
+                resumed = true;
+            }
+        }
+        self.schedule(Task::UpdateSubstore{store, mode, dry_run, filter});
+        return resumed;
     }
 
     fn display_prompt<T: Into<String>>(& self, command_output : T) {
@@ -431,6 +1366,22 @@ impl Updater {
         stdout().flush().unwrap();
     }
 
+    /** Displays the output of an interactive command. 
+     
+        The prompt line is the only place the output can go without being overwritten by the task reports, so output of multiple lines is written to the command output file and only its first line is displayed together with the path of the file. 
+     */
+    fn display_output(& self, output : String) {
+        if ! output.contains('\n') {
+            return self.display_prompt(output);
+        }
+        let path = format!("{}/{}", self.ds.root_folder(), Updater::COMMAND_OUTPUT);
+        let first = output.lines().next().unwrap_or("").to_owned();
+        match std::fs::write(& path, output + "\n") {
+            Ok(()) => self.display_prompt(format!("{}, see {}", first, path)),
+            Err(e) => self.display_error(format!("{}, unable to write {}: {}", first, path, e)),
+        }
+    }
+
     fn display_error<T: Into<String>>(& self, error : T) {
         self.display_prompt(& format!("ERROR: {}", error.into()));
     }
@@ -451,6 +1402,7 @@ impl Updater {
                     let mut threads = self.pool.lock().unwrap();
                     threads.state = State::Stopped;
                     self.cv_workers.notify_all();
+                    self.cv_supervisor.notify_all();
                 }
                 self.display_prompt("Stopping threads...");
             },
@@ -462,26 +1414,70 @@ impl Updater {
                 }
                 self.display_prompt("Resuming worker threads...");
             }, 
+            /* Changes the number of worker threads. 
+             */
+            "threads" => {
+                match cmd.get(1).and_then(|x| x.parse::<u64>().ok()) {
+                    Some(n) if cmd.len() == 2 && n > 0 => {
+                        let old;
+                        {
+                            let mut threads = self.pool.lock().unwrap();
+                            old = threads.target_workers;
+                            threads.target_workers = n;
+                            // idle and paused workers over the target must wake up to exit
+                            self.cv_workers.notify_all();
+                            self.cv_supervisor.notify_all();
+                        }
+                        self.display_prompt(format!("Resizing worker pool from {} to {} threads...", old, n));
+                    },
+                    _ => {
+                        self.display_error("Usage: threads N");
+                    }
+                }
+            },
             /* Updates project belonging to the given substore . 
              */
             "update" => {
-                if cmd.len() != 2 {
-                    self.display_error("No store to update specified");
+                if cmd.len() < 2 || cmd[2..].iter().any(|x| *x != "--dry-run" && *x != "--restart") {
+                    self.display_error("Usage: update STORE [--dry-run] [--restart]");
                 } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
-                    self.schedule(Task::UpdateSubstore{store : kind, mode : UpdateMode::Single});
-                    self.display_prompt(format!("Updating substore {:?}, see task progress...", kind));
+                    let dry_run = cmd.contains(& "--dry-run");
+                    if dry_run && ! self.start_dry_run_report() {
+                        return;
+                    }
+                    let resumed = self.schedule_update_pass(kind, UpdateMode::Single, dry_run, UpdateFilter::default(), cmd.contains(& "--restart"));
+                    self.display_prompt(format!("{} substore {:?}{}, see task progress...", if resumed { "Resuming update of" } else { "Updating" }, kind, if dry_run { " (dry run)" } else { "" }));
                 } else {
                     self.display_error(format!("Unknown store kind {}", cmd[1]));
                 }
             },
-            /* Updates all projects once substore by substore. 
+            /* Updates all projects once substore by substore, or only the projects passing given filters. 
              */
             "updateall" => {
-                if cmd.len() != 1 {
-                    self.display_error("Invalid arguments");
-                } else {
-                    self.schedule(Task::UpdateSubstore{store : StoreKind::from_number(0), mode : UpdateMode::All});
-                    self.display_prompt("Updating all substores , see task progress...");
+                let dry_run = cmd.contains(& "--dry-run");
+                let restart = cmd.contains(& "--restart");
+                let args : Vec<& str> = cmd[1..].iter().filter(|x| **x != "--dry-run" && **x != "--restart").map(|x| *x).collect();
+                match UpdateFilter::parse(& args) {
+                    Ok(filter) => {
+                        if dry_run && ! self.start_dry_run_report() {
+                            return;
+                        }
+                        let what = match filter.substore {
+                            Some(store) => format!("substore {:?}", store),
+                            None => "all substores".to_owned(),
+                        };
+                        let filtered = if filter.is_empty() { String::new() } else { format!(" with filter{}", filter) };
+                        // a single substore is updated alone, the other substores are not visited
+                        let (store, mode) = match filter.substore {
+                            Some(store) => (store, UpdateMode::Single),
+                            None => (StoreKind::from_number(0), UpdateMode::All),
+                        };
+                        let resumed = self.schedule_update_pass(store, mode, dry_run, filter, restart);
+                        self.display_prompt(format!("{} {}{}{}, see task progress...", if resumed { "Resuming update of" } else { "Updating" }, what, filtered, if dry_run { " (dry run)" } else { "" }));
+                    },
+                    Err(e) => {
+                        self.display_error(format!("{}. Usage: updateall [--dry-run] [--restart] [--substore STORE] [--older-than DURATION] [--label LABEL] [--max N]", e));
+                    }
                 }
             },
             /* Retries projects that were marked as errors. 
@@ -490,21 +1486,192 @@ impl Updater {
                 if cmd.len() != 1 {
                     self.display_error("Invalid arguments");
                 } else {
-                    self.schedule(Task::UpdateSubstore{store : StoreKind::from_number(0), mode : UpdateMode::Errors});
+                    self.schedule(Task::UpdateSubstore{store : StoreKind::from_number(0), mode : UpdateMode::Errors, dry_run : false, filter : UpdateFilter::default()});
                     self.display_prompt("Checking all errors , see task progress...");
                 }
 
             },
-            /* Continuously updates all projects store by store
+            /* Starts periodic refreshes of the metadata of all GitHub projects. 
              */
-            "updatecontinuous" => {
+            "updatemetadata" => {
                 if cmd.len() != 1 {
                     self.display_error("Invalid arguments");
+                } else if self.schedule(Task::RefreshMetadata) {
+                    self.display_prompt(format!("Refreshing metadata every {}, see task progress...", helpers::pretty_duration(SETTINGS.metadata_interval as i64)));
+                } else {
+                    self.display_error("Metadata are already being refreshed");
+                }
+            },
+            /* Checks the metadata and remote heads of all projects without cloning them. 
+             */
+            "updateheads" => {
+                if cmd.len() > 2 || (cmd.len() == 2 && cmd[1] != "--restart") {
+                    self.display_error("Usage: updateheads [--restart]");
+                } else if self.schedule_update_pass(StoreKind::from_number(0), UpdateMode::HeadsOnly, false, UpdateFilter::default(), cmd.len() == 2) {
+                    self.display_prompt("Resuming update of heads of all substores, see task progress...");
+                } else {
+                    self.display_prompt("Updating heads of all substores, see task progress...");
+                }
+            },
+            /* Continuously updates all projects store by store
+             */
+            "updatecontinuous" => {
+                if cmd.len() > 2 || (cmd.len() == 2 && cmd[1] != "--restart") {
+                    self.display_error("Usage: updatecontinuous [--restart]");
+                } else if self.schedule_update_pass(StoreKind::from_number(0), UpdateMode::Continuous, false, UpdateFilter::default(), cmd.len() == 2) {
+                    self.display_prompt("Resuming update of all substores , see task progress...");
                 } else {
-                    self.schedule(Task::UpdateSubstore{store : StoreKind::from_number(0), mode : UpdateMode::Continuous});
                     self.display_prompt("Updating all substores , see task progress...");
                 }
             },
+            /* Downloads issues and pull requests of the given project. 
+             */
+            "issues" => {
+                if cmd.len() != 2 {
+                    self.display_error("No project id specified");
+                } else if let Ok(id) = cmd[1].parse::<u64>() {
+                    if (id as usize) < self.num_projects() {
+                        self.schedule(Task::UpdateIssues{id : ProjectId::from(id)});
+                        self.display_prompt(format!("Updating issues of project {}, see task progress...", id));
+                    } else {
+                        self.display_error(format!("Project {} does not exist", id));
+                    }
+                } else {
+                    self.display_error(format!("Invalid project id {}", cmd[1]));
+                }
+            },
+            /* Schedules update of given project, with --now the project is moved to the front of the queue. 
+             */
+            "update-project" => {
+                if cmd.len() < 2 || cmd.len() > 3 || (cmd.len() == 3 && cmd[2] != "--now") {
+                    self.display_error("Usage: update-project PROJECT_ID [--now]");
+                } else if let Ok(id) = cmd[1].parse::<u64>() {
+                    if (id as usize) < self.num_projects() {
+                        let pid = ProjectId::from(id);
+                        let class = if cmd.len() == 3 { PriorityClass::Now } else { PriorityClass::Normal };
+                        let last_update_time = self.ds.get_project_last_update(pid).map(|x| x.time()).unwrap_or(0);
+                        if self.schedule(Task::UpdateRepo{id : pid, last_update_time, class}) {
+                            self.display_prompt(format!("Updating project {}, see task progress...", id));
+                        } else {
+                            self.display_error(format!("Project {} is already scheduled", id));
+                        }
+                    } else {
+                        self.display_error(format!("Project {} does not exist", id));
+                    }
+                } else {
+                    self.display_error(format!("Invalid project id {}", cmd[1]));
+                }
+            },
+            /* Prioritizes or deprioritizes updates of projects with given label, or lists the prioritized labels. 
+             */
+            "prioritize" | "deprioritize" => {
+                if cmd.len() == 1 && cmd[0] == "prioritize" {
+                    let pool = self.pool.lock().unwrap();
+                    let mut labels : Vec<& String> = pool.prioritized_labels.iter().collect();
+                    labels.sort();
+                    self.display_prompt(format!("Prioritized labels: {}", labels.iter().map(|x| x.as_str()).collect::<Vec<& str>>().join(", ")));
+                } else if cmd.len() != 3 || cmd[1] != "--label" {
+                    self.display_error(format!("Usage: {} --label LABEL", cmd[0]));
+                } else if cmd[0] == "prioritize" {
+                    let n = self.prioritize_label(cmd[2]);
+                    self.display_prompt(format!("Prioritized {} projects with label {}", n, cmd[2]));
+                } else if self.deprioritize_label(cmd[2]) {
+                    self.display_prompt(format!("Label {} no longer prioritized", cmd[2]));
+                } else {
+                    self.display_error(format!("Label {} is not prioritized", cmd[2]));
+                }
+            },
+            /* Adds, removes, or lists the labels of given project. 
+             */
+            "label" => {
+                if cmd.len() < 3 || (cmd[1] != "list" && cmd.len() != 4) {
+                    self.display_error("Usage: label add|remove PROJECT_ID LABEL, or label list PROJECT_ID");
+                } else if let Ok(id) = cmd[2].parse::<u64>() {
+                    let pid = ProjectId::from(id);
+                    if (id as usize) >= self.num_projects() {
+                        self.display_error(format!("Project {} does not exist", id));
+                    } else {
+                        match cmd[1] {
+                            "add" => {
+                                if self.ds.add_project_label(pid, cmd[3]) {
+                                    self.display_prompt(format!("Label {} added to project {}", cmd[3], id));
+                                } else {
+                                    self.display_prompt(format!("Project {} already has label {}", id, cmd[3]));
+                                }
+                            },
+                            "remove" => {
+                                if self.ds.remove_project_label(pid, cmd[3]) {
+                                    self.display_prompt(format!("Label {} removed from project {}", cmd[3], id));
+                                } else {
+                                    self.display_prompt(format!("Project {} does not have label {}", id, cmd[3]));
+                                }
+                            },
+                            "list" => {
+                                self.display_prompt(format!("Project {} labels: {}", id, self.ds.get_project_labels(pid).join(", ")));
+                            },
+                            _ => {
+                                self.display_error(format!("Unknown label command {}", cmd[1]));
+                            }
+                        }
+                    }
+                } else {
+                    self.display_error(format!("Invalid project id {}", cmd[2]));
+                }
+            },
+            /* Reports the number of projects, records, contents and memory of given substore. 
+             */
+            "stats" => {
+                match self.ds.stats_command(& cmd[1..]) {
+                    Ok(msg) => self.display_prompt(msg),
+                    Err(e) => self.display_error(format!("{}", e)),
+                }
+            },
+            /* Lists savepoints, or shows the table sizes of given savepoint. 
+             */
+            "savepoints" => {
+                match self.ds.savepoints_command(& cmd[1..]) {
+                    Ok(msg) => self.display_output(msg),
+                    Err(e) => self.display_error(format!("{}", e)),
+                }
+            },
+            /* Adds, removes, or lists the entries of the denylist. 
+             */
+            "blacklist" | "denylist" => {
+                match self.ds.denylist_command(& cmd[1..]) {
+                    Ok(msg) => self.display_output(msg),
+                    Err(e) => self.display_error(format!("{}", e)),
+                }
+            },
+            /* Starts, removes, lists, or reports the status of update campaigns, and schedules the updates of the projects a campaign has not yet completed. 
+             */
+            "campaign" => {
+                let msg = if cmd.get(1) == Some(& "resume") {
+                    if cmd.len() != 3 {
+                        self.display_error("Usage: campaign resume NAME");
+                        return;
+                    }
+                    format!("Resuming campaign {}", cmd[2])
+                } else {
+                    match self.ds.campaign_command(& cmd[1..]) {
+                        Ok(msg) => msg,
+                        Err(e) => {
+                            self.display_error(format!("{}", e));
+                            return;
+                        }
+                    }
+                };
+                if cmd[1] == "start" || cmd[1] == "resume" {
+                    if self.ds.get_campaign(cmd[2]).is_none() {
+                        self.display_error(format!("Campaign {} not found", cmd[2]));
+                    } else if self.schedule(Task::UpdateCampaign{name : cmd[2].to_owned()}) {
+                        self.display_prompt(format!("{}, see task progress...", msg));
+                    } else {
+                        self.display_error(format!("Campaign {} is already being updated", cmd[2]));
+                    }
+                } else {
+                    self.display_prompt(msg);
+                }
+            },
             /* Adds given project url, or projects from given csv file. 
              */
             "add" => {
@@ -515,6 +1682,17 @@ impl Updater {
                     self.schedule(Task::AddProjects{ source : cmd[1].to_owned() });
                 }
             },
+            /* Imports projects from GHTorrent's projects.csv, or from SEART json export. 
+             */
+            "add-ghtorrent" | "add-seart" => {
+                if cmd.len() != 2 {
+                    self.display_error("Specify the project dump file to import the projects from");
+                } else {
+                    let dump = if cmd[0] == "add-ghtorrent" { ProjectsDump::GHTorrent } else { ProjectsDump::Seart };
+                    self.display_prompt("Importing projects to datastore, see task progress...");
+                    self.schedule(Task::ImportProjects{ source : cmd[1].to_owned(), dump });
+                }
+            },
             /* Loads given substore in memory. 
              */
             "load" => {
@@ -547,7 +1725,7 @@ impl Updater {
                 if cmd.len() != 2 {
                     self.display_error("No store to verify specified");
                 } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
-                    self.schedule(Task::VerifySubstore{store : kind, mode : UpdateMode::Single});
+                    self.schedule(Task::VerifySubstore{store : kind, mode : UpdateMode::Single, repair : false});
                     self.display_prompt(format!("Verifying substore {:?}, see task progress...", kind));
                 } else {
                     self.display_error(format!("Unknown store kind {}", cmd[1]));
@@ -557,7 +1735,7 @@ impl Updater {
                 if cmd.len() != 1 {
                     self.display_error("Invalid arguments");
                 } else {
-                    self.schedule(Task::VerifySubstore{store : StoreKind::from_number(0), mode : UpdateMode::All});
+                    self.schedule(Task::VerifySubstore{store : StoreKind::from_number(0), mode : UpdateMode::All, repair : false});
                     self.display_prompt("Verifying all substores, see task progress...");
                 }
             },
@@ -565,10 +1743,40 @@ impl Updater {
                 if cmd.len() != 1 {
                     self.display_error("Invalid arguments");
                 } else {
-                    self.schedule(Task::VerifyDatastore{});
+                    self.schedule(Task::VerifyDatastore{repair : false});
                     self.display_prompt("Verifying main datastore, see task progress...");
                 }
             },
+            /* Repairs recoverable corruption of the given substore and verifies it. 
+             
+               The substore should not be updated while it is being repaired. 
+             */
+            "repair" => {
+                if cmd.len() != 2 {
+                    self.display_error("No store to repair specified");
+                } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
+                    self.schedule(Task::VerifySubstore{store : kind, mode : UpdateMode::Single, repair : true});
+                    self.display_prompt(format!("Repairing substore {:?}, see task progress...", kind));
+                } else {
+                    self.display_error(format!("Unknown store kind {}", cmd[1]));
+                }
+            },
+            "repairall" => {
+                if cmd.len() != 1 {
+                    self.display_error("Invalid arguments");
+                } else {
+                    self.schedule(Task::VerifySubstore{store : StoreKind::from_number(0), mode : UpdateMode::All, repair : true});
+                    self.display_prompt("Repairing all substores and main datastore, see task progress...");
+                }
+            },
+            "repairds" => {
+                if cmd.len() != 1 {
+                    self.display_error("Invalid arguments");
+                } else {
+                    self.schedule(Task::VerifyDatastore{repair : true});
+                    self.display_prompt("Repairing main datastore, see task progress...");
+                }
+            },
             "create-savepoint" => {
                 if cmd.len() != 2 {
                     self.display_error("Invalid arguments");
@@ -577,6 +1785,76 @@ impl Updater {
                     self.display_prompt("Creating savepoint, see task progress...");
                 }
             },
+            "pack-substore" => {
+                if cmd.len() < 3 || cmd.len() > 4 {
+                    self.display_error("Invalid arguments");
+                } else if let Some(store) = StoreKind::from_string(cmd[1]) {
+                    self.schedule(Task::PackSubstore{store, output : cmd[2].to_owned(), savepoint : cmd.get(3).map(|x| x.to_string())});
+                    self.display_prompt("Packing substore, see task progress...");
+                } else {
+                    self.display_error(format!("Unknown substore {}", cmd[1]));
+                }
+            },
+            /* Builds or updates the commit messages index of given substore. 
+             */
+            "index-messages" => {
+                if cmd.len() != 2 {
+                    self.display_error("No store to index specified");
+                } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
+                    self.schedule(Task::IndexMessages{store : kind});
+                    self.display_prompt(format!("Indexing commit messages of substore {:?}, see task progress...", kind));
+                } else {
+                    self.display_error(format!("Unknown store kind {}", cmd[1]));
+                }
+            },
+            /* Determines users of given substore that are the same person. 
+             */
+            "resolve-users" => {
+                if cmd.len() != 2 {
+                    self.display_error("No store to resolve users of specified");
+                } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
+                    self.schedule(Task::ResolveUsers{store : kind});
+                    self.display_prompt(format!("Resolving users of substore {:?}, see task progress...", kind));
+                } else {
+                    self.display_error(format!("Unknown store kind {}", cmd[1]));
+                }
+            },
+            /* Builds or updates the index of projects containing the commits of given substore. 
+             */
+            "index-commit-projects" => {
+                if cmd.len() != 2 {
+                    self.display_error("No store to index specified");
+                } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
+                    self.schedule(Task::IndexCommitProjects{store : kind});
+                    self.display_prompt(format!("Indexing commit projects of substore {:?}, see task progress...", kind));
+                } else {
+                    self.display_error(format!("Unknown store kind {}", cmd[1]));
+                }
+            },
+            /* Builds or updates the index of commits and paths at which the file hashes of given substore occur. 
+             */
+            "index-files" => {
+                if cmd.len() != 2 {
+                    self.display_error("No store to index specified");
+                } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
+                    self.schedule(Task::IndexFiles{store : kind});
+                    self.display_prompt(format!("Indexing files of substore {:?}, see task progress...", kind));
+                } else {
+                    self.display_error(format!("Unknown store kind {}", cmd[1]));
+                }
+            },
+            /* Calculates the structural metrics of the commits of given substore. 
+             */
+            "commit-metrics" => {
+                if cmd.len() != 2 {
+                    self.display_error("No store specified");
+                } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
+                    self.schedule(Task::CommitMetrics{store : kind});
+                    self.display_prompt(format!("Calculating commit metrics of substore {:?}, see task progress...", kind));
+                } else {
+                    self.display_error(format!("Unknown store kind {}", cmd[1]));
+                }
+            },
             // debug stuffz
 
             /* Kill immediately aborts the entire process. 
@@ -626,6 +1904,90 @@ impl Updater {
 impl std::panic::RefUnwindSafe for Updater { }
 
 
+/** Restricts the projects scheduled by substore updates. 
+ 
+    Projects must pass all of the given conditions. The default filter schedules all projects that are due. 
+ */
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct UpdateFilter {
+    /** Only projects of given substore, unspecified projects are not scheduled. 
+     */
+    pub substore : Option<StoreKind>,
+    /** Only projects not updated for at least given number of seconds. Projects that were never updated always pass. 
+     */
+    pub older_than : Option<i64>,
+    /** Only projects with given label. 
+     */
+    pub label : Option<String>,
+    /** Maximum number of projects scheduled, in total for all substores. 
+     
+        The projects are selected substore by substore in the order the pass visits them: the least recently updated projects of each substore are scheduled first and whatever remains of the maximum is passed on to the next substore. The selection is thus not the least recently updated projects of the whole datastore, substores visited earlier take precedence. 
+     */
+    pub max : Option<usize>,
+}
+
+impl UpdateFilter {
+    /** Parses the filter from the arguments of the update commands, i.e. `--substore STORE`, `--older-than DURATION`, `--label LABEL` and `--max N`. 
+     */
+    pub fn parse(args : & [& str]) -> Result<UpdateFilter, String> {
+        let mut result = UpdateFilter::default();
+        let mut i = 0;
+        while i < args.len() {
+            let value = args.get(i + 1).ok_or_else(|| format!("Missing value of {}", args[i]))?;
+            match args[i] {
+                "--substore" => result.substore = Some(StoreKind::from_string(value).ok_or_else(|| format!("Unknown store kind {}", value))?),
+                "--older-than" => result.older_than = Some(helpers::parse_duration(value).ok_or_else(|| format!("Invalid duration {}, must be a number followed by s, m, h, d, or w", value))?),
+                "--label" => result.label = Some(value.to_string()),
+                "--max" => result.max = Some(value.parse::<usize>().map_err(|_| format!("Invalid maximum number of projects {}", value))?),
+                x => return Err(format!("Unknown filter {}", x)),
+            }
+            i += 2;
+        }
+        return Ok(result);
+    }
+
+    pub fn is_empty(& self) -> bool {
+        return * self == UpdateFilter::default();
+    }
+
+    /** Returns the arguments that `parse` turns into the same filter. 
+     */
+    pub fn args(& self) -> Vec<String> {
+        let mut result = Vec::new();
+        if let Some(store) = self.substore {
+            result.extend(["--substore".to_owned(), format!("{:?}", store)]);
+        }
+        if let Some(older_than) = self.older_than {
+            result.extend(["--older-than".to_owned(), format!("{}s", older_than)]);
+        }
+        if let Some(label) = & self.label {
+            result.extend(["--label".to_owned(), label.clone()]);
+        }
+        if let Some(max) = self.max {
+            result.extend(["--max".to_owned(), max.to_string()]);
+        }
+        return result;
+    }
+}
+
+impl std::fmt::Display for UpdateFilter {
+    fn fmt(& self, f : & mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(store) = self.substore {
+            write!(f, " substore {:?}", store)?;
+        }
+        if let Some(older_than) = self.older_than {
+            write!(f, " older than {}", helpers::pretty_duration(older_than))?;
+        }
+        if let Some(label) = & self.label {
+            write!(f, " label {}", label)?;
+        }
+        if let Some(max) = self.max {
+            write!(f, " max {}", max)?;
+        }
+        return Ok(());
+    }
+}
+
 /** Determines the mode of the update. 
  */
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
@@ -634,59 +1996,229 @@ pub enum UpdateMode {
     All,
     Continuous,
     Errors,
+    /** Goes through all substores once, but only checks the metadata and remote heads of the projects, without cloning them. 
+     */
+    HeadsOnly,
+}
+
+impl UpdateMode {
+    /** Returns the mode of given name, as formatted by `Debug`. 
+     */
+    pub fn from_string(name : & str) -> Option<UpdateMode> {
+        match name {
+            "Single" => return Some(UpdateMode::Single),
+            "All" => return Some(UpdateMode::All),
+            "Continuous" => return Some(UpdateMode::Continuous),
+            "Errors" => return Some(UpdateMode::Errors),
+            "HeadsOnly" => return Some(UpdateMode::HeadsOnly),
+            _ => return None,
+        }
+    }
+}
+
+/** Kinds of project dumps that can be imported. 
+ */
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ProjectsDump {
+    /** GHTorrent's `projects.csv`. 
+     */
+    GHTorrent,
+    /** JSON export of the SEART GitHub search. 
+     */
+    Seart,
+}
+
+/** Priority classes of the scheduled tasks. 
+ 
+    Tasks of higher classes are always executed before tasks of lower classes. Within the same class project updates are ordered by the time of their last update. 
+ */
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+pub enum PriorityClass {
+    /** Regular updates scheduled by substore updates. 
+     */
+    Normal,
+    /** Updates of projects with prioritized labels. 
+     */
+    Label,
+    /** Updates requested manually and all other tasks. 
+     */
+    Now,
 }
 
 #[derive(Eq, PartialEq, Debug)] 
 pub enum Task {
-    UpdateRepo{id : ProjectId, last_update_time : i64},
+    /** Updates the given project. 
+     
+        Updates of other than normal priority class load the project's substore if it is not loaded. 
+     */
+    UpdateRepo{id : ProjectId, last_update_time : i64, class : PriorityClass},
+    /** Downloads issues and pull requests of the given project. 
+     */
+    UpdateIssues{id : ProjectId},
     AddProjects{source : String},
+    /** Imports projects and their initial metadata from a GHTorrent or SEART project dump. 
+     */
+    ImportProjects{source : String, dump : ProjectsDump},
     /** Updates projects that belong to the specific substore. 
      
-        Also looks at all unspecified projects and assigns their store, updating those that belong to the provided store. 
+        Also looks at all unspecified projects and assigns their store, updating those that belong to the provided store. Only projects passing the filter are scheduled. 
+     */
+    UpdateSubstore{store: StoreKind, mode : UpdateMode, dry_run : bool, filter : UpdateFilter},
+    /** Determines what an update of the given project would do without changing the datastore and appends the result to the dry run report. 
+     */
+    DryRunRepo{id : ProjectId, last_update_time : i64},
+    /** Checks the metadata and remote heads of the given project and records their changes without cloning the project. 
      */
-    UpdateSubstore{store: StoreKind, mode : UpdateMode},
+    UpdateHeads{id : ProjectId, last_update_time : i64},
+    /** Refreshes the GitHub metadata of the given project. Scheduled in the metadata queue by the metadata refresh. 
+     */
+    UpdateMetadata{id : ProjectId, last_check_time : i64},
+    /** Periodically schedules metadata updates of all GitHub projects whose metadata were checked longer than the metadata interval ago. Runs until the updater stops, or the task is cancelled. 
+     */
+    RefreshMetadata,
+    /** Schedules the updates of the pending projects of given campaign as the queue drains. 
+     */
+    UpdateCampaign{name : String},
     /** Loads given substore to memory.
      */
     LoadSubstore{store: StoreKind},
     /** Drops the given substore from memory. 
      */
     DropSubstore{store: StoreKind},
-    VerifySubstore{store : StoreKind, mode : UpdateMode},
-    VerifyDatastore{},
+    /** Verifies the given substore, repairing recoverable corruption first if in repair mode. 
+     */
+    VerifySubstore{store : StoreKind, mode : UpdateMode, repair : bool},
+    /** Verifies a chunk of a substore table, scheduled by the substore verification. 
+     */
+    VerifySubstoreChunk{store : StoreKind, table : usize, chunk : usize},
+    VerifyDatastore{repair : bool},
     CreateSavepoint{name : String},
+    /** Packs the substore into a tar archive that can be unpacked into another datastore. 
+     */
+    PackSubstore{store : StoreKind, output : String, savepoint : Option<String>},
+    UnpackSubstore{input : String},
+    /** Migrates the datastore to the current version, in place or into a new root. 
+     */
+    Migrate{into : Option<String>},
+    /** Removes the contents of the substore that are not reachable from any of its projects. 
+     */
+    CollectContents{store : StoreKind, keep_backup : bool},
+    /** Adds commits not yet indexed to the commit messages index of the substore. 
+     */
+    IndexMessages{store : StoreKind},
+    /** Determines users of the substore that are the same person and updates the user aliases. 
+     */
+    ResolveUsers{store : StoreKind},
+    /** Adds the projects of the substore to the commit projects index of the commits not yet indexed for them. 
+     */
+    IndexCommitProjects{store : StoreKind},
+    /** Adds commits not yet indexed to the hash occurrences index of the substore. 
+     */
+    IndexFiles{store : StoreKind},
+    /** Calculates the commit metrics of the projects of the substore whose heads changed since their metrics were calculated. 
+     */
+    CommitMetrics{store : StoreKind},
 }
 
 impl Task {
     pub fn priority(& self) -> i64 {
         match self {
-            Task::UpdateRepo{last_update_time, id : _} => *last_update_time, 
+            Task::UpdateRepo{last_update_time, id : _, class : _} => *last_update_time, 
+            Task::DryRunRepo{last_update_time, id : _} => *last_update_time, 
+            Task::UpdateHeads{last_update_time, id : _} => *last_update_time, 
+            Task::UpdateMetadata{last_check_time, id : _} => *last_check_time, 
             _ => -1,
         }
     }
 
+    /** Returns the priority class of the task. 
+     */
+    pub fn class(& self) -> PriorityClass {
+        match self {
+            Task::UpdateRepo{id : _, last_update_time : _, class} => *class,
+            Task::DryRunRepo{id : _, last_update_time : _} => PriorityClass::Normal,
+            Task::UpdateHeads{id : _, last_update_time : _} => PriorityClass::Normal,
+            Task::UpdateMetadata{id : _, last_check_time : _} => PriorityClass::Normal,
+            _ => PriorityClass::Now,
+        }
+    }
+
+    /** Returns true for metadata updates and metadata refreshes, which are accounted for separately from the other tasks. 
+     */
+    pub fn is_metadata_task(& self) -> bool {
+        match self {
+            Task::UpdateMetadata{id : _, last_check_time : _} | Task::RefreshMetadata => true,
+            _ => false,
+        }
+    }
+
+    /** Returns true for the tasks that must not be running while the workers are held (see `Updater::hold_workers`). 
+     
+        These are the tasks working on a single project and the incremental index tasks, which write each of their batches to several tables. 
+     */
+    pub fn is_guarded(& self) -> bool {
+        match self {
+            Task::IndexMessages{store : _} | Task::IndexCommitProjects{store : _} | Task::IndexFiles{store : _} => true,
+            _ => self.project().is_some(),
+        }
+    }
+
+    /** Returns the project the task works on, if any. 
+     */
+    pub fn project(& self) -> Option<ProjectId> {
+        match self {
+            Task::UpdateRepo{id, last_update_time : _, class : _} => Some(*id),
+            Task::DryRunRepo{id, last_update_time : _} => Some(*id),
+            Task::UpdateHeads{id, last_update_time : _} => Some(*id),
+            Task::UpdateMetadata{id, last_check_time : _} => Some(*id),
+            Task::UpdateIssues{id} => Some(*id),
+            _ => None,
+        }
+    }
+
     pub fn name(& self) -> String {
         match self {
-            Task::UpdateRepo{id, last_update_time : _} => format!("{:?}", id),
+            Task::UpdateRepo{id, last_update_time : _, class : _} => format!("{:?}", id),
+            Task::UpdateIssues{id} => format!("issues {:?}", id),
             Task::AddProjects{source : _ } => "add".to_owned(), 
-            Task::UpdateSubstore{store, mode} => format!("update {:?} {:?}", store, mode),
+            Task::ImportProjects{source : _, dump} => format!("import {:?}", dump),
+            Task::UpdateSubstore{store, mode, dry_run : false, filter} => format!("update {:?} {:?}{}", store, mode, filter),
+            Task::UpdateSubstore{store, mode, dry_run : true, filter} => format!("dry run {:?} {:?}{}", store, mode, filter),
+            Task::DryRunRepo{id, last_update_time : _} => format!("dry run {:?}", id),
+            Task::UpdateHeads{id, last_update_time : _} => format!("heads {:?}", id),
+            Task::UpdateMetadata{id, last_check_time : _} => format!("metadata {:?}", id),
+            Task::RefreshMetadata => "refresh metadata".to_owned(),
+            Task::UpdateCampaign{name} => format!("campaign {}", name),
             Task::LoadSubstore{store} => format!("load {:?}", store),
             Task::DropSubstore{store} => format!("drop {:?}", store),
-            Task::VerifySubstore{store, mode} => format!("verify {:?} {:?}", store, mode),
-            Task::VerifyDatastore{} => format!("verify datastore"),
+            Task::VerifySubstore{store, mode, repair : false} => format!("verify {:?} {:?}", store, mode),
+            Task::VerifySubstore{store, mode, repair : true} => format!("repair {:?} {:?}", store, mode),
+            Task::VerifySubstoreChunk{store, table, chunk} => format!("verify {:?} {} #{}", store, Substore::VERIFIED_TABLES[*table].name, chunk),
+            Task::VerifyDatastore{repair : false} => format!("verify datastore"),
+            Task::VerifyDatastore{repair : true} => format!("repair datastore"),
             Task::CreateSavepoint{name} => format!("create savepoint {}", name),
+            Task::PackSubstore{store, output : _, savepoint : _} => format!("pack {:?}", store),
+            Task::UnpackSubstore{input : _} => "unpack".to_owned(),
+            Task::Migrate{into : _} => "migrate".to_owned(),
+            Task::CollectContents{store, keep_backup : _} => format!("gc {:?}", store),
+            Task::IndexMessages{store} => format!("index messages {:?}", store),
+            Task::ResolveUsers{store} => format!("resolve users {:?}", store),
+            Task::IndexCommitProjects{store} => format!("index commit projects {:?}", store),
+            Task::IndexFiles{store} => format!("index files {:?}", store),
+            Task::CommitMetrics{store} => format!("commit metrics {:?}", store),
         }
     }
 }
 
 impl Ord for Task {
     fn cmp(& self, other : & Self) -> std::cmp::Ordering {
-        return self.priority().cmp(& other.priority()).reverse();
+        return self.class().cmp(& other.class()).then_with(|| self.priority().cmp(& other.priority()).reverse());
     }
 }
 
 impl PartialOrd for Task {
     fn partial_cmp(& self, other : & Self) -> Option<std::cmp::Ordering> {
-        return Some(self.priority().cmp(& other.priority()));
+        return Some(self.cmp(other));
     }
 }
 
@@ -703,7 +2235,43 @@ pub (crate) struct Pool {
     pub (crate) running_workers : u64, 
     pub (crate) idle_workers : u64,
     pub (crate) paused_workers : u64,
+    /** The number of workers the pool should have. 
+     */
+    pub (crate) target_workers : u64,
     pub (crate) queue : BinaryHeap<Task>,
+    /** Metadata updates, scheduled separately so that they do not wait behind the regular updates. 
+     */
+    pub (crate) metadata_queue : BinaryHeap<Task>,
+    /** Number of running metadata updates and metadata refreshes. 
+     */
+    pub (crate) metadata_running : u64,
+    /** Number of running guarded tasks, see `Task::is_guarded`. 
+     */
+    pub (crate) guarded_running : u64,
+    /** Number of tasks holding the workers, see `Updater::hold_workers`. While non-zero, workers do not start new tasks. 
+     */
+    pub (crate) holds : u64,
+    /** Tasks of substores that exceed their quota, waiting for the quota to be freed. 
+     */
+    pub (crate) deferred : Vec<Task>,
+    /** Updates of projects whose substore is not loaded, waiting for the preloading policy to load it. 
+     */
+    pub (crate) waiting : Vec<Task>,
+    /** Number of running tasks that use each substore. 
+     */
+    pub (crate) busy : HashMap<StoreKind, usize>,
+    /** Number of running clones from each host with limited concurrent clones. 
+     */
+    pub (crate) hosts : HashMap<String, usize>,
+    /** Tasks of hosts at their limit of concurrent clones, waiting for the running clones of the host to finish. 
+     */
+    pub (crate) throttled : HashMap<String, VecDeque<Task>>,
+    /** Labels whose projects are updated with priority. 
+     */
+    pub (crate) prioritized_labels : HashSet<String>,
+    /** Names of the tasks that are queued, or running, so that no task is scheduled twice, see `Updater::schedule`. 
+     */
+    pub (crate) scheduled : HashSet<String>,
 }
 
 #[derive(Eq, PartialEq)]
@@ -714,16 +2282,34 @@ pub (crate) enum State {
 }
 
 impl Pool {
-    fn new() -> Pool {
+    fn new(target_workers : u64) -> Pool {
         return Pool {
             // start running, threads will have nothing to do and go to idle immediately, but that is expected
             state : State::Running,
             running_workers : 0,
             idle_workers : 0,
             paused_workers : 0,
-            queue : BinaryHeap::new()
+            target_workers,
+            queue : BinaryHeap::new(),
+            metadata_queue : BinaryHeap::new(),
+            metadata_running : 0,
+            guarded_running : 0,
+            holds : 0,
+            deferred : Vec::new(),
+            waiting : Vec::new(),
+            busy : HashMap::new(),
+            hosts : HashMap::new(),
+            throttled : HashMap::new(),
+            prioritized_labels : HashSet::new(),
+            scheduled : HashSet::new(),
         };
     }
+    /** Returns the number of workers in the pool, whatever their state. 
+     */
+    fn workers(& self) -> u64 {
+        return self.running_workers + self.idle_workers + self.paused_workers;
+    }
+
     fn is_paused(& self) -> bool {
         return self.running_workers == 0 && self.idle_workers == 0;
     }
@@ -759,18 +2345,24 @@ impl Pool {
 /** Messages that communicate to the updater changes about tasks. 
  */
 pub enum TaskMessage {
-    Start{name : String},
+    /** Sent when the task starts, together with the flag the watchdog sets to cancel the task. 
+     */
+    Start{name : String, project : Option<ProjectId>, cancelled : Arc<AtomicBool>},
     Done{name : String},
     Error{name : String, cause : String},
     Progress{name : String, progress : usize, max : usize },
     Info{name : String, info : String },
     Extra{name : String, extra : String },
     Color{name : String, color : String },
+    /** Sets, or clears, the time after which the watchdog cancels the task. 
+     */
+    Deadline{name : String, deadline : Option<i64> },
 }
 
 /** Task info as stored on the updater's end. 
  */
 struct TaskInfo {
+    project : Option<ProjectId>,
     start_time : i64,
     end_time : i64,
     progress : usize, 
@@ -781,11 +2373,16 @@ struct TaskInfo {
     extra : String,
     // color to be printed before the task, if any
     color : String,
+    // cancellation flag shared with the task
+    cancelled : Arc<AtomicBool>,
+    // time after which the watchdog cancels the task, if any
+    deadline : Option<i64>,
 }
 
 impl TaskInfo {
-    fn new() -> TaskInfo {
+    fn new(project : Option<ProjectId>, cancelled : Arc<AtomicBool>) -> TaskInfo {
         return TaskInfo{
+            project,
             start_time : helpers::now(),
             end_time : 0,
             progress : 0, 
@@ -794,6 +2391,8 @@ impl TaskInfo {
             info : String::new(),
             extra : String::new(),
             color : String::new(),
+            cancelled,
+            deadline : None,
         };
     }
 
@@ -812,6 +2411,44 @@ impl TaskInfo {
             println!("    {}\x1b[K", self.info)
         }
     }
+
+    /** Appends the outcome of the finished task to the task log as a single JSON line. 
+     
+        Failures to write the log are ignored so that they do not bring the updater down. 
+     */
+    fn log_outcome(& self, f : & mut File, name : & str, cause : Option<& str>) {
+        let mut entry = json::JsonValue::new_object();
+        entry["task"] = name.into();
+        entry["project"] = match self.project {
+            Some(id) => u64::from(id).into(),
+            None => json::JsonValue::Null,
+        };
+        entry["start"] = self.start_time.into();
+        entry["end"] = self.end_time.into();
+        entry["duration"] = (self.end_time - self.start_time).into();
+        entry["result"] = if self.cancelled.load(Ordering::SeqCst) { "cancelled" } else if cause.is_some() { "error" } else { "done" }.into();
+        entry["cause"] = match cause {
+            Some(cause) => cause.into(),
+            None => json::JsonValue::Null,
+        };
+        let _ = writeln!(f, "{}", entry.dump());
+    }
+}
+
+/** Sends a POST request with given JSON body to the url. 
+ */
+fn post_json(url : & str, body : & str) -> Result<(), curl::Error> {
+    let mut conn = curl::easy::Easy::new();
+    conn.url(url)?;
+    conn.proxy(SETTINGS.curl_proxy())?;
+    conn.post(true)?;
+    conn.post_fields_copy(body.as_bytes())?;
+    let mut headers = curl::easy::List::new();
+    headers.append("Content-Type: application/json")?;
+    conn.http_headers(headers)?;
+    conn.timeout(std::time::Duration::from_secs(60))?;
+    conn.perform()?;
+    return Ok(());
 }
 
 struct ReporterInfo {
@@ -824,6 +2461,21 @@ struct ReporterInfo {
     tick_tasks_error : usize,
     total_tasks_done : usize,
     total_tasks_error : usize,
+    /** Seconds until the next quota check and the substores over their quota as of the last check. 
+     */
+    quota_check : usize,
+    over_quota : Vec<(StoreKind, u64, u64)>,
+    /** Seconds until the preloading policy is applied next time. 
+     */
+    preload_check : usize,
+    /** Free space and inodes of the datastore and temporary volumes as of the last check and whether they are below the thresholds. 
+     */
+    disks : Vec<(&'static str, u64, Option<u64>, bool)>,
+    /** Tasks finished in the current error rate notification window and seconds until the window ends. 
+     */
+    window_tasks_done : usize,
+    window_tasks_error : usize,
+    notify_check : usize,
 }
 
 impl ReporterInfo {
@@ -838,6 +2490,13 @@ impl ReporterInfo {
             tick_tasks_error : 0,
             total_tasks_done : 0,
             total_tasks_error : 0,
+            quota_check : 0,
+            over_quota : Vec::new(),
+            preload_check : Updater::PRELOAD_CHECK_INTERVAL,
+            disks : Vec::new(),
+            window_tasks_done : 0,
+            window_tasks_error : 0,
+            notify_check : Updater::NOTIFY_ERROR_WINDOW,
         };
     }
 
@@ -853,6 +2512,8 @@ impl ReporterInfo {
 
     fn tick(& mut self) {
         self.tick_num = ( self.tick_num + 1) % 4;
+        self.window_tasks_done += self.tick_tasks_done;
+        self.window_tasks_error += self.tick_tasks_error;
         self.total_tasks_done += self.tick_tasks_done;
         self.total_tasks_error += self.tick_tasks_error;
         self.tick_tasks_done = 0;